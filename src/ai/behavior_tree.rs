@@ -0,0 +1,338 @@
+use std::{borrow::Cow, sync::Arc, time::Duration};
+
+use trait_set::trait_set;
+
+use crate::utils::mutex::Mutex;
+
+use super::blackboard::Blackboard;
+
+trait_set! {
+    pub trait ActionFn = FnMut(&mut Blackboard) -> Status + Send;
+    pub trait ConditionFn = Fn(&Blackboard) -> bool + Send;
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+pub enum NodeKind {
+    /// ticks children in order until one of them doesn't succeed, resuming
+    /// from the running child
+    Sequence,
+    /// ticks children in order until one of them doesn't fail, restarting
+    /// from the first child every tick
+    Selector,
+    /// ticks every child, succeeds once `required_successes` children
+    /// succeeded, fails once that is no longer possible
+    Parallel {
+        required_successes: usize,
+    },
+    Inverter,
+    /// reruns the child until it succeeded `limit` times (forever if `None`)
+    Repeat {
+        limit: Option<u32>,
+    },
+    AlwaysSucceed,
+    Action(Box<dyn ActionFn>),
+    Condition(Box<dyn ConditionFn>),
+}
+
+/// Tree description used to build a `BehaviorTree`.
+pub struct Node {
+    name: Cow<'static, str>,
+    kind: NodeKind,
+    children: Vec<Node>,
+}
+
+impl Node {
+    pub fn new(name: impl Into<Cow<'static, str>>, kind: NodeKind, children: Vec<Node>) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            children,
+        }
+    }
+
+    pub fn sequence(name: impl Into<Cow<'static, str>>, children: Vec<Node>) -> Self {
+        Self::new(name, NodeKind::Sequence, children)
+    }
+
+    pub fn selector(name: impl Into<Cow<'static, str>>, children: Vec<Node>) -> Self {
+        Self::new(name, NodeKind::Selector, children)
+    }
+
+    pub fn parallel(
+        name: impl Into<Cow<'static, str>>,
+        required_successes: usize,
+        children: Vec<Node>,
+    ) -> Self {
+        Self::new(name, NodeKind::Parallel { required_successes }, children)
+    }
+
+    pub fn inverter(name: impl Into<Cow<'static, str>>, child: Node) -> Self {
+        Self::new(name, NodeKind::Inverter, vec![child])
+    }
+
+    pub fn repeat(name: impl Into<Cow<'static, str>>, limit: Option<u32>, child: Node) -> Self {
+        Self::new(name, NodeKind::Repeat { limit }, vec![child])
+    }
+
+    pub fn always_succeed(name: impl Into<Cow<'static, str>>, child: Node) -> Self {
+        Self::new(name, NodeKind::AlwaysSucceed, vec![child])
+    }
+
+    pub fn action(name: impl Into<Cow<'static, str>>, f: impl ActionFn + 'static) -> Self {
+        Self::new(name, NodeKind::Action(Box::new(f)), Vec::new())
+    }
+
+    pub fn condition(name: impl Into<Cow<'static, str>>, f: impl ConditionFn + 'static) -> Self {
+        Self::new(name, NodeKind::Condition(Box::new(f)), Vec::new())
+    }
+}
+
+/// Status of a node after the last tick, `status` is `None` if the node
+/// wasn't ticked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeSnapshot {
+    pub name: Cow<'static, str>,
+    pub depth: usize,
+    pub status: Option<Status>,
+}
+
+struct NodeData {
+    name: Cow<'static, str>,
+    kind: NodeKind,
+    depth: usize,
+    children: Vec<usize>,
+    // index of the running child for sequences/selectors, success count for
+    // repeats
+    counter: usize,
+    status: Option<Status>,
+}
+
+/// A behavior tree stored as a flat list of nodes in pre-order.
+pub struct BehaviorTree {
+    nodes: Vec<NodeData>,
+    pub tick_interval: Duration,
+    pub blackboard: Arc<Mutex<Blackboard>>,
+    snapshot: Arc<Mutex<Vec<NodeSnapshot>>>,
+}
+
+impl BehaviorTree {
+    pub const DEFAULT_TICK_INTERVAL: Duration = Duration::from_millis(50);
+
+    pub fn new(root: Node) -> Self {
+        fn flatten(node: Node, depth: usize, nodes: &mut Vec<NodeData>) -> usize {
+            let index = nodes.len();
+            nodes.push(NodeData {
+                name: node.name,
+                kind: node.kind,
+                depth,
+                children: Vec::new(),
+                counter: 0,
+                status: None,
+            });
+            let children = node
+                .children
+                .into_iter()
+                .map(|child| flatten(child, depth + 1, nodes))
+                .collect();
+            nodes[index].children = children;
+            index
+        }
+
+        let mut nodes = Vec::new();
+        flatten(root, 0, &mut nodes);
+        let slf = Self {
+            nodes,
+            tick_interval: Self::DEFAULT_TICK_INTERVAL,
+            blackboard: Arc::new(Mutex::new(Blackboard::new())),
+            snapshot: Arc::new(Mutex::new(Vec::new())),
+        };
+        slf.publish_snapshot();
+        slf
+    }
+
+    pub fn with_tick_interval(mut self, tick_interval: Duration) -> Self {
+        self.tick_interval = tick_interval;
+        self
+    }
+
+    /// node statuses of the last tick, shared with whoever is visualizing
+    /// the tree
+    pub fn snapshot(&self) -> Arc<Mutex<Vec<NodeSnapshot>>> {
+        self.snapshot.clone()
+    }
+
+    pub fn tick(&mut self) -> Status {
+        for node in self.nodes.iter_mut() {
+            node.status = None;
+        }
+
+        let blackboard = self.blackboard.clone();
+        let status = self.tick_node(0, &mut blackboard.lock());
+        self.publish_snapshot();
+        status
+    }
+
+    fn publish_snapshot(&self) {
+        *self.snapshot.lock() = self
+            .nodes
+            .iter()
+            .map(|node| NodeSnapshot {
+                name: node.name.clone(),
+                depth: node.depth,
+                status: node.status,
+            })
+            .collect();
+    }
+
+    // resets the memory of a subtree that stopped being ticked while running
+    fn reset(&mut self, index: usize) {
+        self.nodes[index].counter = 0;
+        for child in self.nodes[index].children.clone() {
+            self.reset(child);
+        }
+    }
+
+    fn tick_node(&mut self, index: usize, blackboard: &mut Blackboard) -> Status {
+        let children = self.nodes[index].children.clone();
+        let status = match &mut self.nodes[index].kind {
+            NodeKind::Action(f) => f(blackboard),
+            NodeKind::Condition(f) => {
+                if f(blackboard) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            NodeKind::Sequence => {
+                self.tick_composite(index, &children, blackboard, Status::Success, false)
+            }
+            NodeKind::Selector => {
+                self.tick_composite(index, &children, blackboard, Status::Failure, true)
+            }
+            &mut NodeKind::Parallel { required_successes } => {
+                let statuses: Vec<_> = children
+                    .iter()
+                    .map(|&child| self.tick_node(child, blackboard))
+                    .collect();
+                let successes = statuses.iter().filter(|&&s| s == Status::Success).count();
+                let failures = statuses.iter().filter(|&&s| s == Status::Failure).count();
+                if successes >= required_successes {
+                    Status::Success
+                } else if children.len() - failures < required_successes {
+                    Status::Failure
+                } else {
+                    Status::Running
+                }
+            }
+            NodeKind::Inverter => match self.tick_child(&children, blackboard) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            NodeKind::AlwaysSucceed => match self.tick_child(&children, blackboard) {
+                Status::Running => Status::Running,
+                _ => Status::Success,
+            },
+            &mut NodeKind::Repeat { limit } => match self.tick_child(&children, blackboard) {
+                Status::Running => Status::Running,
+                Status::Failure => {
+                    self.nodes[index].counter = 0;
+                    Status::Failure
+                }
+                Status::Success => {
+                    let counter = &mut self.nodes[index].counter;
+                    *counter += 1;
+                    if matches!(limit, Some(limit) if *counter >= limit as usize) {
+                        *counter = 0;
+                        Status::Success
+                    } else {
+                        Status::Running
+                    }
+                }
+            },
+        };
+
+        self.nodes[index].status = Some(status);
+        status
+    }
+
+    fn tick_child(&mut self, children: &[usize], blackboard: &mut Blackboard) -> Status {
+        match children.first() {
+            Some(&child) => self.tick_node(child, blackboard),
+            None => Status::Failure,
+        }
+    }
+
+    // sequences continue while children succeed and resume from their running
+    // child, selectors continue while children fail and are reactive: they
+    // restart from their first child every tick, aborting the running child
+    // if a higher priority one doesn't fail
+    fn tick_composite(
+        &mut self,
+        index: usize,
+        children: &[usize],
+        blackboard: &mut Blackboard,
+        continue_status: Status,
+        reactive: bool,
+    ) -> Status {
+        let running = self.nodes[index].counter;
+        let start = if reactive { 0 } else { running };
+        for (i, &child) in children.iter().enumerate().skip(start) {
+            let status = self.tick_node(child, blackboard);
+            if status == continue_status {
+                continue;
+            }
+
+            if reactive && i != running {
+                if let Some(&aborted) = children.get(running) {
+                    self.reset(aborted);
+                }
+            }
+            if status == Status::Running {
+                self.nodes[index].counter = i;
+            } else {
+                self.reset(index);
+            }
+            return status;
+        }
+        self.reset(index);
+        continue_status
+    }
+}
+
+#[test]
+fn test() {
+    let mut tree = BehaviorTree::new(Node::sequence(
+        "root",
+        vec![
+            Node::condition("has_target", |bb| bb.contains("target")),
+            Node::action("count", |bb| {
+                let count = bb.get_or_default::<u32>("count") + 1;
+                bb.set("count", count);
+                if count < 2 {
+                    Status::Running
+                } else {
+                    Status::Success
+                }
+            }),
+        ],
+    ));
+
+    assert_eq!(tree.tick(), Status::Failure);
+    tree.blackboard.lock().set("target", ());
+    assert_eq!(tree.tick(), Status::Running);
+    assert_eq!(tree.tick(), Status::Success);
+
+    let statuses: Vec<_> = tree.snapshot().lock().iter().map(|n| n.status).collect();
+    // the condition is skipped while resuming the running action
+    assert_eq!(
+        statuses,
+        vec![Some(Status::Success), None, Some(Status::Success)]
+    );
+}
@@ -0,0 +1,52 @@
+use std::{
+    any::Any,
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+};
+
+/// Typed key-value storage shared between the nodes of a behavior tree and
+/// the game code driving it.
+#[derive(Default)]
+pub struct Blackboard {
+    values: HashMap<Cow<'static, str>, Box<dyn Any + Send>>,
+}
+
+impl Blackboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set<T: Any + Send>(&mut self, key: impl Into<Cow<'static, str>>, value: T) {
+        self.values.insert(key.into(), Box::new(value));
+    }
+
+    /// `None` if the key doesn't exist or the value is not a `T`
+    pub fn get<T: Any>(&self, key: &str) -> Option<&T> {
+        self.values.get(key).and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<T: Any>(&mut self, key: &str) -> Option<&mut T> {
+        self.values
+            .get_mut(key)
+            .and_then(|value| value.downcast_mut())
+    }
+
+    pub fn get_or_default<T: Any + Copy + Default>(&self, key: &str) -> T {
+        self.get(key).copied().unwrap_or_default()
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.values.remove(key).is_some()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+}
+
+impl Debug for Blackboard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.values.keys()).finish()
+    }
+}
@@ -0,0 +1,2 @@
+pub mod behavior_tree;
+pub mod blackboard;
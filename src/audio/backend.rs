@@ -0,0 +1,255 @@
+use std::{
+    sync::Arc,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+
+use crate::utils::{
+    mpsc::{self, Sender},
+    mutex::Mutex,
+};
+
+use super::mixer::Mixer;
+
+/// Sample rate used by the null backend
+pub const NULL_SAMPLE_RATE: u32 = 48000;
+// the null backend doesn't catch up on more than this after a stall
+const NULL_MAX_CATCH_UP: Duration = Duration::from_millis(250);
+
+/// Where the mixed samples go.
+pub enum AudioBackend {
+    /// Discards the samples, but still advances the mixer in real time so
+    /// that sounds finish as they would with an output device (headless
+    /// test mode, machines without audio output).
+    Null(NullBackend),
+    Cpal(CpalBackend),
+}
+
+pub struct NullBackend {
+    last_update: Instant,
+    buffer: Vec<f32>,
+    /// fractional frame carried over from the last update
+    remainder: f64,
+}
+
+/// Output stream on the default output device, the mixer is run from the
+/// cpal audio callback.
+///
+/// `cpal::Stream` is not `Send` on every platform, so it is owned by a
+/// dedicated thread and the backend only keeps a handle to stop it.
+pub struct CpalBackend {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AudioBackend {
+    /// Falls back to the null backend if the output stream can't be created.
+    pub fn new(mixer: &Arc<Mutex<Mixer>>, null: bool) -> Self {
+        if null {
+            return Self::null(mixer);
+        }
+        match CpalBackend::new(mixer.clone()) {
+            Ok(backend) => Self::Cpal(backend),
+            Err(e) => {
+                tracing::warn!("unable to open audio output, falling back to null backend: {e:#}");
+                Self::null(mixer)
+            }
+        }
+    }
+
+    fn null(mixer: &Arc<Mutex<Mixer>>) -> Self {
+        mixer.lock().set_sample_rate(NULL_SAMPLE_RATE);
+        Self::Null(NullBackend {
+            last_update: Instant::now(),
+            buffer: Vec::new(),
+            remainder: 0.0,
+        })
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null(_))
+    }
+
+    /// Called every audio server tick.
+    pub fn update(&mut self, mixer: &Mutex<Mixer>) {
+        if let Self::Null(backend) = self {
+            let now = Instant::now();
+            let elapsed = now
+                .duration_since(backend.last_update)
+                .min(NULL_MAX_CATCH_UP);
+            backend.last_update = now;
+
+            let mut mixer = mixer.lock();
+            let frames = elapsed.as_secs_f64() * mixer.sample_rate() as f64 + backend.remainder;
+            backend.remainder = frames.fract();
+            backend.buffer.resize(frames as usize, 0.0);
+            mixer.mix(&mut backend.buffer, 1);
+        }
+    }
+}
+
+impl CpalBackend {
+    pub fn new(mixer: Arc<Mutex<Mixer>>) -> anyhow::Result<Self> {
+        let (ready_sender, ready_receiver) = mpsc::channels();
+        let (stop_sender, stop_receiver) = mpsc::channels::<()>();
+        let thread = std::thread::Builder::new()
+            .name("audio output".into())
+            .spawn(move || match open_stream(mixer) {
+                Ok(stream) => {
+                    let _ = ready_sender.send(Ok(()));
+                    // blocks until the backend is dropped
+                    let _ = stop_receiver.recv();
+                    drop(stream);
+                }
+                Err(e) => {
+                    let _ = ready_sender.send(Err(e));
+                }
+            })
+            .context("unable to spawn audio output thread")?;
+
+        ready_receiver
+            .recv()
+            .context("audio output thread exited unexpectedly")??;
+        Ok(Self {
+            stop: Some(stop_sender),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for CpalBackend {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                tracing::error!("audio output thread panicked");
+            }
+        }
+    }
+}
+
+fn open_stream(mixer: Arc<Mutex<Mixer>>) -> anyhow::Result<cpal::Stream> {
+    use cpal::{
+        traits::{DeviceTrait, HostTrait, StreamTrait},
+        SampleFormat,
+    };
+
+    let device = cpal::default_host()
+        .default_output_device()
+        .context("no audio output device available")?;
+    let config = device
+        .default_output_config()
+        .context("unable to query default output config")?;
+    let format = config.sample_format();
+    let config: cpal::StreamConfig = config.into();
+    mixer.lock().set_sample_rate(config.sample_rate.0);
+    tracing::info!(
+        "audio output: {} ({} channels, {}Hz, {format:?})",
+        device.name().unwrap_or_else(|_| "unknown device".into()),
+        config.channels,
+        config.sample_rate.0
+    );
+
+    let stream = match format {
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, mixer)?,
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, mixer)?,
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, mixer)?,
+        format => bail!("unsupported sample format {format:?}"),
+    };
+    stream
+        .play()
+        .context("unable to start audio output stream")?;
+    Ok(stream)
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mixer: Arc<Mutex<Mixer>>,
+) -> anyhow::Result<cpal::Stream>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    use cpal::traits::DeviceTrait;
+
+    let channels = config.channels as usize;
+    let mut buffer = Vec::new();
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                buffer.resize(data.len(), 0.0);
+                mixer.lock().mix(&mut buffer, channels);
+                for (out, &sample) in data.iter_mut().zip(buffer.iter()) {
+                    *out = T::from_sample(sample.clamp(-1.0, 1.0));
+                }
+            },
+            |e| tracing::error!("audio output stream error: {e}"),
+            None,
+        )
+        .context("unable to build audio output stream")
+}
+
+/// Lists the output devices of every audio host and their configs, for
+/// the `--diagnose` report.
+pub fn describe_output_devices(report: &mut String) -> std::fmt::Result {
+    use std::fmt::Write;
+
+    use cpal::traits::{DeviceTrait, HostTrait};
+
+    for id in cpal::available_hosts() {
+        writeln!(report, "host {}:", id.name())?;
+        let host = match cpal::host_from_id(id) {
+            Ok(host) => host,
+            Err(e) => {
+                writeln!(report, "  unavailable: {e}")?;
+                continue;
+            }
+        };
+        let default_name = host.default_output_device().and_then(|d| d.name().ok());
+        let devices = match host.output_devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                writeln!(report, "  unable to list output devices: {e}")?;
+                continue;
+            }
+        };
+        for device in devices {
+            let name = device.name().unwrap_or_else(|_| "unknown device".into());
+            let default = if default_name.as_ref() == Some(&name) {
+                " (default)"
+            } else {
+                ""
+            };
+            writeln!(report, "  {name}{default}")?;
+            match device.default_output_config() {
+                Ok(config) => writeln!(
+                    report,
+                    "    default: {} channels, {}Hz, {:?}",
+                    config.channels(),
+                    config.sample_rate().0,
+                    config.sample_format()
+                )?,
+                Err(e) => writeln!(report, "    no default config: {e}")?,
+            }
+            match device.supported_output_configs() {
+                Ok(configs) => {
+                    for config in configs {
+                        writeln!(
+                            report,
+                            "    supported: {} channels, {}-{}Hz, {:?}",
+                            config.channels(),
+                            config.min_sample_rate().0,
+                            config.max_sample_rate().0,
+                            config.sample_format()
+                        )?;
+                    }
+                }
+                Err(e) => writeln!(report, "    unable to list configs: {e}")?,
+            }
+        }
+    }
+    Ok(())
+}
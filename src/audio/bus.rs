@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use super::ChannelId;
+
+// mixer channels double as buses, these are the ones with a name
+pub const SFX: ChannelId = 0;
+pub const MUSIC: ChannelId = 1;
+pub const UI: ChannelId = 2;
+pub const VOICE: ChannelId = 3;
+
+const NAMES: [(&str, ChannelId); 4] =
+    [("sfx", SFX), ("music", MUSIC), ("ui", UI), ("voice", VOICE)];
+
+/// Attenuates the `target` bus while any sound plays on the `trigger` bus,
+/// e.g. music ducked under dialogue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DuckingRule {
+    pub trigger: ChannelId,
+    pub target: ChannelId,
+    /// gain of the target bus while ducked
+    pub gain: f32,
+    /// how long it takes to duck
+    pub attack: Duration,
+    /// how long it takes to come back once the trigger bus is silent
+    pub release: Duration,
+}
+
+pub fn by_name(name: &str) -> Option<ChannelId> {
+    NAMES
+        .iter()
+        .find(|(bus_name, _)| bus_name.eq_ignore_ascii_case(name))
+        .map(|&(_, id)| id)
+}
+
+impl DuckingRule {
+    pub fn new(trigger: ChannelId, target: ChannelId, gain: f32) -> Self {
+        Self {
+            trigger,
+            target,
+            gain,
+            attack: Duration::from_millis(100),
+            release: Duration::from_millis(500),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use crate::utils::mutex::Mutex;
+
+use super::{ChannelId, PlayOptions, PlaybackId, SoundHandle};
+
+pub const DEFAULT_BPM: f64 = 120.0;
+
+pub type SharedBeatClock = Arc<BeatClock>;
+
+/// Tempo of the beat clock, anchored so that tempo changes keep the beat
+/// continuous.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Tempo {
+    bpm: f64,
+    sample_rate: u32,
+    anchor_frame: u64,
+    anchor_beat: f64,
+}
+
+/// Musical clock counted in frames mixed by the mixer, readable from any
+/// thread (e.g. by the update server to sync gameplay to the music).
+pub struct BeatClock {
+    frames: AtomicU64,
+    tempo: Mutex<Tempo>,
+}
+
+/// When a scheduled audio event happens.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScheduleAt {
+    /// mixer frame, see `BeatClock::frames`
+    Frame(u64),
+    Beat(f64),
+    /// next beat that is a multiple of this many beats, e.g. 1 for the
+    /// next beat or 4 for the next bar in 4/4
+    NextMultiple(f64),
+}
+
+/// Change applied by the mixer at the exact frame it was scheduled at.
+#[derive(Clone, Debug)]
+pub enum ScheduledAction {
+    Play(PlaybackId, SoundHandle, PlayOptions),
+    Stop(PlaybackId),
+    SetChannelGain(ChannelId, f32),
+    SetChannelPan(ChannelId, f32),
+    SetChannelMuted(ChannelId, bool),
+}
+
+impl Tempo {
+    fn beat(&self, frame: u64) -> f64 {
+        let frames = frame as f64 - self.anchor_frame as f64;
+        self.anchor_beat + frames / self.sample_rate.max(1) as f64 * self.bpm / 60.0
+    }
+
+    /// First frame at or after `beat`, frames before the anchor are
+    /// clamped to it.
+    fn frame(&self, beat: f64) -> u64 {
+        let beats = (beat - self.anchor_beat).max(0.0);
+        let frames = beats * 60.0 / self.bpm * self.sample_rate as f64;
+        self.anchor_frame + frames.ceil() as u64
+    }
+}
+
+impl Default for BeatClock {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl BeatClock {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            frames: AtomicU64::new(0),
+            tempo: Mutex::new(Tempo {
+                bpm: DEFAULT_BPM,
+                sample_rate,
+                anchor_frame: 0,
+                anchor_beat: 0.0,
+            }),
+        }
+    }
+
+    /// Frames mixed so far.
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Acquire)
+    }
+
+    pub fn advance(&self, frames: u64) {
+        self.frames.fetch_add(frames, Ordering::AcqRel);
+    }
+
+    /// Changes the tempo from the current frame on.
+    pub fn set_bpm(&self, bpm: f64) {
+        let frame = self.frames();
+        let mut tempo = self.tempo.lock();
+        tempo.anchor_beat = tempo.beat(frame);
+        tempo.anchor_frame = frame;
+        tempo.bpm = bpm.max(f64::EPSILON);
+    }
+
+    /// Restarts counting beats at the current frame.
+    pub fn reset_beat(&self) {
+        let frame = self.frames();
+        let mut tempo = self.tempo.lock();
+        tempo.anchor_beat = 0.0;
+        tempo.anchor_frame = frame;
+    }
+
+    pub fn set_sample_rate(&self, sample_rate: u32) {
+        let frame = self.frames();
+        let mut tempo = self.tempo.lock();
+        tempo.anchor_beat = tempo.beat(frame);
+        tempo.anchor_frame = frame;
+        tempo.sample_rate = sample_rate;
+    }
+
+    /// Fractional beat at the current frame.
+    pub fn beat(&self) -> f64 {
+        self.tempo.lock().beat(self.frames())
+    }
+
+    /// Frame `at` refers to, past frames are returned as is.
+    pub fn resolve(&self, at: ScheduleAt) -> u64 {
+        let frame = self.frames();
+        let tempo = *self.tempo.lock();
+        match at {
+            ScheduleAt::Frame(frame) => frame,
+            ScheduleAt::Beat(beat) => tempo.frame(beat),
+            ScheduleAt::NextMultiple(beats) => {
+                let beats = beats.max(f64::EPSILON);
+                // a beat starting on this very frame is still "next"
+                let beat = (tempo.beat(frame) / beats).ceil() * beats;
+                tempo.frame(beat).max(frame)
+            }
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let clock = BeatClock::new(100);
+    // 2 beats per second, 50 frames per beat
+    assert_eq!(clock.resolve(ScheduleAt::NextMultiple(1.0)), 0);
+    clock.advance(10);
+    assert!((clock.beat() - 0.2).abs() < 1e-9);
+    assert_eq!(clock.resolve(ScheduleAt::NextMultiple(1.0)), 50);
+    assert_eq!(clock.resolve(ScheduleAt::NextMultiple(4.0)), 200);
+    assert_eq!(clock.resolve(ScheduleAt::Beat(3.0)), 150);
+    assert_eq!(clock.resolve(ScheduleAt::Frame(7)), 7);
+
+    // the beat stays continuous across tempo changes
+    clock.advance(40);
+    clock.set_bpm(60.0);
+    assert!((clock.beat() - 1.0).abs() < 1e-9);
+    assert_eq!(clock.resolve(ScheduleAt::Beat(2.0)), 150);
+
+    clock.reset_beat();
+    assert_eq!(clock.beat(), 0.0);
+    assert_eq!(clock.resolve(ScheduleAt::NextMultiple(1.0)), 50);
+}
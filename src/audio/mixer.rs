@@ -0,0 +1,595 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use glam::Vec2;
+
+use super::{
+    bus::{self, DuckingRule},
+    clock::{BeatClock, ScheduledAction, SharedBeatClock},
+    spatial::Listener,
+    stream::SharedStreamBuffer,
+    ChannelId, PlayOptions, PlaybackId, SoundHandle,
+};
+
+pub const DEFAULT_CHANNEL: ChannelId = bus::SFX;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelParams {
+    pub gain: f32,
+    /// -1 (left) to 1 (right)
+    pub pan: f32,
+    pub muted: bool,
+}
+
+struct Voice {
+    id: PlaybackId,
+    sound: SoundHandle,
+    options: PlayOptions,
+    /// in frames of the sound, fractional because of resampling
+    position: f64,
+}
+
+/// Gain ramp of a streamed voice, advanced per mixed frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Fade {
+    gain: f32,
+    target: f32,
+    /// gain change per second
+    rate: f32,
+}
+
+struct Ducking {
+    rule: DuckingRule,
+    fade: Fade,
+    active: bool,
+}
+
+struct StreamVoice {
+    id: PlaybackId,
+    buffer: SharedStreamBuffer,
+    channel: ChannelId,
+    fade: Fade,
+    /// fractional frame offset into the buffer
+    position: f64,
+    /// removed once faded out
+    stopping: bool,
+}
+
+/// Software mixer summing every playing sound into the output buffer, with
+/// gain and pan applied per channel.
+///
+/// Sounds are resampled (linearly) to the output sample rate.
+pub struct Mixer {
+    sample_rate: u32,
+    channels: HashMap<ChannelId, ChannelParams>,
+    voices: Vec<Voice>,
+    streams: Vec<StreamVoice>,
+    ducking: Vec<Ducking>,
+    clock: SharedBeatClock,
+    /// sorted by frame, events scheduled for the same frame keep their order
+    scheduled: Vec<(u64, ScheduledAction)>,
+    pub listener: Listener,
+    pub master_gain: f32,
+}
+
+impl Default for ChannelParams {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            pan: 0.0,
+            muted: false,
+        }
+    }
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            channels: HashMap::new(),
+            voices: Vec::new(),
+            streams: Vec::new(),
+            ducking: Vec::new(),
+            clock: Arc::new(BeatClock::new(sample_rate)),
+            scheduled: Vec::new(),
+            listener: Listener::default(),
+            master_gain: 1.0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.clock.set_sample_rate(sample_rate);
+    }
+
+    /// Counts the mixed frames, see `BeatClock`.
+    pub fn clock(&self) -> &SharedBeatClock {
+        &self.clock
+    }
+
+    /// Applies `action` when the mixer reaches `frame`, events in the past
+    /// are applied at the start of the next mixed block.
+    pub fn schedule(&mut self, frame: u64, action: ScheduledAction) {
+        let index = self.scheduled.partition_point(|&(other, _)| other <= frame);
+        self.scheduled.insert(index, (frame, action));
+    }
+
+    pub fn scheduled_count(&self) -> usize {
+        self.scheduled.len()
+    }
+
+    fn apply(&mut self, action: ScheduledAction) {
+        match action {
+            ScheduledAction::Play(id, sound, options) => self.play(id, sound, options),
+            ScheduledAction::Stop(id) => {
+                self.stop(id);
+            }
+            ScheduledAction::SetChannelGain(channel, gain) => self.channel_mut(channel).gain = gain,
+            ScheduledAction::SetChannelPan(channel, pan) => {
+                self.channel_mut(channel).pan = pan.clamp(-1.0, 1.0);
+            }
+            ScheduledAction::SetChannelMuted(channel, muted) => {
+                self.channel_mut(channel).muted = muted;
+            }
+        }
+    }
+
+    pub fn play(&mut self, id: PlaybackId, sound: SoundHandle, options: PlayOptions) {
+        self.voices.push(Voice {
+            id,
+            sound,
+            options,
+            position: 0.0,
+        });
+    }
+
+    /// Plays frames pushed to `buffer` as they arrive, fading in from
+    /// silence over `fade_in`. The voice ends once the buffer is finished
+    /// and drained.
+    pub fn play_stream(
+        &mut self,
+        id: PlaybackId,
+        buffer: SharedStreamBuffer,
+        channel: ChannelId,
+        fade_in: Duration,
+    ) {
+        let mut fade = Fade {
+            gain: 0.0,
+            target: 0.0,
+            rate: 0.0,
+        };
+        fade.start(1.0, fade_in);
+        self.streams.push(StreamVoice {
+            id,
+            buffer,
+            channel,
+            fade,
+            position: 0.0,
+            stopping: false,
+        });
+    }
+
+    /// Fades a streamed voice out over `duration` before removing it,
+    /// returns false if there is no such stream.
+    pub fn fade_out(&mut self, id: PlaybackId, duration: Duration) -> bool {
+        match self.streams.iter_mut().find(|stream| stream.id == id) {
+            Some(stream) => {
+                stream.fade.start(0.0, duration);
+                stream.stopping = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves a positional sound, returns false if there is no such sound or
+    /// it isn't positional.
+    pub fn set_emitter_position(&mut self, id: PlaybackId, position: Vec2) -> bool {
+        match self
+            .voices
+            .iter_mut()
+            .find(|voice| voice.id == id)
+            .and_then(|voice| voice.options.emitter.as_mut())
+        {
+            Some(emitter) => {
+                emitter.position = position;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn stop(&mut self, id: PlaybackId) -> bool {
+        let len = self.voices.len() + self.streams.len();
+        self.voices.retain(|voice| voice.id != id);
+        self.streams.retain(|stream| stream.id != id);
+        self.voices.len() + self.streams.len() != len
+    }
+
+    pub fn stop_all(&mut self) {
+        self.voices.clear();
+        self.streams.clear();
+    }
+
+    pub fn is_playing(&self, id: PlaybackId) -> bool {
+        self.voices.iter().any(|voice| voice.id == id)
+            || self.streams.iter().any(|stream| stream.id == id)
+    }
+
+    pub fn voice_count(&self) -> usize {
+        self.voices.len() + self.streams.len()
+    }
+
+    pub fn channel(&self, channel: ChannelId) -> ChannelParams {
+        self.channels.get(&channel).copied().unwrap_or_default()
+    }
+
+    pub fn channel_mut(&mut self, channel: ChannelId) -> &mut ChannelParams {
+        self.channels.entry(channel).or_default()
+    }
+
+    /// Replaces the rule with the same trigger and target buses, if any.
+    pub fn add_ducking_rule(&mut self, rule: DuckingRule) {
+        self.remove_ducking_rule(rule.trigger, rule.target);
+        self.ducking.push(Ducking {
+            rule,
+            fade: Fade {
+                gain: 1.0,
+                target: 1.0,
+                rate: 0.0,
+            },
+            active: false,
+        });
+    }
+
+    pub fn remove_ducking_rule(&mut self, trigger: ChannelId, target: ChannelId) -> bool {
+        let len = self.ducking.len();
+        self.ducking
+            .retain(|ducking| (ducking.rule.trigger, ducking.rule.target) != (trigger, target));
+        self.ducking.len() != len
+    }
+
+    /// Current ducking attenuation of `channel`, 1 if it isn't ducked.
+    pub fn duck_gain(&self, channel: ChannelId) -> f32 {
+        self.ducking
+            .iter()
+            .filter(|ducking| ducking.rule.target == channel)
+            .map(|ducking| ducking.fade.gain)
+            .product()
+    }
+
+    fn channel_active(&self, channel: ChannelId) -> bool {
+        self.voices
+            .iter()
+            .any(|voice| voice.options.channel == channel)
+            || self
+                .streams
+                .iter()
+                .any(|stream| stream.channel == channel && !stream.stopping)
+    }
+
+    /// Ducking is evaluated once per mixed block.
+    fn update_ducking(&mut self, frames: usize) {
+        let dt = frames as f32 / self.sample_rate.max(1) as f32;
+        for i in 0..self.ducking.len() {
+            let active = self.channel_active(self.ducking[i].rule.trigger);
+            let ducking = &mut self.ducking[i];
+            if active != ducking.active {
+                ducking.active = active;
+                if active {
+                    ducking.fade.start(ducking.rule.gain, ducking.rule.attack);
+                } else {
+                    ducking.fade.start(1.0, ducking.rule.release);
+                }
+            }
+            ducking.fade.advance(dt);
+        }
+    }
+
+    /// Gain and pan of `channel` with mute and ducking applied.
+    fn effective_params(&self, channel: ChannelId) -> (f32, f32) {
+        let params = self.channel(channel);
+        let gain = if params.muted {
+            0.0
+        } else {
+            self.master_gain * params.gain * self.duck_gain(channel)
+        };
+        (gain, params.pan.clamp(-1.0, 1.0))
+    }
+
+    /// Fills `out` (interleaved, `out_channels` per frame) with the next
+    /// samples, finished sounds are removed.
+    ///
+    /// The block is split at the frames of the scheduled events, so that
+    /// they are applied on the exact frame.
+    pub fn mix(&mut self, out: &mut [f32], out_channels: usize) {
+        out.fill(0.0);
+        if out_channels == 0 {
+            return;
+        }
+
+        let frames = out.len() / out_channels;
+        let start = self.clock.frames();
+        let mut mixed = 0;
+        while mixed < frames {
+            let now = start + mixed as u64;
+            let due = self.scheduled.partition_point(|&(frame, _)| frame <= now);
+            for (_, action) in self.scheduled.drain(..due).collect::<Vec<_>>() {
+                self.apply(action);
+            }
+            let end = match self.scheduled.first() {
+                Some(&(frame, _)) => frames.min((frame - start) as usize),
+                None => frames,
+            };
+            self.mix_block(
+                &mut out[mixed * out_channels..end * out_channels],
+                out_channels,
+            );
+            self.clock.advance((end - mixed) as u64);
+            mixed = end;
+        }
+    }
+
+    /// Adds the next samples of the playing sounds to `out`.
+    fn mix_block(&mut self, out: &mut [f32], out_channels: usize) {
+        let frames = out.len() / out_channels;
+        self.update_ducking(frames);
+        let params: HashMap<_, _> = self
+            .voices
+            .iter()
+            .map(|voice| voice.options.channel)
+            .chain(self.streams.iter().map(|stream| stream.channel))
+            .map(|channel| (channel, self.effective_params(channel)))
+            .collect();
+        let sample_rate = self.sample_rate as f64;
+        let listener = self.listener;
+        self.voices.retain_mut(|voice| {
+            let sound = voice.sound.data();
+            let len = sound.frames();
+            if len == 0 {
+                return false;
+            }
+
+            let (gain, pan) = params[&voice.options.channel];
+            let (spatial_gain, spatial_pan) = voice
+                .options
+                .emitter
+                .map(|emitter| emitter.spatialize(&listener))
+                .unwrap_or((1.0, 0.0));
+            let gain = gain * voice.options.gain * spatial_gain;
+            let pan = (pan + spatial_pan).clamp(-1.0, 1.0);
+            let (left_gain, right_gain) =
+                (gain * (1.0 - pan).min(1.0), gain * (1.0 + pan).min(1.0));
+            let step = sound.sample_rate as f64 / sample_rate;
+
+            for frame in out.chunks_exact_mut(out_channels).take(frames) {
+                if voice.position >= len as f64 {
+                    if !voice.options.looping {
+                        return false;
+                    }
+                    voice.position = voice.position.rem_euclid(len as f64);
+                }
+
+                let index = voice.position as usize;
+                let t = (voice.position - index as f64) as f32;
+                let next = if index + 1 < len {
+                    index + 1
+                } else if voice.options.looping {
+                    0
+                } else {
+                    index
+                };
+                let (l0, r0) = sound.frame(index);
+                let (l1, r1) = sound.frame(next);
+                let left = (l0 + (l1 - l0) * t) * left_gain;
+                let right = (r0 + (r1 - r0) * t) * right_gain;
+
+                if out_channels == 1 {
+                    frame[0] += (left + right) * 0.5;
+                } else {
+                    frame[0] += left;
+                    frame[1] += right;
+                }
+                voice.position += step;
+            }
+            voice.options.looping || voice.position < len as f64
+        });
+
+        self.streams.retain_mut(|stream| {
+            let mut buffer = stream.buffer.lock();
+            if buffer.sample_rate == 0 {
+                // still opening, nothing to fade out yet
+                return !buffer.finished && !stream.stopping;
+            }
+
+            let (gain, pan) = params[&stream.channel];
+            let (left_gain, right_gain) =
+                (gain * (1.0 - pan).min(1.0), gain * (1.0 + pan).min(1.0));
+            let step = buffer.sample_rate as f64 / sample_rate;
+            let dt = (1.0 / sample_rate) as f32;
+
+            for frame in out.chunks_exact_mut(out_channels).take(frames) {
+                let index = stream.position as usize;
+                // the next frame is needed for interpolation, unless the
+                // stream has ended
+                if (index + 1 >= buffer.frames.len() && !buffer.finished)
+                    || index >= buffer.frames.len()
+                {
+                    // underrun or end of the stream
+                    break;
+                }
+
+                let t = (stream.position - index as f64) as f32;
+                let (l0, r0) = buffer.frames[index];
+                let (l1, r1) = buffer.frames.get(index + 1).copied().unwrap_or((l0, r0));
+                let fade = stream.fade.advance(dt);
+                let left = (l0 + (l1 - l0) * t) * left_gain * fade;
+                let right = (r0 + (r1 - r0) * t) * right_gain * fade;
+
+                if out_channels == 1 {
+                    frame[0] += (left + right) * 0.5;
+                } else {
+                    frame[0] += left;
+                    frame[1] += right;
+                }
+                stream.position += step;
+            }
+
+            let consumed = (stream.position as usize).min(buffer.frames.len());
+            buffer.frames.drain(..consumed);
+            stream.position -= consumed as f64;
+
+            let faded_out = stream.stopping && stream.fade.gain <= 0.0;
+            let drained = buffer.finished && buffer.frames.is_empty();
+            !faded_out && !drained
+        });
+    }
+}
+
+impl Fade {
+    /// Ramps from the current gain to `target` over `duration`.
+    fn start(&mut self, target: f32, duration: Duration) {
+        self.target = target;
+        match duration.as_secs_f32() {
+            secs if secs > 0.0 => self.rate = (target - self.gain).abs() / secs,
+            _ => self.gain = target,
+        }
+    }
+
+    /// Moves the gain `dt` seconds towards the target, returns the new gain.
+    fn advance(&mut self, dt: f32) -> f32 {
+        // snapped to avoid never quite reaching the target due to rounding
+        let delta = self.rate * dt + f32::EPSILON * 16.0;
+        self.gain = if self.gain < self.target {
+            (self.gain + delta).min(self.target)
+        } else {
+            (self.gain - delta).max(self.target)
+        };
+        self.gain
+    }
+}
+
+#[test]
+fn test() {
+    use super::SoundData;
+    use crate::utils::uid::Uid;
+
+    let sound = SoundHandle::new(SoundData::new(vec![1.0; 4], 1, 100).unwrap());
+    let mut mixer = Mixer::new(100);
+    let (a, b) = (Uid::new(), Uid::new());
+    mixer.play(a, sound.clone(), PlayOptions::default());
+    mixer.play(
+        b,
+        sound,
+        PlayOptions {
+            channel: 1,
+            gain: 0.5,
+            looping: true,
+            ..Default::default()
+        },
+    );
+    *mixer.channel_mut(1) = ChannelParams {
+        gain: 1.0,
+        pan: 1.0,
+        ..Default::default()
+    };
+
+    let mut out = [0.0; 6];
+    mixer.mix(&mut out, 2);
+    assert_eq!(out, [1.0, 1.5, 1.0, 1.5, 1.0, 1.5]);
+    mixer.mix(&mut out, 2);
+    assert_eq!(out, [1.0, 1.5, 0.0, 0.5, 0.0, 0.5]);
+    assert!(!mixer.is_playing(a));
+    assert!(mixer.is_playing(b));
+
+    assert!(mixer.stop(b));
+    mixer.mix(&mut out, 1);
+    assert_eq!(out, [0.0; 6]);
+}
+
+#[test]
+fn test_stream() {
+    use super::stream::{SharedStreamBuffer, StreamBuffer};
+    use crate::utils::{mutex::Mutex, uid::Uid};
+    use std::sync::Arc;
+
+    let buffer: SharedStreamBuffer = Arc::new(Mutex::new(StreamBuffer {
+        frames: vec![(1.0, 1.0); 10].into(),
+        sample_rate: 100,
+        finished: true,
+    }));
+    let mut mixer = Mixer::new(100);
+    let id = Uid::new();
+    mixer.play_stream(id, buffer.clone(), bus::MUSIC, Duration::ZERO);
+
+    let mut out = [0.0; 4];
+    mixer.mix(&mut out, 1);
+    assert_eq!(out, [1.0; 4]);
+    assert_eq!(buffer.lock().frames.len(), 6);
+
+    assert!(mixer.fade_out(id, Duration::from_millis(40)));
+    mixer.mix(&mut out, 1);
+    for (sample, expected) in out.into_iter().zip([0.75, 0.5, 0.25, 0.0]) {
+        assert!((sample - expected).abs() < 1e-4, "{out:?}");
+    }
+    assert!(!mixer.is_playing(id));
+}
+
+#[test]
+fn test_ducking() {
+    use super::SoundData;
+    use crate::utils::uid::Uid;
+
+    let mut mixer = Mixer::new(100);
+    let mut rule = DuckingRule::new(bus::VOICE, bus::MUSIC, 0.25);
+    rule.attack = Duration::ZERO;
+    rule.release = Duration::ZERO;
+    mixer.add_ducking_rule(rule);
+
+    let voice = Uid::new();
+    mixer.play(
+        voice,
+        SoundHandle::new(SoundData::new(vec![0.0; 4], 1, 100).unwrap()),
+        PlayOptions {
+            channel: bus::VOICE,
+            ..Default::default()
+        },
+    );
+    let mut out = [0.0; 2];
+    mixer.mix(&mut out, 1);
+    assert_eq!(mixer.duck_gain(bus::MUSIC), 0.25);
+    assert_eq!(mixer.duck_gain(bus::SFX), 1.0);
+
+    mixer.stop(voice);
+    mixer.mix(&mut out, 1);
+    assert_eq!(mixer.duck_gain(bus::MUSIC), 1.0);
+}
+
+#[test]
+fn test_schedule() {
+    use super::{clock::ScheduleAt, SoundData};
+    use crate::utils::uid::Uid;
+
+    let sound = SoundHandle::new(SoundData::new(vec![1.0; 2], 1, 100).unwrap());
+    let mut mixer = Mixer::new(100);
+    let id = Uid::new();
+    // 50 frames per beat at the default tempo
+    mixer.clock().advance(48);
+    let frame = mixer.clock().resolve(ScheduleAt::NextMultiple(1.0));
+    assert_eq!(frame, 50);
+    mixer.schedule(
+        frame,
+        ScheduledAction::Play(id, sound, PlayOptions::default()),
+    );
+    mixer.schedule(frame + 1, ScheduledAction::SetChannelGain(bus::SFX, 0.5));
+    assert!(!mixer.is_playing(id));
+
+    let mut out = [0.0; 6];
+    mixer.mix(&mut out, 1);
+    assert_eq!(out, [0.0, 0.0, 1.0, 0.5, 0.0, 0.0]);
+    assert_eq!(mixer.clock().frames(), 54);
+    assert_eq!(mixer.scheduled_count(), 0);
+}
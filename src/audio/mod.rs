@@ -0,0 +1,109 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::ensure;
+
+use crate::utils::uid::Uid;
+
+pub mod backend;
+pub mod bus;
+pub mod clock;
+pub mod mixer;
+pub mod spatial;
+pub mod stream;
+
+pub type PlaybackId = Uid;
+pub type ChannelId = u32;
+
+/// Decoded PCM samples, interleaved if there is more than one channel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoundData {
+    pub samples: Vec<f32>,
+    /// 1 (mono) or 2 (stereo)
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+/// Cheaply cloneable reference to sound samples, shared with the mixer.
+#[derive(Clone, Debug)]
+pub struct SoundHandle(Arc<SoundData>);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayOptions {
+    pub channel: ChannelId,
+    pub gain: f32,
+    pub looping: bool,
+    /// positional sound, spatialized relative to the mixer's listener
+    pub emitter: Option<spatial::Emitter>,
+}
+
+impl SoundData {
+    pub fn new(samples: Vec<f32>, channels: u16, sample_rate: u32) -> anyhow::Result<Self> {
+        ensure!(
+            channels == 1 || channels == 2,
+            "unsupported channel count {channels}"
+        );
+        ensure!(sample_rate > 0, "invalid sample rate");
+        ensure!(
+            samples.len() % channels as usize == 0,
+            "sample count is not a multiple of the channel count"
+        );
+        Ok(Self {
+            samples,
+            channels,
+            sample_rate,
+        })
+    }
+
+    /// Mono sine wave, mostly for tests.
+    pub fn sine(frequency: f32, duration: Duration, sample_rate: u32) -> Self {
+        let len = (duration.as_secs_f64() * sample_rate as f64) as usize;
+        let samples = (0..len)
+            .map(|i| (i as f32 / sample_rate as f32 * frequency * std::f32::consts::TAU).sin())
+            .collect();
+        Self {
+            samples,
+            channels: 1,
+            sample_rate,
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames() as f64 / self.sample_rate as f64)
+    }
+
+    /// Stereo frame at `index`, mono sounds are duplicated on both sides.
+    pub fn frame(&self, index: usize) -> (f32, f32) {
+        match self.channels {
+            1 => {
+                let sample = self.samples[index];
+                (sample, sample)
+            }
+            _ => (self.samples[index * 2], self.samples[index * 2 + 1]),
+        }
+    }
+}
+
+impl SoundHandle {
+    pub fn new(data: SoundData) -> Self {
+        Self(Arc::new(data))
+    }
+
+    pub fn data(&self) -> &SoundData {
+        &self.0
+    }
+}
+
+impl Default for PlayOptions {
+    fn default() -> Self {
+        Self {
+            channel: mixer::DEFAULT_CHANNEL,
+            gain: 1.0,
+            looping: false,
+            emitter: None,
+        }
+    }
+}
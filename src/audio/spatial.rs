@@ -0,0 +1,74 @@
+use glam::Vec2;
+
+/// Where a positional sound is heard from, in world units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Listener {
+    pub position: Vec2,
+    /// horizontal offset at which sounds are panned fully to one side
+    pub pan_width: f32,
+}
+
+/// Position and attenuation range of a world-placed sound.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Emitter {
+    pub position: Vec2,
+    /// full volume up to this distance
+    pub min_distance: f32,
+    /// silent past this distance, the gain falls off linearly in between
+    pub max_distance: f32,
+}
+
+impl Default for Listener {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            pan_width: 500.0,
+        }
+    }
+}
+
+impl Emitter {
+    pub fn new(position: Vec2) -> Self {
+        Self {
+            position,
+            min_distance: 50.0,
+            max_distance: 1000.0,
+        }
+    }
+
+    /// Gain and pan of this emitter as heard by `listener`.
+    pub fn spatialize(&self, listener: &Listener) -> (f32, f32) {
+        let offset = self.position - listener.position;
+        let distance = offset.length();
+        let gain = if distance <= self.min_distance {
+            1.0
+        } else if distance >= self.max_distance {
+            0.0
+        } else {
+            1.0 - (distance - self.min_distance) / (self.max_distance - self.min_distance)
+        };
+        let pan = match listener.pan_width {
+            width if width > 0.0 => (offset.x / width).clamp(-1.0, 1.0),
+            _ => 0.0,
+        };
+        (gain, pan)
+    }
+}
+
+#[test]
+fn test() {
+    let listener = Listener {
+        position: Vec2::new(100.0, 0.0),
+        pan_width: 100.0,
+    };
+    let mut emitter = Emitter {
+        position: Vec2::new(100.0, 10.0),
+        min_distance: 20.0,
+        max_distance: 120.0,
+    };
+    assert_eq!(emitter.spatialize(&listener), (1.0, 0.0));
+    emitter.position = Vec2::new(50.0, 0.0);
+    assert_eq!(emitter.spatialize(&listener), (0.7, -0.5));
+    emitter.position = Vec2::new(300.0, 0.0);
+    assert_eq!(emitter.spatialize(&listener), (0.0, 1.0));
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use lewton::inside_ogg::OggStreamReader;
+
+use crate::{
+    exec::task::{Joinable, TaskExecutor, TaskHandle, TryJoinTaskResult},
+    utils::mutex::Mutex,
+};
+
+use super::PlaybackId;
+
+// decoding is requested once less than this is buffered
+const LOW_WATERMARK_SECS: f64 = 1.0;
+const CHUNK_SECS: f64 = 0.25;
+
+/// Stereo frames decoded ahead of the mixer.
+#[derive(Debug, Default)]
+pub struct StreamBuffer {
+    pub frames: VecDeque<(f32, f32)>,
+    /// 0 until the stream is opened
+    pub sample_rate: u32,
+    /// no more frames will be pushed
+    pub finished: bool,
+}
+
+pub type SharedStreamBuffer = Arc<Mutex<StreamBuffer>>;
+
+/// OGG/Vorbis file decoder.
+pub struct Decoder {
+    reader: OggStreamReader<BufReader<File>>,
+    path: PathBuf,
+    looping: bool,
+}
+
+type DecodeTask = TaskHandle<anyhow::Result<(Decoder, Vec<(f32, f32)>, bool)>>;
+
+enum StreamState {
+    Opening(TaskHandle<anyhow::Result<Decoder>>),
+    Idle(Decoder),
+    Decoding(DecodeTask),
+    Finished,
+}
+
+/// Audio server side of a streamed track: keeps the buffer shared with the
+/// mixer filled with chunks decoded on the job system, so that large files
+/// are never decoded on the audio thread.
+pub struct MusicStream {
+    pub id: PlaybackId,
+    pub buffer: SharedStreamBuffer,
+    state: StreamState,
+}
+
+impl Decoder {
+    pub fn open(path: &Path, looping: bool) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("unable to open {}", path.display()))?;
+        let reader = OggStreamReader::new(BufReader::new(file))
+            .with_context(|| format!("unable to read OGG headers of {}", path.display()))?;
+        anyhow::ensure!(
+            reader.ident_hdr.audio_channels > 0,
+            "{} has no audio channel",
+            path.display()
+        );
+        Ok(Self {
+            reader,
+            path: path.to_owned(),
+            looping,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.reader.ident_hdr.audio_sample_rate
+    }
+
+    /// Decodes at least `frames` frames unless the stream ends, returns
+    /// whether it has ended.
+    pub fn decode(&mut self, frames: usize, out: &mut Vec<(f32, f32)>) -> anyhow::Result<bool> {
+        let channels = self.reader.ident_hdr.audio_channels as usize;
+        let target = out.len() + frames;
+        // guards against looping empty files forever
+        let mut rewound = false;
+        while out.len() < target {
+            let packet = self
+                .reader
+                .read_dec_packet_itl()
+                .with_context(|| format!("unable to decode {}", self.path.display()))?;
+            match packet {
+                Some(samples) => {
+                    rewound = false;
+                    out.extend(samples.chunks_exact(channels).map(|frame| {
+                        let left = frame[0] as f32 / 32768.0;
+                        let right = frame.get(1).map(|&s| s as f32 / 32768.0);
+                        (left, right.unwrap_or(left))
+                    }));
+                }
+                None if self.looping && !rewound => {
+                    self.reader
+                        .seek_absgp_pg(0)
+                        .with_context(|| format!("unable to rewind {}", self.path.display()))?;
+                    rewound = true;
+                }
+                None => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl MusicStream {
+    pub fn new(id: PlaybackId, path: PathBuf, looping: bool, executor: &TaskExecutor) -> Self {
+        Self {
+            id,
+            buffer: SharedStreamBuffer::default(),
+            state: StreamState::Opening(executor.spawn(move |_| Decoder::open(&path, looping))),
+        }
+    }
+
+    /// Called every audio server tick, collects decoded chunks and schedules
+    /// the next one when the buffer runs low.
+    pub fn pump(&mut self, executor: &TaskExecutor) -> anyhow::Result<()> {
+        let state = std::mem::replace(&mut self.state, StreamState::Finished);
+        self.state = match self.poll(state) {
+            Ok(StreamState::Idle(decoder)) => self.request_chunk(decoder, executor),
+            Ok(state) => state,
+            Err(e) => {
+                self.buffer.lock().finished = true;
+                return Err(e);
+            }
+        };
+        Ok(())
+    }
+
+    fn poll(&mut self, state: StreamState) -> anyhow::Result<StreamState> {
+        Ok(match state {
+            StreamState::Opening(task) => match task.join.try_join() {
+                TryJoinTaskResult::Joined(decoder) => {
+                    let decoder = decoder?;
+                    self.buffer.lock().sample_rate = decoder.sample_rate();
+                    StreamState::Idle(decoder)
+                }
+                TryJoinTaskResult::NotJoined => StreamState::Opening(task),
+                TryJoinTaskResult::JoinedResultTaken => {
+                    anyhow::bail!("stream decoder task was dropped")
+                }
+            },
+
+            StreamState::Decoding(task) => match task.join.try_join() {
+                TryJoinTaskResult::Joined(result) => {
+                    let (decoder, frames, ended) = result?;
+                    let mut buffer = self.buffer.lock();
+                    buffer.frames.extend(frames);
+                    if ended {
+                        buffer.finished = true;
+                        StreamState::Finished
+                    } else {
+                        StreamState::Idle(decoder)
+                    }
+                }
+                TryJoinTaskResult::NotJoined => StreamState::Decoding(task),
+                TryJoinTaskResult::JoinedResultTaken => {
+                    anyhow::bail!("stream decoder task was dropped")
+                }
+            },
+
+            state => state,
+        })
+    }
+
+    fn request_chunk(&self, mut decoder: Decoder, executor: &TaskExecutor) -> StreamState {
+        let sample_rate = decoder.sample_rate() as f64;
+        if self.buffer.lock().frames.len() as f64 >= sample_rate * LOW_WATERMARK_SECS {
+            return StreamState::Idle(decoder);
+        }
+
+        let frames = (sample_rate * CHUNK_SECS) as usize;
+        StreamState::Decoding(executor.spawn(move |_| {
+            let mut chunk = Vec::with_capacity(frames);
+            let ended = decoder.decode(frames, &mut chunk)?;
+            Ok((decoder, chunk, ended))
+        }))
+    }
+}
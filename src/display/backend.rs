@@ -0,0 +1,190 @@
+use std::{env, num::NonZeroU32};
+
+use anyhow::Context;
+use glutin::{
+    config::{Config, ConfigSurfaceTypes},
+    context::{NotCurrentContext, PossiblyCurrentContext},
+    display::Display,
+    prelude::{
+        GlDisplay, NotCurrentGlContextSurfaceAccessor, PossiblyCurrentContextGlSurfaceAccessor,
+    },
+    surface::{
+        GlSurface, PbufferSurface, Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface,
+    },
+};
+use raw_window_handle::RawWindowHandle;
+use winit::dpi::PhysicalSize;
+
+use crate::utils::args::args;
+
+/// What the draw server renders into, selected with `--gl-backend`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum GLBackend {
+    /// the window surface, nothing is rendered with `--headless`
+    Window,
+    /// a pbuffer, without any window: frames are rendered (and can be
+    /// captured) without showing anything, even with `--headless`
+    Offscreen,
+    /// `offscreen` rendered by Mesa's software rasterizer, for machines
+    /// without a GPU
+    Software,
+}
+
+/// The surface of the draw server, a window or an offscreen pbuffer.
+pub enum DrawSurface {
+    Window(Surface<WindowSurface>),
+    Offscreen(Surface<PbufferSurface>),
+}
+
+impl GLBackend {
+    pub fn is_offscreen(self) -> bool {
+        self != Self::Window
+    }
+
+    /// Surface types the OpenGL config must support.
+    pub fn surface_types(self) -> ConfigSurfaceTypes {
+        if self.is_offscreen() {
+            ConfigSurfaceTypes::WINDOW | ConfigSurfaceTypes::PBUFFER
+        } else {
+            ConfigSurfaceTypes::WINDOW
+        }
+    }
+
+    /// Environment variables making Mesa pick its driver, they have to be
+    /// set before the display is created.
+    fn driver_env(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Software => &[
+                ("LIBGL_ALWAYS_SOFTWARE", "1"),
+                ("GALLIUM_DRIVER", "llvmpipe"),
+            ],
+            Self::Window | Self::Offscreen => &[],
+        }
+    }
+
+    /// Sets the environment variables of this backend, those already set by
+    /// the user are kept. `set_var` races with the threads reading the
+    /// environment, it has to be called before any other thread is started.
+    pub fn set_driver_env(self) {
+        for (name, value) in self.driver_env() {
+            if env::var_os(name).is_none() {
+                env::set_var(name, value);
+            }
+        }
+    }
+}
+
+/// Whether the draw server renders frames: always, except for the window
+/// backend in `--headless` mode.
+pub fn renders_frames() -> bool {
+    !args().headless || args().gl_backend.is_offscreen()
+}
+
+impl DrawSurface {
+    /// Creates the surface of the current `--gl-backend`, `window` is only
+    /// needed by the window backend.
+    ///
+    /// # Safety
+    ///
+    /// `window` must be a valid window of `display`.
+    pub unsafe fn new(
+        display: &Display,
+        config: &Config,
+        window: Option<RawWindowHandle>,
+        size: PhysicalSize<NonZeroU32>,
+    ) -> anyhow::Result<Self> {
+        if args().gl_backend.is_offscreen() {
+            let attributes =
+                SurfaceAttributesBuilder::<PbufferSurface>::new().build(size.width, size.height);
+            display
+                .create_pbuffer_surface(config, &attributes)
+                .map(Self::Offscreen)
+                .context("unable to create pbuffer surface for offscreen rendering")
+        } else {
+            let window = window.context("the window backend needs a window")?;
+            let attributes = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+                window,
+                size.width,
+                size.height,
+            );
+            display
+                .create_window_surface(config, &attributes)
+                .map(Self::Window)
+                .context("unable to create window surface for OpenGL rendering")
+        }
+    }
+
+    pub fn make_current(
+        &self,
+        context: NotCurrentContext,
+    ) -> anyhow::Result<PossiblyCurrentContext> {
+        match self {
+            Self::Window(surface) => context.make_current(surface),
+            Self::Offscreen(surface) => context.make_current(surface),
+        }
+        .context("unable to make OpenGL context current")
+    }
+
+    /// Presents the frame, offscreen frames are only read back.
+    pub fn swap_buffers(&self, context: &PossiblyCurrentContext) -> anyhow::Result<()> {
+        match self {
+            Self::Window(surface) => surface.swap_buffers(context)?,
+            Self::Offscreen(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Offscreen surfaces accept every interval, there is nothing to
+    /// synchronize with.
+    pub fn set_swap_interval(
+        &self,
+        context: &PossiblyCurrentContext,
+        interval: SwapInterval,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Window(surface) => surface.set_swap_interval(context, interval)?,
+            Self::Offscreen(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Pbuffers can't be resized, a new one is created and made current.
+    pub fn resize(
+        &mut self,
+        display: &Display,
+        config: &Config,
+        context: &PossiblyCurrentContext,
+        size: PhysicalSize<NonZeroU32>,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Window(surface) => surface.resize(context, size.width, size.height),
+            Self::Offscreen(surface) => {
+                let attributes = SurfaceAttributesBuilder::<PbufferSurface>::new()
+                    .build(size.width, size.height);
+                let resized = unsafe { display.create_pbuffer_surface(config, &attributes) }
+                    .context("unable to create resized pbuffer surface")?;
+                context
+                    .make_current(&resized)
+                    .context("unable to make OpenGL context current")?;
+                *surface = resized;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test() {
+    assert!(!GLBackend::Window.is_offscreen());
+    assert_eq!(
+        GLBackend::Window.surface_types(),
+        ConfigSurfaceTypes::WINDOW
+    );
+    assert!(GLBackend::Software
+        .surface_types()
+        .contains(ConfigSurfaceTypes::PBUFFER));
+    assert!(GLBackend::Offscreen.driver_env().is_empty());
+    assert!(GLBackend::Software
+        .driver_env()
+        .contains(&("LIBGL_ALWAYS_SOFTWARE", "1")));
+}
@@ -0,0 +1,230 @@
+use std::{
+    ffi::{CStr, CString},
+    fmt::{self, Write},
+    num::NonZeroU32,
+    path::Path,
+};
+
+use anyhow::Context;
+use glutin::{
+    config::{Config, ConfigTemplateBuilder},
+    context::{ContextApi, ContextAttributesBuilder, Version},
+    display::GetGlDisplay,
+    prelude::{GlDisplay, NotCurrentGlContextSurfaceAccessor, PossiblyCurrentGlContext},
+    surface::{SurfaceAttributesBuilder, WindowSurface},
+};
+use glutin_winit::DisplayBuilder;
+use raw_window_handle::HasRawWindowHandle;
+use winit::{
+    dpi::PhysicalSize,
+    event_loop::EventLoopWindowTarget,
+    window::{Window, WindowBuilder},
+};
+
+use crate::{audio::backend::describe_output_devices, utils::args::args};
+
+use super::{surface_format::SurfaceFormat, Display, GLConfigInfo};
+
+/// Probes the monitors, OpenGL configs and contexts, and audio devices, and
+/// writes what was found to `path`. Every step carries on after a failure,
+/// so that the report shows how far display creation gets.
+pub fn write_report<T>(event_loop: &EventLoopWindowTarget<T>, path: &Path) -> anyhow::Result<()> {
+    let mut report = String::new();
+    build_report(&mut report, event_loop).context("unable to format diagnostic report")?;
+    std::fs::write(path, report)
+        .with_context(|| format!("unable to write diagnostic report to {}", path.display()))?;
+    tracing::info!("diagnostic report written to {}", path.display());
+    Ok(())
+}
+
+fn build_report<T>(report: &mut String, event_loop: &EventLoopWindowTarget<T>) -> fmt::Result {
+    writeln!(report, "== System ==")?;
+    writeln!(
+        report,
+        "{} {} on {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(
+        report,
+        "surface format: {:?}, config index: {:?}, sRGB disabled: {}",
+        args().surface_format,
+        args().gl_config_index,
+        args().gl_disable_srgb
+    )?;
+
+    writeln!(report, "\n== Monitors ==")?;
+    describe_monitors(report, event_loop)?;
+
+    let window_builder = WindowBuilder::new()
+        .with_inner_size(PhysicalSize::new(64, 64))
+        .with_title("diagnose")
+        .with_visible(false);
+    let mut chosen = None;
+    for float_pixels in [false, true] {
+        writeln!(
+            report,
+            "\n== OpenGL configs (float pixels: {float_pixels}) =="
+        )?;
+        let mut infos = Vec::new();
+        let format = if float_pixels {
+            SurfaceFormat::ScRgb
+        } else {
+            args().surface_format
+        };
+        let result = DisplayBuilder::new()
+            .with_window_builder(Some(window_builder.clone()))
+            .build(
+                event_loop,
+                ConfigTemplateBuilder::new().with_float_pixels(float_pixels),
+                |configs| {
+                    let configs: Vec<Config> = configs.collect();
+                    infos.extend(configs.iter().map(GLConfigInfo::new));
+                    Display::choose_config(Box::new(configs.into_iter()), format)
+                },
+            );
+        for (index, info) in infos.iter().enumerate() {
+            writeln!(report, "[{index}] {info:?}")?;
+        }
+        match result {
+            Ok((window, config)) => {
+                writeln!(report, "chosen: {:?}", GLConfigInfo::new(&config))?;
+                if chosen.is_none() {
+                    chosen = window.map(|window| (window, config));
+                }
+            }
+            Err(e) => writeln!(report, "unable to find a config: {e}")?,
+        }
+    }
+
+    writeln!(report, "\n== OpenGL contexts ==")?;
+    match &chosen {
+        Some((window, config)) => describe_contexts(report, window, config)?,
+        None => writeln!(report, "no config to create contexts with")?,
+    }
+
+    writeln!(report, "\n== Audio devices ==")?;
+    describe_output_devices(report)
+}
+
+fn describe_monitors<T>(report: &mut String, event_loop: &EventLoopWindowTarget<T>) -> fmt::Result {
+    let primary = event_loop.primary_monitor();
+    for monitor in event_loop.available_monitors() {
+        let name = monitor.name().unwrap_or_else(|| "unknown monitor".into());
+        let primary = if primary.as_ref() == Some(&monitor) {
+            " (primary)"
+        } else {
+            ""
+        };
+        writeln!(
+            report,
+            "{name}{primary}: {:?} at {:?}, scale factor {}, {:?}mHz",
+            monitor.size(),
+            monitor.position(),
+            monitor.scale_factor(),
+            monitor.refresh_rate_millihertz()
+        )?;
+        for mode in monitor.video_modes() {
+            writeln!(
+                report,
+                "  {}x{} {}bpp {}mHz",
+                mode.size().width,
+                mode.size().height,
+                mode.bit_depth(),
+                mode.refresh_rate_millihertz()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn describe_contexts(report: &mut String, window: &Window, config: &Config) -> fmt::Result {
+    let gl_display = config.display();
+    let size = window.inner_size();
+    let surface = unsafe {
+        gl_display.create_window_surface(
+            config,
+            &SurfaceAttributesBuilder::<WindowSurface>::new().build(
+                window.raw_window_handle(),
+                NonZeroU32::new(size.width.max(1)).unwrap(),
+                NonZeroU32::new(size.height.max(1)).unwrap(),
+            ),
+        )
+    };
+    let surface = match surface {
+        Ok(surface) => Some(surface),
+        Err(e) => {
+            writeln!(report, "unable to create window surface: {e}")?;
+            None
+        }
+    };
+    // the first one is what the draw server asks for
+    let apis = [
+        ContextApi::Gles(None),
+        ContextApi::Gles(Some(Version::new(3, 2))),
+        ContextApi::Gles(Some(Version::new(3, 0))),
+        ContextApi::Gles(Some(Version::new(2, 0))),
+        ContextApi::OpenGl(None),
+        ContextApi::OpenGl(Some(Version::new(4, 6))),
+        ContextApi::OpenGl(Some(Version::new(3, 3))),
+    ];
+    let mut loaded = false;
+    for api in apis {
+        let attribs = ContextAttributesBuilder::new()
+            .with_context_api(api)
+            .build(Some(window.raw_window_handle()));
+        let context = match unsafe { gl_display.create_context(config, &attribs) } {
+            Ok(context) => context,
+            Err(e) => {
+                writeln!(report, "{api:?}: failed: {e}")?;
+                continue;
+            }
+        };
+        let surface = match &surface {
+            Some(surface) => surface,
+            None => {
+                writeln!(report, "{api:?}: created")?;
+                continue;
+            }
+        };
+        let context = match context.make_current(surface) {
+            Ok(context) => context,
+            Err(e) => {
+                writeln!(report, "{api:?}: created, unable to make current: {e}")?;
+                continue;
+            }
+        };
+        if !loaded {
+            gl::load_with(|symbol| {
+                let symbol = CString::new(symbol).unwrap();
+                gl_display.get_proc_address(symbol.as_c_str()).cast()
+            });
+            loaded = true;
+        }
+        writeln!(
+            report,
+            "{api:?}: {} ({} {}), GLSL {}",
+            gl_string(gl::VERSION),
+            gl_string(gl::VENDOR),
+            gl_string(gl::RENDERER),
+            gl_string(gl::SHADING_LANGUAGE_VERSION)
+        )?;
+        if let Err(e) = context.make_not_current() {
+            writeln!(report, "{api:?}: unable to make not current: {e}")?;
+        }
+    }
+    Ok(())
+}
+
+fn gl_string(name: gl::types::GLenum) -> String {
+    let string = unsafe { gl::GetString(name) };
+    if string.is_null() {
+        "unknown".into()
+    } else {
+        unsafe { CStr::from_ptr(string.cast()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
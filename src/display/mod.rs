@@ -14,13 +14,32 @@ use winit::{
     window::{Window, WindowBuilder, WindowId},
 };
 
-use crate::utils::args::args;
+use crate::utils::{args::args, mutex::Mutex};
+
+use self::surface_format::SurfaceFormat;
+
+pub mod backend;
+pub mod diagnose;
+pub mod platform;
+pub mod surface_format;
 
 pub struct Display {
-    window: Window,
+    window: DisplayWindow,
+    surface_format: SurfaceFormat,
+}
+
+/// The offscreen backends don't create a window, the display only keeps
+/// the size the frames are rendered at, changed by synthetic resizes.
+enum DisplayWindow {
+    Window(Window),
+    Windowless {
+        display_handle: RawDisplayHandle,
+        size: Mutex<PhysicalSize<u32>>,
+    },
 }
 
-pub struct SendRawHandle(pub RawWindowHandle, pub RawDisplayHandle);
+/// The window handle is `None` for windowless displays.
+pub struct SendRawHandle(pub Option<RawWindowHandle>, pub RawDisplayHandle);
 unsafe impl Send for SendRawHandle {}
 
 #[derive(Debug)]
@@ -56,7 +75,10 @@ impl GLConfigInfo {
 }
 
 impl Display {
-    fn choose_config<'a>(config: Box<dyn Iterator<Item = Config> + 'a>) -> Config {
+    fn choose_config<'a>(
+        config: Box<dyn Iterator<Item = Config> + 'a>,
+        preferred_format: SurfaceFormat,
+    ) -> Config {
         let mut config: VecDeque<Config> = config.collect();
         let x: Vec<GLConfigInfo> = config.iter().map(GLConfigInfo::new).collect();
         tracing::trace!("Available OpenGL configs: {:#?}", x);
@@ -72,6 +94,10 @@ impl Display {
                 .enumerate()
                 .max_by_key(|(_, config)| {
                     let mut score: i32 = 0;
+                    // deeper formats are only picked when asked for
+                    if SurfaceFormat::of_config(config) == Some(preferred_format) {
+                        score += 1000;
+                    }
                     if config.srgb_capable() {
                         if args().gl_disable_srgb {
                             return i32::MIN;
@@ -80,6 +106,8 @@ impl Display {
                     }
 
                     score += config.num_samples() as i32;
+                    // rotated clip rects are stenciled
+                    score += config.stencil_size().min(8) as i32;
                     score += config.alpha_size() as i32;
                     match config.color_buffer_type() {
                         Some(ColorBufferType::Luminance(bit)) => {
@@ -122,45 +150,114 @@ impl Display {
             .with_title(title)
             .with_visible(!args().headless);
         tracing::trace!("WindowBuilder structure: {:?}", window_builder);
-        let (window, gl_config) = DisplayBuilder::new()
-            .with_window_builder(Some(window_builder))
-            .build(event_loop, ConfigTemplateBuilder::new(), |config| {
-                Self::choose_config(config)
-            })
-            .map_err(|e| anyhow::format_err!("{}", e))?;
+
+        let requested = args().surface_format;
+        // float and fixed point configs can't be queried together, so a
+        // float format falls back to a second query, fixed point formats
+        // fall back to each other through the config score
+        let mut attempts = vec![requested];
+        if requested.is_float() {
+            attempts.push(SurfaceFormat::Rgb10A2);
+        }
+        let mut result = Err(anyhow::format_err!("no surface format to try"));
+        for format in attempts {
+            result = DisplayBuilder::new()
+                .with_window_builder(
+                    (!args().gl_backend.is_offscreen()).then(|| window_builder.clone()),
+                )
+                .build(
+                    event_loop,
+                    ConfigTemplateBuilder::new()
+                        .with_float_pixels(format.is_float())
+                        .with_surface_type(args().gl_backend.surface_types()),
+                    |config| Self::choose_config(config, format),
+                )
+                .map_err(|e| anyhow::format_err!("{}", e));
+            match &result {
+                Ok(_) => break,
+                Err(e) => tracing::warn!("no OpenGL config for surface format {format:?}: {e}"),
+            }
+        }
+        let (window, gl_config) = result?;
+
+        let surface_format = SurfaceFormat::of_config(&gl_config).unwrap_or(SurfaceFormat::Rgba8);
+        if surface_format != requested {
+            tracing::warn!("surface format {requested:?} unavailable, using {surface_format:?}");
+        } else {
+            tracing::info!("using surface format {surface_format:?}");
+        }
+        let window = match window {
+            Some(window) => DisplayWindow::Window(window),
+            None if args().gl_backend.is_offscreen() => DisplayWindow::Windowless {
+                display_handle: event_loop.raw_display_handle(),
+                size: Mutex::new(size),
+            },
+            None => anyhow::bail!("no window was created"),
+        };
         Ok((
             Display {
-                window: window.unwrap(),
+                window,
+                surface_format,
             },
             gl_config,
         ))
     }
 
-    pub fn get_raw_window_handle(&self) -> RawWindowHandle {
-        self.window.raw_window_handle()
+    pub fn get_raw_window_handle(&self) -> Option<RawWindowHandle> {
+        self.get_winit_window().map(Window::raw_window_handle)
     }
 
     pub fn get_raw_display_handle(&self) -> RawDisplayHandle {
-        self.window.raw_display_handle()
+        match &self.window {
+            DisplayWindow::Window(window) => window.raw_display_handle(),
+            DisplayWindow::Windowless { display_handle, .. } => *display_handle,
+        }
     }
 
     pub fn get_raw_handles(&self) -> SendRawHandle {
         SendRawHandle(self.get_raw_window_handle(), self.get_raw_display_handle())
     }
 
+    /// The id of the synthetic window events for windowless displays.
     pub fn get_window_id(&self) -> WindowId {
-        self.window.id()
+        match &self.window {
+            DisplayWindow::Window(window) => window.id(),
+            // SAFETY: only compared with the ids of the window events, there
+            // is no other window to be mistaken for
+            DisplayWindow::Windowless { .. } => unsafe { WindowId::dummy() },
+        }
     }
 
     pub fn get_size(&self) -> PhysicalSize<u32> {
-        self.window.inner_size()
+        match &self.window {
+            DisplayWindow::Window(window) => window.inner_size(),
+            DisplayWindow::Windowless { size, .. } => *size.lock(),
+        }
     }
 
     pub fn get_scale_factor(&self) -> f64 {
-        self.window.scale_factor()
+        match &self.window {
+            DisplayWindow::Window(window) => window.scale_factor(),
+            DisplayWindow::Windowless { .. } => 1.0,
+        }
     }
 
-    pub fn get_winit_window(&self) -> &Window {
-        &self.window
+    /// Called on every `Resized` event, windowless displays take its size.
+    pub fn handle_resize(&self, new_size: PhysicalSize<u32>) {
+        if let DisplayWindow::Windowless { size, .. } = &self.window {
+            *size.lock() = new_size;
+        }
+    }
+
+    pub fn get_surface_format(&self) -> SurfaceFormat {
+        self.surface_format
+    }
+
+    /// `None` with the offscreen backends.
+    pub fn get_winit_window(&self) -> Option<&Window> {
+        match &self.window {
+            DisplayWindow::Window(window) => Some(window),
+            DisplayWindow::Windowless { .. } => None,
+        }
     }
 }
@@ -0,0 +1,245 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use winit::window::Theme;
+
+/// Published on the `EventBus` when the window theme changed.
+#[derive(Clone, Debug)]
+pub struct ThemeChanged {
+    /// `None` if no theme was reported before
+    pub old: Option<Theme>,
+    pub new: Theme,
+}
+
+/// Published on the `EventBus` when the machine switched between AC and
+/// battery power or the battery level changed.
+#[derive(Clone, Debug)]
+pub struct PowerChanged {
+    pub old: PowerState,
+    pub new: PowerState,
+}
+
+/// Published on the `EventBus` when the user locale changed.
+#[derive(Clone, Debug)]
+pub struct LocaleChanged {
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    /// no power supply information, e.g. desktops or unsupported platforms
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PowerState {
+    pub source: PowerSource,
+    /// charge of the battery in `[0, 1]`, if there is one
+    pub battery: Option<f32>,
+}
+
+/// Tracks the platform state that winit doesn't report as window events,
+/// polled by the main thread.
+pub struct PlatformWatcher {
+    theme: Option<Theme>,
+    power: PowerState,
+    locale: String,
+}
+
+impl Default for PowerState {
+    fn default() -> Self {
+        Self {
+            source: PowerSource::Unknown,
+            battery: None,
+        }
+    }
+}
+
+impl PowerState {
+    /// Reads the power supplies exposed by the OS.
+    pub fn query() -> Self {
+        if cfg!(target_os = "linux") {
+            Self::from_supplies(Path::new("/sys/class/power_supply"))
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Parses a sysfs `power_supply` class directory.
+    fn from_supplies(dir: &Path) -> Self {
+        let read = |path: PathBuf| {
+            fs::read_to_string(path)
+                .map(|s| s.trim().to_owned())
+                .unwrap_or_default()
+        };
+        let mut state = Self::default();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return state,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match read(path.join("type")).as_str() {
+                "Mains" if read(path.join("online")) == "1" => state.source = PowerSource::Ac,
+                "Mains" if state.source == PowerSource::Unknown => {
+                    state.source = PowerSource::Battery
+                }
+                "Battery" => {
+                    state.battery = read(path.join("capacity"))
+                        .parse::<f32>()
+                        .ok()
+                        .map(|capacity| (capacity / 100.0).clamp(0.0, 1.0));
+                    if read(path.join("status")) == "Discharging" {
+                        state.source = PowerSource::Battery;
+                    }
+                }
+                _ => {}
+            }
+        }
+        state
+    }
+
+    pub fn on_battery(&self) -> bool {
+        self.source == PowerSource::Battery
+    }
+}
+
+impl PlatformWatcher {
+    pub fn new() -> Self {
+        Self {
+            theme: None,
+            power: PowerState::query(),
+            locale: current_locale(),
+        }
+    }
+
+    pub fn power(&self) -> PowerState {
+        self.power
+    }
+
+    /// Called with the themes reported by the window, `None` if the theme
+    /// didn't actually change.
+    pub fn set_theme(&mut self, theme: Theme) -> Option<ThemeChanged> {
+        if self.theme == Some(theme) {
+            return None;
+        }
+        let old = self.theme.replace(theme);
+        Some(ThemeChanged { old, new: theme })
+    }
+
+    pub fn poll_power(&mut self) -> Option<PowerChanged> {
+        let power = PowerState::query();
+        if power == self.power {
+            return None;
+        }
+        let old = std::mem::replace(&mut self.power, power);
+        Some(PowerChanged { old, new: power })
+    }
+
+    pub fn poll_locale(&mut self) -> Option<LocaleChanged> {
+        let locale = current_locale();
+        if locale == self.locale {
+            return None;
+        }
+        let old = std::mem::replace(&mut self.locale, locale.clone());
+        Some(LocaleChanged { old, new: locale })
+    }
+}
+
+impl Default for PlatformWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Locale of the user, e.g. `en_US.UTF-8`.
+///
+/// The environment of a running process never changes, so the locale
+/// files written by the desktop settings are read first on Linux.
+pub fn current_locale() -> String {
+    let mut files = Vec::new();
+    if cfg!(target_os = "linux") {
+        if let Some(config) = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        {
+            files.push(config.join("locale.conf"));
+        }
+        files.push(PathBuf::from("/etc/locale.conf"));
+    }
+    files
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .find_map(|content| parse_locale_conf(&content))
+        .or_else(|| {
+            ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .into_iter()
+                .filter_map(|name| std::env::var(name).ok())
+                .find(|value| !value.is_empty())
+        })
+        .unwrap_or_else(|| "C".to_owned())
+}
+
+/// `LC_MESSAGES`, or `LANG` if it isn't set, of a `locale.conf` file.
+fn parse_locale_conf(content: &str) -> Option<String> {
+    let value = |name: &str| {
+        content.lines().find_map(|line| {
+            line.trim()
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('='))
+                .map(|value| value.trim_matches('"').to_owned())
+                .filter(|value| !value.is_empty())
+        })
+    };
+    value("LC_MESSAGES").or_else(|| value("LANG"))
+}
+
+#[test]
+fn test() {
+    assert_eq!(
+        parse_locale_conf("# comment\nLANG=\"fr_FR.UTF-8\"\n"),
+        Some("fr_FR.UTF-8".to_owned())
+    );
+    assert_eq!(
+        parse_locale_conf("LANG=en_US.UTF-8\nLC_MESSAGES=de_DE.UTF-8\n"),
+        Some("de_DE.UTF-8".to_owned())
+    );
+    assert_eq!(parse_locale_conf("LC_TIME=en_GB.UTF-8\n"), None);
+
+    let dir = std::env::temp_dir().join(format!("power-supply-{}", std::process::id()));
+    let supply = |name: &str, files: &[(&str, &str)]| {
+        let path = dir.join(name);
+        fs::create_dir_all(&path).unwrap();
+        for (file, content) in files {
+            fs::write(path.join(file), format!("{content}\n")).unwrap();
+        }
+    };
+    assert_eq!(PowerState::from_supplies(&dir), PowerState::default());
+    supply("AC", &[("type", "Mains"), ("online", "0")]);
+    supply(
+        "BAT0",
+        &[
+            ("type", "Battery"),
+            ("capacity", "42"),
+            ("status", "Discharging"),
+        ],
+    );
+    let state = PowerState::from_supplies(&dir);
+    assert!(state.on_battery());
+    assert_eq!(state.battery, Some(0.42));
+    supply("AC", &[("online", "1")]);
+    supply("BAT0", &[("status", "Charging")]);
+    assert_eq!(PowerState::from_supplies(&dir).source, PowerSource::Ac);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let mut watcher = PlatformWatcher::new();
+    assert!(watcher.set_theme(Theme::Dark).is_some());
+    assert!(watcher.set_theme(Theme::Dark).is_none());
+    let change = watcher.set_theme(Theme::Light).unwrap();
+    assert_eq!((change.old, change.new), (Some(Theme::Dark), Theme::Light));
+}
@@ -0,0 +1,56 @@
+use gl::types::{GLenum, GLint};
+use glutin::{
+    config::{ColorBufferType, Config},
+    prelude::GlConfig,
+};
+
+/// Color format of the window surface, negotiated with `--surface-format`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum SurfaceFormat {
+    /// 8 bits per channel, SDR
+    #[value(name = "8bit")]
+    Rgba8,
+    /// 10 bits per color channel, 2 bits of alpha
+    #[value(name = "10bit")]
+    Rgb10A2,
+    /// 16-bit floats in linear extended sRGB, HDR
+    #[value(name = "scrgb")]
+    ScRgb,
+}
+
+impl SurfaceFormat {
+    /// `None` for luminance-only or unknown configs.
+    pub fn of_config(config: &Config) -> Option<Self> {
+        match config.color_buffer_type() {
+            Some(ColorBufferType::Rgb { r_size, .. }) if config.float_pixels() && r_size >= 16 => {
+                Some(Self::ScRgb)
+            }
+            Some(ColorBufferType::Rgb { r_size, .. }) if r_size >= 10 => Some(Self::Rgb10A2),
+            Some(ColorBufferType::Rgb { .. }) => Some(Self::Rgba8),
+            _ => None,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        self == Self::ScRgb
+    }
+
+    /// Internal format and pixel type of offscreen color targets, chosen so
+    /// that passes rendering into them don't lose the surface precision.
+    pub fn color_target_format(self, srgb: bool) -> (GLint, GLenum) {
+        match self {
+            Self::Rgba8 if srgb => (gl::SRGB8_ALPHA8 as GLint, gl::UNSIGNED_BYTE),
+            Self::Rgba8 => (gl::RGBA8 as GLint, gl::UNSIGNED_BYTE),
+            Self::Rgb10A2 => (gl::RGB10_A2 as GLint, gl::UNSIGNED_INT_2_10_10_10_REV),
+            Self::ScRgb => (gl::RGBA16F as GLint, gl::HALF_FLOAT),
+        }
+    }
+
+    /// Size of a pixel of the offscreen color targets.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgba8 | Self::Rgb10A2 => 4,
+            Self::ScRgb => 8,
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::Display;
+
+/// Part of the program an error originated from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    Main,
+    Draw,
+    Update,
+    Audio,
+    Network,
+    Content,
+    Test,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// something went wrong but the program can carry on as usual
+    Warning,
+    Error,
+    /// the program can't carry on, a crash report is written before exiting
+    Fatal,
+}
+
+/// Error reported to the main thread via `GameUserEvent::Error`.
+#[derive(Debug)]
+pub struct GameError {
+    pub subsystem: Subsystem,
+    pub severity: Severity,
+    /// stable identifier of the failing operation, e.g. `draw.create_handle`
+    pub code: &'static str,
+    pub error: anyhow::Error,
+}
+
+/// Owned, cloneable description of a `GameError`, published on the
+/// `EventBus` for the scenes interested in errors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorReport {
+    pub subsystem: Subsystem,
+    pub severity: Severity,
+    pub code: &'static str,
+    /// the error followed by its sources, outermost first
+    pub chain: Vec<String>,
+}
+
+impl GameError {
+    pub fn new(subsystem: Subsystem, code: &'static str, error: anyhow::Error) -> Self {
+        Self {
+            subsystem,
+            severity: Severity::Error,
+            code,
+            error,
+        }
+    }
+
+    pub fn severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            subsystem: self.subsystem,
+            severity: self.severity,
+            code: self.code,
+            chain: self.error.chain().map(|e| e.to_string()).collect(),
+        }
+    }
+}
+
+impl Display for GameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{:?}/{:?}] {}: {:#}",
+            self.subsystem, self.severity, self.code, self.error
+        )
+    }
+}
+
+impl Display for ErrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{:?}/{:?}] {}",
+            self.subsystem, self.severity, self.code
+        )?;
+        for (i, message) in self.chain.iter().enumerate() {
+            if i == 0 {
+                write!(f, ": {message}")?;
+            } else {
+                write!(f, "\n    caused by: {message}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test() {
+    use anyhow::Context;
+
+    let error = Err::<(), _>(anyhow::anyhow!("file not found"))
+        .context("unable to load shader")
+        .unwrap_err();
+    let report = GameError::new(Subsystem::Draw, "draw.create_program", error)
+        .severity(Severity::Fatal)
+        .report();
+    assert_eq!(report.chain, ["unable to load shader", "file not found"]);
+    assert_eq!(
+        report.to_string(),
+        "[Draw/Fatal] draw.create_program: unable to load shader\n    caused by: file not found"
+    );
+}
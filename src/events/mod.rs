@@ -1,16 +1,21 @@
 use std::num::NonZeroU32;
 
 use derivative::Derivative;
-use glutin::surface::SwapInterval;
 use trait_set::trait_set;
 use winit::dpi::PhysicalSize;
 
 use crate::{
-    exec::{dispatch::DispatchMsg, main_ctx::MainContext},
+    exec::{dispatch::DispatchMsg, main_ctx::MainContext, query::QueryWaker},
+    graphics::present::PresentModeReport,
     scene::main::RootScene,
     ui::utils::geom::UISize,
+    utils::uid::Uid,
 };
 
+use self::error::{GameError, Subsystem};
+
+pub mod error;
+
 pub type GameEvent<'a> = winit::event::Event<'a, GameUserEvent>;
 
 trait_set! {
@@ -23,14 +28,35 @@ pub enum GameUserEvent {
     Exit(i32),
     Dispatch(DispatchMsg),
     Execute(#[derivative(Debug = "ignore")] Box<dyn ExecuteCallback>),
-    VSyncSet(Option<SwapInterval>),
+    /// effective present mode after a change requested to the draw server
+    VSyncSet(PresentModeReport),
     ExecuteReturn(ExecuteReturnEvent),
-    Error(anyhow::Error),
+    Error(GameError),
     CheckedResize {
         display_size: PhysicalSize<NonZeroU32>,
         ui_size: UISize,
     },
 }
 
-#[derive(Debug)]
-pub enum ExecuteReturnEvent {}
+impl GameUserEvent {
+    pub fn error(subsystem: Subsystem, code: &'static str, error: anyhow::Error) -> Self {
+        Self::Error(GameError::new(subsystem, code, error))
+    }
+}
+
+/// Signals that the server responded to the `ServerQuery` with the id `id`.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ExecuteReturnEvent {
+    pub id: Uid,
+    #[derivative(Debug = "ignore")]
+    pub waker: QueryWaker,
+}
+
+impl ExecuteReturnEvent {
+    pub fn wake(self) {
+        if let Some(waker) = self.waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
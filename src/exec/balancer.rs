@@ -0,0 +1,211 @@
+use std::time::{Duration, Instant};
+
+use super::{runner::RunnerId, server::ServerKind, stats::RunnerStats, NUM_GAME_LOOPS};
+
+#[derive(Clone, Debug)]
+pub struct BalancerConfig {
+    /// runners with a load above this are considered overloaded
+    pub overload_threshold: f64,
+    /// runners with a load below this can receive servers
+    pub idle_threshold: f64,
+    pub check_interval: Duration,
+    /// number of consecutive overloaded checks before a migration
+    pub overloaded_checks: u32,
+    /// minimum time between two migrations
+    pub cooldown: Duration,
+}
+
+impl Default for BalancerConfig {
+    fn default() -> Self {
+        Self {
+            overload_threshold: 0.85,
+            idle_threshold: 0.4,
+            check_interval: Duration::from_millis(500),
+            overloaded_checks: 4,
+            cooldown: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Migration {
+    pub kind: ServerKind,
+    pub from: RunnerId,
+    pub to: RunnerId,
+}
+
+/// Decides when to migrate servers between runners based on the runner
+/// stats.
+///
+/// A runner has to stay overloaded for several checks in a row, the
+/// destination has to be idle enough to take the server without becoming
+/// overloaded itself, and migrations are rate limited, so that servers don't
+/// ping-pong between runners.
+pub struct Balancer {
+    config: BalancerConfig,
+    last_check: Instant,
+    last_migration: Option<Instant>,
+    overload_streaks: [u32; NUM_GAME_LOOPS + 1],
+}
+
+impl Balancer {
+    pub fn new(config: BalancerConfig) -> Self {
+        Self {
+            config,
+            last_check: Instant::now(),
+            last_migration: None,
+            overload_streaks: Default::default(),
+        }
+    }
+
+    /// `runners` contains the stats of every constructed runner.
+    pub fn plan(&mut self, runners: &[(RunnerId, RunnerStats)]) -> Option<Migration> {
+        self.plan_at(Instant::now(), runners)
+    }
+
+    fn plan_at(&mut self, now: Instant, runners: &[(RunnerId, RunnerStats)]) -> Option<Migration> {
+        if now.duration_since(self.last_check) < self.config.check_interval {
+            return None;
+        }
+        self.last_check = now;
+
+        for (id, stats) in runners {
+            let streak = &mut self.overload_streaks[usize::from(*id)];
+            if stats.load > self.config.overload_threshold && stats.num_servers() > 1 {
+                *streak += 1;
+            } else {
+                *streak = 0;
+            }
+        }
+
+        let cooling_down = self
+            .last_migration
+            .map(|last| now.duration_since(last) < self.config.cooldown)
+            .unwrap_or_default();
+        if cooling_down {
+            return None;
+        }
+
+        let (from, from_stats) = runners
+            .iter()
+            .filter(|(id, _)| {
+                self.overload_streaks[usize::from(*id)] >= self.config.overloaded_checks
+            })
+            .max_by(|(_, a), (_, b)| a.load.total_cmp(&b.load))?;
+
+        let (kind, cost) = ServerKind::ALL
+            .into_iter()
+            .filter_map(|kind| from_stats.server_cost(kind).map(|cost| (kind, cost)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+        let (to, _) = runners
+            .iter()
+            .filter(|(id, _)| id != from)
+            .filter(|(_, stats)| stats.load < self.config.idle_threshold)
+            .filter(|(_, stats)| {
+                // runners without a target frequency tick as often as they
+                // can, estimate their new load with the source frequency
+                let frequency = if stats.frequency > 0.0 {
+                    stats.frequency
+                } else {
+                    from_stats.frequency
+                };
+                stats.load + cost * frequency < self.config.overload_threshold
+            })
+            .min_by(|(_, a), (_, b)| a.load.total_cmp(&b.load))?;
+
+        self.last_migration = Some(now);
+        self.overload_streaks = Default::default();
+        Some(Migration {
+            kind,
+            from: *from,
+            to: *to,
+        })
+    }
+}
+
+#[test]
+fn test() {
+    fn runner(load: f64, costs: &[(ServerKind, f64)]) -> RunnerStats {
+        let mut stats = RunnerStats::default();
+        stats.frequency = 1000.0;
+        stats.load = load;
+        for &(kind, cost) in costs {
+            stats.server_costs[kind as usize] = Some(cost);
+        }
+        stats
+    }
+
+    let config = BalancerConfig::default();
+    // the first check comes more than an interval after the creation of
+    // every balancer below
+    let start = Instant::now() + config.check_interval / 2;
+    let check = |i: u32| start + config.check_interval * i;
+    let overloaded = runner(
+        0.95,
+        &[(ServerKind::Audio, 0.0001), (ServerKind::Update, 0.0005)],
+    );
+    let runners = [
+        (0, overloaded.clone()),
+        (1, runner(0.1, &[(ServerKind::Draw, 0.0001)])),
+    ];
+
+    let mut balancer = Balancer::new(config.clone());
+    for i in 1..config.overloaded_checks {
+        assert_eq!(balancer.plan_at(check(i), &runners), None, "check {i}");
+    }
+    // checks closer than the interval don't count
+    let last = check(config.overloaded_checks);
+    assert_eq!(
+        balancer.plan_at(check(config.overloaded_checks - 1), &runners),
+        None
+    );
+    // the heaviest server moves to the idle runner
+    assert_eq!(
+        balancer.plan_at(last, &runners),
+        Some(Migration {
+            kind: ServerKind::Update,
+            from: 0,
+            to: 1,
+        })
+    );
+    // no migration until the cooldown is over, even if still overloaded
+    for i in 1..=config.overloaded_checks {
+        assert_eq!(
+            balancer.plan_at(last + config.check_interval * i, &runners),
+            None
+        );
+    }
+    let after_cooldown = last + config.cooldown;
+    assert!(balancer.plan_at(after_cooldown, &runners).is_some());
+
+    let plan = |runners: &[(RunnerId, RunnerStats)]| {
+        let mut balancer = Balancer::new(config.clone());
+        (1..=config.overloaded_checks)
+            .map(|i| balancer.plan_at(check(i), runners))
+            .last()
+            .flatten()
+    };
+    // a runner hosting a single server has nothing to move
+    let single = runner(0.95, &[(ServerKind::Update, 0.0009)]);
+    assert_eq!(plan(&[(0, single), (1, runner(0.1, &[]))]), None);
+    // the destination isn't idle
+    assert_eq!(
+        plan(&[(0, overloaded.clone()), (1, runner(0.5, &[]))]),
+        None
+    );
+    // the destination would be overloaded by the server
+    assert_eq!(
+        plan(&[(0, overloaded.clone()), (1, runner(0.39, &[]))]),
+        None
+    );
+    // an overload streak is broken by a single check under the threshold
+    let mut balancer = Balancer::new(config.clone());
+    let mut recovered = overloaded.clone();
+    recovered.load = 0.5;
+    let idle = [(0, recovered), runners[1].clone()];
+    for i in 1..=config.overloaded_checks {
+        let runners = if i == 2 { &idle } else { &runners };
+        assert_eq!(balancer.plan_at(check(i), runners), None, "check {i}");
+    }
+}
@@ -1,18 +1,57 @@
-use std::collections::HashMap;
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    mem,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
 
 use trait_set::trait_set;
 
-use crate::{scene::main::RootScene, utils::uid::Uid};
+use crate::{
+    scene::{
+        lifetime::{SceneLifetime, SceneWeak},
+        main::RootScene,
+    },
+    ui::Widget,
+    utils::{
+        alloc::{self, AllocTag},
+        error::ResultExt,
+        uid::Uid,
+    },
+};
 
-use super::main_ctx::MainContext;
+use super::{
+    event_bus::TopicPayload,
+    main_ctx::MainContext,
+    task::{Cancellable, CancellationToken},
+};
 
 trait_set! {
     pub trait EventDispatch = FnOnce(&mut MainContext, &mut RootScene) -> anyhow::Result<()>;
+    pub trait IntervalDispatch = FnMut(&mut MainContext, &mut RootScene) -> anyhow::Result<()>;
+    pub trait FrameDispatch = FnMut(&mut MainContext, &mut RootScene, Duration) -> anyhow::Result<()>;
+}
+
+/// What a dispatch belongs to, the dispatch is dropped along with everything
+/// its callback captured once the owner is gone.
+#[derive(Clone)]
+pub enum DispatchOwner {
+    /// gone once the scene is removed
+    Scene(SceneLifetime),
+    /// gone once the widget is dropped or its scene is removed
+    Widget(SceneWeak<dyn Widget>),
+}
+
+struct Dispatch {
+    callback: Box<dyn EventDispatch>,
+    owner: Option<DispatchOwner>,
 }
 
 #[derive(Default)]
 pub struct DispatchList {
-    dispatches: HashMap<Uid, Box<dyn EventDispatch>>,
+    dispatches: HashMap<Uid, Dispatch>,
 }
 
 impl DispatchList {
@@ -24,24 +63,145 @@ impl DispatchList {
     where
         F: EventDispatch + 'static,
     {
-        self.push_boxed(Box::new(callback))
+        self.push_scoped(None, callback)
+    }
+
+    /// Like `push`, but the dispatch is discarded if `owner` is gone
+    /// before it gets executed.
+    pub fn push_scoped<F>(&mut self, owner: Option<DispatchOwner>, callback: F) -> Uid
+    where
+        F: EventDispatch + 'static,
+    {
+        let _scope = alloc::scope(AllocTag::Dispatch);
+        self.insert(Box::new(callback), owner)
     }
 
     pub fn push_boxed(&mut self, callback: Box<dyn EventDispatch>) -> Uid {
+        let _scope = alloc::scope(AllocTag::Dispatch);
+        self.insert(callback, None)
+    }
+
+    fn insert(&mut self, callback: Box<dyn EventDispatch>, owner: Option<DispatchOwner>) -> Uid {
         let id = Uid::new();
         debug_assert!(!self.dispatches.contains_key(&id));
-        self.dispatches.insert(id, callback);
+        self.dispatches.insert(id, Dispatch { callback, owner });
         id
     }
 
+    /// `None` if there is no such dispatch or its owner is gone.
     pub fn pop(&mut self, id: Uid) -> Option<Box<dyn EventDispatch>> {
-        self.dispatches.remove(&id)
+        self.dispatches
+            .remove(&id)
+            .filter(|dispatch| dispatch.is_alive())
+            .map(|dispatch| dispatch.callback)
+    }
+
+    /// Drops the dispatches whose owner is gone, returns their ids.
+    pub fn sweep(&mut self) -> Vec<Uid> {
+        let mut swept = Vec::new();
+        self.dispatches.retain(|&id, dispatch| {
+            let alive = dispatch.is_alive();
+            if !alive {
+                swept.push(id);
+            }
+            alive
+        });
+        swept
+    }
+
+    pub fn len(&self) -> usize {
+        self.dispatches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dispatches.is_empty()
+    }
+}
+
+struct FrameCallback {
+    callback: Box<dyn FrameDispatch>,
+    owner: Option<DispatchOwner>,
+    cancel: CancellationToken,
+}
+
+/// Callbacks executed on the main thread once per frame with the time
+/// elapsed since the previous frame.
+#[derive(Default)]
+pub struct FrameCallbacks {
+    callbacks: Vec<FrameCallback>,
+    last_frame: Option<Instant>,
+}
+
+impl FrameCallbacks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(
+        &mut self,
+        owner: Option<DispatchOwner>,
+        cancel: CancellationToken,
+        callback: Box<dyn FrameDispatch>,
+    ) {
+        let _scope = alloc::scope(AllocTag::Dispatch);
+        self.callbacks.push(FrameCallback {
+            callback,
+            owner,
+            cancel,
+        });
+    }
+
+    /// Executes every callback, the ones that are cancelled, whose owner is
+    /// gone or that failed are dropped.
+    pub fn run(main_ctx: &mut MainContext, root_scene: &mut RootScene) {
+        let now = Instant::now();
+        let list = &mut main_ctx.frame_callbacks;
+        let delta = list
+            .last_frame
+            .replace(now)
+            .map(|last| now - last)
+            .unwrap_or_default();
+        let mut callbacks = mem::take(&mut list.callbacks);
+        callbacks.retain_mut(|frame| {
+            frame.is_alive()
+                && (frame.callback)(main_ctx, root_scene, delta)
+                    .context("frame callback failed")
+                    .log_error()
+                    .is_some()
+        });
+        // callbacks registered meanwhile run from the next frame on
+        let list = &mut main_ctx.frame_callbacks;
+        callbacks.append(&mut list.callbacks);
+        list.callbacks = callbacks;
+    }
+}
+
+impl FrameCallback {
+    fn is_alive(&self) -> bool {
+        !self.cancel.is_cancelled() && self.owner.as_ref().map(|o| o.is_alive()).unwrap_or(true)
+    }
+}
+
+impl DispatchOwner {
+    pub fn is_alive(&self) -> bool {
+        match self {
+            Self::Scene(scene) => scene.is_alive(),
+            Self::Widget(widget) => !widget.is_expired(),
+        }
+    }
+}
+
+impl Dispatch {
+    fn is_alive(&self) -> bool {
+        self.owner.as_ref().map(|o| o.is_alive()).unwrap_or(true)
     }
 }
 
 #[derive(Debug)]
 pub enum DispatchMsg {
     ExecuteDispatch(Vec<Uid>),
+    /// an event published on the `EventBus`
+    Publish(TypeId, TopicPayload),
 }
 
 // #[derive(Clone, Copy, Debug, PartialEq, Eq)]
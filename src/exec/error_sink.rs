@@ -0,0 +1,81 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+
+use crate::events::error::{ErrorReport, GameError, Severity};
+
+/// Central destination of the errors reported to the main thread. Besides
+/// logging, it keeps the latest reports for the console, and writes crash
+/// reports for fatal errors. Routing to the `EventBus` and the test manager
+/// is done by `MainContext::report_error`.
+pub struct ErrorSink {
+    recent: VecDeque<ErrorReport>,
+    crash_dir: PathBuf,
+}
+
+impl ErrorSink {
+    pub const RECENT_CAPACITY: usize = 64;
+
+    pub fn new(crash_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            recent: VecDeque::with_capacity(Self::RECENT_CAPACITY),
+            crash_dir: crash_dir.into(),
+        }
+    }
+
+    /// Logs and records `error`, returns its report.
+    pub fn record(&mut self, error: &GameError) -> ErrorReport {
+        match error.severity {
+            Severity::Warning => tracing::warn!("{}", error),
+            Severity::Error | Severity::Fatal => tracing::error!("{}", error),
+        }
+
+        let report = error.report();
+        if self.recent.len() == Self::RECENT_CAPACITY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(report.clone());
+        report
+    }
+
+    /// Latest reports, oldest first.
+    pub fn recent(&self) -> impl Iterator<Item = &ErrorReport> {
+        self.recent.iter()
+    }
+
+    /// Writes `report` along with the previous errors to a new file in the
+    /// crash directory, returns its path.
+    pub fn write_crash_report(&self, report: &ErrorReport) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(&self.crash_dir).with_context(|| {
+            format!(
+                "unable to create crash report directory {}",
+                self.crash_dir.display()
+            )
+        })?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let path = self.crash_dir.join(format!("crash-{timestamp}.txt"));
+        write_report(&path, report, self.recent.iter())?;
+        Ok(path)
+    }
+}
+
+fn write_report<'a>(
+    path: &Path,
+    report: &ErrorReport,
+    recent: impl Iterator<Item = &'a ErrorReport>,
+) -> anyhow::Result<()> {
+    let mut content = format!("{report}\n\nrecent errors:\n");
+    for report in recent {
+        content.push_str(&format!("{report}\n"));
+    }
+    fs::write(path, content)
+        .with_context(|| format!("unable to write crash report {}", path.display()))
+}
@@ -0,0 +1,198 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, Weak},
+};
+
+use anyhow::{anyhow, Context};
+use trait_set::trait_set;
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    events::GameUserEvent,
+    scene::main::RootScene,
+    utils::{
+        error::ResultExt,
+        mpsc::{self, Receiver, Sender},
+        mutex::Mutex,
+        uid::Uid,
+    },
+};
+
+use super::{dispatch::DispatchMsg, main_ctx::MainContext};
+
+trait_set! {
+    /// Any type can be used as a topic, the type itself identifies the topic.
+    pub trait Topic = Any + Send + Sync;
+    pub trait TopicHandler<T> = Fn(&mut MainContext, &mut RootScene, &T) -> anyhow::Result<()> + Send + Sync;
+}
+
+pub type TopicPayload = Arc<dyn Any + Send + Sync>;
+
+type DynTopicHandler = Arc<
+    dyn Fn(&mut MainContext, &mut RootScene, &TopicPayload) -> anyhow::Result<()> + Send + Sync,
+>;
+
+enum Subscriber {
+    /// called on the main thread
+    Dispatch(DynTopicHandler),
+    /// returns false once the receiver is gone
+    Channel(Box<dyn Fn(&TopicPayload) -> bool + Send>),
+}
+
+#[derive(Default)]
+struct Registry {
+    topics: HashMap<TypeId, Vec<(Uid, Subscriber)>>,
+}
+
+/// Publish/subscribe bus for typed topics, letting scenes and servers talk
+/// to each other without knowing each other.
+///
+/// Handlers registered with `subscribe` run on the main thread as
+/// dispatches, channel subscribers (e.g. servers) receive the events
+/// immediately on the publishing thread. Events can be published from any
+/// thread.
+#[derive(Clone)]
+pub struct EventBus {
+    registry: Arc<Mutex<Registry>>,
+    proxy: EventLoopProxy<GameUserEvent>,
+}
+
+/// Unsubscribes on drop.
+#[must_use]
+pub struct Subscription {
+    id: Uid,
+    topic: TypeId,
+    registry: Weak<Mutex<Registry>>,
+}
+
+impl EventBus {
+    pub fn new(proxy: EventLoopProxy<GameUserEvent>) -> Self {
+        Self {
+            registry: Default::default(),
+            proxy,
+        }
+    }
+
+    fn add_subscriber(&self, topic: TypeId, subscriber: Subscriber) -> Subscription {
+        let id = Uid::new();
+        self.registry
+            .lock()
+            .topics
+            .entry(topic)
+            .or_default()
+            .push((id, subscriber));
+        Subscription {
+            id,
+            topic,
+            registry: Arc::downgrade(&self.registry),
+        }
+    }
+
+    pub fn subscribe<T, F>(&self, handler: F) -> Subscription
+    where
+        T: Topic,
+        F: TopicHandler<T> + 'static,
+    {
+        let handler: DynTopicHandler = Arc::new(
+            move |main_ctx: &mut MainContext,
+                  root_scene: &mut RootScene,
+                  payload: &TopicPayload| {
+                match payload.downcast_ref::<T>() {
+                    Some(event) => handler(main_ctx, root_scene, event),
+                    None => Ok(()),
+                }
+            },
+        );
+        self.add_subscriber(TypeId::of::<T>(), Subscriber::Dispatch(handler))
+    }
+
+    /// Subscribes with a channel instead of a main thread handler, for
+    /// subscribers living on other threads.
+    pub fn subscribe_channel<T: Topic>(&self) -> (Subscription, Receiver<Arc<T>>) {
+        let (sender, receiver): (Sender<Arc<T>>, _) = mpsc::channels();
+        let subscription = self.add_subscriber(
+            TypeId::of::<T>(),
+            Subscriber::Channel(Box::new(move |payload: &TopicPayload| {
+                match payload.clone().downcast::<T>() {
+                    Ok(event) => sender.send(event).is_ok(),
+                    Err(_) => true,
+                }
+            })),
+        );
+        (subscription, receiver)
+    }
+
+    pub fn publish<T: Topic>(&self, event: T) -> anyhow::Result<()> {
+        let topic = TypeId::of::<T>();
+        let payload: TopicPayload = Arc::new(event);
+        let has_dispatch = {
+            let mut registry = self.registry.lock();
+            let subscribers = match registry.topics.get_mut(&topic) {
+                Some(subscribers) => subscribers,
+                None => return Ok(()),
+            };
+            subscribers.retain(|(_, subscriber)| match subscriber {
+                Subscriber::Channel(send) => send(&payload),
+                Subscriber::Dispatch(_) => true,
+            });
+            subscribers
+                .iter()
+                .any(|(_, subscriber)| matches!(subscriber, Subscriber::Dispatch(_)))
+        };
+
+        if has_dispatch {
+            self.proxy
+                .send_event(GameUserEvent::Dispatch(DispatchMsg::Publish(
+                    topic, payload,
+                )))
+                .map_err(|e| anyhow!("{e}"))
+                .context("unable to send published event to main thread")?;
+        }
+        Ok(())
+    }
+
+    /// Runs the main thread handlers of `topic`, handlers subscribed after
+    /// the event was published are called too.
+    pub fn deliver(
+        &self,
+        main_ctx: &mut MainContext,
+        root_scene: &mut RootScene,
+        topic: TypeId,
+        payload: &TopicPayload,
+    ) {
+        // handlers are called without holding the lock, so that they can
+        // (un)subscribe and publish
+        let handlers: Vec<DynTopicHandler> = self
+            .registry
+            .lock()
+            .topics
+            .get(&topic)
+            .into_iter()
+            .flatten()
+            .filter_map(|(_, subscriber)| match subscriber {
+                Subscriber::Dispatch(handler) => Some(handler.clone()),
+                Subscriber::Channel(_) => None,
+            })
+            .collect();
+        for handler in handlers {
+            handler(main_ctx, root_scene, payload)
+                .context("topic handler failed")
+                .log_error();
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(registry) = self.registry.upgrade() {
+            let mut registry = registry.lock();
+            if let Some(subscribers) = registry.topics.get_mut(&self.topic) {
+                subscribers.retain(|(id, _)| *id != self.id);
+                if subscribers.is_empty() {
+                    registry.topics.remove(&self.topic);
+                }
+            }
+        }
+    }
+}
@@ -1,19 +1,29 @@
 use anyhow::Context;
 
-use crate::utils::error::ResultExt;
+use crate::utils::{
+    affinity::{set_current_thread_affinity, set_current_thread_priority, CoreSet, ThreadPriority},
+    error::ResultExt,
+};
 
 use super::{
+    balancer::Balancer,
     runner::{
-        container::ServerContainer, MainRunner, Runner, RunnerId, ServerMover, ThreadRunnerHandle,
+        container::{PauseFlags, ServerContainer},
+        MainRunner, Runner, RunnerConfig, RunnerId, ServerMover, ThreadRunnerHandle,
         MAIN_RUNNER_ID,
     },
-    server::{audio, draw, update, SendGameServer, ServerKind},
+    server::{audio, draw, network, update, SendGameServer, ServerKind},
+    stats::{RunnerStats, StatsRegistry},
     NUM_GAME_LOOPS,
 };
 
 pub struct GameServerExecutor {
     pub main_runner: MainRunner,
     thread_runners: [Option<ThreadRunnerHandle>; NUM_GAME_LOOPS],
+    runner_configs: [RunnerConfig; NUM_GAME_LOOPS],
+    pub stats: StatsRegistry,
+    balancer: Option<Balancer>,
+    paused: PauseFlags,
 }
 
 impl GameServerExecutor {
@@ -35,7 +45,14 @@ impl GameServerExecutor {
         match to {
             MAIN_RUNNER_ID => self.main_runner.emplace_server_check(server),
             _ => self.thread_runners[usize::from(to)]
-                .get_or_insert_with(|| ThreadRunnerHandle::new(to))
+                .get_or_insert_with(|| {
+                    ThreadRunnerHandle::new(
+                        to,
+                        self.runner_configs[usize::from(to)],
+                        self.stats.runner(to),
+                        self.paused.clone(),
+                    )
+                })
                 .emplace_server_check(server),
         }
     }
@@ -64,25 +81,138 @@ impl GameServerExecutor {
         Ok(())
     }
 
+    /// Pins the runner `id` to `cores`. Thread runners that haven't been
+    /// constructed yet will be pinned as soon as their thread is spawned.
+    pub fn set_runner_affinity(&mut self, id: RunnerId, cores: CoreSet) -> anyhow::Result<()> {
+        match id {
+            MAIN_RUNNER_ID => set_current_thread_affinity(&cores)?,
+            _ => {
+                let index = usize::from(id);
+                anyhow::ensure!(index < NUM_GAME_LOOPS, "invalid runner id {}", id);
+                self.runner_configs[index].affinity = Some(cores);
+                if let Some(runner) = self.thread_runners[index].as_ref() {
+                    runner.set_affinity(cores)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the scheduling priority hint of the runner `id`, same as
+    /// `set_runner_affinity` for runners not constructed yet.
+    pub fn set_runner_priority(
+        &mut self,
+        id: RunnerId,
+        priority: ThreadPriority,
+    ) -> anyhow::Result<()> {
+        match id {
+            MAIN_RUNNER_ID => set_current_thread_priority(priority)?,
+            _ => {
+                let index = usize::from(id);
+                anyhow::ensure!(index < NUM_GAME_LOOPS, "invalid runner id {}", id);
+                self.runner_configs[index].priority = Some(priority);
+                if let Some(runner) = self.thread_runners[index].as_ref() {
+                    runner.set_priority(priority)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies every hint set in `config` to the runner `id`, failures are
+    /// only logged like in `RunnerConfig::apply`.
+    pub fn configure_runner(&mut self, id: RunnerId, config: RunnerConfig) {
+        if let Some(cores) = config.affinity {
+            self.set_runner_affinity(id, cores)
+                .with_context(|| format!("unable to pin runner {id} to cores {cores:?}"))
+                .log_warn();
+        }
+        if let Some(priority) = config.priority {
+            self.set_runner_priority(id, priority)
+                .with_context(|| format!("unable to set priority of runner {id} to {priority:?}"))
+                .log_warn();
+        }
+    }
+
+    /// Freezes a server without moving it out of its container, it keeps
+    /// processing its messages but stops ticking.
+    pub fn pause_server(&self, kind: ServerKind) {
+        self.paused.set(kind, true);
+    }
+
+    pub fn resume_server(&self, kind: ServerKind) {
+        self.paused.set(kind, false);
+    }
+
+    pub fn is_server_paused(&self, kind: ServerKind) -> bool {
+        self.paused.is_paused(kind)
+    }
+
+    /// Enables or disables automatic server migration.
+    pub fn set_balancer(&mut self, balancer: Option<Balancer>) {
+        self.balancer = balancer;
+    }
+
+    /// Migrates a server off an overloaded runner if the balancer is enabled
+    /// and decides to, should be called periodically from the main thread.
+    pub fn balance(&mut self) -> anyhow::Result<()> {
+        let balancer = match self.balancer.as_mut() {
+            Some(balancer) => balancer,
+            None => return Ok(()),
+        };
+
+        let runners: Vec<_> = (0..NUM_GAME_LOOPS)
+            .filter(|&index| self.thread_runners[index].is_some())
+            .map(|index| index as RunnerId)
+            .chain(std::iter::once(MAIN_RUNNER_ID))
+            .map(|id| (id, self.stats.snapshot(id)))
+            .collect();
+
+        if let Some(migration) = balancer.plan(&runners) {
+            tracing::info!(
+                "balancer: moving {:?} server from runner {} to runner {}",
+                migration.kind,
+                migration.from,
+                migration.to
+            );
+            self.move_server(migration.from, migration.to, migration.kind)
+                .context("automatic server migration failed")?;
+            // the stats of the server on the old runner are stale now
+            self.stats.runner(migration.from).lock().server_costs[migration.kind as usize] = None;
+        }
+        Ok(())
+    }
+
+    /// Frame pacing statistics of every runner that has ticked at least once
+    pub fn runner_stats(&self) -> Vec<(RunnerId, RunnerStats)> {
+        self.stats.snapshot_all()
+    }
+
     pub fn new(
         audio: audio::Server,
         draw: draw::SendServer,
         update: update::Server,
+        network: network::Server,
     ) -> anyhow::Result<Self> {
+        let paused = PauseFlags::default();
         let mut container = ServerContainer {
             audio: Some(audio),
             draw: None,
             update: Some(update),
+            network: Some(network),
+            paused: paused.clone(),
         };
         container.emplace_server_check(SendGameServer::Draw(Box::new(draw)))?;
+        let stats = StatsRegistry::new();
         Ok(Self {
             thread_runners: Default::default(),
+            runner_configs: Default::default(),
             main_runner: MainRunner {
-                base: Runner {
-                    container,
-                    ..Default::default()
-                },
+                base: Runner::new(container, stats.runner(MAIN_RUNNER_ID)),
             },
+            stats,
+            balancer: None,
+            paused,
         })
     }
 
@@ -98,5 +228,12 @@ impl GameServerExecutor {
                 }
             }
         }
+
+        self.main_runner
+            .base
+            .container
+            .shutdown()
+            .context("error shutting down main runner servers")
+            .log_error();
     }
 }
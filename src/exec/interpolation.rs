@@ -0,0 +1,154 @@
+use std::time::Instant;
+
+use glam::{Vec2, Vec3, Vec4};
+
+use crate::{
+    ui::utils::geom::{UIPos, UIRect, UISize},
+    utils::{mutex::Mutex, sync::frame_alpha},
+};
+
+/// State that can be blended between two snapshots.
+pub trait Interpolate: Clone + Send + 'static {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self;
+}
+
+struct Published<T> {
+    state: T,
+    time: Instant,
+}
+
+struct Buffers<T> {
+    previous: Option<Published<T>>,
+    current: Option<Published<T>>,
+}
+
+/// Double-buffered snapshots of some game state, published by the update
+/// server at its fixed rate and sampled by the draw server.
+///
+/// Sampling blends the last two snapshots with the progress through the
+/// current update step, so the rendered state lags one step behind the
+/// simulation but moves smoothly at any refresh rate.
+pub struct StateSnapshots<T> {
+    buffers: Mutex<Buffers<T>>,
+}
+
+impl<T: Interpolate> StateSnapshots<T> {
+    pub fn new() -> Self {
+        Self {
+            buffers: Mutex::new(Buffers {
+                previous: None,
+                current: None,
+            }),
+        }
+    }
+
+    pub fn publish(&self, state: T) {
+        self.publish_at(state, Instant::now())
+    }
+
+    pub fn publish_at(&self, state: T, time: Instant) {
+        let mut buffers = self.buffers.lock();
+        buffers.previous = buffers.current.take();
+        buffers.current = Some(Published { state, time });
+    }
+
+    /// `None` until the first snapshot is published.
+    pub fn sample(&self, now: Instant) -> Option<T> {
+        let buffers = self.buffers.lock();
+        match (&buffers.previous, &buffers.current) {
+            (Some(previous), Some(current)) => {
+                let step = current.time.duration_since(previous.time).as_secs_f64();
+                if step <= 0.0 {
+                    return Some(current.state.clone());
+                }
+                let alpha = frame_alpha(
+                    now.saturating_duration_since(current.time).as_secs_f64(),
+                    1.0 / step,
+                );
+                Some(previous.state.interpolate(&current.state, alpha as f32))
+            }
+            (_, current) => current.as_ref().map(|current| current.state.clone()),
+        }
+    }
+}
+
+impl<T: Interpolate> Default for StateSnapshots<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        self + (next - self) * alpha
+    }
+}
+
+impl Interpolate for Vec2 {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        self.lerp(*next, alpha)
+    }
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        self.lerp(*next, alpha)
+    }
+}
+
+impl Interpolate for Vec4 {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        self.lerp(*next, alpha)
+    }
+}
+
+impl Interpolate for UIPos {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        Vec2::from(*self).lerp((*next).into(), alpha).into()
+    }
+}
+
+impl Interpolate for UISize {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        Vec2::from(*self).lerp((*next).into(), alpha).into()
+    }
+}
+
+impl Interpolate for UIRect {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        UIRect::new(
+            self.pos.interpolate(&next.pos, alpha),
+            self.size.interpolate(&next.size, alpha),
+        )
+    }
+}
+
+/// Element-wise, the previous snapshot is ignored if the lengths differ.
+impl<T: Interpolate> Interpolate for Vec<T> {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        if self.len() != next.len() {
+            return next.clone();
+        }
+        self.iter()
+            .zip(next.iter())
+            .map(|(a, b)| a.interpolate(b, alpha))
+            .collect()
+    }
+}
+
+#[test]
+fn test() {
+    use std::time::Duration;
+
+    let snapshots = StateSnapshots::<f32>::new();
+    let start = Instant::now();
+    assert_eq!(snapshots.sample(start), None);
+    snapshots.publish_at(0.0, start);
+    assert_eq!(snapshots.sample(start), Some(0.0));
+
+    let step = Duration::from_millis(100);
+    snapshots.publish_at(10.0, start + step);
+    assert_eq!(snapshots.sample(start + step), Some(0.0));
+    assert_eq!(snapshots.sample(start + step + step / 2), Some(5.0));
+    assert_eq!(snapshots.sample(start + step * 5), Some(10.0));
+}
@@ -0,0 +1,114 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use crate::utils::clock::{Clock, SteadyClock, VirtualClock};
+
+/// Frame counter shared between the draw server, which counts rendered
+/// frames, and the update server, which ticks a fixed number of times per
+/// counted frame.
+pub struct Lockstep {
+    frames: AtomicU64,
+    pub ticks_per_frame: u32,
+    /// virtual time step of a single update tick, in seconds
+    pub tick_duration: f64,
+}
+
+impl Lockstep {
+    /// virtual duration of a frame, regardless of the actual refresh rate
+    pub const FRAME_DURATION: f64 = 1.0 / 60.0;
+
+    pub fn new(ticks_per_frame: u32) -> Arc<Self> {
+        Arc::new(Self {
+            frames: AtomicU64::new(0),
+            ticks_per_frame,
+            tick_duration: Self::FRAME_DURATION / f64::from(ticks_per_frame),
+        })
+    }
+
+    pub fn frame_rendered(&self) {
+        self.frames.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Acquire)
+    }
+
+    pub fn target_ticks(&self) -> u64 {
+        self.frames() * u64::from(self.ticks_per_frame)
+    }
+}
+
+/// Time source of the update server.
+pub enum ServerClock {
+    Steady(SteadyClock),
+    /// advanced by whoever owns the clock, e.g. a test
+    Virtual(VirtualClock),
+    Lockstep {
+        lockstep: Arc<Lockstep>,
+        clock: VirtualClock,
+        ticks: u64,
+    },
+}
+
+impl ServerClock {
+    pub fn new(lockstep: Option<Arc<Lockstep>>) -> Self {
+        match lockstep {
+            Some(lockstep) => Self::Lockstep {
+                lockstep,
+                clock: VirtualClock::new(),
+                ticks: 0,
+            },
+            None => Self::Steady(SteadyClock::new()),
+        }
+    }
+
+    pub fn is_lockstep(&self) -> bool {
+        matches!(self, Self::Lockstep { .. })
+    }
+
+    /// number of ticks to run before the server catches up with the draw
+    /// server, always 1 outside of lockstep mode
+    pub fn pending_ticks(&self) -> u64 {
+        match self {
+            Self::Steady(_) | Self::Virtual(_) => 1,
+            Self::Lockstep {
+                lockstep, ticks, ..
+            } => lockstep.target_ticks().saturating_sub(*ticks),
+        }
+    }
+
+    /// drops the pending ticks without advancing the virtual clock
+    pub fn skip_pending(&mut self) {
+        if let Self::Lockstep {
+            lockstep, ticks, ..
+        } = self
+        {
+            *ticks = (*ticks).max(lockstep.target_ticks());
+        }
+    }
+
+    /// called after every tick
+    pub fn advance(&mut self) {
+        if let Self::Lockstep {
+            lockstep,
+            clock,
+            ticks,
+        } = self
+        {
+            *ticks += 1;
+            clock.advance(lockstep.tick_duration);
+        }
+    }
+}
+
+impl Clock for ServerClock {
+    fn now(&self) -> f64 {
+        match self {
+            Self::Steady(clock) => clock.now(),
+            Self::Virtual(clock) => clock.now(),
+            Self::Lockstep { clock, .. } => clock.now(),
+        }
+    }
+}
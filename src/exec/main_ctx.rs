@@ -1,46 +1,141 @@
 use std::{
     borrow::Cow,
     collections::HashMap,
+    future::Future,
+    path::PathBuf,
     sync::Arc,
     time::{Duration, Instant},
 };
 
 use anyhow::Context;
-use tracing_appender::non_blocking::WorkerGuard;
 use winit::{
-    event::Event,
+    event::{Event, WindowEvent},
     event_loop::{EventLoop, EventLoopProxy},
 };
 
 use crate::{
-    display::Display,
-    events::{GameEvent, GameUserEvent},
-    graphics::{context::DrawContext, wrappers::vertex_array::VertexArrayHandle},
-    scene::main::RootScene,
-    test::TestManager,
-    ui::{EventContext, Widget},
-    utils::{args::args, error::ResultExt, mpsc},
+    display::{platform::PlatformWatcher, Display},
+    events::{
+        error::{GameError, Severity, Subsystem},
+        GameEvent, GameUserEvent,
+    },
+    graphics::{
+        context::DrawContext,
+        image_loader::PendingTexture,
+        post_effect::PostEffect,
+        present::PresentModeReport,
+        shader_watcher::{FragmentTemplate, ShaderFiles, ShaderWatcher},
+        wrappers::{
+            shader::ProgramHandle, texture::TextureOptions, vertex_array::VertexArrayHandle,
+        },
+    },
+    nav::{NavGrid, PathQuery},
+    net::{NetEvent, NetHandler, NetHandlers},
+    scene::{
+        lifetime::{SceneLifetime, SceneWeak},
+        main::RootScene,
+    },
+    test::{
+        capture::{self, LogCapture, LogSource},
+        TestManager,
+    },
+    ui::{
+        event::{FocusDirection, UIFocusEvent},
+        focus_order,
+        overlay::OverlayLayer,
+        utils::geom::UIPos,
+        EventContext, Widget, WidgetId,
+    },
+    utils::{
+        alloc,
+        args::args,
+        config::{Config, ConfigChanged, ConfigWatcher},
+        error::ResultExt,
+        log::LogGuard,
+        mpsc::is_disconnected_error,
+        property::PropertyRegistry,
+        uid::Uid,
+    },
 };
 
 use super::{
-    dispatch::{DispatchList, DispatchMsg, EventDispatch},
+    dispatch::{
+        DispatchList, DispatchMsg, DispatchOwner, EventDispatch, FrameCallbacks, FrameDispatch,
+        IntervalDispatch,
+    },
+    error_sink::ErrorSink,
+    event_bus::EventBus,
     executor::GameServerExecutor,
-    server::{draw::ServerSendChannelExt, ServerChannels},
-    task::TaskExecutor,
+    interpolation::Interpolate,
+    query::{LocalTasks, ServerQuery},
+    replay::{EventRecorder, EventReplay, RecordedEvent},
+    runner::{RunnerId, MAIN_RUNNER_ID},
+    server::{
+        draw::{
+            self,
+            pointer::{PointerLatch, PointerLatches, PointerSample},
+            ServerSendChannelExt,
+        },
+        update::{
+            sequence::Sequence,
+            tween::{Tween, TweenApply},
+            PathCallback,
+        },
+        GameServerSendChannel, ServerChannels,
+    },
+    stats::RunnerStats,
+    task::{Cancellable, CancellationToken, TaskExecutor},
 };
 
+/// How often the config file is checked for changes.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the sources of watched shaders are checked for changes.
+const SHADER_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How often test timeouts and retries are checked.
+const TEST_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How often the power supply and locale are checked for changes.
+const PLATFORM_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
 pub struct MainContext {
-    pub focused_widget: Option<Arc<dyn Widget>>,
-    pub prev_focused_widget: Option<Arc<dyn Widget>>,
+    pub config: Arc<Config>,
+    config_watcher: ConfigWatcher,
+    pub platform: PlatformWatcher,
+    pub shader_watcher: ShaderWatcher,
+    pub focused_widget: Option<SceneWeak<dyn Widget>>,
+    pub prev_focused_widget: Option<SceneWeak<dyn Widget>>,
+    /// top layer of the UI, shared with the UI scene drawing it
+    pub overlays: Arc<OverlayLayer>,
+    /// scene the event being handled was delivered to, timeouts and focus
+    /// set meanwhile are released when that scene is removed
+    pub current_scene: Option<SceneLifetime>,
     pub test_logs: HashMap<Cow<'static, str>, String>,
+    pub test_capture: LogCapture,
     pub test_manager: Option<Arc<TestManager>>,
     pub executor: GameServerExecutor,
-    pub dummy_vao: VertexArrayHandle,
+    /// `None` once shut down, see `dummy_vao()`
+    dummy_vao: Option<VertexArrayHandle>,
     pub task_executor: TaskExecutor,
+    pub local_tasks: LocalTasks,
     pub channels: ServerChannels,
     pub dispatch_list: DispatchList,
+    pub frame_callbacks: FrameCallbacks,
+    pub event_bus: EventBus,
+    pub error_sink: ErrorSink,
+    pub net_handlers: NetHandlers,
     pub event_loop_proxy: EventLoopProxy<GameUserEvent>,
     pub display: Display,
+    /// result of the last present mode change
+    pub present_mode: Option<PresentModeReport>,
+    pub pointer_latches: PointerLatches,
+    /// debug properties tunable from the inspector
+    pub properties: PropertyRegistry,
+    /// set once shutdown started, no new work is submitted to the draw
+    /// server from then on
+    pub shutting_down: bool,
+    /// set with `--record`
+    pub recorder: Option<EventRecorder>,
+    /// set with `--replay`, cleared once every event was replayed
+    pub replay: Option<EventReplay>,
 }
 
 impl MainContext {
@@ -49,54 +144,377 @@ impl MainContext {
         display: Display,
         event_loop_proxy: EventLoopProxy<GameUserEvent>,
         mut channels: ServerChannels,
+        task_executor: TaskExecutor,
+        config: Config,
     ) -> anyhow::Result<Self> {
         let mut slf = Self {
             executor,
             test_manager: args()
                 .test
-                .then(|| TestManager::new(event_loop_proxy.clone())),
-            dummy_vao: VertexArrayHandle::new(&mut channels.draw, "dummy vertex array")?,
-            task_executor: TaskExecutor::new(),
+                .then(|| TestManager::new(event_loop_proxy.clone(), &config.test)),
+            config_watcher: ConfigWatcher::new(),
+            platform: PlatformWatcher::new(),
+            shader_watcher: ShaderWatcher::new(),
+            config: Arc::new(config),
+            dummy_vao: Some(VertexArrayHandle::new(
+                &mut channels.draw,
+                "dummy vertex array",
+            )?),
+            task_executor,
+            local_tasks: LocalTasks::new(),
             display,
+            event_bus: EventBus::new(event_loop_proxy.clone()),
+            error_sink: ErrorSink::new(&args().artifacts_dir),
+            net_handlers: NetHandlers::new(),
             event_loop_proxy,
             dispatch_list: DispatchList::new(),
+            frame_callbacks: FrameCallbacks::new(),
             channels,
             test_logs: HashMap::new(),
+            test_capture: LogCapture::new(LogSource::Main),
             prev_focused_widget: None,
             focused_widget: None,
+            overlays: Arc::new(OverlayLayer::new()),
+            current_scene: None,
+            present_mode: None,
+            pointer_latches: PointerLatches::default(),
+            properties: PropertyRegistry::new(),
+            shutting_down: false,
+            recorder: None,
+            replay: None,
         };
 
         if let Some(test_manager) = slf.test_manager.as_ref() {
             let test_manager = test_manager.clone();
-            slf.set_timeout(Duration::from_secs(30), move |_, _| {
-                test_manager.set_timeout_func();
+            let timeout =
+                Duration::from_secs(slf.config.test.timeout + args().soak.unwrap_or_default());
+            slf.set_timeout(
+                timeout,
+                enclose!((test_manager) move |_, _| {
+                    test_manager.set_timeout_func();
+                    Ok(())
+                }),
+            )
+            .context("unable to set test timeout")?;
+            slf.set_interval(TEST_POLL_INTERVAL, move |main_ctx, _| {
+                test_manager.poll(main_ctx);
                 Ok(())
             })
-            .context("unable to set test timeout")?;
+            .context("unable to poll test timeouts")?;
         }
 
+        // the runners were started with the ungoverned frequencies
+        let started = (0..=MAIN_RUNNER_ID)
+            .map(|id| slf.config.runners.frequency(id))
+            .collect::<Vec<_>>();
+        slf.apply_runner_frequencies(&started);
+
+        slf.watch_config().context("unable to watch config file")?;
+        slf.watch_shaders()
+            .context("unable to watch shader files")?;
+        slf.watch_platform()
+            .context("unable to watch platform state")?;
         Ok(slf)
     }
 
+    fn watch_shaders(&mut self) -> anyhow::Result<()> {
+        self.set_timeout(SHADER_POLL_INTERVAL, |main_ctx, _| {
+            let watcher = main_ctx.shader_watcher.clone();
+            let draw = main_ctx.channels.draw.clone_sender();
+            let proxy = main_ctx.event_loop_proxy.clone();
+            main_ctx.execute_blocking_task(move |_| watcher.poll(&draw, &proxy));
+            main_ctx.watch_shaders()
+        })
+    }
+
+    /// Loads a program from `files`, recompiled whenever they change.
+    pub fn load_program(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        files: ShaderFiles,
+    ) -> anyhow::Result<ProgramHandle> {
+        self.shader_watcher
+            .load_vf(&mut self.channels.draw, name, files)
+    }
+
+    /// Like `load_program`, for a single fragment shader file completed by
+    /// `template`.
+    pub fn load_fragment_program(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        path: impl Into<PathBuf>,
+        template: FragmentTemplate,
+    ) -> anyhow::Result<ProgramHandle> {
+        self.shader_watcher
+            .load_fragment(&mut self.channels.draw, name, path, template)
+    }
+
+    /// Decodes the image at `path` on the task executor and uploads it to a
+    /// texture, which holds a placeholder until then.
+    pub fn load_texture(
+        &mut self,
+        name: impl Into<Cow<'static, str>> + Send + 'static,
+        path: impl Into<PathBuf>,
+        options: TextureOptions,
+    ) -> anyhow::Result<PendingTexture> {
+        let cancel = self.cancellation_token();
+        PendingTexture::new(
+            &mut self.channels.draw,
+            self.event_loop_proxy.clone(),
+            &self.task_executor,
+            cancel,
+            name,
+            path.into(),
+            options,
+        )
+    }
+
+    fn watch_config(&mut self) -> anyhow::Result<()> {
+        self.set_timeout(CONFIG_POLL_INTERVAL, |main_ctx, _| {
+            main_ctx.poll_config();
+            main_ctx.watch_config()
+        })
+    }
+
+    /// Reloads the config if its file changed, publishing `ConfigChanged`
+    /// if the merged config differs.
+    pub fn poll_config(&mut self) {
+        let config = match self.config_watcher.poll() {
+            Some(Ok(config)) => config,
+            Some(Err(e)) => {
+                // the previous config is kept
+                self.report_error(
+                    GameError::new(Subsystem::Main, "config.reload", e).severity(Severity::Warning),
+                );
+                return;
+            }
+            None => return,
+        };
+        if config == *self.config {
+            return;
+        }
+
+        tracing::info!("config reloaded");
+        let old = std::mem::replace(&mut self.config, Arc::new(config));
+        let power = self.platform.power();
+        let frequencies = (0..=MAIN_RUNNER_ID)
+            .map(|id| old.runners.governed_frequency(id, &power))
+            .collect::<Vec<_>>();
+        self.apply_runner_frequencies(&frequencies);
+        self.event_bus
+            .publish(ConfigChanged {
+                old,
+                new: self.config.clone(),
+            })
+            .context("unable to publish config change")
+            .log_warn();
+    }
+
+    /// Sets the frequency of every runner whose governed frequency differs
+    /// from `old`, indexed by runner id.
+    fn apply_runner_frequencies(&mut self, old: &[f64]) {
+        let power = self.platform.power();
+        for id in 0..=MAIN_RUNNER_ID {
+            let frequency = self.config.runners.governed_frequency(id, &power);
+            if old.get(usize::from(id)) != Some(&frequency) {
+                self.executor
+                    .set_frequency(id, frequency)
+                    .with_context(|| format!("unable to set frequency of runner {id}"))
+                    .log_warn();
+            }
+        }
+    }
+
+    fn watch_platform(&mut self) -> anyhow::Result<()> {
+        self.set_timeout(PLATFORM_POLL_INTERVAL, |main_ctx, _| {
+            main_ctx.poll_platform();
+            main_ctx.watch_platform()
+        })
+    }
+
+    /// Publishes `PowerChanged` and `LocaleChanged` if the platform state
+    /// changed, runner frequencies are capped while on battery.
+    pub fn poll_platform(&mut self) {
+        if let Some(change) = self.platform.poll_power() {
+            tracing::info!(
+                "power state changed from {:?} to {:?}",
+                change.old,
+                change.new
+            );
+            let frequencies = (0..=MAIN_RUNNER_ID)
+                .map(|id| self.config.runners.governed_frequency(id, &change.old))
+                .collect::<Vec<_>>();
+            self.apply_runner_frequencies(&frequencies);
+            self.event_bus
+                .publish(change)
+                .context("unable to publish power state change")
+                .log_warn();
+        }
+        if let Some(change) = self.platform.poll_locale() {
+            tracing::info!("locale changed from {} to {}", change.old, change.new);
+            self.event_bus
+                .publish(change)
+                .context("unable to publish locale change")
+                .log_warn();
+        }
+    }
+
     pub fn set_focus_widget(&mut self, new_widget: Option<Arc<dyn Widget>>) {
+        self.sweep_focus();
         if self.focused_widget.is_some() {
             tracing::warn!("two widgets tried to be focused in one mouse press event");
             return;
         }
 
-        self.focused_widget = new_widget;
-        if self.prev_focused_widget.as_ref().map(|w| w.id())
-            == self.focused_widget.as_ref().map(|w| w.id())
+        self.focused_widget = new_widget
+            .as_ref()
+            .map(|w| SceneWeak::new(w, self.current_scene.clone()));
+        if self
+            .prev_focused_widget
+            .as_ref()
+            .and_then(|w| w.upgrade())
+            .map(|w| w.id())
+            == new_widget.as_ref().map(|w| w.id())
         {
             return;
         }
 
         if let Some(widget) = self.prev_focused_widget.take() {
-            widget.focus_changed(&mut EventContext { main_ctx: self }, false);
+            self.deliver_focus_changed(&widget, false);
         }
 
         if let Some(widget) = self.focused_widget.clone() {
-            widget.focus_changed(&mut EventContext { main_ctx: self }, true);
+            self.deliver_focus_changed(&widget, true);
+        }
+    }
+
+    /// Starts the handling of a mouse press, the widget claiming the focus
+    /// meanwhile with `set_focus_widget` takes it from the focused one.
+    pub fn begin_focus_press(&mut self) {
+        self.sweep_focus();
+        if self.focused_widget.is_some() {
+            self.prev_focused_widget = self.focused_widget.take();
+        }
+    }
+
+    /// Ends the handling of a mouse press, the focus is dropped if no
+    /// widget claimed it, e.g. when clicking outside of the focused one.
+    pub fn end_focus_press(&mut self) {
+        self.sweep_focus();
+        if let Some(widget) = self.prev_focused_widget.take() {
+            if self.focused_widget.is_none() {
+                self.deliver_focus_changed(&widget, false);
+            }
+        }
+    }
+
+    /// Moves the focus to the next or previous widget of `focus_order(root)`,
+    /// wrapping around, from either end if the focused widget isn't in it.
+    /// Returns whether the focus moved.
+    pub fn move_focus(&mut self, root: &Arc<dyn Widget>, direction: FocusDirection) -> bool {
+        let order = focus_order(root);
+        let focused = self.get_focused_widget().map(|w| w.id());
+        let current = focused.and_then(|id| order.iter().position(|w| w.id() == id));
+        let len = order.len();
+        let next = match (direction, current) {
+            _ if len == 0 => return false,
+            (FocusDirection::Next, Some(i)) => (i + 1) % len,
+            (FocusDirection::Next, None) => 0,
+            (FocusDirection::Previous, Some(i)) => (i + len - 1) % len,
+            (FocusDirection::Previous, None) => len - 1,
+        };
+        if current == Some(next) {
+            return false;
+        }
+
+        self.sweep_focus();
+        let new_widget = SceneWeak::new(&order[next], self.current_scene.clone());
+        let old_widget = self.focused_widget.replace(new_widget.clone());
+        if let Some(widget) = old_widget {
+            self.deliver_focus_changed(&widget, false);
+        }
+        self.deliver_focus_changed(&new_widget, true);
+        true
+    }
+
+    /// Drops the focus if it's held by a widget matching `predicate`, which
+    /// is notified, e.g. when its subtree is removed from the UI.
+    pub fn release_focus_if<F>(&mut self, predicate: F)
+    where
+        F: Fn(WidgetId) -> bool,
+    {
+        self.sweep_focus();
+        let mut released = Vec::new();
+        for slot in [&mut self.focused_widget, &mut self.prev_focused_widget] {
+            let matches = slot
+                .as_ref()
+                .and_then(|w| w.upgrade())
+                .map(|w| predicate(w.id()))
+                .unwrap_or(false);
+            if matches {
+                released.extend(slot.take());
+            }
+        }
+        released.dedup_by_key(|w| w.upgrade().map(|w| w.id()));
+        for widget in released {
+            self.deliver_focus_changed(&widget, false);
+        }
+    }
+
+    /// Empty vertex array for the draws generating their vertices in the
+    /// vertex shader, e.g. full-screen passes.
+    pub fn dummy_vao(&self) -> VertexArrayHandle {
+        self.dummy_vao
+            .clone()
+            .expect("dummy vertex array used after shutdown")
+    }
+
+    pub fn get_focused_widget(&self) -> Option<Arc<dyn Widget>> {
+        self.focused_widget.as_ref().and_then(|w| w.upgrade())
+    }
+
+    fn deliver_focus_changed(&mut self, widget: &SceneWeak<dyn Widget>, new_focus: bool) {
+        if let Some(widget_arc) = widget.upgrade_unchecked() {
+            // expired references are swept before any delivery
+            debug_assert!(
+                widget.scene_alive(),
+                "focus event delivered to widget {:?} whose scene was removed",
+                widget_arc.id()
+            );
+            let mut ctx = EventContext { main_ctx: self };
+            widget_arc.focus_changed(&mut ctx, new_focus);
+            let event = if new_focus {
+                UIFocusEvent::Gained
+            } else {
+                UIFocusEvent::Lost
+            };
+            widget_arc.handle_focus_event(&mut ctx, event);
+        }
+    }
+
+    fn sweep_focus(&mut self) {
+        for widget in [&mut self.focused_widget, &mut self.prev_focused_widget] {
+            if widget.as_ref().map(|w| w.is_expired()).unwrap_or(false) {
+                *widget = None;
+            }
+        }
+    }
+
+    /// Releases what was retained on behalf of removed scenes or dropped
+    /// widgets, run once per frame.
+    pub fn sweep_expired(&mut self) {
+        self.sweep_focus();
+        let swept = self.dispatch_list.sweep();
+        if !swept.is_empty() {
+            tracing::debug!("dropped {} dispatches of removed owners", swept.len());
+        }
+        // the ids of other kinds of dispatches are ignored by the server
+        for id in swept {
+            self.channels
+                .update
+                .cancel_timeout(id)
+                .context("unable to cancel timeout of removed owner")
+                .log_warn();
         }
     }
 
@@ -129,14 +547,58 @@ impl MainContext {
                         dispatch(self, root_scene)?;
                     }
                 }
+                DispatchMsg::Publish(topic, payload) => {
+                    let event_bus = self.event_bus.clone();
+                    event_bus.deliver(self, root_scene, topic, &payload);
+                }
             },
 
             Event::UserEvent(GameUserEvent::Execute(callback)) => {
                 callback(self, root_scene).log_error();
             }
 
-            Event::UserEvent(GameUserEvent::Error(e)) => {
-                tracing::error!("GameUserEvent::Error caught: {}", e);
+            Event::UserEvent(GameUserEvent::ExecuteReturn(event)) => {
+                event.wake();
+                self.local_tasks.poll_ready();
+            }
+
+            Event::UserEvent(GameUserEvent::VSyncSet(report)) => {
+                // published for the settings UI and tests
+                self.event_bus
+                    .publish(report.clone())
+                    .context("unable to publish present mode change")
+                    .log_warn();
+                self.present_mode = Some(report);
+            }
+
+            Event::UserEvent(GameUserEvent::Error(error)) => {
+                self.report_error(error);
+            }
+
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } if !self.pointer_latches.is_empty() => {
+                let scale_factor = self.display.get_scale_factor();
+                self.pointer_latches.publish(PointerSample {
+                    position: position.to_logical::<f32>(scale_factor).into(),
+                    received: Instant::now(),
+                });
+                root_scene.handle_event(self, event);
+            }
+
+            Event::WindowEvent {
+                event: WindowEvent::ThemeChanged(theme),
+                ..
+            } => {
+                if let Some(change) = self.platform.set_theme(theme) {
+                    tracing::info!("theme changed from {:?} to {:?}", change.old, change.new);
+                    self.event_bus
+                        .publish(change)
+                        .context("unable to publish theme change")
+                        .log_warn();
+                }
+                root_scene.handle_event(self, event);
             }
 
             event => {
@@ -146,54 +608,404 @@ impl MainContext {
         Ok(())
     }
 
+    /// Routes `error` to the log, the console (as an `ErrorReport` on the
+    /// `EventBus`), the test manager and, if fatal, the crash reporter.
+    pub fn report_error(&mut self, error: GameError) {
+        let report = self.error_sink.record(&error);
+        if let Some(test_manager) = self.test_manager.as_ref() {
+            test_manager.report_error(&report);
+        }
+
+        if error.severity == Severity::Fatal {
+            if let Some(path) = self
+                .error_sink
+                .write_crash_report(&report)
+                .context("unable to write crash report")
+                .log_error()
+            {
+                tracing::error!("crash report written to {}", path.display());
+            }
+            self.event_loop_proxy
+                .send_event(GameUserEvent::Exit(1))
+                .log_error();
+        }
+
+        self.event_bus
+            .publish(report)
+            .context("unable to publish error report")
+            .log_warn();
+    }
+
+    /// Inserts `effect` at `index` of the post effect chain of the draw
+    /// server, clamped to its length.
+    pub fn insert_post_effect(
+        &mut self,
+        index: usize,
+        effect: impl PostEffect + 'static,
+    ) -> anyhow::Result<()> {
+        if self.shutting_down {
+            return Ok(());
+        }
+        self.channels.draw.execute_draw_event(move |context, _| {
+            context
+                .insert_post_effect(index, Box::new(effect))
+                .err()
+                .map(|e| GameUserEvent::error(Subsystem::Draw, "draw.insert_post_effect", e))
+        })
+    }
+
+    pub fn remove_post_effect(&mut self, name: impl Into<String>) -> anyhow::Result<()> {
+        if self.shutting_down {
+            return Ok(());
+        }
+        let name = name.into();
+        self.channels.draw.execute(move |context, _| {
+            context.remove_post_effect(&name);
+        })
+    }
+
+    /// Resolves to the widget (or other pickable) drawn at `pos` a frame or
+    /// two later, `None` if there is nothing there.
+    pub fn pick_at(&self, pos: UIPos) -> anyhow::Result<ServerQuery<Option<Uid>>> {
+        self.channels.draw.pick_at(pos)
+    }
+
+    /// Subscribes to the low-latency pointer path, see `PointerLatch`.
+    pub fn subscribe_pointer(&mut self) -> PointerLatch {
+        self.pointer_latches.subscribe()
+    }
+
+    /// Executes `callback` after `timeout`, unless the current scene is
+    /// removed meanwhile.
     pub fn set_timeout<F>(&mut self, timeout: Duration, callback: F) -> anyhow::Result<()>
     where
         F: EventDispatch + 'static,
     {
-        let id = self.dispatch_list.push(callback);
-        self.channels.update.set_timeout(timeout, id)?;
+        let owner = self.current_owner();
+        self.set_timeout_owned(owner, timeout, callback)
+    }
+
+    /// Like `set_timeout`, but cancelled once `owner` is gone instead.
+    pub fn set_timeout_owned<F>(
+        &mut self,
+        owner: Option<DispatchOwner>,
+        timeout: Duration,
+        callback: F,
+    ) -> anyhow::Result<()>
+    where
+        F: EventDispatch + 'static,
+    {
+        let id = self.dispatch_list.push_scoped(owner, callback);
+        if let Err(e) = self.channels.update.set_timeout(timeout, id) {
+            self.dispatch_list.pop(id);
+            return Err(e);
+        }
         Ok(())
     }
 
-    pub fn execute_blocking_task<F>(&mut self, f: F)
+    /// Executes `callback` every `period` until the returned token is
+    /// cancelled, the current scene is removed or `callback` fails.
+    pub fn set_interval<F>(
+        &mut self,
+        period: Duration,
+        callback: F,
+    ) -> anyhow::Result<CancellationToken>
+    where
+        F: IntervalDispatch + 'static,
+    {
+        let owner = self.current_owner();
+        self.set_interval_owned(owner, period, callback)
+    }
+
+    /// Like `set_interval`, but stopped once `owner` is gone instead.
+    pub fn set_interval_owned<F>(
+        &mut self,
+        owner: Option<DispatchOwner>,
+        period: Duration,
+        callback: F,
+    ) -> anyhow::Result<CancellationToken>
+    where
+        F: IntervalDispatch + 'static,
+    {
+        let cancel = CancellationToken::new();
+        self.arm_interval(owner, period, cancel.clone(), Box::new(callback))?;
+        Ok(cancel)
+    }
+
+    fn arm_interval(
+        &mut self,
+        owner: Option<DispatchOwner>,
+        period: Duration,
+        cancel: CancellationToken,
+        mut callback: Box<dyn IntervalDispatch>,
+    ) -> anyhow::Result<()> {
+        self.set_timeout_owned(owner.clone(), period, move |main_ctx, root_scene| {
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+            callback(main_ctx, root_scene)?;
+            main_ctx.arm_interval(owner, period, cancel, callback)
+        })
+    }
+
+    /// Executes `callback` once per frame with the time elapsed since the
+    /// previous one, until the returned token is cancelled, the current
+    /// scene is removed or `callback` fails.
+    pub fn on_frame<F>(&mut self, callback: F) -> CancellationToken
+    where
+        F: FrameDispatch + 'static,
+    {
+        let owner = self.current_owner();
+        self.on_frame_owned(owner, callback)
+    }
+
+    /// Like `on_frame`, but stopped once `owner` is gone instead.
+    pub fn on_frame_owned<F>(
+        &mut self,
+        owner: Option<DispatchOwner>,
+        callback: F,
+    ) -> CancellationToken
+    where
+        F: FrameDispatch + 'static,
+    {
+        let cancel = CancellationToken::new();
+        self.frame_callbacks
+            .push(owner, cancel.clone(), Box::new(callback));
+        cancel
+    }
+
+    /// Owner of the dispatches registered while handling an event.
+    pub fn current_owner(&self) -> Option<DispatchOwner> {
+        self.current_scene.clone().map(DispatchOwner::Scene)
+    }
+
+    /// Owner of dispatches that belong to `widget` of the current scene.
+    pub fn widget_owner(&self, widget: &Arc<dyn Widget>) -> DispatchOwner {
+        DispatchOwner::Widget(SceneWeak::new(widget, self.current_scene.clone()))
+    }
+
+    /// Runs `tween` on the update server, `on_complete` is executed on the
+    /// main thread once it ends. Returns the id to cancel it with.
+    pub fn tween<T, F, C>(
+        &mut self,
+        tween: Tween<T>,
+        apply: F,
+        on_complete: C,
+    ) -> anyhow::Result<Uid>
+    where
+        T: Interpolate,
+        F: TweenApply<T> + 'static,
+        C: EventDispatch + 'static,
+    {
+        let id = self.dispatch_list.push(on_complete);
+        if let Err(e) = self.channels.update.tween(id, tween, apply) {
+            self.dispatch_list.pop(id);
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    pub fn cancel_tween(&mut self, id: Uid) -> anyhow::Result<()> {
+        self.dispatch_list.pop(id);
+        self.channels.update.cancel_tween(id)
+    }
+
+    /// Runs `sequence` on the update server, returns the id to cancel it
+    /// with.
+    pub fn run_sequence(&mut self, sequence: Sequence) -> anyhow::Result<Uid> {
+        let id = Uid::new();
+        let sequence = sequence.schedule(&mut self.dispatch_list);
+        let calls = sequence.pending_calls();
+        if let Err(e) = self.channels.update.start_sequence(id, sequence) {
+            calls.into_iter().for_each(|id| {
+                self.dispatch_list.pop(id);
+            });
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    pub fn cancel_sequence(&mut self, id: Uid) -> anyhow::Result<()> {
+        self.channels.update.cancel_sequence(id)
+    }
+
+    /// Frame pacing statistics of every active runner
+    pub fn runner_stats(&self) -> Vec<(RunnerId, RunnerStats)> {
+        self.executor.runner_stats()
+    }
+
+    pub fn reset_runner_stats(&self) {
+        self.executor.stats.reset_all()
+    }
+
+    /// Token cancelled when the current scene is removed, a standalone one
+    /// outside of any scene.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.current_scene
+            .as_ref()
+            .map(|scene| scene.cancellation_token())
+            .unwrap_or_default()
+    }
+
+    /// Runs `f` on the job system, `f` is skipped if the current scene is
+    /// removed before it starts, and should check the token while running.
+    /// Returns the token, which also cancels the task.
+    pub fn execute_blocking_task<F>(&mut self, f: F) -> CancellationToken
+    where
+        F: FnOnce(&CancellationToken) + Send + 'static,
+    {
+        let cancel = self.cancellation_token();
+        self.task_executor.spawn_with(cancel.clone(), f);
+        cancel
+    }
+
+    /// Queues an A* query on the job system through the update server,
+    /// `callback` is executed on the main thread once the path is found.
+    /// The query is dropped without calling back if the current scene is
+    /// removed in the meantime.
+    pub fn find_path<F>(
+        &mut self,
+        grid: Arc<NavGrid>,
+        query: PathQuery,
+        callback: F,
+    ) -> anyhow::Result<()>
+    where
+        F: PathCallback + 'static,
+    {
+        let cancel = self.cancellation_token();
+        self.channels
+            .update
+            .find_path(grid, query, cancel, callback)
+    }
+
+    /// Registers a handler for the events of the network server
+    /// (connections, disconnections and messages), returns its id for
+    /// `remove_net_handler`.
+    pub fn add_net_handler<F>(&mut self, handler: F) -> Uid
+    where
+        F: NetHandler + 'static,
+    {
+        self.net_handlers.add(handler)
+    }
+
+    pub fn remove_net_handler(&mut self, id: Uid) -> bool {
+        self.net_handlers.remove(id)
+    }
+
+    pub fn handle_net_events(&mut self, root_scene: &mut RootScene, events: &[NetEvent]) {
+        for event in events {
+            for handler in self.net_handlers.handlers() {
+                handler(self, root_scene, event)
+                    .context("network event handler failed")
+                    .log_error();
+            }
+        }
+    }
+
+    /// Polls `future` on the main thread, mostly used to await `ServerQuery`
+    /// results without blocking the event loop.
+    pub fn spawn_local<F>(&mut self, future: F)
     where
-        F: FnOnce() + Send + 'static,
+        F: Future<Output = anyhow::Result<()>> + 'static,
     {
-        self.task_executor.execute(f)
+        self.local_tasks.spawn(future);
+        self.local_tasks.poll_ready();
     }
 
-    pub fn execute_draw_sync<F, R>(&mut self, callback: F) -> anyhow::Result<R>
+    /// Executes `callback` on the draw server and waits for its result,
+    /// `None` if it wasn't executed because the draw server shut down.
+    pub fn execute_draw_sync<F, R>(&mut self, callback: F) -> anyhow::Result<Option<R>>
     where
         R: Send + 'static,
         F: FnOnce(&mut DrawContext, &mut Option<RootScene>) -> R + Send + 'static,
     {
+        if self.shutting_down {
+            return Ok(None);
+        }
         if let Some(server) = self.executor.main_runner.base.container.draw.as_mut() {
-            Ok(callback(&mut server.context, &mut server.root_scene))
-        } else {
-            let (sender, receiver) = mpsc::channels();
-            self.channels
-                .draw
-                .execute(move |context, root_scene| {
-                    let value = callback(context, root_scene);
-                    sender
-                        .send(value)
-                        .context("unable to send value back to event thread")
-                        .log_error();
-                    // this error can only happen if the below `recv` calls were not called
-                    // for some reason
-                })
-                .context("unable to execute sync-type callback")?;
-            receiver.recv().context("unable to receive callback result")
+            return Ok(Some(callback(&mut server.context, &mut server.root_scene)));
+        }
+        match self
+            .channels
+            .draw
+            .query(callback)
+            .and_then(ServerQuery::wait)
+        {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if is_disconnected_error(&e) => Ok(None),
+            Err(e) => Err(e.context("unable to execute sync-type callback")),
+        }
+    }
+
+    /// Records the input `event` with `--record`, returns whether the event
+    /// should be handled: real input is ignored during a replay.
+    fn record_event(&mut self, event: &GameEvent) -> bool {
+        let event = match event {
+            Event::WindowEvent { window_id, event }
+                if *window_id == self.display.get_window_id() =>
+            {
+                event
+            }
+            _ => return true,
+        };
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder
+                .record(event)
+                .context("unable to record event")
+                .log_warn();
+        }
+        self.replay.is_none() || RecordedEvent::from_window_event(event).is_none()
+    }
+
+    /// Handles the recorded events due by this frame with `--replay`.
+    fn replay_events(&mut self, root_scene: &mut RootScene) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.next_frame().log_warn();
+        }
+        if let Some(mut replay) = self.replay.take() {
+            replay
+                .feed(self, root_scene)
+                .context("unable to replay events")
+                .log_error();
+            if replay.finished() {
+                tracing::info!("replay finished");
+            } else {
+                self.replay = Some(replay);
+            }
+        }
+    }
+
+    /// Releases every main thread reference to draw server resources, then
+    /// stops the executor, letting the draw server check for leaked handles
+    /// before the window is destroyed.
+    fn shutdown(&mut self, root_scene: RootScene) {
+        self.shutting_down = true;
+        draw::begin_shutdown();
+        drop(root_scene);
+        self.dispatch_list = DispatchList::new();
+        self.frame_callbacks = FrameCallbacks::new();
+        self.net_handlers = NetHandlers::new();
+        self.focused_widget = None;
+        self.prev_focused_widget = None;
+        self.overlays.clear();
+        self.current_scene = None;
+        // the dummy VAO is owned by the main context itself, released here
+        // so that it doesn't get reported as leaked
+        drop(self.dummy_vao.take());
+        self.executor.stop();
+        if alloc::enabled() {
+            tracing::info!("allocations at shutdown:\n{}", alloc::report());
         }
     }
 
     pub fn run(
         mut self,
         event_loop: EventLoop<GameUserEvent>,
-        mut root_scene: RootScene,
-        guard: Option<WorkerGuard>,
+        root_scene: RootScene,
+        guard: LogGuard,
     ) -> ! {
         use winit::event_loop::ControlFlow;
+        let mut root_scene = Some(root_scene);
         event_loop.run(move |event, _target, control_flow| {
             // guarantee drop order
             fn unused<T>(_: &T) {}
@@ -202,25 +1014,43 @@ impl MainContext {
             unused(&guard);
             match event {
                 Event::MainEventsCleared => {
+                    capture::next_frame();
+                    self.sweep_expired();
+                    if let Some(root_scene) = root_scene.as_mut() {
+                        self.replay_events(root_scene);
+                        FrameCallbacks::run(&mut self, root_scene);
+                    }
                     self.executor
                         .main_runner
                         .base
                         .run_single(true)
                         .expect("error running main runner");
+                    self.local_tasks.poll_ready();
+                    self.executor
+                        .balance()
+                        .context("unable to balance servers")
+                        .log_error();
                 }
 
                 Event::UserEvent(GameUserEvent::Exit(code)) => {
                     control_flow.set_exit_with_code(code)
                 }
 
-                event => self
-                    .handle_event(&mut root_scene, event)
-                    .expect("error handling events"),
+                event => {
+                    if let Some(root_scene) = root_scene.as_mut() {
+                        if self.record_event(&event) {
+                            self.handle_event(root_scene, event)
+                                .expect("error handling events")
+                        }
+                    }
+                }
             }
 
             match *control_flow {
                 ControlFlow::ExitWithCode(_) => {
-                    self.executor.stop();
+                    if let Some(root_scene) = root_scene.take() {
+                        self.shutdown(root_scene);
+                    }
                 }
 
                 _ => {
@@ -1,11 +1,20 @@
 use std::time::Duration;
 
+pub mod balancer;
 pub mod dispatch;
+pub mod error_sink;
+pub mod event_bus;
 pub mod executor;
+pub mod interpolation;
+pub mod lockstep;
 pub mod main_ctx;
+pub mod query;
+pub mod replay;
 pub mod runner;
 pub mod server;
+pub mod stats;
 pub mod task;
+pub mod thread_bounds;
 
 const NUM_GAME_LOOPS: usize = 3;
 
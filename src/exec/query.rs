@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll, Wake, Waker},
+};
+
+use anyhow::{anyhow, Context};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    events::{ExecuteReturnEvent, GameUserEvent},
+    utils::{
+        error::ResultExt,
+        mpsc::{self, Receiver, Sender},
+        mutex::Mutex,
+        uid::Uid,
+    },
+};
+
+/// Waker of the task awaiting a `ServerQuery`, woken on the main thread by
+/// the `ExecuteReturn` event of the query.
+pub type QueryWaker = Arc<Mutex<Option<Waker>>>;
+
+/// Server side of a query, sends the result back to the main thread.
+pub struct QueryReturn<R> {
+    id: Uid,
+    sender: Sender<R>,
+    waker: QueryWaker,
+}
+
+/// Main thread side of a query, resolves once the server executed the query
+/// callback.
+///
+/// The future is meant to be polled on the main thread (see
+/// `MainContext::spawn_local`), `wait` can be used to block on the result
+/// instead.
+pub struct ServerQuery<R> {
+    id: Uid,
+    receiver: Receiver<R>,
+    waker: QueryWaker,
+}
+
+pub fn query<R>() -> (QueryReturn<R>, ServerQuery<R>) {
+    let id = Uid::new();
+    let (sender, receiver) = mpsc::channels();
+    let waker = QueryWaker::default();
+    (
+        QueryReturn {
+            id,
+            sender,
+            waker: waker.clone(),
+        },
+        ServerQuery {
+            id,
+            receiver,
+            waker,
+        },
+    )
+}
+
+impl<R> QueryReturn<R> {
+    pub fn send(self, value: R, proxy: &EventLoopProxy<GameUserEvent>) -> anyhow::Result<()> {
+        if self.sender.send(value).is_err() {
+            // the query was dropped, nobody is waiting for the result
+            return Ok(());
+        }
+        proxy
+            .send_event(GameUserEvent::ExecuteReturn(ExecuteReturnEvent {
+                id: self.id,
+                waker: self.waker,
+            }))
+            .map_err(|e| anyhow!("{e}"))
+            .context("unable to send query result event to main thread")
+    }
+}
+
+impl<R> ServerQuery<R> {
+    pub fn id(&self) -> Uid {
+        self.id
+    }
+
+    /// Blocks until the server responded, must not be called on the main
+    /// thread if the server is run by the main runner.
+    pub fn wait(self) -> anyhow::Result<R> {
+        self.receiver
+            .recv()
+            .context("server dropped the query without responding")
+    }
+}
+
+impl<R> Future for ServerQuery<R> {
+    type Output = anyhow::Result<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        // register the waker before checking, so that a result arriving in
+        // between can't be missed
+        *self.waker.lock() = Some(cx.waker().clone());
+        match self.receiver.try_recv() {
+            Ok(Some(value)) => Poll::Ready(Ok(value)),
+            Ok(None) => Poll::Pending,
+            Err(e) => Poll::Ready(Err(e.context("server dropped the query without responding"))),
+        }
+    }
+}
+
+type LocalTask = Pin<Box<dyn Future<Output = anyhow::Result<()>>>>;
+
+struct TaskWaker {
+    id: Uid,
+    ready: Arc<Mutex<Vec<Uid>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.lock().push(self.id);
+    }
+}
+
+/// Minimal executor for futures living on the main thread.
+///
+/// Tasks are polled after `ExecuteReturn` events and once per event loop
+/// iteration, so wakers triggered by other threads are picked up with a
+/// small delay.
+#[derive(Default)]
+pub struct LocalTasks {
+    tasks: HashMap<Uid, LocalTask>,
+    ready: Arc<Mutex<Vec<Uid>>>,
+}
+
+impl LocalTasks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<F>(&mut self, future: F)
+    where
+        F: Future<Output = anyhow::Result<()>> + 'static,
+    {
+        let id = Uid::new();
+        self.tasks.insert(id, Box::pin(future));
+        self.ready.lock().push(id);
+    }
+
+    pub fn poll_ready(&mut self) {
+        loop {
+            let ready = std::mem::take(&mut *self.ready.lock());
+            if ready.is_empty() {
+                break;
+            }
+
+            for id in ready {
+                let task = match self.tasks.get_mut(&id) {
+                    Some(task) => task,
+                    // already finished, woken more than once
+                    None => continue,
+                };
+                let waker = Waker::from(Arc::new(TaskWaker {
+                    id,
+                    ready: self.ready.clone(),
+                }));
+                if let Poll::Ready(result) =
+                    task.as_mut().poll(&mut TaskContext::from_waker(&waker))
+                {
+                    self.tasks.remove(&id);
+                    result.context("local task failed").log_error();
+                }
+            }
+        }
+    }
+}
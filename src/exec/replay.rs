@@ -0,0 +1,402 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+    time::Instant,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+        TouchPhase, VirtualKeyCode, WindowEvent,
+    },
+};
+
+use crate::{scene::main::RootScene, test::input::synthetic_device_id};
+
+use super::{lockstep::Lockstep, main_ctx::MainContext};
+
+const RECORDING_VERSION: u32 = 2;
+
+/// Window event of a recording, the subset of `WindowEvent` affecting the
+/// game.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedEvent {
+    Resized {
+        width: u32,
+        height: u32,
+    },
+    Focused {
+        focused: bool,
+    },
+    ReceivedCharacter {
+        ch: char,
+    },
+    KeyboardInput {
+        scancode: u32,
+        pressed: bool,
+        key: Option<VirtualKeyCode>,
+    },
+    ModifiersChanged {
+        modifiers: ModifiersState,
+    },
+    CursorMoved {
+        x: f64,
+        y: f64,
+    },
+    CursorEntered,
+    CursorLeft,
+    MouseWheel {
+        lines: bool,
+        x: f64,
+        y: f64,
+    },
+    MouseInput {
+        pressed: bool,
+        button: MouseButton,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEntry {
+    /// rendered frame in lockstep mode, main loop iteration otherwise
+    pub frame: u64,
+    /// seconds since the recording started
+    pub time: f64,
+    pub event: RecordedEvent,
+}
+
+/// First line of a recording, followed by one `RecordedEntry` per line so
+/// that the file stays valid if the game crashes.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lockstep: Option<u32>,
+}
+
+/// A session recorded with `--record`.
+#[derive(Debug)]
+pub struct Recording {
+    pub version: u32,
+    /// ticks per frame of the recorded session, if it ran in lockstep mode
+    pub lockstep: Option<u32>,
+    pub events: Vec<RecordedEntry>,
+}
+
+/// Frame the events are timestamped with.
+struct FrameCounter {
+    lockstep: Option<Arc<Lockstep>>,
+    main_loop: u64,
+}
+
+impl FrameCounter {
+    fn get(&self) -> u64 {
+        match self.lockstep.as_ref() {
+            Some(lockstep) => lockstep.frames(),
+            None => self.main_loop,
+        }
+    }
+}
+
+/// Writes the window events received by the main thread to a file.
+pub struct EventRecorder {
+    writer: BufWriter<File>,
+    frames: FrameCounter,
+    start: Instant,
+}
+
+/// Feeds the events of a recording back through `MainContext::handle_event`
+/// at the frame they were recorded on.
+pub struct EventReplay {
+    entries: VecDeque<RecordedEntry>,
+    /// recorded without lockstep, events are replayed by their time against
+    /// the virtual clock instead
+    by_time: bool,
+    lockstep: Arc<Lockstep>,
+}
+
+impl RecordedEvent {
+    #[allow(deprecated)]
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::Resized(size) => Self::Resized {
+                width: size.width,
+                height: size.height,
+            },
+            WindowEvent::Focused(focused) => Self::Focused { focused: *focused },
+            WindowEvent::ReceivedCharacter(ch) => Self::ReceivedCharacter { ch: *ch },
+            WindowEvent::KeyboardInput { input, .. } => Self::KeyboardInput {
+                scancode: input.scancode,
+                pressed: input.state == ElementState::Pressed,
+                key: input.virtual_keycode,
+            },
+            WindowEvent::ModifiersChanged(modifiers) => Self::ModifiersChanged {
+                modifiers: *modifiers,
+            },
+            WindowEvent::CursorMoved { position, .. } => Self::CursorMoved {
+                x: position.x,
+                y: position.y,
+            },
+            WindowEvent::CursorEntered { .. } => Self::CursorEntered,
+            WindowEvent::CursorLeft { .. } => Self::CursorLeft,
+            WindowEvent::MouseWheel { delta, .. } => match *delta {
+                MouseScrollDelta::LineDelta(x, y) => Self::MouseWheel {
+                    lines: true,
+                    x: x.into(),
+                    y: y.into(),
+                },
+                MouseScrollDelta::PixelDelta(delta) => Self::MouseWheel {
+                    lines: false,
+                    x: delta.x,
+                    y: delta.y,
+                },
+            },
+            WindowEvent::MouseInput { state, button, .. } => Self::MouseInput {
+                pressed: *state == ElementState::Pressed,
+                button: *button,
+            },
+            _ => return None,
+        })
+    }
+
+    #[allow(deprecated)]
+    pub fn to_window_event(&self) -> WindowEvent<'static> {
+        let device_id = synthetic_device_id();
+        let state = |pressed| {
+            if pressed {
+                ElementState::Pressed
+            } else {
+                ElementState::Released
+            }
+        };
+        match self {
+            Self::Resized { width, height } => {
+                WindowEvent::Resized(PhysicalSize::new(*width, *height))
+            }
+            Self::Focused { focused } => WindowEvent::Focused(*focused),
+            Self::ReceivedCharacter { ch } => WindowEvent::ReceivedCharacter(*ch),
+            Self::KeyboardInput {
+                scancode,
+                pressed,
+                key,
+            } => WindowEvent::KeyboardInput {
+                device_id,
+                input: KeyboardInput {
+                    scancode: *scancode,
+                    state: state(*pressed),
+                    virtual_keycode: *key,
+                    modifiers: ModifiersState::empty(),
+                },
+                is_synthetic: false,
+            },
+            Self::ModifiersChanged { modifiers } => WindowEvent::ModifiersChanged(*modifiers),
+            Self::CursorMoved { x, y } => WindowEvent::CursorMoved {
+                device_id,
+                position: PhysicalPosition::new(*x, *y),
+                modifiers: ModifiersState::empty(),
+            },
+            Self::CursorEntered => WindowEvent::CursorEntered { device_id },
+            Self::CursorLeft => WindowEvent::CursorLeft { device_id },
+            Self::MouseWheel { lines, x, y } => WindowEvent::MouseWheel {
+                device_id,
+                delta: if *lines {
+                    MouseScrollDelta::LineDelta(*x as f32, *y as f32)
+                } else {
+                    MouseScrollDelta::PixelDelta(PhysicalPosition::new(*x, *y))
+                },
+                phase: TouchPhase::Moved,
+                modifiers: ModifiersState::empty(),
+            },
+            Self::MouseInput { pressed, button } => WindowEvent::MouseInput {
+                device_id,
+                state: state(*pressed),
+                button: *button,
+                modifiers: ModifiersState::empty(),
+            },
+        }
+    }
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("unable to read recording {}", path.display()))?;
+        let mut lines = content.lines().enumerate();
+        let header = match lines.next() {
+            Some((_, header)) => serde_json::from_str::<Header>(header)
+                .with_context(|| format!("invalid header in recording {}", path.display()))?,
+            None => anyhow::bail!("recording {} is empty", path.display()),
+        };
+        anyhow::ensure!(
+            header.version == RECORDING_VERSION,
+            "unsupported recording version {}",
+            header.version
+        );
+        anyhow::ensure!(
+            header.lockstep != Some(0),
+            "recording {} ticks 0 times per frame",
+            path.display()
+        );
+        let events = lines
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).with_context(|| {
+                    format!("invalid event on line {} of {}", index + 1, path.display())
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut recording = Self {
+            version: header.version,
+            lockstep: header.lockstep,
+            events,
+        };
+        recording.events.sort_by_key(|entry| entry.frame);
+        Ok(recording)
+    }
+}
+
+impl EventRecorder {
+    pub fn create(path: &Path, lockstep: Option<Arc<Lockstep>>) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("unable to create recording {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        let header = Header {
+            version: RECORDING_VERSION,
+            lockstep: lockstep.as_ref().map(|lockstep| lockstep.ticks_per_frame),
+        };
+        serde_json::to_writer(&mut writer, &header)?;
+        if lockstep.is_none() {
+            tracing::warn!("recording without --lockstep, the replay won't be deterministic");
+        }
+        Ok(Self {
+            writer,
+            frames: FrameCounter {
+                lockstep,
+                main_loop: 0,
+            },
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, event: &WindowEvent) -> anyhow::Result<()> {
+        if let Some(event) = RecordedEvent::from_window_event(event) {
+            let entry = RecordedEntry {
+                frame: self.frames.get(),
+                time: self.start.elapsed().as_secs_f64(),
+                event,
+            };
+            self.writer.write_all(b"\n")?;
+            serde_json::to_writer(&mut self.writer, &entry)?;
+        }
+        Ok(())
+    }
+
+    /// Called once per main loop iteration, flushes the events of the frame.
+    pub fn next_frame(&mut self) -> anyhow::Result<()> {
+        self.frames.main_loop += 1;
+        self.writer.flush().context("unable to write recording")
+    }
+}
+
+impl EventReplay {
+    pub fn new(recording: Recording, lockstep: Arc<Lockstep>) -> Self {
+        Self {
+            entries: recording.events.into(),
+            by_time: recording.lockstep.is_none(),
+            lockstep,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Handles every event due by the current frame.
+    pub fn feed(
+        &mut self,
+        main_ctx: &mut MainContext,
+        root_scene: &mut RootScene,
+    ) -> anyhow::Result<()> {
+        let frame = self.lockstep.frames();
+        let time = frame as f64 * Lockstep::FRAME_DURATION;
+        let window_id = main_ctx.display.get_window_id();
+        while let Some(entry) = self.entries.front() {
+            let due = if self.by_time {
+                entry.time <= time
+            } else {
+                entry.frame <= frame
+            };
+            if !due {
+                break;
+            }
+            let event = entry.event.to_window_event();
+            self.entries.pop_front();
+            main_ctx.handle_event(root_scene, Event::WindowEvent { window_id, event })?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test() {
+    let events = [
+        RecordedEvent::CursorMoved { x: 12.0, y: 34.5 },
+        RecordedEvent::MouseInput {
+            pressed: true,
+            button: MouseButton::Other(4),
+        },
+        RecordedEvent::MouseInput {
+            pressed: false,
+            button: MouseButton::Left,
+        },
+        RecordedEvent::KeyboardInput {
+            scancode: 30,
+            pressed: false,
+            key: Some(VirtualKeyCode::A),
+        },
+        RecordedEvent::ModifiersChanged {
+            modifiers: ModifiersState::CTRL | ModifiersState::SHIFT,
+        },
+        RecordedEvent::MouseWheel {
+            lines: true,
+            x: 0.0,
+            y: -1.0,
+        },
+        RecordedEvent::CursorLeft,
+        RecordedEvent::ReceivedCharacter { ch: 'a' },
+    ];
+    let path = std::env::temp_dir().join(format!("replay-{}.jsonl", std::process::id()));
+    let mut recorder = EventRecorder::create(&path, None).unwrap();
+    for event in events.iter() {
+        recorder.record(&event.to_window_event()).unwrap();
+        recorder.next_frame().unwrap();
+    }
+    // not part of a recording
+    recorder.record(&WindowEvent::HoveredFileCancelled).unwrap();
+    recorder.next_frame().unwrap();
+    drop(recorder);
+
+    let recording = Recording::load(&path);
+    std::fs::remove_file(&path).ok();
+    let recording = recording.unwrap();
+    assert_eq!(recording.lockstep, None);
+    let frames = recording
+        .events
+        .iter()
+        .map(|entry| entry.frame)
+        .collect::<Vec<_>>();
+    assert_eq!(frames, (0..events.len() as u64).collect::<Vec<_>>());
+    let replayed = recording
+        .events
+        .iter()
+        .map(|entry| RecordedEvent::from_window_event(&entry.event.to_window_event()))
+        .collect::<Option<Vec<_>>>();
+    assert_eq!(replayed.as_deref(), Some(&events[..]));
+}
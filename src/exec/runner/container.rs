@@ -1,12 +1,43 @@
-use crate::exec::server::{audio, draw, update, GameServer, SendGameServer, ServerKind};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use crate::{
+    exec::{
+        server::{audio, draw, network, update, GameServer, SendGameServer, ServerKind},
+        stats::NUM_SERVER_KINDS,
+    },
+    utils::profile::profile_span,
+};
 
 use super::ServerMover;
 
+/// Paused state of every server kind, shared between the executor and the
+/// containers of all runners.
+#[derive(Clone, Default)]
+pub struct PauseFlags(Arc<[AtomicBool; NUM_SERVER_KINDS]>);
+
+impl PauseFlags {
+    pub fn set(&self, kind: ServerKind, paused: bool) {
+        self.0[kind as usize].store(paused, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self, kind: ServerKind) -> bool {
+        self.0[kind as usize].load(Ordering::Relaxed)
+    }
+}
+
 #[derive(Default)]
 pub struct ServerContainer {
     pub audio: Option<audio::Server>,
     pub draw: Option<draw::Server>,
     pub update: Option<update::Server>,
+    pub network: Option<network::Server>,
+    pub paused: PauseFlags,
 }
 
 impl ServerMover for ServerContainer {
@@ -15,6 +46,7 @@ impl ServerMover for ServerContainer {
             ServerKind::Audio => self.audio.take().map(|s| s.to_send()).transpose(),
             ServerKind::Draw => self.draw.take().map(|s| s.to_send()).transpose(),
             ServerKind::Update => self.update.take().map(|s| s.to_send()).transpose(),
+            ServerKind::Network => self.network.take().map(|s| s.to_send()).transpose(),
         }
     }
 
@@ -23,26 +55,47 @@ impl ServerMover for ServerContainer {
             SendGameServer::Audio(server) => self.audio = Some(*server),
             SendGameServer::Draw(server) => self.draw = Some(server.to_nonsend()?),
             SendGameServer::Update(server) => self.update = Some(*server),
+            SendGameServer::Network(server) => self.network = Some(*server),
         }
         Ok(())
     }
 }
 
 impl ServerContainer {
+    pub fn new(paused: PauseFlags) -> Self {
+        Self {
+            paused,
+            ..Default::default()
+        }
+    }
+
+    /// Runs every server once, returning the time each of them took
+    /// (indexed by `ServerKind`). Paused servers only drain their channels.
     pub fn run_single(
         &mut self,
         is_main_runner: bool,
         runner_frequency: f64,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<[Option<f64>; NUM_SERVER_KINDS]> {
         fn run<S: GameServer>(
+            span: tracing::Span,
             server: &mut Option<S>,
+            paused: bool,
             single: bool,
             runner_frequency: f64,
-        ) -> anyhow::Result<()> {
-            if let Some(server) = server {
-                server.run(single, runner_frequency)?;
-            }
-            Ok(())
+        ) -> anyhow::Result<Option<f64>> {
+            server
+                .as_mut()
+                .map(|server| {
+                    let _span = span.entered();
+                    let start = Instant::now();
+                    if paused {
+                        server.drain(single)?;
+                    } else {
+                        server.run(single, runner_frequency)?;
+                    }
+                    Ok(start.elapsed().as_secs_f64())
+                })
+                .transpose()
         }
 
         let single = !is_main_runner
@@ -50,18 +103,58 @@ impl ServerContainer {
                 self.audio.is_some(),
                 self.draw.is_some(),
                 self.update.is_some(),
+                self.network.is_some(),
             ]
             .into_iter()
             .filter(|b| *b)
             .count()
                 <= 1;
-        run(&mut self.audio, single, runner_frequency)?;
-        run(&mut self.draw, single, runner_frequency)?;
-        run(&mut self.update, single, runner_frequency)?;
+        let mut costs = [None; NUM_SERVER_KINDS];
+        let paused = &self.paused;
+        costs[ServerKind::Audio as usize] = run(
+            profile_span!("audio server"),
+            &mut self.audio,
+            paused.is_paused(ServerKind::Audio),
+            single,
+            runner_frequency,
+        )?;
+        costs[ServerKind::Draw as usize] = run(
+            profile_span!("draw server"),
+            &mut self.draw,
+            paused.is_paused(ServerKind::Draw),
+            single,
+            runner_frequency,
+        )?;
+        costs[ServerKind::Update as usize] = run(
+            profile_span!("update server"),
+            &mut self.update,
+            paused.is_paused(ServerKind::Update),
+            single,
+            runner_frequency,
+        )?;
+        costs[ServerKind::Network as usize] = run(
+            profile_span!("network server"),
+            &mut self.network,
+            paused.is_paused(ServerKind::Network),
+            single,
+            runner_frequency,
+        )?;
+        Ok(costs)
+    }
+
+    /// Shuts down servers that need an orderly shutdown (the draw server),
+    /// must be called from the thread running this container.
+    pub fn shutdown(&mut self) -> anyhow::Result<()> {
+        if let Some(mut draw) = self.draw.take() {
+            draw.shutdown()?;
+        }
         Ok(())
     }
 
     pub fn does_run(&self) -> bool {
-        self.audio.is_some() || self.update.is_some() || self.draw.is_some()
+        self.audio.is_some()
+            || self.update.is_some()
+            || self.draw.is_some()
+            || self.network.is_some()
     }
 }
@@ -6,20 +6,30 @@ use std::{
 use anyhow::{bail, Context};
 
 use crate::utils::{
-    clock::SteadyClock,
-    mpsc,
+    affinity::{
+        current_core, set_current_thread_affinity, set_current_thread_priority, CoreSet,
+        ThreadPriority,
+    },
+    clock::{Clock, GameClock},
+    error::ResultExt,
+    mpsc::{self, Backpressure},
+    profile::profile_span,
     sync::{ClockSync, OFClockSync},
 };
 
-use self::container::ServerContainer;
+use self::container::{PauseFlags, ServerContainer};
 
 use super::{
     server::{SendGameServer, ServerKind},
+    stats::SharedRunnerStats,
     DEFAULT_RECV_TIMEOUT,
 };
 
 pub mod container;
 
+// control messages to the runner, rarely more than a few in flight
+const CHANNEL_CAPACITY: usize = 64;
+
 pub enum FromRunnerMsg {
     MoveServer(Option<SendGameServer>),
 }
@@ -27,20 +37,86 @@ pub enum ToRunnerMsg {
     RequestServer(ServerKind),
     MoveServer(SendGameServer),
     SetFrequency(f64),
+    SetAffinity(CoreSet),
+    SetPriority(ThreadPriority),
     Stop,
 }
 
+/// Scheduling hints of a runner thread, `None` leaves the OS defaults.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RunnerConfig {
+    pub affinity: Option<CoreSet>,
+    pub priority: Option<ThreadPriority>,
+}
+
+impl RunnerConfig {
+    /// Applies the hints to the calling thread, failures are only logged
+    /// since they don't prevent the runner from working.
+    pub fn apply(&self, id: RunnerId) {
+        if let Some(cores) = self.affinity {
+            set_current_thread_affinity(&cores)
+                .with_context(|| format!("unable to pin runner {id} to cores {cores:?}"))
+                .log_warn();
+        }
+        if let Some(priority) = self.priority {
+            set_current_thread_priority(priority)
+                .with_context(|| format!("unable to set priority of runner {id} to {priority:?}"))
+                .log_warn();
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Runner {
     pub container: ServerContainer,
-    pub sync: OFClockSync<SteadyClock>,
+    pub sync: OFClockSync<GameClock>,
     pub frequency: f64,
+    pub stats: SharedRunnerStats,
+    clock: GameClock,
+    last_tick_end: Option<f64>,
 }
 
 impl Runner {
+    pub fn new(container: ServerContainer, stats: SharedRunnerStats) -> Self {
+        Self {
+            container,
+            stats,
+            ..Default::default()
+        }
+    }
+
+    /// Runner timed by `clock` instead of the wall clock.
+    pub fn with_clock(
+        container: ServerContainer,
+        stats: SharedRunnerStats,
+        clock: GameClock,
+    ) -> Self {
+        Self {
+            container,
+            stats,
+            sync: OFClockSync::new(clock.clone()),
+            clock,
+            ..Default::default()
+        }
+    }
+
     pub fn run_single(&mut self, is_main_runner: bool) -> anyhow::Result<()> {
-        self.container.run_single(is_main_runner, self.frequency)?;
+        let tick_start = self.clock.now();
+        let server_costs = {
+            let _span = profile_span!("runner tick").entered();
+            self.container.run_single(is_main_runner, self.frequency)?
+        };
+        let work = self.clock.now() - tick_start;
         self.sync.sync(self.frequency);
+
+        let tick_end = self.clock.now();
+        let mut stats = self.stats.lock();
+        stats.record_server_costs(server_costs);
+        stats.record_core(current_core());
+        // the first tick has no previous tick to measure the interval against
+        if let Some(last_tick_end) = self.last_tick_end.replace(tick_end) {
+            stats.record_tick(tick_end - last_tick_end, work, self.frequency);
+        }
         Ok(())
     }
 }
@@ -72,7 +148,14 @@ impl ThreadRunner {
                 .expect("thread runner channel was unexpectedly closed");
             for msg in pending_msgs {
                 match msg {
-                    ToRunnerMsg::Stop => return,
+                    ToRunnerMsg::Stop => {
+                        self.base
+                            .container
+                            .shutdown()
+                            .context("error shutting down servers")
+                            .log_error();
+                        return;
+                    }
                     ToRunnerMsg::MoveServer(server) => self
                         .base
                         .container
@@ -88,6 +171,18 @@ impl ThreadRunner {
                             .expect("thread runner channel was unexpectedly closed");
                     }
                     ToRunnerMsg::SetFrequency(frequency) => self.base.frequency = frequency,
+                    ToRunnerMsg::SetAffinity(cores) => {
+                        set_current_thread_affinity(&cores)
+                            .with_context(|| format!("unable to set runner affinity to {cores:?}"))
+                            .log_warn();
+                    }
+                    ToRunnerMsg::SetPriority(priority) => {
+                        set_current_thread_priority(priority)
+                            .with_context(|| {
+                                format!("unable to set runner priority to {priority:?}")
+                            })
+                            .log_warn();
+                    }
                 }
             }
 
@@ -99,15 +194,22 @@ impl ThreadRunner {
 }
 
 impl ThreadRunnerHandle {
-    pub fn new(id: RunnerId) -> Self {
-        let (to_send, to_recv) = mpsc::channels();
+    pub fn new(
+        id: RunnerId,
+        config: RunnerConfig,
+        stats: SharedRunnerStats,
+        paused: PauseFlags,
+    ) -> Self {
+        let (to_send, to_recv) = mpsc::bounded_channels(CHANNEL_CAPACITY, Backpressure::Block);
+        // one reply per server request, the runner thread never waits on it
         let (from_send, from_recv) = mpsc::channels();
         Self {
             join_handle: thread::Builder::new()
                 .name(format!("runner thread {id}"))
                 .spawn(move || {
+                    config.apply(id);
                     ThreadRunner {
-                        base: Runner::default(),
+                        base: Runner::new(ServerContainer::new(paused), stats),
                         sender: from_send,
                         receiver: to_recv,
                     }
@@ -143,6 +245,14 @@ impl ThreadRunnerHandle {
     pub fn set_frequency(&self, frequency: f64) -> anyhow::Result<()> {
         self.send(ToRunnerMsg::SetFrequency(frequency))
     }
+
+    pub fn set_affinity(&self, cores: CoreSet) -> anyhow::Result<()> {
+        self.send(ToRunnerMsg::SetAffinity(cores))
+    }
+
+    pub fn set_priority(&self, priority: ThreadPriority) -> anyhow::Result<()> {
+        self.send(ToRunnerMsg::SetPriority(priority))
+    }
 }
 
 pub trait ServerMover {
@@ -157,6 +267,7 @@ pub trait ServerMover {
                     ServerKind::Audio => "audio",
                     ServerKind::Draw => "draw",
                     ServerKind::Update => "update",
+                    ServerKind::Network => "network",
                 }
             )
         })
@@ -220,3 +331,24 @@ pub struct MainRunner {
 
 pub type RunnerId = u8;
 pub const MAIN_RUNNER_ID: RunnerId = 3;
+
+#[test]
+fn test() {
+    use crate::utils::clock::VirtualClock;
+
+    let clock = VirtualClock::new();
+    let mut runner = Runner::with_clock(
+        ServerContainer::new(Default::default()),
+        Default::default(),
+        GameClock::Virtual(clock.clone()),
+    );
+    runner.frequency = 20.0;
+    for _ in 0..20 {
+        runner.run_single(false).unwrap();
+    }
+    assert!((clock.now() - 1.0).abs() < 1e-6);
+    let stats = runner.stats.lock();
+    assert_eq!(stats.num_ticks, 19);
+    assert!((stats.mean_interval - 0.05).abs() < 1e-6);
+    assert!(stats.jitter() < 1e-6);
+}
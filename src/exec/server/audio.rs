@@ -1,10 +1,32 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
 use anyhow::Context;
+use glam::Vec2;
+use trait_set::trait_set;
 use winit::event_loop::EventLoopProxy;
 
 use crate::{
+    audio::{
+        backend::AudioBackend,
+        bus::{self, DuckingRule},
+        clock::{ScheduleAt, ScheduledAction, SharedBeatClock},
+        mixer::Mixer,
+        spatial::Emitter,
+        stream::MusicStream,
+        ChannelId, PlayOptions, PlaybackId, SoundHandle,
+    },
     events::GameUserEvent,
-    exec::dispatch::DispatchMsg,
-    utils::mpsc::{Receiver, Sender},
+    exec::{
+        dispatch::DispatchMsg,
+        query::{self, ServerQuery},
+        task::TaskExecutor,
+    },
+    utils::{
+        error::ResultExt,
+        mpsc::{Receiver, Sender},
+        mutex::Mutex,
+        uid::Uid,
+    },
 };
 
 use super::{BaseGameServer, GameServer, GameServerChannel, GameServerSendChannel, SendGameServer};
@@ -12,17 +34,45 @@ use super::{BaseGameServer, GameServer, GameServerChannel, GameServerSendChannel
 pub enum SendMsg {
     Dispatch(DispatchMsg),
 }
+trait_set! {
+    pub trait AudioDispatch = FnOnce(&mut Server) + Send;
+}
+
 pub enum RecvMsg {
     SetFrequencyProfiling(bool),
+    Play(PlaybackId, SoundHandle, PlayOptions),
+    Stop(PlaybackId),
+    SetChannelGain(ChannelId, f32),
+    SetChannelPan(ChannelId, f32),
+    SetChannelMuted(ChannelId, bool),
+    SetListener(Vec2),
+    SetEmitterPosition(PlaybackId, Vec2),
+    AddDuckingRule(DuckingRule),
+    RemoveDuckingRule(ChannelId, ChannelId),
+    PlayMusic(PlaybackId, PathBuf, Duration),
+    CrossfadeTo(PlaybackId, PathBuf, Duration),
+    StopMusic(Duration),
+    Schedule(ScheduleAt, ScheduledAction),
+    SetTempo(f64),
+    ResetBeat,
+    Execute(Box<dyn AudioDispatch>),
 }
 
 pub struct Server {
     pub base: BaseGameServer<SendMsg, RecvMsg>,
+    /// shared with the output stream callback
+    pub mixer: Arc<Mutex<Mixer>>,
+    pub backend: AudioBackend,
+    /// streamed tracks, kept until their voice ends
+    music: Vec<MusicStream>,
+    current_music: Option<PlaybackId>,
+    task_executor: TaskExecutor,
 }
 
 pub struct ServerChannel {
     sender: Sender<RecvMsg>,
     receiver: Receiver<SendMsg>,
+    clock: SharedBeatClock,
 }
 
 impl GameServerChannel<SendMsg, RecvMsg> for ServerChannel {
@@ -40,29 +90,146 @@ impl GameServerSendChannel<RecvMsg> for ServerChannel {
 impl GameServer for Server {
     fn run(&mut self, _: bool, runner_frequency: f64) -> anyhow::Result<()> {
         self.base.run("Audio", runner_frequency);
+        self.process_messages()?;
+        self.update_music();
+        self.backend.update(&self.mixer);
+        Ok(())
+    }
+
+    fn drain(&mut self, _: bool) -> anyhow::Result<()> {
+        self.process_messages()
+    }
+
+    fn to_send(self) -> anyhow::Result<SendGameServer> {
+        Ok(SendGameServer::Audio(Box::new(self)))
+    }
+}
+
+impl Server {
+    fn process_messages(&mut self) -> anyhow::Result<()> {
         let messages = self
             .base
             .receiver
             .try_iter(None)
-            .context("thread runner channel was unexpectedly closed")?;
+            .context("thread runner channel was unexpectedly closed")?
+            // collected so that `Execute` callbacks can borrow the server
+            .collect::<Vec<_>>();
         for message in messages {
             match message {
                 RecvMsg::SetFrequencyProfiling(fp) => {
                     self.base.frequency_profiling = fp;
                 }
+                RecvMsg::Play(id, sound, options) => self.mixer.lock().play(id, sound, options),
+                RecvMsg::Stop(id) => {
+                    self.mixer.lock().stop(id);
+                }
+                RecvMsg::SetChannelGain(channel, gain) => {
+                    self.mixer.lock().channel_mut(channel).gain = gain;
+                }
+                RecvMsg::SetChannelPan(channel, pan) => {
+                    self.mixer.lock().channel_mut(channel).pan = pan.clamp(-1.0, 1.0);
+                }
+                RecvMsg::SetChannelMuted(channel, muted) => {
+                    self.mixer.lock().channel_mut(channel).muted = muted;
+                }
+                RecvMsg::SetListener(position) => self.mixer.lock().listener.position = position,
+                RecvMsg::SetEmitterPosition(id, position) => {
+                    self.mixer.lock().set_emitter_position(id, position);
+                }
+                RecvMsg::AddDuckingRule(rule) => self.mixer.lock().add_ducking_rule(rule),
+                RecvMsg::RemoveDuckingRule(trigger, target) => {
+                    self.mixer.lock().remove_ducking_rule(trigger, target);
+                }
+                RecvMsg::PlayMusic(id, path, fade_in) => {
+                    self.start_music(id, path, fade_in, None);
+                }
+                RecvMsg::CrossfadeTo(id, path, duration) => {
+                    self.start_music(id, path, duration, Some(duration));
+                }
+                RecvMsg::StopMusic(fade_out) => self.stop_music(Some(fade_out)),
+                RecvMsg::Schedule(at, action) => {
+                    let mut mixer = self.mixer.lock();
+                    let frame = mixer.clock().resolve(at);
+                    mixer.schedule(frame, action);
+                }
+                RecvMsg::SetTempo(bpm) => self.mixer.lock().clock().set_bpm(bpm),
+                RecvMsg::ResetBeat => self.mixer.lock().clock().reset_beat(),
+                RecvMsg::Execute(callback) => callback(self),
             }
         }
         Ok(())
     }
-    fn to_send(self) -> anyhow::Result<SendGameServer> {
-        Ok(SendGameServer::Audio(Box::new(self)))
+
+    /// Replaces the current music, which is stopped right away or faded out
+    /// over `fade_out`.
+    fn start_music(
+        &mut self,
+        id: PlaybackId,
+        path: PathBuf,
+        fade_in: Duration,
+        fade_out: Option<Duration>,
+    ) {
+        self.stop_music(fade_out);
+        let stream = MusicStream::new(id, path, true, &self.task_executor);
+        self.mixer
+            .lock()
+            .play_stream(id, stream.buffer.clone(), bus::MUSIC, fade_in);
+        self.music.push(stream);
+        self.current_music = Some(id);
     }
-}
 
-impl Server {
-    pub fn new(proxy: EventLoopProxy<GameUserEvent>) -> (Self, ServerChannel) {
+    fn stop_music(&mut self, fade_out: Option<Duration>) {
+        if let Some(current) = self.current_music.take() {
+            let mut mixer = self.mixer.lock();
+            match fade_out {
+                Some(duration) => mixer.fade_out(current, duration),
+                None => mixer.stop(current),
+            };
+        }
+    }
+
+    fn update_music(&mut self) {
+        for stream in self.music.iter_mut() {
+            stream
+                .pump(&self.task_executor)
+                .context("unable to stream music")
+                .log_warn();
+        }
+
+        let mixer = self.mixer.lock();
+        self.music.retain(|stream| mixer.is_playing(stream.id));
+        if let Some(current) = self.current_music {
+            if !mixer.is_playing(current) {
+                self.current_music = None;
+            }
+        }
+    }
+
+    /// `null_backend` disables audio output, e.g. in headless mode.
+    pub fn new(
+        proxy: EventLoopProxy<GameUserEvent>,
+        task_executor: TaskExecutor,
+        null_backend: bool,
+    ) -> (Self, ServerChannel) {
         let (base, sender, receiver) = BaseGameServer::new(proxy);
-        (Self { base }, ServerChannel { receiver, sender })
+        let mixer = Arc::new(Mutex::new(Mixer::new(0)));
+        let backend = AudioBackend::new(&mixer, null_backend);
+        let clock = mixer.lock().clock().clone();
+        (
+            Self {
+                base,
+                mixer,
+                backend,
+                music: Vec::new(),
+                current_music: None,
+                task_executor,
+            },
+            ServerChannel {
+                receiver,
+                sender,
+                clock,
+            },
+        )
     }
 }
 
@@ -71,4 +238,179 @@ impl ServerChannel {
         self.send(RecvMsg::SetFrequencyProfiling(fp))
             .context("unable to send frequency profiling request")
     }
+
+    pub fn play(&self, sound: SoundHandle) -> anyhow::Result<PlaybackId> {
+        self.play_with(sound, PlayOptions::default())
+    }
+
+    /// Starts playing `sound`, the returned id can be used to stop it.
+    pub fn play_with(
+        &self,
+        sound: SoundHandle,
+        options: PlayOptions,
+    ) -> anyhow::Result<PlaybackId> {
+        let id = Uid::new();
+        self.send(RecvMsg::Play(id, sound, options))
+            .context("unable to send play request")?;
+        Ok(id)
+    }
+
+    /// Plays `sound` at a world position, see `audio::spatial`.
+    pub fn play_at(&self, sound: SoundHandle, position: Vec2) -> anyhow::Result<PlaybackId> {
+        self.play_with(
+            sound,
+            PlayOptions {
+                emitter: Some(Emitter::new(position)),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn stop(&self, id: PlaybackId) -> anyhow::Result<()> {
+        self.send(RecvMsg::Stop(id))
+            .context("unable to send stop request")
+    }
+
+    pub fn set_channel_gain(&self, channel: ChannelId, gain: f32) -> anyhow::Result<()> {
+        self.send(RecvMsg::SetChannelGain(channel, gain))
+            .context("unable to send channel gain")
+    }
+
+    /// `pan` goes from -1 (left) to 1 (right).
+    pub fn set_channel_pan(&self, channel: ChannelId, pan: f32) -> anyhow::Result<()> {
+        self.send(RecvMsg::SetChannelPan(channel, pan))
+            .context("unable to send channel pan")
+    }
+
+    pub fn set_channel_muted(&self, channel: ChannelId, muted: bool) -> anyhow::Result<()> {
+        self.send(RecvMsg::SetChannelMuted(channel, muted))
+            .context("unable to send channel mute")
+    }
+
+    /// Sets the volume of a named bus (see `audio::bus`).
+    pub fn set_bus_volume(&self, name: &str, volume: f32) -> anyhow::Result<()> {
+        let channel = bus::by_name(name).with_context(|| format!("unknown audio bus {name}"))?;
+        self.set_channel_gain(channel, volume)
+    }
+
+    pub fn set_bus_muted(&self, name: &str, muted: bool) -> anyhow::Result<()> {
+        let channel = bus::by_name(name).with_context(|| format!("unknown audio bus {name}"))?;
+        self.set_channel_muted(channel, muted)
+    }
+
+    pub fn add_ducking_rule(&self, rule: DuckingRule) -> anyhow::Result<()> {
+        self.send(RecvMsg::AddDuckingRule(rule))
+            .context("unable to send ducking rule")
+    }
+
+    pub fn remove_ducking_rule(&self, trigger: ChannelId, target: ChannelId) -> anyhow::Result<()> {
+        self.send(RecvMsg::RemoveDuckingRule(trigger, target))
+            .context("unable to send ducking rule removal")
+    }
+
+    /// Streams the OGG/Vorbis file at `path` (looping) in place of the
+    /// current music, which is stopped right away.
+    pub fn play_music(
+        &self,
+        path: impl Into<PathBuf>,
+        fade_in: Duration,
+    ) -> anyhow::Result<PlaybackId> {
+        let id = Uid::new();
+        self.send(RecvMsg::PlayMusic(id, path.into(), fade_in))
+            .context("unable to send play music request")?;
+        Ok(id)
+    }
+
+    /// Like `play_music`, but the current music fades out while the new one
+    /// fades in.
+    pub fn crossfade_to(
+        &self,
+        path: impl Into<PathBuf>,
+        duration: Duration,
+    ) -> anyhow::Result<PlaybackId> {
+        let id = Uid::new();
+        self.send(RecvMsg::CrossfadeTo(id, path.into(), duration))
+            .context("unable to send crossfade request")?;
+        Ok(id)
+    }
+
+    pub fn stop_music(&self, fade_out: Duration) -> anyhow::Result<()> {
+        self.send(RecvMsg::StopMusic(fade_out))
+            .context("unable to send stop music request")
+    }
+
+    /// Beat clock of the mixer, e.g. for the update server to follow the
+    /// music.
+    pub fn beat_clock(&self) -> &SharedBeatClock {
+        &self.clock
+    }
+
+    /// Changes the tempo of the beat clock, the current beat is kept.
+    pub fn set_tempo(&self, bpm: f64) -> anyhow::Result<()> {
+        self.send(RecvMsg::SetTempo(bpm))
+            .context("unable to send tempo change")
+    }
+
+    /// Makes the current frame beat 0, e.g. when a track starts.
+    pub fn reset_beat(&self) -> anyhow::Result<()> {
+        self.send(RecvMsg::ResetBeat)
+            .context("unable to send beat reset")
+    }
+
+    /// Applies `action` on the exact frame `at` refers to, which is resolved
+    /// when the audio server receives the request.
+    pub fn schedule(&self, at: ScheduleAt, action: ScheduledAction) -> anyhow::Result<()> {
+        self.send(RecvMsg::Schedule(at, action))
+            .context("unable to send scheduled audio event")
+    }
+
+    /// Like `play_with`, but the sound starts on the frame `at` refers to.
+    pub fn schedule_play(
+        &self,
+        at: ScheduleAt,
+        sound: SoundHandle,
+        options: PlayOptions,
+    ) -> anyhow::Result<PlaybackId> {
+        let id = Uid::new();
+        self.schedule(at, ScheduledAction::Play(id, sound, options))?;
+        Ok(id)
+    }
+
+    pub fn is_playing(&self, id: PlaybackId) -> anyhow::Result<ServerQuery<bool>> {
+        self.query(move |server| server.mixer.lock().is_playing(id))
+    }
+
+    /// Executes `callback` on the audio server, the returned future resolves
+    /// to its result.
+    pub fn query<F, R>(&self, callback: F) -> anyhow::Result<ServerQuery<R>>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Server) -> R + Send + 'static,
+    {
+        let (ret, query) = query::query();
+        self.send(RecvMsg::Execute(Box::new(move |server: &mut Server| {
+            let value = callback(server);
+            ret.send(value, &server.base.proxy)
+                .context("unable to return audio query result")
+                .log_warn();
+        })))
+        .context("unable to send query to audio server")?;
+        Ok(query)
+    }
+}
+
+/// Spatial audio commands, also usable through a cloned sender so that the
+/// update or draw server can feed the listener position every tick.
+pub trait ServerSendChannelExt: GameServerSendChannel<RecvMsg> {
+    fn set_listener(&self, position: Vec2) -> anyhow::Result<()> {
+        self.send(RecvMsg::SetListener(position))
+            .context("unable to send listener position")
+    }
+
+    fn set_emitter_position(&self, id: PlaybackId, position: Vec2) -> anyhow::Result<()> {
+        self.send(RecvMsg::SetEmitterPosition(id, position))
+            .context("unable to send emitter position")
+    }
 }
+
+impl<T> ServerSendChannelExt for T where T: GameServerSendChannel<RecvMsg> {}
@@ -1,19 +1,40 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
 use crate::{
     events::GameUserEvent,
-    graphics::context::{DrawContext, SendDrawContext},
+    exec::{
+        lockstep::Lockstep,
+        query::{self, ServerQuery},
+    },
+    graphics::{
+        context::{DrawContext, SendDrawContext},
+        present::PresentMode,
+        wrappers::texture::ImageData,
+    },
     scene::main::RootScene,
+    ui::utils::geom::UIPos,
     utils::{
         error::ResultExt,
-        mpsc::{Receiver, Sender},
+        mpsc::{is_disconnected_error, Receiver, Sender},
+        uid::Uid,
     },
 };
 use anyhow::{anyhow, Context};
+use glam::Vec4;
 use glutin::config::Config;
 use trait_set::trait_set;
 use winit::event_loop::EventLoopProxy;
 
+use self::command::DrawCommand;
+
 use super::{GameServer, GameServerChannel, GameServerSendChannel, SendGameServer};
 
+pub mod command;
+pub mod pointer;
+
 pub type SendMsg = ();
 
 trait_set! {
@@ -22,43 +43,63 @@ trait_set! {
 
 pub enum RecvMsg {
     SetFrequencyProfiling(bool),
+    Command(DrawCommand),
     Execute(Box<dyn DrawDispatch>),
 }
 pub struct Server {
     pub context: DrawContext,
     pub root_scene: Option<RootScene>,
+    lockstep: Option<Arc<Lockstep>>,
 }
 
 pub struct SendServer {
     pub context: SendDrawContext,
     pub root_scene: Option<RootScene>,
+    lockstep: Option<Arc<Lockstep>>,
 }
 
 impl GameServer for Server {
     fn run(&mut self, single: bool, runner_frequency: f64) -> anyhow::Result<()> {
         self.context
-            .draw(&mut self.root_scene, single, runner_frequency)
+            .draw(&mut self.root_scene, single, runner_frequency)?;
+        if let Some(lockstep) = self.lockstep.as_ref() {
+            lockstep.frame_rendered();
+        }
+        Ok(())
+    }
+
+    fn drain(&mut self, single: bool) -> anyhow::Result<()> {
+        self.context.drain(single, &mut self.root_scene)
     }
 
     fn to_send(self) -> anyhow::Result<SendGameServer> {
         Ok(SendGameServer::Draw(Box::new(SendServer {
             context: self.context.to_send()?,
             root_scene: self.root_scene,
+            lockstep: self.lockstep,
         })))
     }
 }
 
+impl Server {
+    pub fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.context.shutdown(&mut self.root_scene)
+    }
+}
+
 impl SendServer {
     pub fn new(
         proxy: EventLoopProxy<GameUserEvent>,
         gl_config: Config,
         display: &crate::display::Display,
+        lockstep: Option<Arc<Lockstep>>,
     ) -> anyhow::Result<(Self, ServerChannel)> {
         let (context, channel) = SendDrawContext::new(proxy, gl_config, display)?;
         Ok((
             Self {
                 context,
                 root_scene: None,
+                lockstep,
             },
             channel,
         ))
@@ -68,6 +109,7 @@ impl SendServer {
         Ok(Server {
             context: self.context.to_nonsend()?,
             root_scene: self.root_scene,
+            lockstep: self.lockstep,
         })
     }
 }
@@ -95,14 +137,53 @@ pub trait ServerSendChannelExt: GameServerSendChannel<RecvMsg> {
             .context("unable to send frequency profiling request")
     }
 
+    /// Does nothing if the draw server was shut down during the shutdown,
+    /// see `begin_shutdown`. Queries sent meanwhile fail with a
+    /// disconnected error.
     fn execute<F>(&self, callback: F) -> anyhow::Result<()>
     where
         F: DrawDispatch + 'static,
     {
-        self.send(RecvMsg::Execute(Box::new(callback)))
+        ignore_closed(self.send(RecvMsg::Execute(Box::new(callback))))
             .context("unable to send execute message to draw server")
     }
 
+    /// Executes `callback` on the draw server, the returned future resolves
+    /// to its result.
+    fn query<F, R>(&self, callback: F) -> anyhow::Result<ServerQuery<R>>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut DrawContext, &mut Option<RootScene>) -> R + Send + 'static,
+    {
+        let (ret, query) = query::query();
+        self.execute(move |context, root_scene| {
+            let value = callback(context, root_scene);
+            ret.send(value, &context.base.proxy)
+                .context("unable to return draw query result")
+                .log_warn();
+        })
+        .context("unable to send query to draw server")?;
+        Ok(query)
+    }
+
+    /// Resolves to the next frame rendered by the draw server, read back
+    /// before it is presented, see `screenshot::read_back_buffer`.
+    fn capture_frame(&self) -> anyhow::Result<ServerQuery<ImageData>> {
+        let (ret, query) = query::query();
+        self.execute(move |context, _| context.frame_captures.push(ret))
+            .context("unable to send frame capture request to draw server")?;
+        Ok(query)
+    }
+
+    /// Resolves to the id of the pickable drawn at `pos` (in window UI
+    /// units) in a later frame, see `Scene::draw_pick`.
+    fn pick_at(&self, pos: UIPos) -> anyhow::Result<ServerQuery<Option<Uid>>> {
+        let (ret, query) = query::query();
+        self.execute(move |context, _| context.picking.push(pos, ret))
+            .context("unable to send pick request to draw server")?;
+        Ok(query)
+    }
+
     fn execute_draw_event<F, R>(&self, callback: F) -> anyhow::Result<()>
     where
         R: IntoIterator<Item = GameUserEvent> + Send + 'static,
@@ -120,12 +201,51 @@ pub trait ServerSendChannelExt: GameServerSendChannel<RecvMsg> {
             }
         })
     }
+
+    fn command(&self, command: DrawCommand) -> anyhow::Result<()> {
+        ignore_closed(self.send(RecvMsg::Command(command)))
+            .context("unable to send command to draw server")
+    }
+
+    fn set_clear_color(&self, color: Vec4) -> anyhow::Result<()> {
+        self.command(DrawCommand::SetClearColor(color))
+            .context("unable to send clear color change to draw server")
+    }
+
+    /// Falls back to other modes if the surface doesn't accept `mode`, the
+    /// effective one is reported with a `VSyncSet` event.
+    fn set_present_mode(&self, mode: PresentMode) -> anyhow::Result<()> {
+        self.command(DrawCommand::SetPresentMode(mode))
+            .context("unable to send present mode change to draw server")
+    }
+
+    /// Multisamples the scene pass with `samples` samples, clamped to what
+    /// the driver supports, 0 or 1 disabling it.
+    fn set_multisample(&self, samples: u32) -> anyhow::Result<()> {
+        self.command(DrawCommand::SetMultisample(samples))
+            .context("unable to send multisample change to draw server")
+    }
 }
 
 impl<T> ServerSendChannelExt for T where T: GameServerSendChannel<RecvMsg> {}
 
-#[test]
-fn test_send_sync() {
-    use crate::assert_send;
-    assert_send!(SendServer);
+/// Set once the main thread started shutting down.
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Called by the main thread when it starts shutting down, messages sent to
+/// the closed draw server are dropped silently from then on.
+pub fn begin_shutdown() {
+    SHUTTING_DOWN.store(true, Ordering::Relaxed);
+}
+
+/// Messages sent while shutting down are dropped with the server, which is
+/// not worth reporting. A server closed before that is still an error.
+fn ignore_closed(result: anyhow::Result<()>) -> anyhow::Result<()> {
+    match result {
+        Err(e) if SHUTTING_DOWN.load(Ordering::Relaxed) && is_disconnected_error(&e) => {
+            tracing::trace!("draw server closed, message dropped");
+            Ok(())
+        }
+        result => result,
+    }
 }
@@ -0,0 +1,185 @@
+use std::{borrow::Cow, collections::BTreeMap, ptr::null};
+
+use derivative::Derivative;
+use glam::Vec4;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    events::{error::Subsystem, GameUserEvent},
+    graphics::{
+        context::DrawContext,
+        present::PresentMode,
+        render_graph::SCENE_PASS,
+        wrappers::{
+            texture::{TextureHandle, TextureType},
+            GLHandle,
+        },
+    },
+};
+
+/// Common draw server operations sent as data instead of closures, so that
+/// the traffic of the draw channel can be logged, counted and replayed.
+/// Anything else still goes through `execute`.
+#[derive(Derivative, Clone)]
+#[derivative(Debug)]
+pub enum DrawCommand {
+    /// allocates an RGBA8 2D texture, the storage is reallocated if the
+    /// handle was already created
+    CreateTexture {
+        #[derivative(Debug = "ignore")]
+        handle: TextureHandle,
+        name: Cow<'static, str>,
+        size: PhysicalSize<u32>,
+    },
+    /// color of the following `glClear`s, frames aren't cleared by the
+    /// draw server, the scenes cover the whole window
+    SetClearColor(Vec4),
+    SetViewport {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    /// falls back to other modes if the surface doesn't accept it, the
+    /// effective one is reported with a `VSyncSet` event
+    SetPresentMode(PresentMode),
+    /// sample count of the scene pass, 0 or 1 disabling multisampling
+    SetMultisample(u32),
+}
+
+/// Number of commands executed by the draw server per kind, optionally
+/// keeping them to be replayed later.
+#[derive(Default)]
+pub struct CommandLog {
+    counts: BTreeMap<&'static str, u64>,
+    recording: Option<Vec<DrawCommand>>,
+}
+
+impl DrawCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CreateTexture { .. } => "create_texture",
+            Self::SetClearColor(_) => "set_clear_color",
+            Self::SetViewport { .. } => "set_viewport",
+            Self::SetPresentMode(_) => "set_present_mode",
+            Self::SetMultisample(_) => "set_multisample",
+        }
+    }
+
+    /// Returns the event to send back to the main thread, if any.
+    pub fn execute(self, context: &mut DrawContext) -> Option<GameUserEvent> {
+        match self {
+            Self::CreateTexture { handle, name, size } => {
+                create_texture(context, &handle, name, size)
+                    .err()
+                    .map(|e| GameUserEvent::error(Subsystem::Draw, "draw.create_texture", e))
+            }
+            Self::SetClearColor(color) => {
+                context.clear_color = color;
+                unsafe { gl::ClearColor(color.x, color.y, color.z, color.w) };
+                None
+            }
+            Self::SetViewport {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                unsafe { gl::Viewport(x, y, width, height) };
+                None
+            }
+            Self::SetPresentMode(mode) => {
+                Some(GameUserEvent::VSyncSet(context.set_present_mode(mode)))
+            }
+            Self::SetMultisample(samples) => {
+                context.render_graph.set_samples(SCENE_PASS, samples);
+                None
+            }
+        }
+    }
+}
+
+fn create_texture(
+    context: &mut DrawContext,
+    handle: &TextureHandle,
+    name: Cow<'static, str>,
+    size: PhysicalSize<u32>,
+) -> anyhow::Result<()> {
+    let texture = match handle.try_get(context) {
+        Some(texture) => texture,
+        None => {
+            let texture = GLHandle::new_args(name, TextureType::E2D)?;
+            context.handles.textures.insert(handle, texture)
+        }
+    };
+    texture.bind();
+    texture.set_estimated_size(size.width as usize * size.height as usize * 4);
+    unsafe {
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA8.try_into()?,
+            size.width.try_into()?,
+            size.height.try_into()?,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            null(),
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_MIN_FILTER,
+            gl::LINEAR.try_into()?,
+        );
+    }
+    texture.unbind();
+    Ok(())
+}
+
+impl CommandLog {
+    pub fn record(&mut self, command: &DrawCommand) {
+        *self.counts.entry(command.name()).or_default() += 1;
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(command.clone());
+        }
+    }
+
+    /// Number of commands named `name` executed so far.
+    pub fn count(&self, name: &str) -> u64 {
+        self.counts.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn counts(&self) -> &BTreeMap<&'static str, u64> {
+        &self.counts
+    }
+
+    /// Starts keeping the executed commands, dropping the ones recorded
+    /// before.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Commands executed since `start_recording`, in order.
+    pub fn stop_recording(&mut self) -> Vec<DrawCommand> {
+        self.recording.take().unwrap_or_default()
+    }
+}
+
+#[test]
+fn test() {
+    let mut log = CommandLog::default();
+    log.record(&DrawCommand::SetClearColor(Vec4::ONE));
+    log.start_recording();
+    log.record(&DrawCommand::SetPresentMode(PresentMode::Fifo));
+    log.record(&DrawCommand::SetClearColor(Vec4::ZERO));
+    assert_eq!(log.count("set_clear_color"), 2);
+    assert_eq!(log.count("set_present_mode"), 1);
+    assert_eq!(log.count("set_viewport"), 0);
+
+    let recorded = log.stop_recording();
+    assert_eq!(
+        recorded.iter().map(|c| c.name()).collect::<Vec<_>>(),
+        ["set_present_mode", "set_clear_color"]
+    );
+    assert!(log.stop_recording().is_empty());
+}
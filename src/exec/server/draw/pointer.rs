@@ -0,0 +1,115 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+    time::Instant,
+};
+
+use crate::{ui::utils::geom::UIPos, utils::mutex::Mutex};
+
+/// Samples kept for a latch that is never drained, the oldest ones are
+/// dropped first.
+const MAX_PENDING_SAMPLES: usize = 1024;
+
+/// A cursor position, as received by the main thread.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointerSample {
+    /// logical window coordinates
+    pub position: UIPos,
+    pub received: Instant,
+}
+
+#[derive(Default)]
+struct LatchState {
+    latest: Option<PointerSample>,
+    pending: VecDeque<PointerSample>,
+}
+
+/// Low-latency pointer path for drawing/ink scenes.
+///
+/// Cursor moves are written to the latch by the main thread as soon as they
+/// are received, before the scene tree handles them, and read by the draw
+/// thread right before drawing ("late latching"), instead of waiting for
+/// the scenes to update their state for the next frame.
+#[derive(Clone, Default)]
+pub struct PointerLatch(Arc<Mutex<LatchState>>);
+
+/// Latches subscribed by the scenes, fed by `MainContext::handle_event`.
+#[derive(Default)]
+pub struct PointerLatches(Vec<Weak<Mutex<LatchState>>>);
+
+impl PointerLatch {
+    pub fn push(&self, sample: PointerSample) {
+        let mut state = self.0.lock();
+        state.latest = Some(sample);
+        if state.pending.len() == MAX_PENDING_SAMPLES {
+            state.pending.pop_front();
+        }
+        state.pending.push_back(sample);
+    }
+
+    /// The most recent sample, e.g. to position a cursor overlay.
+    pub fn latest(&self) -> Option<PointerSample> {
+        self.0.lock().latest
+    }
+
+    /// Every sample received since the previous call, oldest first, e.g. to
+    /// extend an ink stroke without skipping the positions in between.
+    pub fn drain(&self) -> Vec<PointerSample> {
+        self.0.lock().pending.drain(..).collect()
+    }
+}
+
+impl PointerLatches {
+    /// The latch is unsubscribed once all of its clones are dropped.
+    pub fn subscribe(&mut self) -> PointerLatch {
+        let latch = PointerLatch::default();
+        self.0.push(Arc::downgrade(&latch.0));
+        latch
+    }
+
+    pub fn publish(&mut self, sample: PointerSample) {
+        self.0.retain(|latch| match latch.upgrade() {
+            Some(latch) => {
+                PointerLatch(latch).push(sample);
+                true
+            }
+            None => false,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[test]
+fn test() {
+    let mut latches = PointerLatches::default();
+    assert!(latches.is_empty());
+    let latch = latches.subscribe();
+    let dropped = latches.subscribe();
+    drop(dropped);
+    assert_eq!(latch.latest(), None);
+
+    let sample = |x: f32| PointerSample {
+        position: UIPos::new(x, 0.0),
+        received: Instant::now(),
+    };
+    let samples = [sample(1.0), sample(2.0)];
+    samples.iter().for_each(|&s| latches.publish(s));
+    // the dropped latch was pruned
+    assert_eq!(latches.0.len(), 1);
+    assert_eq!(latch.latest(), Some(samples[1]));
+    assert_eq!(latch.drain(), samples);
+    assert!(latch.drain().is_empty());
+    assert_eq!(latch.latest(), Some(samples[1]));
+
+    (0..MAX_PENDING_SAMPLES + 1).for_each(|i| latch.push(sample(i as f32)));
+    let pending = latch.drain();
+    assert_eq!(pending.len(), MAX_PENDING_SAMPLES);
+    assert_eq!(pending[0].position.x, 1.0);
+
+    drop(latch);
+    latches.publish(sample(0.0));
+    assert!(latches.is_empty());
+}
@@ -2,7 +2,7 @@ use crate::{
     events::GameUserEvent,
     utils::{
         frequency_runner::FrequencyProfiler,
-        mpsc::{self, Receiver, Sender},
+        mpsc::{self, Backpressure, Receiver, Sender},
     },
 };
 use anyhow::Context;
@@ -11,8 +11,15 @@ use winit::event_loop::EventLoopProxy;
 
 pub mod audio;
 pub mod draw;
+pub mod network;
 pub mod update;
 
+// main thread -> server messages, the main thread waits for the server to
+// catch up instead of queueing without bounds
+const RECV_CHANNEL_CAPACITY: usize = 4096;
+// server -> main thread messages, nothing reads them yet
+const SEND_CHANNEL_CAPACITY: usize = 256;
+
 pub enum BaseSendMsg {
     SetRelativeFrequency(f64),
 }
@@ -30,12 +37,9 @@ pub struct BaseGameServer<SendMsg, RecvMsg> {
 pub trait GameServerSendChannel<RecvMsg> {
     fn sender(&self) -> &Sender<RecvMsg>;
     fn send(&self, message: RecvMsg) -> anyhow::Result<()> {
-        self.sender()
-            .send(message)
-            .map_err(|e| anyhow::format_err!("{}", e))
-            .context(
-                "unable to send message to (local) game server (the server was probably closed)",
-            )
+        self.sender().send(message).context(
+            "unable to send message to (local) game server (the server was probably closed)",
+        )
     }
 
     fn clone_sender(&self) -> ServerSendChannel<RecvMsg> {
@@ -65,6 +69,7 @@ pub struct ServerChannels {
     pub audio: audio::ServerChannel,
     pub draw: draw::ServerChannel,
     pub update: update::ServerChannel,
+    pub network: network::ServerChannel,
 }
 
 impl<SendMsg, RecvMsg> BaseGameServer<SendMsg, RecvMsg> {
@@ -81,10 +86,18 @@ pub enum ServerKind {
     Audio,
     Draw,
     Update,
+    Network,
+}
+
+impl ServerKind {
+    pub const ALL: [ServerKind; 4] = [Self::Audio, Self::Draw, Self::Update, Self::Network];
 }
 
 pub trait GameServer {
     fn run(&mut self, single: bool, runner_frequency: f64) -> anyhow::Result<()>;
+    /// Called instead of `run` while the server is paused, processes the
+    /// incoming messages without ticking.
+    fn drain(&mut self, single: bool) -> anyhow::Result<()>;
     fn to_send(self) -> anyhow::Result<SendGameServer>;
 }
 
@@ -92,6 +105,7 @@ pub enum SendGameServer {
     Audio(Box<audio::Server>),
     Update(Box<update::Server>),
     Draw(Box<draw::SendServer>),
+    Network(Box<network::Server>),
 }
 
 impl SendGameServer {
@@ -100,14 +114,17 @@ impl SendGameServer {
             Self::Audio(_) => ServerKind::Audio,
             Self::Draw(_) => ServerKind::Draw,
             Self::Update(_) => ServerKind::Update,
+            Self::Network(_) => ServerKind::Network,
         }
     }
 }
 
 impl<SendMsg, RecvMsg> BaseGameServer<SendMsg, RecvMsg> {
     pub fn new(proxy: EventLoopProxy<GameUserEvent>) -> (Self, Sender<RecvMsg>, Receiver<SendMsg>) {
-        let (send_sender, send_receiver) = mpsc::channels();
-        let (recv_sender, recv_receiver) = mpsc::channels();
+        let (send_sender, send_receiver) =
+            mpsc::bounded_channels(SEND_CHANNEL_CAPACITY, Backpressure::DropOldest);
+        let (recv_sender, recv_receiver) =
+            mpsc::bounded_channels(RECV_CHANNEL_CAPACITY, Backpressure::Block);
         (
             Self {
                 receiver: recv_receiver,
@@ -0,0 +1,616 @@
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+use rand::{thread_rng, Rng};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    events::GameUserEvent,
+    net::{Delivery, NetEvent, NetMessage, PeerId, PROTOCOL_MAGIC, PROTOCOL_VERSION},
+    utils::{
+        error::ResultExt,
+        mpsc::{self, Receiver, Sender},
+        uid::Uid,
+    },
+};
+
+use super::{
+    update, BaseGameServer, GameServer, GameServerChannel, GameServerSendChannel, SendGameServer,
+    ServerSendChannel,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const UDP_BIND_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_DATAGRAM_SIZE: usize = 1200;
+
+pub enum SendMsg {}
+pub enum RecvMsg {
+    SetFrequencyProfiling(bool),
+    Listen(SocketAddr),
+    Connect(SocketAddr),
+    Send(PeerId, Delivery, NetMessage),
+    Broadcast(Delivery, NetMessage),
+    Disconnect(PeerId),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PeerState {
+    Handshaking,
+    Connected,
+}
+
+struct Peer {
+    stream: TcpStream,
+    addr: SocketAddr,
+    /// whether the peer connected to us
+    incoming: bool,
+    state: PeerState,
+    since: Instant,
+    token: u64,
+    /// where unreliable messages are sent, known once the client bound its
+    /// UDP socket
+    udp_addr: Option<SocketAddr>,
+    /// whether the server registered the client UDP address
+    udp_bound: bool,
+    last_udp_bind: Option<Instant>,
+    read_buf: Vec<u8>,
+    write_buf: Vec<u8>,
+    closed: Option<String>,
+}
+
+/// Outgoing connection established by a worker thread, std has no
+/// non-blocking connect.
+struct PendingConnection {
+    addr: SocketAddr,
+    stream: Receiver<std::io::Result<TcpStream>>,
+}
+
+/// Owns the sockets, performs the session handshakes and forwards the
+/// decoded messages to the update server.
+///
+/// Reliable messages go through a TCP connection per peer, unreliable ones
+/// through a single UDP socket bound to the same port as the TCP listener
+/// (any port for clients). All sockets are non-blocking and polled every
+/// tick.
+pub struct Server {
+    pub base: BaseGameServer<SendMsg, RecvMsg>,
+    update: ServerSendChannel<update::RecvMsg>,
+    listener: Option<TcpListener>,
+    udp: Option<UdpSocket>,
+    connecting: Vec<PendingConnection>,
+    peers: HashMap<PeerId, Peer>,
+    events: Vec<NetEvent>,
+}
+
+pub struct ServerChannel {
+    sender: Sender<RecvMsg>,
+    receiver: Receiver<SendMsg>,
+}
+
+impl GameServerChannel<SendMsg, RecvMsg> for ServerChannel {
+    fn receiver(&mut self) -> &mut Receiver<SendMsg> {
+        &mut self.receiver
+    }
+}
+
+impl GameServerSendChannel<RecvMsg> for ServerChannel {
+    fn sender(&self) -> &Sender<RecvMsg> {
+        &self.sender
+    }
+}
+
+impl GameServer for Server {
+    fn run(&mut self, _: bool, runner_frequency: f64) -> anyhow::Result<()> {
+        self.base.run("Network", runner_frequency);
+        self.process_messages()?;
+        self.poll_connecting();
+        self.accept();
+        self.poll_peers();
+        self.poll_udp();
+        self.remove_closed_peers();
+        self.forward_events()
+    }
+
+    fn drain(&mut self, _: bool) -> anyhow::Result<()> {
+        // connections are kept alive, incoming data waits in the socket
+        // buffers until the server is resumed
+        self.process_messages()?;
+        self.flush_peers();
+        self.remove_closed_peers();
+        self.forward_events()
+    }
+
+    fn to_send(self) -> anyhow::Result<SendGameServer> {
+        Ok(SendGameServer::Network(Box::new(self)))
+    }
+}
+
+impl Server {
+    pub fn new(
+        proxy: EventLoopProxy<GameUserEvent>,
+        update: ServerSendChannel<update::RecvMsg>,
+    ) -> (Self, ServerChannel) {
+        let (base, sender, receiver) = BaseGameServer::new(proxy);
+        (
+            Self {
+                base,
+                update,
+                listener: None,
+                udp: None,
+                connecting: Vec::new(),
+                peers: HashMap::new(),
+                events: Vec::new(),
+            },
+            ServerChannel { sender, receiver },
+        )
+    }
+
+    fn process_messages(&mut self) -> anyhow::Result<()> {
+        let messages = self
+            .base
+            .receiver
+            .try_iter(None)
+            .context("thread runner channel was unexpectedly closed")?
+            .collect::<Vec<_>>();
+        for message in messages {
+            match message {
+                RecvMsg::SetFrequencyProfiling(fp) => {
+                    self.base.frequency_profiling = fp;
+                }
+                RecvMsg::Listen(addr) => self
+                    .listen(addr)
+                    .with_context(|| format!("unable to listen on {addr}"))
+                    .log_error()
+                    .unwrap_or_default(),
+                RecvMsg::Connect(addr) => self
+                    .connect(addr)
+                    .with_context(|| format!("unable to connect to {addr}"))
+                    .log_error()
+                    .unwrap_or_default(),
+                RecvMsg::Send(id, delivery, message) => self.send_to(id, delivery, &message),
+                RecvMsg::Broadcast(delivery, message) => {
+                    let ids: Vec<_> = self
+                        .peers
+                        .iter()
+                        .filter(|(_, peer)| peer.state == PeerState::Connected)
+                        .map(|(&id, _)| id)
+                        .collect();
+                    for id in ids {
+                        self.send_to(id, delivery, &message);
+                    }
+                }
+                RecvMsg::Disconnect(id) => {
+                    if let Some(peer) = self.peers.get_mut(&id) {
+                        peer.queue(&NetMessage::new(NetMessage::GOODBYE, "disconnected"));
+                        peer.flush();
+                        peer.close("disconnected locally");
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn listen(&mut self, addr: SocketAddr) -> anyhow::Result<()> {
+        let listener = TcpListener::bind(addr).context("unable to bind TCP listener")?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let udp = UdpSocket::bind(local_addr).context("unable to bind UDP socket")?;
+        udp.set_nonblocking(true)?;
+        tracing::info!("network server listening on {local_addr}");
+        self.listener = Some(listener);
+        self.udp = Some(udp);
+        Ok(())
+    }
+
+    /// Connects on a worker thread, the peer is added by `poll_connecting`
+    /// once the connection is established.
+    fn connect(&mut self, addr: SocketAddr) -> anyhow::Result<()> {
+        let (sender, receiver) = mpsc::channels();
+        thread::Builder::new()
+            .name(format!("network connect {addr}"))
+            .spawn(move || {
+                // the server may have shut down meanwhile
+                let _ = sender.send(TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT));
+            })
+            .context("unable to spawn connect thread")?;
+        self.connecting.push(PendingConnection {
+            addr,
+            stream: receiver,
+        });
+        Ok(())
+    }
+
+    fn poll_connecting(&mut self) {
+        let mut connecting = std::mem::take(&mut self.connecting);
+        connecting.retain(|pending| {
+            let stream = match pending.stream.try_recv() {
+                Ok(None) => return true,
+                Ok(Some(stream)) => stream.map_err(Into::into),
+                Err(e) => Err(e.context("connect thread stopped")),
+            };
+            stream
+                .and_then(|stream| self.add_server_peer(stream, pending.addr))
+                .with_context(|| format!("unable to connect to {}", pending.addr))
+                .log_error();
+            false
+        });
+        self.connecting = connecting;
+    }
+
+    fn add_server_peer(&mut self, stream: TcpStream, addr: SocketAddr) -> anyhow::Result<()> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        if self.udp.is_none() {
+            let local: SocketAddr = if addr.is_ipv4() {
+                ([0, 0, 0, 0], 0).into()
+            } else {
+                ([0u16; 8], 0).into()
+            };
+            let udp = UdpSocket::bind(local).context("unable to bind UDP socket")?;
+            udp.set_nonblocking(true)?;
+            self.udp = Some(udp);
+        }
+
+        let mut peer = Peer::new(stream, addr, false);
+        peer.queue(&NetMessage::hello());
+        // the server UDP socket shares the port of its TCP listener
+        peer.udp_addr = Some(addr);
+        self.peers.insert(Uid::new(), peer);
+        Ok(())
+    }
+
+    /// Adds the pending incoming connections, errors are logged and the
+    /// remaining connections accepted on the next tick.
+    fn accept(&mut self) {
+        let listener = match self.listener.as_ref() {
+            Some(listener) => listener,
+            None => return,
+        };
+        loop {
+            match listener.accept() {
+                Ok((stream, addr)) => {
+                    let configured = stream
+                        .set_nonblocking(true)
+                        .and_then(|()| stream.set_nodelay(true))
+                        .with_context(|| format!("unable to configure connection from {addr}"))
+                        .log_warn();
+                    if configured.is_some() {
+                        self.peers.insert(Uid::new(), Peer::new(stream, addr, true));
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    // e.g. out of file descriptors, or the connection was
+                    // reset before it was accepted
+                    Err::<(), _>(e)
+                        .context("unable to accept connection")
+                        .log_warn();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn send_to(&mut self, id: PeerId, delivery: Delivery, message: &NetMessage) {
+        let peer = match self.peers.get_mut(&id) {
+            Some(peer) if peer.state == PeerState::Connected => peer,
+            _ => {
+                tracing::warn!("dropping message to unknown or unconnected peer {id:?}");
+                return;
+            }
+        };
+
+        match (delivery, peer.udp_addr, self.udp.as_ref()) {
+            (Delivery::Unreliable, Some(udp_addr), Some(udp)) => {
+                udp.send_to(&message.encode_datagram(peer.token), udp_addr)
+                    .context("unable to send datagram")
+                    .log_trace();
+            }
+            // unreliable messages fall back to TCP until the peer bound its
+            // UDP socket
+            _ => peer.queue(message),
+        }
+    }
+
+    fn poll_peers(&mut self) {
+        for (&id, peer) in self.peers.iter_mut() {
+            if let Err(e) = peer.read() {
+                peer.close(format!("{e:#}"));
+                continue;
+            }
+
+            loop {
+                let message = match NetMessage::decode_frame(&mut peer.read_buf) {
+                    Ok(Some(message)) => message,
+                    Ok(None) => break,
+                    Err(e) => {
+                        peer.close(format!("{e:#}"));
+                        break;
+                    }
+                };
+                if let Err(e) = Self::handle_frame(id, peer, message, &mut self.events) {
+                    peer.close(format!("{e:#}"));
+                    break;
+                }
+            }
+
+            if peer.state == PeerState::Handshaking && peer.since.elapsed() > HANDSHAKE_TIMEOUT {
+                peer.close("handshake timed out");
+            }
+            if peer.state == PeerState::Connected && !peer.incoming && !peer.udp_bound {
+                // datagrams can get lost, register our UDP address until the
+                // server acknowledges it
+                let due = peer
+                    .last_udp_bind
+                    .map(|last| last.elapsed() >= UDP_BIND_INTERVAL)
+                    .unwrap_or(true);
+                if let (true, Some(udp), Some(udp_addr)) = (due, self.udp.as_ref(), peer.udp_addr) {
+                    udp.send_to(
+                        &NetMessage::new(NetMessage::BIND_UDP, Vec::new())
+                            .encode_datagram(peer.token),
+                        udp_addr,
+                    )
+                    .context("unable to send UDP binding")
+                    .log_trace();
+                    peer.last_udp_bind = Some(Instant::now());
+                }
+            }
+            peer.flush();
+        }
+    }
+
+    fn handle_frame(
+        id: PeerId,
+        peer: &mut Peer,
+        message: NetMessage,
+        events: &mut Vec<NetEvent>,
+    ) -> anyhow::Result<()> {
+        match (peer.state, message.kind) {
+            (_, NetMessage::GOODBYE) => {
+                peer.close(String::from_utf8_lossy(&message.payload).into_owned());
+            }
+
+            (PeerState::Handshaking, NetMessage::HELLO) if peer.incoming => {
+                let payload = &message.payload;
+                if payload.len() != 6 || payload[..4] != PROTOCOL_MAGIC {
+                    bail!("invalid handshake");
+                }
+                let version = u16::from_be_bytes([payload[4], payload[5]]);
+                if version != PROTOCOL_VERSION {
+                    peer.queue(&NetMessage::new(
+                        NetMessage::GOODBYE,
+                        format!("unsupported protocol version {version}"),
+                    ));
+                    peer.flush();
+                    bail!("unsupported protocol version {version}");
+                }
+                // 0 is reserved for "no session"
+                peer.token = thread_rng().gen_range(1..=u64::MAX);
+                peer.queue(&NetMessage::new(
+                    NetMessage::WELCOME,
+                    peer.token.to_be_bytes().to_vec(),
+                ));
+                peer.connected(id, events);
+            }
+
+            (PeerState::Handshaking, NetMessage::WELCOME) if !peer.incoming => {
+                let token: [u8; 8] = message
+                    .payload
+                    .as_slice()
+                    .try_into()
+                    .context("invalid session token")?;
+                peer.token = u64::from_be_bytes(token);
+                peer.connected(id, events);
+            }
+
+            (PeerState::Connected, NetMessage::BIND_UDP) if !peer.incoming => {
+                peer.udp_bound = true;
+            }
+
+            (PeerState::Connected, _) if !message.is_reserved() => {
+                events.push(NetEvent::Message(id, message));
+            }
+
+            (state, kind) => bail!("unexpected message {kind:#x} in state {state:?}"),
+        }
+        Ok(())
+    }
+
+    fn poll_udp(&mut self) {
+        let udp = match self.udp.as_ref() {
+            Some(udp) => udp,
+            None => return,
+        };
+        let mut buf = [0; MAX_DATAGRAM_SIZE];
+        loop {
+            let (size, from) = match udp.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    // e.g. ICMP port unreachable reported on Windows, the
+                    // remaining datagrams are received on the next tick
+                    tracing::trace!("UDP receive error: {e}");
+                    return;
+                }
+            };
+            let (token, message) = match NetMessage::decode_datagram(&buf[..size]) {
+                Ok(decoded) => decoded,
+                Err(_) => continue,
+            };
+            let peer = self.peers.iter_mut().find(|(_, peer)| {
+                peer.state == PeerState::Connected && peer.token == token && token != 0
+            });
+            if let Some((&id, peer)) = peer {
+                if message.kind == NetMessage::BIND_UDP && peer.incoming {
+                    peer.udp_addr = Some(from);
+                    if !peer.udp_bound {
+                        peer.udp_bound = true;
+                        peer.queue(&message);
+                    }
+                } else if !message.is_reserved() {
+                    self.events.push(NetEvent::Message(id, message));
+                }
+            }
+        }
+    }
+
+    fn flush_peers(&mut self) {
+        for peer in self.peers.values_mut() {
+            peer.flush();
+        }
+    }
+
+    fn remove_closed_peers(&mut self) {
+        let events = &mut self.events;
+        self.peers.retain(|&id, peer| match peer.closed.take() {
+            Some(reason) => {
+                tracing::info!("peer {} disconnected: {reason}", peer.addr);
+                if peer.state == PeerState::Connected {
+                    events.push(NetEvent::Disconnected(id, reason));
+                }
+                false
+            }
+            None => true,
+        });
+    }
+
+    /// The runner thread doesn't wait for a busy update server, the events
+    /// are kept and sent along with the next ones instead.
+    fn forward_events(&mut self) -> anyhow::Result<()> {
+        if self.events.is_empty() {
+            return Ok(());
+        }
+        let message = update::RecvMsg::Network(std::mem::take(&mut self.events));
+        let rejected = self
+            .update
+            .sender()
+            .try_send(message)
+            .context("unable to forward network events to update server")?;
+        if let Some(update::RecvMsg::Network(events)) = rejected {
+            tracing::warn!(
+                "update server channel full, {} network events delayed",
+                events.len()
+            );
+            self.events = events;
+        }
+        Ok(())
+    }
+}
+
+impl Peer {
+    fn new(stream: TcpStream, addr: SocketAddr, incoming: bool) -> Self {
+        Self {
+            stream,
+            addr,
+            incoming,
+            state: PeerState::Handshaking,
+            since: Instant::now(),
+            token: 0,
+            udp_addr: None,
+            udp_bound: false,
+            last_udp_bind: None,
+            read_buf: Vec::new(),
+            write_buf: Vec::new(),
+            closed: None,
+        }
+    }
+
+    fn connected(&mut self, id: PeerId, events: &mut Vec<NetEvent>) {
+        tracing::info!("peer {} connected", self.addr);
+        self.state = PeerState::Connected;
+        self.since = Instant::now();
+        events.push(NetEvent::Connected(id, self.addr));
+    }
+
+    fn close(&mut self, reason: impl Into<String>) {
+        if self.closed.is_none() {
+            self.closed = Some(reason.into());
+        }
+    }
+
+    fn queue(&mut self, message: &NetMessage) {
+        message.encode_frame(&mut self.write_buf);
+    }
+
+    fn read(&mut self) -> anyhow::Result<()> {
+        let mut buf = [0; 4096];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => bail!("connection closed by peer"),
+                Ok(size) => self.read_buf.extend_from_slice(&buf[..size]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        while !self.write_buf.is_empty() {
+            match self.stream.write(&self.write_buf) {
+                Ok(0) => {
+                    self.close("connection closed while writing");
+                    return;
+                }
+                Ok(size) => {
+                    self.write_buf.drain(..size);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.close(e.to_string());
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl ServerChannel {
+    pub fn set_frequency_profiling(&self, fp: bool) -> anyhow::Result<()> {
+        self.send(RecvMsg::SetFrequencyProfiling(fp))
+            .context("unable to send frequency profiling request")
+    }
+
+    /// Starts accepting connections on `addr` (TCP and UDP).
+    pub fn listen(&self, addr: SocketAddr) -> anyhow::Result<()> {
+        self.send(RecvMsg::Listen(addr))
+            .context("unable to send listen request")
+    }
+
+    pub fn connect(&self, addr: SocketAddr) -> anyhow::Result<()> {
+        self.send(RecvMsg::Connect(addr))
+            .context("unable to send connect request")
+    }
+
+    pub fn send_message(
+        &self,
+        peer: PeerId,
+        delivery: Delivery,
+        message: NetMessage,
+    ) -> anyhow::Result<()> {
+        debug_assert!(!message.is_reserved());
+        self.send(RecvMsg::Send(peer, delivery, message))
+            .context("unable to send network message request")
+    }
+
+    /// Sends `message` to every connected peer.
+    pub fn broadcast(&self, delivery: Delivery, message: NetMessage) -> anyhow::Result<()> {
+        debug_assert!(!message.is_reserved());
+        self.send(RecvMsg::Broadcast(delivery, message))
+            .context("unable to send network broadcast request")
+    }
+
+    pub fn disconnect(&self, peer: PeerId) -> anyhow::Result<()> {
+        self.send(RecvMsg::Disconnect(peer))
+            .context("unable to send disconnect request")
+    }
+}
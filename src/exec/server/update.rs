@@ -1,45 +1,229 @@
-use std::{
-    collections::HashMap,
-    time::{Duration, Instant},
-};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use anyhow::Context;
+use glam::Vec2;
+use trait_set::trait_set;
 use winit::event_loop::EventLoopProxy;
 
-use super::{BaseGameServer, GameServer, GameServerChannel, GameServerSendChannel, SendGameServer};
+use super::{
+    BaseGameServer, GameServer, GameServerChannel, GameServerSendChannel, SendGameServer,
+    ServerSendChannel,
+};
 use crate::{
+    ai::{
+        behavior_tree::{BehaviorTree, NodeSnapshot},
+        blackboard::Blackboard,
+    },
+    audio::clock::SharedBeatClock,
     events::GameUserEvent,
-    exec::dispatch::DispatchMsg,
+    exec::{
+        dispatch::DispatchMsg,
+        interpolation::Interpolate,
+        lockstep::{Lockstep, ServerClock},
+        main_ctx::MainContext,
+        query::{self, ServerQuery},
+        task::{
+            Cancellable, CancellationToken, JoinTaskResult, Joinable, TaskExecutor, TaskHandle,
+            TryJoinTaskResult,
+        },
+    },
+    graphics::particles::{EmitterDef, ParticleEffectHandle, ParticleEmitter, ParticleSprites},
+    nav::{astar, NavGrid, NavPath, PathQuery},
+    net::NetEvent,
+    scene::main::RootScene,
+    spatial::{
+        culling::VisibleSet,
+        grid::{RayHit, SpatialGrid},
+        Aabb, Ray,
+    },
     utils::{
+        clock::Clock,
+        error::ResultExt,
         mpsc::{Receiver, Sender},
+        mutex::Mutex,
+        rng::RngService,
         uid::Uid,
     },
 };
 
+use self::{
+    sequence::ScheduledSequence,
+    tween::{RunningTween, ScheduledTween, Tween, TweenApply},
+};
+
+pub mod sequence;
+pub mod tween;
+
+trait_set! {
+    pub trait PathCallback = FnOnce(&mut MainContext, &mut RootScene, Option<NavPath>) -> anyhow::Result<()> + Send;
+    pub trait UpdateDispatch = FnOnce(&mut Server) + Send;
+}
+
 pub enum SendMsg {}
 pub enum RecvMsg {
     SetFrequencyProfiling(bool),
-    SetTimeout(Instant, Uid),
+    SetTimeout(Duration, Uid),
     CancelTimeout(Uid),
+    FindPath(
+        Arc<NavGrid>,
+        PathQuery,
+        CancellationToken,
+        Box<dyn PathCallback>,
+    ),
+    AddBehaviorTree(Uid, Box<BehaviorTree>),
+    RemoveBehaviorTree(Uid),
+    SetCollider(Uid, Aabb),
+    RemoveCollider(Uid),
+    SetCullView(Uid, Aabb, Arc<VisibleSet>),
+    RemoveCullView(Uid),
+    Network(Vec<NetEvent>),
+    StartTween(Uid, Box<dyn RunningTween>),
+    CancelTween(Uid),
+    StartSequence(Uid, ScheduledSequence),
+    CancelSequence(Uid),
+    AddParticleEffect(Uid, Box<EmitterDef>, Vec2, Arc<ParticleSprites>),
+    MoveParticleEffect(Uid, Vec2),
+    SetParticleEmission(Uid, bool),
+    RemoveParticleEffect(Uid),
+    Execute(Box<dyn UpdateDispatch>),
+}
+
+struct ScheduledTree {
+    tree: Box<BehaviorTree>,
+    next_tick: f64,
+}
+
+struct PendingPath {
+    task: TaskHandle<Option<NavPath>>,
+    callback: Box<dyn PathCallback>,
+}
+
+struct ParticleEffect {
+    emitter: ParticleEmitter,
+    sprites: Arc<ParticleSprites>,
+    /// server time of the last step
+    last_step: f64,
+}
+
+struct CullView {
+    bounds: Aabb,
+    visible: Arc<VisibleSet>,
+    task: Option<TaskHandle<Vec<Uid>>>,
 }
 
 pub struct Server {
     pub base: BaseGameServer<SendMsg, RecvMsg>,
-    pub timeouts: HashMap<Uid, Instant>,
+    pub timeouts: HashMap<Uid, f64>,
+    clock: ServerClock,
+    pub rng: RngService,
+    task_executor: TaskExecutor,
+    pending_paths: Vec<PendingPath>,
+    behavior_trees: HashMap<Uid, ScheduledTree>,
+    pub spatial: SpatialGrid,
+    /// copy of `spatial` read by the culling jobs, brought up to date by
+    /// replaying `cull_changes` once no job reads it anymore
+    cull_grid: Arc<SpatialGrid>,
+    /// collider changes not applied to `cull_grid` yet, `None` removes
+    cull_changes: Vec<(Uid, Option<Aabb>)>,
+    cull_views: HashMap<Uid, CullView>,
+    /// received from the network server, delivered to the main thread on
+    /// the next tick
+    net_events: Vec<NetEvent>,
+    /// keyed by the dispatch executed when the tween ends
+    tweens: HashMap<Uid, Box<dyn RunningTween>>,
+    sequences: HashMap<Uid, ScheduledSequence>,
+    particle_effects: HashMap<Uid, ParticleEffect>,
+    /// the audio server's, see `audio::ServerChannel::beat_clock`
+    pub beat_clock: SharedBeatClock,
 }
 
 impl GameServer for Server {
     fn run(&mut self, _: bool, runner_frequency: f64) -> anyhow::Result<()> {
         self.base.run("Update", runner_frequency);
+        self.process_messages()?;
+        for _ in 0..self.clock.pending_ticks() {
+            self.tick()?;
+            self.clock.advance();
+        }
+        Ok(())
+    }
+
+    /// Paused update servers still fire timeouts, since those are mostly
+    /// used by the main thread, but stop ticking behavior trees and
+    /// delivering paths.
+    fn drain(&mut self, _: bool) -> anyhow::Result<()> {
+        self.process_messages()?;
+        if self.clock.is_lockstep() {
+            // the virtual clock stays frozen, the missed ticks are skipped
+            // instead of caught up on resume
+            self.clock.skip_pending();
+            Ok(())
+        } else {
+            self.fire_timeouts()
+        }
+    }
+
+    fn to_send(self) -> anyhow::Result<SendGameServer> {
+        Ok(SendGameServer::Update(Box::new(self)))
+    }
+}
+
+impl Server {
+    /// `lockstep` enables the deterministic lockstep mode, see `Lockstep`.
+    pub fn new(
+        proxy: EventLoopProxy<GameUserEvent>,
+        task_executor: TaskExecutor,
+        lockstep: Option<Arc<Lockstep>>,
+        seed: u64,
+    ) -> (Self, ServerChannel) {
+        Self::with_clock(proxy, task_executor, ServerClock::new(lockstep), seed)
+    }
+
+    /// Server timed by `clock`, e.g. a `ServerClock::Virtual` advanced by a
+    /// test so that timeouts fire without waiting for them.
+    pub fn with_clock(
+        proxy: EventLoopProxy<GameUserEvent>,
+        task_executor: TaskExecutor,
+        clock: ServerClock,
+        seed: u64,
+    ) -> (Self, ServerChannel) {
+        let (base, sender, receiver) = BaseGameServer::new(proxy);
+        (
+            Self {
+                base,
+                timeouts: HashMap::new(),
+                clock,
+                rng: RngService::new(seed),
+                task_executor,
+                pending_paths: Vec::new(),
+                behavior_trees: HashMap::new(),
+                spatial: SpatialGrid::default(),
+                cull_grid: Arc::default(),
+                cull_changes: Vec::new(),
+                cull_views: HashMap::new(),
+                net_events: Vec::new(),
+                tweens: HashMap::new(),
+                sequences: HashMap::new(),
+                particle_effects: HashMap::new(),
+                beat_clock: SharedBeatClock::default(),
+            },
+            ServerChannel { sender, receiver },
+        )
+    }
+
+    fn process_messages(&mut self) -> anyhow::Result<()> {
         let messages = self
             .base
             .receiver
             .try_iter(None)
-            .context("thread runner channel was unexpectedly closed")?;
+            .context("thread runner channel was unexpectedly closed")?
+            // collected so that `Execute` callbacks can borrow the server
+            .collect::<Vec<_>>();
         for message in messages {
             match message {
-                RecvMsg::SetTimeout(inst, id) => {
-                    self.timeouts.insert(id, inst);
+                RecvMsg::SetTimeout(duration, id) => {
+                    self.timeouts
+                        .insert(id, self.clock.now() + duration.as_secs_f64());
                 }
                 RecvMsg::CancelTimeout(id) => {
                     self.timeouts.remove(&id);
@@ -47,11 +231,265 @@ impl GameServer for Server {
                 RecvMsg::SetFrequencyProfiling(fp) => {
                     self.base.frequency_profiling = fp;
                 }
+                RecvMsg::FindPath(grid, query, cancel, callback) => {
+                    let task = self.task_executor.spawn_with(cancel, move |cancel| {
+                        astar::find_path(&grid, &query, Some(cancel))
+                    });
+                    self.pending_paths.push(PendingPath { task, callback });
+                }
+                RecvMsg::AddBehaviorTree(id, tree) => {
+                    tree.blackboard
+                        .lock()
+                        .set(RngService::BLACKBOARD_KEY, self.rng.fork());
+                    self.behavior_trees.insert(
+                        id,
+                        ScheduledTree {
+                            tree,
+                            next_tick: self.clock.now(),
+                        },
+                    );
+                }
+                RecvMsg::RemoveBehaviorTree(id) => {
+                    self.behavior_trees.remove(&id);
+                }
+                RecvMsg::SetCollider(id, aabb) => {
+                    self.spatial.insert(id, aabb);
+                    self.cull_changes.push((id, Some(aabb)));
+                }
+                RecvMsg::RemoveCollider(id) => {
+                    self.spatial.remove(id);
+                    self.cull_changes.push((id, None));
+                }
+                RecvMsg::SetCullView(id, bounds, visible) => {
+                    let view = self.cull_views.entry(id).or_insert_with(|| CullView {
+                        bounds,
+                        visible: visible.clone(),
+                        task: None,
+                    });
+                    view.bounds = bounds;
+                    view.visible = visible;
+                }
+                RecvMsg::RemoveCullView(id) => {
+                    if let Some(task) = self.cull_views.remove(&id).and_then(|view| view.task) {
+                        task.cancel.cancel();
+                    }
+                }
+                RecvMsg::Network(events) => {
+                    self.net_events.extend(events);
+                }
+                RecvMsg::StartTween(id, tween) => {
+                    self.tweens.insert(id, tween);
+                }
+                RecvMsg::CancelTween(id) => {
+                    self.tweens.remove(&id);
+                }
+                RecvMsg::StartSequence(id, sequence) => {
+                    self.sequences.insert(id, sequence);
+                }
+                RecvMsg::CancelSequence(id) => {
+                    if let Some(sequence) = self.sequences.remove(&id) {
+                        self.drop_dispatches(sequence.pending_calls())?;
+                    }
+                }
+                RecvMsg::AddParticleEffect(id, def, position, sprites) => {
+                    let emitter = ParticleEmitter::new(*def, position, self.rng.fork());
+                    self.particle_effects.insert(
+                        id,
+                        ParticleEffect {
+                            emitter,
+                            sprites,
+                            last_step: self.clock.now(),
+                        },
+                    );
+                }
+                RecvMsg::MoveParticleEffect(id, position) => {
+                    if let Some(effect) = self.particle_effects.get_mut(&id) {
+                        effect.emitter.position = position;
+                    }
+                }
+                RecvMsg::SetParticleEmission(id, emitting) => {
+                    if let Some(effect) = self.particle_effects.get_mut(&id) {
+                        effect.emitter.emitting = emitting;
+                    }
+                }
+                RecvMsg::RemoveParticleEffect(id) => {
+                    self.particle_effects.remove(&id);
+                }
+                RecvMsg::Execute(callback) => callback(self),
             };
         }
+        Ok(())
+    }
+
+    fn tick(&mut self) -> anyhow::Result<()> {
+        self.poll_paths()?;
+        self.deliver_net_events()?;
+        self.tick_behavior_trees();
+        self.tick_tweens()?;
+        self.tick_sequences()?;
+        self.tick_particles();
+        self.tick_culling();
+        self.fire_timeouts()
+    }
+
+    /// Publishes the views culled since the last tick and, once every job
+    /// is done, starts culling them again on the job system against the
+    /// collider changes received since.
+    fn tick_culling(&mut self) {
+        // same as paths, lockstep results can't depend on the job timing
+        let blocking = self.clock.is_lockstep();
+        let mut running = false;
+        for view in self.cull_views.values_mut() {
+            if let Some(task) = &view.task {
+                let result = if blocking {
+                    match task.join.join() {
+                        JoinTaskResult::Done(ids) => TryJoinTaskResult::Joined(ids),
+                        JoinTaskResult::ResultTaken => TryJoinTaskResult::JoinedResultTaken,
+                    }
+                } else {
+                    task.join.try_join()
+                };
+                match result {
+                    TryJoinTaskResult::NotJoined => {
+                        running = true;
+                        continue;
+                    }
+                    TryJoinTaskResult::Joined(ids) => view.visible.set(ids),
+                    TryJoinTaskResult::JoinedResultTaken => {
+                        tracing::warn!("culling task finished without a result")
+                    }
+                }
+                view.task = None;
+            }
+        }
+        if running {
+            return;
+        }
+        // only the changes are applied, the grid is never copied
+        let grid = match Arc::get_mut(&mut self.cull_grid) {
+            Some(grid) => grid,
+            None => return,
+        };
+        for (id, aabb) in self.cull_changes.drain(..) {
+            match aabb {
+                Some(aabb) => grid.insert(id, aabb),
+                None => {
+                    grid.remove(id);
+                }
+            }
+        }
+
+        for view in self.cull_views.values_mut() {
+            let grid = self.cull_grid.clone();
+            let bounds = view.bounds;
+            view.task = Some(
+                self.task_executor
+                    .spawn(move |_| grid.query_region(&bounds)),
+            );
+        }
+    }
+
+    /// Steps the particle effects by the time since their last step and
+    /// publishes their sprites.
+    fn tick_particles(&mut self) {
+        let now = self.clock.now();
+        for effect in self.particle_effects.values_mut() {
+            effect.emitter.step((now - effect.last_step) as f32);
+            effect.last_step = now;
+            effect.sprites.set(effect.emitter.sprites());
+        }
+    }
+
+    fn tick_sequences(&mut self) -> anyhow::Result<()> {
+        let now = self.clock.now();
+        let mut calls = Vec::new();
+        let mut ids: Vec<_> = self.sequences.keys().copied().collect();
+        // sequences started first run first
+        ids.sort();
+        for id in ids {
+            let done = self
+                .sequences
+                .get_mut(&id)
+                .map(|sequence| sequence.advance(now, &mut calls))
+                .unwrap_or(true);
+            if done {
+                self.sequences.remove(&id);
+            }
+        }
+        if !calls.is_empty() {
+            self.base
+                .proxy
+                .send_event(GameUserEvent::Dispatch(DispatchMsg::ExecuteDispatch(calls)))
+                .map_err(|e| anyhow::format_err!("{}", e))
+                .context("unable to send event to event loop")?;
+        }
+        Ok(())
+    }
+
+    /// Removes dispatches that will never be executed from the main thread
+    /// dispatch list.
+    fn drop_dispatches(&self, ids: Vec<Uid>) -> anyhow::Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        self.base
+            .proxy
+            .send_event(GameUserEvent::Execute(Box::new(move |main_ctx, _| {
+                for id in ids {
+                    main_ctx.dispatch_list.pop(id);
+                }
+                Ok(())
+            })))
+            .map_err(|e| anyhow::format_err!("{}", e))
+            .context("unable to send event to event loop")
+    }
+
+    fn tick_tweens(&mut self) -> anyhow::Result<()> {
+        let now = self.clock.now();
+        let mut done_tweens = Vec::new();
+        self.tweens.retain(|&id, tween| {
+            if tween.advance(now) {
+                done_tweens.push(id);
+                false
+            } else {
+                true
+            }
+        });
+        if !done_tweens.is_empty() {
+            done_tweens.sort();
+            self.base
+                .proxy
+                .send_event(GameUserEvent::Dispatch(DispatchMsg::ExecuteDispatch(
+                    done_tweens,
+                )))
+                .map_err(|e| anyhow::format_err!("{}", e))
+                .context("unable to send event to event loop")?;
+        }
+        Ok(())
+    }
+
+    fn deliver_net_events(&mut self) -> anyhow::Result<()> {
+        if self.net_events.is_empty() {
+            return Ok(());
+        }
+        let events = std::mem::take(&mut self.net_events);
+        self.base
+            .proxy
+            .send_event(GameUserEvent::Execute(Box::new(
+                move |main_ctx, root_scene| {
+                    main_ctx.handle_net_events(root_scene, &events);
+                    Ok(())
+                },
+            )))
+            .map_err(|e| anyhow::format_err!("{}", e))
+            .context("unable to send event to event loop")
+    }
+
+    fn fire_timeouts(&mut self) -> anyhow::Result<()> {
+        let now = self.clock.now();
         let mut done_timeouts = Vec::new();
         self.timeouts.retain(|&id, &mut end| {
-            if Instant::now() >= end {
+            if now >= end {
                 done_timeouts.push(id);
                 false
             } else {
@@ -59,6 +497,9 @@ impl GameServer for Server {
             }
         });
         if !done_timeouts.is_empty() {
+            // sorted so that timeouts ending on the same tick are executed
+            // in a deterministic order
+            done_timeouts.sort();
             self.base
                 .proxy
                 .send_event(GameUserEvent::Dispatch(DispatchMsg::ExecuteDispatch(
@@ -69,21 +510,73 @@ impl GameServer for Server {
         }
         Ok(())
     }
-    fn to_send(self) -> anyhow::Result<SendGameServer> {
-        Ok(SendGameServer::Update(Box::new(self)))
+
+    fn tick_behavior_trees(&mut self) {
+        let now = self.clock.now();
+        for scheduled in self.behavior_trees.values_mut() {
+            if now >= scheduled.next_tick {
+                scheduled.tree.tick();
+                // don't try to catch up on missed ticks
+                scheduled.next_tick =
+                    (scheduled.next_tick + scheduled.tree.tick_interval.as_secs_f64()).max(now);
+            }
+        }
+    }
+
+    fn poll_paths(&mut self) -> anyhow::Result<()> {
+        // results in lockstep mode have to be delivered on the tick after
+        // the query, no matter how long the search takes
+        let blocking = self.clock.is_lockstep();
+        for pending in std::mem::take(&mut self.pending_paths) {
+            // the requesting scene is gone, its callback must not run
+            if pending.task.cancel.is_cancelled() {
+                continue;
+            }
+            let result = if blocking {
+                match pending.task.join.join() {
+                    JoinTaskResult::Done(path) => TryJoinTaskResult::Joined(path),
+                    JoinTaskResult::ResultTaken => TryJoinTaskResult::JoinedResultTaken,
+                }
+            } else {
+                pending.task.join.try_join()
+            };
+            let path = match result {
+                TryJoinTaskResult::NotJoined => {
+                    self.pending_paths.push(pending);
+                    continue;
+                }
+                TryJoinTaskResult::Joined(path) => path,
+                TryJoinTaskResult::JoinedResultTaken => {
+                    tracing::warn!("pathfinding task finished without a result");
+                    None
+                }
+            };
+
+            let callback = pending.callback;
+            self.base
+                .proxy
+                .send_event(GameUserEvent::Execute(Box::new(
+                    move |main_ctx, root_scene| callback(main_ctx, root_scene, path),
+                )))
+                .map_err(|e| anyhow::format_err!("{}", e))
+                .context("unable to send event to event loop")?;
+        }
+        Ok(())
     }
 }
 
-impl Server {
-    pub fn new(proxy: EventLoopProxy<GameUserEvent>) -> (Self, ServerChannel) {
-        let (base, sender, receiver) = BaseGameServer::new(proxy);
-        (
-            Self {
-                base,
-                timeouts: HashMap::new(),
-            },
-            ServerChannel { sender, receiver },
-        )
+impl Drop for Server {
+    fn drop(&mut self) {
+        for pending in self.pending_paths.iter() {
+            pending.task.cancel.cancel();
+        }
+        for task in self
+            .cull_views
+            .values()
+            .filter_map(|view| view.task.as_ref())
+        {
+            task.cancel.cancel();
+        }
     }
 }
 
@@ -106,7 +599,7 @@ impl GameServerSendChannel<RecvMsg> for ServerChannel {
 
 impl ServerChannel {
     pub fn set_timeout(&self, duration: Duration, id: Uid) -> anyhow::Result<()> {
-        self.send(RecvMsg::SetTimeout(Instant::now() + duration, id))
+        self.send(RecvMsg::SetTimeout(duration, id))
             .context("unable to send timeout request")
     }
 
@@ -115,8 +608,188 @@ impl ServerChannel {
             .context("unable to send cancel timeout request")
     }
 
+    /// Animates a value on the update server, `apply` is called with the
+    /// new value every tick and the dispatch `id` is executed on the main
+    /// thread once the tween ends.
+    pub fn tween<T, F>(&self, id: Uid, tween: Tween<T>, apply: F) -> anyhow::Result<()>
+    where
+        T: Interpolate,
+        F: TweenApply<T> + 'static,
+    {
+        self.send(RecvMsg::StartTween(
+            id,
+            Box::new(ScheduledTween::new(tween, apply)),
+        ))
+        .context("unable to send tween")
+    }
+
+    /// The value is left where it was, the completion dispatch is never
+    /// executed.
+    pub fn cancel_tween(&self, id: Uid) -> anyhow::Result<()> {
+        self.send(RecvMsg::CancelTween(id))
+            .context("unable to send tween cancellation")
+    }
+
+    /// `id` is used to cancel the sequence, its calls must already be in
+    /// the main thread dispatch list, see `Sequence::schedule`.
+    pub fn start_sequence(&self, id: Uid, sequence: ScheduledSequence) -> anyhow::Result<()> {
+        self.send(RecvMsg::StartSequence(id, sequence))
+            .context("unable to send sequence")
+    }
+
+    /// Steps that haven't run yet are dropped.
+    pub fn cancel_sequence(&self, id: Uid) -> anyhow::Result<()> {
+        self.send(RecvMsg::CancelSequence(id))
+            .context("unable to send sequence cancellation")
+    }
+
+    pub fn find_path<F>(
+        &self,
+        grid: Arc<NavGrid>,
+        query: PathQuery,
+        cancel: CancellationToken,
+        callback: F,
+    ) -> anyhow::Result<()>
+    where
+        F: PathCallback + 'static,
+    {
+        self.send(RecvMsg::FindPath(grid, query, cancel, Box::new(callback)))
+            .context("unable to send pathfinding request")
+    }
+
+    /// Hands `tree` over to the update server, which ticks it every
+    /// `tree.tick_interval` until the returned handle is dropped.
+    pub fn add_behavior_tree(&self, tree: BehaviorTree) -> anyhow::Result<BehaviorTreeHandle> {
+        let id = Uid::new();
+        let handle = BehaviorTreeHandle {
+            id,
+            blackboard: tree.blackboard.clone(),
+            snapshot: tree.snapshot(),
+            channel: self.clone_sender(),
+        };
+        self.send(RecvMsg::AddBehaviorTree(id, Box::new(tree)))
+            .context("unable to send behavior tree")?;
+        Ok(handle)
+    }
+
+    /// Inserts or moves the box `id` in the spatial index of the update
+    /// server.
+    pub fn set_collider(&self, id: Uid, aabb: Aabb) -> anyhow::Result<()> {
+        self.send(RecvMsg::SetCollider(id, aabb))
+            .context("unable to send collider update")
+    }
+
+    pub fn remove_collider(&self, id: Uid) -> anyhow::Result<()> {
+        self.send(RecvMsg::RemoveCollider(id))
+            .context("unable to send collider removal")
+    }
+
+    /// Culls the spatial index against `bounds` on the job system every
+    /// tick until the returned handle is dropped, see
+    /// `Camera2D::world_bounds`.
+    pub fn add_cull_view(&self, bounds: Aabb) -> anyhow::Result<CullViewHandle> {
+        let handle = CullViewHandle {
+            id: Uid::new(),
+            visible: Arc::new(VisibleSet::new()),
+            channel: self.clone_sender(),
+        };
+        handle.set_bounds(bounds)?;
+        Ok(handle)
+    }
+
+    pub fn query_region(&self, region: Aabb) -> anyhow::Result<ServerQuery<Vec<Uid>>> {
+        self.query(move |server| server.spatial.query_region(&region))
+    }
+
+    pub fn raycast(&self, ray: Ray) -> anyhow::Result<ServerQuery<Option<RayHit>>> {
+        self.query(move |server| server.spatial.raycast(&ray))
+    }
+
+    /// Current beat of the audio server's beat clock.
+    pub fn current_beat(&self) -> anyhow::Result<ServerQuery<f64>> {
+        self.query(|server| server.beat_clock.beat())
+    }
+
+    /// Executes `callback` on the update server, the returned future
+    /// resolves to its result.
+    pub fn query<F, R>(&self, callback: F) -> anyhow::Result<ServerQuery<R>>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Server) -> R + Send + 'static,
+    {
+        let (ret, query) = query::query();
+        self.send(RecvMsg::Execute(Box::new(move |server: &mut Server| {
+            let value = callback(server);
+            ret.send(value, &server.base.proxy)
+                .context("unable to return update query result")
+                .log_warn();
+        })))
+        .context("unable to send query to update server")?;
+        Ok(query)
+    }
+
+    /// Simulates particles spawned at `position` as defined by `def` every
+    /// tick, until the returned handle is dropped.
+    pub fn add_particle_effect(
+        &self,
+        def: EmitterDef,
+        position: Vec2,
+    ) -> anyhow::Result<ParticleEffectHandle> {
+        let id = Uid::new();
+        let sprites = Arc::new(ParticleSprites::new());
+        self.send(RecvMsg::AddParticleEffect(
+            id,
+            Box::new(def),
+            position,
+            sprites.clone(),
+        ))
+        .context("unable to send particle effect")?;
+        Ok(ParticleEffectHandle::new(id, sprites, self.clone_sender()))
+    }
+
     pub fn set_frequency_profiling(&self, fp: bool) -> anyhow::Result<()> {
         self.send(RecvMsg::SetFrequencyProfiling(fp))
             .context("unable to send frequency profiling request")
     }
 }
+
+pub struct BehaviorTreeHandle {
+    id: Uid,
+    pub blackboard: Arc<Mutex<Blackboard>>,
+    pub snapshot: Arc<Mutex<Vec<NodeSnapshot>>>,
+    channel: ServerSendChannel<RecvMsg>,
+}
+
+impl Drop for BehaviorTreeHandle {
+    fn drop(&mut self) {
+        self.channel
+            .send(RecvMsg::RemoveBehaviorTree(self.id))
+            .context("unable to remove behavior tree")
+            .log_warn();
+    }
+}
+
+pub struct CullViewHandle {
+    id: Uid,
+    pub visible: Arc<VisibleSet>,
+    channel: ServerSendChannel<RecvMsg>,
+}
+
+impl CullViewHandle {
+    /// Moves the view, e.g. after the camera, the visible set catches up
+    /// within a couple of ticks.
+    pub fn set_bounds(&self, bounds: Aabb) -> anyhow::Result<()> {
+        self.channel
+            .send(RecvMsg::SetCullView(self.id, bounds, self.visible.clone()))
+            .context("unable to send cull view")
+    }
+}
+
+impl Drop for CullViewHandle {
+    fn drop(&mut self) {
+        self.channel
+            .send(RecvMsg::RemoveCullView(self.id))
+            .context("unable to remove cull view")
+            .log_warn();
+    }
+}
@@ -0,0 +1,211 @@
+use std::{collections::VecDeque, time::Duration};
+
+use trait_set::trait_set;
+
+use crate::{
+    exec::dispatch::{DispatchList, EventDispatch},
+    utils::uid::Uid,
+};
+
+trait_set! {
+    pub trait SequenceCondition = FnMut() -> bool + Send;
+}
+
+enum Step {
+    Wait(Duration),
+    Call(Box<dyn EventDispatch>),
+    WaitUntil(Box<dyn SequenceCondition>),
+    Parallel(Vec<Sequence>),
+}
+
+/// Scripted steps run one after another by the update server, e.g. an intro
+/// animation followed by enabling input, instead of nesting `set_timeout`
+/// callbacks.
+#[derive(Default)]
+pub struct Sequence {
+    steps: Vec<Step>,
+}
+
+enum ScheduledStep {
+    Wait(f64),
+    Call(Uid),
+    WaitUntil(Box<dyn SequenceCondition>),
+    Parallel(Vec<ScheduledSequence>),
+}
+
+/// Update server side of a `Sequence`, the callbacks are replaced by the ids
+/// of their dispatches.
+pub struct ScheduledSequence {
+    steps: VecDeque<ScheduledStep>,
+    /// server time the current step started at, waits are chained from the
+    /// end of the previous one so that they don't drift with the tick rate
+    time: Option<f64>,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn wait(mut self, duration: Duration) -> Self {
+        self.steps.push(Step::Wait(duration));
+        self
+    }
+
+    /// Executes `callback` on the main thread. The sequence doesn't wait for
+    /// the callback to finish before moving to the next step.
+    pub fn call<F>(mut self, callback: F) -> Self
+    where
+        F: EventDispatch + 'static,
+    {
+        self.steps.push(Step::Call(Box::new(callback)));
+        self
+    }
+
+    /// Waits until `condition`, checked every update tick, returns true.
+    pub fn wait_until<F>(mut self, condition: F) -> Self
+    where
+        F: SequenceCondition + 'static,
+    {
+        self.steps.push(Step::WaitUntil(Box::new(condition)));
+        self
+    }
+
+    /// Runs `sequences` side by side, the next step starts once all of them
+    /// have ended.
+    pub fn parallel(mut self, sequences: impl IntoIterator<Item = Sequence>) -> Self {
+        self.steps
+            .push(Step::Parallel(sequences.into_iter().collect()));
+        self
+    }
+
+    /// Moves the callbacks into `dispatch_list`.
+    pub fn schedule(self, dispatch_list: &mut DispatchList) -> ScheduledSequence {
+        ScheduledSequence {
+            steps: self
+                .steps
+                .into_iter()
+                .map(|step| match step {
+                    Step::Wait(duration) => ScheduledStep::Wait(duration.as_secs_f64()),
+                    Step::Call(callback) => ScheduledStep::Call(dispatch_list.push_boxed(callback)),
+                    Step::WaitUntil(condition) => ScheduledStep::WaitUntil(condition),
+                    Step::Parallel(sequences) => ScheduledStep::Parallel(
+                        sequences
+                            .into_iter()
+                            .map(|sequence| sequence.schedule(dispatch_list))
+                            .collect(),
+                    ),
+                })
+                .collect(),
+            time: None,
+        }
+    }
+}
+
+impl ScheduledSequence {
+    /// Runs every step that can run at `now`, pushing the dispatches to
+    /// execute into `calls`. Returns whether the sequence has ended.
+    pub fn advance(&mut self, now: f64, calls: &mut Vec<Uid>) -> bool {
+        while let Some(step) = self.steps.front_mut() {
+            let start = *self.time.get_or_insert(now);
+            match step {
+                ScheduledStep::Wait(duration) => {
+                    if now < start + *duration {
+                        return false;
+                    }
+                    self.time = Some(start + *duration);
+                }
+
+                ScheduledStep::Call(id) => calls.push(*id),
+
+                ScheduledStep::WaitUntil(condition) => {
+                    if !condition() {
+                        return false;
+                    }
+                    self.time = Some(now);
+                }
+
+                ScheduledStep::Parallel(sequences) => {
+                    let mut done = true;
+                    for sequence in sequences.iter_mut() {
+                        sequence.time.get_or_insert(start);
+                        done &= sequence.advance(now, calls);
+                    }
+                    if !done {
+                        return false;
+                    }
+                    // continue from the branch that ended last
+                    self.time = sequences
+                        .iter()
+                        .filter_map(|sequence| sequence.time)
+                        .reduce(f64::max)
+                        .or(Some(now));
+                }
+            }
+            self.steps.pop_front();
+        }
+        true
+    }
+
+    /// Dispatches that haven't been executed yet, to be dropped if the
+    /// sequence is cancelled.
+    pub fn pending_calls(&self) -> Vec<Uid> {
+        let mut calls = Vec::new();
+        self.collect_pending_calls(&mut calls);
+        calls
+    }
+
+    fn collect_pending_calls(&self, calls: &mut Vec<Uid>) {
+        for step in self.steps.iter() {
+            match step {
+                ScheduledStep::Call(id) => calls.push(*id),
+                ScheduledStep::Parallel(sequences) => sequences
+                    .iter()
+                    .for_each(|sequence| sequence.collect_pending_calls(calls)),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[test]
+fn test() {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    let mut dispatch_list = DispatchList::new();
+    let flag = Arc::new(AtomicBool::new(false));
+    let mut sequence = Sequence::new()
+        .wait(Duration::from_secs(1))
+        .call(|_, _| Ok(()))
+        .parallel([
+            Sequence::new()
+                .wait(Duration::from_secs(1))
+                .call(|_, _| Ok(())),
+            Sequence::new()
+                .wait(Duration::from_secs(2))
+                .call(|_, _| Ok(())),
+        ])
+        .wait_until({
+            let flag = flag.clone();
+            move || flag.load(Ordering::Relaxed)
+        })
+        .call(|_, _| Ok(()))
+        .schedule(&mut dispatch_list);
+    let ids = sequence.pending_calls();
+    assert_eq!(ids.len(), 4);
+
+    let mut calls = Vec::new();
+    assert!(!sequence.advance(0.0, &mut calls));
+    assert!(!sequence.advance(1.5, &mut calls));
+    assert_eq!(calls, &ids[..1]);
+    assert!(!sequence.advance(2.0, &mut calls));
+    assert_eq!(calls, &ids[..2]);
+    assert!(!sequence.advance(3.5, &mut calls));
+    assert_eq!(calls, &ids[..3]);
+    flag.store(true, Ordering::Relaxed);
+    assert!(sequence.advance(3.6, &mut calls));
+    assert_eq!(calls, ids);
+}
@@ -0,0 +1,176 @@
+use std::{f32::consts::PI, time::Duration};
+
+use trait_set::trait_set;
+
+use crate::exec::interpolation::Interpolate;
+
+trait_set! {
+    pub trait TweenApply<T> = FnMut(T) + Send;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineInOut,
+    /// overshoots the target slightly before settling
+    BackOut,
+}
+
+/// Animation of a value from `from` to `to`, run by the update server.
+#[derive(Clone, Debug)]
+pub struct Tween<T> {
+    pub from: T,
+    pub to: T,
+    pub duration: Duration,
+    /// time before the value starts changing, `from` is applied meanwhile
+    pub delay: Duration,
+    pub easing: Easing,
+}
+
+/// Type-erased tween owned by the update server.
+pub trait RunningTween: Send {
+    /// Applies the value at `now` (seconds on the server clock), returns
+    /// whether the tween has ended.
+    fn advance(&mut self, now: f64) -> bool;
+}
+
+pub struct ScheduledTween<T, F> {
+    tween: Tween<T>,
+    apply: F,
+    /// server time of the first tick
+    start: Option<f64>,
+}
+
+impl Easing {
+    /// Maps the linear progress `t` in `[0, 1]` to the eased one.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::QuadIn => t * t,
+            Self::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Self::QuadInOut if t < 0.5 => 2.0 * t * t,
+            Self::QuadInOut => 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0,
+            Self::CubicIn => t * t * t,
+            Self::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Self::CubicInOut if t < 0.5 => 4.0 * t * t * t,
+            Self::CubicInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+            Self::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+            Self::BackOut => {
+                const C1: f32 = 1.70158;
+                const C3: f32 = C1 + 1.0;
+                1.0 + C3 * (t - 1.0).powi(3) + C1 * (t - 1.0).powi(2)
+            }
+        }
+    }
+}
+
+impl<T: Interpolate> Tween<T> {
+    pub fn new(from: T, to: T, duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            delay: Duration::ZERO,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Value `elapsed` seconds after the tween was started, delay included.
+    pub fn sample(&self, elapsed: f64) -> T {
+        let elapsed = elapsed - self.delay.as_secs_f64();
+        let duration = self.duration.as_secs_f64();
+        let t = if duration <= 0.0 {
+            if elapsed >= 0.0 {
+                1.0
+            } else {
+                0.0
+            }
+        } else {
+            (elapsed / duration) as f32
+        };
+        self.from.interpolate(&self.to, self.easing.apply(t))
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.delay + self.duration
+    }
+}
+
+impl<T, F> ScheduledTween<T, F>
+where
+    T: Interpolate,
+    F: TweenApply<T>,
+{
+    pub fn new(tween: Tween<T>, apply: F) -> Self {
+        Self {
+            tween,
+            apply,
+            start: None,
+        }
+    }
+}
+
+impl<T, F> RunningTween for ScheduledTween<T, F>
+where
+    T: Interpolate,
+    F: TweenApply<T>,
+{
+    fn advance(&mut self, now: f64) -> bool {
+        let elapsed = now - *self.start.get_or_insert(now);
+        if elapsed >= self.tween.total_duration().as_secs_f64() {
+            // the last value is exactly `to`, whatever the easing
+            (self.apply)(self.tween.to.clone());
+            return true;
+        }
+        (self.apply)(self.tween.sample(elapsed));
+        false
+    }
+}
+
+#[test]
+fn test() {
+    for easing in [
+        Easing::Linear,
+        Easing::QuadIn,
+        Easing::QuadOut,
+        Easing::QuadInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::SineInOut,
+        Easing::BackOut,
+    ] {
+        assert!(easing.apply(0.0).abs() < 1e-5, "{easing:?}");
+        assert!((easing.apply(1.0) - 1.0).abs() < 1e-5, "{easing:?}");
+    }
+
+    let tween = Tween::new(0.0f32, 10.0, Duration::from_secs(1)).delay(Duration::from_secs(1));
+    assert_eq!(tween.sample(0.5), 0.0);
+    assert_eq!(tween.sample(1.5), 5.0);
+    assert_eq!(tween.sample(3.0), 10.0);
+
+    let mut values = Vec::new();
+    let mut scheduled = ScheduledTween::new(tween, |value| values.push(value));
+    assert!(!scheduled.advance(1.0));
+    assert!(!scheduled.advance(2.25));
+    assert!(scheduled.advance(3.0));
+    drop(scheduled);
+    assert_eq!(values, [0.0, 2.5, 10.0]);
+}
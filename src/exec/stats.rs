@@ -0,0 +1,245 @@
+use std::{fmt::Display, sync::Arc};
+
+use crate::utils::{affinity::CoreSet, mutex::Mutex};
+
+use super::{runner::RunnerId, server::ServerKind};
+
+/// upper bounds (exclusive, in milliseconds) of the histogram buckets, the
+/// last bucket collects everything above the last bound
+pub const TICK_HISTOGRAM_BOUNDS_MS: [f64; 8] = [0.5, 1.0, 2.0, 4.0, 8.0, 16.7, 33.3, 66.7];
+pub const NUM_TICK_HISTOGRAM_BUCKETS: usize = TICK_HISTOGRAM_BOUNDS_MS.len() + 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TickHistogram {
+    pub buckets: [u64; NUM_TICK_HISTOGRAM_BUCKETS],
+}
+
+impl TickHistogram {
+    pub fn bucket_index(duration: f64) -> usize {
+        let ms = duration * 1e3;
+        TICK_HISTOGRAM_BOUNDS_MS
+            .iter()
+            .position(|&bound| ms < bound)
+            .unwrap_or(TICK_HISTOGRAM_BOUNDS_MS.len())
+    }
+
+    pub fn record(&mut self, duration: f64) {
+        self.buckets[Self::bucket_index(duration)] += 1;
+    }
+}
+
+impl Display for TickHistogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[")?;
+        for (i, count) in self.buckets.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match TICK_HISTOGRAM_BOUNDS_MS.get(i) {
+                Some(bound) => write!(f, "<{bound}ms: {count}")?,
+                None => write!(f, ">={}ms: {count}", TICK_HISTOGRAM_BOUNDS_MS[i - 1])?,
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+pub const NUM_SERVER_KINDS: usize = 4;
+
+// smoothing factor of the exponential moving averages used for balancing
+const EMA_ALPHA: f64 = 0.05;
+
+fn ema(current: f64, sample: f64) -> f64 {
+    current + (sample - current) * EMA_ALPHA
+}
+
+/// Frame pacing statistics of a runner, all durations are in seconds.
+#[derive(Clone, Debug, Default)]
+pub struct RunnerStats {
+    /// the frequency the runner was targeting at the last tick
+    pub frequency: f64,
+    pub num_ticks: u64,
+    /// number of ticks where the servers took longer than the target period
+    pub missed_deadlines: u64,
+    /// histogram of the durations between consecutive ticks
+    pub histogram: TickHistogram,
+    pub last_interval: f64,
+    pub max_interval: f64,
+    pub mean_interval: f64,
+    pub mean_work: f64,
+    /// smoothed fraction of the time the runner spends running servers,
+    /// kept across resets
+    pub load: f64,
+    /// smoothed tick cost of each server hosted by the runner (`None` if the
+    /// server is not hosted), indexed by `ServerKind`, kept across resets
+    pub server_costs: [Option<f64>; NUM_SERVER_KINDS],
+    /// core the last tick ran on, `None` if unknown
+    pub core: Option<usize>,
+    /// every core a tick ran on
+    pub cores: CoreSet,
+    /// number of ticks that ran on a different core than the previous one
+    pub core_migrations: u64,
+    // sum of squared differences from the mean (Welford's algorithm)
+    interval_m2: f64,
+}
+
+impl RunnerStats {
+    pub fn record_tick(&mut self, interval: f64, work: f64, frequency: f64) {
+        self.frequency = frequency;
+        self.num_ticks += 1;
+        if frequency > 0.0 && work > 1.0 / frequency {
+            self.missed_deadlines += 1;
+        }
+
+        self.histogram.record(interval);
+        self.last_interval = interval;
+        self.max_interval = self.max_interval.max(interval);
+
+        let n = self.num_ticks as f64;
+        let delta = interval - self.mean_interval;
+        self.mean_interval += delta / n;
+        self.interval_m2 += delta * (interval - self.mean_interval);
+        self.mean_work += (work - self.mean_work) / n;
+        if interval > 0.0 {
+            self.load = ema(self.load, (work / interval).min(1.0));
+        }
+    }
+
+    pub fn record_core(&mut self, core: Option<usize>) {
+        if let Some(core) = core {
+            if self.core.map(|last| last != core).unwrap_or(false) {
+                self.core_migrations += 1;
+            }
+            if core < CoreSet::MAX_CORES {
+                self.cores.insert(core);
+            }
+        }
+        self.core = core;
+    }
+
+    pub fn record_server_costs(&mut self, costs: [Option<f64>; NUM_SERVER_KINDS]) {
+        for (current, cost) in self.server_costs.iter_mut().zip(costs) {
+            *current = cost.map(|cost| current.map_or(cost, |current| ema(current, cost)));
+        }
+    }
+
+    pub fn server_cost(&self, kind: ServerKind) -> Option<f64> {
+        self.server_costs[kind as usize]
+    }
+
+    pub fn num_servers(&self) -> usize {
+        self.server_costs.iter().filter(|c| c.is_some()).count()
+    }
+
+    /// standard deviation of the durations between consecutive ticks
+    pub fn jitter(&self) -> f64 {
+        if self.num_ticks < 2 {
+            0.0
+        } else {
+            (self.interval_m2 / (self.num_ticks - 1) as f64).sqrt()
+        }
+    }
+
+    pub fn missed_deadline_ratio(&self) -> f64 {
+        if self.num_ticks == 0 {
+            0.0
+        } else {
+            self.missed_deadlines as f64 / self.num_ticks as f64
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self {
+            frequency: self.frequency,
+            load: self.load,
+            server_costs: self.server_costs,
+            ..Default::default()
+        };
+    }
+}
+
+impl Display for RunnerStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "target: {:.1}Hz, ticks: {}, mean interval: {:.3}ms, max interval: {:.3}ms, \
+             jitter: {:.3}ms, mean work: {:.3}ms, load: {:.1}%, missed deadlines: {} ({:.1}%), histogram: {}",
+            self.frequency,
+            self.num_ticks,
+            self.mean_interval * 1e3,
+            self.max_interval * 1e3,
+            self.jitter() * 1e3,
+            self.mean_work * 1e3,
+            self.load * 1e2,
+            self.missed_deadlines,
+            self.missed_deadline_ratio() * 1e2,
+            self.histogram,
+        )?;
+        if let Some(core) = self.core {
+            write!(
+                f,
+                ", cores: {:?} (last: {}, migrations: {})",
+                self.cores, core, self.core_migrations
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub type SharedRunnerStats = Arc<Mutex<RunnerStats>>;
+
+/// Stats of every runner, indexed by `RunnerId`
+#[derive(Clone, Default)]
+pub struct StatsRegistry {
+    runners: [SharedRunnerStats; super::NUM_GAME_LOOPS + 1],
+}
+
+impl StatsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn runner(&self, id: RunnerId) -> SharedRunnerStats {
+        self.runners[usize::from(id)].clone()
+    }
+
+    pub fn snapshot(&self, id: RunnerId) -> RunnerStats {
+        self.runners[usize::from(id)].lock().clone()
+    }
+
+    /// snapshots of all runners that have ticked at least once
+    pub fn snapshot_all(&self) -> Vec<(RunnerId, RunnerStats)> {
+        self.runners
+            .iter()
+            .enumerate()
+            .map(|(id, stats)| (id as RunnerId, stats.lock().clone()))
+            .filter(|(_, stats)| stats.num_ticks > 0)
+            .collect()
+    }
+
+    pub fn reset_all(&self) {
+        self.runners.iter().for_each(|stats| stats.lock().reset());
+    }
+}
+
+#[test]
+fn test() {
+    assert_eq!(TickHistogram::bucket_index(0.0), 0);
+    assert_eq!(TickHistogram::bucket_index(0.003), 3);
+    assert_eq!(
+        TickHistogram::bucket_index(1.0),
+        NUM_TICK_HISTOGRAM_BUCKETS - 1
+    );
+
+    let mut stats = RunnerStats::default();
+    for interval in [0.010, 0.020, 0.010, 0.020] {
+        stats.record_tick(interval, interval * 0.8, 100.0);
+    }
+    assert_eq!(stats.num_ticks, 4);
+    assert_eq!(stats.missed_deadlines, 2);
+    assert!((stats.mean_interval - 0.015).abs() < 1e-9);
+    assert!((stats.jitter() - 0.005773502).abs() < 1e-6);
+    stats.reset();
+    assert_eq!(stats.num_ticks, 0);
+    assert_eq!(stats.frequency, 100.0);
+}
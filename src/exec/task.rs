@@ -16,10 +16,23 @@ use executors::{
     Executor,
 };
 
-pub struct TaskExecutor(ManuallyDrop<ThreadPool<StaticParker<SmallThreadData>>>);
+struct TaskPool(ManuallyDrop<ThreadPool<StaticParker<SmallThreadData>>>);
 
+/// Handle to the shared job system, the thread pool is shut down when the
+/// last handle is dropped.
 #[derive(Clone)]
-pub struct CancellationToken(Arc<AtomicBool>);
+pub struct TaskExecutor(Arc<TaskPool>);
+
+/// Cooperative cancellation flag shared by a job and its owner. A token
+/// created with `child` is also cancelled when its parent is.
+#[derive(Clone, Debug)]
+pub struct CancellationToken(Arc<TokenState>);
+
+#[derive(Debug)]
+struct TokenState {
+    cancelled: AtomicBool,
+    parent: Option<CancellationToken>,
+}
 pub struct JoinToken<R>(mpsc::Receiver<R>);
 pub struct TaskHandle<R> {
     pub cancel: CancellationToken,
@@ -49,7 +62,7 @@ where
     }
 }
 
-impl Drop for TaskExecutor {
+impl Drop for TaskPool {
     fn drop(&mut self) {
         unsafe { ManuallyDrop::take(&mut self.0) }
             .shutdown()
@@ -66,7 +79,27 @@ impl<R> TaskHandle<R> {
 
 impl CancellationToken {
     pub fn new() -> Self {
-        Self(Arc::new(AtomicBool::new(false)))
+        Self(Arc::new(TokenState {
+            cancelled: AtomicBool::new(false),
+            parent: None,
+        }))
+    }
+
+    /// Token cancelled either on its own or together with `self`,
+    /// cancelling it doesn't affect `self`.
+    pub fn child(&self) -> Self {
+        Self(Arc::new(TokenState {
+            cancelled: AtomicBool::new(false),
+            parent: Some(self.clone()),
+        }))
+    }
+
+    /// Bails out of long operations, to be used with `?`.
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.is_cancelled() {
+            anyhow::bail!("operation cancelled");
+        }
+        Ok(())
     }
 }
 
@@ -85,14 +118,44 @@ impl<R> JoinToken<R> {
 
 impl TaskExecutor {
     pub fn new() -> Self {
-        Self(ManuallyDrop::new(small_pool(4)))
+        Self(Arc::new(TaskPool(ManuallyDrop::new(small_pool(4)))))
     }
 
     pub fn execute<F>(&self, callback: F)
     where
         F: FnOnce() + Send + 'static,
     {
-        self.0.execute(callback)
+        self.0 .0.execute(callback)
+    }
+
+    /// Runs `callback` on the job system, the callback should check the
+    /// cancellation token periodically.
+    pub fn spawn<F, R>(&self, callback: F) -> TaskHandle<R>
+    where
+        F: FnOnce(&CancellationToken) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.spawn_with(CancellationToken::new(), callback)
+    }
+
+    /// Same as `spawn`, but cancelled through `cancel`, e.g. a child of the
+    /// token of the scene which started the job.
+    pub fn spawn_with<F, R>(&self, cancel: CancellationToken, callback: F) -> TaskHandle<R>
+    where
+        F: FnOnce(&CancellationToken) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (sender, join) = JoinToken::new();
+        let task_cancel = cancel.clone();
+        self.execute(move || {
+            // the job may have been cancelled while still queued
+            if task_cancel.is_cancelled() {
+                return;
+            }
+            // the handle may have been dropped if nobody is waiting for the result
+            sender.send(callback(&task_cancel)).log_trace();
+        });
+        TaskHandle { cancel, join }
     }
 }
 
@@ -143,11 +206,17 @@ pub trait Joinable<R> {
 
 impl Cancellable for CancellationToken {
     fn cancel(&self) {
-        self.0.store(true, Ordering::Relaxed)
+        self.0.cancelled.store(true, Ordering::Release)
     }
 
     fn is_cancelled(&self) -> bool {
-        self.0.load(Ordering::Relaxed)
+        self.0.cancelled.load(Ordering::Acquire)
+            || self
+                .0
+                .parent
+                .as_ref()
+                .map(|parent| parent.is_cancelled())
+                .unwrap_or(false)
     }
 }
 
@@ -179,3 +248,26 @@ impl<R> Joinable<R> for JoinToken<R> {
         self.0.is_disconnected()
     }
 }
+
+#[test]
+fn test() {
+    let parent = CancellationToken::new();
+    let child = parent.child();
+    let grandchild = child.child();
+    assert!(child.check().is_ok());
+
+    grandchild.cancel();
+    assert!(grandchild.is_cancelled());
+    assert!(!child.is_cancelled());
+
+    let sibling = child.child();
+    parent.cancel();
+    assert!(child.is_cancelled());
+    assert!(sibling.is_cancelled());
+    assert!(sibling.check().is_err());
+
+    // a cancelled job doesn't run and leaves nothing to join
+    let executor = TaskExecutor::new();
+    let handle = executor.spawn_with(parent.child(), |_| 0);
+    assert!(matches!(handle.join.join(), JoinTaskResult::ResultTaken));
+}
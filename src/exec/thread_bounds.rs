@@ -0,0 +1,72 @@
+//! Compile-time checks of the types crossing thread boundaries.
+//!
+//! Servers are moved between runner threads as `SendGameServer`, messages
+//! and dispatch closures are sent to them through the server channels.
+//! Adding non-`Send` state to any of these fails the test build here, with
+//! the offending type named in the error, instead of somewhere deep in the
+//! runner code.
+
+#[test]
+fn test_send_sync() {
+    use crate::{
+        assert_not_send, assert_send, assert_sync,
+        graphics::SendHandleContainer,
+        scene::main::RootScene,
+        utils::mpsc::{Receiver, Sender},
+    };
+
+    use super::{
+        dispatch::{DispatchMsg, EventDispatch},
+        server::{
+            audio::{self, AudioDispatch},
+            draw::{self, DrawDispatch},
+            network,
+            update::{self, PathCallback, UpdateDispatch},
+            BaseSendMsg, SendGameServer, ServerSendChannel,
+        },
+    };
+
+    // servers, moved to the runner threads
+    assert_send!(SendGameServer);
+    assert_send!(audio::Server);
+    assert_send!(draw::SendServer);
+    assert_send!(network::Server);
+    assert_send!(update::Server);
+    assert_send!(SendHandleContainer);
+    // shared by the main thread and the draw server
+    assert_send!(RootScene);
+    assert_sync!(RootScene);
+
+    // messages
+    assert_send!(BaseSendMsg);
+    assert_send!(DispatchMsg);
+    assert_send!(audio::RecvMsg);
+    assert_send!(audio::SendMsg);
+    assert_send!(draw::RecvMsg);
+    assert_send!(draw::SendMsg);
+    assert_send!(network::RecvMsg);
+    assert_send!(network::SendMsg);
+    assert_send!(update::RecvMsg);
+    assert_send!(update::SendMsg);
+
+    // channels, cloned senders are handed to tasks and other servers
+    assert_send!(Sender<draw::RecvMsg>);
+    assert_sync!(Sender<draw::RecvMsg>);
+    assert_send!(Receiver<draw::SendMsg>);
+    assert_send!(ServerSendChannel<audio::RecvMsg>);
+    assert_send!(ServerSendChannel<draw::RecvMsg>);
+    assert_send!(ServerSendChannel<network::RecvMsg>);
+    assert_send!(ServerSendChannel<update::RecvMsg>);
+    assert_send!(audio::ServerChannel);
+    assert_send!(draw::ServerChannel);
+    assert_send!(network::ServerChannel);
+    assert_send!(update::ServerChannel);
+
+    // dispatch closures
+    assert_send!(Box<dyn AudioDispatch>);
+    assert_send!(Box<dyn DrawDispatch>);
+    assert_send!(Box<dyn UpdateDispatch>);
+    assert_send!(Box<dyn PathCallback>);
+    // executed on the main thread only, free to capture `Rc`s
+    assert_not_send!(Box<dyn EventDispatch>);
+}
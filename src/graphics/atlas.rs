@@ -0,0 +1,398 @@
+use std::{
+    borrow::Cow,
+    cmp::Reverse,
+    iter,
+    sync::{Arc, Weak},
+};
+
+use anyhow::{ensure, Context};
+use glam::Vec2;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+use crate::{
+    events::{error::Subsystem, GameUserEvent},
+    exec::server::draw::{self, ServerSendChannelExt},
+    utils::mutex::Mutex,
+};
+
+use super::{
+    context::DrawContext,
+    sprite_renderer::Sprite,
+    wrappers::texture::{ImageData, TextureHandle, TextureOptions, TextureWrap},
+};
+
+/// Transparent pixels around every region, so that linear filtering doesn't
+/// bleed the neighbouring regions in.
+const PADDING: u32 = 1;
+
+/// Packs rectangles into rows ("shelves") as tall as the first rectangle
+/// put in them, from the top of the page down.
+#[derive(Clone, Debug)]
+pub struct ShelfPacker {
+    size: PhysicalSize<u32>,
+    shelves: Vec<Shelf>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// width taken from the left
+    used: u32,
+}
+
+/// Where a region is, set on the draw server once its pixels are uploaded
+/// there.
+struct Placement {
+    texture: TextureHandle,
+    uv: [Vec2; 2],
+}
+
+/// An image packed into an `Atlas`, drawn with `DrawContext::draw_region`.
+/// Its space is reclaimed by the next repack once every clone is dropped.
+#[derive(Clone)]
+pub struct AtlasRegion {
+    size: PhysicalSize<u32>,
+    placement: Arc<Mutex<Option<Placement>>>,
+}
+
+struct AtlasEntry {
+    /// padded with transparent pixels, which also clears what a previous
+    /// layout left around the region
+    image: Arc<ImageData>,
+    page: usize,
+    position: PhysicalPosition<u32>,
+    placement: Weak<Mutex<Option<Placement>>>,
+}
+
+struct AtlasPage {
+    texture: TextureHandle,
+    packer: ShelfPacker,
+}
+
+/// Packs many small images into a few large textures on the draw server,
+/// so that sprites of different images are drawn in the same batch.
+pub struct Atlas {
+    name: Cow<'static, str>,
+    page_size: PhysicalSize<u32>,
+    pages: Vec<AtlasPage>,
+    entries: Vec<AtlasEntry>,
+    /// regions were dropped since the last repack
+    fragmented: bool,
+}
+
+impl ShelfPacker {
+    pub fn new(size: PhysicalSize<u32>) -> Self {
+        Self {
+            size,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Top left corner of free space of `size`, `None` if the page is full.
+    pub fn insert(&mut self, size: PhysicalSize<u32>) -> Option<PhysicalPosition<u32>> {
+        let page = self.size;
+        // the lowest shelf it fits in wastes the least space
+        if let Some(shelf) = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= size.height && page.width - shelf.used >= size.width)
+            .min_by_key(|shelf| shelf.height)
+        {
+            let position = PhysicalPosition::new(shelf.used, shelf.y);
+            shelf.used += size.width;
+            return Some(position);
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map(|shelf| shelf.y + shelf.height)
+            .unwrap_or(0);
+        if size.width > page.width || y + size.height > page.height {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height: size.height,
+            used: size.width,
+        });
+        Some(PhysicalPosition::new(0, y))
+    }
+}
+
+impl AtlasRegion {
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    /// Page texture and top left, bottom right UVs of the region, `None`
+    /// until the draw server uploaded it.
+    pub fn placement(&self) -> Option<(TextureHandle, [Vec2; 2])> {
+        self.placement
+            .lock()
+            .as_ref()
+            .map(|placement| (placement.texture.clone(), placement.uv))
+    }
+}
+
+impl Atlas {
+    pub fn new(name: impl Into<Cow<'static, str>>, page_size: PhysicalSize<u32>) -> Self {
+        Self {
+            name: name.into(),
+            page_size,
+            pages: Vec::new(),
+            entries: Vec::new(),
+            fragmented: false,
+        }
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Packs `image` into a page. If the pages are full, they are repacked
+    /// first when regions were dropped, then a new page is created if that
+    /// wasn't enough.
+    pub fn add(
+        &mut self,
+        draw: &mut draw::ServerChannel,
+        image: ImageData,
+    ) -> anyhow::Result<AtlasRegion> {
+        let size = image.size;
+        ensure!(
+            size.width > 0
+                && size.height > 0
+                && size.width + 2 * PADDING <= self.page_size.width
+                && size.height + 2 * PADDING <= self.page_size.height,
+            "a {}x{} image doesn't fit in the {}x{} pages of atlas {}",
+            size.width,
+            size.height,
+            self.page_size.width,
+            self.page_size.height,
+            self.name
+        );
+        self.sweep();
+
+        let placement = Arc::new(Mutex::new(None));
+        let mut entry = AtlasEntry {
+            image: Arc::new(pad(&image)),
+            page: 0,
+            position: PhysicalPosition::new(0, 0),
+            placement: Arc::downgrade(&placement),
+        };
+        if !self.place(&mut entry) && !(self.fragmented && self.repack(draw, &mut entry)?) {
+            self.add_page(draw)?;
+            ensure!(
+                self.place(&mut entry),
+                "unable to place image in a new page"
+            );
+        }
+        self.upload(draw, &entry)?;
+        self.entries.push(entry);
+        Ok(AtlasRegion { size, placement })
+    }
+
+    fn sweep(&mut self) {
+        let count = self.entries.len();
+        self.entries
+            .retain(|entry| entry.placement.strong_count() > 0);
+        self.fragmented |= self.entries.len() < count;
+    }
+
+    fn place(&mut self, entry: &mut AtlasEntry) -> bool {
+        let size = entry.image.size;
+        let placed = self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, page)| Some((index, page.packer.insert(size)?)));
+        if let Some((page, position)) = placed {
+            entry.page = page;
+            entry.position = position;
+        }
+        placed.is_some()
+    }
+
+    /// Packs the live regions and `entry` into the existing pages from
+    /// scratch, tallest first. The layout is only applied (and the moved
+    /// regions uploaded again) if they all fit.
+    fn repack(
+        &mut self,
+        draw: &mut draw::ServerChannel,
+        entry: &mut AtlasEntry,
+    ) -> anyhow::Result<bool> {
+        let sizes = self
+            .entries
+            .iter()
+            .map(|entry| entry.image.size)
+            .chain(iter::once(entry.image.size))
+            .collect::<Vec<_>>();
+        let mut order = (0..sizes.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| Reverse(sizes[i].height));
+
+        let mut packers = vec![ShelfPacker::new(self.page_size); self.pages.len()];
+        let mut layout = vec![(0, PhysicalPosition::new(0, 0)); sizes.len()];
+        for i in order {
+            let placed = packers
+                .iter_mut()
+                .enumerate()
+                .find_map(|(page, packer)| Some((page, packer.insert(sizes[i])?)));
+            match placed {
+                Some(placed) => layout[i] = placed,
+                None => return Ok(false),
+            }
+        }
+
+        tracing::debug!("repacking {} regions of atlas {}", sizes.len(), self.name);
+        for (page, packer) in self.pages.iter_mut().zip(packers) {
+            page.packer = packer;
+        }
+        for (entry, (page, position)) in self
+            .entries
+            .iter_mut()
+            .chain(iter::once(&mut *entry))
+            .zip(layout)
+        {
+            entry.page = page;
+            entry.position = position;
+        }
+        self.fragmented = false;
+        for entry in self.entries.iter() {
+            self.upload(draw, entry)?;
+        }
+        Ok(true)
+    }
+
+    fn add_page(&mut self, draw: &mut draw::ServerChannel) -> anyhow::Result<()> {
+        let (width, height) = (self.page_size.width, self.page_size.height);
+        // mipmaps would blend the neighbouring regions together
+        let options = TextureOptions {
+            wrap: TextureWrap::ClampToEdge,
+            mipmaps: false,
+            ..Default::default()
+        };
+        let blank = ImageData::new(
+            self.page_size,
+            vec![0; width as usize * height as usize * 4],
+        )?
+        .options(options);
+        let texture = TextureHandle::new(
+            draw,
+            format!("{} page {}", self.name, self.pages.len()),
+            blank,
+        )
+        .context("unable to create atlas page")?;
+        self.pages.push(AtlasPage {
+            texture,
+            packer: ShelfPacker::new(self.page_size),
+        });
+        Ok(())
+    }
+
+    fn upload(&self, draw: &mut draw::ServerChannel, entry: &AtlasEntry) -> anyhow::Result<()> {
+        // not held meanwhile, so that the space of a region dropped before
+        // the upload is reclaimed as well
+        let placement = entry.placement.clone();
+        let texture = self.pages[entry.page].texture.clone();
+        let (image, position) = (entry.image.clone(), entry.position);
+        let uv = region_uv(position, image.size, self.page_size);
+        draw.execute_draw_event(move |context, _| {
+            let placement = placement.upgrade()?;
+            texture
+                .try_get(context)
+                .context("atlas page was not created")
+                .and_then(|page| page.upload_at(position.x, position.y, &image))
+                .map(|()| *placement.lock() = Some(Placement { texture, uv }))
+                .err()
+                .map(|e| GameUserEvent::error(Subsystem::Draw, "draw.atlas_upload", e))
+        })
+    }
+}
+
+impl DrawContext {
+    /// Queues `sprite` textured with `region`, its UVs are relative to the
+    /// region. Nothing is drawn until the region is uploaded.
+    pub fn draw_region(&mut self, region: &AtlasRegion, sprite: &Sprite) {
+        let (texture, [uv0, uv1]) = match region.placement() {
+            Some(placement) => placement,
+            None => return,
+        };
+        let texture = match texture.try_get(self) {
+            Some(texture) => *texture,
+            None => return,
+        };
+        let [top_left, bottom_right] = sprite.uv;
+        let sprite = Sprite {
+            uv: [
+                uv0 + (uv1 - uv0) * top_left,
+                uv0 + (uv1 - uv0) * bottom_right,
+            ],
+            ..*sprite
+        };
+        self.draw_sprite(texture, &sprite);
+    }
+}
+
+/// Copy of `image` with `PADDING` transparent pixels around it.
+fn pad(image: &ImageData) -> ImageData {
+    let size = PhysicalSize::new(
+        image.size.width + 2 * PADDING,
+        image.size.height + 2 * PADDING,
+    );
+    let mut pixels = vec![0; size.width as usize * size.height as usize * 4];
+    let row = image.size.width as usize * 4;
+    for (y, src) in image.pixels.chunks_exact(row).enumerate() {
+        let start = ((y + PADDING as usize) * size.width as usize + PADDING as usize) * 4;
+        pixels[start..start + row].copy_from_slice(src);
+    }
+    ImageData {
+        size,
+        pixels,
+        options: image.options,
+    }
+}
+
+/// UVs of the image inside the padded rectangle at `position`.
+fn region_uv(
+    position: PhysicalPosition<u32>,
+    padded: PhysicalSize<u32>,
+    page: PhysicalSize<u32>,
+) -> [Vec2; 2] {
+    let page = Vec2::new(page.width as f32, page.height as f32);
+    let top_left = Vec2::new(position.x as f32, position.y as f32) + PADDING as f32;
+    let size = Vec2::new(padded.width as f32, padded.height as f32) - 2.0 * PADDING as f32;
+    [top_left / page, (top_left + size) / page]
+}
+
+#[test]
+fn test() {
+    let mut packer = ShelfPacker::new(PhysicalSize::new(10, 10));
+    let at = |x, y| Some(PhysicalPosition::new(x, y));
+    assert_eq!(packer.insert(PhysicalSize::new(6, 4)), at(0, 0));
+    assert_eq!(packer.insert(PhysicalSize::new(4, 3)), at(6, 0));
+    // too wide for the first shelf
+    assert_eq!(packer.insert(PhysicalSize::new(5, 5)), at(0, 4));
+    assert_eq!(packer.insert(PhysicalSize::new(5, 2)), at(5, 4));
+    assert_eq!(packer.insert(PhysicalSize::new(3, 2)), None);
+    assert_eq!(packer.insert(PhysicalSize::new(11, 1)), None);
+    assert_eq!(packer.insert(PhysicalSize::new(10, 1)), at(0, 9));
+
+    let uv = region_uv(
+        PhysicalPosition::new(2, 0),
+        PhysicalSize::new(6, 4),
+        PhysicalSize::new(8, 8),
+    );
+    assert_eq!(uv, [Vec2::new(0.375, 0.125), Vec2::new(0.875, 0.375)]);
+
+    let image = ImageData::new(PhysicalSize::new(1, 2), vec![255; 8]).unwrap();
+    let padded = pad(&image);
+    assert_eq!(padded.size, PhysicalSize::new(3, 4));
+    let opaque = padded
+        .pixels
+        .chunks_exact(4)
+        .map(|pixel| pixel[3] == 255)
+        .collect::<Vec<_>>();
+    let expected = [0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0].map(|pixel| pixel == 1);
+    assert_eq!(opaque, expected);
+}
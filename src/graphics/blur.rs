@@ -1,12 +1,14 @@
+use std::borrow::Cow;
+
+use gl::types::GLuint;
 use winit::dpi::PhysicalSize;
 
-use crate::exec::server::draw::{self, ServerSendChannelExt};
+use crate::exec::server::draw;
 
-use super::wrappers::{
-    framebuffer::{DefaultTextureFramebuffer, Framebuffer, FramebufferHandle},
-    shader::ProgramHandle,
-    texture::TextureHandle,
-    vertex_array::VertexArrayHandle,
+use super::{
+    context::DrawContext,
+    post_effect::{FullscreenPass, PostEffect},
+    wrappers::{framebuffer::DefaultTextureFramebuffer, vertex_array::VertexArrayHandle},
 };
 
 pub fn generate_gaussian_kernel<const N: usize>(sigma: f32) -> [f32; N] {
@@ -35,20 +37,6 @@ pub fn calc_blur_framebuffer_scale(sigma: f32) -> f32 {
 }
 
 mod shader {
-    pub const VERTEX: &str = r#"
-    #version 300 es
-    out vec2 tex_coords;
-    const vec2 positions[4] = vec2[](
-        vec2(-1.0, 1.0), vec2(1.0, 1.0),
-        vec2(-1.0, -1.0), vec2(1.0, -1.0)
-    );
-    void main() {
-        vec2 pos = positions[gl_VertexID];
-        gl_Position = vec4(pos, 0.0, 1.0);
-        tex_coords = (pos + vec2(1.0)) * vec2(0.5);
-    }
-    "#;
-
     pub const FRAGMENT: &str = r#"
     #version 300 es
     precision mediump float;
@@ -76,127 +64,76 @@ mod shader {
     }"#;
 }
 
+/// Gaussian blur of the whole screen as a post effect, blurring
+/// horizontally into an intermediate framebuffer then vertically. The
+/// intermediate framebuffer is downscaled for large `sigma`s, see
+/// `calc_blur_framebuffer_scale`.
 #[derive(Clone)]
-pub struct TexturedFramebuffer {
-    pub framebuffer: FramebufferHandle,
-    pub texture: TextureHandle,
+pub struct BlurEffect {
+    pass: FullscreenPass,
+    intermediate: DefaultTextureFramebuffer,
+    /// downscale of the intermediate framebuffer, chosen on `resize`
+    scale: f32,
+    pub sigma: f32,
 }
 
-#[derive(Clone)]
-pub struct BlurRenderer {
-    vertex_array: VertexArrayHandle,
-    program: ProgramHandle,
-    pub framebuffers: [DefaultTextureFramebuffer; 2],
-}
-
-impl BlurRenderer {
+impl BlurEffect {
     pub fn new(
         dummy_vao: VertexArrayHandle,
         draw: &mut draw::ServerChannel,
+        sigma: f32,
     ) -> anyhow::Result<Self> {
-        let program = ProgramHandle::new_vf(
-            draw,
-            "blur shader program",
-            shader::VERTEX,
-            shader::FRAGMENT,
-        )?;
-        let framebuffer_0 = DefaultTextureFramebuffer::new(draw, "blur framebuffer 0")?;
-        let framebuffer_1 = DefaultTextureFramebuffer::new(draw, "blur framebuffer 1")?;
-        let framebuffers = [framebuffer_0, framebuffer_1];
-        // unstable lol
-        // let framebuffers = Self::zero_range_two().try_map(|i| {
-        //     DefaultTextureFramebuffer::new(executor, draw, format!("blur framebuffer {i}"))
-        // })?;
-
         Ok(Self {
-            vertex_array: dummy_vao,
-            program,
-            framebuffers,
+            pass: FullscreenPass::new(dummy_vao, draw, "blur effect program", shader::FRAGMENT)?,
+            intermediate: DefaultTextureFramebuffer::new(draw, "blur effect framebuffer")?,
+            scale: 1.0,
+            sigma,
         })
     }
+}
 
-    pub fn redraw(
-        &mut self,
-        draw: &mut draw::ServerChannel,
-        window_size: PhysicalSize<u32>,
-        texture: TextureHandle,
-        lod: f32,
-        blur_sigma: f32,
-    ) -> anyhow::Result<()> {
-        let downscale = calc_blur_framebuffer_scale(blur_sigma);
-        let framebuffer_size = PhysicalSize {
-            width: (window_size.width as f32 * downscale) as u32,
-            height: (window_size.height as f32 * downscale) as u32,
-        };
-        let blur_sigma = blur_sigma * downscale;
-        for framebuffer in self.framebuffers.iter_mut() {
-            framebuffer.resize(draw, framebuffer_size)?;
-        }
-
-        let slf = self.clone();
-        draw.execute_draw_event(move |context, _| {
-            tracing::info!("redraw");
-            let program = slf.program.get(context);
-            let vertex_array = slf.vertex_array.get(context);
-            let framebuffers = slf
-                .framebuffers
-                .iter()
-                .map(|f| f.framebuffer.get(context))
-                .collect::<Vec<_>>();
+impl PostEffect for BlurEffect {
+    fn name(&self) -> Cow<'static, str> {
+        "blur".into()
+    }
 
-            vertex_array.bind();
-            unsafe {
-                gl::UseProgram(*program);
-                gl::Uniform1f(
-                    gl::GetUniformLocation(*program, "sigma\0".as_ptr() as *const _),
-                    blur_sigma,
-                );
-                gl::Uniform1i(
-                    gl::GetUniformLocation(*program, "tex\0".as_ptr() as *const _),
-                    0,
-                );
-                let loc_pixel = gl::GetUniformLocation(*program, "pixel\0".as_ptr() as *const _);
-                let loc_lod = gl::GetUniformLocation(*program, "lod\0".as_ptr() as *const _);
-                gl::Uniform2f(loc_pixel, 1.0 / framebuffer_size.width as f32, 0.0);
-                gl::Uniform1f(loc_lod, lod);
-                gl::ActiveTexture(gl::TEXTURE0);
-                texture.get(context).bind();
-                framebuffers[0].bind();
-                gl::Clear(gl::COLOR_BUFFER_BIT);
-                gl::Viewport(
-                    0,
-                    0,
-                    framebuffer_size.width as _,
-                    framebuffer_size.height as _,
-                );
-                gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
-                gl::Uniform2f(loc_pixel, 0.0, 1.0 / framebuffer_size.height as f32);
-                gl::Uniform1f(loc_lod, 0.0);
-                gl::ActiveTexture(gl::TEXTURE0);
-                slf.framebuffers[0].texture.get(context).bind();
-                framebuffers[1].bind();
-                gl::Clear(gl::COLOR_BUFFER_BIT);
-                gl::Viewport(
-                    0,
-                    0,
-                    framebuffer_size.width as _,
-                    framebuffer_size.height as _,
-                );
-                gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
-                Framebuffer::unbind_static();
-                gl::Viewport(
-                    0,
-                    0,
-                    window_size.width.try_into().unwrap(),
-                    window_size.height.try_into().unwrap(),
-                );
-            };
-            []
-        })?;
+    fn resize(&mut self, ctx: &mut DrawContext, size: PhysicalSize<u32>) -> anyhow::Result<()> {
+        self.scale = calc_blur_framebuffer_scale(self.sigma);
+        let size = PhysicalSize::new(
+            ((size.width as f32 * self.scale) as u32).max(1),
+            ((size.height as f32 * self.scale) as u32).max(1),
+        );
+        self.intermediate.resize_in_server(ctx, size)?;
+        self.intermediate.size = Some(size);
         Ok(())
     }
 
-    pub fn output_texture_handle(&self) -> TextureHandle {
-        self.framebuffers[1].texture.clone()
+    fn apply(&mut self, ctx: &mut DrawContext, src: GLuint, dst: GLuint) -> anyhow::Result<()> {
+        let size = self
+            .intermediate
+            .size
+            .ok_or_else(|| anyhow::format_err!("blur effect applied before being resized"))?;
+        // in pixels of the intermediate framebuffer, for both passes
+        let sigma = self.sigma * self.scale;
+        let intermediate = *self.intermediate.framebuffer.get(ctx);
+        unsafe { gl::Viewport(0, 0, size.width as _, size.height as _) };
+        self.pass.draw(ctx, src, intermediate, |location| unsafe {
+            gl::Uniform1f(location("sigma"), sigma);
+            gl::Uniform2f(location("pixel"), 1.0 / size.width as f32, 0.0);
+        });
+        unsafe {
+            gl::Viewport(
+                0,
+                0,
+                ctx.display_size.width.get() as _,
+                ctx.display_size.height.get() as _,
+            )
+        };
+        let intermediate = *self.intermediate.texture.get(ctx);
+        self.pass.draw(ctx, intermediate, dst, |location| unsafe {
+            gl::Uniform1f(location("sigma"), sigma);
+            gl::Uniform2f(location("pixel"), 0.0, 1.0 / size.height as f32);
+        });
+        Ok(())
     }
 }
@@ -0,0 +1,124 @@
+use glam::{Affine2, Vec2};
+
+use crate::{
+    spatial::Aabb,
+    ui::utils::geom::{UIPos, UIRect},
+};
+
+use super::context::DrawContext;
+
+/// View of a 2D world drawn into `viewport`, `position` is the world point
+/// shown at the center of the viewport.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera2D {
+    pub position: Vec2,
+    /// screen units per world unit
+    pub zoom: f32,
+    /// rotation of the camera, the world appears rotated the other way
+    pub rotation: f32,
+    /// in window UI units
+    pub viewport: UIRect,
+}
+
+impl Camera2D {
+    pub fn new(viewport: UIRect) -> Self {
+        Self {
+            position: Vec2::ZERO,
+            zoom: 1.0,
+            rotation: 0.0,
+            viewport,
+        }
+    }
+
+    /// World to window UI units.
+    pub fn view(&self) -> Affine2 {
+        let center = Vec2::from(self.viewport.pos) + Vec2::from(self.viewport.size) * 0.5;
+        Affine2::from_translation(center)
+            * Affine2::from_scale_angle_translation(
+                Vec2::splat(self.zoom),
+                -self.rotation,
+                Vec2::ZERO,
+            )
+            * Affine2::from_translation(-self.position)
+    }
+
+    pub fn world_to_screen(&self, world: Vec2) -> UIPos {
+        self.view().transform_point2(world).into()
+    }
+
+    pub fn screen_to_world(&self, screen: UIPos) -> Vec2 {
+        self.view().inverse().transform_point2(screen.into())
+    }
+
+    /// World space box around everything shown in the viewport, e.g. to cull
+    /// what is off screen.
+    pub fn world_bounds(&self) -> Aabb {
+        let view = self.view().inverse();
+        let min = Vec2::from(self.viewport.pos);
+        let max = min + Vec2::from(self.viewport.size);
+        let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+            .map(|corner| view.transform_point2(corner));
+        Aabb {
+            min: corners.into_iter().reduce(Vec2::min).unwrap_or_default(),
+            max: corners.into_iter().reduce(Vec2::max).unwrap_or_default(),
+        }
+    }
+
+    /// Whether the screen position is inside the viewport, e.g. to route
+    /// cursor events to the world instead of the UI around it.
+    pub fn contains(&self, screen: UIPos) -> bool {
+        self.viewport.contains(screen)
+    }
+}
+
+impl DrawContext {
+    /// Draws in world units of `camera` until the matching `pop_camera`,
+    /// clipped to its viewport.
+    pub fn push_camera(&mut self, camera: &Camera2D) {
+        self.transform_stack.push();
+        // the viewport is in window units, whatever was pushed before
+        self.transform_stack.reset_current_transform();
+        self.push_clip(camera.viewport);
+        self.transform_stack.apply(&camera.view());
+    }
+
+    pub fn pop_camera(&mut self) {
+        self.pop_clip();
+        self.transform_stack.pop();
+    }
+}
+
+#[test]
+fn test() {
+    use crate::ui::utils::geom::UISize;
+
+    let mut camera = Camera2D::new(UIRect::new(
+        UIPos::new(100.0, 0.0),
+        UISize::new(200.0, 100.0),
+    ));
+    camera.position = Vec2::new(10.0, 10.0);
+    camera.zoom = 2.0;
+    assert_eq!(
+        camera.world_to_screen(camera.position),
+        UIPos::new(200.0, 50.0)
+    );
+    assert_eq!(
+        camera.world_to_screen(Vec2::new(20.0, 10.0)),
+        UIPos::new(220.0, 50.0)
+    );
+
+    // turning the camera right makes the world turn left
+    camera.rotation = std::f32::consts::FRAC_PI_2;
+    let screen = camera.world_to_screen(Vec2::new(20.0, 10.0));
+    assert_eq!(screen, UIPos::new(200.0, 30.0));
+    assert!(camera
+        .screen_to_world(screen)
+        .abs_diff_eq(Vec2::new(20.0, 10.0), 1e-4));
+    assert!(camera.contains(screen));
+    assert!(!camera.contains(UIPos::new(50.0, 50.0)));
+
+    // rotated by a quarter turn, the 100x50 world units view stands upright
+    let bounds = camera.world_bounds();
+    assert!(bounds.min.abs_diff_eq(Vec2::new(-15.0, -40.0), 1e-4));
+    assert!(bounds.max.abs_diff_eq(Vec2::new(35.0, 60.0), 1e-4));
+}
@@ -0,0 +1,224 @@
+use std::sync::Arc;
+
+use glam::{Affine2, Vec2};
+use glutin::prelude::GlConfig;
+
+use crate::ui::utils::geom::{UIRect, UISize};
+
+use super::context::DrawContext;
+
+/// Area drawing is restricted to, in window UI units.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClipRegion {
+    /// intersection of the bounding boxes of every clip, applied with the
+    /// scissor test
+    pub scissor: UIRect,
+    /// rotated or sheared clips, applied with the stencil buffer on top of
+    /// the scissor
+    pub quads: Vec<[Vec2; 4]>,
+}
+
+/// Nested clip regions, each one inside the previous ones. Regions are
+/// shared with the draws queued while they were current.
+#[derive(Default)]
+pub struct ClipStack(Vec<Arc<ClipRegion>>);
+
+impl ClipRegion {
+    /// `parent` clipped to `rect` transformed by `transform`.
+    pub fn new(parent: Option<&ClipRegion>, rect: UIRect, transform: &Affine2) -> Self {
+        let (min, max) = (
+            Vec2::from(rect.pos),
+            Vec2::from(rect.pos) + Vec2::from(rect.size),
+        );
+        let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+            .map(|corner| transform.transform_point2(corner));
+        let (low, high) = corners.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(low, high), &corner| (low.min(corner), high.max(corner)),
+        );
+        let bounds = UIRect::new(low.into(), (high - low).into());
+
+        let mut region = match parent {
+            Some(parent) => Self {
+                scissor: parent.scissor.intersection(&bounds),
+                quads: parent.quads.clone(),
+            },
+            None => Self {
+                scissor: bounds,
+                quads: Vec::new(),
+            },
+        };
+        let matrix = transform.matrix2;
+        let axis_aligned = matrix.x_axis.y.abs() < 1e-6 && matrix.y_axis.x.abs() < 1e-6;
+        if !axis_aligned {
+            region.quads.push(corners);
+        }
+        region
+    }
+
+    /// Scissor box in pixels of a framebuffer with the given viewport
+    /// (x, y, width, height), whose size is `ui_size` in UI units.
+    pub fn scissor_box(&self, viewport: [i32; 4], ui_size: UISize) -> [i32; 4] {
+        let scale = Vec2::new(
+            viewport[2] as f32 / ui_size.width.max(1.0),
+            viewport[3] as f32 / ui_size.height.max(1.0),
+        );
+        let min = (Vec2::from(self.scissor.pos) * scale).floor();
+        let max = ((Vec2::from(self.scissor.pos) + Vec2::from(self.scissor.size)) * scale).ceil();
+        // GL counts rows from the bottom
+        [
+            viewport[0] + min.x as i32,
+            viewport[1] + viewport[3] - max.y as i32,
+            (max.x - min.x).max(0.0) as i32,
+            (max.y - min.y).max(0.0) as i32,
+        ]
+    }
+}
+
+impl ClipStack {
+    pub fn current(&self) -> Option<&Arc<ClipRegion>> {
+        self.0.last()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+}
+
+impl DrawContext {
+    /// Restricts drawing to `rect`, transformed by the current transform,
+    /// and to the clips pushed before until the matching `pop_clip`.
+    ///
+    /// Rotated clips need a stencil buffer, without one they clip to their
+    /// bounding box.
+    pub fn push_clip(&mut self, rect: UIRect) {
+        let transform = self.transform_stack.current();
+        let region = ClipRegion::new(self.clip_stack.current().map(|r| &**r), rect, &transform);
+        self.clip_stack.0.push(Arc::new(region));
+        self.apply_clip(self.clip_stack.current().cloned());
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clip_stack.0.pop().expect("empty clip stack");
+        self.apply_clip(self.clip_stack.current().cloned());
+    }
+
+    /// Draws what was drawn so far with the previous clip, then sets up the
+    /// scissor and stencil tests for `region`.
+    pub fn apply_clip(&mut self, region: Option<Arc<ClipRegion>>) {
+        if self.applied_clip == region {
+            return;
+        }
+        self.flush_shapes();
+        self.flush_sprites();
+        self.applied_clip = region.clone();
+
+        let region = match region {
+            Some(region) => region,
+            None => {
+                unsafe {
+                    gl::Disable(gl::SCISSOR_TEST);
+                    gl::Disable(gl::STENCIL_TEST);
+                }
+                return;
+            }
+        };
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            let [x, y, width, height] = region.scissor_box(viewport, self.ui_size);
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor(x, y, width, height);
+        }
+        if region.quads.is_empty() || !self.stencil_available() {
+            unsafe { gl::Disable(gl::STENCIL_TEST) };
+            return;
+        }
+
+        // every quad increments the pixels covered by all of the previous
+        // ones, so only the intersection reaches the quad count
+        unsafe {
+            gl::Enable(gl::STENCIL_TEST);
+            gl::StencilMask(0xFF);
+            gl::ClearStencil(0);
+            gl::Clear(gl::STENCIL_BUFFER_BIT);
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::INCR);
+        }
+        for (i, quad) in region.quads.iter().enumerate() {
+            unsafe { gl::StencilFunc(gl::EQUAL, i as _, 0xFF) };
+            self.draw_stencil_polygon(quad);
+            self.flush_shapes();
+        }
+        unsafe {
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+            gl::StencilFunc(gl::EQUAL, region.quads.len() as _, 0xFF);
+        }
+    }
+
+    /// Whether the bound draw framebuffer has a stencil buffer.
+    fn stencil_available(&self) -> bool {
+        let mut framebuffer = 0;
+        unsafe { gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut framebuffer) };
+        if framebuffer == 0 {
+            return self.gl_config.stencil_size() > 0;
+        }
+        let mut object_type = 0;
+        unsafe {
+            gl::GetFramebufferAttachmentParameteriv(
+                gl::DRAW_FRAMEBUFFER,
+                gl::STENCIL_ATTACHMENT,
+                gl::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE,
+                &mut object_type,
+            )
+        };
+        object_type != gl::NONE as i32
+    }
+}
+
+#[test]
+fn test() {
+    use crate::ui::utils::geom::UIPos;
+
+    let rect = UIRect::new(UIPos::new(10.0, 10.0), UISize::new(100.0, 50.0));
+    let outer = ClipRegion::new(None, rect, &Affine2::from_translation(Vec2::new(5.0, 0.0)));
+    assert_eq!(outer.scissor.pos, UIPos::new(15.0, 10.0));
+    assert!(outer.quads.is_empty());
+
+    let inner = ClipRegion::new(
+        Some(&outer),
+        rect,
+        &Affine2::from_translation(Vec2::splat(50.0)),
+    );
+    assert_eq!(inner.scissor.pos, UIPos::new(60.0, 60.0));
+    assert_eq!(inner.scissor.size, UISize::new(55.0, 0.0));
+
+    // rotated clips are stenciled, inside their bounding box
+    let rotated = ClipRegion::new(
+        Some(&outer),
+        UIRect::new(UIPos::new(-10.0, -10.0), UISize::new(20.0, 20.0)),
+        &Affine2::from_angle_translation(std::f32::consts::FRAC_PI_4, Vec2::new(50.0, 30.0)),
+    );
+    assert_eq!(rotated.quads.len(), 1);
+    let half_diagonal = 200.0f32.sqrt();
+    assert_eq!(
+        rotated.scissor.pos,
+        UIPos::new(50.0 - half_diagonal, 30.0 - half_diagonal)
+    );
+
+    // 2 pixels per unit, 100 units high
+    assert_eq!(
+        outer.scissor_box([0, 0, 400, 200], UISize::new(200.0, 100.0)),
+        [30, 80, 200, 100]
+    );
+    assert_eq!(
+        rect.intersection(&UIRect::new(UIPos::new(200.0, 0.0), UISize::new(1.0, 1.0)))
+            .size,
+        UISize::ZERO
+    );
+}
@@ -1,39 +1,85 @@
 use crate::{
     events::GameUserEvent,
     exec::server::{
-        draw::{RecvMsg, SendMsg, ServerChannel},
+        draw::{
+            command::{CommandLog, DrawCommand},
+            RecvMsg, SendMsg, ServerChannel,
+        },
         BaseGameServer,
     },
     graphics::{debug_callback::enable_gl_debug_callback, HandleContainer, SendHandleContainer},
     scene::main::RootScene,
+    test::capture::{LogCapture, LogSource},
     ui::utils::geom::UISize,
-    utils::args::args,
+    utils::{error::ResultExt, profile::profile_span, startup},
+};
+use std::{
+    borrow::Cow, collections::HashMap, ffi::CString, num::NonZeroU32, sync::Arc, time::Duration,
 };
-use std::{borrow::Cow, collections::HashMap, ffi::CString, num::NonZeroU32, time::Duration};
 
 use anyhow::Context;
+use glam::Vec4;
 use glutin::{
     config::Config,
     context::{ContextApi, ContextAttributesBuilder, NotCurrentContext, PossiblyCurrentContext},
     display::{Display, GetGlDisplay},
-    prelude::{GlDisplay, NotCurrentGlContextSurfaceAccessor, PossiblyCurrentGlContext},
-    surface::{GlSurface, Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface},
+    prelude::{GlDisplay, PossiblyCurrentGlContext},
+    surface::SwapInterval,
 };
 use winit::{dpi::PhysicalSize, event_loop::EventLoopProxy};
 
-use crate::display::SendRawHandle;
+use crate::display::{
+    backend::{self, DrawSurface},
+    surface_format::SurfaceFormat,
+    SendRawHandle,
+};
 
-use super::transform_stack::TransformStack;
+use super::{
+    clip_stack::{ClipRegion, ClipStack},
+    draw_queue::DrawQueue,
+    gpu_timer::GpuTimer,
+    picking::Picking,
+    post_effect::PostChain,
+    present::{PresentMode, PresentModeReport},
+    render_graph::RenderGraph,
+    screenshot::FrameCaptures,
+    shape_renderer::ShapeRenderer,
+    sprite_renderer::SpriteRenderer,
+    text::GlyphAtlas,
+    transform_stack::TransformStack,
+    uniform::FrameUniforms,
+    upload::UploadScheduler,
+    wrappers::query::TimerQuerySupport,
+};
 
 pub struct DrawContext {
     pub test_logs: HashMap<Cow<'static, str>, String>,
+    pub test_capture: LogCapture,
+    pub command_log: CommandLog,
+    pub clear_color: Vec4,
     pub transform_stack: TransformStack,
+    pub clip_stack: ClipStack,
+    /// clip the GL state is set up for, see `apply_clip`
+    pub applied_clip: Option<Arc<ClipRegion>>,
+    pub draw_queue: DrawQueue,
+    pub shapes: ShapeRenderer,
+    pub sprites: SpriteRenderer,
+    pub glyphs: GlyphAtlas,
+    pub render_graph: RenderGraph,
+    pub uploads: UploadScheduler,
+    pub gpu_timer: GpuTimer,
+    pub post_effects: PostChain,
+    pub frame_captures: FrameCaptures,
+    pub picking: Picking,
+    pub frame_uniforms: FrameUniforms,
     pub handles: HandleContainer,
     pub swap_interval: SwapInterval,
-    pub gl_surface: Surface<WindowSurface>,
+    pub gl_surface: DrawSurface,
     pub gl_context: PossiblyCurrentContext,
     pub gl_display: Display,
     pub gl_config: Config,
+    /// format of the window surface, offscreen passes match its precision
+    pub surface_format: SurfaceFormat,
     pub display_size: PhysicalSize<NonZeroU32>,
     pub ui_size: UISize,
     pub display_handles: SendRawHandle,
@@ -42,12 +88,31 @@ pub struct DrawContext {
 
 pub struct SendDrawContext {
     pub test_logs: HashMap<Cow<'static, str>, String>,
+    pub test_capture: LogCapture,
+    pub command_log: CommandLog,
+    pub clear_color: Vec4,
     pub transform_stack: TransformStack,
+    pub clip_stack: ClipStack,
+    /// clip the GL state is set up for, see `apply_clip`
+    pub applied_clip: Option<Arc<ClipRegion>>,
+    pub draw_queue: DrawQueue,
+    pub shapes: ShapeRenderer,
+    pub sprites: SpriteRenderer,
+    pub glyphs: GlyphAtlas,
+    pub render_graph: RenderGraph,
+    pub uploads: UploadScheduler,
+    pub gpu_timer: GpuTimer,
+    pub post_effects: PostChain,
+    pub frame_captures: FrameCaptures,
+    pub picking: Picking,
+    pub frame_uniforms: FrameUniforms,
     pub handles: SendHandleContainer,
     pub swap_interval: SwapInterval,
     pub gl_context: NotCurrentContext,
     pub gl_display: Display,
     pub gl_config: Config,
+    /// format of the window surface, offscreen passes match its precision
+    pub surface_format: SurfaceFormat,
     pub display_size: PhysicalSize<NonZeroU32>,
     pub ui_size: UISize,
     pub display_handles: SendRawHandle,
@@ -65,30 +130,31 @@ impl SendDrawContext {
         let context_attribs = ContextAttributesBuilder::new()
             .with_context_api(ContextApi::Gles(None))
             .with_debug(cfg!(debug_assertions))
-            .build(Some(display.get_raw_window_handle()));
+            .build(display.get_raw_window_handle());
         let gl_context = unsafe { gl_display.create_context(&gl_config, &context_attribs) }
             .context("unable to create OpenGL context")?;
         let display_size = display.get_size();
         let gl_surface = unsafe {
-            gl_display
-                .create_window_surface(
-                    &gl_config,
-                    &SurfaceAttributesBuilder::<WindowSurface>::new().build(
-                        display.get_raw_window_handle(),
-                        NonZeroU32::new(display_size.width).unwrap(),
-                        NonZeroU32::new(display_size.height).unwrap(),
-                    ),
-                )
-                .context("unable to create window surface for OpenGL rendering")?
+            DrawSurface::new(
+                &gl_display,
+                &gl_config,
+                display.get_raw_window_handle(),
+                PhysicalSize::new(
+                    NonZeroU32::new(display_size.width).unwrap(),
+                    NonZeroU32::new(display_size.height).unwrap(),
+                ),
+            )?
         };
-        let current_gl_context = gl_context
-            .make_current(&gl_surface)
-            .context("unable to make OpenGL context current")?;
+        let current_gl_context = gl_surface.make_current(gl_context)?;
         gl::load_with(|symbol| {
             let symbol = CString::new(symbol).unwrap();
             gl_display.get_proc_address(symbol.as_c_str()).cast()
         });
         enable_gl_debug_callback();
+        let timer_queries = TimerQuerySupport::load(|symbol| gl_display.get_proc_address(symbol));
+        if timer_queries == TimerQuerySupport::Unsupported {
+            tracing::info!("GL_TIME_ELAPSED queries unsupported, render passes won't be timed");
+        }
         unsafe {
             gl::Enable(gl::BLEND);
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA)
@@ -107,6 +173,25 @@ impl SendDrawContext {
             .get_size()
             .to_logical(display.get_scale_factor())
             .into();
+        let mut channel = ServerChannel { sender, receiver };
+        // the handles are created once the server processes its messages
+        let shapes = ShapeRenderer::new(&mut channel).context("unable to create shape renderer")?;
+        let sprites =
+            SpriteRenderer::new(&mut channel).context("unable to create sprite renderer")?;
+        let glyphs = GlyphAtlas::new(&mut channel).context("unable to create glyph atlas")?;
+        let gpu_timer =
+            GpuTimer::new(&mut channel, timer_queries).context("unable to create GPU timer")?;
+        let post_effects =
+            PostChain::new(&mut channel).context("unable to create post effect chain")?;
+        let render_graph =
+            RenderGraph::new(&mut channel).context("unable to create render graph")?;
+        let uploads =
+            UploadScheduler::new(&mut channel).context("unable to create upload scheduler")?;
+        let frame_captures =
+            FrameCaptures::new(&mut channel).context("unable to create frame captures")?;
+        let picking = Picking::new(&mut channel).context("unable to create picking")?;
+        let frame_uniforms =
+            FrameUniforms::new(&mut channel).context("unable to create frame uniforms")?;
         Ok((
             Self {
                 base,
@@ -116,12 +201,29 @@ impl SendDrawContext {
                 gl_display,
                 gl_context,
                 gl_config,
+                surface_format: display.get_surface_format(),
                 swap_interval: SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
                 handles: SendHandleContainer::new(),
                 test_logs: HashMap::new(),
+                test_capture: LogCapture::new(LogSource::Draw),
+                command_log: CommandLog::default(),
+                clear_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
                 transform_stack: TransformStack::default(),
+                clip_stack: ClipStack::default(),
+                applied_clip: None,
+                draw_queue: DrawQueue::default(),
+                shapes,
+                sprites,
+                glyphs,
+                render_graph,
+                uploads,
+                gpu_timer,
+                post_effects,
+                frame_captures,
+                picking,
+                frame_uniforms,
             },
-            ServerChannel { sender, receiver },
+            channel,
         ))
     }
 }
@@ -147,6 +249,57 @@ impl DrawContext {
         Ok(())
     }
 
+    pub fn present_mode(&self) -> PresentMode {
+        PresentMode::from_swap_interval(self.swap_interval)
+    }
+
+    /// Modes accepted by the surface, found by setting each of them and
+    /// restoring the current one afterwards.
+    pub fn supported_present_modes(&mut self) -> Vec<PresentMode> {
+        let supported = PresentMode::ALL
+            .into_iter()
+            .filter(|mode| {
+                self.gl_surface
+                    .set_swap_interval(&self.gl_context, mode.swap_interval())
+                    .is_ok()
+            })
+            .collect();
+        self.set_swap_interval(self.swap_interval)
+            .context("unable to restore swap interval")
+            .log_warn();
+        supported
+    }
+
+    /// Sets `requested`, or the first of its fallbacks accepted by the
+    /// surface.
+    pub fn set_present_mode(&mut self, requested: PresentMode) -> PresentModeReport {
+        let supported = self.supported_present_modes();
+        let effective = requested.fallbacks().iter().copied().find(|&mode| {
+            self.set_swap_interval(mode.swap_interval())
+                .with_context(|| format!("unable to set present mode {mode:?}"))
+                .log_warn()
+                .is_some()
+        });
+        match effective {
+            Some(mode) if mode != requested => {
+                tracing::warn!("present mode {requested:?} unavailable, fell back to {mode:?}")
+            }
+            Some(mode) => tracing::info!("present mode set to {mode:?}"),
+            None => tracing::error!("no present mode could be set, requested {requested:?}"),
+        }
+        PresentModeReport {
+            requested,
+            effective,
+            supported,
+        }
+    }
+
+    /// Processes pending messages without drawing, blocking for a while if
+    /// `block` so that a paused draw server doesn't spin.
+    pub fn drain(&mut self, block: bool, root_scene: &mut Option<RootScene>) -> anyhow::Result<()> {
+        self.process_messages(block, root_scene)
+    }
+
     fn process_messages(
         &mut self,
         block: bool,
@@ -161,6 +314,7 @@ impl DrawContext {
         for message in messages {
             match message {
                 RecvMsg::SetFrequencyProfiling(fp) => self.base.frequency_profiling = fp,
+                RecvMsg::Command(command) => self.execute_command(command),
                 RecvMsg::Execute(callback) => callback(self, root_scene),
             }
         }
@@ -168,9 +322,37 @@ impl DrawContext {
         Ok(())
     }
 
+    pub fn execute_command(&mut self, command: DrawCommand) {
+        tracing::trace!("draw command {command:?}");
+        self.command_log.record(&command);
+        if let Some(event) = command.execute(self) {
+            self.base
+                .proxy
+                .send_event(event)
+                .map_err(|e| anyhow::format_err!("{e}"))
+                .context("unable to send draw command event to main thread")
+                .log_warn();
+        }
+    }
+
+    /// Executes previously recorded commands again, they are logged (and
+    /// recorded) like new ones.
+    pub fn replay(&mut self, commands: Vec<DrawCommand>) {
+        for command in commands {
+            self.execute_command(command);
+        }
+    }
+
     pub fn resize(&mut self, new_size: PhysicalSize<NonZeroU32>, ui_size: UISize) {
         self.gl_surface
-            .resize(&self.gl_context, new_size.width, new_size.height);
+            .resize(
+                &self.gl_display,
+                &self.gl_config,
+                &self.gl_context,
+                new_size,
+            )
+            .context("unable to resize surface")
+            .log_warn();
         unsafe {
             gl::Viewport(
                 0,
@@ -183,6 +365,59 @@ impl DrawContext {
         self.ui_size = ui_size;
     }
 
+    /// Pixels per UI unit.
+    pub fn ui_scale(&self) -> f32 {
+        if self.ui_size.width > 0.0 {
+            self.display_size.width.get() as f32 / self.ui_size.width
+        } else {
+            1.0
+        }
+    }
+
+    /// Shuts the draw server down in an orderly fashion: drops the draw
+    /// server's reference to the root scene, flushes pending commands (including
+    /// handle drop requests), reports handles that are still alive and deletes
+    /// every GL object while the context is still current.
+    pub fn shutdown(&mut self, root_scene: &mut Option<RootScene>) -> anyhow::Result<()> {
+        // dropping the root scene (or executing pending commands) can enqueue
+        // more commands, so keep flushing until the channel is drained
+        const MAX_FLUSH_ROUNDS: usize = 16;
+        *root_scene = None;
+        for _ in 0..MAX_FLUSH_ROUNDS {
+            if self.base.receiver.is_empty() {
+                break;
+            }
+            self.process_messages(false, root_scene)?;
+            *root_scene = None;
+        }
+
+        let leaked = self.handles.handle_infos();
+        if leaked.is_empty() {
+            tracing::info!("draw server shut down, no GL handle leaked");
+        } else {
+            tracing::warn!(
+                "draw server shut down with {} GL handle(s) still alive",
+                leaked.len()
+            );
+            for info in leaked.iter() {
+                tracing::warn!(
+                    "leaked {} `{}` (GL id {})",
+                    info.type_name,
+                    info.name,
+                    info.gl_handle
+                );
+            }
+        }
+
+        // fences are deleted while the context is still current
+        self.uploads.clear();
+        self.frame_captures.clear();
+        self.picking.clear();
+        self.handles.clear();
+        unsafe { gl::Finish() };
+        Ok(())
+    }
+
     pub fn to_send(self) -> anyhow::Result<SendDrawContext> {
         let gl_context = self
             .gl_context
@@ -194,12 +429,29 @@ impl DrawContext {
             gl_context,
             gl_display: self.gl_display,
             display_handles: self.display_handles,
+            surface_format: self.surface_format,
             display_size: self.display_size,
             ui_size: self.ui_size,
             swap_interval: self.swap_interval,
             handles: self.handles.to_send(),
             test_logs: self.test_logs,
+            test_capture: self.test_capture,
+            command_log: self.command_log,
+            clear_color: self.clear_color,
             transform_stack: self.transform_stack,
+            clip_stack: self.clip_stack,
+            applied_clip: self.applied_clip,
+            draw_queue: self.draw_queue,
+            shapes: self.shapes,
+            sprites: self.sprites,
+            glyphs: self.glyphs,
+            render_graph: self.render_graph,
+            uploads: self.uploads,
+            gpu_timer: self.gpu_timer,
+            post_effects: self.post_effects,
+            frame_captures: self.frame_captures,
+            picking: self.picking,
+            frame_uniforms: self.frame_uniforms,
         })
     }
 
@@ -209,15 +461,35 @@ impl DrawContext {
         single: bool,
         runner_frequency: f64,
     ) -> anyhow::Result<()> {
-        let headless = args().headless;
+        let headless = !backend::renders_frames();
         self.base.run("Draw", runner_frequency);
-        self.process_messages(single && headless, root_scene)?;
+        {
+            let _span = profile_span!("draw messages").entered();
+            // pending uploads progress every frame, even headless
+            let block = single && headless && self.uploads.is_idle();
+            self.process_messages(block, root_scene)?;
+        }
+        {
+            let _span = profile_span!("uploads").entered();
+            let budget = self.uploads.budget;
+            self.debug_group("uploads", |ctx| ctx.run_uploads(budget));
+        }
+        // headless frames are still rendered when someone captures them
+        if !headless || self.frame_captures.is_requested() {
+            self.render(root_scene.as_ref());
+            self.debug_group("frame capture", |ctx| ctx.start_captures());
+        }
+        // picks are answered even headless
+        self.debug_group("picking", |ctx| ctx.start_picking(root_scene.as_ref()));
+        self.poll_captures();
+        self.poll_picking();
         if !headless {
-            if let Some(root_scene) = root_scene {
-                root_scene.draw(self);
-            }
+            let _span = profile_span!("swap buffers").entered();
             self.gl_surface.swap_buffers(&self.gl_context)?;
         }
+        if root_scene.is_some() {
+            startup::finish_first_frame();
+        }
         Ok(())
     }
 }
@@ -225,21 +497,14 @@ impl DrawContext {
 impl SendDrawContext {
     pub fn to_nonsend(self) -> anyhow::Result<DrawContext> {
         let gl_surface = unsafe {
-            self.gl_display
-                .create_window_surface(
-                    &self.gl_config,
-                    &SurfaceAttributesBuilder::<WindowSurface>::new().build(
-                        self.display_handles.0,
-                        self.display_size.width,
-                        self.display_size.height,
-                    ),
-                )
-                .context("unable to create window surface for OpenGL rendering")?
+            DrawSurface::new(
+                &self.gl_display,
+                &self.gl_config,
+                self.display_handles.0,
+                self.display_size,
+            )?
         };
-        let gl_context = self
-            .gl_context
-            .make_current(&gl_surface)
-            .context("unable to make OpenGL context current")?;
+        let gl_context = gl_surface.make_current(self.gl_context)?;
         gl_surface.set_swap_interval(&gl_context, self.swap_interval)?;
         Ok(DrawContext {
             base: self.base,
@@ -248,12 +513,29 @@ impl SendDrawContext {
             gl_display: self.gl_display,
             gl_surface,
             display_handles: self.display_handles,
+            surface_format: self.surface_format,
             display_size: self.display_size,
             ui_size: self.ui_size,
             swap_interval: self.swap_interval,
             handles: self.handles.to_nonsend(),
             test_logs: self.test_logs,
+            test_capture: self.test_capture,
+            command_log: self.command_log,
+            clear_color: self.clear_color,
             transform_stack: self.transform_stack,
+            clip_stack: self.clip_stack,
+            applied_clip: self.applied_clip,
+            draw_queue: self.draw_queue,
+            shapes: self.shapes,
+            sprites: self.sprites,
+            glyphs: self.glyphs,
+            render_graph: self.render_graph,
+            uploads: self.uploads,
+            gpu_timer: self.gpu_timer,
+            post_effects: self.post_effects,
+            frame_captures: self.frame_captures,
+            picking: self.picking,
+            frame_uniforms: self.frame_uniforms,
         })
     }
 }
@@ -1,9 +1,22 @@
-use std::{ffi::CStr, ptr::null};
+use std::{
+    ffi::{CStr, CString},
+    ptr::null,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use gl::types::{GLenum, GLint, GLuint, GLvoid};
+use gl::types::{GLenum, GLint, GLsizei, GLuint, GLvoid};
 
 use crate::utils::args::args;
 
+use super::context::DrawContext;
+
+static GL_ERROR_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of errors reported through the debug callback so far.
+pub fn gl_error_count() -> usize {
+    GL_ERROR_COUNT.load(Ordering::Relaxed)
+}
+
 extern "system" fn debug_callback(
     source: GLenum,
     typ: GLenum,
@@ -31,6 +44,7 @@ extern "system" fn debug_callback(
     let typ = match typ {
         gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "DEPRECATED_BEHAVIOR",
         gl::DEBUG_TYPE_ERROR => {
+            GL_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
             level = tracing::Level::ERROR;
             "ERROR"
         }
@@ -78,3 +92,26 @@ pub fn enable_gl_debug_callback() -> bool {
         }
     }
 }
+
+impl DrawContext {
+    /// Runs `f` inside a named debug group, so GPU debuggers like RenderDoc
+    /// show the calls it issues nested under `name`.
+    pub fn debug_group<R>(&mut self, name: &str, f: impl FnOnce(&mut Self) -> R) -> R {
+        let pushed = gl::PushDebugGroup::is_loaded()
+            && CString::new(name)
+                .map(|c_name| unsafe {
+                    gl::PushDebugGroup(
+                        gl::DEBUG_SOURCE_APPLICATION,
+                        0,
+                        name.len() as GLsizei,
+                        c_name.as_ptr(),
+                    );
+                })
+                .is_ok();
+        let result = f(self);
+        if pushed {
+            unsafe { gl::PopDebugGroup() };
+        }
+        result
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use glam::Affine2;
+use trait_set::trait_set;
+
+use super::{clip_stack::ClipRegion, context::DrawContext};
+
+trait_set! {
+    pub trait QueuedDrawFn = FnOnce(&mut DrawContext, &Affine2) + Send;
+}
+
+/// Order of a queued draw: by z-index first, then by batch key so that
+/// draws sharing the same state (e.g. texture or program) end up next to
+/// each other.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortKey {
+    pub z_index: i32,
+    pub batch: u64,
+}
+
+pub struct QueuedDraw {
+    pub key: SortKey,
+    /// transform at the time the draw was queued
+    pub transform: Affine2,
+    /// clip at the time the draw was queued
+    pub clip: Option<Arc<ClipRegion>>,
+    draw: Box<dyn QueuedDrawFn>,
+}
+
+/// Draws submitted during the UI pass, executed in sort key order when the
+/// queue is flushed. Draws with equal keys keep their submission order, so
+/// overlapping widgets are still drawn back to front.
+#[derive(Default)]
+pub struct DrawQueue {
+    items: Vec<QueuedDraw>,
+}
+
+impl SortKey {
+    pub fn new(z_index: i32, batch: u64) -> Self {
+        Self { z_index, batch }
+    }
+}
+
+impl DrawQueue {
+    pub fn push<F>(
+        &mut self,
+        key: SortKey,
+        transform: Affine2,
+        clip: Option<Arc<ClipRegion>>,
+        draw: F,
+    ) where
+        F: QueuedDrawFn + 'static,
+    {
+        self.items.push(QueuedDraw {
+            key,
+            transform,
+            clip,
+            draw: Box::new(draw),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Empties the queue, the draws are returned in execution order.
+    pub fn take_sorted(&mut self) -> Vec<QueuedDraw> {
+        let mut items = std::mem::take(&mut self.items);
+        // stable, equal keys keep their submission order
+        items.sort_by_key(|item| item.key);
+        items
+    }
+}
+
+impl DrawContext {
+    /// Queues `draw` with the current transform and clip, it is executed by
+    /// the next `flush_draw_queue`.
+    pub fn queue_draw<F>(&mut self, key: SortKey, draw: F)
+    where
+        F: QueuedDrawFn + 'static,
+    {
+        let transform = self.transform_stack.current();
+        let clip = self.clip_stack.current().cloned();
+        self.draw_queue.push(key, transform, clip, draw);
+    }
+
+    /// Executes the queued draws sorted by key, each one under the clip it
+    /// was queued with, returns how many there were.
+    pub fn flush_draw_queue(&mut self) -> usize {
+        let items = self.draw_queue.take_sorted();
+        let count = items.len();
+        for item in items {
+            self.apply_clip(item.clip);
+            (item.draw)(self, &item.transform);
+        }
+        self.apply_clip(self.clip_stack.current().cloned());
+        count
+    }
+}
+
+#[test]
+fn test() {
+    let mut queue = DrawQueue::default();
+    let keys = [(1, 2), (0, 5), (1, 1), (0, 5), (-1, 9)];
+    for (i, (z_index, batch)) in keys.into_iter().enumerate() {
+        queue.push(
+            SortKey::new(z_index, batch),
+            Affine2::from_translation(glam::Vec2::new(i as f32, 0.0)),
+            None,
+            |_, _| {},
+        );
+    }
+    assert_eq!(queue.len(), keys.len());
+
+    let order = queue
+        .take_sorted()
+        .iter()
+        .map(|item| item.transform.translation.x as usize)
+        .collect::<Vec<_>>();
+    assert_eq!(order, [4, 1, 3, 2, 0]);
+    assert!(queue.is_empty());
+}
@@ -0,0 +1,206 @@
+use std::{fmt::Write, path::Path, time::Duration};
+
+use anyhow::Context;
+
+/// Render target drawn into or read by the passes of a `FrameGraph`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameGraphTarget {
+    pub name: String,
+    /// passes drawing into the target in the frame, more than one if it is
+    /// reused
+    pub writers: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameGraphPass {
+    pub name: String,
+    /// indices in `FrameGraph::targets`
+    pub inputs: Vec<usize>,
+    pub output: usize,
+    /// sample count the pass was last drawn with, if multisampled
+    pub samples: Option<u32>,
+    /// latest GPU time measured for the pass, a few frames old
+    pub gpu_time: Option<Duration>,
+}
+
+/// Snapshot of the render graph: the passes in execution order and the
+/// targets they depend on, see `DrawContext::frame_graph`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameGraph {
+    pub passes: Vec<FrameGraphPass>,
+    pub targets: Vec<FrameGraphTarget>,
+}
+
+impl FrameGraph {
+    /// Index of the target named `name`, added if it isn't there yet.
+    pub fn target(&mut self, name: &str) -> usize {
+        match self.targets.iter().position(|target| target.name == name) {
+            Some(index) => index,
+            None => {
+                self.targets.push(FrameGraphTarget {
+                    name: name.to_owned(),
+                    writers: 0,
+                });
+                self.targets.len() - 1
+            }
+        }
+    }
+
+    /// Graphviz graph, passes are ellipses labeled with their order and
+    /// timing, targets are boxes.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph frame {\n    rankdir=LR;\n");
+        for (i, target) in self.targets.iter().enumerate() {
+            let reused = if target.writers > 1 {
+                format!("\\nwritten {} times", target.writers)
+            } else {
+                String::new()
+            };
+            writeln!(
+                dot,
+                "    t{i} [shape=box, label=\"{}{reused}\"];",
+                escape_dot(&target.name)
+            )
+            .unwrap();
+        }
+        for (i, pass) in self.passes.iter().enumerate() {
+            let mut label = format!("#{i} {}", escape_dot(&pass.name));
+            if let Some(samples) = pass.samples {
+                write!(label, "\\n{samples}x MSAA").unwrap();
+            }
+            if let Some(time) = pass.gpu_time {
+                write!(label, "\\n{:.3} ms", time.as_secs_f64() * 1e3).unwrap();
+            }
+            writeln!(dot, "    p{i} [label=\"{label}\"];").unwrap();
+            for input in pass.inputs.iter() {
+                writeln!(dot, "    t{input} -> p{i};").unwrap();
+            }
+            writeln!(dot, "    p{i} -> t{};", pass.output).unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Passes with the names of their targets, times in nanoseconds.
+    pub fn to_json(&self) -> String {
+        let target = |index: usize| json_string(&self.targets[index].name);
+        let passes = self
+            .passes
+            .iter()
+            .map(|pass| {
+                let inputs = pass
+                    .inputs
+                    .iter()
+                    .map(|&input| target(input))
+                    .collect::<Vec<_>>();
+                format!(
+                    "{{\"name\":{},\"inputs\":[{}],\"output\":{},\"samples\":{},\"gpu_time_ns\":{}}}",
+                    json_string(&pass.name),
+                    inputs.join(","),
+                    target(pass.output),
+                    json_option(pass.samples),
+                    json_option(pass.gpu_time.map(|time| time.as_nanos())),
+                )
+            })
+            .collect::<Vec<_>>();
+        let targets = self
+            .targets
+            .iter()
+            .map(|target| {
+                format!(
+                    "{{\"name\":{},\"writers\":{}}}",
+                    json_string(&target.name),
+                    target.writers
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"passes\":[{}],\"targets\":[{}]}}\n",
+            passes.join(","),
+            targets.join(",")
+        )
+    }
+
+    /// Writes `<path>.dot` and `<path>.json`, creating their directory if
+    /// needed.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("unable to create {}", dir.display()))?;
+        }
+        for (extension, contents) in [("dot", self.to_dot()), ("json", self.to_json())] {
+            let path = path.with_extension(extension);
+            std::fs::write(&path, contents)
+                .with_context(|| format!("unable to write {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn json_option(value: Option<impl ToString>) -> String {
+    value.map_or_else(|| "null".to_owned(), |value| value.to_string())
+}
+
+#[test]
+fn test() {
+    let mut graph = FrameGraph::default();
+    let screen = graph.target("screen");
+    let scene = graph.target("scene \"offscreen\"");
+    assert_eq!(graph.target("screen"), screen);
+    graph.targets[scene].writers = 2;
+    graph.targets[screen].writers = 1;
+    graph.passes = vec![
+        FrameGraphPass {
+            name: "scene".to_owned(),
+            inputs: vec![],
+            output: scene,
+            samples: Some(4),
+            gpu_time: Some(Duration::from_micros(1500)),
+        },
+        FrameGraphPass {
+            name: "post".to_owned(),
+            inputs: vec![scene],
+            output: screen,
+            samples: None,
+            gpu_time: None,
+        },
+    ];
+
+    let dot = graph.to_dot();
+    assert!(dot.contains("t1 [shape=box, label=\"scene \\\"offscreen\\\"\\nwritten 2 times\"];"));
+    assert!(dot.contains("p0 [label=\"#0 scene\\n4x MSAA\\n1.500 ms\"];"));
+    assert!(dot.contains("t1 -> p1;"));
+    assert!(dot.contains("p1 -> t0;"));
+    assert_eq!(
+        graph.to_json(),
+        concat!(
+            "{\"passes\":[",
+            "{\"name\":\"scene\",\"inputs\":[],\"output\":\"scene \\\"offscreen\\\"\",",
+            "\"samples\":4,\"gpu_time_ns\":1500000},",
+            "{\"name\":\"post\",\"inputs\":[\"scene \\\"offscreen\\\"\"],\"output\":\"screen\",",
+            "\"samples\":null,\"gpu_time_ns\":null}],",
+            "\"targets\":[{\"name\":\"screen\",\"writers\":1},",
+            "{\"name\":\"scene \\\"offscreen\\\"\",\"writers\":2}]}\n"
+        )
+    );
+}
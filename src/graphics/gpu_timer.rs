@@ -0,0 +1,157 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    time::Duration,
+};
+
+use crate::{
+    exec::server::draw,
+    utils::profile::{profile_counter, PROFILE_TARGET},
+};
+
+use super::{
+    context::DrawContext,
+    wrappers::query::{QueryHandle, QueryTarget, TimerQuerySupport},
+};
+
+/// Test log the pass timings are appended to, if a test created it.
+pub const GPU_TIMINGS_LOG: &str = "gpu timings";
+
+/// Frames whose timings may be pending at once.
+const FRAMES_IN_FLIGHT: usize = 4;
+/// Passes timed per frame, the passes after them aren't timed.
+const PASSES_PER_FRAME: usize = 16;
+
+struct FrameQueries {
+    frame: u64,
+    /// pass names and indices of their queries
+    passes: Vec<(Cow<'static, str>, usize)>,
+}
+
+/// Times the render passes on the GPU with `GL_TIME_ELAPSED` queries. The
+/// results are read a few frames later, once available, so that reading
+/// them doesn't stall the pipeline.
+pub struct GpuTimer {
+    support: TimerQuerySupport,
+    queries: Vec<QueryHandle>,
+    free: Vec<usize>,
+    pending: VecDeque<FrameQueries>,
+    recording: Option<FrameQueries>,
+    frame: u64,
+    /// latest result per pass name, in nanoseconds
+    latest: HashMap<Cow<'static, str>, u64>,
+}
+
+impl GpuTimer {
+    pub fn new(draw: &mut draw::ServerChannel, support: TimerQuerySupport) -> anyhow::Result<Self> {
+        let count = match support {
+            TimerQuerySupport::Unsupported => 0,
+            _ => FRAMES_IN_FLIGHT * PASSES_PER_FRAME,
+        };
+        let queries = (0..count)
+            .map(|i| {
+                QueryHandle::new_args(
+                    draw,
+                    format!("gpu timer query {i}"),
+                    QueryTarget::TimeElapsed,
+                )
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self {
+            support,
+            free: (0..count).rev().collect(),
+            queries,
+            pending: VecDeque::new(),
+            recording: None,
+            frame: 0,
+            latest: HashMap::new(),
+        })
+    }
+
+    pub fn supported(&self) -> bool {
+        self.support != TimerQuerySupport::Unsupported
+    }
+
+    /// Latest GPU time measured for the pass `name`, a few frames old.
+    pub fn pass_time(&self, name: &str) -> Option<Duration> {
+        self.latest.get(name).copied().map(Duration::from_nanos)
+    }
+}
+
+impl DrawContext {
+    /// Reads the timings of the finished frames and starts recording the
+    /// passes of a new one.
+    pub fn begin_gpu_frame(&mut self) {
+        if !self.gpu_timer.supported() {
+            return;
+        }
+        self.collect_gpu_timings();
+        let timer = &mut self.gpu_timer;
+        timer.frame += 1;
+        timer.recording = Some(FrameQueries {
+            frame: timer.frame,
+            passes: Vec::new(),
+        });
+    }
+
+    /// Starts timing the pass `name`, returns the query to end.
+    pub fn begin_pass_timer(&mut self, name: &Cow<'static, str>) -> Option<usize> {
+        let timer = &mut self.gpu_timer;
+        let recording = timer.recording.as_mut()?;
+        let index = timer.free.pop()?;
+        recording.passes.push((name.clone(), index));
+        let query = timer.queries[index].clone();
+        query.get(self).begin();
+        Some(index)
+    }
+
+    pub fn end_pass_timer(&mut self, index: Option<usize>) {
+        if let Some(index) = index {
+            self.gpu_timer.queries[index].get(self).end();
+        }
+    }
+
+    pub fn end_gpu_frame(&mut self) {
+        let timer = &mut self.gpu_timer;
+        if let Some(frame) = timer.recording.take() {
+            timer.pending.push_back(frame);
+        }
+    }
+
+    fn collect_gpu_timings(&mut self) {
+        let mut finished = Vec::new();
+        while let Some(frame) = self.gpu_timer.pending.front() {
+            // results become available in submission order
+            let available = frame.passes.last().map_or(true, |&(_, index)| {
+                self.gpu_timer.queries[index].get(self).available()
+            });
+            if !available {
+                break;
+            }
+            finished.extend(self.gpu_timer.pending.pop_front());
+        }
+        if finished.is_empty() {
+            return;
+        }
+
+        let disjoint = self.gpu_timer.support.disjoint();
+        for frame in finished {
+            for (name, index) in frame.passes {
+                let nanos = self.gpu_timer.queries[index].get(self).result();
+                self.gpu_timer.free.push(index);
+                if disjoint {
+                    continue;
+                }
+                let ms = nanos as f64 / 1e6;
+                if tracing::enabled!(target: PROFILE_TARGET, tracing::Level::TRACE) {
+                    profile_counter!(format!("gpu {name} ms").as_str(), ms);
+                }
+                if let Some(log) = self.test_logs.get_mut(GPU_TIMINGS_LOG) {
+                    writeln!(log, "{} {name} {nanos}", frame.frame).unwrap();
+                }
+                self.gpu_timer.latest.insert(name, nanos);
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    borrow::Cow,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::{Context as TaskContext, Poll},
+};
+
+use anyhow::Context;
+use winit::{dpi::PhysicalSize, event_loop::EventLoopProxy};
+
+use crate::{
+    enclose,
+    events::GameUserEvent,
+    exec::{
+        query::{self, ServerQuery},
+        server::{
+            draw::{self, ServerSendChannelExt},
+            GameServerSendChannel,
+        },
+        task::{CancellationToken, TaskExecutor},
+    },
+    utils::error::ResultExt,
+};
+
+use super::wrappers::texture::{
+    ImageData, TextureFilter, TextureHandle, TextureOptions, TextureWrap,
+};
+
+#[rustfmt::skip]
+const PLACEHOLDER_PIXELS: [u8; 16] = [
+    96, 96, 96, 255,    160, 160, 160, 255,
+    160, 160, 160, 255, 96, 96, 96, 255,
+];
+
+/// Texture being decoded and uploaded in the background.
+///
+/// `handle` refers to a small placeholder texture until the image is
+/// uploaded into it, so it can be drawn right away. The future resolves to
+/// the same handle once the image replaced the placeholder.
+pub struct PendingTexture {
+    pub handle: TextureHandle,
+    query: ServerQuery<anyhow::Result<TextureHandle>>,
+}
+
+/// Decodes a PNG or JPEG file (or any other format `image` was built with)
+/// into RGBA8 pixels.
+pub fn decode_image_file(path: &Path) -> anyhow::Result<ImageData> {
+    let image = image::io::Reader::open(path)
+        .with_context(|| format!("unable to open image {}", path.display()))?
+        .with_guessed_format()
+        .with_context(|| format!("unable to guess format of image {}", path.display()))?
+        .decode()
+        .with_context(|| format!("unable to decode image {}", path.display()))?;
+    Ok(ImageData::from(image.into_rgba8()))
+}
+
+pub fn placeholder_image() -> ImageData {
+    ImageData::new(PhysicalSize::new(2, 2), PLACEHOLDER_PIXELS.to_vec())
+        .expect("placeholder pixels must match its size")
+        .options(TextureOptions {
+            min_filter: TextureFilter::Nearest,
+            mag_filter: TextureFilter::Nearest,
+            wrap: TextureWrap::Repeat,
+            mipmaps: false,
+            ..Default::default()
+        })
+}
+
+impl PendingTexture {
+    /// Creates the placeholder texture and decodes `path` on `executor`,
+    /// the image isn't uploaded if `cancel` is cancelled meanwhile.
+    pub fn new(
+        draw: &mut draw::ServerChannel,
+        proxy: EventLoopProxy<GameUserEvent>,
+        executor: &TaskExecutor,
+        cancel: CancellationToken,
+        name: impl Into<Cow<'static, str>> + Send + 'static,
+        path: PathBuf,
+        options: TextureOptions,
+    ) -> anyhow::Result<Self> {
+        let handle = TextureHandle::new(draw, name, placeholder_image())
+            .context("unable to create placeholder texture")?;
+        let staging = unsafe { TextureHandle::new_uninit(draw) };
+        let (ret, query) = query::query();
+        let draw = draw.clone_sender();
+        executor.spawn_with(
+            cancel,
+            enclose!((handle) move |cancel| {
+                let image = decode_image_file(&path)
+                    .and_then(|image| cancel.check().map(|_| image.options(options)));
+                let image = match image {
+                    Ok(image) => image,
+                    Err(e) => {
+                        ret.send(Err(e), &proxy).log_warn();
+                        return;
+                    }
+                };
+                // sliced so that a large image doesn't stall a frame
+                draw.execute(move |context, _| {
+                    let target = handle.clone();
+                    context.uploads.push_texture(target, staging, image, move |context, result| {
+                        ret.send(result.map(|_| handle), &context.base.proxy)
+                            .context("unable to return loaded texture")
+                            .log_warn();
+                    });
+                })
+                .context("unable to send decoded image to draw server")
+                .log_warn();
+            }),
+        );
+        Ok(Self { handle, query })
+    }
+}
+
+impl Future for PendingTexture {
+    type Output = anyhow::Result<TextureHandle>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.query)
+            .poll(cx)
+            .map(|result| result.and_then(|result| result))
+    }
+}
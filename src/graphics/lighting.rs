@@ -0,0 +1,324 @@
+use anyhow::Context;
+use glam::{Vec2, Vec3};
+use winit::dpi::PhysicalSize;
+
+use crate::exec::server::draw;
+
+use super::{
+    context::DrawContext,
+    wrappers::{
+        framebuffer::{DefaultTextureFramebuffer, Framebuffer},
+        shader::ProgramHandle,
+        vertex_array::VertexArrayHandle,
+    },
+};
+
+// must match `MAX_OCCLUDERS` in the light fragment shader
+pub const MAX_OCCLUDERS: usize = 32;
+
+mod shader {
+    pub(super) use crate::graphics::post_effect::shader::VERTEX;
+
+    pub const LIGHT_FRAGMENT: &str = r#"
+    #version 300 es
+    precision mediump float;
+    #define MAX_OCCLUDERS 32
+
+    out vec4 color;
+
+    uniform vec2 light_pos;
+    uniform float light_radius;
+    uniform vec3 light_color;
+    uniform vec2 light_dir;
+    uniform float light_cos_cutoff;
+    uniform int num_occluders;
+    uniform vec4 occluders[MAX_OCCLUDERS];
+
+    // slab test of the segment a -> b against the rectangle (min.xy, max.zw)
+    bool segment_hits_rect(vec2 a, vec2 b, vec4 rect) {
+        vec2 d = b - a;
+        vec2 safe_d = vec2(
+            abs(d.x) < 1e-6 ? 1e-6 : d.x,
+            abs(d.y) < 1e-6 ? 1e-6 : d.y
+        );
+        vec2 t0 = (rect.xy - a) / safe_d;
+        vec2 t1 = (rect.zw - a) / safe_d;
+        vec2 tmin = min(t0, t1);
+        vec2 tmax = max(t0, t1);
+        float enter = max(tmin.x, tmin.y);
+        float exit = min(tmax.x, tmax.y);
+        return enter <= exit && exit >= 0.0 && enter <= 1.0;
+    }
+
+    void main() {
+        vec2 pos = gl_FragCoord.xy;
+        vec2 delta = pos - light_pos;
+        float dist = length(delta);
+        if (dist >= light_radius) {
+            discard;
+        }
+
+        float attenuation = 1.0 - dist / light_radius;
+        attenuation *= attenuation;
+        if (light_cos_cutoff > -1.0 && dist > 1e-3) {
+            float cos_angle = dot(delta / dist, light_dir);
+            attenuation *= smoothstep(light_cos_cutoff, light_cos_cutoff + 0.05, cos_angle);
+        }
+
+        for (int i = 0; i < MAX_OCCLUDERS; i++) {
+            if (i >= num_occluders) {
+                break;
+            }
+            if (segment_hits_rect(light_pos, pos, occluders[i])) {
+                discard;
+            }
+        }
+
+        color = vec4(light_color * attenuation, 1.0);
+    }
+    "#;
+
+    pub const COMPOSITE_FRAGMENT: &str = r#"
+    #version 300 es
+    precision mediump float;
+    in vec2 tex_coords;
+    out vec4 color;
+    uniform sampler2D light_map;
+    void main() {
+        color = vec4(texture(light_map, tex_coords).rgb, 1.0);
+    }
+    "#;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LightShape {
+    Point,
+    Cone {
+        /// normalized direction the cone is facing
+        direction: Vec2,
+        /// half of the cone's opening angle, in radians
+        half_angle: f32,
+    },
+}
+
+/// A light source, positioned in framebuffer pixel coordinates (origin at
+/// the bottom-left corner, same as `gl_FragCoord`)
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    pub position: Vec2,
+    pub radius: f32,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub shape: LightShape,
+    pub cast_shadows: bool,
+}
+
+/// An axis-aligned rectangle blocking light, in the same coordinate space as
+/// `Light::position`
+#[derive(Clone, Copy, Debug)]
+pub struct Occluder {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LightingState {
+    pub ambient: Vec3,
+    pub lights: Vec<Light>,
+    pub occluders: Vec<Occluder>,
+}
+
+impl Light {
+    pub fn point(position: Vec2, radius: f32, color: Vec3) -> Self {
+        Self {
+            position,
+            radius,
+            color,
+            intensity: 1.0,
+            shape: LightShape::Point,
+            cast_shadows: true,
+        }
+    }
+
+    pub fn cone(
+        position: Vec2,
+        radius: f32,
+        color: Vec3,
+        direction: Vec2,
+        half_angle: f32,
+    ) -> Self {
+        Self {
+            shape: LightShape::Cone {
+                direction: direction.normalize_or_zero(),
+                half_angle,
+            },
+            ..Self::point(position, radius, color)
+        }
+    }
+}
+
+impl Occluder {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self {
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+}
+
+/// Accumulates lights into a light map, which is then multiplied over
+/// whatever was drawn to the currently bound framebuffer.
+#[derive(Clone)]
+pub struct LightRenderer {
+    vertex_array: VertexArrayHandle,
+    light_program: ProgramHandle,
+    composite_program: ProgramHandle,
+    pub light_map: DefaultTextureFramebuffer,
+}
+
+impl LightRenderer {
+    pub fn new(
+        dummy_vao: VertexArrayHandle,
+        draw: &mut draw::ServerChannel,
+    ) -> anyhow::Result<Self> {
+        let light_program = ProgramHandle::new_vf(
+            draw,
+            "light shader program",
+            shader::VERTEX,
+            shader::LIGHT_FRAGMENT,
+        )
+        .context("light program initialization failed")?;
+        let composite_program = ProgramHandle::new_vf(
+            draw,
+            "light composite shader program",
+            shader::VERTEX,
+            shader::COMPOSITE_FRAGMENT,
+        )
+        .context("light composite program initialization failed")?;
+        let light_map = DefaultTextureFramebuffer::new(draw, "light map framebuffer")?;
+
+        Ok(Self {
+            vertex_array: dummy_vao,
+            light_program,
+            composite_program,
+            light_map,
+        })
+    }
+
+    pub fn resize(
+        &mut self,
+        draw: &mut draw::ServerChannel,
+        window_size: PhysicalSize<u32>,
+    ) -> anyhow::Result<()> {
+        self.light_map.resize(draw, window_size)
+    }
+
+    /// Renders `state` into the light map, then multiplies the light map over
+    /// the currently bound (default) framebuffer.
+    pub fn draw(&self, context: &DrawContext, state: &LightingState) {
+        if self.light_map.size.is_none() {
+            return;
+        }
+
+        self.render_light_map(context, state);
+        self.composite(context);
+    }
+
+    fn render_light_map(&self, context: &DrawContext, state: &LightingState) {
+        let program = self.light_program.get(context);
+        let vertex_array = self.vertex_array.get(context);
+        let framebuffer = self.light_map.framebuffer.get(context);
+        let occluders = state
+            .occluders
+            .iter()
+            .take(MAX_OCCLUDERS)
+            .flat_map(|o| [o.min.x, o.min.y, o.max.x, o.max.y])
+            .collect::<Vec<_>>();
+        if state.occluders.len() > MAX_OCCLUDERS {
+            tracing::warn!(
+                "{} occluders were submitted, only the first {} cast shadows",
+                state.occluders.len(),
+                MAX_OCCLUDERS
+            );
+        }
+
+        framebuffer.bind();
+        vertex_array.bind();
+        unsafe {
+            gl::ClearColor(state.ambient.x, state.ambient.y, state.ambient.z, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+            gl::UseProgram(*program);
+
+            let loc_pos = gl::GetUniformLocation(*program, "light_pos\0".as_ptr() as *const _);
+            let loc_radius =
+                gl::GetUniformLocation(*program, "light_radius\0".as_ptr() as *const _);
+            let loc_color = gl::GetUniformLocation(*program, "light_color\0".as_ptr() as *const _);
+            let loc_dir = gl::GetUniformLocation(*program, "light_dir\0".as_ptr() as *const _);
+            let loc_cutoff =
+                gl::GetUniformLocation(*program, "light_cos_cutoff\0".as_ptr() as *const _);
+            let loc_num_occluders =
+                gl::GetUniformLocation(*program, "num_occluders\0".as_ptr() as *const _);
+            gl::Uniform4fv(
+                gl::GetUniformLocation(*program, "occluders\0".as_ptr() as *const _),
+                (occluders.len() / 4) as _,
+                occluders.as_ptr(),
+            );
+
+            for light in state.lights.iter() {
+                let color = light.color * light.intensity;
+                let (direction, cos_cutoff) = match light.shape {
+                    LightShape::Point => (Vec2::X, -2.0),
+                    LightShape::Cone {
+                        direction,
+                        half_angle,
+                    } => (direction, half_angle.cos()),
+                };
+                gl::Uniform2f(loc_pos, light.position.x, light.position.y);
+                gl::Uniform1f(loc_radius, light.radius);
+                gl::Uniform3f(loc_color, color.x, color.y, color.z);
+                gl::Uniform2f(loc_dir, direction.x, direction.y);
+                gl::Uniform1f(loc_cutoff, cos_cutoff);
+                gl::Uniform1i(
+                    loc_num_occluders,
+                    if light.cast_shadows {
+                        (occluders.len() / 4) as _
+                    } else {
+                        0
+                    },
+                );
+                gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+            }
+
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+        Framebuffer::unbind_static();
+    }
+
+    fn composite(&self, context: &DrawContext) {
+        let program = self.composite_program.get(context);
+        let vertex_array = self.vertex_array.get(context);
+
+        vertex_array.bind();
+        unsafe {
+            gl::UseProgram(*program);
+            gl::Uniform1i(
+                gl::GetUniformLocation(*program, "light_map\0".as_ptr() as *const _),
+                0,
+            );
+            gl::ActiveTexture(gl::TEXTURE0);
+            self.light_map.texture.get(context).bind();
+            gl::BlendFunc(gl::DST_COLOR, gl::ZERO);
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+}
+
+#[test]
+fn test_send_sync() {
+    use crate::{assert_send, assert_sync};
+    assert_send!(LightRenderer);
+    assert_sync!(LightRenderer);
+}
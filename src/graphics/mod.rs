@@ -1,22 +1,50 @@
-use std::{borrow::Cow, hash::Hash, marker::PhantomData};
+use std::{borrow::Cow, collections::BTreeMap, fmt::Write, hash::Hash, marker::PhantomData};
+
+use gl::types::GLuint;
 
 use crate::utils::uid::Uid;
 
 use self::wrappers::{
-    buffer::{BufferContainer, SendBufferContainer},
+    buffer::{Buffer, BufferContainer, BufferHandle, BufferTarget, SendBufferContainer},
     framebuffer::{Framebuffer, FramebufferContainer, FramebufferHandle, SendFramebufferContainer},
+    query::{QueryContainer, SendQueryContainer},
+    renderbuffer::{RenderbufferContainer, SendRenderbufferContainer},
     shader::{Program, ProgramContainer, ProgramHandle, SendProgramContainer},
-    texture::{SendTextureContainer, TextureContainer},
+    texture::{
+        ImageData, SendTextureContainer, Texture, TextureContainer, TextureHandle, TextureType,
+    },
     vertex_array::{
         SendVertexArrayContainer, VertexArray, VertexArrayContainer, VertexArrayHandle,
     },
+    GLHandle, GLHandleContainer, GLHandleTrait,
 };
 
+pub mod atlas;
 pub mod blur;
+pub mod camera;
+pub mod clip_stack;
 pub mod context;
 pub mod debug_callback;
+pub mod draw_queue;
+pub mod frame_graph;
+pub mod gpu_timer;
+pub mod image_loader;
+pub mod lighting;
+pub mod particles;
+pub mod picking;
+pub mod post_effect;
+pub mod present;
 pub mod quad_renderer;
+pub mod render_graph;
+pub mod screenshot;
+pub mod shader_watcher;
+pub mod shape_renderer;
+pub mod sprite_renderer;
+pub mod text;
+pub mod tilemap;
 pub mod transform_stack;
+pub mod uniform;
+pub mod upload;
 pub mod wrappers;
 
 #[derive(Debug)]
@@ -72,6 +100,50 @@ impl<T> Default for GfxHandle<T> {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct HandleInfo {
+    pub name: Cow<'static, str>,
+    pub type_name: &'static str,
+    pub gl_handle: GLuint,
+    pub estimated_size: usize,
+}
+
+impl HandleInfo {
+    pub fn new<T: GLHandleTrait<A>, A: Clone>(handle: &GLHandle<T, A>) -> Self {
+        Self {
+            name: handle.name(),
+            type_name: handle.type_name(),
+            gl_handle: **handle,
+            estimated_size: handle.estimated_size(),
+        }
+    }
+
+    /// Human readable table of `infos`, largest objects first, followed by
+    /// the count and size totals of each type.
+    pub fn dump(mut infos: Vec<HandleInfo>) -> String {
+        infos.sort_by(|a, b| {
+            (b.estimated_size, a.type_name, &a.name).cmp(&(a.estimated_size, b.type_name, &b.name))
+        });
+        let mut totals = BTreeMap::<&str, (usize, usize)>::new();
+        let mut dump = format!("{} live GL object(s)\n", infos.len());
+        for info in infos.iter() {
+            let total = totals.entry(info.type_name).or_default();
+            total.0 += 1;
+            total.1 += info.estimated_size;
+            writeln!(
+                dump,
+                "{:>12} {:>6} {:>10}B `{}`",
+                info.type_name, info.gl_handle, info.estimated_size, info.name
+            )
+            .unwrap();
+        }
+        for (type_name, (count, size)) in totals {
+            writeln!(dump, "{type_name}: {count} object(s), {size}B").unwrap();
+        }
+        dump
+    }
+}
+
 #[derive(Default)]
 pub struct HandleContainer {
     pub vertex_arrays: VertexArrayContainer,
@@ -79,6 +151,8 @@ pub struct HandleContainer {
     pub textures: TextureContainer,
     pub programs: ProgramContainer,
     pub framebuffers: FramebufferContainer,
+    pub queries: QueryContainer,
+    pub renderbuffers: RenderbufferContainer,
 }
 
 #[derive(Default)]
@@ -88,6 +162,8 @@ pub struct SendHandleContainer {
     textures: SendTextureContainer,
     programs: SendProgramContainer,
     framebuffers: SendFramebufferContainer,
+    queries: SendQueryContainer,
+    renderbuffers: SendRenderbufferContainer,
 }
 
 impl HandleContainer {
@@ -103,21 +179,25 @@ impl HandleContainer {
         VertexArray::new(name).map(|v| self.vertex_arrays.insert(handle, v))
     }
 
-    // pub fn create_buffer(
-    //     &mut self,
-    //     name: impl Into<Cow<'static, str>>,
-    //     handle: &BufferHandle,
-    // ) -> anyhow::Result<Buffer> {
-    //     Buffer::new(name).map(|b| self.buffers.insert(handle, b))
-    // }
+    pub fn create_buffer(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        handle: &BufferHandle,
+        target: BufferTarget,
+    ) -> anyhow::Result<Buffer> {
+        Buffer::new_args(name, target).map(|b| self.buffers.insert(handle, b))
+    }
 
-    // pub fn create_texture(
-    //     &mut self,
-    //     name: impl Into<Cow<'static, str>>,
-    //     handle: &TextureHandle,
-    // ) -> anyhow::Result<Texture> {
-    //     Texture::new(name).map(|t| self.textures.insert(handle, t))
-    // }
+    pub fn create_texture(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        handle: &TextureHandle,
+        image: &ImageData,
+    ) -> anyhow::Result<Texture> {
+        let texture = Texture::new_args(name, TextureType::E2D)?;
+        texture.upload(image)?;
+        Ok(self.textures.insert(handle, texture))
+    }
 
     pub fn create_vf_program(
         &mut self,
@@ -131,6 +211,24 @@ impl HandleContainer {
         Ok(program)
     }
 
+    /// Compiles a new program for `handle`, the current one is only replaced
+    /// on success so that a broken shader edit keeps the old one running.
+    pub fn recreate_vf_program(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        handle: &ProgramHandle,
+        vertex: &str,
+        fragment: &str,
+    ) -> anyhow::Result<Program> {
+        let program = Program::new(name.into())?;
+        program.init_vf(vertex, fragment)?;
+        if self.programs.get(handle).is_some() {
+            self.programs.replace(handle, |_| Ok(program))
+        } else {
+            Ok(self.programs.insert(handle, program))
+        }
+    }
+
     pub fn create_framebuffer(
         &mut self,
         name: impl Into<Cow<'static, str>>,
@@ -139,6 +237,53 @@ impl HandleContainer {
         Framebuffer::new(name).map(|f| self.framebuffers.insert(handle, f))
     }
 
+    pub fn len(&self) -> usize {
+        self.vertex_arrays.len()
+            + self.buffers.len()
+            + self.textures.len()
+            + self.programs.len()
+            + self.framebuffers.len()
+            + self.queries.len()
+            + self.renderbuffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Information of every GL object currently alive in this container.
+    pub fn handle_infos(&self) -> Vec<HandleInfo> {
+        fn collect<T: GLHandleTrait<A>, A: Clone>(
+            container: &GLHandleContainer<T, A>,
+            infos: &mut Vec<HandleInfo>,
+        ) {
+            infos.extend(container.iter().map(HandleInfo::new));
+        }
+
+        let mut infos = Vec::with_capacity(self.len());
+        collect(&self.vertex_arrays, &mut infos);
+        collect(&self.buffers, &mut infos);
+        collect(&self.textures, &mut infos);
+        collect(&self.programs, &mut infos);
+        collect(&self.framebuffers, &mut infos);
+        collect(&self.queries, &mut infos);
+        collect(&self.renderbuffers, &mut infos);
+        infos
+    }
+
+    /// Deletes every GL object, requires the GL context to be current.
+    pub fn clear(&mut self) {
+        // framebuffers reference textures and renderbuffers, so delete them
+        // first
+        self.framebuffers.clear();
+        self.renderbuffers.clear();
+        self.vertex_arrays.clear();
+        self.programs.clear();
+        self.textures.clear();
+        self.buffers.clear();
+        self.queries.clear();
+    }
+
     pub fn to_send(self) -> SendHandleContainer {
         SendHandleContainer {
             vertex_arrays: self.vertex_arrays.to_send(),
@@ -146,6 +291,8 @@ impl HandleContainer {
             textures: self.textures.to_send(),
             programs: self.programs.to_send(),
             framebuffers: self.framebuffers.to_send(),
+            queries: self.queries.to_send(),
+            renderbuffers: self.renderbuffers.to_send(),
         }
     }
 }
@@ -162,6 +309,8 @@ impl SendHandleContainer {
             textures: self.textures.to_nonsend(),
             programs: self.programs.to_nonsend(),
             framebuffers: self.framebuffers.to_nonsend(),
+            queries: self.queries.to_nonsend(),
+            renderbuffers: self.renderbuffers.to_nonsend(),
         }
     }
 }
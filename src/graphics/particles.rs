@@ -0,0 +1,314 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use gl::types::GLuint;
+use glam::{Vec2, Vec4};
+use rand::Rng;
+
+use crate::{
+    exec::{
+        interpolation::Interpolate,
+        server::{update, GameServerSendChannel, ServerSendChannel},
+    },
+    utils::{error::ResultExt, mutex::Mutex, rng::GameRng, uid::Uid},
+};
+
+use super::{context::DrawContext, sprite_renderer::Sprite};
+
+/// Value of a particle property over its life, keyed by the elapsed
+/// fraction of the life and interpolated linearly between the keys.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve<T> {
+    /// sorted by key, never empty
+    keys: Vec<(f32, T)>,
+}
+
+/// Definition of a particle emitter, see `update::ServerChannel::add_particle_effect`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmitterDef {
+    /// particles per second
+    pub spawn_rate: f32,
+    /// shortest and longest life, picked uniformly for every particle
+    pub lifetime: [Duration; 2],
+    /// corners of the box initial velocities are picked in, UI units per
+    /// second
+    pub velocity: [Vec2; 2],
+    pub acceleration: Vec2,
+    pub color: Curve<Vec4>,
+    pub size: Curve<f32>,
+    /// spawning pauses while that many particles are alive
+    pub max_particles: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+}
+
+/// Simulation of an emitter, owned by the update server.
+pub struct ParticleEmitter {
+    def: EmitterDef,
+    pub position: Vec2,
+    /// alive particles keep moving while not emitting
+    pub emitting: bool,
+    particles: Vec<Particle>,
+    /// fraction of a particle left to spawn from the previous steps
+    spawn_debt: f32,
+    rng: GameRng,
+}
+
+/// Sprites of the particles as of the last update server tick, read by the
+/// draw server.
+#[derive(Default)]
+pub struct ParticleSprites {
+    sprites: Mutex<Arc<Vec<Sprite>>>,
+}
+
+/// A particle effect simulated on the update server, removed when the
+/// handle is dropped. Scenes draw it with `draw`.
+pub struct ParticleEffectHandle {
+    id: Uid,
+    sprites: Arc<ParticleSprites>,
+    channel: ServerSendChannel<update::RecvMsg>,
+}
+
+impl<T: Interpolate> Curve<T> {
+    pub fn constant(value: T) -> Self {
+        Self {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    /// Curve through `keys`, pairs of life fraction and value.
+    pub fn new(keys: impl IntoIterator<Item = (f32, T)>) -> anyhow::Result<Self> {
+        let mut keys = keys.into_iter().collect::<Vec<_>>();
+        anyhow::ensure!(!keys.is_empty(), "a curve needs at least one key");
+        keys.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Ok(Self { keys })
+    }
+
+    /// Value at the life fraction `t`, the first and last keys extend to
+    /// both ends.
+    pub fn sample(&self, t: f32) -> T {
+        let next = self.keys.partition_point(|(key, _)| *key <= t);
+        if next == 0 {
+            return self.keys[0].1.clone();
+        }
+        let (key, value) = &self.keys[next - 1];
+        match self.keys.get(next) {
+            Some((next_key, next_value)) => {
+                value.interpolate(next_value, (t - key) / (next_key - key))
+            }
+            None => value.clone(),
+        }
+    }
+}
+
+impl EmitterDef {
+    /// White particles of size 4 standing still.
+    pub fn new(spawn_rate: f32, lifetime: Duration) -> Self {
+        Self {
+            spawn_rate,
+            lifetime: [lifetime; 2],
+            velocity: [Vec2::ZERO; 2],
+            acceleration: Vec2::ZERO,
+            color: Curve::constant(Vec4::ONE),
+            size: Curve::constant(4.0),
+            max_particles: 1024,
+        }
+    }
+
+    pub fn lifetime(mut self, min: Duration, max: Duration) -> Self {
+        self.lifetime = [min, max];
+        self
+    }
+
+    pub fn velocity(mut self, min: Vec2, max: Vec2) -> Self {
+        self.velocity = [min, max];
+        self
+    }
+
+    pub fn acceleration(mut self, acceleration: Vec2) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    pub fn color(mut self, color: Curve<Vec4>) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn size(mut self, size: Curve<f32>) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn max_particles(mut self, max_particles: usize) -> Self {
+        self.max_particles = max_particles;
+        self
+    }
+}
+
+impl ParticleEmitter {
+    pub fn new(def: EmitterDef, position: Vec2, rng: GameRng) -> Self {
+        Self {
+            def,
+            position,
+            emitting: true,
+            particles: Vec::new(),
+            spawn_debt: 0.0,
+            rng,
+        }
+    }
+
+    /// Advances the simulation by `dt` seconds: ages and moves the
+    /// particles, removes the dead ones and spawns the new ones.
+    pub fn step(&mut self, dt: f32) {
+        let acceleration = self.def.acceleration;
+        self.particles.retain_mut(|particle| {
+            particle.age += dt;
+            particle.velocity += acceleration * dt;
+            particle.position += particle.velocity * dt;
+            particle.age < particle.lifetime
+        });
+
+        if !self.emitting {
+            self.spawn_debt = 0.0;
+            return;
+        }
+        self.spawn_debt += self.def.spawn_rate * dt;
+        while self.spawn_debt >= 1.0 {
+            self.spawn_debt -= 1.0;
+            if self.particles.len() >= self.def.max_particles {
+                continue;
+            }
+            let [min_life, max_life] = self.def.lifetime.map(|life| life.as_secs_f32());
+            let [min_velocity, max_velocity] = self.def.velocity;
+            let mut between = |min: f32, max: f32| min + (max - min) * self.rng.gen::<f32>();
+            let lifetime = between(min_life, max_life);
+            let velocity = Vec2::new(
+                between(min_velocity.x, max_velocity.x),
+                between(min_velocity.y, max_velocity.y),
+            );
+            self.particles.push(Particle {
+                position: self.position,
+                velocity,
+                age: 0.0,
+                lifetime,
+            });
+        }
+    }
+
+    /// Square sprites of the particles, oldest first.
+    pub fn sprites(&self) -> Vec<Sprite> {
+        self.particles
+            .iter()
+            .map(|particle| {
+                let t = particle.age / particle.lifetime;
+                let size = self.def.size.sample(t);
+                Sprite::new(particle.position, Vec2::splat(size)).color(self.def.color.sample(t))
+            })
+            .collect()
+    }
+}
+
+impl ParticleSprites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self) -> Arc<Vec<Sprite>> {
+        self.sprites.lock().clone()
+    }
+
+    pub fn set(&self, sprites: Vec<Sprite>) {
+        *self.sprites.lock() = Arc::new(sprites);
+    }
+}
+
+impl ParticleEffectHandle {
+    pub fn new(
+        id: Uid,
+        sprites: Arc<ParticleSprites>,
+        channel: ServerSendChannel<update::RecvMsg>,
+    ) -> Self {
+        Self {
+            id,
+            sprites,
+            channel,
+        }
+    }
+
+    /// Sprites of the last simulated tick, empty until the first one.
+    pub fn sprites(&self) -> Arc<Vec<Sprite>> {
+        self.sprites.get()
+    }
+
+    /// Moves the emitter, the particles already spawned stay where they are.
+    pub fn set_position(&self, position: Vec2) -> anyhow::Result<()> {
+        self.channel
+            .send(update::RecvMsg::MoveParticleEffect(self.id, position))
+            .context("unable to send particle effect position")
+    }
+
+    /// Pauses or resumes spawning, e.g. to let a burst fade out.
+    pub fn set_emitting(&self, emitting: bool) -> anyhow::Result<()> {
+        self.channel
+            .send(update::RecvMsg::SetParticleEmission(self.id, emitting))
+            .context("unable to send particle emission")
+    }
+
+    /// Draws the particles with `texture` and the current transform, in a
+    /// single instanced draw call.
+    pub fn draw(&self, ctx: &mut DrawContext, texture: GLuint) {
+        let sprites = self.sprites();
+        if !sprites.is_empty() {
+            ctx.draw_sprites_instanced(texture, &sprites);
+        }
+    }
+}
+
+impl Drop for ParticleEffectHandle {
+    fn drop(&mut self) {
+        self.channel
+            .send(update::RecvMsg::RemoveParticleEffect(self.id))
+            .context("unable to remove particle effect")
+            .log_warn();
+    }
+}
+
+#[test]
+fn test() {
+    use rand::SeedableRng;
+
+    let curve = Curve::new([(1.0, 10.0), (0.0, 0.0), (0.5, 2.0)]).unwrap();
+    assert_eq!(curve.sample(-1.0), 0.0);
+    assert_eq!(curve.sample(0.25), 1.0);
+    assert_eq!(curve.sample(0.75), 6.0);
+    assert_eq!(curve.sample(2.0), 10.0);
+    assert!(Curve::<f32>::new([]).is_err());
+
+    let def = EmitterDef::new(10.0, Duration::from_secs(1))
+        .velocity(Vec2::new(1.0, 0.0), Vec2::new(1.0, 0.0))
+        .acceleration(Vec2::new(0.0, 2.0))
+        .size(Curve::new([(0.0, 2.0), (1.0, 0.0)]).unwrap())
+        .max_particles(8);
+    let mut emitter = ParticleEmitter::new(def, Vec2::ZERO, GameRng::seed_from_u64(0));
+    // a particle every 0.1 s
+    emitter.step(0.25);
+    assert_eq!(emitter.sprites().len(), 2);
+    emitter.step(0.5);
+    let sprites = emitter.sprites();
+    assert_eq!(sprites.len(), 7);
+    // the first one is half way through its life
+    assert_eq!(sprites[0].center, Vec2::new(0.5, 0.5));
+    assert_eq!(sprites[0].size, Vec2::ONE);
+    emitter.step(0.5);
+    assert_eq!(emitter.sprites().len(), 8, "capped at max_particles");
+    emitter.emitting = false;
+    emitter.step(1.0);
+    assert!(emitter.sprites().is_empty());
+}
@@ -0,0 +1,286 @@
+use std::{collections::VecDeque, mem};
+
+use anyhow::{ensure, Context};
+use glam::{Affine2, Vec2, Vec4};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{query::QueryReturn, server::draw},
+    scene::main::RootScene,
+    ui::utils::geom::{UIPos, UIRect},
+    utils::{error::ResultExt, uid::Uid},
+};
+
+use super::{
+    context::DrawContext,
+    wrappers::{
+        buffer::{BufferHandle, BufferTarget},
+        fence::Fence,
+        framebuffer::{Framebuffer, FramebufferHandle},
+        texture::TextureHandle,
+    },
+};
+
+/// Side of the square of pixels rendered around a picked position.
+pub const PICK_SIZE: u32 = 5;
+
+/// `pick_at` queries, answered by drawing the pickable objects around the
+/// position with colors encoding their ids, read back like `FrameCaptures`.
+pub struct Picking {
+    requested: VecDeque<(UIPos, QueryReturn<Option<Uid>>)>,
+    /// one pick at a time, later requests are drawn in later frames
+    in_flight: Option<PickReadback>,
+    target: TextureHandle,
+    framebuffer: FramebufferHandle,
+    attached: bool,
+    pixels: BufferHandle,
+    /// ids drawn in the current pass, the color of an id is its index + 1
+    ids: Vec<Uid>,
+}
+
+struct PickReadback {
+    fence: Fence,
+    ret: QueryReturn<Option<Uid>>,
+    ids: Vec<Uid>,
+}
+
+impl Picking {
+    pub fn new(draw: &mut draw::ServerChannel) -> anyhow::Result<Self> {
+        Ok(Self {
+            requested: VecDeque::new(),
+            in_flight: None,
+            target: TextureHandle::new_2d(
+                draw,
+                "pick target",
+                PhysicalSize::new(PICK_SIZE, PICK_SIZE),
+            )?,
+            framebuffer: FramebufferHandle::new(draw, "pick framebuffer")?,
+            attached: false,
+            pixels: BufferHandle::new(draw, "pick readback", BufferTarget::PixelPackBuffer)?,
+            ids: Vec::new(),
+        })
+    }
+
+    pub fn push(&mut self, pos: UIPos, ret: QueryReturn<Option<Uid>>) {
+        self.requested.push_back((pos, ret));
+    }
+
+    /// Drops the requests, the fence has to be deleted while the context is
+    /// still current.
+    pub fn clear(&mut self) {
+        self.requested.clear();
+        self.in_flight = None;
+    }
+}
+
+impl DrawContext {
+    /// Color `id` is drawn with in the picking pass.
+    pub fn pick_color(&mut self, id: Uid) -> Vec4 {
+        self.picking.ids.push(id);
+        encode_index(self.picking.ids.len())
+    }
+
+    /// Fills `rect`, in the current transform, as the pickable `id`.
+    pub fn draw_pick_rect(&mut self, id: Uid, rect: UIRect) {
+        let color = self.pick_color(id);
+        let min = Vec2::from(rect.pos);
+        self.draw_rounded_rect(min, min + Vec2::from(rect.size), 0.0, color);
+    }
+
+    /// Draws the pickables around the next requested position and starts
+    /// reading them back, unless a pick is still in flight.
+    pub fn start_picking(&mut self, root_scene: Option<&RootScene>) {
+        if self.picking.in_flight.is_some() {
+            return;
+        }
+        let (pos, ret) = match self.picking.requested.pop_front() {
+            Some(request) => request,
+            None => return,
+        };
+        let started = self
+            .render_pick(pos, root_scene)
+            .context("unable to render picking pass")
+            .log_warn();
+        let ids = mem::take(&mut self.picking.ids);
+        // failed requests are dropped, which fails their queries
+        if started.is_some() {
+            self.picking.in_flight = Some(PickReadback {
+                fence: Fence::insert(),
+                ret,
+                ids,
+            });
+        }
+    }
+
+    fn render_pick(&mut self, pos: UIPos, root_scene: Option<&RootScene>) -> anyhow::Result<()> {
+        let framebuffer = self
+            .picking
+            .framebuffer
+            .try_get(self)
+            .context("pick framebuffer was not created")?;
+        framebuffer.bind();
+        if !self.picking.attached {
+            let target = self
+                .picking
+                .target
+                .try_get(self)
+                .context("pick target was not created")?;
+            let status = unsafe {
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_2D,
+                    *target,
+                    0,
+                );
+                gl::CheckFramebufferStatus(gl::FRAMEBUFFER)
+            };
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                Framebuffer::unbind_static();
+            }
+            ensure!(
+                status == gl::FRAMEBUFFER_COMPLETE,
+                "incomplete pick framebuffer ({status:#x})"
+            );
+            self.picking.attached = true;
+        }
+        unsafe {
+            gl::Viewport(0, 0, PICK_SIZE as _, PICK_SIZE as _);
+            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            // colors are ids, they must be written as is
+            gl::Disable(gl::BLEND);
+            gl::Disable(gl::DITHER);
+        }
+
+        // the target covers the pixels around `pos`, the scenes are drawn
+        // in window UI units as usual
+        let region = PICK_SIZE as f32 / self.ui_scale();
+        let min = Vec2::from(pos) - region * 0.5;
+        let old_len = self.transform_stack.len();
+        self.transform_stack.push();
+        *self.transform_stack.peek_mut() = Affine2::from_scale(Vec2::from(self.ui_size) / region)
+            * Affine2::from_translation(-min);
+        self.picking.ids.clear();
+        if let Some(root_scene) = root_scene {
+            root_scene.draw_pick(self);
+        }
+        self.flush_shapes();
+        self.flush_sprites();
+        self.transform_stack.pop();
+        debug_assert!(old_len == self.transform_stack.len());
+        self.clip_stack.clear();
+        self.apply_clip(None);
+
+        let readback = self
+            .picking
+            .pixels
+            .try_get(self)
+            .context("pick readback buffer was not created")
+            .and_then(|pixels| {
+                pixels.allocate(PICK_SIZE as usize * PICK_SIZE as usize * 4)?;
+                pixels.bind();
+                unsafe {
+                    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+                    // offset 0 in the bound buffer
+                    gl::ReadPixels(
+                        0,
+                        0,
+                        PICK_SIZE as _,
+                        PICK_SIZE as _,
+                        gl::RGBA,
+                        gl::UNSIGNED_BYTE,
+                        std::ptr::null_mut(),
+                    );
+                }
+                pixels.unbind();
+                Ok(())
+            });
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::Enable(gl::DITHER);
+            gl::Viewport(
+                0,
+                0,
+                self.display_size.width.get() as _,
+                self.display_size.height.get() as _,
+            );
+        }
+        Framebuffer::unbind_static();
+        readback
+    }
+
+    /// Answers the pick whose readback the GPU is done with.
+    pub fn poll_picking(&mut self) {
+        let readback = match self.picking.in_flight.take() {
+            Some(readback) if readback.fence.is_signaled() => readback,
+            readback => {
+                self.picking.in_flight = readback;
+                return;
+            }
+        };
+        let picked = self
+            .picking
+            .pixels
+            .try_get(self)
+            .context("pick readback buffer was deleted")
+            .and_then(|pixels| pixels.read_range(0, PICK_SIZE as usize * PICK_SIZE as usize * 4))
+            .map(|pixels| nearest_pick(&pixels, &readback.ids))
+            .context("unable to read back picking pass")
+            .log_warn();
+        if let Some(picked) = picked {
+            readback
+                .ret
+                .send(picked, &self.base.proxy)
+                .context("unable to return picked id")
+                .log_warn();
+        }
+    }
+}
+
+fn encode_index(index: usize) -> Vec4 {
+    let channel = |shift: usize| ((index >> shift) & 0xff) as f32 / 255.0;
+    Vec4::new(channel(0), channel(8), channel(16), 1.0)
+}
+
+/// Id drawn the closest to the center of `pixels`, mostly opaque pixels
+/// only, the anti-aliasing fringe keeps the color but not the alpha.
+fn nearest_pick(pixels: &[u8], ids: &[Uid]) -> Option<Uid> {
+    let center = (PICK_SIZE / 2) as i32;
+    pixels
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|(_, pixel)| pixel[3] > 127)
+        .filter_map(|(i, pixel)| {
+            let index = pixel[0] as usize | (pixel[1] as usize) << 8 | (pixel[2] as usize) << 16;
+            let id = ids.get(index.checked_sub(1)?)?;
+            let (x, y) = ((i as u32 % PICK_SIZE) as i32, (i as u32 / PICK_SIZE) as i32);
+            Some(((x - center).pow(2) + (y - center).pow(2), *id))
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, id)| id)
+}
+
+#[test]
+fn test() {
+    let ids = [Uid::new(), Uid::new()];
+    let pixel = |index: usize| {
+        let color = encode_index(index) * 255.0;
+        [color.x, color.y, color.z, color.w].map(|c| c.round() as u8)
+    };
+    assert_eq!(pixel(0x030201), [1, 2, 3, 255]);
+
+    let mut pixels = vec![0u8; PICK_SIZE as usize * PICK_SIZE as usize * 4];
+    assert_eq!(nearest_pick(&pixels, &ids), None);
+    // the first id in a corner, the second one next to the center
+    pixels[..4].copy_from_slice(&pixel(1));
+    assert_eq!(nearest_pick(&pixels, &ids), Some(ids[0]));
+    let next_to_center = (PICK_SIZE * (PICK_SIZE / 2) + PICK_SIZE / 2 + 1) as usize * 4;
+    pixels[next_to_center..next_to_center + 4].copy_from_slice(&pixel(2));
+    assert_eq!(nearest_pick(&pixels, &ids), Some(ids[1]));
+    // fringe and unknown colors are ignored
+    pixels[next_to_center + 3] = 100;
+    assert_eq!(nearest_pick(&pixels, &ids), Some(ids[0]));
+    pixels[..4].copy_from_slice(&pixel(3));
+    assert_eq!(nearest_pick(&pixels, &ids), None);
+}
@@ -0,0 +1,453 @@
+use std::{borrow::Cow, mem};
+
+use gl::types::GLuint;
+use glam::Vec3;
+use winit::dpi::PhysicalSize;
+
+use crate::{exec::server::draw, utils::error::ResultExt};
+
+use super::{
+    context::DrawContext,
+    render_graph::{RenderPass, RenderTarget, SCENE_PASS},
+    wrappers::{
+        framebuffer::{DefaultTextureFramebuffer, Framebuffer},
+        shader::ProgramHandle,
+        texture::{ImageData, TextureFilter, TextureHandle, TextureOptions, TextureWrap},
+        vertex_array::VertexArrayHandle,
+    },
+};
+
+/// Name of the render pass applying the post effects.
+pub const POST_PASS: &str = "post";
+
+pub(super) mod shader {
+    /// full screen quad, `tex_coords` spanning the whole texture
+    pub const VERTEX: &str = r#"
+    #version 300 es
+    out vec2 tex_coords;
+    const vec2 positions[4] = vec2[](
+        vec2(-1.0, 1.0), vec2(1.0, 1.0),
+        vec2(-1.0, -1.0), vec2(1.0, -1.0)
+    );
+    void main() {
+        vec2 pos = positions[gl_VertexID];
+        gl_Position = vec4(pos, 0.0, 1.0);
+        tex_coords = (pos + vec2(1.0)) * vec2(0.5);
+    }
+    "#;
+
+    pub const VIGNETTE: &str = r#"
+    #version 300 es
+    precision mediump float;
+    in vec2 tex_coords;
+    out vec4 color;
+    uniform sampler2D tex;
+    uniform float strength;
+    uniform float radius;
+    void main() {
+        color = texture(tex, tex_coords);
+        float dist = length(tex_coords - vec2(0.5)) * 1.41421;
+        color.rgb *= 1.0 - strength * smoothstep(radius, 1.0, dist);
+    }
+    "#;
+
+    pub const CHROMATIC_ABERRATION: &str = r#"
+    #version 300 es
+    precision mediump float;
+    in vec2 tex_coords;
+    out vec4 color;
+    uniform sampler2D tex;
+    uniform float offset;
+    void main() {
+        vec2 dir = (tex_coords - vec2(0.5)) * offset;
+        color = texture(tex, tex_coords);
+        color.r = texture(tex, tex_coords + dir).r;
+        color.b = texture(tex, tex_coords - dir).b;
+    }
+    "#;
+
+    pub const COLOR_GRADING: &str = r#"
+    #version 300 es
+    precision mediump float;
+    in vec2 tex_coords;
+    out vec4 color;
+    uniform sampler2D tex;
+    uniform sampler2D lut;
+    uniform float lut_size;
+    void main() {
+        color = texture(tex, tex_coords);
+        vec3 c = clamp(color.rgb, 0.0, 1.0);
+        // blue slices side by side, red along x and green along y
+        float b = c.b * (lut_size - 1.0);
+        float b0 = floor(b);
+        float b1 = min(b0 + 1.0, lut_size - 1.0);
+        vec2 uv = (c.rg * (lut_size - 1.0) + 0.5) / vec2(lut_size * lut_size, lut_size);
+        vec3 c0 = texture(lut, uv + vec2(b0 / lut_size, 0.0)).rgb;
+        vec3 c1 = texture(lut, uv + vec2(b1 / lut_size, 0.0)).rgb;
+        color.rgb = mix(c0, c1, b - b0);
+    }
+    "#;
+}
+
+/// A full screen effect of the post effect chain, executed on the draw
+/// server.
+pub trait PostEffect: Send {
+    fn name(&self) -> Cow<'static, str>;
+
+    /// Called once the effect is inserted into the chain.
+    fn init(&mut self, _ctx: &mut DrawContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called before the first `apply` and whenever the display is resized.
+    fn resize(&mut self, _ctx: &mut DrawContext, _size: PhysicalSize<u32>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Draws the color texture `src` into the framebuffer `dst`, 0 being the
+    /// screen, both of the display size.
+    fn apply(&mut self, ctx: &mut DrawContext, src: GLuint, dst: GLuint) -> anyhow::Result<()>;
+}
+
+/// Post effects applied in order to the output of the scene pass. The
+/// scene is only drawn offscreen while the chain isn't empty.
+pub struct PostChain {
+    effects: Vec<Box<dyn PostEffect>>,
+    scene: DefaultTextureFramebuffer,
+    /// intermediate targets, alternated between effects
+    ping_pong: [DefaultTextureFramebuffer; 2],
+    size: Option<PhysicalSize<u32>>,
+}
+
+impl PostChain {
+    pub fn new(draw: &mut draw::ServerChannel) -> anyhow::Result<Self> {
+        Ok(Self {
+            effects: Vec::new(),
+            scene: DefaultTextureFramebuffer::new(draw, "post effect scene")?,
+            ping_pong: [
+                DefaultTextureFramebuffer::new(draw, "post effect framebuffer 0")?,
+                DefaultTextureFramebuffer::new(draw, "post effect framebuffer 1")?,
+            ],
+            size: None,
+        })
+    }
+
+    pub fn names(&self) -> Vec<Cow<'static, str>> {
+        self.effects.iter().map(|effect| effect.name()).collect()
+    }
+}
+
+/// Index of the intermediate target every effect of a chain of `len`
+/// effects draws into, `None` for the screen.
+fn chain_targets(len: usize) -> impl Iterator<Item = Option<usize>> {
+    (0..len).map(move |i| (i + 1 < len).then_some(i % 2))
+}
+
+impl DrawContext {
+    /// Inserts `effect` at `index` of the post effect chain, clamped to its
+    /// length.
+    pub fn insert_post_effect(
+        &mut self,
+        index: usize,
+        mut effect: Box<dyn PostEffect>,
+    ) -> anyhow::Result<()> {
+        effect.init(self)?;
+        if let Some(size) = self.post_effects.size {
+            effect.resize(self, size)?;
+        }
+        let index = index.min(self.post_effects.effects.len());
+        self.post_effects.effects.insert(index, effect);
+        self.update_post_pass();
+        Ok(())
+    }
+
+    pub fn remove_post_effect(&mut self, name: &str) -> Option<Box<dyn PostEffect>> {
+        let effects = &mut self.post_effects.effects;
+        let index = effects.iter().position(|effect| effect.name() == name)?;
+        let effect = effects.remove(index);
+        self.update_post_pass();
+        Some(effect)
+    }
+
+    /// Redirects the scene pass offscreen and adds the post pass if there
+    /// are effects, undoes it otherwise.
+    fn update_post_pass(&mut self) {
+        let graph = &mut self.render_graph;
+        if self.post_effects.effects.is_empty() {
+            graph.set_output(SCENE_PASS, RenderTarget::Screen);
+            graph.remove_pass(POST_PASS);
+        } else if !graph.pass_names().contains(&POST_PASS) {
+            let scene = RenderTarget::Offscreen(self.post_effects.scene.clone());
+            graph.set_output(SCENE_PASS, scene.clone());
+            graph.add_pass(
+                RenderPass::new(
+                    POST_PASS,
+                    RenderTarget::Screen,
+                    |ctx: &mut DrawContext, _| ctx.apply_post_effects(),
+                )
+                .input(scene),
+            );
+        }
+    }
+
+    fn apply_post_effects(&mut self) {
+        let size = PhysicalSize::new(
+            self.display_size.width.get(),
+            self.display_size.height.get(),
+        );
+        let mut effects = mem::take(&mut self.post_effects.effects);
+        let (scene, ping_pong) = (
+            self.post_effects.scene.clone(),
+            self.post_effects.ping_pong.clone(),
+        );
+        if self.post_effects.size != Some(size) {
+            for target in ping_pong.iter() {
+                let mut target = target.clone();
+                target.size = self.post_effects.size;
+                target.resize_in_server(self, size).log_warn();
+            }
+            for effect in effects.iter_mut() {
+                effect.resize(self, size).log_warn();
+            }
+            self.post_effects.size = Some(size);
+        }
+
+        let mut src = *scene.texture.get(self);
+        let targets = chain_targets(effects.len());
+        for (effect, target) in effects.iter_mut().zip(targets) {
+            let (dst, texture) = match target {
+                Some(i) => (
+                    *ping_pong[i].framebuffer.get(self),
+                    *ping_pong[i].texture.get(self),
+                ),
+                None => (0, 0),
+            };
+            let name = effect.name();
+            self.debug_group(&name, |ctx| effect.apply(ctx, src, dst))
+                .map_err(|e| e.context(format!("post effect {} failed", name)))
+                .log_warn();
+            src = texture;
+        }
+        Framebuffer::unbind_static();
+        // effects inserted while applying
+        effects.append(&mut self.post_effects.effects);
+        self.post_effects.effects = effects;
+    }
+}
+
+/// Draws a full screen quad with a program sampling `tex`.
+#[derive(Clone)]
+pub struct FullscreenPass {
+    vertex_array: VertexArrayHandle,
+    program: ProgramHandle,
+}
+
+impl FullscreenPass {
+    pub fn new(
+        dummy_vao: VertexArrayHandle,
+        draw: &mut draw::ServerChannel,
+        name: &'static str,
+        fragment: &'static str,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            vertex_array: dummy_vao,
+            program: ProgramHandle::new_vf(draw, name, shader::VERTEX, fragment)?,
+        })
+    }
+
+    /// Binds `dst` and draws `src` through the program, `uniforms` sets the
+    /// other uniforms of the bound program.
+    pub fn draw(
+        &self,
+        ctx: &DrawContext,
+        src: GLuint,
+        dst: GLuint,
+        uniforms: impl FnOnce(&dyn Fn(&str) -> i32),
+    ) {
+        let program = self.program.get(ctx);
+        self.vertex_array.get(ctx).bind();
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, dst);
+            gl::UseProgram(*program);
+            let location = |name: &str| {
+                let name = format!("{name}\0");
+                gl::GetUniformLocation(*program, name.as_ptr() as *const _)
+            };
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, src);
+            gl::Uniform1i(location("tex"), 0);
+            uniforms(&location);
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+}
+
+/// Darkens the corners of the screen.
+pub struct Vignette {
+    pass: FullscreenPass,
+    pub strength: f32,
+    /// distance from the center where the darkening starts, 1 being the
+    /// corners
+    pub radius: f32,
+}
+
+impl Vignette {
+    pub fn new(
+        dummy_vao: VertexArrayHandle,
+        draw: &mut draw::ServerChannel,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            pass: FullscreenPass::new(dummy_vao, draw, "vignette program", shader::VIGNETTE)?,
+            strength: 0.6,
+            radius: 0.5,
+        })
+    }
+}
+
+impl PostEffect for Vignette {
+    fn name(&self) -> Cow<'static, str> {
+        "vignette".into()
+    }
+
+    fn apply(&mut self, ctx: &mut DrawContext, src: GLuint, dst: GLuint) -> anyhow::Result<()> {
+        self.pass.draw(ctx, src, dst, |location| unsafe {
+            gl::Uniform1f(location("strength"), self.strength);
+            gl::Uniform1f(location("radius"), self.radius);
+        });
+        Ok(())
+    }
+}
+
+/// Splits the red and blue channels towards the edges of the screen.
+pub struct ChromaticAberration {
+    pass: FullscreenPass,
+    /// channel offset at the edges, relative to the screen size
+    pub offset: f32,
+}
+
+impl ChromaticAberration {
+    pub fn new(
+        dummy_vao: VertexArrayHandle,
+        draw: &mut draw::ServerChannel,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            pass: FullscreenPass::new(
+                dummy_vao,
+                draw,
+                "chromatic aberration program",
+                shader::CHROMATIC_ABERRATION,
+            )?,
+            offset: 0.01,
+        })
+    }
+}
+
+impl PostEffect for ChromaticAberration {
+    fn name(&self) -> Cow<'static, str> {
+        "chromatic aberration".into()
+    }
+
+    fn apply(&mut self, ctx: &mut DrawContext, src: GLuint, dst: GLuint) -> anyhow::Result<()> {
+        self.pass.draw(ctx, src, dst, |location| unsafe {
+            gl::Uniform1f(location("offset"), self.offset);
+        });
+        Ok(())
+    }
+}
+
+/// Maps colors through a 3D lookup table, stored as `size` slices of
+/// `size`x`size` side by side, blue selecting the slice.
+pub struct ColorGrading {
+    pass: FullscreenPass,
+    lut: TextureHandle,
+    size: u32,
+}
+
+impl ColorGrading {
+    pub fn new(
+        dummy_vao: VertexArrayHandle,
+        draw: &mut draw::ServerChannel,
+        size: u32,
+        grade: impl Fn(Vec3) -> Vec3,
+    ) -> anyhow::Result<Self> {
+        let lut = TextureHandle::new(draw, "color grading lut", lut_image(size, grade)?)?;
+        Ok(Self {
+            pass: FullscreenPass::new(
+                dummy_vao,
+                draw,
+                "color grading program",
+                shader::COLOR_GRADING,
+            )?,
+            lut,
+            size,
+        })
+    }
+}
+
+fn lut_image(size: u32, grade: impl Fn(Vec3) -> Vec3) -> anyhow::Result<ImageData> {
+    let max = (size.max(2) - 1) as f32;
+    let mut pixels = Vec::with_capacity((size * size * size * 4) as usize);
+    for g in 0..size {
+        for b in 0..size {
+            for r in 0..size {
+                let color = grade(Vec3::new(r as f32, g as f32, b as f32) / max);
+                let color = (color.clamp(Vec3::ZERO, Vec3::ONE) * 255.0).round();
+                pixels.extend([color.x as u8, color.y as u8, color.z as u8, 255]);
+            }
+        }
+    }
+    Ok(
+        ImageData::new(PhysicalSize::new(size * size, size), pixels)?.options(TextureOptions {
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            wrap: TextureWrap::ClampToEdge,
+            mipmaps: false,
+            ..Default::default()
+        }),
+    )
+}
+
+impl PostEffect for ColorGrading {
+    fn name(&self) -> Cow<'static, str> {
+        "color grading".into()
+    }
+
+    fn apply(&mut self, ctx: &mut DrawContext, src: GLuint, dst: GLuint) -> anyhow::Result<()> {
+        let lut = self
+            .lut
+            .try_get(ctx)
+            .ok_or_else(|| anyhow::format_err!("color grading lookup table was not created"))?;
+        self.pass.draw(ctx, src, dst, |location| unsafe {
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, *lut);
+            gl::Uniform1i(location("lut"), 1);
+            gl::Uniform1f(location("lut_size"), self.size as f32);
+            gl::ActiveTexture(gl::TEXTURE0);
+        });
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test() {
+    assert_eq!(chain_targets(0).count(), 0);
+    assert_eq!(chain_targets(1).collect::<Vec<_>>(), [None]);
+    assert_eq!(
+        chain_targets(4).collect::<Vec<_>>(),
+        [Some(0), Some(1), Some(0), None]
+    );
+
+    let lut = lut_image(4, |color| color).unwrap();
+    assert_eq!(lut.size, PhysicalSize::new(16, 4));
+    // red along x, blue slices, green along y
+    let pixel = |x: usize, y: usize| &lut.pixels[(y * 16 + x) * 4..][..3];
+    assert_eq!(pixel(3, 0), [255, 0, 0]);
+    assert_eq!(pixel(12, 0), [0, 0, 255]);
+    assert_eq!(pixel(0, 3), [0, 255, 0]);
+}
@@ -0,0 +1,68 @@
+use std::num::NonZeroU32;
+
+use glutin::surface::SwapInterval;
+use serde::{Deserialize, Serialize};
+
+/// How finished frames are handed to the display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentMode {
+    /// no vsync, may tear
+    Immediate,
+    /// vsync, frames wait for the next vblank
+    Fifo,
+}
+
+/// Sent back to the main thread every time the present mode is changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PresentModeReport {
+    pub requested: PresentMode,
+    /// `None` if none of the fallbacks could be set either, the previous
+    /// mode is kept in that case
+    pub effective: Option<PresentMode>,
+    pub supported: Vec<PresentMode>,
+}
+
+impl Default for PresentMode {
+    fn default() -> Self {
+        Self::Fifo
+    }
+}
+
+impl PresentMode {
+    pub const ALL: [PresentMode; 2] = [Self::Immediate, Self::Fifo];
+
+    pub fn swap_interval(self) -> SwapInterval {
+        match self {
+            Self::Immediate => SwapInterval::DontWait,
+            Self::Fifo => SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
+        }
+    }
+
+    pub fn from_swap_interval(interval: SwapInterval) -> Self {
+        match interval {
+            SwapInterval::DontWait => Self::Immediate,
+            SwapInterval::Wait(_) => Self::Fifo,
+        }
+    }
+
+    /// Modes tried in order when `self` is requested, starting with `self`.
+    pub fn fallbacks(self) -> &'static [PresentMode] {
+        match self {
+            Self::Immediate => &[Self::Immediate, Self::Fifo],
+            Self::Fifo => &[Self::Fifo, Self::Immediate],
+        }
+    }
+
+    /// The mode after this one when cycling through all of them.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Immediate => Self::Fifo,
+            Self::Fifo => Self::Immediate,
+        }
+    }
+
+    pub fn is_vsync(self) -> bool {
+        self != Self::Immediate
+    }
+}
@@ -0,0 +1,408 @@
+use std::{borrow::Cow, collections::HashMap, mem};
+
+use gl::types::GLuint;
+use trait_set::trait_set;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::server::draw,
+    scene::main::RootScene,
+    utils::{error::ResultExt, profile::profile_span, uid::Uid},
+};
+
+use super::{
+    context::DrawContext,
+    frame_graph::{FrameGraph, FrameGraphPass},
+    wrappers::framebuffer::{DefaultTextureFramebuffer, Framebuffer, MultisampleFramebuffer},
+};
+
+trait_set! {
+    pub trait RenderPassCallback = FnMut(&mut DrawContext, Option<&RootScene>) + Send;
+}
+
+/// Name of the pass clearing the frame and drawing the root scene.
+pub const SCENE_PASS: &str = "scene";
+
+/// Framebuffer a pass reads from or draws into. Offscreen targets are
+/// resized to the display by the graph, their owner shouldn't resize them.
+#[derive(Clone)]
+pub enum RenderTarget {
+    Screen,
+    Offscreen(DefaultTextureFramebuffer),
+}
+
+impl RenderTarget {
+    fn id(&self) -> Option<Uid> {
+        match self {
+            Self::Screen => None,
+            Self::Offscreen(target) => Some(target.framebuffer.0.handle.handle),
+        }
+    }
+
+    fn bind(
+        &self,
+        ctx: &mut DrawContext,
+        sizes: &mut HashMap<Uid, PhysicalSize<u32>>,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Screen => Framebuffer::unbind_static(),
+            Self::Offscreen(target) => {
+                let id = target.framebuffer.0.handle.handle;
+                let size =
+                    PhysicalSize::new(ctx.display_size.width.get(), ctx.display_size.height.get());
+                let mut target = target.clone();
+                target.size = sizes.get(&id).copied();
+                target.resize_in_server(ctx, size)?;
+                sizes.insert(id, size);
+                target.framebuffer.get(ctx).bind();
+            }
+        }
+        Ok(())
+    }
+
+    fn name(&self, ctx: &DrawContext) -> String {
+        match self {
+            Self::Screen => "screen".to_owned(),
+            Self::Offscreen(target) => match target.framebuffer.try_get(ctx) {
+                Some(framebuffer) => framebuffer.name().into_owned(),
+                None => format!("framebuffer {:?}", target.framebuffer.0.handle.handle),
+            },
+        }
+    }
+
+    fn gl_framebuffer(&self, ctx: &DrawContext) -> GLuint {
+        match self {
+            Self::Screen => 0,
+            Self::Offscreen(target) => *target.framebuffer.get(ctx),
+        }
+    }
+}
+
+pub struct RenderPass {
+    name: Cow<'static, str>,
+    inputs: Vec<RenderTarget>,
+    output: RenderTarget,
+    multisample: Option<MultisampleFramebuffer>,
+    callback: Box<dyn RenderPassCallback>,
+}
+
+impl RenderPass {
+    pub fn new(
+        name: impl Into<Cow<'static, str>>,
+        output: RenderTarget,
+        callback: impl RenderPassCallback + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            inputs: Vec::new(),
+            output,
+            multisample: None,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// The pass runs after every pass drawing into `input`.
+    pub fn input(mut self, input: RenderTarget) -> Self {
+        self.inputs.push(input);
+        self
+    }
+
+    /// The pass draws into `target` while it is enabled, which is then
+    /// resolved into the output. The whole output is overwritten, so the
+    /// pass should clear it.
+    pub fn multisample(mut self, target: MultisampleFramebuffer) -> Self {
+        self.multisample = Some(target);
+        self
+    }
+}
+
+/// Passes of a frame, executed in dependency order: a pass runs after the
+/// passes drawing into its inputs, and passes drawing into the same target
+/// run in the order they were added.
+pub struct RenderGraph {
+    passes: Vec<RenderPass>,
+    order: Option<Vec<usize>>,
+    sizes: HashMap<Uid, PhysicalSize<u32>>,
+}
+
+impl RenderGraph {
+    /// The graph with the scene pass, multisampling is disabled until
+    /// `set_samples` is called.
+    pub fn new(draw: &mut draw::ServerChannel) -> anyhow::Result<Self> {
+        let mut slf = Self::empty();
+        let multisample = MultisampleFramebuffer::new(draw, "scene multisample framebuffer", 0)?;
+        slf.add_pass(
+            RenderPass::new(
+                SCENE_PASS,
+                RenderTarget::Screen,
+                |ctx: &mut DrawContext, root_scene: Option<&RootScene>| {
+                    if let Some(root_scene) = root_scene {
+                        let _span = profile_span!("draw scenes").entered();
+                        root_scene.draw(ctx);
+                    }
+                    // shapes are usually backgrounds, below text and images
+                    ctx.flush_shapes();
+                    ctx.flush_sprites();
+                },
+            )
+            .multisample(multisample),
+        );
+        Ok(slf)
+    }
+
+    fn empty() -> Self {
+        Self {
+            passes: Vec::new(),
+            order: None,
+            sizes: HashMap::new(),
+        }
+    }
+
+    /// Adds `pass`, replacing the pass of the same name if any.
+    pub fn add_pass(&mut self, pass: RenderPass) {
+        self.order = None;
+        match self.passes.iter_mut().find(|p| p.name == pass.name) {
+            Some(old) => *old = pass,
+            None => self.passes.push(pass),
+        }
+    }
+
+    pub fn remove_pass(&mut self, name: &str) -> Option<RenderPass> {
+        let index = self.passes.iter().position(|p| p.name == name)?;
+        self.order = None;
+        Some(self.passes.remove(index))
+    }
+
+    /// Redirects the output of the pass `name`, e.g. drawing the scene
+    /// offscreen for a post effect, returns whether the pass exists.
+    pub fn set_output(&mut self, name: &str, output: RenderTarget) -> bool {
+        match self.passes.iter_mut().find(|p| p.name == name) {
+            Some(pass) => {
+                pass.output = output;
+                self.order = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Makes the pass `name` run after the passes drawing into `input`,
+    /// e.g. the scene sampling an offscreen target, returns whether the
+    /// pass exists.
+    pub fn add_input(&mut self, name: &str, input: RenderTarget) -> bool {
+        match self.passes.iter_mut().find(|p| p.name == name) {
+            Some(pass) => {
+                pass.inputs.push(input);
+                self.order = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Undoes `add_input`, returns whether the pass read `input`.
+    pub fn remove_input(&mut self, name: &str, input: &RenderTarget) -> bool {
+        let pass = match self.passes.iter_mut().find(|p| p.name == name) {
+            Some(pass) => pass,
+            None => return false,
+        };
+        match pass.inputs.iter().position(|i| i.id() == input.id()) {
+            Some(index) => {
+                pass.inputs.remove(index);
+                self.order = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the sample count of the pass `name`, 0 or 1 disabling
+    /// multisampling, returns whether the pass can be multisampled.
+    pub fn set_samples(&mut self, name: &str, samples: u32) -> bool {
+        match self
+            .passes
+            .iter_mut()
+            .find(|p| p.name == name)
+            .and_then(|p| p.multisample.as_mut())
+        {
+            Some(target) => {
+                target.samples = samples;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sample count the pass `name` was last drawn with, `None` if it isn't
+    /// multisampled.
+    pub fn samples(&self, name: &str) -> Option<u32> {
+        self.passes
+            .iter()
+            .find(|p| p.name == name)
+            .and_then(|p| p.multisample.as_ref())
+            .filter(|target| target.enabled())
+            .and_then(MultisampleFramebuffer::effective_samples)
+    }
+
+    /// Pass names in execution order.
+    pub fn pass_names(&mut self) -> Vec<&str> {
+        let order = self.order().to_vec();
+        order
+            .into_iter()
+            .map(|index| self.passes[index].name.as_ref())
+            .collect()
+    }
+
+    fn order(&mut self) -> &[usize] {
+        if self.order.is_none() {
+            let ids = self
+                .passes
+                .iter()
+                .map(|pass| {
+                    let inputs = pass.inputs.iter().map(RenderTarget::id).collect::<Vec<_>>();
+                    (inputs, pass.output.id())
+                })
+                .collect::<Vec<_>>();
+            let order = topological_order(&ids).unwrap_or_else(|index| {
+                tracing::warn!(
+                    "render pass {} depends on a cycle, the passes are executed in insertion order",
+                    self.passes[index].name
+                );
+                (0..self.passes.len()).collect()
+            });
+            // forget the sizes of removed targets
+            self.sizes
+                .retain(|id, _| ids.iter().any(|(_, output)| output.as_ref() == Some(id)));
+            self.order = Some(order);
+        }
+        self.order.as_deref().unwrap_or_default()
+    }
+}
+
+/// Kahn's algorithm, ties are broken by insertion order. `passes` are the
+/// inputs and output of every pass. Returns a pass blocked by a cycle on
+/// failure.
+fn topological_order<K: PartialEq>(passes: &[(Vec<K>, K)]) -> Result<Vec<usize>, usize> {
+    let depends = |pass: usize, on: usize| {
+        let (inputs, output) = &passes[pass];
+        let other = &passes[on].1;
+        pass != on && (inputs.contains(other) || (on < pass && output == other))
+    };
+    let mut dependencies = (0..passes.len())
+        .map(|pass| (0..passes.len()).filter(|&on| depends(pass, on)).count())
+        .collect::<Vec<_>>();
+    let mut done = vec![false; passes.len()];
+    let mut order = Vec::with_capacity(passes.len());
+    while order.len() < passes.len() {
+        let next = (0..passes.len())
+            .find(|&pass| !done[pass] && dependencies[pass] == 0)
+            .ok_or_else(|| done.iter().position(|done| !done).unwrap_or_default())?;
+        done[next] = true;
+        order.push(next);
+        for pass in 0..passes.len() {
+            if !done[pass] && depends(pass, next) {
+                dependencies[pass] -= 1;
+            }
+        }
+    }
+    Ok(order)
+}
+
+impl DrawContext {
+    /// Executes every pass of the render graph, then binds the screen back.
+    pub fn render(&mut self, root_scene: Option<&RootScene>) {
+        let mut graph = mem::replace(&mut self.render_graph, RenderGraph::empty());
+        let order = graph.order().to_vec();
+        let RenderGraph { passes, sizes, .. } = &mut graph;
+        self.upload_frame_data(true);
+        self.begin_gpu_frame();
+        for index in order {
+            let pass = &mut passes[index];
+            let name = pass.name.clone();
+            self.debug_group(&name, |ctx| ctx.render_pass(pass, sizes, root_scene));
+        }
+        self.end_gpu_frame();
+        Framebuffer::unbind_static();
+        // passes added while rendering
+        let added = mem::replace(&mut self.render_graph, graph);
+        added
+            .passes
+            .into_iter()
+            .for_each(|pass| self.render_graph.add_pass(pass));
+    }
+
+    /// Snapshot of the render graph with the latest GPU timings, see
+    /// `FrameGraph::save`.
+    pub fn frame_graph(&mut self) -> FrameGraph {
+        let order = self.render_graph.order().to_vec();
+        let mut graph = FrameGraph::default();
+        for index in order {
+            let pass = &self.render_graph.passes[index];
+            let inputs = pass
+                .inputs
+                .iter()
+                .map(|input| graph.target(&input.name(self)))
+                .collect();
+            let output = graph.target(&pass.output.name(self));
+            graph.targets[output].writers += 1;
+            graph.passes.push(FrameGraphPass {
+                name: pass.name.to_string(),
+                inputs,
+                output,
+                samples: self.render_graph.samples(&pass.name),
+                gpu_time: self.gpu_timer.pass_time(&pass.name),
+            });
+        }
+        graph
+    }
+
+    fn render_pass(
+        &mut self,
+        pass: &mut RenderPass,
+        sizes: &mut HashMap<Uid, PhysicalSize<u32>>,
+        root_scene: Option<&RootScene>,
+    ) {
+        if pass.output.bind(self, sizes).log_warn().is_none() {
+            return;
+        }
+        let timer = self.begin_pass_timer(&pass.name);
+        let size = PhysicalSize::new(
+            self.display_size.width.get(),
+            self.display_size.height.get(),
+        );
+        // falls back to drawing into the output directly
+        let multisample = pass
+            .multisample
+            .as_mut()
+            .filter(|target| target.enabled())
+            .and_then(|target| {
+                target.resize_in_server(self, size).log_warn()?;
+                target.framebuffer.get(self).bind();
+                Some(&*target)
+            });
+        (pass.callback)(self, root_scene);
+        debug_assert!(self.clip_stack.is_empty(), "unbalanced push_clip");
+        // clips don't leak into the next pass
+        self.clip_stack.clear();
+        self.apply_clip(None);
+        if let Some(target) = multisample {
+            target.resolve(self, pass.output.gl_framebuffer(self));
+        }
+        self.end_pass_timer(timer);
+    }
+}
+
+#[test]
+fn test() {
+    // post reads blur, which reads scene, ui draws over post
+    let passes = vec![
+        (vec![Some(2)], None),
+        (vec![], Some(1)),
+        (vec![Some(1)], Some(2)),
+        (vec![], None),
+    ];
+    assert_eq!(topological_order(&passes), Ok(vec![1, 2, 0, 3]));
+    let cycle = vec![(vec![1], 0), (vec![0], 1), (vec![], 2)];
+    assert!(topological_order(&cycle).is_err());
+    assert_eq!(topological_order::<u8>(&[]), Ok(vec![]));
+}
@@ -0,0 +1,246 @@
+use std::{mem, path::Path};
+
+use anyhow::Context;
+use gl::types::{GLint, GLuint};
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{query::QueryReturn, server::draw},
+    utils::error::ResultExt,
+};
+
+use super::{
+    context::DrawContext,
+    wrappers::{
+        buffer::{BufferHandle, BufferTarget},
+        fence::Fence,
+        framebuffer::DefaultTextureFramebuffer,
+        texture::ImageData,
+    },
+};
+
+/// `capture_frame` queries, answered with frames read back into a pixel
+/// pack buffer, so that the draw server doesn't wait for the GPU to finish
+/// the frame before carrying on.
+pub struct FrameCaptures {
+    requested: Vec<QueryReturn<ImageData>>,
+    /// one readback at a time, later requests get a later frame
+    in_flight: Option<Readback>,
+    pixels: BufferHandle,
+    /// single sample copy of multisampled framebuffers, which can't be read
+    /// from directly
+    resolved: DefaultTextureFramebuffer,
+}
+
+struct Readback {
+    size: PhysicalSize<u32>,
+    fence: Fence,
+    requests: Vec<QueryReturn<ImageData>>,
+}
+
+impl FrameCaptures {
+    pub fn new(draw: &mut draw::ServerChannel) -> anyhow::Result<Self> {
+        Ok(Self {
+            requested: Vec::new(),
+            in_flight: None,
+            pixels: BufferHandle::new(draw, "frame readback", BufferTarget::PixelPackBuffer)?,
+            resolved: DefaultTextureFramebuffer::new(draw, "frame readback resolve framebuffer")?,
+        })
+    }
+
+    pub fn push(&mut self, ret: QueryReturn<ImageData>) {
+        self.requested.push(ret);
+    }
+
+    /// Whether the next frame will be read back.
+    pub fn is_requested(&self) -> bool {
+        !self.requested.is_empty() && self.in_flight.is_none()
+    }
+
+    /// Drops the requests, the fence has to be deleted while the context is
+    /// still current.
+    pub fn clear(&mut self) {
+        self.requested.clear();
+        self.in_flight = None;
+    }
+}
+
+impl DrawContext {
+    /// Starts reading back the frame just rendered if it was requested,
+    /// before it is presented.
+    pub fn start_captures(&mut self) {
+        if !self.frame_captures.is_requested() {
+            return;
+        }
+        let size = PhysicalSize::new(
+            self.display_size.width.get(),
+            self.display_size.height.get(),
+        );
+        let started = self
+            .readable_framebuffer(0, size)
+            .and_then(|framebuffer| {
+                let pixels = self
+                    .frame_captures
+                    .pixels
+                    .try_get(self)
+                    .context("frame readback buffer was not created")?;
+                pixels.allocate(size.width as usize * size.height as usize * 4)?;
+                pixels.bind();
+                // offset 0 in the bound buffer
+                read_pixels(framebuffer, size, std::ptr::null_mut());
+                pixels.unbind();
+                Ok(())
+            })
+            .context("unable to start frame readback")
+            .log_warn();
+        // failed requests are dropped, which fails their queries
+        let requests = mem::take(&mut self.frame_captures.requested);
+        if started.is_some() {
+            self.frame_captures.in_flight = Some(Readback {
+                size,
+                fence: Fence::insert(),
+                requests,
+            });
+        }
+    }
+
+    /// `framebuffer` if it can be read from, otherwise the single sample
+    /// copy it is resolved into. Leaves `framebuffer` bound for drawing.
+    fn readable_framebuffer(
+        &mut self,
+        framebuffer: GLuint,
+        size: PhysicalSize<u32>,
+    ) -> anyhow::Result<GLuint> {
+        let mut sample_buffers: GLint = 0;
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+            gl::GetIntegerv(gl::SAMPLE_BUFFERS, &mut sample_buffers);
+        }
+        if sample_buffers == 0 {
+            return Ok(framebuffer);
+        }
+
+        let resolved = self.frame_captures.resolved.clone();
+        resolved
+            .resize_in_server(self, size)
+            .context("unable to allocate frame readback resolve framebuffer")?;
+        let resolved = *resolved.framebuffer.get(self);
+        let (width, height) = (size.width as GLint, size.height as GLint);
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, resolved);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, framebuffer);
+        }
+        Ok(resolved)
+    }
+
+    /// Answers the captures whose readback the GPU is done with.
+    pub fn poll_captures(&mut self) {
+        let readback = match self.frame_captures.in_flight.take() {
+            Some(readback) if readback.fence.is_signaled() => readback,
+            readback => {
+                self.frame_captures.in_flight = readback;
+                return;
+            }
+        };
+        let size = readback.size;
+        let image = self
+            .frame_captures
+            .pixels
+            .try_get(self)
+            .context("frame readback buffer was deleted")
+            .and_then(|pixels| pixels.read_range(0, size.width as usize * size.height as usize * 4))
+            .map(|pixels| flip_opaque(size, pixels))
+            .context("unable to read back captured frame")
+            .log_warn();
+        let image = match image {
+            Some(image) => image,
+            None => return,
+        };
+        for ret in readback.requests {
+            ret.send(image.clone(), &self.base.proxy)
+                .context("unable to return captured frame")
+                .log_warn();
+        }
+    }
+}
+
+fn read_pixels(framebuffer: GLuint, size: PhysicalSize<u32>, pixels: *mut u8) {
+    unsafe {
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            0,
+            0,
+            size.width as _,
+            size.height as _,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels as *mut _,
+        );
+    }
+}
+
+/// Rows from top to bottom and alpha made opaque, as the frame should look
+/// in an image viewer.
+fn flip_opaque(size: PhysicalSize<u32>, pixels: Vec<u8>) -> ImageData {
+    let row = size.width as usize * 4;
+    // GL rows go bottom-up
+    let mut flipped = Vec::with_capacity(pixels.len());
+    for line in pixels.chunks_exact(row.max(1)).rev() {
+        flipped.extend(
+            line.chunks_exact(4)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255]),
+        );
+    }
+    ImageData {
+        size,
+        pixels: flipped,
+        options: Default::default(),
+    }
+}
+
+/// Reads the framebuffer being drawn into right away, e.g. the multisampled
+/// scene target, see `flip_opaque`. Stalls until the GPU is done drawing,
+/// unlike `capture_frame`, but can read it in the middle of a frame.
+pub fn read_back_buffer(ctx: &mut DrawContext) -> anyhow::Result<ImageData> {
+    let size = PhysicalSize::new(ctx.display_size.width.get(), ctx.display_size.height.get());
+    let mut framebuffer: GLint = 0;
+    unsafe { gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut framebuffer) };
+    let framebuffer = ctx.readable_framebuffer(framebuffer as GLuint, size)?;
+    let mut pixels = vec![0u8; size.width as usize * size.height as usize * 4];
+    read_pixels(framebuffer, size, pixels.as_mut_ptr());
+    Ok(flip_opaque(size, pixels))
+}
+
+impl ImageData {
+    pub fn into_rgba_image(self) -> anyhow::Result<RgbaImage> {
+        RgbaImage::from_raw(self.size.width, self.size.height, self.pixels)
+            .context("pixel data doesn't match the image size")
+    }
+}
+
+/// Writes `image` to `path` as a PNG file, creating its directory if needed.
+pub fn save_png(image: ImageData, path: &Path) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("unable to create {}", dir.display()))?;
+    }
+    image
+        .into_rgba_image()?
+        .save(path)
+        .with_context(|| format!("unable to write {}", path.display()))
+}
@@ -0,0 +1,227 @@
+use std::{
+    borrow::Cow,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    enclose,
+    events::{
+        error::{GameError, Severity, Subsystem},
+        GameUserEvent,
+    },
+    exec::server::{
+        draw::{self, ServerSendChannelExt},
+        ServerSendChannel,
+    },
+    utils::{error::ResultExt, mutex::Mutex},
+};
+
+use super::wrappers::{
+    shader::{ProgramHandle, ProgramTrait},
+    GLGfxHandle, GLGfxHandleInner,
+};
+
+/// Source files of a program loaded by the `ShaderWatcher`.
+#[derive(Clone, Debug)]
+pub struct ShaderFiles {
+    pub vertex: PathBuf,
+    pub fragment: PathBuf,
+}
+
+/// Completes a single fragment shader file into a program, e.g. a shader
+/// toy only defining `mainImage`.
+#[derive(Clone, Copy, Debug)]
+pub struct FragmentTemplate {
+    pub vertex: &'static str,
+    /// prepended to the file, e.g. the version and the uniforms it reads
+    pub header: &'static str,
+    /// appended to the file, e.g. the `main` calling into it
+    pub footer: &'static str,
+    /// used in place of the file while it doesn't exist
+    pub fallback: &'static str,
+}
+
+#[derive(Clone, Debug)]
+enum Sources {
+    Files(ShaderFiles),
+    Fragment(PathBuf, FragmentTemplate),
+}
+
+struct WatchedProgram {
+    name: Cow<'static, str>,
+    handle: Weak<GLGfxHandleInner<ProgramTrait>>,
+    sources: Sources,
+    modified: [Option<SystemTime>; 2],
+}
+
+/// Programs loaded from files, recompiled on the draw server whenever one
+/// of their sources changes. Polled from a blocking task by the main
+/// context, a program stops being watched once its handle is dropped.
+#[derive(Clone, Default)]
+pub struct ShaderWatcher(Arc<Mutex<Vec<WatchedProgram>>>);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn reload_error(error: anyhow::Error) -> GameUserEvent {
+    // the previous program keeps being used, nothing is broken yet
+    GameUserEvent::Error(
+        GameError::new(Subsystem::Draw, "draw.reload_program", error).severity(Severity::Warning),
+    )
+}
+
+impl ShaderFiles {
+    pub fn new(vertex: impl Into<PathBuf>, fragment: impl Into<PathBuf>) -> Self {
+        Self {
+            vertex: vertex.into(),
+            fragment: fragment.into(),
+        }
+    }
+
+    fn modified(&self) -> [Option<SystemTime>; 2] {
+        [modified(&self.vertex), modified(&self.fragment)]
+    }
+
+    fn read(&self) -> anyhow::Result<(String, String)> {
+        let read = |path: &Path| {
+            std::fs::read_to_string(path)
+                .with_context(|| format!("unable to read shader {}", path.display()))
+        };
+        Ok((read(&self.vertex)?, read(&self.fragment)?))
+    }
+}
+
+impl Sources {
+    fn modified(&self) -> [Option<SystemTime>; 2] {
+        match self {
+            Self::Files(files) => files.modified(),
+            Self::Fragment(path, _) => [None, modified(path)],
+        }
+    }
+
+    fn read(&self) -> anyhow::Result<(String, String)> {
+        match self {
+            Self::Files(files) => files.read(),
+            Self::Fragment(path, template) => {
+                let source = match std::fs::read_to_string(path) {
+                    Ok(source) => source,
+                    Err(e) if e.kind() == ErrorKind::NotFound => template.fallback.to_owned(),
+                    Err(e) => {
+                        return Err(e)
+                            .with_context(|| format!("unable to read shader {}", path.display()))
+                    }
+                };
+                let fragment = format!("{}{}{}", template.header, source, template.footer);
+                Ok((template.vertex.to_owned(), fragment))
+            }
+        }
+    }
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a program from `files` with `HandleContainer::create_vf_program`
+    /// and starts watching them.
+    pub fn load_vf(
+        &self,
+        draw: &mut draw::ServerChannel,
+        name: impl Into<Cow<'static, str>>,
+        files: ShaderFiles,
+    ) -> anyhow::Result<ProgramHandle> {
+        self.load(draw, name.into(), Sources::Files(files))
+    }
+
+    /// Creates a program from the fragment shader at `path` completed by
+    /// `template`, the file may be created, edited or removed afterwards.
+    pub fn load_fragment(
+        &self,
+        draw: &mut draw::ServerChannel,
+        name: impl Into<Cow<'static, str>>,
+        path: impl Into<PathBuf>,
+        template: FragmentTemplate,
+    ) -> anyhow::Result<ProgramHandle> {
+        self.load(draw, name.into(), Sources::Fragment(path.into(), template))
+    }
+
+    fn load(
+        &self,
+        draw: &mut draw::ServerChannel,
+        name: Cow<'static, str>,
+        sources: Sources,
+    ) -> anyhow::Result<ProgramHandle> {
+        let modified = sources.modified();
+        let (vertex, fragment) = sources.read()?;
+        let handle = unsafe { ProgramHandle::new_uninit(draw) };
+        draw.execute_draw_event(enclose!((handle, name) move |context, _| {
+            context
+                .handles
+                .create_vf_program(name, &handle, &vertex, &fragment)
+                .err()
+                .map(|e| GameUserEvent::error(Subsystem::Draw, "draw.create_program", e))
+        }))
+        .context("unable to send program creation to draw server")?;
+
+        self.0.lock().push(WatchedProgram {
+            name,
+            handle: Arc::downgrade(&handle.0),
+            sources,
+            modified,
+        });
+        Ok(handle)
+    }
+
+    /// Reloads the programs whose sources were modified since the last
+    /// poll, errors are sent to the main thread.
+    pub fn poll(
+        &self,
+        draw: &ServerSendChannel<draw::RecvMsg>,
+        proxy: &EventLoopProxy<GameUserEvent>,
+    ) {
+        let mut programs = self.0.lock();
+        programs.retain(|program| program.handle.strong_count() > 0);
+        for program in programs.iter_mut() {
+            let modified = program.sources.modified();
+            if modified == program.modified {
+                continue;
+            }
+            program.modified = modified;
+            if let Err(e) = program.reload(draw) {
+                proxy.send_event(reload_error(e)).log_warn();
+            }
+        }
+    }
+}
+
+impl WatchedProgram {
+    fn reload(&self, draw: &ServerSendChannel<draw::RecvMsg>) -> anyhow::Result<()> {
+        let handle = match self.handle.upgrade() {
+            Some(handle) => GLGfxHandle(handle),
+            None => return Ok(()),
+        };
+        let (vertex, fragment) = self
+            .sources
+            .read()
+            .with_context(|| format!("unable to reload {}", self.name))?;
+        tracing::info!("reloading {}", self.name);
+        let name = self.name.clone();
+        draw.execute_draw_event(move |context, _| {
+            context
+                .handles
+                .recreate_vf_program(name.clone(), &handle, &vertex, &fragment)
+                .with_context(|| format!("unable to reload {name}"))
+                .err()
+                .map(reload_error)
+        })
+        .context("unable to send program reload to draw server")
+    }
+}
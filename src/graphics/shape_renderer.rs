@@ -0,0 +1,515 @@
+use std::{cell::Cell, f32::consts::PI, mem::size_of, ptr};
+
+use anyhow::Context;
+use bytemuck::{Pod, Zeroable};
+use gl::types::GLsizei;
+use glam::{Affine2, Vec2, Vec4};
+
+use crate::{exec::server::draw, utils::error::ResultExt};
+
+use super::{
+    context::DrawContext,
+    wrappers::{
+        buffer::{BufferHandle, BufferTarget},
+        shader::ProgramHandle,
+        vertex_array::{VertexArrayHandle, VertexAttrib},
+    },
+};
+
+mod shader {
+    pub const VERTEX: &str = r#"
+    #version 300 es
+
+    layout(location = 0) in vec2 position;
+    layout(location = 1) in vec4 color;
+
+    out vec4 vf_color;
+
+    layout(std140) uniform FrameData {
+        mat4 ui_projection;
+        vec2 ui_size;
+        vec2 resolution;
+        float time;
+    };
+
+    void main() {
+        gl_Position = ui_projection * vec4(position, 0.0, 1.0);
+        vf_color = color;
+    }
+    "#;
+
+    pub const FRAGMENT: &str = r#"
+    #version 300 es
+    precision mediump float;
+
+    in vec4 vf_color;
+
+    out vec4 color;
+
+    void main() {
+        color = vf_color;
+    }
+    "#;
+}
+
+/// Largest distance between an arc and its segments, in pixels.
+const ARC_TOLERANCE: f32 = 0.25;
+const MAX_ARC_SEGMENTS: usize = 256;
+/// Limits the length of miters at sharp corners, in fringe widths.
+const MAX_MITER_SCALE: f32 = 4.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct ShapeVertex {
+    position: Vec2,
+    color: Vec4,
+}
+
+// SAFETY: `repr(C)`, made of `Pod` fields with no padding in between
+unsafe impl Zeroable for ShapeVertex {}
+unsafe impl Pod for ShapeVertex {}
+
+/// Tessellates lines, arcs and polygons into triangles drawn with a single
+/// draw call per flush.
+///
+/// Edges are anti-aliased with a one pixel wide fringe fading to
+/// transparent, so no multisampling is needed. Shapes are drawn in
+/// submission order.
+pub struct ShapeRenderer {
+    program: ProgramHandle,
+    vertex_array: VertexArrayHandle,
+    vertex_buffer: BufferHandle,
+    index_buffer: BufferHandle,
+    configured: Cell<bool>,
+    vertices: Vec<ShapeVertex>,
+    indices: Vec<u32>,
+}
+
+impl ShapeRenderer {
+    pub fn new(draw: &mut draw::ServerChannel) -> anyhow::Result<Self> {
+        Ok(Self {
+            program: ProgramHandle::new_vf(
+                draw,
+                "shape renderer shader program",
+                shader::VERTEX,
+                shader::FRAGMENT,
+            )
+            .context("unable to create shape renderer program")?,
+            vertex_array: VertexArrayHandle::new(draw, "shape vertex array")
+                .context("unable to create shape vertex array")?,
+            vertex_buffer: BufferHandle::new(
+                draw,
+                "shape vertex buffer",
+                BufferTarget::ArrayBuffer,
+            )
+            .context("unable to create shape vertex buffer")?,
+            index_buffer: BufferHandle::new(
+                draw,
+                "shape index buffer",
+                BufferTarget::ElementArrayBuffer,
+            )
+            .context("unable to create shape index buffer")?,
+            configured: Cell::new(false),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        })
+    }
+
+    /// Triangles waiting for the next flush.
+    pub fn len(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    fn push_vertex(&mut self, position: Vec2, color: Vec4) {
+        self.vertices.push(ShapeVertex { position, color });
+    }
+
+    fn push_quad(&mut self, [a, b, c, d]: [u32; 4]) {
+        self.indices.extend_from_slice(&[a, b, c, a, c, d]);
+    }
+
+    /// Fills the simple polygon `points`, `aa` is the fringe width.
+    fn fill(&mut self, points: &[Vec2], color: Vec4, aa: f32) {
+        let points = dedup(points, true);
+        if points.len() < 3 {
+            return;
+        }
+        let orientation = signed_area(&points).signum();
+        if orientation == 0.0 {
+            return;
+        }
+        let normals = edge_normals(&points, true, orientation);
+        let transparent = color * Vec4::new(1.0, 1.0, 1.0, 0.0);
+        let first = self.vertices.len() as u32;
+        for (i, &point) in points.iter().enumerate() {
+            let offset = miter(normals[(i + normals.len() - 1) % normals.len()], normals[i]);
+            self.push_vertex(point - offset * aa * 0.5, color);
+            self.push_vertex(point + offset * aa * 0.5, transparent);
+        }
+        let inner = |i: usize| first + 2 * i as u32;
+        let outer = |i: usize| first + 2 * i as u32 + 1;
+        for [a, b, c] in triangulate(&points) {
+            self.indices
+                .extend_from_slice(&[inner(a), inner(b), inner(c)]);
+        }
+        for i in 0..points.len() {
+            let j = (i + 1) % points.len();
+            self.push_quad([inner(i), inner(j), outer(j), outer(i)]);
+        }
+    }
+
+    /// Strokes the polyline `points` with butt caps.
+    fn stroke(&mut self, points: &[Vec2], closed: bool, thickness: f32, color: Vec4, aa: f32) {
+        let points = dedup(points, closed);
+        if points.len() < 2 {
+            return;
+        }
+        // lines thinner than the fringe fade out instead of getting thinner
+        let color = color * Vec4::new(1.0, 1.0, 1.0, (thickness / aa).min(1.0));
+        let transparent = color * Vec4::new(1.0, 1.0, 1.0, 0.0);
+        let core = (thickness - aa).max(0.0) * 0.5;
+        let normals = edge_normals(&points, closed, 1.0);
+        let first = self.vertices.len() as u32;
+        for (i, &point) in points.iter().enumerate() {
+            let offset = match (closed, i) {
+                (false, 0) => normals[0],
+                (false, i) if i == points.len() - 1 => normals[i - 1],
+                _ => miter(normals[(i + normals.len() - 1) % normals.len()], normals[i]),
+            };
+            self.push_vertex(point + offset * (core + aa), transparent);
+            self.push_vertex(point + offset * core, color);
+            self.push_vertex(point - offset * core, color);
+            self.push_vertex(point - offset * (core + aa), transparent);
+        }
+        let vertex = |point: usize, k: u32| first + 4 * point as u32 + k;
+        for i in 0..normals.len() {
+            let j = (i + 1) % points.len();
+            for k in 0..3 {
+                self.push_quad([
+                    vertex(i, k),
+                    vertex(j, k),
+                    vertex(j, k + 1),
+                    vertex(i, k + 1),
+                ]);
+            }
+        }
+    }
+
+    fn configure(&self, context: &DrawContext) -> anyhow::Result<()> {
+        let vertex_array = self
+            .vertex_array
+            .try_get(context)
+            .context("shape vertex array was not created")?;
+        let vertex_buffer = self
+            .vertex_buffer
+            .try_get(context)
+            .context("shape vertex buffer was not created")?;
+        let program = self
+            .program
+            .try_get(context)
+            .context("shape renderer program was not created")?;
+        context.frame_uniforms.bind_block(*program);
+        vertex_array.set_attributes(
+            &vertex_buffer,
+            size_of::<ShapeVertex>(),
+            &[VertexAttrib::new(0, 2, 0), VertexAttrib::new(1, 4, 8)],
+        );
+        vertex_array.unbind();
+        self.configured.set(true);
+        Ok(())
+    }
+
+    /// Draws the tessellated shapes, returns the number of triangles.
+    fn flush(
+        &self,
+        context: &DrawContext,
+        vertices: Vec<ShapeVertex>,
+        indices: Vec<u32>,
+    ) -> anyhow::Result<usize> {
+        if indices.is_empty() {
+            return Ok(0);
+        }
+        if !self.configured.get() {
+            self.configure(context)?;
+        }
+        let vertex_array = self.vertex_array.get(context);
+        let index_buffer = self.index_buffer.get(context);
+        let program = self.program.get(context);
+        // the element array binding is part of the vertex array state, don't
+        // overwrite the one of whatever vertex array is bound
+        vertex_array.unbind();
+        self.vertex_buffer.get(context).stream_slice(&vertices)?;
+        index_buffer.stream_slice(&indices)?;
+        vertex_array.bind();
+        index_buffer.bind();
+        context.upload_frame_data(false);
+        unsafe {
+            gl::UseProgram(*program);
+            gl::DrawElements(
+                gl::TRIANGLES,
+                indices.len() as GLsizei,
+                gl::UNSIGNED_INT,
+                ptr::null(),
+            );
+        }
+        vertex_array.unbind();
+        Ok(indices.len() / 3)
+    }
+}
+
+/// Drops repeated points, including the last one if it closes the path.
+fn dedup(points: &[Vec2], closed: bool) -> Vec<Vec2> {
+    let mut result: Vec<Vec2> = Vec::with_capacity(points.len());
+    for &point in points {
+        if result
+            .last()
+            .map_or(true, |last| last.distance_squared(point) > f32::EPSILON)
+        {
+            result.push(point);
+        }
+    }
+    if closed
+        && result.len() > 1
+        && result[0].distance_squared(result[result.len() - 1]) <= f32::EPSILON
+    {
+        result.pop();
+    }
+    result
+}
+
+/// Positive if `points` turn from +x to +y.
+fn signed_area(points: &[Vec2]) -> f32 {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum::<f32>()
+        * 0.5
+}
+
+/// Unit normals of the edges, pointing out of the polygon if `orientation`
+/// is the sign of its area.
+fn edge_normals(points: &[Vec2], closed: bool, orientation: f32) -> Vec<Vec2> {
+    let edges = if closed {
+        points.len()
+    } else {
+        points.len() - 1
+    };
+    (0..edges)
+        .map(|i| {
+            let edge = (points[(i + 1) % points.len()] - points[i]).normalize_or_zero();
+            Vec2::new(edge.y, -edge.x) * orientation
+        })
+        .collect()
+}
+
+/// Offset of a corner between edges of normals `a` and `b`, such that the
+/// offset edges stay parallel to the original ones.
+fn miter(a: Vec2, b: Vec2) -> Vec2 {
+    let average = (a + b) * 0.5;
+    let length_squared = average.length_squared();
+    if length_squared < f32::EPSILON {
+        return a;
+    }
+    average / length_squared.max(1.0 / MAX_MITER_SCALE)
+}
+
+/// Ear clipping, the polygon must not intersect itself. Falls back to a fan
+/// if no ear is found.
+fn triangulate(points: &[Vec2]) -> Vec<[usize; 3]> {
+    let orientation = signed_area(points).signum();
+    let mut remaining = (0..points.len()).collect::<Vec<_>>();
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let corner = |i: usize| {
+            [
+                remaining[(i + count - 1) % count],
+                remaining[i],
+                remaining[(i + 1) % count],
+            ]
+        };
+        let ear = (0..count).find(|&i| {
+            let [a, b, c] = corner(i).map(|k| points[k]);
+            if (b - a).perp_dot(c - b) * orientation <= 0.0 {
+                return false;
+            }
+            !remaining
+                .iter()
+                .filter(|k| !corner(i).contains(k))
+                .any(|&k| in_triangle(points[k], [a, b, c], orientation))
+        });
+        match ear {
+            Some(i) => {
+                triangles.push(corner(i));
+                remaining.remove(i);
+            }
+            None => break,
+        }
+    }
+    for i in 1..remaining.len().saturating_sub(1) {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+fn in_triangle(point: Vec2, [a, b, c]: [Vec2; 3], orientation: f32) -> bool {
+    [(a, b), (b, c), (c, a)]
+        .into_iter()
+        .all(|(from, to)| (to - from).perp_dot(point - from) * orientation >= 0.0)
+}
+
+/// Points of an arc from `start` to `end` radians, clockwise on screen.
+fn arc_points(center: Vec2, radius: f32, start: f32, end: f32, aa: f32) -> Vec<Vec2> {
+    // `aa` is one pixel
+    let radius_px = radius / aa;
+    let step = if radius_px > ARC_TOLERANCE {
+        2.0 * (1.0 - ARC_TOLERANCE / radius_px).acos()
+    } else {
+        PI
+    };
+    let segments = ((end - start).abs() / step)
+        .ceil()
+        .clamp(1.0, MAX_ARC_SEGMENTS as f32) as usize;
+    (0..=segments)
+        .map(|i| {
+            let angle = start + (end - start) * i as f32 / segments as f32;
+            center + Vec2::from_angle(angle) * radius
+        })
+        .collect()
+}
+
+impl DrawContext {
+    fn shape_points(&self, points: impl IntoIterator<Item = Vec2>) -> Vec<Vec2> {
+        let transform: Affine2 = self.transform_stack.current();
+        points
+            .into_iter()
+            .map(|point| transform.transform_point2(point))
+            .collect()
+    }
+
+    /// Width of a pixel in UI units, the width of the anti-aliasing fringe.
+    fn shape_aa(&self) -> f32 {
+        1.0 / self.ui_scale()
+    }
+
+    pub fn draw_line(&mut self, from: Vec2, to: Vec2, thickness: f32, color: Vec4) {
+        self.draw_polyline(&[from, to], false, thickness, color);
+    }
+
+    pub fn draw_polyline(&mut self, points: &[Vec2], closed: bool, thickness: f32, color: Vec4) {
+        let points = self.shape_points(points.iter().copied());
+        let aa = self.shape_aa();
+        self.shapes.stroke(&points, closed, thickness, color, aa);
+    }
+
+    pub fn draw_circle(&mut self, center: Vec2, radius: f32, color: Vec4) {
+        let aa = self.shape_aa();
+        let points = self.shape_points(arc_points(center, radius, 0.0, 2.0 * PI, aa));
+        self.shapes.fill(&points, color, aa);
+    }
+
+    /// Strokes an arc from `start` to `end` radians, clockwise on screen
+    /// starting from +x.
+    pub fn draw_arc(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        [start, end]: [f32; 2],
+        thickness: f32,
+        color: Vec4,
+    ) {
+        let aa = self.shape_aa();
+        let points = self.shape_points(arc_points(center, radius, start, end, aa));
+        let closed = (end - start).abs() >= 2.0 * PI;
+        self.shapes.stroke(&points, closed, thickness, color, aa);
+    }
+
+    pub fn draw_rounded_rect(&mut self, min: Vec2, max: Vec2, radius: f32, color: Vec4) {
+        let aa = self.shape_aa();
+        let radius = radius.min((max.x - min.x) * 0.5).min((max.y - min.y) * 0.5);
+        let points = if radius <= 0.0 {
+            vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+        } else {
+            [
+                (Vec2::new(min.x + radius, min.y + radius), PI),
+                (Vec2::new(max.x - radius, min.y + radius), 1.5 * PI),
+                (Vec2::new(max.x - radius, max.y - radius), 0.0),
+                (Vec2::new(min.x + radius, max.y - radius), 0.5 * PI),
+            ]
+            .into_iter()
+            .flat_map(|(center, start)| arc_points(center, radius, start, start + 0.5 * PI, aa))
+            .collect()
+        };
+        let points = self.shape_points(points);
+        self.shapes.fill(&points, color, aa);
+    }
+
+    /// Fills a polygon, which may be concave but must not intersect itself.
+    pub fn draw_polygon(&mut self, points: &[Vec2], color: Vec4) {
+        let points = self.shape_points(points.iter().copied());
+        let aa = self.shape_aa();
+        self.shapes.fill(&points, color, aa);
+    }
+
+    /// Fills `points`, in window UI units, without the anti-aliasing fringe,
+    /// e.g. to write clip masks into the stencil buffer.
+    pub fn draw_stencil_polygon(&mut self, points: &[Vec2]) {
+        self.shapes.fill(points, Vec4::ONE, 0.0);
+    }
+
+    /// Draws the queued shapes, returns the number of triangles.
+    pub fn flush_shapes(&mut self) -> usize {
+        let vertices = std::mem::take(&mut self.shapes.vertices);
+        let indices = std::mem::take(&mut self.shapes.indices);
+        self.shapes
+            .flush(self, vertices, indices)
+            .context("unable to draw shapes")
+            .log_warn()
+            .unwrap_or_default()
+    }
+}
+
+#[test]
+fn test() {
+    // an L shape, concave at (1, 1)
+    let points = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(2.0, 0.0),
+        Vec2::new(2.0, 1.0),
+        Vec2::new(1.0, 1.0),
+        Vec2::new(1.0, 2.0),
+        Vec2::new(0.0, 2.0),
+    ];
+    let area = |triangles: &[[usize; 3]], points: &[Vec2]| {
+        triangles
+            .iter()
+            .map(|t| signed_area(&t.map(|i| points[i])).abs())
+            .sum::<f32>()
+    };
+    assert_eq!(signed_area(&points), 3.0);
+    let triangles = triangulate(&points);
+    assert_eq!(triangles.len(), 4);
+    assert_eq!(area(&triangles, &points), 3.0);
+    let reversed = points.iter().rev().copied().collect::<Vec<_>>();
+    assert_eq!(signed_area(&reversed), -3.0);
+    assert_eq!(area(&triangulate(&reversed), &reversed), 3.0);
+
+    let normals = edge_normals(&points, true, 1.0);
+    assert_eq!(normals[0], Vec2::new(0.0, -1.0));
+    assert_eq!(miter(normals[0], normals[1]), Vec2::new(1.0, -1.0));
+
+    assert_eq!(
+        dedup(&[Vec2::ZERO, Vec2::ZERO, Vec2::ONE, Vec2::ZERO], true).len(),
+        2
+    );
+    let circle = arc_points(Vec2::ZERO, 100.0, 0.0, 2.0 * PI, 1.0);
+    assert!(circle.len() > 16 && circle.len() <= MAX_ARC_SEGMENTS + 1);
+    assert!(circle
+        .iter()
+        .all(|point| (point.length() - 100.0).abs() < 1e-3));
+}
@@ -0,0 +1,653 @@
+use std::{cell::Cell, ffi::CStr, mem::size_of, ptr};
+
+use anyhow::Context;
+use bytemuck::{Pod, Zeroable};
+use gl::types::{GLsizei, GLuint};
+use glam::{Affine2, Mat3, Vec2, Vec4};
+
+use crate::{
+    exec::server::draw,
+    spatial::culling::VisibleSet,
+    ui::utils::geom::UIRect,
+    utils::{error::ResultExt, uid::Uid},
+};
+
+use super::{
+    context::DrawContext,
+    wrappers::{
+        buffer::{BufferHandle, BufferTarget},
+        shader::ProgramHandle,
+        vertex_array::{VertexArrayHandle, VertexAttrib},
+    },
+};
+
+mod shader {
+    pub const VERTEX: &str = r#"
+    #version 300 es
+
+    layout(location = 0) in vec2 position;
+    layout(location = 1) in vec2 tex_coords;
+    layout(location = 2) in vec4 color;
+
+    out vec2 vf_tex_coords;
+    out vec4 vf_color;
+
+    layout(std140) uniform FrameData {
+        mat4 ui_projection;
+        vec2 ui_size;
+        vec2 resolution;
+        float time;
+    };
+
+    void main() {
+        gl_Position = ui_projection * vec4(position, 0.0, 1.0);
+        vf_tex_coords = tex_coords;
+        vf_color = color;
+    }
+    "#;
+
+    pub const FRAGMENT: &str = r#"
+    #version 300 es
+    precision mediump float;
+
+    in vec2 vf_tex_coords;
+    in vec4 vf_color;
+
+    out vec4 color;
+
+    uniform sampler2D tex;
+
+    void main() {
+        color = texture(tex, vf_tex_coords) * vf_color;
+    }
+    "#;
+
+    /// The quads of the instances, with the fragment shader above.
+    pub const INSTANCED_VERTEX: &str = r#"
+    #version 300 es
+
+    layout(location = 0) in vec2 center;
+    layout(location = 1) in vec2 size;
+    layout(location = 2) in vec4 uv;
+    layout(location = 3) in vec4 color;
+    layout(location = 4) in float rotation;
+
+    out vec2 vf_tex_coords;
+    out vec4 vf_color;
+
+    layout(std140) uniform FrameData {
+        mat4 ui_projection;
+        vec2 ui_size;
+        vec2 resolution;
+        float time;
+    };
+
+    uniform mat3 transform;
+
+    const vec2 corners[4] = vec2[](
+        vec2(0.0, 0.0), vec2(1.0, 0.0),
+        vec2(0.0, 1.0), vec2(1.0, 1.0)
+    );
+
+    void main() {
+        vec2 corner = corners[gl_VertexID];
+        vec2 offset = (corner - 0.5) * size;
+        vec2 rotated = vec2(
+            cos(rotation) * offset.x - sin(rotation) * offset.y,
+            sin(rotation) * offset.x + cos(rotation) * offset.y
+        );
+        vec2 position = (transform * vec3(center + rotated, 1.0)).xy;
+        gl_Position = ui_projection * vec4(position, 0.0, 1.0);
+        vf_tex_coords = mix(uv.xy, uv.zw, corner);
+        vf_color = color;
+    }
+    "#;
+}
+
+/// Sprites drawn by a single draw call at most, so that the indices fit in
+/// 16 bits.
+pub const MAX_SPRITES_PER_DRAW: usize = 4096;
+
+/// A textured quad, in UI coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    pub center: Vec2,
+    pub size: Vec2,
+    /// top left and bottom right texture coordinates, e.g. the sub-rect of
+    /// the sprite in its atlas
+    pub uv: [Vec2; 2],
+    /// multiplied with the texture color
+    pub color: Vec4,
+    /// clockwise, in radians, around the center
+    pub rotation: f32,
+}
+
+/// Border widths of a nine-patch.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Margins {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+/// Borders of a nine-patch that keep their size: `source` in fractions of
+/// the texture region, `dest` in UI units. The edges stretch along their
+/// border, the center both ways.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NinePatchMargins {
+    pub source: Margins,
+    pub dest: Margins,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct SpriteVertex {
+    position: Vec2,
+    tex_coords: Vec2,
+    color: Vec4,
+}
+
+// SAFETY: `repr(C)`, made of `Pod` fields with no padding in between
+unsafe impl Zeroable for SpriteVertex {}
+unsafe impl Pod for SpriteVertex {}
+
+/// A `Sprite` as read by the instanced vertex shader.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct SpriteInstance {
+    center: Vec2,
+    size: Vec2,
+    uv: [Vec2; 2],
+    color: Vec4,
+    rotation: f32,
+    /// `Vec4` may be 16-byte aligned
+    _padding: [f32; 3],
+}
+
+// SAFETY: `repr(C)`, made of `Pod` fields with no padding in between
+unsafe impl Zeroable for SpriteInstance {}
+unsafe impl Pod for SpriteInstance {}
+
+struct SpriteBatch {
+    texture: GLuint,
+    vertices: Vec<SpriteVertex>,
+}
+
+/// Batches the sprites drawn during a frame by texture, each batch is
+/// streamed to the vertex buffer and drawn with one draw call.
+///
+/// Sprites of the same texture are drawn in submission order, there is no
+/// order between different textures, queue them with different sort keys
+/// in the `DrawQueue` if they overlap.
+pub struct SpriteRenderer {
+    program: ProgramHandle,
+    vertex_array: VertexArrayHandle,
+    vertex_buffer: BufferHandle,
+    index_buffer: BufferHandle,
+    instanced_program: ProgramHandle,
+    instanced_vertex_array: VertexArrayHandle,
+    instance_buffer: BufferHandle,
+    /// the vertex arrays are set up on their first draw, once the buffers
+    /// are created on the draw server
+    configured: Cell<bool>,
+    instanced_configured: Cell<bool>,
+    batches: Vec<SpriteBatch>,
+}
+
+impl Sprite {
+    pub fn new(center: Vec2, size: Vec2) -> Self {
+        Self {
+            center,
+            size,
+            uv: [Vec2::ZERO, Vec2::ONE],
+            color: Vec4::ONE,
+            rotation: 0.0,
+        }
+    }
+
+    pub fn uv(mut self, top_left: Vec2, bottom_right: Vec2) -> Self {
+        self.uv = [top_left, bottom_right];
+        self
+    }
+
+    pub fn color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Top left, top right, bottom left and bottom right vertices.
+    fn vertices(&self, transform: &Affine2) -> [SpriteVertex; 4] {
+        let half = self.size * 0.5;
+        let rotation = Vec2::from_angle(self.rotation);
+        let [uv0, uv1] = self.uv;
+        [
+            (Vec2::new(-half.x, -half.y), uv0),
+            (Vec2::new(half.x, -half.y), Vec2::new(uv1.x, uv0.y)),
+            (Vec2::new(-half.x, half.y), Vec2::new(uv0.x, uv1.y)),
+            (Vec2::new(half.x, half.y), uv1),
+        ]
+        .map(|(offset, tex_coords)| SpriteVertex {
+            position: transform.transform_point2(self.center + rotation.rotate(offset)),
+            tex_coords,
+            color: self.color,
+        })
+    }
+}
+
+impl Margins {
+    pub const fn new(left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub const fn uniform(width: f32) -> Self {
+        Self::new(width, width, width, width)
+    }
+}
+
+impl NinePatchMargins {
+    /// The sprites of the (up to) nine slices of `region` covering `dest`,
+    /// the borders shrink proportionally if `dest` is too small for them.
+    pub fn slices(&self, region: [Vec2; 2], dest: UIRect, color: Vec4) -> Vec<Sprite> {
+        let (source, margins) = (self.source, self.dest);
+        let (min, size) = (Vec2::from(dest.pos), Vec2::from(dest.size));
+        let scale = |a: f32, b: f32, len: f32| {
+            if a + b > len && a + b > 0.0 {
+                len / (a + b)
+            } else {
+                1.0
+            }
+        };
+        let scale = Vec2::new(
+            scale(margins.left, margins.right, size.x),
+            scale(margins.top, margins.bottom, size.y),
+        );
+        let max = min + size;
+        let xs = [
+            min.x,
+            min.x + margins.left * scale.x,
+            max.x - margins.right * scale.x,
+            max.x,
+        ];
+        let ys = [
+            min.y,
+            min.y + margins.top * scale.y,
+            max.y - margins.bottom * scale.y,
+            max.y,
+        ];
+        let [uv0, uv1] = region;
+        let uv_size = uv1 - uv0;
+        let us = [
+            uv0.x,
+            uv0.x + uv_size.x * source.left,
+            uv1.x - uv_size.x * source.right,
+            uv1.x,
+        ];
+        let vs = [
+            uv0.y,
+            uv0.y + uv_size.y * source.top,
+            uv1.y - uv_size.y * source.bottom,
+            uv1.y,
+        ];
+
+        let mut sprites = Vec::with_capacity(9);
+        for row in 0..3 {
+            for column in 0..3 {
+                let top_left = Vec2::new(xs[column], ys[row]);
+                let bottom_right = Vec2::new(xs[column + 1], ys[row + 1]);
+                let size = bottom_right - top_left;
+                // zero-width borders
+                if size.x <= 0.0 || size.y <= 0.0 {
+                    continue;
+                }
+                sprites.push(
+                    Sprite::new(top_left + size * 0.5, size)
+                        .uv(
+                            Vec2::new(us[column], vs[row]),
+                            Vec2::new(us[column + 1], vs[row + 1]),
+                        )
+                        .color(color),
+                );
+            }
+        }
+        sprites
+    }
+}
+
+impl SpriteRenderer {
+    pub fn new(draw: &mut draw::ServerChannel) -> anyhow::Result<Self> {
+        let indices = (0..MAX_SPRITES_PER_DRAW as u16)
+            .flat_map(|i| [0, 1, 2, 2, 1, 3].map(|j| i * 4 + j))
+            .collect::<Vec<u16>>();
+        Ok(Self {
+            program: ProgramHandle::new_vf(
+                draw,
+                "sprite renderer shader program",
+                shader::VERTEX,
+                shader::FRAGMENT,
+            )
+            .context("unable to create sprite renderer program")?,
+            vertex_array: VertexArrayHandle::new(draw, "sprite vertex array")
+                .context("unable to create sprite vertex array")?,
+            vertex_buffer: BufferHandle::new(
+                draw,
+                "sprite vertex buffer",
+                BufferTarget::ArrayBuffer,
+            )
+            .context("unable to create sprite vertex buffer")?,
+            index_buffer: BufferHandle::with_data(
+                draw,
+                "sprite index buffer",
+                BufferTarget::ElementArrayBuffer,
+                indices,
+            )
+            .context("unable to create sprite index buffer")?,
+            instanced_program: ProgramHandle::new_vf(
+                draw,
+                "instanced sprite renderer shader program",
+                shader::INSTANCED_VERTEX,
+                shader::FRAGMENT,
+            )
+            .context("unable to create instanced sprite renderer program")?,
+            instanced_vertex_array: VertexArrayHandle::new(draw, "sprite instance vertex array")
+                .context("unable to create sprite instance vertex array")?,
+            instance_buffer: BufferHandle::new(
+                draw,
+                "sprite instance buffer",
+                BufferTarget::ArrayBuffer,
+            )
+            .context("unable to create sprite instance buffer")?,
+            configured: Cell::new(false),
+            instanced_configured: Cell::new(false),
+            batches: Vec::new(),
+        })
+    }
+
+    pub fn push(&mut self, texture: GLuint, sprite: &Sprite, transform: &Affine2) {
+        let index = match self.batches.iter().position(|b| b.texture == texture) {
+            Some(index) => index,
+            None => {
+                self.batches.push(SpriteBatch {
+                    texture,
+                    vertices: Vec::new(),
+                });
+                self.batches.len() - 1
+            }
+        };
+        self.batches[index]
+            .vertices
+            .extend_from_slice(&sprite.vertices(transform));
+    }
+
+    /// Sprites waiting for the next flush.
+    pub fn len(&self) -> usize {
+        self.batches.iter().map(|b| b.vertices.len() / 4).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    fn configure(&self, context: &DrawContext) -> anyhow::Result<()> {
+        let vertex_array = self
+            .vertex_array
+            .try_get(context)
+            .context("sprite vertex array was not created")?;
+        let vertex_buffer = self
+            .vertex_buffer
+            .try_get(context)
+            .context("sprite vertex buffer was not created")?;
+        let index_buffer = self
+            .index_buffer
+            .try_get(context)
+            .context("sprite index buffer was not created")?;
+        let program = self
+            .program
+            .try_get(context)
+            .context("sprite renderer program was not created")?;
+        context.frame_uniforms.bind_block(*program);
+        vertex_array.set_attributes(
+            &vertex_buffer,
+            size_of::<SpriteVertex>(),
+            &[
+                VertexAttrib::new(0, 2, 0),
+                VertexAttrib::new(1, 2, 8),
+                VertexAttrib::new(2, 4, 16),
+            ],
+        );
+        // the element array binding is part of the vertex array state
+        index_buffer.bind();
+        vertex_array.unbind();
+        self.configured.set(true);
+        Ok(())
+    }
+
+    fn configure_instanced(&self, context: &DrawContext) -> anyhow::Result<()> {
+        let vertex_array = self
+            .instanced_vertex_array
+            .try_get(context)
+            .context("sprite instance vertex array was not created")?;
+        let instance_buffer = self
+            .instance_buffer
+            .try_get(context)
+            .context("sprite instance buffer was not created")?;
+        let program = self
+            .instanced_program
+            .try_get(context)
+            .context("instanced sprite renderer program was not created")?;
+        context.frame_uniforms.bind_block(*program);
+        vertex_array.set_attributes(
+            &instance_buffer,
+            size_of::<SpriteInstance>(),
+            &[
+                VertexAttrib::new(0, 2, 0).per_instance(),
+                VertexAttrib::new(1, 2, 8).per_instance(),
+                VertexAttrib::new(2, 4, 16).per_instance(),
+                VertexAttrib::new(3, 4, 32).per_instance(),
+                VertexAttrib::new(4, 1, 48).per_instance(),
+            ],
+        );
+        vertex_array.unbind();
+        self.instanced_configured.set(true);
+        Ok(())
+    }
+
+    /// Draws `instances` with a single draw call.
+    fn draw_instanced(
+        &self,
+        context: &DrawContext,
+        texture: GLuint,
+        instances: &[SpriteInstance],
+        transform: &Affine2,
+    ) -> anyhow::Result<()> {
+        if instances.is_empty() {
+            return Ok(());
+        }
+        if !self.instanced_configured.get() {
+            self.configure_instanced(context)?;
+        }
+        let vertex_array = self.instanced_vertex_array.get(context);
+        let program = self.instanced_program.get(context);
+        context.upload_frame_data(false);
+        self.instance_buffer.get(context).stream_slice(instances)?;
+        vertex_array.bind();
+        unsafe {
+            gl::UseProgram(*program);
+            gl::UniformMatrix3fv(
+                gl::GetUniformLocation(
+                    *program,
+                    CStr::from_bytes_with_nul_unchecked("transform\0".as_bytes()).as_ptr(),
+                ),
+                1,
+                gl::FALSE,
+                Mat3::from(*transform).to_cols_array().as_ptr(),
+            );
+            gl::Uniform1i(
+                gl::GetUniformLocation(
+                    *program,
+                    CStr::from_bytes_with_nul_unchecked("tex\0".as_bytes()).as_ptr(),
+                ),
+                0,
+            );
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::DrawArraysInstanced(gl::TRIANGLE_STRIP, 0, 4, instances.len() as GLsizei);
+        }
+        vertex_array.unbind();
+        Ok(())
+    }
+
+    /// Draws `batches`, returns the number of draw calls.
+    fn flush(&self, context: &DrawContext, batches: Vec<SpriteBatch>) -> anyhow::Result<usize> {
+        if batches.is_empty() {
+            return Ok(0);
+        }
+        if !self.configured.get() {
+            self.configure(context)?;
+        }
+        let vertex_array = self.vertex_array.get(context);
+        let vertex_buffer = self.vertex_buffer.get(context);
+        let program = self.program.get(context);
+        context.upload_frame_data(false);
+
+        let mut draw_calls = 0;
+        unsafe {
+            gl::UseProgram(*program);
+            gl::Uniform1i(
+                gl::GetUniformLocation(
+                    *program,
+                    CStr::from_bytes_with_nul_unchecked("tex\0".as_bytes()).as_ptr(),
+                ),
+                0,
+            );
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+        for batch in batches {
+            unsafe { gl::BindTexture(gl::TEXTURE_2D, batch.texture) };
+            for vertices in batch.vertices.chunks(MAX_SPRITES_PER_DRAW * 4) {
+                vertex_buffer.stream_slice(vertices)?;
+                vertex_array.bind();
+                unsafe {
+                    gl::DrawElements(
+                        gl::TRIANGLES,
+                        (vertices.len() / 4 * 6) as GLsizei,
+                        gl::UNSIGNED_SHORT,
+                        ptr::null(),
+                    );
+                }
+                draw_calls += 1;
+            }
+        }
+        vertex_array.unbind();
+        Ok(draw_calls)
+    }
+}
+
+impl DrawContext {
+    /// Queues `sprite` with the current transform, it is drawn by the next
+    /// `flush_sprites`, at the latest at the end of the frame.
+    pub fn draw_sprite(&mut self, texture: GLuint, sprite: &Sprite) {
+        let transform = self.transform_stack.current();
+        self.sprites.push(texture, sprite, &transform);
+    }
+
+    /// Queues the sprites whose id is in `visible`, e.g. those of the boxes
+    /// in view of a cull view, so that off screen ones cost nothing more
+    /// than a lookup.
+    pub fn draw_visible_sprites(
+        &mut self,
+        texture: GLuint,
+        sprites: &[(Uid, Sprite)],
+        visible: &VisibleSet,
+    ) {
+        let visible = visible.get();
+        for (id, sprite) in sprites {
+            if visible.binary_search(id).is_ok() {
+                self.draw_sprite(texture, sprite);
+            }
+        }
+    }
+
+    /// Queues `region` of `texture` sliced into nine parts to cover `dest`,
+    /// so that panels scale without stretching their corners.
+    pub fn draw_nine_patch(
+        &mut self,
+        texture: GLuint,
+        region: [Vec2; 2],
+        margins: &NinePatchMargins,
+        dest: UIRect,
+        color: Vec4,
+    ) {
+        for sprite in margins.slices(region, dest, color) {
+            self.draw_sprite(texture, &sprite);
+        }
+    }
+
+    /// Draws `sprites` with the current transform in a single instanced
+    /// draw call, e.g. for particles. Unlike `draw_sprite` they are drawn
+    /// right away, after the queued shapes and sprites.
+    pub fn draw_sprites_instanced(&mut self, texture: GLuint, sprites: &[Sprite]) {
+        self.flush_shapes();
+        self.flush_sprites();
+        let instances = sprites
+            .iter()
+            .map(|sprite| SpriteInstance {
+                center: sprite.center,
+                size: sprite.size,
+                uv: sprite.uv,
+                color: sprite.color,
+                rotation: sprite.rotation,
+                _padding: [0.0; 3],
+            })
+            .collect::<Vec<_>>();
+        let transform = self.transform_stack.current();
+        self.sprites
+            .draw_instanced(self, texture, &instances, &transform)
+            .context("unable to draw instanced sprites")
+            .log_warn();
+    }
+
+    /// Draws the queued sprites, returns the number of draw calls.
+    pub fn flush_sprites(&mut self) -> usize {
+        let batches = std::mem::take(&mut self.sprites.batches);
+        self.sprites
+            .flush(self, batches)
+            .context("unable to draw sprites")
+            .log_warn()
+            .unwrap_or_default()
+    }
+}
+
+#[test]
+fn test() {
+    use crate::ui::utils::geom::{UIPos, UISize};
+
+    let margins = NinePatchMargins {
+        source: Margins::uniform(0.25),
+        dest: Margins::new(2.0, 2.0, 2.0, 0.0),
+    };
+    let dest = UIRect::new(UIPos::new(10.0, 10.0), UISize::new(10.0, 6.0));
+    let slices = margins.slices([Vec2::ZERO, Vec2::ONE], dest, Vec4::ONE);
+    // no bottom row
+    assert_eq!(slices.len(), 6);
+    assert_eq!(slices[0].center, Vec2::new(11.0, 11.0));
+    assert_eq!(slices[0].uv, [Vec2::ZERO, Vec2::splat(0.25)]);
+    assert_eq!(slices[4].size, Vec2::new(6.0, 4.0));
+    assert_eq!(slices[4].uv, [Vec2::splat(0.25), Vec2::splat(0.75)]);
+
+    // the borders shrink to fit
+    let dest = UIRect::new(UIPos::ZERO, UISize::new(2.0, 8.0));
+    let slices = margins.slices([Vec2::ZERO, Vec2::ONE], dest, Vec4::ONE);
+    assert_eq!(slices.len(), 4);
+    assert_eq!(slices[0].size, Vec2::new(1.0, 2.0));
+}
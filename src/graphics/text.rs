@@ -0,0 +1,462 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context};
+use fontdue::{FontSettings, Metrics};
+use glam::{Vec2, Vec4};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::server::draw,
+    utils::{error::ResultExt, startup, uid::Uid},
+};
+
+use super::{
+    context::DrawContext,
+    sprite_renderer::Sprite,
+    wrappers::texture::{
+        ImageData, Texture, TextureFilter, TextureHandle, TextureOptions, TextureWrap,
+    },
+    HandleContainer,
+};
+
+/// Width of the glyph atlas, it only grows vertically.
+pub const ATLAS_WIDTH: u32 = 1024;
+pub const ATLAS_INITIAL_HEIGHT: u32 = 128;
+pub const ATLAS_MAX_HEIGHT: u32 = 4096;
+/// empty pixels around every glyph, so that linear filtering doesn't bleed
+/// the neighbouring glyphs in
+const GLYPH_PADDING: u32 = 1;
+const TAB_WIDTH: f32 = 4.0;
+/// fully transparent white, glyph coverage goes into the alpha channel so
+/// that the sprite color tints the text
+const CLEAR_PIXEL: [u8; 4] = [255, 255, 255, 0];
+
+/// Shipped with the game, relative to the working directory. Tests use it
+/// so that text metrics don't depend on the fonts installed.
+const BUNDLED_FONT: &str = "fonts/DejaVuSans.ttf";
+
+/// Looked up by `Font::system_default`, in order.
+const SYSTEM_FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/liberation-sans/LiberationSans-Regular.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+/// A parsed TTF/OTF font, cheap to clone and usable from any thread.
+#[derive(Clone)]
+pub struct Font {
+    id: Uid,
+    inner: Arc<fontdue::Font>,
+}
+
+/// A glyph placed by `Font::layout`, `pen` is the origin of the glyph on
+/// the baseline, relative to the top left of the text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionedGlyph {
+    pub index: u16,
+    pub pen: Vec2,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+struct GlyphKey {
+    font: Uid,
+    index: u16,
+    /// rasterization size, in pixels
+    px: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct AtlasGlyph {
+    x: u32,
+    y: u32,
+    metrics: Metrics,
+}
+
+enum Lookup {
+    /// `None` for glyphs without pixels, e.g. spaces
+    Found(Option<AtlasGlyph>),
+    Full,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// end of the last allocation on the shelf
+    x: u32,
+}
+
+/// Packs rectangles into rows ("shelves") of the height of their first
+/// rectangle, good enough for glyphs of a few sizes.
+#[derive(Debug)]
+struct ShelfPacker {
+    size: PhysicalSize<u32>,
+    shelves: Vec<Shelf>,
+}
+
+/// Glyphs rasterized on the draw server, rasterized once per font, glyph
+/// and pixel size then drawn as sprites.
+///
+/// A copy of the atlas is kept in memory to re-upload it when it grows,
+/// GLES can't read textures back. The texture is only created when text is
+/// first drawn.
+pub struct GlyphAtlas {
+    texture: TextureHandle,
+    pixels: Vec<u8>,
+    packer: ShelfPacker,
+    glyphs: HashMap<GlyphKey, Option<AtlasGlyph>>,
+}
+
+impl Font {
+    pub fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let font = fontdue::Font::from_bytes(bytes, FontSettings::default())
+            .map_err(|e| anyhow::format_err!("{e}"))
+            .context("unable to parse font")?;
+        Ok(Self {
+            id: Uid::new(),
+            inner: Arc::new(font),
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .with_context(|| format!("unable to read font file {}", path.display()))?;
+        Self::from_bytes(bytes).with_context(|| format!("unable to load font {}", path.display()))
+    }
+
+    /// The sans-serif font shipped in `fonts/`.
+    pub fn bundled() -> anyhow::Result<Self> {
+        Self::load(BUNDLED_FONT)
+    }
+
+    /// A sans-serif font installed with the OS.
+    pub fn system_default() -> anyhow::Result<Self> {
+        let path = SYSTEM_FONTS
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.is_file())
+            .context("no system font found")?;
+        Self::load(path)
+    }
+
+    /// Distance between the baselines of two lines.
+    pub fn line_height(&self, size: f32) -> f32 {
+        self.inner
+            .horizontal_line_metrics(size)
+            .map(|metrics| metrics.new_line_size)
+            .unwrap_or(size)
+    }
+
+    /// Distance between the top of a line and its baseline.
+    pub fn ascent(&self, size: f32) -> f32 {
+        self.inner
+            .horizontal_line_metrics(size)
+            .map(|metrics| metrics.ascent)
+            .unwrap_or(size)
+    }
+
+    /// Places the glyphs of `text`, kerned, breaking lines at `\n` and
+    /// expanding tabs. Other control characters are skipped.
+    pub fn layout(&self, text: &str, size: f32) -> Vec<PositionedGlyph> {
+        let line_height = self.line_height(size);
+        let mut pen = Vec2::new(0.0, self.ascent(size));
+        let mut previous = None;
+        let mut glyphs = Vec::new();
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    pen = Vec2::new(0.0, pen.y + line_height);
+                    previous = None;
+                    continue;
+                }
+                '\t' => {
+                    let space = self.inner.lookup_glyph_index(' ');
+                    pen.x += self.inner.metrics_indexed(space, size).advance_width * TAB_WIDTH;
+                    previous = None;
+                    continue;
+                }
+                c if c.is_control() => continue,
+                _ => {}
+            }
+            let index = self.inner.lookup_glyph_index(c);
+            if let Some(previous) = previous {
+                pen.x += self
+                    .inner
+                    .horizontal_kern_indexed(previous, index, size)
+                    .unwrap_or(0.0);
+            }
+            glyphs.push(PositionedGlyph { index, pen });
+            pen.x += self.inner.metrics_indexed(index, size).advance_width;
+            previous = Some(index);
+        }
+        glyphs
+    }
+
+    /// Size of the box `text` is laid out in, as wide as its longest line.
+    pub fn measure(&self, text: &str, size: f32) -> Vec2 {
+        let width = text
+            .split('\n')
+            .map(|line| {
+                self.layout(line, size)
+                    .last()
+                    .map(|glyph| {
+                        glyph.pen.x + self.inner.metrics_indexed(glyph.index, size).advance_width
+                    })
+                    .unwrap_or(0.0)
+            })
+            .fold(0.0, f32::max);
+        let lines = text.split('\n').count();
+        Vec2::new(width, lines as f32 * self.line_height(size))
+    }
+}
+
+impl ShelfPacker {
+    fn new(size: PhysicalSize<u32>) -> Self {
+        Self {
+            size,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Top left corner of a free `width`x`height` rectangle.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > self.size.width {
+            return None;
+        }
+        // the lowest shelf that fits, so that tall shelves aren't filled
+        // with small glyphs
+        let best = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && shelf.x + width <= self.size.width)
+            .min_by_key(|shelf| shelf.height);
+        if let Some(shelf) = best {
+            let x = shelf.x;
+            shelf.x += width;
+            return Some((x, shelf.y));
+        }
+        let y = self
+            .shelves
+            .last()
+            .map(|shelf| shelf.y + shelf.height)
+            .unwrap_or(0);
+        if y + height > self.size.height {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width,
+        });
+        Some((0, y))
+    }
+}
+
+impl GlyphAtlas {
+    pub fn new(draw: &mut draw::ServerChannel) -> anyhow::Result<Self> {
+        let size = PhysicalSize::new(ATLAS_WIDTH, ATLAS_INITIAL_HEIGHT);
+        Ok(Self {
+            // SAFETY: created by `texture` on first use
+            texture: unsafe { TextureHandle::new_uninit(draw) },
+            pixels: Vec::new(),
+            packer: ShelfPacker::new(size),
+            glyphs: HashMap::new(),
+        })
+    }
+
+    /// The atlas texture, created and cleared if it doesn't exist yet.
+    fn texture(&mut self, handles: &mut HandleContainer) -> anyhow::Result<Texture> {
+        if let Some(texture) = handles.textures.get(&self.texture) {
+            return Ok(texture);
+        }
+        startup::lazy("glyph atlas", || {
+            let size = self.packer.size;
+            self.pixels = CLEAR_PIXEL.repeat((size.width * size.height) as usize);
+            let image = ImageData::new(size, self.pixels.clone())?.options(Self::texture_options());
+            handles
+                .create_texture("glyph atlas", &self.texture, &image)
+                .context("unable to create glyph atlas texture")
+        })
+    }
+
+    fn texture_options() -> TextureOptions {
+        TextureOptions {
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            wrap: TextureWrap::ClampToEdge,
+            mipmaps: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.packer.size
+    }
+
+    /// Glyphs rasterized so far, including the empty ones.
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    fn lookup(&mut self, texture: &Texture, font: &Font, index: u16, px: u32) -> Lookup {
+        let key = GlyphKey {
+            font: font.id,
+            index,
+            px,
+        };
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return Lookup::Found(*glyph);
+        }
+        let (metrics, coverage) = font.inner.rasterize_indexed(index, px as f32);
+        if metrics.width == 0 || metrics.height == 0 {
+            self.glyphs.insert(key, None);
+            return Lookup::Found(None);
+        }
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+        if width + GLYPH_PADDING > ATLAS_WIDTH || height + GLYPH_PADDING > ATLAS_MAX_HEIGHT {
+            tracing::warn!("glyph {index} is too large for the atlas at {px}px, not drawn");
+            self.glyphs.insert(key, None);
+            return Lookup::Found(None);
+        }
+        let (x, y) = match self
+            .packer
+            .allocate(width + GLYPH_PADDING, height + GLYPH_PADDING)
+        {
+            Some(position) => position,
+            None => return Lookup::Full,
+        };
+        let pixels = coverage
+            .iter()
+            .flat_map(|&alpha| [255, 255, 255, alpha])
+            .collect::<Vec<u8>>();
+        for (row, line) in pixels.chunks_exact(width as usize * 4).enumerate() {
+            let start = (((y + row as u32) * self.packer.size.width + x) * 4) as usize;
+            self.pixels[start..start + line.len()].copy_from_slice(line);
+        }
+        texture.bind();
+        unsafe {
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x as _,
+                y as _,
+                width as _,
+                height as _,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const _,
+            );
+        }
+        texture.unbind();
+        let glyph = Some(AtlasGlyph { x, y, metrics });
+        self.glyphs.insert(key, glyph);
+        Lookup::Found(glyph)
+    }
+
+    /// Doubles the height of the atlas, the glyphs keep their positions in
+    /// pixels but not their texture coordinates.
+    fn grow(&mut self, texture: &Texture) -> anyhow::Result<()> {
+        let size = self.packer.size;
+        if size.height >= ATLAS_MAX_HEIGHT {
+            bail!("glyph atlas is full ({}x{})", size.width, size.height);
+        }
+        // new rows are appended below the current ones
+        self.pixels
+            .extend(CLEAR_PIXEL.repeat((size.width * size.height) as usize));
+        let size = PhysicalSize::new(size.width, size.height * 2);
+        texture
+            .upload(&ImageData::new(size, self.pixels.clone())?.options(Self::texture_options()))
+            .context("unable to upload grown glyph atlas")?;
+        self.packer.size = size;
+        tracing::debug!("glyph atlas grew to {}x{}", size.width, size.height);
+        Ok(())
+    }
+}
+
+impl DrawContext {
+    /// Queues `text` as sprites with the current transform, `position` is
+    /// the top left of the text and `size` the font size, in UI units.
+    pub fn draw_text(&mut self, font: &Font, text: &str, position: Vec2, size: f32, color: Vec4) {
+        let texture = match self.glyphs.texture(&mut self.handles).log_warn() {
+            Some(texture) => texture,
+            None => return,
+        };
+        // rasterize at the resolution of the display, not of the UI
+        let px = ((size * self.ui_scale()).round() as u32).max(1);
+        let scale = px as f32 / size;
+
+        let layout = font.layout(text, size);
+        let mut glyphs = Vec::with_capacity(layout.len());
+        for glyph in layout.iter() {
+            let found = loop {
+                match self.glyphs.lookup(&texture, font, glyph.index, px) {
+                    Lookup::Found(found) => break found,
+                    Lookup::Full => {
+                        // queued glyphs still use the texture coordinates of
+                        // the current size
+                        self.flush_sprites();
+                        if self.glyphs.grow(&texture).log_warn().is_none() {
+                            break None;
+                        }
+                    }
+                }
+            };
+            glyphs.push(found);
+        }
+
+        let atlas_size = self.glyphs.size();
+        let atlas_size = Vec2::new(atlas_size.width as f32, atlas_size.height as f32);
+        for (glyph, found) in layout.iter().zip(glyphs) {
+            let AtlasGlyph { x, y, metrics } = match found {
+                Some(found) => found,
+                None => continue,
+            };
+            let pixel_size = Vec2::new(metrics.width as f32, metrics.height as f32);
+            let top_left = position
+                + glyph.pen
+                + Vec2::new(
+                    metrics.xmin as f32,
+                    -(metrics.ymin as f32 + metrics.height as f32),
+                ) / scale;
+            let uv = Vec2::new(x as f32, y as f32) / atlas_size;
+            let sprite_size = pixel_size / scale;
+            self.draw_sprite(
+                *texture,
+                &Sprite::new(top_left + sprite_size * 0.5, sprite_size)
+                    .uv(uv, uv + pixel_size / atlas_size)
+                    .color(color),
+            );
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let mut packer = ShelfPacker::new(PhysicalSize::new(16, 8));
+    assert_eq!(packer.allocate(17, 1), None);
+    assert_eq!(packer.allocate(8, 4), Some((0, 0)));
+    assert_eq!(packer.allocate(4, 2), Some((8, 0)));
+    // doesn't fit the first shelf anymore
+    assert_eq!(packer.allocate(8, 2), Some((0, 4)));
+    // the lowest shelf that fits
+    assert_eq!(packer.allocate(4, 2), Some((8, 4)));
+    assert_eq!(packer.allocate(4, 3), Some((12, 0)));
+    assert_eq!(packer.allocate(1, 3), None);
+    packer.size.height = 16;
+    assert_eq!(packer.allocate(1, 3), Some((0, 6)));
+}
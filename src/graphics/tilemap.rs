@@ -0,0 +1,644 @@
+use std::{cell::Cell, ffi::CStr, future::Future, mem::size_of, path::PathBuf, ptr};
+
+use anyhow::{ensure, Context};
+use bytemuck::{Pod, Zeroable};
+use gl::types::GLsizei;
+use glam::{Affine2, Mat3, Vec2};
+use winit::dpi::PhysicalSize;
+
+use crate::{exec::main_ctx::MainContext, spatial::Aabb, utils::error::ResultExt};
+
+use super::{
+    camera::Camera2D,
+    context::DrawContext,
+    wrappers::{
+        buffer::{BufferHandle, BufferTarget},
+        shader::ProgramHandle,
+        texture::{TextureFilter, TextureHandle, TextureOptions, TextureWrap},
+        vertex_array::{VertexArrayHandle, VertexAttrib},
+    },
+};
+
+pub mod tiled;
+
+mod shader {
+    pub const VERTEX: &str = r#"
+    #version 300 es
+
+    layout(location = 0) in vec2 position;
+    layout(location = 1) in vec2 tex_coords;
+
+    out vec2 vf_tex_coords;
+
+    layout(std140) uniform FrameData {
+        mat4 ui_projection;
+        vec2 ui_size;
+        vec2 resolution;
+        float time;
+    };
+
+    uniform mat3 transform;
+
+    void main() {
+        vec2 world = (transform * vec3(position, 1.0)).xy;
+        gl_Position = ui_projection * vec4(world, 0.0, 1.0);
+        vf_tex_coords = tex_coords;
+    }
+    "#;
+
+    pub const FRAGMENT: &str = r#"
+    #version 300 es
+    precision mediump float;
+
+    in vec2 vf_tex_coords;
+
+    out vec4 color;
+
+    uniform sampler2D tex;
+    uniform float opacity;
+
+    void main() {
+        color = texture(tex, vf_tex_coords);
+        color.a *= opacity;
+    }
+    "#;
+}
+
+/// Flip flags stored in the high bits of a global tile id.
+pub const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+pub const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+pub const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// Also covers the hexagonal rotation flag, which is ignored.
+const FLAGS: u32 = 0xf000_0000;
+
+/// Width and height of a chunk, in tiles. A chunk of a layer is drawn with
+/// one draw call per tileset it uses.
+pub const CHUNK_TILES: u32 = 16;
+
+/// An orthogonal tile map, positions are in map pixels with the origin at
+/// the top left corner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileMap {
+    /// in tiles
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: Vec2,
+    /// sorted by `first_gid`
+    pub tilesets: Vec<Tileset>,
+    /// bottom to top
+    pub layers: Vec<TileLayer>,
+}
+
+/// Tiles cut from a single image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tileset {
+    /// global id of the first tile
+    pub first_gid: u32,
+    pub name: String,
+    pub image: PathBuf,
+    pub image_size: PhysicalSize<u32>,
+    pub tile_size: PhysicalSize<u32>,
+    pub columns: u32,
+    pub tile_count: u32,
+    /// around the tiles, in pixels
+    pub margin: u32,
+    /// between the tiles, in pixels
+    pub spacing: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileLayer {
+    pub name: String,
+    /// in tiles
+    pub width: u32,
+    pub height: u32,
+    /// global tile ids with their flip flags, row by row, 0 for no tile
+    pub tiles: Vec<u32>,
+    pub offset: Vec2,
+    /// how fast the layer scrolls relative to the camera, 1 for the layers
+    /// the level is played on, less for the background
+    pub parallax: Vec2,
+    pub opacity: f32,
+    pub visible: bool,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileVertex {
+    pub position: Vec2,
+    pub tex_coords: Vec2,
+}
+
+// SAFETY: `repr(C)`, made of `Pod` fields with no padding in between
+unsafe impl Zeroable for TileVertex {}
+unsafe impl Pod for TileVertex {}
+
+/// Quads of the tiles of one tileset in a chunk of a layer, top left, top
+/// right, bottom left and bottom right vertices of each.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileChunk {
+    pub layer: usize,
+    pub tileset: usize,
+    /// in map pixels, before parallax
+    pub bounds: Aabb,
+    pub vertices: Vec<TileVertex>,
+}
+
+struct ChunkBuffer {
+    layer: usize,
+    tileset: usize,
+    bounds: Aabb,
+    quads: usize,
+    vertex_buffer: BufferHandle,
+}
+
+/// Draws a `TileMap` from static vertex buffers built once, one per chunk
+/// and tileset, skipping the chunks outside of the camera view.
+pub struct TileMapRenderer {
+    program: ProgramHandle,
+    vertex_array: VertexArrayHandle,
+    index_buffer: BufferHandle,
+    /// one per tileset
+    textures: Vec<TextureHandle>,
+    layers: Vec<TileLayer>,
+    chunks: Vec<ChunkBuffer>,
+    /// the index buffer is bound to the vertex array on the first draw,
+    /// once they are created on the draw server
+    configured: Cell<bool>,
+}
+
+impl TileMap {
+    /// Tileset of `gid` and the id of the tile in it, flip flags are
+    /// ignored.
+    pub fn tileset(&self, gid: u32) -> Option<(usize, u32)> {
+        let gid = gid & !FLAGS;
+        if gid == 0 {
+            return None;
+        }
+        let index = self
+            .tilesets
+            .partition_point(|tileset| tileset.first_gid <= gid)
+            .checked_sub(1)?;
+        let local_id = gid - self.tilesets[index].first_gid;
+        (local_id < self.tilesets[index].tile_count).then_some((index, local_id))
+    }
+
+    /// Size of the map, in map pixels.
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width as f32, self.height as f32) * self.tile_size
+    }
+}
+
+impl Tileset {
+    /// Top left and bottom right texture coordinates of tile `local_id`.
+    pub fn uv(&self, local_id: u32) -> [Vec2; 2] {
+        let columns = self.columns.max(1);
+        let (column, row) = (local_id % columns, local_id / columns);
+        let tile = Vec2::new(self.tile_size.width as f32, self.tile_size.height as f32);
+        let image = Vec2::new(self.image_size.width as f32, self.image_size.height as f32);
+        let top_left = Vec2::splat(self.margin as f32)
+            + Vec2::new(column as f32, row as f32) * (tile + self.spacing as f32);
+        [top_left / image, (top_left + tile) / image]
+    }
+}
+
+impl TileLayer {
+    /// Visible, opaque layer without offset or parallax.
+    pub fn new(name: String, width: u32, height: u32, tiles: Vec<u32>) -> anyhow::Result<Self> {
+        ensure!(
+            tiles.len() == width as usize * height as usize,
+            "layer {name} has {} tiles instead of {width}x{height}",
+            tiles.len()
+        );
+        Ok(Self {
+            name,
+            width,
+            height,
+            tiles,
+            offset: Vec2::ZERO,
+            parallax: Vec2::ONE,
+            opacity: 1.0,
+            visible: true,
+        })
+    }
+
+    /// Translation of the layer, on top of its `offset`, when the camera
+    /// looks at `camera`, so that it scrolls at `parallax` times the camera
+    /// speed.
+    pub fn parallax_offset(&self, camera: Vec2) -> Vec2 {
+        camera * (Vec2::ONE - self.parallax)
+    }
+}
+
+/// Texture coordinates of the corners of a tile drawn with the flip `flags`
+/// of its gid, in the order of `TileChunk::vertices`.
+fn flipped_uvs([uv0, uv1]: [Vec2; 2], flags: u32) -> [Vec2; 4] {
+    [Vec2::ZERO, Vec2::X, Vec2::Y, Vec2::ONE].map(|corner| {
+        // the image is flipped diagonally first, then horizontally and
+        // vertically, so the corners are mapped the other way around
+        let mut corner = corner;
+        if flags & FLIPPED_VERTICALLY != 0 {
+            corner.y = 1.0 - corner.y;
+        }
+        if flags & FLIPPED_HORIZONTALLY != 0 {
+            corner.x = 1.0 - corner.x;
+        }
+        if flags & FLIPPED_DIAGONALLY != 0 {
+            corner = Vec2::new(corner.y, corner.x);
+        }
+        uv0 + (uv1 - uv0) * corner
+    })
+}
+
+/// Splits the layers of `map` into chunks of `CHUNK_TILES` squared tiles,
+/// with one mesh per tileset used in each chunk. Invisible layers are
+/// included, so that they can be shown later.
+pub fn build_chunks(map: &TileMap) -> Vec<TileChunk> {
+    let mut chunks = Vec::new();
+    for (layer_index, layer) in map.layers.iter().enumerate() {
+        for chunk_y in (0..layer.height).step_by(CHUNK_TILES as usize) {
+            for chunk_x in (0..layer.width).step_by(CHUNK_TILES as usize) {
+                let first = chunks.len();
+                for y in chunk_y..(chunk_y + CHUNK_TILES).min(layer.height) {
+                    for x in chunk_x..(chunk_x + CHUNK_TILES).min(layer.width) {
+                        let gid = layer.tiles[(y * layer.width + x) as usize];
+                        let (tileset_index, local_id) = match map.tileset(gid) {
+                            Some(tile) => tile,
+                            None => continue,
+                        };
+                        let tileset = &map.tilesets[tileset_index];
+                        // tiles bigger than the grid stick out at the top
+                        // right, like in Tiled
+                        let size = Vec2::new(
+                            tileset.tile_size.width as f32,
+                            tileset.tile_size.height as f32,
+                        );
+                        let bottom_left =
+                            layer.offset + Vec2::new(x as f32, (y + 1) as f32) * map.tile_size;
+                        let top_left = bottom_left - Vec2::new(0.0, size.y);
+                        let bounds = Aabb::new(top_left, top_left + size);
+                        let uvs = flipped_uvs(tileset.uv(local_id), gid & FLAGS);
+                        let positions = [
+                            top_left,
+                            top_left + Vec2::new(size.x, 0.0),
+                            top_left + Vec2::new(0.0, size.y),
+                            top_left + size,
+                        ];
+
+                        let index = match chunks[first..]
+                            .iter()
+                            .position(|chunk: &TileChunk| chunk.tileset == tileset_index)
+                        {
+                            Some(index) => first + index,
+                            None => {
+                                chunks.push(TileChunk {
+                                    layer: layer_index,
+                                    tileset: tileset_index,
+                                    bounds,
+                                    vertices: Vec::new(),
+                                });
+                                chunks.len() - 1
+                            }
+                        };
+                        let chunk = &mut chunks[index];
+                        chunk.bounds = Aabb::new(
+                            chunk.bounds.min.min(bounds.min),
+                            chunk.bounds.max.max(bounds.max),
+                        );
+                        chunk.vertices.extend(positions.into_iter().zip(uvs).map(
+                            |(position, tex_coords)| TileVertex {
+                                position,
+                                tex_coords,
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    chunks
+}
+
+impl TileMapRenderer {
+    /// Uploads the chunks of `map` and starts loading the tileset images in
+    /// the background, the tiles are drawn with a placeholder texture
+    /// until the returned future resolves.
+    pub fn new(
+        main_ctx: &mut MainContext,
+        map: &TileMap,
+    ) -> anyhow::Result<(Self, impl Future<Output = anyhow::Result<()>>)> {
+        let options = TextureOptions {
+            min_filter: TextureFilter::Nearest,
+            mag_filter: TextureFilter::Nearest,
+            // neighbouring tiles would bleed into each other at the edges
+            wrap: TextureWrap::ClampToEdge,
+            mipmaps: false,
+            ..Default::default()
+        };
+        let pending = map
+            .tilesets
+            .iter()
+            .map(|tileset| {
+                let texture = main_ctx
+                    .load_texture(
+                        format!("tileset {}", tileset.name),
+                        tileset.image.clone(),
+                        options,
+                    )
+                    .with_context(|| {
+                        format!("unable to create tileset {} texture", tileset.name)
+                    })?;
+                Ok((tileset.name.clone(), texture))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let textures = pending
+            .iter()
+            .map(|(_, texture)| texture.handle.clone())
+            .collect();
+        let loaded = async move {
+            for (name, texture) in pending {
+                texture
+                    .await
+                    .with_context(|| format!("unable to load tileset {name}"))?;
+            }
+            Ok::<_, anyhow::Error>(())
+        };
+
+        let draw = &mut main_ctx.channels.draw;
+        let chunks = build_chunks(map)
+            .into_iter()
+            .map(|chunk| {
+                let quads = chunk.vertices.len() / 4;
+                let name = format!(
+                    "tile chunk at {} of layer {}",
+                    chunk.bounds.min, map.layers[chunk.layer].name
+                );
+                Ok(ChunkBuffer {
+                    layer: chunk.layer,
+                    tileset: chunk.tileset,
+                    bounds: chunk.bounds,
+                    quads,
+                    vertex_buffer: BufferHandle::with_data(
+                        draw,
+                        name,
+                        BufferTarget::ArrayBuffer,
+                        chunk.vertices,
+                    )
+                    .context("unable to create tile chunk vertex buffer")?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let max_quads = (CHUNK_TILES * CHUNK_TILES) as u16;
+        let indices = (0..max_quads)
+            .flat_map(|i| [0, 1, 2, 2, 1, 3].map(|j| i * 4 + j))
+            .collect::<Vec<u16>>();
+        let renderer = Self {
+            program: ProgramHandle::new_vf(
+                draw,
+                "tilemap shader program",
+                shader::VERTEX,
+                shader::FRAGMENT,
+            )
+            .context("unable to create tilemap program")?,
+            vertex_array: VertexArrayHandle::new(draw, "tilemap vertex array")
+                .context("unable to create tilemap vertex array")?,
+            index_buffer: BufferHandle::with_data(
+                draw,
+                "tilemap index buffer",
+                BufferTarget::ElementArrayBuffer,
+                indices,
+            )
+            .context("unable to create tilemap index buffer")?,
+            textures,
+            layers: map.layers.clone(),
+            chunks,
+            configured: Cell::new(false),
+        };
+        Ok((renderer, loaded))
+    }
+
+    /// Number of chunk meshes, each drawn with one draw call when visible.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    fn configure(&self, context: &DrawContext) -> anyhow::Result<()> {
+        let vertex_array = self
+            .vertex_array
+            .try_get(context)
+            .context("tilemap vertex array was not created")?;
+        let index_buffer = self
+            .index_buffer
+            .try_get(context)
+            .context("tilemap index buffer was not created")?;
+        let program = self
+            .program
+            .try_get(context)
+            .context("tilemap program was not created")?;
+        context.frame_uniforms.bind_block(*program);
+        vertex_array.bind();
+        // the element array binding is part of the vertex array state
+        index_buffer.bind();
+        vertex_array.unbind();
+        self.configured.set(true);
+        Ok(())
+    }
+
+    /// Draws the chunks visible from `camera`, with `transform` from world
+    /// to UI units, returns the number of draw calls.
+    fn draw(
+        &self,
+        context: &DrawContext,
+        camera: &Camera2D,
+        transform: &Affine2,
+    ) -> anyhow::Result<usize> {
+        if !self.configured.get() {
+            self.configure(context)?;
+        }
+        let vertex_array = self.vertex_array.get(context);
+        let program = self.program.get(context);
+        context.upload_frame_data(false);
+        let view = camera.world_bounds();
+
+        let location = |name: &str| unsafe {
+            gl::GetUniformLocation(
+                *program,
+                CStr::from_bytes_with_nul_unchecked(name.as_bytes()).as_ptr(),
+            )
+        };
+        let (transform_location, opacity_location) =
+            (location("transform\0"), location("opacity\0"));
+        unsafe {
+            gl::UseProgram(*program);
+            gl::Uniform1i(location("tex\0"), 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+
+        let mut draw_calls = 0;
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            if !layer.visible || layer.opacity <= 0.0 {
+                continue;
+            }
+            let offset = layer.parallax_offset(camera.position);
+            let layer_transform = *transform * Affine2::from_translation(offset);
+            unsafe {
+                gl::UniformMatrix3fv(
+                    transform_location,
+                    1,
+                    gl::FALSE,
+                    Mat3::from(layer_transform).to_cols_array().as_ptr(),
+                );
+                gl::Uniform1f(opacity_location, layer.opacity);
+            }
+            for chunk in self
+                .chunks
+                .iter()
+                .filter(|chunk| chunk.layer == layer_index)
+            {
+                let bounds = Aabb::new(chunk.bounds.min + offset, chunk.bounds.max + offset);
+                if !bounds.intersects(&view) {
+                    continue;
+                }
+                let texture = match self.textures[chunk.tileset].try_get(context) {
+                    Some(texture) => texture,
+                    None => continue,
+                };
+                let vertex_buffer = match chunk.vertex_buffer.try_get(context) {
+                    Some(vertex_buffer) => vertex_buffer,
+                    None => continue,
+                };
+                vertex_array.set_attributes(
+                    &vertex_buffer,
+                    size_of::<TileVertex>(),
+                    &[VertexAttrib::new(0, 2, 0), VertexAttrib::new(1, 2, 8)],
+                );
+                unsafe {
+                    gl::BindTexture(gl::TEXTURE_2D, *texture);
+                    gl::DrawElements(
+                        gl::TRIANGLES,
+                        (chunk.quads * 6) as GLsizei,
+                        gl::UNSIGNED_SHORT,
+                        ptr::null(),
+                    );
+                }
+                draw_calls += 1;
+            }
+        }
+        vertex_array.unbind();
+        Ok(draw_calls)
+    }
+}
+
+impl DrawContext {
+    /// Draws `tilemap` seen through `camera`, after the queued shapes and
+    /// sprites, returns the number of chunks drawn.
+    pub fn draw_tilemap(&mut self, tilemap: &TileMapRenderer, camera: &Camera2D) -> usize {
+        self.flush_shapes();
+        self.flush_sprites();
+        self.push_camera(camera);
+        let transform = self.transform_stack.current();
+        let draw_calls = tilemap
+            .draw(self, camera, &transform)
+            .context("unable to draw tilemap")
+            .log_warn()
+            .unwrap_or_default();
+        self.pop_camera();
+        draw_calls
+    }
+}
+
+#[test]
+fn test() {
+    let tileset = |first_gid, tile_size| Tileset {
+        first_gid,
+        name: String::new(),
+        image: PathBuf::new(),
+        image_size: PhysicalSize::new(34, 18),
+        tile_size: PhysicalSize::new(tile_size, tile_size),
+        columns: 2,
+        tile_count: 2,
+        margin: 1,
+        spacing: 0,
+    };
+    let mut tiles = vec![0; 20 * 2];
+    tiles[0] = 1;
+    tiles[1] = 2 | FLIPPED_HORIZONTALLY;
+    tiles[16] = 3;
+    tiles[20] = 4 | FLIPPED_HORIZONTALLY | FLIPPED_DIAGONALLY;
+    let mut background = TileLayer::new("background".into(), 20, 2, tiles.clone()).unwrap();
+    background.parallax = Vec2::splat(0.5);
+    let map = TileMap {
+        width: 20,
+        height: 2,
+        tile_size: Vec2::splat(16.0),
+        tilesets: vec![tileset(1, 16), tileset(3, 32)],
+        layers: vec![
+            background,
+            TileLayer::new("ground".into(), 20, 2, tiles).unwrap(),
+        ],
+    };
+    assert!(TileLayer::new("broken".into(), 2, 2, vec![0; 3]).is_err());
+    assert_eq!(map.tileset(0), None);
+    assert_eq!(map.tileset(2 | FLIPPED_VERTICALLY), Some((0, 1)));
+    assert_eq!(map.tileset(4), Some((1, 1)));
+    assert_eq!(map.tileset(5), None);
+    assert_eq!(map.size(), Vec2::new(320.0, 32.0));
+    assert_eq!(
+        map.tilesets[0].uv(1),
+        [
+            Vec2::new(17.0 / 34.0, 1.0 / 18.0),
+            Vec2::new(33.0 / 34.0, 17.0 / 18.0)
+        ]
+    );
+
+    let chunks = build_chunks(&map);
+    // per layer, both tilesets in the first chunk, the big tile in the
+    // second one
+    let summary = chunks
+        .iter()
+        .map(|chunk| (chunk.layer, chunk.tileset, chunk.vertices.len() / 4))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (0, 0, 2),
+            (0, 1, 1),
+            (0, 1, 1),
+            (1, 0, 2),
+            (1, 1, 1),
+            (1, 1, 1)
+        ]
+    );
+    let first = &chunks[0];
+    assert_eq!(first.bounds, Aabb::new(Vec2::ZERO, Vec2::new(32.0, 16.0)));
+    let [uv0, uv1] = map.tilesets[0].uv(1);
+    // flipped horizontally, the left corners show the right of the tile
+    assert_eq!(first.vertices[4].position, Vec2::new(16.0, 0.0));
+    assert_eq!(first.vertices[4].tex_coords, Vec2::new(uv1.x, uv0.y));
+    assert_eq!(first.vertices[7].tex_coords, Vec2::new(uv0.x, uv1.y));
+    // the 32x32 tile on the second row sticks out above its cell
+    assert_eq!(
+        chunks[1].bounds,
+        Aabb::new(Vec2::ZERO, Vec2::new(32.0, 32.0))
+    );
+    // rotated a quarter turn clockwise, the top left corner shows the
+    // bottom left of the tile
+    let [uv0, uv1] = map.tilesets[1].uv(1);
+    assert_eq!(chunks[1].vertices[0].tex_coords, Vec2::new(uv0.x, uv1.y));
+    assert_eq!(chunks[1].vertices[3].tex_coords, Vec2::new(uv1.x, uv0.y));
+    assert_eq!(
+        chunks[2].bounds,
+        Aabb::new(Vec2::new(256.0, -16.0), Vec2::new(288.0, 16.0))
+    );
+
+    // half speed, the background trails behind the camera
+    assert_eq!(
+        map.layers[0].parallax_offset(Vec2::new(100.0, 0.0)),
+        Vec2::new(50.0, 0.0)
+    );
+    assert_eq!(
+        map.layers[1].parallax_offset(Vec2::new(100.0, 0.0)),
+        Vec2::ZERO
+    );
+}
@@ -0,0 +1,397 @@
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{bail, ensure, Context};
+use glam::Vec2;
+use roxmltree::{Document, Node};
+use serde::Deserialize;
+use winit::dpi::PhysicalSize;
+
+use super::{TileLayer, TileMap, Tileset};
+
+/// Loads a map saved by the Tiled editor as `.tmx` or `.json`/`.tmj`.
+///
+/// Only orthogonal, finite maps are supported, with CSV (TMX) or array
+/// (JSON) layer data and tilesets made of a single image, embedded or
+/// external. Group, object and image layers are skipped.
+pub fn load(path: &Path) -> anyhow::Result<TileMap> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("unable to read map {}", path.display()))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let map = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("tmx") => parse_tmx(&source, dir),
+        Some("json" | "tmj") => parse_json_map(&source, dir),
+        _ => bail!("unknown map format, expected .tmx, .tmj or .json"),
+    };
+    map.with_context(|| format!("unable to load map {}", path.display()))
+}
+
+fn parse_tmx(source: &str, dir: &Path) -> anyhow::Result<TileMap> {
+    let document = Document::parse(source).context("invalid XML")?;
+    let root = document.root_element();
+    ensure!(
+        root.has_tag_name("map"),
+        "root element is <{}>, not <map>",
+        root.tag_name().name()
+    );
+    let orientation = root.attribute("orientation").unwrap_or("orthogonal");
+    ensure!(
+        orientation == "orthogonal",
+        "{orientation} maps are not supported"
+    );
+    ensure!(
+        root.attribute("infinite") != Some("1"),
+        "infinite maps are not supported"
+    );
+
+    let mut tilesets = Vec::new();
+    let mut layers = Vec::new();
+    for child in root.children().filter(Node::is_element) {
+        match child.tag_name().name() {
+            "tileset" => {
+                let first_gid = attribute(child, "firstgid")?;
+                let tileset = match child.attribute("source") {
+                    Some(source) => {
+                        let path = dir.join(source);
+                        let external = fs::read_to_string(&path).with_context(|| {
+                            format!("unable to read tileset {}", path.display())
+                        })?;
+                        let document = Document::parse(&external)
+                            .with_context(|| format!("invalid XML in {}", path.display()))?;
+                        let dir = path.parent().unwrap_or(dir);
+                        tmx_tileset(document.root_element(), first_gid, dir)
+                            .with_context(|| format!("unable to load tileset {}", path.display()))?
+                    }
+                    None => tmx_tileset(child, first_gid, dir)?,
+                };
+                tilesets.push(tileset);
+            }
+            "layer" => layers.push(tmx_layer(child)?),
+            _ => {}
+        }
+    }
+    Ok(TileMap {
+        width: attribute(root, "width")?,
+        height: attribute(root, "height")?,
+        tile_size: Vec2::new(
+            attribute(root, "tilewidth")?,
+            attribute(root, "tileheight")?,
+        ),
+        tilesets,
+        layers,
+    })
+}
+
+/// First child element named `name`.
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn attribute<T>(node: Node, name: &str) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let tag = node.tag_name().name();
+    let value = node
+        .attribute(name)
+        .with_context(|| format!("<{tag}> has no {name} attribute"))?;
+    value
+        .parse()
+        .with_context(|| format!("invalid {name} {value:?} in <{tag}>"))
+}
+
+fn attribute_or<T>(node: Node, name: &str, default: T) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match node.attribute(name) {
+        Some(_) => attribute(node, name),
+        None => Ok(default),
+    }
+}
+
+fn tmx_tileset(node: Node, first_gid: u32, dir: &Path) -> anyhow::Result<Tileset> {
+    let name = node.attribute("name").unwrap_or_default().to_owned();
+    let image = child(node, "image").with_context(|| format!("tileset {name} has no image"))?;
+    Ok(Tileset {
+        first_gid,
+        image: dir.join(image.attribute("source").context("image has no source")?),
+        image_size: PhysicalSize::new(attribute(image, "width")?, attribute(image, "height")?),
+        tile_size: PhysicalSize::new(
+            attribute(node, "tilewidth")?,
+            attribute(node, "tileheight")?,
+        ),
+        columns: attribute(node, "columns")?,
+        tile_count: attribute(node, "tilecount")?,
+        margin: attribute_or(node, "margin", 0)?,
+        spacing: attribute_or(node, "spacing", 0)?,
+        name,
+    })
+}
+
+fn tmx_layer(node: Node) -> anyhow::Result<TileLayer> {
+    let name = node.attribute("name").unwrap_or_default().to_owned();
+    let data = child(node, "data").with_context(|| format!("layer {name} has no data"))?;
+    let encoding = data.attribute("encoding").unwrap_or("xml");
+    ensure!(
+        encoding == "csv",
+        "{encoding} encoded layer data is not supported, save the map with CSV layers"
+    );
+    let tiles = data
+        .text()
+        .unwrap_or_default()
+        .split(',')
+        .map(|gid| gid.trim())
+        .filter(|gid| !gid.is_empty())
+        .map(|gid| {
+            gid.parse::<u32>()
+                .with_context(|| format!("invalid tile {gid} in layer {name}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let layer = TileLayer::new(
+        name,
+        attribute(node, "width")?,
+        attribute(node, "height")?,
+        tiles,
+    )?;
+    Ok(TileLayer {
+        offset: Vec2::new(
+            attribute_or(node, "offsetx", 0.0)?,
+            attribute_or(node, "offsety", 0.0)?,
+        ),
+        parallax: Vec2::new(
+            attribute_or(node, "parallaxx", 1.0)?,
+            attribute_or(node, "parallaxy", 1.0)?,
+        ),
+        opacity: attribute_or(node, "opacity", 1.0)?,
+        visible: node.attribute("visible") != Some("0"),
+        ..layer
+    })
+}
+
+fn orthogonal() -> String {
+    "orthogonal".to_owned()
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn yes() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct JsonMap {
+    #[serde(default = "orthogonal")]
+    orientation: String,
+    #[serde(default)]
+    infinite: bool,
+    width: u32,
+    height: u32,
+    tilewidth: f32,
+    tileheight: f32,
+    tilesets: Vec<JsonTilesetRef>,
+    layers: Vec<JsonLayer>,
+}
+
+/// An embedded tileset, or the path of an external one.
+#[derive(Deserialize)]
+struct JsonTilesetRef {
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(flatten)]
+    embedded: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct JsonTileset {
+    #[serde(default)]
+    name: String,
+    image: String,
+    imagewidth: u32,
+    imageheight: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    columns: u32,
+    tilecount: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    spacing: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonLayer {
+    TileLayer(JsonTileLayer),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct JsonTileLayer {
+    #[serde(default)]
+    name: String,
+    width: u32,
+    height: u32,
+    data: JsonLayerData,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    #[serde(default = "one")]
+    parallaxx: f32,
+    #[serde(default = "one")]
+    parallaxy: f32,
+    #[serde(default = "one")]
+    opacity: f32,
+    #[serde(default = "yes")]
+    visible: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonLayerData {
+    Tiles(Vec<u32>),
+    /// base64, optionally compressed
+    Encoded(String),
+}
+
+fn parse_json_map(source: &str, dir: &Path) -> anyhow::Result<TileMap> {
+    let map: JsonMap = serde_json::from_str(source).context("invalid JSON map")?;
+    ensure!(
+        map.orientation == "orthogonal",
+        "{} maps are not supported",
+        map.orientation
+    );
+    ensure!(!map.infinite, "infinite maps are not supported");
+
+    let mut tilesets = Vec::new();
+    for tileset in map.tilesets {
+        let tileset = match tileset.source {
+            Some(source) => {
+                let path = dir.join(source);
+                let external = fs::read_to_string(&path)
+                    .with_context(|| format!("unable to read tileset {}", path.display()))?;
+                let dir = path.parent().unwrap_or(dir);
+                serde_json::from_str(&external)
+                    .context("invalid JSON tileset")
+                    .map(|parsed| json_tileset(parsed, tileset.firstgid, dir))
+                    .with_context(|| format!("unable to load tileset {}", path.display()))?
+            }
+            None => serde_json::from_value(tileset.embedded)
+                .context("invalid embedded tileset")
+                .map(|embedded| json_tileset(embedded, tileset.firstgid, dir))?,
+        };
+        tilesets.push(tileset);
+    }
+    let layers = map
+        .layers
+        .into_iter()
+        .filter_map(|layer| match layer {
+            JsonLayer::TileLayer(layer) => Some(json_layer(layer)),
+            JsonLayer::Other => None,
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(TileMap {
+        width: map.width,
+        height: map.height,
+        tile_size: Vec2::new(map.tilewidth, map.tileheight),
+        tilesets,
+        layers,
+    })
+}
+
+fn json_tileset(tileset: JsonTileset, first_gid: u32, dir: &Path) -> Tileset {
+    Tileset {
+        first_gid,
+        name: tileset.name,
+        image: dir.join(tileset.image),
+        image_size: PhysicalSize::new(tileset.imagewidth, tileset.imageheight),
+        tile_size: PhysicalSize::new(tileset.tilewidth, tileset.tileheight),
+        columns: tileset.columns,
+        tile_count: tileset.tilecount,
+        margin: tileset.margin,
+        spacing: tileset.spacing,
+    }
+}
+
+fn json_layer(layer: JsonTileLayer) -> anyhow::Result<TileLayer> {
+    let tiles = match layer.data {
+        JsonLayerData::Tiles(tiles) => tiles,
+        JsonLayerData::Encoded(_) => bail!(
+            "encoded data of layer {} is not supported, save the map with CSV layers",
+            layer.name
+        ),
+    };
+    let tile_layer = TileLayer::new(layer.name, layer.width, layer.height, tiles)?;
+    Ok(TileLayer {
+        offset: Vec2::new(layer.offsetx, layer.offsety),
+        parallax: Vec2::new(layer.parallaxx, layer.parallaxy),
+        opacity: layer.opacity,
+        visible: layer.visible,
+        ..tile_layer
+    })
+}
+
+#[test]
+fn test() {
+    let dir = Path::new("maps");
+    let tmx = parse_tmx(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+        <!-- saved by Tiled -->
+        <map orientation="orthogonal" width="2" height="1" tilewidth="16" tileheight="8">
+          <tileset firstgid="1" name="tiles &amp; more" tilewidth="16" tileheight="8" tilecount="4" columns="2">
+            <image source="tiles.png" width="32" height="16"/>
+          </tileset>
+          <layer name="ground" width="2" height="1" parallaxx="0.5" visible="0">
+            <data encoding="csv">
+1,2147483650
+</data>
+          </layer>
+          <objectgroup name="objects"/>
+        </map>"#,
+        dir,
+    )
+    .unwrap();
+    let json = parse_json_map(
+        r#"{"orientation": "orthogonal", "width": 2, "height": 1,
+            "tilewidth": 16, "tileheight": 8, "infinite": false,
+            "tilesets": [{"firstgid": 1, "name": "tiles & more", "tilewidth": 16,
+                "tileheight": 8, "tilecount": 4, "columns": 2,
+                "image": "tiles.png", "imagewidth": 32, "imageheight": 16}],
+            "layers": [{"type": "tilelayer", "name": "ground", "width": 2,
+                "height": 1, "parallaxx": 0.5, "visible": false,
+                "data": [1, 2147483650]},
+                {"type": "objectgroup", "name": "objects", "objects": []}]}"#,
+        dir,
+    )
+    .unwrap();
+    assert_eq!(tmx, json);
+    assert_eq!(tmx.tile_size, Vec2::new(16.0, 8.0));
+    assert_eq!(tmx.tilesets[0].name, "tiles & more");
+    assert_eq!(tmx.tilesets[0].image, Path::new("maps/tiles.png"));
+    let layer = &tmx.layers[0];
+    assert_eq!(layer.tiles, vec![1, 0x8000_0002]);
+    assert_eq!(layer.parallax, Vec2::new(0.5, 1.0));
+    assert!(!layer.visible);
+
+    assert!(parse_tmx("<map><layer></map>", dir).is_err());
+    assert!(parse_tmx(r#"<map orientation="isometric"/>"#, dir).is_err());
+    assert!(parse_tmx(
+        r#"<map width="1" height="1" tilewidth="1" tileheight="1">
+          <layer name="l" width="1" height="1"><data encoding="base64">AQAAAA==</data></layer>
+        </map>"#,
+        dir
+    )
+    .is_err());
+    assert!(parse_json_map(
+        r#"{"width": 1, "height": 1, "tilewidth": 1, "tileheight": 1, "tilesets": [],
+            "layers": [{"type": "tilelayer", "width": 1, "height": 1, "data": "AQAAAA=="}]}"#,
+        dir
+    )
+    .is_err());
+    assert!(parse_json_map("{} x", dir).is_err());
+}
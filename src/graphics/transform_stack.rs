@@ -1,4 +1,4 @@
-use glam::{Affine2, Vec2};
+use glam::{Affine2, Mat2, Vec2};
 
 use crate::ui::utils::geom::UIPos;
 
@@ -18,6 +18,11 @@ impl TransformStack {
         self.0.last().expect("empty stack")
     }
 
+    /// Top of the stack, identity if the stack is empty.
+    pub fn current(&self) -> Affine2 {
+        self.0.last().copied().unwrap_or_default()
+    }
+
     pub fn peek_mut(&mut self) -> &mut Affine2 {
         self.0.last_mut().expect("empty stack")
     }
@@ -35,8 +40,39 @@ impl TransformStack {
         *current = *current * *transform;
     }
 
+    /// Moves the origin by `offset`, in the current (transformed) units.
     pub fn translate(&mut self, offset: UIPos) {
-        self.peek_mut().translation += Vec2::from(offset);
+        self.apply(&Affine2::from_translation(offset.into()));
+    }
+
+    /// Rotates clockwise (UI y goes down) around the current origin.
+    pub fn rotate(&mut self, angle: f32) {
+        self.apply(&Affine2::from_angle(angle));
+    }
+
+    pub fn scale(&mut self, scale: Vec2) {
+        self.apply(&Affine2::from_scale(scale));
+    }
+
+    /// Shears by the angles between the axes and their skewed versions,
+    /// `x` slants the vertical axis and `y` the horizontal one.
+    pub fn skew(&mut self, angles: Vec2) {
+        let shear = Mat2::from_cols(
+            Vec2::new(1.0, angles.y.tan()),
+            Vec2::new(angles.x.tan(), 1.0),
+        );
+        self.apply(&Affine2::from_mat2(shear));
+    }
+
+    /// Position in window UI units of `pos`, in the current units.
+    pub fn to_screen(&self, pos: UIPos) -> UIPos {
+        self.current().transform_point2(pos.into()).into()
+    }
+
+    /// Inverse of `to_screen`, e.g. to hit-test the cursor against what is
+    /// drawn with the current transform.
+    pub fn to_local(&self, pos: UIPos) -> UIPos {
+        self.current().inverse().transform_point2(pos.into()).into()
     }
 
     pub fn clear(&mut self) {
@@ -47,3 +83,24 @@ impl TransformStack {
         *self.peek_mut() = Affine2::IDENTITY;
     }
 }
+
+#[test]
+fn test() {
+    use std::f32::consts::FRAC_PI_2;
+
+    let mut stack = TransformStack::default();
+    stack.push();
+    stack.translate(UIPos::new(10.0, 0.0));
+    stack.rotate(FRAC_PI_2);
+    stack.scale(Vec2::splat(2.0));
+    let screen = stack.to_screen(UIPos::new(1.0, 0.0));
+    assert_eq!(screen, UIPos::new(10.0, 2.0));
+    assert_eq!(stack.to_local(screen), UIPos::new(1.0, 0.0));
+
+    stack.push();
+    stack.reset_current_transform();
+    stack.skew(Vec2::new(std::f32::consts::FRAC_PI_4, 0.0));
+    assert_eq!(stack.to_screen(UIPos::new(0.0, 3.0)), UIPos::new(3.0, 3.0));
+    stack.pop();
+    assert_eq!(stack.to_screen(UIPos::new(1.0, 0.0)), screen);
+}
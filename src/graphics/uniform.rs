@@ -0,0 +1,337 @@
+use std::{borrow::Cow, cell::Cell, ffi::CStr, time::Instant};
+
+use anyhow::Context;
+use gl::types::GLuint;
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
+
+use crate::{exec::server::draw, utils::error::ResultExt};
+
+use super::{
+    context::DrawContext,
+    wrappers::buffer::{BufferHandle, BufferTarget},
+};
+
+/// Binding point of the `FrameData` block.
+pub const FRAME_DATA_BINDING: GLuint = 0;
+
+/// A value of a uniform block with the std140 layout: scalars are aligned
+/// to their size, `vec2` to 8 bytes, everything else (`vec3`, `vec4`,
+/// matrix columns, array elements and structs) to 16 bytes.
+pub trait Std140 {
+    const ALIGN: usize;
+
+    /// Appends the value, `out` is already aligned to `ALIGN`.
+    fn write_std140(&self, out: &mut Std140Writer);
+}
+
+/// Implements `Std140` for a struct from its fields, in declaration order.
+#[macro_export]
+macro_rules! impl_std140 {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::graphics::uniform::Std140 for $ty {
+            const ALIGN: usize = 16;
+
+            fn write_std140(&self, out: &mut $crate::graphics::uniform::Std140Writer) {
+                $(out.push(&self.$field);)*
+                out.align(16);
+            }
+        }
+    };
+}
+
+pub use impl_std140;
+
+/// Bytes of a uniform block, see `Std140`.
+#[derive(Default)]
+pub struct Std140Writer {
+    bytes: Vec<u8>,
+}
+
+impl Std140Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn align(&mut self, align: usize) {
+        let len = (self.bytes.len() + align - 1) / align * align;
+        self.bytes.resize(len, 0);
+    }
+
+    pub fn push<T: Std140 + ?Sized>(&mut self, value: &T) -> &mut Self {
+        self.align(T::ALIGN);
+        value.write_std140(self);
+        self
+    }
+
+    fn floats(&mut self, floats: &[f32]) {
+        self.bytes.extend_from_slice(bytemuck::cast_slice(floats));
+    }
+
+    /// The bytes, padded to a whole block.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.align(16);
+        self.bytes
+    }
+}
+
+macro_rules! impl_std140_scalar {
+    ($($ty:ty),*) => {
+        $(impl Std140 for $ty {
+            const ALIGN: usize = 4;
+
+            fn write_std140(&self, out: &mut Std140Writer) {
+                out.bytes.extend_from_slice(&self.to_ne_bytes());
+            }
+        })*
+    };
+}
+
+impl_std140_scalar!(f32, i32, u32);
+
+impl Std140 for Vec2 {
+    const ALIGN: usize = 8;
+
+    fn write_std140(&self, out: &mut Std140Writer) {
+        out.floats(&self.to_array());
+    }
+}
+
+impl Std140 for Vec3 {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Std140Writer) {
+        out.floats(&self.to_array());
+    }
+}
+
+impl Std140 for Vec4 {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Std140Writer) {
+        out.floats(&self.to_array());
+    }
+}
+
+impl Std140 for Mat3 {
+    const ALIGN: usize = 16;
+
+    /// The columns are padded like arrays of `vec3`.
+    fn write_std140(&self, out: &mut Std140Writer) {
+        for column in self.to_cols_array_2d() {
+            out.floats(&column);
+            out.align(16);
+        }
+    }
+}
+
+impl Std140 for Mat4 {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Std140Writer) {
+        out.floats(&self.to_cols_array());
+    }
+}
+
+impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Std140Writer) {
+        for element in self {
+            out.align(16);
+            element.write_std140(out);
+        }
+        out.align(16);
+    }
+}
+
+/// Buffer bound to a uniform block binding point, read by every program
+/// whose block is bound to it with `bind_block`.
+#[derive(Clone)]
+pub struct UniformBuffer {
+    buffer: BufferHandle,
+    binding: GLuint,
+}
+
+impl UniformBuffer {
+    pub fn new(
+        draw: &mut draw::ServerChannel,
+        name: impl Into<Cow<'static, str>> + Send + 'static,
+        binding: GLuint,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            buffer: BufferHandle::new(draw, name, BufferTarget::UniformBuffer)?,
+            binding,
+        })
+    }
+
+    /// Uploads `value` and binds the buffer to its binding point.
+    pub fn upload<T: Std140>(&self, ctx: &DrawContext, value: &T) -> anyhow::Result<()> {
+        let buffer = self
+            .buffer
+            .try_get(ctx)
+            .context("uniform buffer was not created")?;
+        let mut writer = Std140Writer::new();
+        writer.push(value);
+        buffer.stream_slice(&writer.finish())?;
+        unsafe { gl::BindBufferBase(gl::UNIFORM_BUFFER, self.binding, *buffer) };
+        Ok(())
+    }
+
+    /// Makes the block `name` of `program` read this buffer, returns
+    /// whether the program declares it.
+    pub fn bind_block(&self, program: GLuint, name: &CStr) -> bool {
+        unsafe {
+            let index = gl::GetUniformBlockIndex(program, name.as_ptr());
+            if index == gl::INVALID_INDEX {
+                return false;
+            }
+            gl::UniformBlockBinding(program, index, self.binding);
+        }
+        true
+    }
+}
+
+/// Contents of the `FrameData` block, declared in shaders as:
+///
+/// ```glsl
+/// layout(std140) uniform FrameData {
+///     mat4 ui_projection;
+///     vec2 ui_size;
+///     vec2 resolution;
+///     float time;
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameData {
+    /// window UI units to clip space, y pointing down
+    pub ui_projection: Mat4,
+    pub ui_size: Vec2,
+    /// in pixels
+    pub resolution: Vec2,
+    /// seconds since the draw server started
+    pub time: f32,
+}
+
+impl_std140!(FrameData {
+    ui_projection,
+    ui_size,
+    resolution,
+    time,
+});
+
+/// The `FrameData` block shared by the renderers, uploaded once per frame
+/// instead of setting the same uniforms on every program.
+pub struct FrameUniforms {
+    buffer: UniformBuffer,
+    start: Instant,
+    uploaded: Cell<Option<FrameData>>,
+}
+
+impl FrameUniforms {
+    pub fn new(draw: &mut draw::ServerChannel) -> anyhow::Result<Self> {
+        Ok(Self {
+            buffer: UniformBuffer::new(draw, "frame data uniform buffer", FRAME_DATA_BINDING)?,
+            start: Instant::now(),
+            uploaded: Cell::new(None),
+        })
+    }
+
+    /// Makes the `FrameData` block of `program` read the frame data.
+    pub fn bind_block(&self, program: GLuint) -> bool {
+        // SAFETY: nul-terminated, no nul inside
+        let name = unsafe { CStr::from_bytes_with_nul_unchecked(b"FrameData\0") };
+        self.buffer.bind_block(program, name)
+    }
+}
+
+impl DrawContext {
+    pub fn frame_data(&self) -> FrameData {
+        let ui_size = Vec2::from(self.ui_size);
+        FrameData {
+            ui_projection: Mat4::from_cols(
+                Vec4::new(2.0 / ui_size.x, 0.0, 0.0, 0.0),
+                Vec4::new(0.0, -2.0 / ui_size.y, 0.0, 0.0),
+                Vec4::Z,
+                Vec4::new(-1.0, 1.0, 0.0, 1.0),
+            ),
+            ui_size,
+            resolution: Vec2::new(
+                self.display_size.width.get() as f32,
+                self.display_size.height.get() as f32,
+            ),
+            time: self.frame_uniforms.start.elapsed().as_secs_f32(),
+        }
+    }
+
+    /// Uploads the frame data at the start of a frame (`force`), or again
+    /// if the sizes changed since, e.g. while drawing offscreen.
+    pub fn upload_frame_data(&self, force: bool) {
+        let data = self.frame_data();
+        let uploaded = self.frame_uniforms.uploaded.get();
+        let stale = uploaded.map_or(true, |uploaded| {
+            (uploaded.ui_size, uploaded.resolution) != (data.ui_size, data.resolution)
+        });
+        if !force && !stale {
+            return;
+        }
+        if self
+            .frame_uniforms
+            .buffer
+            .upload(self, &data)
+            .context("unable to upload frame data")
+            .log_warn()
+            .is_some()
+        {
+            self.frame_uniforms.uploaded.set(Some(data));
+        }
+    }
+}
+
+#[test]
+fn test() {
+    struct Light {
+        position: Vec3,
+        radius: f32,
+        weights: [f32; 2],
+        color: Vec2,
+    }
+    impl_std140!(Light {
+        position,
+        radius,
+        weights,
+        color
+    });
+
+    let light = Light {
+        position: Vec3::new(1.0, 2.0, 3.0),
+        radius: 4.0,
+        weights: [5.0, 6.0],
+        color: Vec2::new(7.0, 8.0),
+    };
+    let mut writer = Std140Writer::new();
+    writer.push(&1.0f32).push(&light).push(&Mat3::IDENTITY);
+    let floats = writer
+        .finish()
+        .chunks_exact(4)
+        .map(|bytes| f32::from_ne_bytes(bytes.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    // the struct starts at 16, the vec3 is followed by the float, array
+    // elements take 16 bytes each
+    assert_eq!(floats.len(), 4 + 4 + 8 + 4 + 12);
+    assert_eq!(&floats[4..8], &[1.0, 2.0, 3.0, 4.0]);
+    assert_eq!((floats[8], floats[12]), (5.0, 6.0));
+    assert_eq!(&floats[16..18], &[7.0, 8.0]);
+    assert_eq!(&floats[20..24], &[1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(&floats[24..27], &[0.0, 1.0, 0.0]);
+
+    let data = FrameData {
+        ui_projection: Mat4::IDENTITY,
+        ui_size: Vec2::ONE,
+        resolution: Vec2::ONE,
+        time: 0.0,
+    };
+    let mut writer = Std140Writer::new();
+    writer.push(&data);
+    // mat4, two vec2 and a float padded to a whole block
+    assert_eq!(writer.finish().len(), 96);
+}
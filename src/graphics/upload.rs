@@ -0,0 +1,332 @@
+use std::{
+    collections::VecDeque,
+    mem,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use bytemuck::Pod;
+use trait_set::trait_set;
+
+use crate::{
+    exec::{
+        query::{self, ServerQuery},
+        server::draw::{self, ServerSendChannelExt},
+    },
+    utils::error::ResultExt,
+};
+
+use super::{
+    context::DrawContext,
+    wrappers::{
+        buffer::{Buffer, BufferHandle, BufferTarget},
+        fence::Fence,
+        texture::{ImageData, Texture, TextureHandle, TextureType},
+    },
+};
+
+/// Time spent uploading per frame by default.
+pub const DEFAULT_UPLOAD_BUDGET: Duration = Duration::from_micros(2000);
+
+/// Bytes uploaded per step, the budget is checked between steps.
+const CHUNK_BYTES: usize = 256 * 1024;
+
+trait_set! {
+    pub trait UploadCallback = FnOnce(&mut DrawContext, anyhow::Result<()>) + Send;
+}
+
+enum UploadData {
+    Texture {
+        handle: TextureHandle,
+        staging: TextureHandle,
+        image: ImageData,
+    },
+    Buffer {
+        handle: BufferHandle,
+        staging: BufferHandle,
+        bytes: Vec<u8>,
+    },
+}
+
+struct UploadJob {
+    data: UploadData,
+    /// rows or bytes uploaded so far
+    uploaded: usize,
+    on_done: Box<dyn UploadCallback>,
+}
+
+/// Large uploads split into chunks spread across frames, under a per-frame
+/// time budget. The data goes into a staging object swapped into the
+/// handle once complete, so the handle keeps its old contents until then.
+///
+/// Texture rows go through a pixel unpack buffer, so the GPU copies them
+/// while the draw server carries on. Uploads are only complete once a fence
+/// placed after the last chunk is signaled, the server never waits for one.
+pub struct UploadScheduler {
+    jobs: VecDeque<UploadJob>,
+    /// every chunk was issued, waiting for the GPU to be done with them
+    fenced: Vec<(UploadJob, Fence)>,
+    pixels: BufferHandle,
+    pub budget: Duration,
+}
+
+impl UploadScheduler {
+    pub fn new(draw: &mut draw::ServerChannel) -> anyhow::Result<Self> {
+        Ok(Self {
+            jobs: VecDeque::new(),
+            fenced: Vec::new(),
+            pixels: BufferHandle::new(
+                draw,
+                "upload pixel buffer",
+                BufferTarget::PixelUnpackBuffer,
+            )?,
+            budget: DEFAULT_UPLOAD_BUDGET,
+        })
+    }
+
+    /// Uploads `image` into `handle` through `staging`, a handle that wasn't
+    /// created yet (see `GLGfxHandle::new_uninit`).
+    pub fn push_texture(
+        &mut self,
+        handle: TextureHandle,
+        staging: TextureHandle,
+        image: ImageData,
+        on_done: impl UploadCallback + 'static,
+    ) {
+        self.push(
+            UploadData::Texture {
+                handle,
+                staging,
+                image,
+            },
+            on_done,
+        );
+    }
+
+    /// Uploads `bytes` into `handle` through `staging`, a handle that wasn't
+    /// created yet (see `GLGfxHandle::new_uninit`).
+    pub fn push_buffer(
+        &mut self,
+        handle: BufferHandle,
+        staging: BufferHandle,
+        bytes: Vec<u8>,
+        on_done: impl UploadCallback + 'static,
+    ) {
+        self.push(
+            UploadData::Buffer {
+                handle,
+                staging,
+                bytes,
+            },
+            on_done,
+        );
+    }
+
+    fn push(&mut self, data: UploadData, on_done: impl UploadCallback + 'static) {
+        self.jobs.push_back(UploadJob {
+            data,
+            uploaded: 0,
+            on_done: Box::new(on_done),
+        });
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.jobs.is_empty() && self.fenced.is_empty()
+    }
+
+    /// Drops every upload without calling back, the fences have to be
+    /// deleted while the context is still current.
+    pub fn clear(&mut self) {
+        self.jobs.clear();
+        self.fenced.clear();
+    }
+}
+
+/// Units of `total` uploaded by the step after `uploaded`, of `unit_bytes`
+/// each, at least one.
+fn next_chunk(uploaded: usize, total: usize, unit_bytes: usize) -> usize {
+    let units = (CHUNK_BYTES / unit_bytes.max(1)).max(1);
+    (total - uploaded).min(units)
+}
+
+impl UploadJob {
+    /// Uploads the next chunk, returns whether it was the last one.
+    fn step(&mut self, ctx: &mut DrawContext) -> anyhow::Result<bool> {
+        match &self.data {
+            UploadData::Texture {
+                handle,
+                staging,
+                image,
+            } => {
+                let staged = match staging.try_get(ctx) {
+                    Some(staged) => staged,
+                    None => {
+                        let name = handle
+                            .try_get(ctx)
+                            .context("texture was not created")?
+                            .name();
+                        let texture = Texture::new_args(name, TextureType::E2D)?;
+                        texture.allocate(image.size, &image.options)?;
+                        ctx.handles.textures.insert(staging, texture)
+                    }
+                };
+                let height = image.size.height as usize;
+                let rows = next_chunk(self.uploaded, height, image.size.width as usize * 4);
+                let end = self.uploaded + rows;
+                let range = self.uploaded as u32..end as u32;
+                match ctx.uploads.pixels.try_get(ctx) {
+                    Some(pixels) => staged.upload_rows_unpacked(image, range, &pixels)?,
+                    None => staged.upload_rows(image, range)?,
+                }
+                self.uploaded = end;
+                Ok(end == height)
+            }
+            UploadData::Buffer {
+                handle,
+                staging,
+                bytes,
+            } => {
+                let staged = match staging.try_get(ctx) {
+                    Some(staged) => staged,
+                    None => {
+                        let buffer = handle.try_get(ctx).context("buffer was not created")?;
+                        let staged = Buffer::new_args(buffer.name(), buffer.target())?;
+                        staged.allocate(bytes.len())?;
+                        ctx.handles.buffers.insert(staging, staged)
+                    }
+                };
+                let end = self.uploaded + next_chunk(self.uploaded, bytes.len(), 1);
+                staged.upload_range(self.uploaded, &bytes[self.uploaded..end])?;
+                self.uploaded = end;
+                Ok(end == bytes.len())
+            }
+        }
+    }
+
+    /// Swaps the staging object into the handle once the GPU is done.
+    fn finish(&self, ctx: &mut DrawContext) -> anyhow::Result<()> {
+        match &self.data {
+            UploadData::Texture {
+                handle, staging, ..
+            } => {
+                handle
+                    .try_get(ctx)
+                    .context("texture was deleted during the upload")?;
+                let staged = unsafe { ctx.handles.textures.remove(&staging.0.handle) };
+                ctx.handles
+                    .textures
+                    .replace(handle, |_| staged.context("staging texture was deleted"))?;
+            }
+            UploadData::Buffer {
+                handle, staging, ..
+            } => {
+                handle
+                    .try_get(ctx)
+                    .context("buffer was deleted during the upload")?;
+                let staged = unsafe { ctx.handles.buffers.remove(&staging.0.handle) };
+                ctx.handles
+                    .buffers
+                    .replace(handle, |_| staged.context("staging buffer was deleted"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DrawContext {
+    /// Completes the uploads the GPU is done with, then uploads chunks of
+    /// the scheduled ones until `budget` is spent, at least one chunk.
+    pub fn run_uploads(&mut self, budget: Duration) {
+        for (job, fence) in mem::take(&mut self.uploads.fenced) {
+            if fence.is_signaled() {
+                let result = job.finish(self);
+                (job.on_done)(self, result);
+            } else {
+                self.uploads.fenced.push((job, fence));
+            }
+        }
+        if self.uploads.jobs.is_empty() {
+            return;
+        }
+        let start = Instant::now();
+        let mut jobs = mem::take(&mut self.uploads.jobs);
+        while let Some(job) = jobs.front_mut() {
+            match job.step(self) {
+                Ok(false) => {}
+                Ok(true) => {
+                    let job = jobs.pop_front().unwrap();
+                    self.uploads.fenced.push((job, Fence::insert()));
+                }
+                Err(e) => {
+                    let job = jobs.pop_front().unwrap();
+                    (job.on_done)(self, Err(e));
+                }
+            }
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+        // scheduled by the callbacks
+        jobs.append(&mut self.uploads.jobs);
+        self.uploads.jobs = jobs;
+    }
+}
+
+impl TextureHandle {
+    /// Uploads `image` to the texture with the upload scheduler, the
+    /// texture keeps its contents until the query resolves.
+    pub fn upload_sliced(
+        &self,
+        draw: &mut draw::ServerChannel,
+        image: ImageData,
+    ) -> anyhow::Result<ServerQuery<anyhow::Result<()>>> {
+        let staging = unsafe { Self::new_uninit(draw) };
+        let (ret, query) = query::query();
+        let handle = self.clone();
+        draw.execute(move |context, _| {
+            context
+                .uploads
+                .push_texture(handle, staging, image, move |context, result| {
+                    ret.send(result, &context.base.proxy)
+                        .context("unable to return texture upload result")
+                        .log_warn();
+                });
+        })?;
+        Ok(query)
+    }
+}
+
+impl BufferHandle {
+    /// Uploads `data` to the buffer with the upload scheduler, the buffer
+    /// keeps its contents until the query resolves.
+    pub fn upload_sliced<T: Pod + Send>(
+        &self,
+        draw: &mut draw::ServerChannel,
+        data: Vec<T>,
+    ) -> anyhow::Result<ServerQuery<anyhow::Result<()>>> {
+        let staging = unsafe { Self::new_uninit(draw) };
+        let (ret, query) = query::query();
+        let handle = self.clone();
+        draw.execute(move |context, _| {
+            let bytes = bytemuck::cast_slice::<T, u8>(&data).to_vec();
+            context
+                .uploads
+                .push_buffer(handle, staging, bytes, move |context, result| {
+                    ret.send(result, &context.base.proxy)
+                        .context("unable to return buffer upload result")
+                        .log_warn();
+                });
+        })?;
+        Ok(query)
+    }
+}
+
+#[test]
+fn test() {
+    let rows = CHUNK_BYTES / 4096;
+    assert_eq!(next_chunk(0, 1000, 4096), rows);
+    assert_eq!(next_chunk(1000 - 3, 1000, 4096), 3);
+    // rows wider than a chunk are still uploaded one at a time
+    assert_eq!(next_chunk(0, 10, CHUNK_BYTES * 2), 1);
+    assert_eq!(next_chunk(0, CHUNK_BYTES + 1, 1), CHUNK_BYTES);
+}
@@ -1,14 +1,28 @@
-use gl::types::{GLenum, GLuint};
+use std::{borrow::Cow, ptr};
 
-use crate::graphics::context::DrawContext;
+use anyhow::{bail, Context};
+use bytemuck::Pod;
+use gl::types::{GLenum, GLintptr, GLsizeiptr, GLuint};
+
+use crate::{
+    enclose,
+    events::{error::Subsystem, GameUserEvent},
+    exec::server::draw::{self, ServerSendChannelExt},
+    graphics::context::DrawContext,
+};
 
 use super::{GLGfxHandle, GLHandle, GLHandleContainer, GLHandleTrait, SendGLHandleContainer};
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum BufferTarget {
     ArrayBuffer = gl::ARRAY_BUFFER as _,
+    ElementArrayBuffer = gl::ELEMENT_ARRAY_BUFFER as _,
     UniformBuffer = gl::UNIFORM_BUFFER as _,
     ShaderStorageBuffer = gl::SHADER_STORAGE_BUFFER as _,
+    /// destination of `glReadPixels` while bound
+    PixelPackBuffer = gl::PIXEL_PACK_BUFFER as _,
+    /// source of `glTexSubImage2D` while bound
+    PixelUnpackBuffer = gl::PIXEL_UNPACK_BUFFER as _,
 }
 
 pub struct BufferTrait;
@@ -36,6 +50,10 @@ impl GLHandleTrait<BufferTarget> for BufferTrait {
         gl::BUFFER
     }
 
+    fn type_name() -> &'static str {
+        "buffer"
+    }
+
     fn delete_mul(handles: &[GLuint]) {
         unsafe { gl::DeleteBuffers(handles.len().try_into().unwrap(), handles.as_ptr()) }
     }
@@ -50,3 +68,172 @@ impl GLHandleTrait<BufferTarget> for BufferTrait {
         Some(&context.handles.buffers)
     }
 }
+
+impl Buffer {
+    pub fn target(&self) -> BufferTarget {
+        *self.args()
+    }
+
+    /// (Re)allocates the buffer storage with `data`, for data that is
+    /// written once and drawn many times.
+    pub fn upload_slice<T: Pod>(&self, data: &[T]) -> anyhow::Result<()> {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let size: GLsizeiptr = bytes.len().try_into()?;
+        self.bind();
+        unsafe {
+            gl::BufferData(
+                self.target() as GLenum,
+                size,
+                bytes.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+        }
+        self.unbind();
+        self.set_estimated_size(bytes.len());
+        Ok(())
+    }
+
+    /// (Re)allocates uninitialized storage of `size` bytes, filled
+    /// afterwards with `upload_range` (or by the GPU for pixel pack
+    /// buffers).
+    pub fn allocate(&self, size: usize) -> anyhow::Result<()> {
+        self.set_estimated_size(size);
+        let size: GLsizeiptr = size.try_into()?;
+        let usage = match self.target() {
+            BufferTarget::PixelPackBuffer => gl::STREAM_READ,
+            BufferTarget::PixelUnpackBuffer => gl::STREAM_DRAW,
+            _ => gl::STATIC_DRAW,
+        };
+        self.bind();
+        unsafe { gl::BufferData(self.target() as GLenum, size, ptr::null(), usage) };
+        self.unbind();
+        Ok(())
+    }
+
+    /// Copies `len` bytes at `offset` of the buffer storage back, blocks
+    /// until the GPU is done writing them (see `Fence` to avoid that).
+    pub fn read_range(&self, offset: usize, len: usize) -> anyhow::Result<Vec<u8>> {
+        let target = self.target() as GLenum;
+        self.bind();
+        let bytes = unsafe {
+            let mapped = gl::MapBufferRange(
+                target,
+                offset.try_into()?,
+                len.try_into()?,
+                gl::MAP_READ_BIT,
+            ) as *const u8;
+            if mapped.is_null() {
+                self.unbind();
+                bail!("unable to map {len} bytes of buffer {}", self.name());
+            }
+            let bytes = std::slice::from_raw_parts(mapped, len).to_vec();
+            gl::UnmapBuffer(target);
+            bytes
+        };
+        self.unbind();
+        Ok(bytes)
+    }
+
+    /// Writes `bytes` at `offset` of the buffer storage.
+    pub fn upload_range(&self, offset: usize, bytes: &[u8]) -> anyhow::Result<()> {
+        let offset: GLintptr = offset.try_into()?;
+        let size: GLsizeiptr = bytes.len().try_into()?;
+        self.bind();
+        unsafe {
+            gl::BufferSubData(
+                self.target() as GLenum,
+                offset,
+                size,
+                bytes.as_ptr() as *const _,
+            )
+        };
+        self.unbind();
+        Ok(())
+    }
+
+    /// Replaces the buffer contents with `data`, for data rewritten every
+    /// frame. The old storage is orphaned first, so the driver doesn't have
+    /// to wait for draw calls still reading it.
+    pub fn stream_slice<T: Pod>(&self, data: &[T]) -> anyhow::Result<()> {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let size: GLsizeiptr = bytes.len().try_into()?;
+        let target = self.target() as GLenum;
+        self.bind();
+        unsafe {
+            gl::BufferData(target, size, ptr::null(), gl::STREAM_DRAW);
+            gl::BufferSubData(target, 0, size, bytes.as_ptr() as *const _);
+        }
+        self.unbind();
+        self.set_estimated_size(bytes.len());
+        Ok(())
+    }
+}
+
+impl BufferHandle {
+    /// Creates an empty buffer on the draw server.
+    pub fn new(
+        draw: &mut draw::ServerChannel,
+        name: impl Into<Cow<'static, str>> + Send + 'static,
+        target: BufferTarget,
+    ) -> anyhow::Result<Self> {
+        Self::new_args(draw, name, target)
+    }
+
+    /// Creates a buffer on the draw server holding `data`, see
+    /// `Buffer::upload_slice`.
+    pub fn with_data<T: Pod + Send>(
+        draw: &mut draw::ServerChannel,
+        name: impl Into<Cow<'static, str>> + Send + 'static,
+        target: BufferTarget,
+        data: Vec<T>,
+    ) -> anyhow::Result<Self> {
+        let handle = unsafe { Self::new_uninit(draw) };
+        draw.execute_draw_event(enclose!((handle) move |context, _| {
+            context
+                .handles
+                .create_buffer(name, &handle, target)
+                .and_then(|buffer| buffer.upload_slice(&data))
+                .err()
+                .map(|e| GameUserEvent::error(Subsystem::Draw, "draw.create_buffer", e))
+        }))?;
+        Ok(handle)
+    }
+
+    /// Sends `data` to the draw server to be uploaded with
+    /// `Buffer::upload_slice`.
+    pub fn upload<T: Pod + Send>(
+        &self,
+        draw: &impl ServerSendChannelExt,
+        data: Vec<T>,
+    ) -> anyhow::Result<()> {
+        let handle = self.clone();
+        draw.execute_draw_event(move |context, _| {
+            handle.upload_result(context, |buffer| buffer.upload_slice(&data))
+        })
+    }
+
+    /// Sends `data` to the draw server to be uploaded with
+    /// `Buffer::stream_slice`.
+    pub fn stream<T: Pod + Send>(
+        &self,
+        draw: &impl ServerSendChannelExt,
+        data: Vec<T>,
+    ) -> anyhow::Result<()> {
+        let handle = self.clone();
+        draw.execute_draw_event(move |context, _| {
+            handle.upload_result(context, |buffer| buffer.stream_slice(&data))
+        })
+    }
+
+    fn upload_result(
+        &self,
+        context: &DrawContext,
+        upload: impl FnOnce(&Buffer) -> anyhow::Result<()>,
+    ) -> Option<GameUserEvent> {
+        self.try_get(context)
+            .context("buffer was not created")
+            .and_then(|buffer| upload(&buffer))
+            .err()
+            .map(|e| GameUserEvent::error(Subsystem::Draw, "draw.upload_buffer", e))
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use gl::types::GLsync;
+
+/// Sync object signaled once the GPU is done with every command issued
+/// before it, to find out when an asynchronous transfer completed without
+/// waiting for it.
+///
+/// Unlike the other wrappers it has no name to store in a handle container,
+/// it is owned by whatever waits on it and must be dropped on the draw
+/// server while the context is current.
+pub struct Fence(GLsync);
+
+// only ever used by the draw server, sync objects belong to the context
+// wherever the server moves it
+unsafe impl Send for Fence {}
+
+impl Fence {
+    pub fn insert() -> Self {
+        Self(unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) })
+    }
+
+    /// Doesn't block, a failed wait (e.g. lost context) counts as signaled
+    /// so that nothing waits on the fence forever.
+    pub fn is_signaled(&self) -> bool {
+        self.wait(Duration::ZERO)
+    }
+
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let timeout = timeout.as_nanos().try_into().unwrap_or(u64::MAX);
+        // flushing makes sure the fence is eventually reached
+        let status = unsafe { gl::ClientWaitSync(self.0, gl::SYNC_FLUSH_COMMANDS_BIT, timeout) };
+        status != gl::TIMEOUT_EXPIRED
+    }
+}
+
+impl Drop for Fence {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteSync(self.0) }
+    }
+}
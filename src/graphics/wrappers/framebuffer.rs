@@ -1,16 +1,18 @@
 use std::{borrow::Cow, ptr::null};
 
-use gl::types::{GLenum, GLuint};
+use anyhow::bail;
+use gl::types::{GLenum, GLint, GLuint};
 use glutin::prelude::GlConfig;
 use winit::dpi::PhysicalSize;
 
 use crate::{
-    events::GameUserEvent,
+    events::{error::Subsystem, GameUserEvent},
     exec::server::draw::{self, ServerSendChannelExt},
     graphics::context::DrawContext,
 };
 
 use super::{
+    renderbuffer::RenderbufferHandle,
     texture::{Texture, TextureHandle, TextureType},
     GLGfxHandle, GLHandle, GLHandleContainer, GLHandleTrait, SendGLHandleContainer,
 };
@@ -40,6 +42,10 @@ impl GLHandleTrait for FramebufferTrait {
         gl::FRAMEBUFFER
     }
 
+    fn type_name() -> &'static str {
+        "framebuffer"
+    }
+
     fn delete_mul(handles: &[GLuint]) {
         unsafe { gl::DeleteFramebuffers(handles.len().try_into().unwrap(), handles.as_ptr()) }
     }
@@ -78,7 +84,7 @@ impl DefaultTextureFramebuffer {
         Ok(slf)
     }
 
-    fn resize_in_server(
+    pub fn resize_in_server(
         &self,
         context: &mut DrawContext,
         size: PhysicalSize<u32>,
@@ -97,24 +103,28 @@ impl DefaultTextureFramebuffer {
                 (self.framebuffer.get(context), texture)
             }
         };
+        let (internal_format, pixel_type) = context
+            .surface_format
+            .color_target_format(context.gl_config.srgb_capable());
         unsafe {
             gl::BindFramebuffer(gl::FRAMEBUFFER, *framebuffer);
             gl::BindTexture(gl::TEXTURE_2D, *texture);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                if context.gl_config.srgb_capable() {
-                    gl::SRGB8_ALPHA8.try_into().unwrap()
-                } else {
-                    gl::RGBA8.try_into().unwrap()
-                },
+                internal_format,
                 size.width.try_into().unwrap(),
                 size.height.try_into().unwrap(),
                 0,
                 gl::RGBA,
-                gl::UNSIGNED_BYTE,
+                pixel_type,
                 null(),
             );
+            texture.set_estimated_size(
+                size.width as usize
+                    * size.height as usize
+                    * context.surface_format.bytes_per_pixel(),
+            );
             gl::TexParameteri(
                 gl::TEXTURE_2D,
                 gl::TEXTURE_MIN_FILTER,
@@ -162,8 +172,150 @@ impl DefaultTextureFramebuffer {
         draw.execute_draw_event(move |context, _| {
             slf.resize_in_server(context, new_size)
                 .err()
-                .map(GameUserEvent::Error)
+                .map(|e| GameUserEvent::error(Subsystem::Draw, "draw.resize_framebuffer", e))
         })?;
         Ok(())
     }
 }
+
+/// Multisampled color and depth-stencil renderbuffers, drawn into then
+/// resolved into a regular framebuffer, smoothing the edges of geometry.
+#[derive(Clone)]
+pub struct MultisampleFramebuffer {
+    pub framebuffer: FramebufferHandle,
+    pub color: RenderbufferHandle,
+    pub depth_stencil: RenderbufferHandle,
+    /// requested sample count, 0 or 1 disables multisampling
+    pub samples: u32,
+    /// size and effective sample count of the allocated storage
+    allocated: Option<(PhysicalSize<u32>, u32)>,
+}
+
+impl MultisampleFramebuffer {
+    pub fn new(
+        draw: &mut draw::ServerChannel,
+        name: impl Into<Cow<'static, str>>,
+        samples: u32,
+    ) -> anyhow::Result<Self> {
+        let name = name.into();
+        Ok(Self {
+            color: RenderbufferHandle::new(draw, format!("{name} color attachment"))?,
+            depth_stencil: RenderbufferHandle::new(
+                draw,
+                format!("{name} depth stencil attachment"),
+            )?,
+            framebuffer: FramebufferHandle::new(draw, name)?,
+            samples,
+            allocated: None,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.samples > 1
+    }
+
+    /// Sample count of the storage, `None` until it is allocated.
+    pub fn effective_samples(&self) -> Option<u32> {
+        self.allocated.map(|(_, samples)| samples)
+    }
+
+    /// Reallocates the storage if the size or the sample count changed, the
+    /// sample count is clamped to what the driver supports.
+    pub fn resize_in_server(
+        &mut self,
+        context: &mut DrawContext,
+        size: PhysicalSize<u32>,
+    ) -> anyhow::Result<()> {
+        let mut max_samples: GLint = 0;
+        unsafe { gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples) };
+        let samples = self.samples.min(max_samples.max(0) as u32);
+        if self.allocated == Some((size, samples)) {
+            return Ok(());
+        }
+
+        let framebuffer = self.framebuffer.get(context);
+        let color = self.color.get(context);
+        let depth_stencil = self.depth_stencil.get(context);
+        let (internal_format, _) = context
+            .surface_format
+            .color_target_format(context.gl_config.srgb_capable());
+        let (width, height) = (size.width.try_into()?, size.height.try_into()?);
+        let status = unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, *framebuffer);
+            for (renderbuffer, format, attachment, pixel_bytes) in [
+                (
+                    &color,
+                    internal_format as GLenum,
+                    gl::COLOR_ATTACHMENT0,
+                    context.surface_format.bytes_per_pixel(),
+                ),
+                (
+                    &depth_stencil,
+                    gl::DEPTH24_STENCIL8,
+                    gl::DEPTH_STENCIL_ATTACHMENT,
+                    4,
+                ),
+            ] {
+                gl::BindRenderbuffer(gl::RENDERBUFFER, **renderbuffer);
+                gl::RenderbufferStorageMultisample(
+                    gl::RENDERBUFFER,
+                    samples.try_into()?,
+                    format,
+                    width,
+                    height,
+                );
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::RENDERBUFFER,
+                    **renderbuffer,
+                );
+                renderbuffer.set_estimated_size(
+                    size.width as usize
+                        * size.height as usize
+                        * samples.max(1) as usize
+                        * pixel_bytes,
+                );
+            }
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            status
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            bail!(
+                "multisample framebuffer {} is incomplete ({status:#x})",
+                framebuffer.name()
+            );
+        }
+        self.allocated = Some((size, samples));
+        Ok(())
+    }
+
+    /// Averages the samples into the framebuffer `dst` of the same size, 0
+    /// being the screen, and leaves `dst` bound.
+    pub fn resolve(&self, context: &DrawContext, dst: GLuint) {
+        let (size, _) = match self.allocated {
+            Some(allocated) => allocated,
+            None => return,
+        };
+        let (width, height) = (size.width as GLint, size.height as GLint);
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, *self.framebuffer.get(context));
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, dst);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, dst);
+        }
+    }
+}
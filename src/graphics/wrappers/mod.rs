@@ -1,25 +1,39 @@
 use std::{
-    borrow::Cow, collections::HashMap, ffi::CString, marker::PhantomData, ops::Deref, sync::Arc,
+    borrow::Cow,
+    cell::Cell,
+    collections::HashMap,
+    ffi::{CStr, CString},
+    marker::PhantomData,
+    ops::Deref,
+    sync::Arc,
 };
 
 use anyhow::{bail, Context};
-use gl::types::{GLenum, GLuint};
+use gl::types::{GLenum, GLsizei, GLuint};
 use sendable::{send_rc::PostSend, SendRc};
 
 use crate::{
     enclose,
-    events::GameUserEvent,
+    events::{error::Subsystem, GameUserEvent},
     exec::server::{
         draw::{self, ServerSendChannelExt},
         GameServerSendChannel, ServerSendChannel,
     },
-    utils::{error::ResultExt, send_sync::PhantomUnsync, uid::Uid},
+    utils::{
+        alloc::{self, AllocTag},
+        error::ResultExt,
+        send_sync::PhantomUnsync,
+        uid::Uid,
+    },
 };
 
 use super::{context::DrawContext, GfxHandle};
 
 pub mod buffer;
+pub mod fence;
 pub mod framebuffer;
+pub mod query;
+pub mod renderbuffer;
 pub mod shader;
 pub mod texture;
 pub mod vertex_array;
@@ -29,10 +43,23 @@ pub trait GLHandleTrait<A: Clone = ()> {
     fn delete(handle: GLuint);
     fn bind(handle: GLuint, args: A);
     fn identifier() -> GLenum;
+    fn type_name() -> &'static str;
     fn delete_mul(handles: &[GLuint]) {
         handles.iter().for_each(|&handle| Self::delete(handle));
     }
 
+    /// Attaches `name` to the object for GPU debuggers, the object is bound
+    /// first since some drivers only create it on the first bind.
+    fn label(handle: GLuint, args: A, name: &CStr) {
+        if !gl::ObjectLabel::is_loaded() {
+            return;
+        }
+        let len = name.to_bytes().len() as GLsizei;
+        Self::bind(handle, args.clone());
+        unsafe { gl::ObjectLabel(Self::identifier(), handle, len, name.as_ptr()) };
+        Self::bind(0, args);
+    }
+
     fn get_container_mut(_server: &mut DrawContext) -> Option<&mut GLHandleContainer<Self, A>>
     where
         Self: Sized,
@@ -52,6 +79,8 @@ pub struct GLHandleInner<T: GLHandleTrait<A>, A: Clone = ()> {
     gl_handle: GLuint,
     args: A,
     name: Cow<'static, str>,
+    /// bytes of the storage last specified for the object
+    estimated_size: Cell<usize>,
     _phantom: PhantomData<(T, A)>,
 }
 
@@ -116,11 +145,12 @@ impl<T: GLHandleTrait<A> + 'static, A: Clone + 'static> GLGfxHandle<T, A> {
     {
         let slf = unsafe { Self::new_uninit(draw) };
         draw.execute_draw_event(enclose!((slf) move |context, _| {
+            let _scope = alloc::scope(AllocTag::Handles);
             if let Some(container) = T::get_container_mut(context) {
                 return GLHandle::<T, A>::new_args(name, args)
                     .map(|handle| container.insert(&slf, handle))
                     .err()
-                    .map(GameUserEvent::Error);
+                    .map(|e| GameUserEvent::error(Subsystem::Draw, "draw.create_handle", e));
             }
 
             None
@@ -172,24 +202,13 @@ impl<T: GLHandleTrait<A>, A: Clone> GLHandle<T, A> {
             bail!("unable to create GL object for {}", name);
         }
 
-        let c_name = CString::new(name.as_ref())?;
-        unsafe {
-            if gl::ObjectLabel::is_loaded() {
-                T::bind(handle, args.clone());
-                gl::ObjectLabel(
-                    T::identifier(),
-                    handle,
-                    name.len().try_into()?,
-                    c_name.as_ptr(),
-                );
-                T::bind(0, args.clone());
-            }
-        };
+        T::label(handle, args.clone(), &CString::new(name.as_ref())?);
 
         Ok(Self(SendRc::new(GLHandleInner {
             gl_handle: handle,
             args,
             name,
+            estimated_size: Cell::new(0),
             _phantom: PhantomData,
         })))
     }
@@ -198,6 +217,24 @@ impl<T: GLHandleTrait<A>, A: Clone> GLHandle<T, A> {
         self.0.name.clone()
     }
 
+    /// Estimated GPU memory used by the object, 0 until its storage is
+    /// specified.
+    pub fn estimated_size(&self) -> usize {
+        self.0.estimated_size.get()
+    }
+
+    pub fn set_estimated_size(&self, bytes: usize) {
+        self.0.estimated_size.set(bytes);
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        T::type_name()
+    }
+
+    pub fn args(&self) -> &A {
+        &self.0.args
+    }
+
     pub fn bind(&self) {
         T::bind(self.0.gl_handle, self.0.args.clone())
     }
@@ -310,6 +347,24 @@ impl<T: GLHandleTrait<A>, A: Clone> GLHandleContainer<T, A> {
         self.0.get(&Self::handle_to_key(gfx_handle)).cloned()
     }
 
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &GLHandle<T, A>> {
+        self.0.values()
+    }
+
+    /// Deletes all GL objects in this container, requires the GL context
+    /// to be current.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
     pub fn to_send(mut self) -> SendGLHandleContainer<T, A> {
         let presend = SendRc::pre_send();
         for value in self.0.values_mut() {
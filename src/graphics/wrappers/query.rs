@@ -0,0 +1,145 @@
+use std::ffi::{c_void, CStr, CString};
+
+use gl::types::{GLenum, GLint, GLuint, GLuint64};
+
+use crate::graphics::context::DrawContext;
+
+use super::{GLGfxHandle, GLHandle, GLHandleContainer, GLHandleTrait, SendGLHandleContainer};
+
+/// `GL_GPU_DISJOINT_EXT`, missing from the desktop bindings
+const GPU_DISJOINT: GLenum = 0x8FBB;
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum QueryTarget {
+    /// GPU time between the start and the end of the query
+    TimeElapsed = gl::TIME_ELAPSED as _,
+}
+
+pub struct QueryTrait;
+pub type Query = GLHandle<QueryTrait, QueryTarget>;
+pub type QueryContainer = GLHandleContainer<QueryTrait, QueryTarget>;
+pub type SendQueryContainer = SendGLHandleContainer<QueryTrait, QueryTarget>;
+pub type QueryHandle = GLGfxHandle<QueryTrait, QueryTarget>;
+
+impl GLHandleTrait<QueryTarget> for QueryTrait {
+    fn create(_: QueryTarget) -> GLuint {
+        let mut handle = 0;
+        unsafe { gl::GenQueries(1, &mut handle) };
+        handle
+    }
+
+    fn delete(handle: GLuint) {
+        Self::delete_mul(&[handle])
+    }
+
+    // queries have no binding point, but only exist once they have been
+    // begun, which labelling them requires
+    fn bind(handle: GLuint, args: QueryTarget) {
+        unsafe {
+            if handle != 0 && gl::IsQuery(handle) == gl::FALSE {
+                gl::BeginQuery(args as GLenum, handle);
+                gl::EndQuery(args as GLenum);
+            }
+        }
+    }
+
+    fn identifier() -> GLenum {
+        gl::QUERY
+    }
+
+    fn type_name() -> &'static str {
+        "query"
+    }
+
+    fn delete_mul(handles: &[GLuint]) {
+        unsafe { gl::DeleteQueries(handles.len().try_into().unwrap(), handles.as_ptr()) }
+    }
+
+    fn get_container_mut(
+        context: &mut DrawContext,
+    ) -> Option<&mut GLHandleContainer<Self, QueryTarget>> {
+        Some(&mut context.handles.queries)
+    }
+
+    fn get_container(context: &DrawContext) -> Option<&GLHandleContainer<Self, QueryTarget>> {
+        Some(&context.handles.queries)
+    }
+}
+
+impl Query {
+    /// Starts the query, only one query of a target can be active at once.
+    pub fn begin(&self) {
+        unsafe { gl::BeginQuery(*self.args() as GLenum, **self) }
+    }
+
+    pub fn end(&self) {
+        unsafe { gl::EndQuery(*self.args() as GLenum) }
+    }
+
+    /// Whether the result can be read without stalling.
+    pub fn available(&self) -> bool {
+        let mut available = 0;
+        unsafe { gl::GetQueryObjectuiv(**self, gl::QUERY_RESULT_AVAILABLE, &mut available) };
+        available != 0
+    }
+
+    /// The result of the query, waits for it if it isn't available.
+    pub fn result(&self) -> GLuint64 {
+        let mut result = 0;
+        unsafe { gl::GetQueryObjectui64v(**self, gl::QUERY_RESULT, &mut result) };
+        result
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerQuerySupport {
+    Unsupported,
+    /// desktop `ARB_timer_query`
+    Arb,
+    /// GLES `EXT_disjoint_timer_query`, which can report disjoint results
+    DisjointExt,
+}
+
+impl TimerQuerySupport {
+    /// Detects `GL_TIME_ELAPSED` query support, loading the entry point of
+    /// the GLES extension. Requires the GL context to be current.
+    pub fn load(get_proc_address: impl Fn(&CStr) -> *const c_void) -> Self {
+        let extensions = unsafe {
+            let mut count: GLint = 0;
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+            (0..count.max(0) as GLuint)
+                .filter_map(|i| {
+                    let name = gl::GetStringi(gl::EXTENSIONS, i);
+                    (!name.is_null()).then(|| CStr::from_ptr(name as *const _))
+                })
+                .filter_map(|name| name.to_str().ok())
+                .collect::<Vec<_>>()
+        };
+        let support = if extensions.contains(&"GL_EXT_disjoint_timer_query") {
+            gl::GetQueryObjectui64v::load_with(|symbol| {
+                let symbol = CString::new(format!("{symbol}EXT")).unwrap();
+                get_proc_address(&symbol)
+            });
+            Self::DisjointExt
+        } else if extensions.contains(&"GL_ARB_timer_query") {
+            Self::Arb
+        } else {
+            Self::Unsupported
+        };
+        if gl::GetQueryObjectui64v::is_loaded() {
+            support
+        } else {
+            Self::Unsupported
+        }
+    }
+
+    /// Whether the GPU timer was disturbed (e.g. by a frequency change) since
+    /// the last call, in which case the pending results are meaningless.
+    pub fn disjoint(self) -> bool {
+        let mut disjoint = 0;
+        if self == Self::DisjointExt {
+            unsafe { gl::GetIntegerv(GPU_DISJOINT, &mut disjoint) };
+        }
+        disjoint != 0
+    }
+}
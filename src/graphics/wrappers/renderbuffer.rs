@@ -0,0 +1,47 @@
+use gl::types::{GLenum, GLuint};
+
+use crate::graphics::context::DrawContext;
+
+use super::{GLGfxHandle, GLHandle, GLHandleContainer, GLHandleTrait, SendGLHandleContainer};
+
+pub struct RenderbufferTrait;
+pub type Renderbuffer = GLHandle<RenderbufferTrait>;
+pub type RenderbufferContainer = GLHandleContainer<RenderbufferTrait>;
+pub type SendRenderbufferContainer = SendGLHandleContainer<RenderbufferTrait>;
+pub type RenderbufferHandle = GLGfxHandle<RenderbufferTrait>;
+
+impl GLHandleTrait for RenderbufferTrait {
+    fn create(_: ()) -> GLuint {
+        let mut handle = 0;
+        unsafe { gl::GenRenderbuffers(1, &mut handle) };
+        handle
+    }
+
+    fn delete(handle: GLuint) {
+        Self::delete_mul(&[handle])
+    }
+
+    fn bind(handle: GLuint, _: ()) {
+        unsafe { gl::BindRenderbuffer(gl::RENDERBUFFER, handle) }
+    }
+
+    fn identifier() -> GLenum {
+        gl::RENDERBUFFER
+    }
+
+    fn type_name() -> &'static str {
+        "renderbuffer"
+    }
+
+    fn delete_mul(handles: &[GLuint]) {
+        unsafe { gl::DeleteRenderbuffers(handles.len().try_into().unwrap(), handles.as_ptr()) }
+    }
+
+    fn get_container_mut(context: &mut DrawContext) -> Option<&mut GLHandleContainer<Self, ()>> {
+        Some(&mut context.handles.renderbuffers)
+    }
+
+    fn get_container(context: &DrawContext) -> Option<&GLHandleContainer<Self, ()>> {
+        Some(&context.handles.renderbuffers)
+    }
+}
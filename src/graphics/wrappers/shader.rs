@@ -9,7 +9,7 @@ use gl::types::{GLchar, GLenum, GLuint};
 
 use crate::{
     enclose,
-    events::GameUserEvent,
+    events::{error::Subsystem, GameUserEvent},
     exec::server::draw::{self, ServerSendChannelExt},
     graphics::{context::DrawContext, GfxHandle},
 };
@@ -40,6 +40,10 @@ impl GLHandleTrait<ShaderType> for ShaderTrait {
     fn identifier() -> GLenum {
         gl::SHADER
     }
+
+    fn type_name() -> &'static str {
+        "shader"
+    }
 }
 pub struct ProgramTrait;
 pub type Program = GLHandle<ProgramTrait>;
@@ -60,6 +64,10 @@ impl GLHandleTrait for ProgramTrait {
         gl::PROGRAM
     }
 
+    fn type_name() -> &'static str {
+        "program"
+    }
+
     fn bind(_: GLuint, _: ()) {}
 
     fn get_container_mut(context: &mut DrawContext) -> Option<&mut GLHandleContainer<Self, ()>> {
@@ -162,7 +170,7 @@ impl ProgramHandle {
         draw.execute_draw_event(enclose!((handle) move |context, _| {
             context.handles.create_vf_program(name, &handle, vertex, fragment)
                 .err()
-                .map(GameUserEvent::Error)
+                .map(|e| GameUserEvent::error(Subsystem::Draw, "draw.create_program", e))
         }))?;
         Ok(handle)
     }
@@ -1,14 +1,67 @@
-use gl::types::{GLenum, GLuint};
+use std::{borrow::Cow, ffi::c_void, ops::Range, ptr};
 
-use crate::graphics::context::DrawContext;
+use anyhow::{ensure, Context};
+use gl::types::{GLenum, GLint, GLuint};
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
 
-use super::{GLGfxHandle, GLHandle, GLHandleContainer, GLHandleTrait, SendGLHandleContainer};
+use crate::{
+    enclose,
+    events::{error::Subsystem, GameUserEvent},
+    exec::server::draw::{self, command::DrawCommand, ServerSendChannelExt},
+    graphics::context::DrawContext,
+};
+
+use super::{
+    buffer::{Buffer, BufferTarget},
+    GLGfxHandle, GLHandle, GLHandleContainer, GLHandleTrait, SendGLHandleContainer,
+};
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TextureType {
     E2D = gl::TEXTURE_2D as _,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    Rgba8,
+    /// color channels are decoded from sRGB when sampled
+    Srgb8Alpha8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFilter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureWrap {
+    ClampToEdge,
+    Repeat,
+    MirroredRepeat,
+}
+
+/// How pixels uploaded with `Texture::upload` are stored and sampled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureOptions {
+    pub format: TextureFormat,
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    pub wrap: TextureWrap,
+    /// mipmaps are generated after the upload, `min_filter` then also
+    /// applies between mip levels
+    pub mipmaps: bool,
+}
+
+/// RGBA8 pixels, rows from top to bottom, with the options to upload them with.
+#[derive(Clone, Debug)]
+pub struct ImageData {
+    pub size: PhysicalSize<u32>,
+    pub pixels: Vec<u8>,
+    pub options: TextureOptions,
+}
+
 pub struct TextureTrait;
 pub type Texture = GLHandle<TextureTrait, TextureType>;
 pub type TextureContainer = GLHandleContainer<TextureTrait, TextureType>;
@@ -34,6 +87,10 @@ impl GLHandleTrait<TextureType> for TextureTrait {
         gl::TEXTURE
     }
 
+    fn type_name() -> &'static str {
+        "texture"
+    }
+
     fn delete_mul(handles: &[GLuint]) {
         unsafe { gl::DeleteTextures(handles.len().try_into().unwrap(), handles.as_ptr()) }
     }
@@ -48,3 +105,280 @@ impl GLHandleTrait<TextureType> for TextureTrait {
         Some(&context.handles.textures)
     }
 }
+
+impl TextureFormat {
+    fn internal_format(self) -> GLenum {
+        match self {
+            Self::Rgba8 => gl::RGBA8,
+            Self::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+        }
+    }
+}
+
+impl TextureFilter {
+    fn gl_filter(self, mipmaps: bool) -> GLenum {
+        match (self, mipmaps) {
+            (Self::Nearest, false) => gl::NEAREST,
+            (Self::Linear, false) => gl::LINEAR,
+            (Self::Nearest, true) => gl::NEAREST_MIPMAP_NEAREST,
+            (Self::Linear, true) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+}
+
+impl TextureWrap {
+    fn gl_wrap(self) -> GLenum {
+        match self {
+            Self::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Self::Repeat => gl::REPEAT,
+            Self::MirroredRepeat => gl::MIRRORED_REPEAT,
+        }
+    }
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            format: TextureFormat::Rgba8,
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            wrap: TextureWrap::Repeat,
+            mipmaps: true,
+        }
+    }
+}
+
+impl ImageData {
+    pub fn new(size: PhysicalSize<u32>, pixels: Vec<u8>) -> anyhow::Result<Self> {
+        ensure!(
+            pixels.len() == size.width as usize * size.height as usize * 4,
+            "{} bytes of pixel data for a {}x{} RGBA8 image",
+            pixels.len(),
+            size.width,
+            size.height
+        );
+        Ok(Self {
+            size,
+            pixels,
+            options: TextureOptions::default(),
+        })
+    }
+
+    pub fn options(mut self, options: TextureOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl From<RgbaImage> for ImageData {
+    fn from(image: RgbaImage) -> Self {
+        Self {
+            size: PhysicalSize::new(image.width(), image.height()),
+            pixels: image.into_raw(),
+            options: TextureOptions::default(),
+        }
+    }
+}
+
+impl Texture {
+    /// (Re)specifies the texture storage with `image`, requires the GL
+    /// context to be current.
+    pub fn upload(&self, image: &ImageData) -> anyhow::Result<()> {
+        ensure!(
+            image.pixels.len() == image.size.width as usize * image.size.height as usize * 4,
+            "{} bytes of pixel data for a {}x{} RGBA8 image",
+            image.pixels.len(),
+            image.size.width,
+            image.size.height
+        );
+        self.specify(image.size, &image.options, image.pixels.as_ptr())
+    }
+
+    /// (Re)specifies uninitialized storage of `size`, filled afterwards with
+    /// `upload_rows`.
+    pub fn allocate(
+        &self,
+        size: PhysicalSize<u32>,
+        options: &TextureOptions,
+    ) -> anyhow::Result<()> {
+        self.specify(size, options, ptr::null())
+    }
+
+    /// Uploads `rows` of `image` into storage allocated for it, then
+    /// generates the mipmaps if they are enabled and the last row was
+    /// uploaded.
+    pub fn upload_rows(&self, image: &ImageData, rows: Range<u32>) -> anyhow::Result<()> {
+        let row_bytes = image.size.width as usize * 4;
+        let pixels = image
+            .pixels
+            .get(rows.start as usize * row_bytes..rows.end as usize * row_bytes)
+            .with_context(|| format!("rows {rows:?} out of the image pixels"))?;
+        self.sub_image(image, rows, pixels.as_ptr() as *const _)
+    }
+
+    /// Same as `upload_rows`, but the rows are streamed into `pixels`, a
+    /// pixel unpack buffer, which the GPU copies them from asynchronously.
+    /// `pixels` may be reused right away, its storage is orphaned.
+    pub fn upload_rows_unpacked(
+        &self,
+        image: &ImageData,
+        rows: Range<u32>,
+        pixels: &Buffer,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            pixels.target() == BufferTarget::PixelUnpackBuffer,
+            "rows must be uploaded through a pixel unpack buffer"
+        );
+        let row_bytes = image.size.width as usize * 4;
+        let bytes = image
+            .pixels
+            .get(rows.start as usize * row_bytes..rows.end as usize * row_bytes)
+            .with_context(|| format!("rows {rows:?} out of the image pixels"))?;
+        pixels.stream_slice(bytes)?;
+        pixels.bind();
+        // offset 0 in the bound buffer
+        let result = self.sub_image(image, rows, ptr::null());
+        pixels.unbind();
+        result
+    }
+
+    /// Uploads the whole `image` into the storage at `x`, `y` (from the
+    /// first row), e.g. a region of an atlas page.
+    pub fn upload_at(&self, x: u32, y: u32, image: &ImageData) -> anyhow::Result<()> {
+        self.bind();
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x.try_into()?,
+                y.try_into()?,
+                image.size.width.try_into()?,
+                image.size.height.try_into()?,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                image.pixels.as_ptr() as *const _,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+        self.unbind();
+        Ok(())
+    }
+
+    fn sub_image(
+        &self,
+        image: &ImageData,
+        rows: Range<u32>,
+        pixels: *const c_void,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            rows.start <= rows.end && rows.end <= image.size.height,
+            "rows {rows:?} out of a {}x{} image",
+            image.size.width,
+            image.size.height
+        );
+        self.bind();
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                rows.start.try_into()?,
+                image.size.width.try_into()?,
+                rows.len().try_into()?,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            if image.options.mipmaps && rows.end == image.size.height {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+        }
+        self.unbind();
+        Ok(())
+    }
+
+    fn specify(
+        &self,
+        size: PhysicalSize<u32>,
+        options: &TextureOptions,
+        pixels: *const u8,
+    ) -> anyhow::Result<()> {
+        self.bind();
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                options.format.internal_format() as GLint,
+                size.width.try_into()?,
+                size.height.try_into()?,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels as *const _,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            let parameters = [
+                (
+                    gl::TEXTURE_MIN_FILTER,
+                    options.min_filter.gl_filter(options.mipmaps),
+                ),
+                (gl::TEXTURE_MAG_FILTER, options.mag_filter.gl_filter(false)),
+                (gl::TEXTURE_WRAP_S, options.wrap.gl_wrap()),
+                (gl::TEXTURE_WRAP_T, options.wrap.gl_wrap()),
+            ];
+            for (name, value) in parameters {
+                gl::TexParameteri(gl::TEXTURE_2D, name, value as GLint);
+            }
+            if options.mipmaps && !pixels.is_null() {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+        }
+        self.unbind();
+        let level_size = size.width as usize * size.height as usize * 4;
+        // the mip chain adds up to a third of the base level
+        self.set_estimated_size(if options.mipmaps {
+            level_size * 4 / 3
+        } else {
+            level_size
+        });
+        Ok(())
+    }
+}
+
+impl TextureHandle {
+    /// Creates a 2D texture on the draw server and uploads `image` to it.
+    pub fn new(
+        draw: &mut draw::ServerChannel,
+        name: impl Into<Cow<'static, str>> + Send + 'static,
+        image: ImageData,
+    ) -> anyhow::Result<Self> {
+        let handle = unsafe { Self::new_uninit(draw) };
+        draw.execute_draw_event(enclose!((handle) move |context, _| {
+            context
+                .handles
+                .create_texture(name, &handle, &image)
+                .err()
+                .map(|e| GameUserEvent::error(Subsystem::Draw, "draw.create_texture", e))
+        }))?;
+        Ok(handle)
+    }
+
+    /// Creates an RGBA8 2D texture of `size` with a `DrawCommand`.
+    pub fn new_2d(
+        draw: &mut draw::ServerChannel,
+        name: impl Into<Cow<'static, str>>,
+        size: PhysicalSize<u32>,
+    ) -> anyhow::Result<Self> {
+        let handle = unsafe { Self::new_uninit(draw) };
+        draw.command(DrawCommand::CreateTexture {
+            handle: handle.clone(),
+            name: name.into(),
+            size,
+        })?;
+        Ok(handle)
+    }
+}
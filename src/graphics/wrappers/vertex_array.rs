@@ -1,8 +1,10 @@
-use gl::types::{GLenum, GLuint};
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
 
 use crate::graphics::context::DrawContext;
 
-use super::{GLGfxHandle, GLHandle, GLHandleContainer, GLHandleTrait, SendGLHandleContainer};
+use super::{
+    buffer::Buffer, GLGfxHandle, GLHandle, GLHandleContainer, GLHandleTrait, SendGLHandleContainer,
+};
 
 pub struct VertexArrayTrait;
 pub type VertexArray = GLHandle<VertexArrayTrait>;
@@ -29,6 +31,10 @@ impl GLHandleTrait for VertexArrayTrait {
         gl::VERTEX_ARRAY
     }
 
+    fn type_name() -> &'static str {
+        "vertex array"
+    }
+
     fn delete_mul(handles: &[GLuint]) {
         unsafe { gl::DeleteVertexArrays(handles.len().try_into().unwrap(), handles.as_ptr()) }
     }
@@ -42,6 +48,60 @@ impl GLHandleTrait for VertexArrayTrait {
     }
 }
 
+/// Float attribute read from an array buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexAttrib {
+    pub location: GLuint,
+    /// number of components, 1 to 4
+    pub size: GLint,
+    /// in bytes, from the start of the vertex (or instance)
+    pub offset: usize,
+    /// 0 advances the attribute every vertex, `n` every `n` instances
+    pub divisor: GLuint,
+}
+
+impl VertexAttrib {
+    pub const fn new(location: GLuint, size: GLint, offset: usize) -> Self {
+        Self {
+            location,
+            size,
+            offset,
+            divisor: 0,
+        }
+    }
+
+    /// Advances every instance instead of every vertex.
+    pub const fn per_instance(mut self) -> Self {
+        self.divisor = 1;
+        self
+    }
+}
+
+impl VertexArray {
+    /// Reads `attributes` from `buffer`, whose elements are `stride` bytes
+    /// apart. Leaves the vertex array bound, e.g. to bind an element array.
+    pub fn set_attributes(&self, buffer: &Buffer, stride: usize, attributes: &[VertexAttrib]) {
+        self.bind();
+        buffer.bind();
+        unsafe {
+            for attribute in attributes {
+                gl::EnableVertexAttribArray(attribute.location);
+                gl::VertexAttribPointer(
+                    attribute.location,
+                    attribute.size,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride as GLsizei,
+                    attribute.offset as *const _,
+                );
+                gl::VertexAttribDivisor(attribute.location, attribute.divisor);
+            }
+        }
+        // the array buffer binding isn't part of the vertex array state
+        buffer.unbind();
+    }
+}
+
 #[test]
 fn test_send_sync() {
     use crate::{assert_not_sync, assert_send, assert_sync};
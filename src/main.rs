@@ -2,48 +2,173 @@ use anyhow::Context;
 use display::Display;
 use events::GameUserEvent;
 use exec::{
+    balancer::{Balancer, BalancerConfig},
     executor::GameServerExecutor,
+    lockstep::Lockstep,
     main_ctx::MainContext,
-    runner::MAIN_RUNNER_ID,
-    server::{audio, draw, update, ServerChannels, ServerKind},
+    replay::{EventRecorder, EventReplay, Recording},
+    runner::{RunnerConfig, MAIN_RUNNER_ID},
+    server::{
+        draw::{self, ServerSendChannelExt},
+        network, update, GameServerSendChannel, ServerChannels, ServerKind,
+    },
+    task::TaskExecutor,
 };
 use scene::main::RootScene;
-use utils::{args::parse_args, log::init_log};
+use utils::{
+    affinity::CoreSet,
+    args::{args, parse_args},
+    config::Config,
+    log::init_log,
+    rng::RngService,
+    startup,
+};
 use winit::{dpi::PhysicalSize, event_loop::EventLoopBuilder};
 
+pub mod ai;
+pub mod audio;
 pub mod display;
 pub mod events;
 pub mod exec;
 pub mod graphics;
+pub mod nav;
+pub mod net;
 pub mod scene;
+pub mod spatial;
 pub mod test;
 pub mod ui;
 pub mod utils;
 
 fn main() -> anyhow::Result<()> {
+    startup::begin();
     parse_args();
+    // before the log writer thread is started
+    args().gl_backend.set_driver_env();
     let guard = init_log()?;
+    tracing::info!("using the {:?} GL backend", args().gl_backend);
+    let config = Config::load().context("unable to load config")?;
     let event_loop = EventLoopBuilder::<GameUserEvent>::with_user_event().build();
-    let (display, gl_config) =
-        Display::new_display(&event_loop, PhysicalSize::new(1280, 720), "hello")
-            .context("unable to create main display")?;
-    let (draw, draw_channels) =
-        draw::SendServer::new(event_loop.create_proxy(), gl_config, &display)
-            .context("unable to initialize draw server")?;
-    let (audio, audio_channels) = audio::Server::new(event_loop.create_proxy());
-    let (update, update_channels) = update::Server::new(event_loop.create_proxy());
-    let mut executor = GameServerExecutor::new(audio, draw, update)?;
+    if let Some(path) = &args().diagnose {
+        return display::diagnose::write_report(&event_loop, path);
+    }
+    let (display, gl_config) = startup::phase("display", || {
+        Display::new_display(
+            &event_loop,
+            PhysicalSize::new(config.window.width, config.window.height),
+            &config.window.title,
+        )
+    })
+    .context("unable to create main display, run with --diagnose <FILE> for details")?;
+    let recording = args().replay.as_deref().map(Recording::load).transpose()?;
+    let lockstep = args()
+        .lockstep
+        .or_else(|| {
+            recording
+                .as_ref()
+                .map(|recording| recording.lockstep.unwrap_or(1))
+        })
+        .map(Lockstep::new);
+    let (draw, draw_channels) = startup::phase("draw server", || {
+        draw::SendServer::new(
+            event_loop.create_proxy(),
+            gl_config,
+            &display,
+            lockstep.clone(),
+        )
+    })
+    .context("unable to initialize draw server")?;
+    let task_executor = TaskExecutor::new();
+    let (audio, audio_channels) = startup::phase("audio server", || {
+        exec::server::audio::Server::new(
+            event_loop.create_proxy(),
+            task_executor.clone(),
+            args().headless,
+        )
+    });
+    let seed = args()
+        .seed
+        .unwrap_or_else(|| RngService::from_entropy().seed());
+    tracing::info!("update server RNG seed: {seed}");
+    let (mut update, update_channels) = startup::phase("update server", || {
+        update::Server::new(
+            event_loop.create_proxy(),
+            task_executor.clone(),
+            lockstep.clone(),
+            seed,
+        )
+    });
+    update.beat_clock = audio_channels.beat_clock().clone();
+    let (network, network_channels) = startup::phase("network server", || {
+        network::Server::new(event_loop.create_proxy(), update_channels.clone_sender())
+    });
+    let mut executor = GameServerExecutor::new(audio, draw, update, network)?;
     let event_loop_proxy = event_loop.create_proxy();
     let channels = ServerChannels {
         audio: audio_channels,
         draw: draw_channels,
         update: update_channels,
+        network: network_channels,
     };
-    executor.move_server(MAIN_RUNNER_ID, 0, ServerKind::Audio)?;
-    executor.move_server(MAIN_RUNNER_ID, 0, ServerKind::Update)?;
-    executor.move_server(MAIN_RUNNER_ID, 1, ServerKind::Draw)?;
-    executor.set_frequency(0, 1000.0)?;
-    let mut main_ctx = MainContext::new(executor, display, event_loop_proxy, channels)?;
-    let root_scene = RootScene::new(&mut main_ctx)?;
+    // configured before the runner threads are spawned by the moves below,
+    // runners share cores when there are fewer cores than runners
+    let num_cores = CoreSet::all().len();
+    for id in 0..=MAIN_RUNNER_ID {
+        let config = RunnerConfig {
+            affinity: args()
+                .pin_runners
+                .then(|| CoreSet::single(usize::from(id) % num_cores)),
+            priority: args().runner_priority,
+        };
+        // scheduling hints are best effort, e.g. raising priority may be denied
+        executor.configure_runner(id, config);
+    }
+    let layout = &config.runners;
+    startup::phase("runner threads", || {
+        executor.move_server(MAIN_RUNNER_ID, layout.audio, ServerKind::Audio)?;
+        executor.move_server(MAIN_RUNNER_ID, layout.update, ServerKind::Update)?;
+        executor.move_server(MAIN_RUNNER_ID, layout.network, ServerKind::Network)?;
+        executor.move_server(MAIN_RUNNER_ID, layout.draw, ServerKind::Draw)
+    })?;
+    for id in 0..=MAIN_RUNNER_ID {
+        let frequency = layout.frequency(id);
+        if frequency > 0.0 {
+            executor
+                .set_frequency(id, frequency)
+                .with_context(|| format!("unable to set frequency of runner {id}"))?;
+        }
+    }
+    channels
+        .draw
+        .set_multisample(config.graphics.msaa_samples)
+        .context("unable to set scene multisampling")?;
+    if let Some(addr) = args().listen {
+        channels.network.listen(addr)?;
+    }
+    if let Some(addr) = args().connect {
+        channels.network.connect(addr)?;
+    }
+    if args().auto_balance {
+        executor.set_balancer(Some(Balancer::new(BalancerConfig::default())));
+    }
+    let mut main_ctx = startup::phase("main context", || {
+        MainContext::new(
+            executor,
+            display,
+            event_loop_proxy,
+            channels,
+            task_executor,
+            config,
+        )
+    })?;
+    main_ctx.recorder = args()
+        .record
+        .as_deref()
+        .map(|path| EventRecorder::create(path, lockstep.clone()))
+        .transpose()?;
+    main_ctx.replay = recording.zip(lockstep).map(|(recording, lockstep)| {
+        tracing::info!("replaying {} recorded events", recording.events.len());
+        EventReplay::new(recording, lockstep)
+    });
+    let root_scene = startup::phase("root scene", || RootScene::new(&mut main_ctx))?;
     main_ctx.run(event_loop, root_scene, guard);
 }
@@ -0,0 +1,165 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use glam::{IVec2, UVec2, Vec2};
+
+use crate::exec::task::{Cancellable, CancellationToken};
+
+use super::{NavGrid, NavPath, PathQuery};
+
+// how many nodes are expanded between cancellation checks
+const CANCEL_CHECK_INTERVAL: usize = 256;
+
+const NEIGHBORS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+struct OpenNode {
+    f: f32,
+    index: usize,
+}
+
+impl Eq for OpenNode {}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, BinaryHeap is a max-heap
+        other
+            .f
+            .partial_cmp(&self.f)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// octile distance, in cells
+fn heuristic(a: UVec2, b: UVec2) -> f32 {
+    let d = (a.as_ivec2() - b.as_ivec2()).abs();
+    let (min, max) = (d.x.min(d.y) as f32, d.x.max(d.y) as f32);
+    max + (std::f32::consts::SQRT_2 - 1.0) * min
+}
+
+/// 8-connected A* search over `grid`, diagonal moves can't cut corners.
+/// Returns `None` if the start or the goal isn't walkable for the agent, if
+/// the goal is unreachable, or if the search was cancelled.
+pub fn find_path(
+    grid: &NavGrid,
+    query: &PathQuery,
+    cancel: Option<&CancellationToken>,
+) -> Option<NavPath> {
+    let start = grid.cell_at(query.start)?;
+    let goal = grid.cell_at(query.goal)?;
+    let radius = query.agent_radius;
+    if !grid.is_walkable(start, radius) || !grid.is_walkable(goal, radius) {
+        return None;
+    }
+
+    let num_cells = (grid.width() * grid.height()) as usize;
+    let mut g_score = vec![f32::INFINITY; num_cells];
+    let mut came_from = vec![usize::MAX; num_cells];
+    let mut closed = vec![false; num_cells];
+    let mut open = BinaryHeap::new();
+
+    let to_cell =
+        |index: usize| UVec2::new(index as u32 % grid.width(), index as u32 / grid.width());
+    let start_index = grid.index(start);
+    let goal_index = grid.index(goal);
+    g_score[start_index] = 0.0;
+    open.push(OpenNode {
+        f: heuristic(start, goal),
+        index: start_index,
+    });
+
+    let mut expanded = 0;
+    while let Some(OpenNode { index, .. }) = open.pop() {
+        if index == goal_index {
+            break;
+        }
+        if std::mem::replace(&mut closed[index], true) {
+            continue;
+        }
+
+        expanded += 1;
+        if expanded % CANCEL_CHECK_INTERVAL == 0 && matches!(cancel, Some(c) if c.is_cancelled()) {
+            return None;
+        }
+
+        let cell = to_cell(index).as_ivec2();
+        for (dx, dy) in NEIGHBORS {
+            let next = cell + IVec2::new(dx, dy);
+            if next.x < 0 || next.y < 0 {
+                continue;
+            }
+            let next = next.as_uvec2();
+            if !grid.is_walkable(next, radius) {
+                continue;
+            }
+
+            let diagonal = dx != 0 && dy != 0;
+            if diagonal
+                && (!grid.is_walkable(UVec2::new(next.x, cell.y as u32), radius)
+                    || !grid.is_walkable(UVec2::new(cell.x as u32, next.y), radius))
+            {
+                continue;
+            }
+
+            let next_index = grid.index(next);
+            let cost = if diagonal {
+                std::f32::consts::SQRT_2
+            } else {
+                1.0
+            };
+            let g = g_score[index] + cost;
+            if g < g_score[next_index] {
+                g_score[next_index] = g;
+                came_from[next_index] = index;
+                open.push(OpenNode {
+                    f: g + heuristic(next, goal),
+                    index: next_index,
+                });
+            }
+        }
+    }
+
+    if !g_score[goal_index].is_finite() {
+        return None;
+    }
+
+    let mut cells = vec![goal_index];
+    while let Some(&index) = cells.last() {
+        if index == start_index {
+            break;
+        }
+        cells.push(came_from[index]);
+    }
+
+    let mut points: Vec<Vec2> = cells
+        .into_iter()
+        .rev()
+        .map(|index| grid.cell_center(to_cell(index)))
+        .collect();
+    // replace the start and goal cell centers with the exact positions
+    *points.first_mut()? = query.start;
+    points.push(query.goal);
+    if points.len() > 2 {
+        points.remove(points.len() - 2);
+    }
+
+    Some(NavPath {
+        points,
+        cost: g_score[goal_index] * grid.cell_size(),
+    })
+}
@@ -0,0 +1,191 @@
+use glam::{UVec2, Vec2};
+
+pub mod astar;
+
+/// A uniform grid of walkable/blocked cells in world space, the cell `(0, 0)`
+/// covers `[0, cell_size) x [0, cell_size)`.
+///
+/// Besides the blocked flags, the grid keeps a clearance map (the distance
+/// from each cell center to the closest obstacle or grid border), so that
+/// agents with a radius can be routed without rebuilding the grid.
+#[derive(Clone, Debug)]
+pub struct NavGrid {
+    width: u32,
+    height: u32,
+    cell_size: f32,
+    blocked: Vec<bool>,
+    // in world units
+    clearance: Vec<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathQuery {
+    pub start: Vec2,
+    pub goal: Vec2,
+    pub agent_radius: f32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NavPath {
+    /// world space waypoints, starting at the query start and ending at the
+    /// query goal
+    pub points: Vec<Vec2>,
+    pub cost: f32,
+}
+
+impl NavGrid {
+    pub fn new(width: u32, height: u32, cell_size: f32) -> Self {
+        let num_cells = (width * height) as usize;
+        let mut slf = Self {
+            width,
+            height,
+            cell_size,
+            blocked: vec![false; num_cells],
+            clearance: vec![0.0; num_cells],
+        };
+        slf.update_clearance();
+        slf
+    }
+
+    /// Creates a grid from rows of characters, `#` being a blocked cell.
+    /// The first row is `y = 0`.
+    pub fn from_rows(rows: &[&str], cell_size: f32) -> Self {
+        let height = rows.len() as u32;
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0) as u32;
+        let mut slf = Self::new(width, height, cell_size);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let index = slf.index(UVec2::new(x as u32, y as u32));
+                slf.blocked[index] = c == '#';
+            }
+        }
+        slf.update_clearance();
+        slf
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn index(&self, cell: UVec2) -> usize {
+        (cell.y * self.width + cell.x) as usize
+    }
+
+    pub fn contains(&self, cell: UVec2) -> bool {
+        cell.x < self.width && cell.y < self.height
+    }
+
+    pub fn is_blocked(&self, cell: UVec2) -> bool {
+        !self.contains(cell) || self.blocked[self.index(cell)]
+    }
+
+    /// Marks a single cell, call `update_clearance` after modifying the grid.
+    pub fn set_blocked(&mut self, cell: UVec2, blocked: bool) {
+        if self.contains(cell) {
+            let index = self.index(cell);
+            self.blocked[index] = blocked;
+        }
+    }
+
+    pub fn clearance(&self, cell: UVec2) -> f32 {
+        if self.contains(cell) {
+            self.clearance[self.index(cell)]
+        } else {
+            0.0
+        }
+    }
+
+    /// whether an agent of radius `agent_radius` centered in `cell` doesn't
+    /// overlap any obstacle
+    pub fn is_walkable(&self, cell: UVec2, agent_radius: f32) -> bool {
+        !self.is_blocked(cell) && self.clearance(cell) >= agent_radius
+    }
+
+    pub fn cell_at(&self, pos: Vec2) -> Option<UVec2> {
+        let cell = (pos / self.cell_size).floor();
+        (cell.x >= 0.0 && cell.y >= 0.0)
+            .then(|| cell.as_uvec2())
+            .filter(|&cell| self.contains(cell))
+    }
+
+    pub fn cell_center(&self, cell: UVec2) -> Vec2 {
+        (cell.as_vec2() + 0.5) * self.cell_size
+    }
+
+    /// Recomputes the clearance map with a two-pass chamfer distance transform.
+    pub fn update_clearance(&mut self) {
+        const DIAGONAL: f32 = std::f32::consts::SQRT_2;
+        let (w, h) = (self.width as i64, self.height as i64);
+        let mut dist: Vec<f32> = self
+            .blocked
+            .iter()
+            .map(|&b| if b { 0.0 } else { f32::INFINITY })
+            .collect();
+
+        let relax = |dist: &mut [f32], x: i64, y: i64, neighbors: [(i64, i64, f32); 4]| {
+            let index = (y * w + x) as usize;
+            for (dx, dy, cost) in neighbors {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx >= 0 && ny >= 0 && nx < w && ny < h {
+                    let d = dist[(ny * w + nx) as usize] + cost;
+                    if d < dist[index] {
+                        dist[index] = d;
+                    }
+                }
+            }
+        };
+
+        for y in 0..h {
+            for x in 0..w {
+                relax(
+                    &mut dist,
+                    x,
+                    y,
+                    [
+                        (-1, 0, 1.0),
+                        (-1, -1, DIAGONAL),
+                        (0, -1, 1.0),
+                        (1, -1, DIAGONAL),
+                    ],
+                );
+            }
+        }
+        for y in (0..h).rev() {
+            for x in (0..w).rev() {
+                relax(
+                    &mut dist,
+                    x,
+                    y,
+                    [
+                        (1, 0, 1.0),
+                        (1, 1, DIAGONAL),
+                        (0, 1, 1.0),
+                        (-1, 1, DIAGONAL),
+                    ],
+                );
+            }
+        }
+
+        for y in 0..h {
+            for x in 0..w {
+                let index = (y * w + x) as usize;
+                // distances are between cell centers, obstacles and borders
+                // start half a cell away from their center
+                let border = (x.min(w - 1 - x).min(y).min(h - 1 - y)) as f32 + 0.5;
+                self.clearance[index] = if self.blocked[index] {
+                    0.0
+                } else {
+                    (dist[index] - 0.5).min(border) * self.cell_size
+                };
+            }
+        }
+    }
+}
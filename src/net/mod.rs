@@ -0,0 +1,191 @@
+use std::{net::SocketAddr, rc::Rc};
+
+use anyhow::{bail, ensure};
+use trait_set::trait_set;
+
+use crate::{exec::main_ctx::MainContext, scene::main::RootScene, utils::uid::Uid};
+
+trait_set! {
+    pub trait NetHandler = Fn(&mut MainContext, &mut RootScene, &NetEvent) -> anyhow::Result<()>;
+}
+
+pub type PeerId = Uid;
+
+pub const PROTOCOL_MAGIC: [u8; 4] = *b"GAT\0";
+pub const PROTOCOL_VERSION: u16 = 1;
+/// frames bigger than this are treated as a protocol error
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
+
+// frame: [u32 length][u16 kind][payload], length covers kind + payload
+const FRAME_HEADER_SIZE: usize = 4;
+// datagram: [magic][u64 session token][u16 kind][payload]
+const DATAGRAM_HEADER_SIZE: usize = 4 + 8 + 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetMessage {
+    /// kinds starting at `NetMessage::RESERVED` are used by the session
+    /// layer
+    pub kind: u16,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// ordered, over TCP
+    Reliable,
+    /// over UDP, may be dropped or reordered
+    Unreliable,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetEvent {
+    Connected(PeerId, SocketAddr),
+    Disconnected(PeerId, String),
+    Message(PeerId, NetMessage),
+}
+
+/// Network event handlers registered by scenes, called on the main thread
+/// in registration order.
+#[derive(Default)]
+pub struct NetHandlers {
+    handlers: Vec<(Uid, Rc<dyn NetHandler>)>,
+}
+
+impl NetHandlers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<F>(&mut self, handler: F) -> Uid
+    where
+        F: NetHandler + 'static,
+    {
+        let id = Uid::new();
+        self.handlers.push((id, Rc::new(handler)));
+        id
+    }
+
+    pub fn remove(&mut self, id: Uid) -> bool {
+        let len = self.handlers.len();
+        self.handlers.retain(|(other, _)| *other != id);
+        self.handlers.len() != len
+    }
+
+    /// Cloned so that handlers can (un)register handlers while being called.
+    pub fn handlers(&self) -> Vec<Rc<dyn NetHandler>> {
+        self.handlers
+            .iter()
+            .map(|(_, handler)| handler.clone())
+            .collect()
+    }
+}
+
+impl NetMessage {
+    pub const RESERVED: u16 = 0xff00;
+    /// client -> server, `PROTOCOL_MAGIC` followed by `PROTOCOL_VERSION`
+    pub const HELLO: u16 = 0xff00;
+    /// server -> client, the session token (u64)
+    pub const WELCOME: u16 = 0xff01;
+    /// either side, UTF-8 reason
+    pub const GOODBYE: u16 = 0xff02;
+    /// client -> server over UDP, registers the client UDP address, echoed
+    /// back over TCP once registered
+    pub const BIND_UDP: u16 = 0xff03;
+
+    pub fn new(kind: u16, payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            kind,
+            payload: payload.into(),
+        }
+    }
+
+    pub fn hello() -> Self {
+        let mut payload = PROTOCOL_MAGIC.to_vec();
+        payload.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        Self::new(Self::HELLO, payload)
+    }
+
+    pub fn is_reserved(&self) -> bool {
+        self.kind >= Self::RESERVED
+    }
+
+    pub fn encode_frame(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&((self.payload.len() + 2) as u32).to_be_bytes());
+        out.extend_from_slice(&self.kind.to_be_bytes());
+        out.extend_from_slice(&self.payload);
+    }
+
+    /// Decodes the first frame of `buf` and removes it, `None` if the frame
+    /// is incomplete.
+    pub fn decode_frame(buf: &mut Vec<u8>) -> anyhow::Result<Option<Self>> {
+        if buf.len() < FRAME_HEADER_SIZE {
+            return Ok(None);
+        }
+        let length = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        ensure!(
+            (2..=MAX_FRAME_SIZE).contains(&length),
+            "invalid frame length {length}"
+        );
+        if buf.len() < FRAME_HEADER_SIZE + length {
+            return Ok(None);
+        }
+
+        let frame: Vec<u8> = buf.drain(..FRAME_HEADER_SIZE + length).collect();
+        Ok(Some(Self {
+            kind: u16::from_be_bytes([frame[4], frame[5]]),
+            payload: frame[6..].to_vec(),
+        }))
+    }
+
+    pub fn encode_datagram(&self, token: u64) -> Vec<u8> {
+        let mut out = Vec::with_capacity(DATAGRAM_HEADER_SIZE + self.payload.len());
+        out.extend_from_slice(&PROTOCOL_MAGIC);
+        out.extend_from_slice(&token.to_be_bytes());
+        out.extend_from_slice(&self.kind.to_be_bytes());
+        out.extend_from_slice(&self.payload);
+        out
+    }
+
+    /// Returns the session token along with the message.
+    pub fn decode_datagram(datagram: &[u8]) -> anyhow::Result<(u64, Self)> {
+        if datagram.len() < DATAGRAM_HEADER_SIZE || datagram[..4] != PROTOCOL_MAGIC {
+            bail!("invalid datagram header");
+        }
+        let mut token = [0; 8];
+        token.copy_from_slice(&datagram[4..12]);
+        Ok((
+            u64::from_be_bytes(token),
+            Self {
+                kind: u16::from_be_bytes([datagram[12], datagram[13]]),
+                payload: datagram[DATAGRAM_HEADER_SIZE..].to_vec(),
+            },
+        ))
+    }
+}
+
+#[test]
+fn test() {
+    let message = NetMessage::new(42, b"hello".to_vec());
+    let mut buf = Vec::new();
+    message.encode_frame(&mut buf);
+    NetMessage::hello().encode_frame(&mut buf);
+
+    let mut partial = buf[..7].to_vec();
+    assert_eq!(NetMessage::decode_frame(&mut partial).unwrap(), None);
+    assert_eq!(
+        NetMessage::decode_frame(&mut buf).unwrap(),
+        Some(message.clone())
+    );
+    assert_eq!(
+        NetMessage::decode_frame(&mut buf).unwrap(),
+        Some(NetMessage::hello())
+    );
+    assert!(buf.is_empty());
+
+    let datagram = message.encode_datagram(7);
+    assert_eq!(
+        NetMessage::decode_datagram(&datagram).unwrap(),
+        (7, message)
+    );
+    assert!(NetMessage::decode_datagram(&datagram[..5]).is_err());
+}
@@ -0,0 +1,102 @@
+use std::sync::{Arc, Weak};
+
+use crate::exec::task::{Cancellable, CancellationToken};
+
+/// Shared flag telling whether a scene is still part of the scene tree.
+/// Clones refer to the same scene. Removing the scene cancels its token,
+/// and with it the work started on its behalf.
+#[derive(Clone, Debug)]
+pub struct SceneLifetime(CancellationToken);
+
+/// Weak reference to something owned by a scene, e.g. the focused widget.
+/// It expires when either the value is dropped or the scene is removed.
+pub struct SceneWeak<T: ?Sized> {
+    value: Weak<T>,
+    scene: Option<SceneLifetime>,
+}
+
+impl SceneLifetime {
+    pub fn new() -> Self {
+        Self(CancellationToken::new())
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.0.is_cancelled()
+    }
+
+    pub fn invalidate(&self) {
+        self.0.cancel();
+    }
+
+    /// Token cancelled when the scene is removed, for jobs started by it.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.0.child()
+    }
+}
+
+impl Default for SceneLifetime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> SceneWeak<T> {
+    /// `scene` is `None` for values not owned by any scene, those only
+    /// expire when dropped.
+    pub fn new(value: &Arc<T>, scene: Option<SceneLifetime>) -> Self {
+        Self {
+            value: Arc::downgrade(value),
+            scene,
+        }
+    }
+
+    pub fn scene_alive(&self) -> bool {
+        self.scene.as_ref().map(|s| s.is_alive()).unwrap_or(true)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        !self.scene_alive() || self.value.strong_count() == 0
+    }
+
+    /// `None` if expired.
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        if self.scene_alive() {
+            self.value.upgrade()
+        } else {
+            None
+        }
+    }
+
+    /// Upgrades without looking at the scene, for debug checks.
+    pub fn upgrade_unchecked(&self) -> Option<Arc<T>> {
+        self.value.upgrade()
+    }
+}
+
+impl<T: ?Sized> Clone for SceneWeak<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            scene: self.scene.clone(),
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let value = Arc::new(0);
+    let scene = SceneLifetime::new();
+    let weak = SceneWeak::new(&value, Some(scene.clone()));
+    assert!(!weak.is_expired());
+    assert_eq!(weak.upgrade().as_deref(), Some(&0));
+    let token = scene.cancellation_token();
+    scene.invalidate();
+    assert!(weak.is_expired());
+    assert!(weak.upgrade().is_none());
+    assert!(token.is_cancelled());
+
+    let weak = SceneWeak::new(&value, None);
+    assert!(!weak.is_expired());
+    drop(value);
+    assert!(weak.is_expired());
+}
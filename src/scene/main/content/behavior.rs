@@ -0,0 +1,236 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use anyhow::Context;
+use glam::{Vec2, Vec4};
+use rand::Rng;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    ai::{
+        behavior_tree::{BehaviorTree, Node, Status},
+        blackboard::Blackboard,
+    },
+    events::{GameEvent, GameUserEvent},
+    exec::{
+        interpolation::StateSnapshots, main_ctx::MainContext, server::update::BehaviorTreeHandle,
+    },
+    graphics::context::DrawContext,
+    scene::{main::RootScene, Scene},
+    utils::rng::{GameRng, RngService},
+};
+
+const AGENT_SIZE: f32 = 20.0;
+const AGENT_SPEED: f32 = 150.0;
+const FLEE_DISTANCE: f32 = 150.0;
+
+// blackboard key of the agent position snapshots
+const AGENT_SNAPSHOTS: &str = "agent_snapshots";
+
+const OVERLAY_ROW_HEIGHT: f32 = 14.0;
+const OVERLAY_ROW_WIDTH: f32 = 120.0;
+const OVERLAY_INDENT: f32 = 16.0;
+
+/// An agent wandering around and fleeing the cursor, driven by a behavior
+/// tree ticked by the update server. Press B to toggle the per-node status
+/// overlay.
+pub struct BehaviorDemo {
+    tree: BehaviorTreeHandle,
+    /// the tree ticks slower than the display refreshes, the agent is drawn
+    /// between its last two positions
+    agent_snapshots: Arc<StateSnapshots<Vec2>>,
+    show_overlay: AtomicBool,
+}
+
+impl Scene for BehaviorDemo {
+    fn handle_event<'a>(
+        self: Arc<Self>,
+        ctx: &mut MainContext,
+        _: &RootScene,
+        event: GameEvent<'a>,
+    ) -> Option<GameEvent<'a>> {
+        match &event {
+            Event::WindowEvent { window_id, event }
+                if ctx.display.get_window_id() == *window_id =>
+            {
+                match event {
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Released,
+                                virtual_keycode: Some(VirtualKeyCode::B),
+                                ..
+                            },
+                        ..
+                    } => {
+                        self.show_overlay.fetch_xor(true, Ordering::Relaxed);
+                    }
+
+                    WindowEvent::CursorMoved { position, .. } => {
+                        let height = ctx.display.get_size().height as f32;
+                        // framebuffer coordinates have their origin at the bottom-left corner
+                        self.tree.blackboard.lock().set(
+                            "cursor",
+                            Vec2::new(position.x as f32, height - position.y as f32),
+                        );
+                    }
+
+                    _ => {}
+                }
+            }
+
+            GameEvent::UserEvent(GameUserEvent::CheckedResize { display_size, .. }) => {
+                self.tree.blackboard.lock().set(
+                    "bounds",
+                    Vec2::new(
+                        display_size.width.get() as f32,
+                        display_size.height.get() as f32,
+                    ),
+                );
+            }
+
+            _ => {}
+        }
+
+        Some(event)
+    }
+
+    fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
+        let agent = match self.agent_snapshots.sample(Instant::now()) {
+            Some(agent) => agent,
+            None => self.tree.blackboard.lock().get_or_default::<Vec2>("agent"),
+        };
+
+        // the agent lives in framebuffer coordinates, the shapes are drawn
+        // in UI units with the origin at the top-left corner
+        let scale = ctx.ui_scale();
+        let height = ctx.display_size.height.get() as f32;
+        let center = Vec2::new(agent.x, height - agent.y) / scale;
+        let half_size = Vec2::splat(AGENT_SIZE * 0.5) / scale;
+        ctx.draw_rounded_rect(
+            center - half_size,
+            center + half_size,
+            0.0,
+            Vec4::new(0.9, 0.6, 0.2, 1.0),
+        );
+
+        if self.show_overlay.load(Ordering::Relaxed) {
+            for (i, node) in self.tree.snapshot.lock().iter().enumerate() {
+                let color = match node.status {
+                    Some(Status::Success) => Vec4::new(0.2, 0.8, 0.2, 1.0),
+                    Some(Status::Failure) => Vec4::new(0.8, 0.2, 0.2, 1.0),
+                    Some(Status::Running) => Vec4::new(0.9, 0.8, 0.1, 1.0),
+                    None => Vec4::new(0.3, 0.3, 0.3, 1.0),
+                };
+                let min = Vec2::new(
+                    8.0 + node.depth as f32 * OVERLAY_INDENT,
+                    8.0 + i as f32 * OVERLAY_ROW_HEIGHT,
+                );
+                let size = Vec2::new(OVERLAY_ROW_WIDTH, OVERLAY_ROW_HEIGHT - 2.0);
+                ctx.draw_rounded_rect(min, min + size, 0.0, color);
+            }
+        }
+    }
+}
+
+fn move_towards(blackboard: &mut Blackboard, target: Vec2) -> bool {
+    let dt = BehaviorTree::DEFAULT_TICK_INTERVAL.as_secs_f32();
+    let agent = blackboard.get_or_default::<Vec2>("agent");
+    let offset = target - agent;
+    let step = AGENT_SPEED * dt;
+    let (agent, arrived) = if offset.length() <= step {
+        (target, true)
+    } else {
+        (agent + offset.normalize() * step, false)
+    };
+    blackboard.set("agent", agent);
+    if let Some(snapshots) = blackboard.get::<Arc<StateSnapshots<Vec2>>>(AGENT_SNAPSHOTS) {
+        snapshots.publish(agent);
+    }
+    arrived
+}
+
+impl BehaviorDemo {
+    pub fn new(main_ctx: &mut MainContext) -> anyhow::Result<Self> {
+        let tree = BehaviorTree::new(Node::selector(
+            "root",
+            vec![
+                Node::sequence(
+                    "flee",
+                    vec![
+                        Node::condition("cursor_near", |bb| {
+                            bb.get::<Vec2>("cursor")
+                                .map(|&cursor| {
+                                    cursor.distance(bb.get_or_default("agent")) < FLEE_DISTANCE
+                                })
+                                .unwrap_or_default()
+                        }),
+                        Node::action("run_away", |bb| {
+                            let agent = bb.get_or_default::<Vec2>("agent");
+                            let cursor = bb.get_or_default::<Vec2>("cursor");
+                            let away = (agent - cursor).normalize_or_zero() * FLEE_DISTANCE;
+                            let bounds = bb.get_or_default::<Vec2>("bounds");
+                            move_towards(bb, (agent + away).clamp(Vec2::ZERO, bounds));
+                            bb.remove("waypoint");
+                            Status::Success
+                        }),
+                    ],
+                ),
+                Node::sequence(
+                    "patrol",
+                    vec![
+                        Node::action("pick_waypoint", |bb| {
+                            if !bb.contains("waypoint") {
+                                let bounds = bb.get_or_default::<Vec2>("bounds");
+                                let rng = match bb.get_mut::<GameRng>(RngService::BLACKBOARD_KEY) {
+                                    Some(rng) => rng,
+                                    None => return Status::Failure,
+                                };
+                                let waypoint = Vec2::new(
+                                    rng.gen_range(0.0..=bounds.x),
+                                    rng.gen_range(0.0..=bounds.y),
+                                );
+                                bb.set("waypoint", waypoint);
+                            }
+                            Status::Success
+                        }),
+                        Node::action("move_to_waypoint", |bb| {
+                            let waypoint = bb.get_or_default::<Vec2>("waypoint");
+                            if move_towards(bb, waypoint) {
+                                bb.remove("waypoint");
+                                Status::Success
+                            } else {
+                                Status::Running
+                            }
+                        }),
+                    ],
+                ),
+            ],
+        ));
+
+        let size = main_ctx.display.get_size();
+        let bounds = Vec2::new(size.width as f32, size.height as f32);
+        let agent_snapshots = Arc::new(StateSnapshots::new());
+        {
+            let mut blackboard = tree.blackboard.lock();
+            blackboard.set("bounds", bounds);
+            blackboard.set("agent", bounds * 0.5);
+            blackboard.set(AGENT_SNAPSHOTS, agent_snapshots.clone());
+        }
+
+        Ok(Self {
+            tree: main_ctx
+                .channels
+                .update
+                .add_behavior_tree(tree)
+                .context("unable to register behavior tree")?,
+            agent_snapshots,
+            show_overlay: AtomicBool::new(false),
+        })
+    }
+}
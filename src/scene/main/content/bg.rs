@@ -1,9 +1,14 @@
-use std::sync::Arc;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use anyhow::Context;
 use glam::{Mat3, Vec2};
 use glutin::prelude::GlConfig;
-use image::EncodableLayout;
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
     event::WindowEvent,
@@ -11,43 +16,49 @@ use winit::{
 
 use crate::{
     enclose,
-    events::{GameEvent, GameUserEvent},
+    events::{error::Subsystem, GameEvent, GameUserEvent},
     exec::{
         main_ctx::MainContext,
-        server::{draw::ServerSendChannelExt, GameServerSendChannel},
-        task::{JoinToken, Joinable, TryJoinTaskResult},
+        server::{
+            draw::{self, ServerSendChannelExt},
+            GameServerSendChannel, ServerSendChannel,
+        },
+        task::Cancellable,
     },
     graphics::{
-        blur::BlurRenderer,
+        blur::BlurEffect,
+        context::DrawContext,
+        image_loader::decode_image_file,
+        post_effect::PostEffect,
         quad_renderer::QuadRenderer,
+        render_graph::{RenderPass, RenderTarget, SCENE_PASS},
         wrappers::{
-            framebuffer::{DefaultTextureFramebuffer, Framebuffer},
-            texture::{TextureHandle, TextureType},
+            framebuffer::DefaultTextureFramebuffer,
+            texture::{TextureFormat, TextureHandle, TextureType},
         },
     },
     scene::{main::RootScene, Scene},
     utils::{
         clock::{Clock, SteadyClock},
         error::ResultExt,
-        mpsc::Sender,
         mutex::Mutex,
     },
 };
 
-pub enum LoadTextureResult {
-    Pending(JoinToken<PhysicalSize<u32>>),
-    Done(PhysicalSize<u32>),
-}
+const BLUR_SIGMA: f32 = 1.0;
+/// Draws the image cropped to the display into an offscreen target.
+const IMAGE_PASS: &str = "background image";
+/// Blurs the output of `IMAGE_PASS`, sampled by the scene pass.
+const BLUR_PASS: &str = "background blur";
 
 pub struct Background {
     renderer: QuadRenderer,
-    texture: TextureHandle,
-    post_processed_texture: Mutex<Option<TextureHandle>>,
+    blurred: DefaultTextureFramebuffer,
+    /// set on the draw server once the image is uploaded
+    image_size: Arc<Mutex<Option<PhysicalSize<u32>>>>,
     offset: Mutex<Vec2>,
     clock: SteadyClock,
-    blur: Mutex<BlurRenderer>,
-    load_texture_result: Mutex<LoadTextureResult>,
-    screen_framebuffer: Mutex<DefaultTextureFramebuffer>,
+    draw: ServerSendChannel<draw::RecvMsg>,
 }
 
 impl Scene for Background {
@@ -58,22 +69,6 @@ impl Scene for Background {
         event: GameEvent<'a>,
     ) -> Option<GameEvent<'a>> {
         match &event {
-            GameEvent::UserEvent(GameUserEvent::CheckedResize {
-                display_size: PhysicalSize { width, height },
-                ..
-            }) => {
-                self.resize(
-                    ctx,
-                    PhysicalSize {
-                        width: width.get(),
-                        height: height.get(),
-                    },
-                    1.0,
-                )
-                .context("unable to handle resize event")
-                .log_error();
-            }
-
             GameEvent::WindowEvent {
                 window_id,
                 event: WindowEvent::CursorMoved { position, .. },
@@ -85,8 +80,10 @@ impl Scene for Background {
         Some(event)
     }
 
-    fn draw(self: Arc<Self>, ctx: &mut crate::graphics::context::DrawContext) {
-        if let Some(texture) = &*self.post_processed_texture.lock() {
+    fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
+        // blurred by the render graph before the scene pass, see `new`
+        if self.image_size.lock().is_some() {
+            let texture = &self.blurred.texture;
             const OFFSET_FACTOR_VECTOR: Vec2 = Vec2::new(0.995, 0.998);
             const BOUNDS_NEG_1: [Vec2; 2] = [Vec2::new(0.0, 0.0), OFFSET_FACTOR_VECTOR];
             const BOUNDS_POS_1: [Vec2; 2] = [
@@ -123,184 +120,177 @@ fn lerp_vec2(amt: Vec2, min: Vec2, max: Vec2) -> Vec2 {
 
 impl Background {
     pub fn new(main_ctx: &mut MainContext) -> anyhow::Result<Arc<Self>> {
-        let renderer = QuadRenderer::new(main_ctx.dummy_vao.clone(), &mut main_ctx.channels.draw)
+        let dummy_vao = main_ctx.dummy_vao();
+        let draw = &mut main_ctx.channels.draw;
+        let renderer = QuadRenderer::new(dummy_vao.clone(), draw)
             .context("quad renderer initialization failed")?;
-        let blur = Mutex::new(
-            BlurRenderer::new(main_ctx.dummy_vao.clone(), &mut main_ctx.channels.draw)
-                .context("blur renderer initialization failed")?,
+        let blur = BlurEffect::new(dummy_vao, draw, BLUR_SIGMA)
+            .context("blur effect initialization failed")?;
+        let image = DefaultTextureFramebuffer::new(draw, "background image framebuffer")
+            .context("background image framebuffer initialization failed")?;
+        let blurred = DefaultTextureFramebuffer::new(draw, "background blur framebuffer")
+            .context("background blur framebuffer initialization failed")?;
+        let texture = TextureHandle::new_args(draw, "test texture", TextureType::E2D)
+            .context("unable to initialize test texture")?;
+        let image_size = Arc::new(Mutex::new(None));
+
+        let passes = Self::passes(
+            renderer.clone(),
+            blur,
+            texture.clone(),
+            image_size.clone(),
+            image,
+            blurred.clone(),
         );
-        let mut screen_framebuffer =
-            DefaultTextureFramebuffer::new(&mut main_ctx.channels.draw, "screen framebuffer")
-                .context("screen framebuffer initialization failed")?;
-        screen_framebuffer.resize(&mut main_ctx.channels.draw, main_ctx.display.get_size())?;
-        let texture = TextureHandle::new_args(
-            &mut main_ctx.channels.draw,
-            "test texture",
-            TextureType::E2D,
-        )
-        .context("unable to initialize test texture")?;
-        let (sender, join_token) = JoinToken::new();
+        let scene_input = RenderTarget::Offscreen(blurred.clone());
+        draw.execute(move |context, _| {
+            for pass in passes {
+                context.render_graph.add_pass(pass);
+            }
+            context.render_graph.add_input(SCENE_PASS, scene_input);
+        })
+        .context("unable to add background render passes")?;
 
         let slf = Arc::new(Self {
-            texture: texture.clone(),
-            post_processed_texture: Mutex::new(None),
-            blur,
             renderer,
-            load_texture_result: Mutex::new(LoadTextureResult::Pending(join_token)),
-            screen_framebuffer: Mutex::new(screen_framebuffer),
+            blurred,
+            image_size: image_size.clone(),
             offset: Mutex::new(Vec2::ZERO),
             clock: SteadyClock::new(),
+            draw: main_ctx.channels.draw.clone_sender(),
         });
 
-        slf.init_test_texture(main_ctx, texture, sender)
+        Self::init_test_texture(main_ctx, texture, image_size)
             .context("unable to initialize test texture")?;
 
         Ok(slf)
     }
 
-    #[allow(unused_mut)]
+    /// `IMAGE_PASS` and `BLUR_PASS`, redrawn once the image is loaded and
+    /// whenever the display is resized, the graph resizing their targets.
+    fn passes(
+        renderer: QuadRenderer,
+        mut blur: BlurEffect,
+        texture: TextureHandle,
+        image_size: Arc<Mutex<Option<PhysicalSize<u32>>>>,
+        image: DefaultTextureFramebuffer,
+        blurred: DefaultTextureFramebuffer,
+    ) -> [RenderPass; 2] {
+        let redrawn = Arc::new(AtomicBool::new(false));
+        let mut drawn = None;
+        let image_pass = RenderPass::new(
+            IMAGE_PASS,
+            RenderTarget::Offscreen(image.clone()),
+            enclose!((redrawn) move |context: &mut DrawContext, _: Option<&RootScene>| {
+                let texture_dimensions = match *image_size.lock() {
+                    Some(size) => size,
+                    None => return,
+                };
+                let viewport_size = context.display_size;
+                if drawn == Some((viewport_size, texture_dimensions)) {
+                    return;
+                }
+                drawn = Some((viewport_size, texture_dimensions));
+                let vw = viewport_size.width.get() as f32;
+                let vh = viewport_size.height.get() as f32;
+                let tw = texture_dimensions.width as f32;
+                let th = texture_dimensions.height as f32;
+                let var = vw / vh;
+                let tar = tw / th;
+                let (hw, hh) = if var < tar {
+                    (0.5 * var / tar, 0.5)
+                } else {
+                    (0.5, 0.5 * tar / var)
+                };
+                renderer.draw(
+                    context,
+                    *texture.get(context),
+                    &QuadRenderer::FULL_WINDOW_POS_BOUNDS,
+                    &[[0.5 - hw, 0.5 + hh].into(), [0.5 + hw, 0.5 - hh].into()],
+                    &Vec2::ZERO,
+                    &Mat3::IDENTITY,
+                );
+                redrawn.store(true, Ordering::Relaxed);
+            }),
+        );
+
+        let mut blur_size = None;
+        let blur_pass = RenderPass::new(
+            BLUR_PASS,
+            RenderTarget::Offscreen(blurred.clone()),
+            move |context: &mut DrawContext, _: Option<&RootScene>| {
+                if !redrawn.swap(false, Ordering::Relaxed) {
+                    return;
+                }
+                let size = PhysicalSize::new(
+                    context.display_size.width.get(),
+                    context.display_size.height.get(),
+                );
+                let mut result = Ok(());
+                if blur_size != Some(size) {
+                    result = blur.resize(context, size);
+                    blur_size = Some(size);
+                }
+                result
+                    .and_then(|()| {
+                        let src = *image.texture.get(context);
+                        let dst = *blurred.framebuffer.get(context);
+                        blur.apply(context, src, dst)
+                    })
+                    .context("unable to blur background")
+                    .log_warn();
+            },
+        )
+        .input(RenderTarget::Offscreen(image.clone()));
+
+        [image_pass, blur_pass]
+    }
+
     fn init_test_texture(
-        self: &Arc<Self>,
         main_ctx: &mut MainContext,
         test_texture: TextureHandle,
-        sender: Sender<PhysicalSize<u32>>,
+        image_size: Arc<Mutex<Option<PhysicalSize<u32>>>>,
     ) -> anyhow::Result<()> {
         let channel = main_ctx.channels.draw.clone_sender();
         let proxy = main_ctx.event_loop_proxy.clone();
 
-        let slf = self.clone();
-        main_ctx.execute_blocking_task(enclose!((test_texture) move || {
-            let result: anyhow::Result<PhysicalSize<u32>> = (|| {
-                let img = image::io::Reader::open("BG.jpg")
-                    .context("unable to load test texture")?
-                    .decode()
-                    .context("unable to decode test texture")?
-                    .into_rgba8();
-                let img_size = PhysicalSize::new(img.width(), img.height());
+        main_ctx.execute_blocking_task(enclose!((test_texture) move |cancel| {
+            let result: anyhow::Result<()> = (|| {
+                let mut image = decode_image_file(Path::new("BG.jpg"))
+                    .context("unable to load test texture")?;
+                cancel.check()?;
 
                 channel.execute_draw_event(move |context, _| {
-                    let tex_handle = test_texture.get(context);
-                    tex_handle.bind();
-                    unsafe {
-                        gl::TexImage2D(
-                            gl::TEXTURE_2D,
-                            0,
-                            if context.gl_config.srgb_capable() {
-                                gl::SRGB8_ALPHA8.try_into().unwrap()
-                            } else {
-                                gl::RGBA8.try_into().unwrap()
-                            },
-                            img.width().try_into().unwrap(),
-                            img.height().try_into().unwrap(),
-                            0,
-                            gl::RGBA,
-                            gl::UNSIGNED_BYTE,
-                            img.as_bytes().as_ptr() as *const _,
-                        );
-                        gl::TexParameteri(
-                            gl::TEXTURE_2D,
-                            gl::TEXTURE_MIN_FILTER,
-                            gl::LINEAR_MIPMAP_LINEAR.try_into().unwrap(),
-                        );
-                        gl::TexParameteri(
-                            gl::TEXTURE_2D,
-                            gl::TEXTURE_MAG_FILTER,
-                            gl::LINEAR.try_into().unwrap(),
-                        );
-                        gl::GenerateMipmap(gl::TEXTURE_2D);
+                    image.options.format = if context.gl_config.srgb_capable() {
+                        TextureFormat::Srgb8Alpha8
+                    } else {
+                        TextureFormat::Rgba8
                     };
-
-                    *slf.post_processed_texture.lock() = Some(slf.blur.lock().output_texture_handle());
-
-                    [GameUserEvent::Execute(Box::new(move |ctx, _| {
-                        slf.resize(ctx, ctx.display.get_size(), 1.0)
-                    }))]
-                })?;
-
-                Ok(img_size)
+                    if let Err(e) = test_texture.get(context).upload(&image) {
+                        return Some(GameUserEvent::error(Subsystem::Draw, "draw.upload_texture", e));
+                    }
+                    *image_size.lock() = Some(image.size);
+                    None
+                })
             })();
 
             match result {
-                Ok(result) => sender.send(result).log_warn(),
-                Err(err) => proxy.send_event(GameUserEvent::Error(err)).log_warn(),
+                Ok(()) => {}
+                // the scene is gone, nobody is waiting for the texture
+                Err(_) if cancel.is_cancelled() => {}
+                Err(err) => proxy
+                    .send_event(GameUserEvent::error(
+                        Subsystem::Content,
+                        "content.load_background",
+                        err,
+                    ))
+                    .log_warn(),
             };
         }));
 
         Ok(())
     }
 
-    fn poll_texture_dimensions(result: &Mutex<LoadTextureResult>) -> Option<PhysicalSize<u32>> {
-        let mut lock = result.lock();
-        match &*lock {
-            LoadTextureResult::Done(texture_dimensions) => Some(*texture_dimensions),
-            LoadTextureResult::Pending(join_handle) => match join_handle.try_join() {
-                TryJoinTaskResult::JoinedResultTaken => {
-                    tracing::warn!("Texture loading task failed, the error (if present) was reported to the event loop via a GameUserEvent::Error event");
-                    None
-                }
-                TryJoinTaskResult::Joined(result) => {
-                    *lock = LoadTextureResult::Done(result);
-                    Some(result)
-                }
-                _ => None,
-            },
-        }
-    }
-
-    fn resize(
-        &self,
-        main_ctx: &mut MainContext,
-        size: PhysicalSize<u32>,
-        blur_factor: f32,
-    ) -> anyhow::Result<()> {
-        if let Some(texture_dimensions) = Self::poll_texture_dimensions(&self.load_texture_result) {
-            let (screen_framebuffer, screen_fb_texture) = {
-                let mut lock = self.screen_framebuffer.lock();
-                lock.resize(&mut main_ctx.channels.draw, size)
-                    .context("unable to resize screen framebuffer")?;
-                (lock.framebuffer.clone(), lock.texture.clone())
-            };
-            let renderer = self.renderer.clone();
-            let texture = self.texture.clone();
-            main_ctx
-                .channels
-                .draw
-                .execute_draw_event(move |context, _| {
-                    screen_framebuffer.get(context).bind();
-                    let viewport_size = context.display_size;
-                    let vw = viewport_size.width.get() as f32;
-                    let vh = viewport_size.height.get() as f32;
-                    let tw = texture_dimensions.width as f32;
-                    let th = texture_dimensions.height as f32;
-                    let var = vw / vh;
-                    let tar = tw / th;
-                    let (hw, hh) = if var < tar {
-                        (0.5 * var / tar, 0.5)
-                    } else {
-                        (0.5, 0.5 * tar / var)
-                    };
-                    renderer.draw(
-                        context,
-                        *texture.get(context),
-                        &QuadRenderer::FULL_WINDOW_POS_BOUNDS,
-                        &[[0.5 - hw, 0.5 + hh].into(), [0.5 + hw, 0.5 - hh].into()],
-                        &Vec2::ZERO,
-                        &Mat3::IDENTITY,
-                    );
-                    Framebuffer::unbind_static();
-                    []
-                })?;
-            self.blur.lock().redraw(
-                &mut main_ctx.channels.draw,
-                size,
-                screen_fb_texture,
-                0.0,
-                blur_factor,
-            )?;
-        }
-        Ok(())
-    }
-
     fn cursor_moved(&self, ctx: &mut MainContext, pos: &PhysicalPosition<f64>) {
         let PhysicalPosition { x, y } = pos;
         let PhysicalSize { width, height } = ctx.display.get_size();
@@ -319,3 +309,18 @@ impl Background {
         *self.offset.lock() = offset;
     }
 }
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        let scene_input = RenderTarget::Offscreen(self.blurred.clone());
+        self.draw
+            .execute(move |context, _| {
+                let graph = &mut context.render_graph;
+                graph.remove_input(SCENE_PASS, &scene_input);
+                graph.remove_pass(BLUR_PASS);
+                graph.remove_pass(IMAGE_PASS);
+            })
+            .context("unable to remove background render passes")
+            .log_warn();
+    }
+}
@@ -0,0 +1,71 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::Context;
+use glam::Vec2;
+
+use crate::{
+    exec::{
+        main_ctx::MainContext,
+        server::{
+            audio::{self, ServerSendChannelExt},
+            GameServerSendChannel, ServerSendChannel,
+        },
+    },
+    graphics::{
+        camera::Camera2D,
+        context::DrawContext,
+        tilemap::{tiled, TileMapRenderer},
+    },
+    scene::Scene,
+    ui::utils::geom::{UIPos, UIRect},
+    utils::{
+        clock::{Clock, SteadyClock},
+        error::ResultExt,
+        mutex::Mutex,
+    },
+};
+
+/// A Tiled map loaded from `PATH`, the camera pans slowly across it so that
+/// the parallax layers show. The audio listener follows the camera.
+pub struct Level {
+    renderer: Mutex<TileMapRenderer>,
+    map_size: Vec2,
+    clock: SteadyClock,
+    audio: ServerSendChannel<audio::RecvMsg>,
+}
+
+impl Level {
+    pub const PATH: &'static str = "level.tmx";
+    /// seconds for a back and forth pan across the map
+    const PAN_PERIOD: f64 = 30.0;
+
+    pub fn new(main_ctx: &mut MainContext) -> anyhow::Result<Self> {
+        let map = tiled::load(Path::new(Self::PATH))?;
+        let (renderer, loaded) =
+            TileMapRenderer::new(main_ctx, &map).context("unable to create tilemap renderer")?;
+        main_ctx.spawn_local(async move { loaded.await.context("unable to load level tilesets") });
+        Ok(Self {
+            renderer: Mutex::new(renderer),
+            map_size: map.size(),
+            clock: SteadyClock::new(),
+            audio: main_ctx.channels.audio.clone_sender(),
+        })
+    }
+}
+
+impl Scene for Level {
+    fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
+        let mut camera = Camera2D::new(UIRect::new(UIPos::ZERO, ctx.ui_size));
+        let view_size = Vec2::from(ctx.ui_size);
+        let phase = (self.clock.now() / Self::PAN_PERIOD * std::f64::consts::TAU).sin() as f32;
+        // pans between the left and right edges, centered if the map is
+        // narrower than the window
+        let overflow = (self.map_size - view_size).max(Vec2::ZERO);
+        camera.position = self.map_size * 0.5 + Vec2::new(overflow.x * 0.5 * phase, 0.0);
+        ctx.draw_tilemap(&self.renderer.lock(), &camera);
+        self.audio
+            .set_listener(camera.position)
+            .context("unable to move the audio listener")
+            .log_warn();
+    }
+}
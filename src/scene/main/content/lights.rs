@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use glam::{Vec2, Vec3};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::WindowEvent,
+};
+
+use crate::{
+    events::{GameEvent, GameUserEvent},
+    exec::{event_bus::Subscription, main_ctx::MainContext},
+    graphics::{
+        context::DrawContext,
+        lighting::{Light, LightRenderer, LightingState, Occluder},
+    },
+    scene::{main::RootScene, Scene},
+    utils::{
+        error::ResultExt,
+        mutex::Mutex,
+        property::{Property, PropertyChanged},
+    },
+};
+
+pub struct Lights {
+    renderer: Mutex<LightRenderer>,
+    state: Arc<Mutex<LightingState>>,
+    properties: LightProperties,
+    _property_subscription: Subscription,
+}
+
+/// Tunable from the inspector.
+#[derive(Clone)]
+struct LightProperties {
+    ambient: Property,
+    intensity: Property,
+    shadows: Property,
+}
+
+impl LightProperties {
+    fn apply(&self, state: &mut LightingState) {
+        state.ambient = Vec3::splat(self.ambient.as_f32());
+        for light in state.lights.iter_mut() {
+            light.intensity = self.intensity.as_f32();
+            light.cast_shadows = self.shadows.as_bool();
+        }
+    }
+}
+
+impl Scene for Lights {
+    fn handle_event<'a>(
+        self: Arc<Self>,
+        ctx: &mut MainContext,
+        _: &RootScene,
+        event: GameEvent<'a>,
+    ) -> Option<GameEvent<'a>> {
+        match &event {
+            GameEvent::UserEvent(GameUserEvent::CheckedResize {
+                display_size: PhysicalSize { width, height },
+                ..
+            }) => {
+                self.resize(
+                    ctx,
+                    PhysicalSize {
+                        width: width.get(),
+                        height: height.get(),
+                    },
+                )
+                .context("unable to handle resize event")
+                .log_error();
+            }
+
+            GameEvent::WindowEvent {
+                window_id,
+                event: WindowEvent::CursorMoved { position, .. },
+            } if *window_id == ctx.display.get_window_id() => self.cursor_moved(ctx, position),
+
+            _ => {}
+        }
+
+        Some(event)
+    }
+
+    fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
+        let state = self.state.lock();
+        self.renderer.lock().draw(ctx, &state);
+    }
+}
+
+impl Lights {
+    pub fn new(main_ctx: &mut MainContext) -> anyhow::Result<Self> {
+        let mut renderer = LightRenderer::new(main_ctx.dummy_vao(), &mut main_ctx.channels.draw)
+            .context("light renderer initialization failed")?;
+        let size = main_ctx.display.get_size();
+        renderer.resize(&mut main_ctx.channels.draw, size)?;
+
+        let properties = LightProperties {
+            ambient: main_ctx
+                .properties
+                .register_f32("lights.ambient", 0.0..=1.0, 0.15),
+            intensity: main_ctx
+                .properties
+                .register_f32("lights.intensity", 0.0..=4.0, 1.0),
+            shadows: main_ctx.properties.register_bool("lights.shadows", true),
+        };
+        let mut state = Self::initial_state(size);
+        properties.apply(&mut state);
+        let state = Arc::new(Mutex::new(state));
+        let property_subscription = main_ctx.event_bus.subscribe({
+            let (state, properties) = (state.clone(), properties.clone());
+            move |_, _, change: &PropertyChanged| {
+                if change.name.starts_with("lights.") {
+                    properties.apply(&mut state.lock());
+                }
+                Ok(())
+            }
+        });
+
+        Ok(Self {
+            renderer: Mutex::new(renderer),
+            state,
+            properties,
+            _property_subscription: property_subscription,
+        })
+    }
+
+    fn initial_state(size: PhysicalSize<u32>) -> LightingState {
+        let size = Vec2::new(size.width as f32, size.height as f32);
+        let center = size * 0.5;
+        LightingState {
+            ambient: Vec3::splat(0.15),
+            lights: vec![
+                // follows the cursor
+                Light::point(center, size.min_element() * 0.6, Vec3::new(1.0, 0.9, 0.7)),
+                Light::cone(
+                    Vec2::new(0.0, size.y),
+                    size.max_element(),
+                    Vec3::new(0.3, 0.5, 1.0),
+                    Vec2::new(1.0, -1.0),
+                    0.3,
+                ),
+            ],
+            occluders: vec![
+                Occluder::new(center * 0.5, center * 0.5 + Vec2::splat(60.0)),
+                Occluder::new(center * 1.4, center * 1.4 + Vec2::new(120.0, 30.0)),
+            ],
+        }
+    }
+
+    fn resize(&self, main_ctx: &mut MainContext, size: PhysicalSize<u32>) -> anyhow::Result<()> {
+        self.renderer
+            .lock()
+            .resize(&mut main_ctx.channels.draw, size)
+            .context("unable to resize light map")?;
+        let cursor_light = self.state.lock().lights.first().copied();
+        let mut state = self.state.lock();
+        *state = Self::initial_state(size);
+        self.properties.apply(&mut state);
+        if let (Some(light), Some(old_light)) = (state.lights.first_mut(), cursor_light) {
+            light.position = old_light.position;
+        }
+        Ok(())
+    }
+
+    fn cursor_moved(&self, ctx: &mut MainContext, pos: &PhysicalPosition<f64>) {
+        let height = ctx.display.get_size().height as f32;
+        if let Some(light) = self.state.lock().lights.first_mut() {
+            // framebuffer coordinates have their origin at the bottom-left corner
+            light.position = Vec2::new(pos.x as f32, height - pos.y as f32);
+        }
+    }
+}
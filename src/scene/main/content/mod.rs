@@ -1,13 +1,74 @@
 use anyhow::Context;
 
-use crate::{exec::main_ctx::MainContext, scene::SceneContainer};
+use crate::{exec::main_ctx::MainContext, scene::SceneContainer, utils::error::ResultExt};
 
-use self::bg::Background;
+use self::{
+    behavior::BehaviorDemo, bg::Background, level::Level, lights::Lights, shader_toy::ShaderToy,
+    ui::UI,
+};
 
+pub mod behavior;
 pub mod bg;
+pub mod level;
+pub mod lights;
+pub mod shader_toy;
+pub mod ui;
+
+pub type SceneConstructor = fn(&mut MainContext) -> anyhow::Result<SceneContainer>;
+
+/// Content scenes in drawing order, named for the `--gallery` mode.
+pub const SCENES: &[(&str, SceneConstructor)] = &[
+    ("background", background),
+    ("level", level),
+    ("lights", lights),
+    ("behavior", behavior),
+    ("shader_toy", shader_toy),
+];
 
 pub fn new(main_ctx: &mut MainContext) -> anyhow::Result<SceneContainer> {
+    let mut container = SceneContainer::new();
+    for (_, new) in SCENES {
+        container.push_all(new(main_ctx)?);
+    }
+    // over every content scene, left out of the gallery
+    container.push_arc(UI::new(main_ctx).context("unable to initialize UI scene")?);
+    Ok(container)
+}
+
+fn background(main_ctx: &mut MainContext) -> anyhow::Result<SceneContainer> {
     let mut container = SceneContainer::new();
     container.push_arc(Background::new(main_ctx).context("unable to initialize background scene")?);
     Ok(container)
 }
+
+/// The level is optional, the scene stays empty without its map.
+fn level(main_ctx: &mut MainContext) -> anyhow::Result<SceneContainer> {
+    let mut container = SceneContainer::new();
+    if let Some(level) = Level::new(main_ctx)
+        .with_context(|| format!("unable to load level from {}", Level::PATH))
+        .log_warn()
+    {
+        container.push(level);
+    }
+    Ok(container)
+}
+
+fn lights(main_ctx: &mut MainContext) -> anyhow::Result<SceneContainer> {
+    let mut container = SceneContainer::new();
+    container.push(Lights::new(main_ctx).context("unable to initialize lights scene")?);
+    Ok(container)
+}
+
+fn behavior(main_ctx: &mut MainContext) -> anyhow::Result<SceneContainer> {
+    let mut container = SceneContainer::new();
+    container.push(
+        BehaviorDemo::new(main_ctx).context("unable to initialize behavior tree demo scene")?,
+    );
+    Ok(container)
+}
+
+fn shader_toy(main_ctx: &mut MainContext) -> anyhow::Result<SceneContainer> {
+    let mut container = SceneContainer::new();
+    container.push_arc(ShaderToy::new(main_ctx));
+    Ok(container)
+}
@@ -0,0 +1,206 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::Context;
+use glam::{Vec2, Vec4};
+use winit::event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    events::GameEvent,
+    exec::main_ctx::MainContext,
+    graphics::{
+        context::DrawContext,
+        shader_watcher::FragmentTemplate,
+        wrappers::{shader::ProgramHandle, vertex_array::VertexArrayHandle},
+    },
+    scene::{main::RootScene, Scene},
+    utils::{error::ResultExt, mutex::Mutex},
+};
+
+mod shader {
+    pub const VERTEX: &str = r#"
+    #version 300 es
+    const vec2 positions[4] = vec2[](
+        vec2(-1.0, 1.0), vec2(1.0, 1.0),
+        vec2(-1.0, -1.0), vec2(1.0, -1.0)
+    );
+    void main() {
+        gl_Position = vec4(positions[gl_VertexID], 0.0, 1.0);
+    }
+    "#;
+
+    // shadertoy-compatible header, the shader asset only has to define `mainImage`
+    pub const FRAGMENT_HEADER: &str = r#"#version 300 es
+    precision highp float;
+    layout(std140) uniform FrameData {
+        mat4 ui_projection;
+        vec2 ui_size;
+        vec2 resolution;
+        float time;
+    };
+    #define iResolution vec3(resolution, 1.0)
+    #define iTime time
+    uniform vec4 iMouse;
+    out vec4 shader_toy_out_color;
+    "#;
+
+    pub const FRAGMENT_FOOTER: &str = r#"
+    void main() {
+        mainImage(shader_toy_out_color, gl_FragCoord.xy);
+    }
+    "#;
+
+    pub const DEFAULT_SOURCE: &str = r#"
+    void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+        vec2 uv = fragCoord / iResolution.xy;
+        vec3 col = 0.5 + 0.5 * cos(iTime + uv.xyx + vec3(0.0, 2.0, 4.0));
+        float d = length(fragCoord - iMouse.xy) / iResolution.y;
+        col *= smoothstep(0.0, 0.3, d);
+        fragColor = vec4(col, 1.0);
+    }
+    "#;
+}
+
+#[derive(Default)]
+struct MouseState {
+    position: Vec2,
+    click_position: Vec2,
+    pressed: bool,
+}
+
+/// Renders a full-screen fragment shader loaded from `SHADER_PATH`, the
+/// shader is recompiled whenever the file changes. Toggled with the T key.
+pub struct ShaderToy {
+    vertex_array: VertexArrayHandle,
+    /// loaded while enabled only, the file isn't watched otherwise
+    program: Mutex<Option<ProgramHandle>>,
+    mouse: Mutex<MouseState>,
+}
+
+impl Scene for ShaderToy {
+    fn handle_event<'a>(
+        self: Arc<Self>,
+        ctx: &mut MainContext,
+        _: &RootScene,
+        event: GameEvent<'a>,
+    ) -> Option<GameEvent<'a>> {
+        match &event {
+            Event::WindowEvent { window_id, event }
+                if ctx.display.get_window_id() == *window_id =>
+            {
+                match event {
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Released,
+                                virtual_keycode: Some(VirtualKeyCode::T),
+                                ..
+                            },
+                        ..
+                    } => self.toggle(ctx),
+
+                    WindowEvent::CursorMoved { position, .. } => {
+                        let height = ctx.display.get_size().height as f32;
+                        let mut mouse = self.mouse.lock();
+                        // framebuffer coordinates have their origin at the bottom-left corner
+                        mouse.position = Vec2::new(position.x as f32, height - position.y as f32);
+                        if mouse.pressed {
+                            mouse.click_position = mouse.position;
+                        }
+                    }
+
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        let mut mouse = self.mouse.lock();
+                        mouse.pressed = *state == ElementState::Pressed;
+                        mouse.click_position = mouse.position;
+                    }
+
+                    _ => {}
+                }
+            }
+
+            _ => {}
+        }
+
+        Some(event)
+    }
+
+    fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
+        let program = match self.program.lock().clone() {
+            Some(program) => program,
+            None => return,
+        };
+
+        if let Some(program) = program.try_get(ctx) {
+            let vertex_array = self.vertex_array.get(ctx);
+            let mouse = {
+                let mouse = self.mouse.lock();
+                let sign = if mouse.pressed { 1.0 } else { -1.0 };
+                Vec4::new(
+                    mouse.position.x,
+                    mouse.position.y,
+                    mouse.click_position.x * sign,
+                    mouse.click_position.y * sign,
+                )
+            };
+
+            // reloads recreate the program, with its block unbound
+            ctx.frame_uniforms.bind_block(*program);
+            vertex_array.bind();
+            unsafe {
+                gl::UseProgram(*program);
+                gl::Uniform4f(
+                    gl::GetUniformLocation(*program, "iMouse\0".as_ptr() as *const _),
+                    mouse.x,
+                    mouse.y,
+                    mouse.z,
+                    mouse.w,
+                );
+                gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+            }
+        }
+    }
+}
+
+impl ShaderToy {
+    pub const SHADER_PATH: &'static str = "shader_toy.frag";
+    const TEMPLATE: FragmentTemplate = FragmentTemplate {
+        vertex: shader::VERTEX,
+        header: shader::FRAGMENT_HEADER,
+        footer: shader::FRAGMENT_FOOTER,
+        fallback: shader::DEFAULT_SOURCE,
+    };
+
+    pub fn new(main_ctx: &mut MainContext) -> Arc<Self> {
+        Arc::new(Self {
+            vertex_array: main_ctx.dummy_vao(),
+            program: Mutex::new(None),
+            mouse: Mutex::new(MouseState::default()),
+        })
+    }
+
+    fn toggle(&self, main_ctx: &mut MainContext) {
+        // dropped outside of the lock, the draw server takes it every frame
+        let program = self.program.lock().take();
+        if let Some(program) = program {
+            drop(program);
+            tracing::info!("shader toy enabled: false");
+            return;
+        }
+        if !Path::new(Self::SHADER_PATH).exists() {
+            tracing::info!(
+                "shader toy asset {} not found, using the built-in shader",
+                Self::SHADER_PATH
+            );
+        }
+        let program = main_ctx
+            .load_fragment_program("shader toy program", Self::SHADER_PATH, Self::TEMPLATE)
+            .context("unable to load shader toy program")
+            .log_error();
+        tracing::info!("shader toy enabled: {}", program.is_some());
+        *self.program.lock() = program;
+    }
+}
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
-use winit::event::{Event, ModifiersState, WindowEvent};
+use anyhow::Context;
+use winit::event::{ElementState, Event, ModifiersState, MouseButton, WindowEvent};
 
 use crate::{
     events::{GameEvent, GameUserEvent},
@@ -9,26 +10,36 @@ use crate::{
     scene::{main::RootScene, Scene},
     ui::{
         containers::stack::Stack,
-        event::{DragDropAction, UICursorEvent, UIFocusEvent, UIPropagatingEvent},
+        event::{
+            focus_traversal, wheel_gestures, DragDropAction, UICursorEvent, UIFocusEvent,
+            UIPropagatingEvent,
+        },
+        overlay::OverlayLayer,
         EventContext, UISizeConstraint, Widget,
     },
-    utils::mutex::Mutex,
+    utils::{
+        error::ResultExt,
+        mutex::Mutex,
+        undo::{HistoryAction, UndoShortcuts},
+    },
 };
 
 pub mod settings;
 
 pub struct UI {
     pub root: Arc<Stack>,
+    pub overlays: Arc<OverlayLayer>,
     pub modifiers: Mutex<ModifiersState>,
-    focused: Mutex<Option<Arc<dyn Widget>>>,
+    undo_shortcuts: Mutex<UndoShortcuts>,
 }
 
 impl UI {
     pub fn new(main_ctx: &mut MainContext) -> anyhow::Result<Arc<Self>> {
         let slf = Arc::new(Self {
             root: Arc::new(Stack::new()),
-            focused: Mutex::new(None),
+            overlays: main_ctx.overlays.clone(),
             modifiers: Mutex::new(ModifiersState::default()),
+            undo_shortcuts: Mutex::new(UndoShortcuts::new()),
         });
 
         settings::init(&slf);
@@ -36,6 +47,37 @@ impl UI {
         Ok(slf)
     }
 
+    /// The overlays get `event` first, then the root if they let it through.
+    fn propagate(
+        &self,
+        ctx: &mut EventContext,
+        event: UIPropagatingEvent,
+    ) -> Option<UIPropagatingEvent> {
+        let event = self.overlays.handle_propagating_event(ctx, event)?;
+        self.root.clone().handle_propagating_event(ctx, event)
+    }
+
+    fn handle_cursor(&self, ctx: &mut EventContext, event: UICursorEvent) -> Option<UICursorEvent> {
+        let event = self.overlays.handle_cursor_event(ctx, event)?;
+        self.root.clone().handle_cursor_event(ctx, event)
+    }
+
+    /// Publishes the `HistoryAction` of an undo/redo shortcut on the event
+    /// bus, for the scenes keeping a `History`. Returns whether `event` was
+    /// one.
+    fn handle_undo_shortcut(&self, ctx: &mut EventContext, event: &WindowEvent) -> bool {
+        let action = match self.undo_shortcuts.lock().handle_event(event) {
+            Some(action) => action,
+            None => return false,
+        };
+        ctx.main_ctx
+            .event_bus
+            .publish::<HistoryAction>(action)
+            .context("unable to publish history action")
+            .log_warn();
+        true
+    }
+
     fn handle_win_event<'a>(
         self: Arc<Self>,
         main_ctx: &mut MainContext,
@@ -45,8 +87,8 @@ impl UI {
         // these kinds of events contain non-copyable data
         let event = match event {
             WindowEvent::DroppedFile(path) => {
-                return if let Some(UIPropagatingEvent::DragDrop(DragDropAction::Drop(path))) =
-                    self.root.handle_propagating_event(
+                return if let Some(UIPropagatingEvent::DragDrop(DragDropAction::Drop(path))) = self
+                    .propagate(
                         &mut ctx,
                         UIPropagatingEvent::DragDrop(DragDropAction::Drop(path)),
                     ) {
@@ -57,8 +99,8 @@ impl UI {
             }
 
             WindowEvent::HoveredFile(path) => {
-                return if let Some(UIPropagatingEvent::DragDrop(DragDropAction::Hover(path))) =
-                    self.root.handle_propagating_event(
+                return if let Some(UIPropagatingEvent::DragDrop(DragDropAction::Hover(path))) = self
+                    .propagate(
                         &mut ctx,
                         UIPropagatingEvent::DragDrop(DragDropAction::Hover(path)),
                     ) {
@@ -69,8 +111,7 @@ impl UI {
             }
 
             WindowEvent::Ime(ime) => {
-                let lock = self.focused.lock();
-                return if let Some(focus_widget) = lock.as_ref() {
+                return if let Some(focus_widget) = ctx.main_ctx.get_focused_widget() {
                     if let Some(UIFocusEvent::Ime(ime)) =
                         focus_widget.handle_focus_event(&mut ctx, UIFocusEvent::Ime(ime))
                     {
@@ -88,68 +129,95 @@ impl UI {
 
         match &event {
             WindowEvent::HoveredFileCancelled => self
-                .root
-                .handle_propagating_event(
+                .propagate(
                     &mut ctx,
                     UIPropagatingEvent::DragDrop(DragDropAction::CancelDrop),
                 )
                 .is_some(),
-            WindowEvent::ReceivedCharacter(ch) => self
-                .focused
-                .lock()
-                .as_ref()
+            WindowEvent::ReceivedCharacter(ch) => ctx
+                .main_ctx
+                .get_focused_widget()
                 .map(|w| {
                     w.handle_focus_event(&mut ctx, UIFocusEvent::ReceivedCharacter(*ch))
                         .is_some()
                 })
                 .unwrap_or(true),
-            WindowEvent::KeyboardInput { input, .. } => self
-                .focused
-                .lock()
-                .as_ref()
-                .map(|w| {
-                    w.handle_focus_event(&mut ctx, UIFocusEvent::KeyboardInput(*input))
-                        .is_some()
-                })
-                .unwrap_or(true),
+            WindowEvent::KeyboardInput { input, .. } => {
+                let unhandled = ctx
+                    .main_ctx
+                    .get_focused_widget()
+                    .map(|w| {
+                        w.handle_focus_event(&mut ctx, UIFocusEvent::KeyboardInput(*input))
+                            .is_some()
+                    })
+                    .unwrap_or(true);
+                // Tab and Escape are left to the focused widget first, e.g. to
+                // indent text
+                let unhandled = unhandled && !self.overlays.handle_keyboard_input(&mut ctx, input);
+                // after the focused widget too, e.g. a text field undoing
+                // its own edits
+                let unhandled = unhandled && !self.handle_undo_shortcut(&mut ctx, &event);
+                match focus_traversal(input, *self.modifiers.lock()) {
+                    Some(direction) if unhandled => {
+                        // the focus doesn't leave modal overlays
+                        let root: Arc<dyn Widget> = match self.overlays.modal_root() {
+                            Some(modal) => modal,
+                            None => self.root.clone(),
+                        };
+                        !ctx.main_ctx.move_focus(&root, direction)
+                    }
+                    _ => unhandled,
+                }
+            }
             WindowEvent::ModifiersChanged(mods) => {
                 *self.modifiers.lock() = *mods;
+                self.handle_undo_shortcut(&mut ctx, &event);
                 false
             }
             WindowEvent::CursorMoved { position, .. } => {
                 let scale_factor = ctx.main_ctx.display.get_scale_factor();
-                self.root
-                    .handle_cursor_event(
-                        &mut ctx,
-                        UICursorEvent::CursorMoved(position.to_logical(scale_factor).into()),
-                    )
-                    .is_some()
+                self.handle_cursor(
+                    &mut ctx,
+                    UICursorEvent::CursorMoved(position.to_logical::<f32>(scale_factor).into()),
+                )
+                .is_some()
             }
             WindowEvent::CursorEntered { .. } => self
-                .root
-                .handle_cursor_event(&mut ctx, UICursorEvent::CursorEntered)
+                .handle_cursor(&mut ctx, UICursorEvent::CursorEntered)
                 .is_some(),
             WindowEvent::CursorLeft { .. } => self
-                .root
-                .handle_cursor_event(&mut ctx, UICursorEvent::CursorExited)
-                .is_some(),
-            WindowEvent::MouseWheel { delta, .. } => self
-                .root
-                .handle_propagating_event(&mut ctx, UIPropagatingEvent::MouseWheel(*delta))
-                .is_some(),
-            WindowEvent::MouseInput { state, button, .. } => self
-                .root
-                .handle_propagating_event(
-                    &mut ctx,
-                    UIPropagatingEvent::MouseInput {
-                        state: *state,
-                        button: *button,
-                    },
-                )
+                .handle_cursor(&mut ctx, UICursorEvent::CursorExited)
                 .is_some(),
+            WindowEvent::MouseWheel { delta, .. } => {
+                let modifiers = *self.modifiers.lock();
+                let scale_factor = ctx.main_ctx.display.get_scale_factor();
+                // the wheel event is consumed if any of its gestures is
+                wheel_gestures(*delta, modifiers, scale_factor)
+                    .into_iter()
+                    .map(|gesture| self.propagate(&mut ctx, gesture))
+                    .fold(true, |unhandled, gesture| unhandled && gesture.is_some())
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let press = (*state, *button) == (ElementState::Pressed, MouseButton::Left);
+                if press {
+                    ctx.main_ctx.begin_focus_press();
+                }
+                let unhandled = self
+                    .propagate(
+                        &mut ctx,
+                        UIPropagatingEvent::MouseInput {
+                            state: *state,
+                            button: *button,
+                        },
+                    )
+                    .is_some();
+                if press {
+                    ctx.main_ctx.end_focus_press();
+                }
+                unhandled
+            }
             WindowEvent::ThemeChanged(theme) => self
-                .root
-                .handle_propagating_event(&mut ctx, UIPropagatingEvent::ThemeChanged(*theme))
+                .propagate(&mut ctx, UIPropagatingEvent::ThemeChanged(*theme))
                 .is_some(),
 
             _ => true,
@@ -167,6 +235,7 @@ impl Scene for UI {
     ) -> Option<GameEvent<'a>> {
         if let Event::UserEvent(GameUserEvent::CheckedResize { ui_size, .. }) = &event {
             self.root.layout(&UISizeConstraint::exact(*ui_size));
+            self.overlays.layout(*ui_size);
         }
         if let Event::WindowEvent { window_id, event } = event {
             if window_id == ctx.display.get_window_id() {
@@ -182,6 +251,16 @@ impl Scene for UI {
     }
 
     fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
-        self.root.draw(ctx)
+        self.root.draw(ctx);
+        // widgets queue the draws that have to be sorted across the tree,
+        // the ones of the root stay under the overlays
+        ctx.flush_draw_queue();
+        self.overlays.draw(ctx);
+        ctx.flush_draw_queue();
+    }
+
+    fn draw_pick(self: Arc<Self>, ctx: &mut DrawContext) {
+        self.root.draw_pick(ctx);
+        self.overlays.draw_pick(ctx);
     }
 }
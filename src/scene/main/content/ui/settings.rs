@@ -2,4 +2,4 @@ use std::sync::Arc;
 
 use super::UI;
 
-pub fn init(ui: Arc<UI>) {}
+pub fn init(_ui: &Arc<UI>) {}
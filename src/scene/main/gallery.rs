@@ -0,0 +1,185 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use image::RgbaImage;
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    display::backend,
+    events::{GameEvent, GameUserEvent},
+    exec::main_ctx::MainContext,
+    graphics::{context::DrawContext, screenshot, wrappers::texture::ImageData},
+    scene::{Scene, SceneContainer},
+    test::gallery::{self, GalleryEntry},
+    utils::{args::args, error::ResultExt, mutex::Mutex},
+};
+
+use super::{content, RootScene};
+
+/// Renders every content scene alone for `--gallery-frames` frames, then
+/// captures it, see `args().gallery`.
+pub struct Gallery {
+    dir: PathBuf,
+    frames: u32,
+    state: Mutex<GalleryState>,
+    results: Arc<Mutex<GalleryResults>>,
+}
+
+/// Screenshots are written by blocking tasks, the page is written by
+/// whoever comes last between them and the end of the gallery.
+#[derive(Default)]
+struct GalleryResults {
+    entries: Vec<(usize, Option<GalleryEntry>)>,
+    /// number of captured scenes, known once every scene is captured
+    expected: Option<usize>,
+}
+
+#[derive(Default)]
+struct GalleryState {
+    index: usize,
+    scene: Option<Arc<SceneContainer>>,
+    frames: u32,
+    captured: usize,
+}
+
+impl Gallery {
+    pub fn new(main_ctx: &mut MainContext, dir: PathBuf) -> anyhow::Result<Arc<Self>> {
+        if !backend::renders_frames() {
+            anyhow::bail!(
+                "gallery mode can't capture anything when headless, select an offscreen --gl-backend"
+            );
+        }
+        gallery::prepare_run(&dir).context("unable to prepare gallery directory")?;
+        let slf = Arc::new(Self {
+            dir,
+            frames: args().gallery_frames.max(1),
+            state: Mutex::new(GalleryState::default()),
+            results: Arc::new(Mutex::new(GalleryResults::default())),
+        });
+        slf.start(main_ctx, 0);
+        Ok(slf)
+    }
+
+    /// Replaces the current scene by the first scene from `index` that can
+    /// be initialized.
+    fn start(&self, main_ctx: &mut MainContext, mut index: usize) {
+        let mut state = self.state.lock();
+        state.scene = None;
+        while let Some((name, new)) = content::SCENES.get(index) {
+            if let Some(scene) = new(main_ctx)
+                .with_context(|| format!("unable to initialize gallery scene {name}"))
+                .log_error()
+            {
+                state.scene = Some(Arc::new(scene));
+                break;
+            }
+            index += 1;
+        }
+        state.index = index;
+        state.frames = 0;
+        if state.scene.is_none() {
+            self.results.lock().expected = Some(state.captured);
+            finish_if_done(&self.dir, &self.results, &main_ctx.event_loop_proxy);
+        }
+    }
+
+    fn capture(&self, main_ctx: &mut MainContext, image: RgbaImage) {
+        let index = {
+            let mut state = self.state.lock();
+            state.captured += 1;
+            state.index
+        };
+        let name = content::SCENES[index].0;
+        let (dir, results) = (self.dir.clone(), self.results.clone());
+        let proxy = main_ctx.event_loop_proxy.clone();
+        main_ctx.execute_blocking_task(move |_| {
+            let entry = gallery::record(&dir, name, &image)
+                .with_context(|| format!("unable to record gallery scene {name}"))
+                .log_error();
+            results.lock().entries.push((index, entry));
+            finish_if_done(&dir, &results, &proxy);
+        });
+        self.start(main_ctx, index + 1);
+    }
+}
+
+/// Writes the gallery page and exits once every captured screenshot is
+/// written.
+fn finish_if_done(
+    dir: &Path,
+    results: &Mutex<GalleryResults>,
+    proxy: &EventLoopProxy<GameUserEvent>,
+) {
+    let mut results = results.lock();
+    if results.expected != Some(results.entries.len()) {
+        return;
+    }
+    results.entries.sort_by_key(|(index, _)| *index);
+    let entries = results
+        .entries
+        .iter()
+        .filter_map(|(_, entry)| entry.clone())
+        .collect::<Vec<_>>();
+    let code = match gallery::write_html(dir, &entries)
+        .context("unable to write gallery page")
+        .log_error()
+    {
+        Some(path) => {
+            tracing::info!("gallery written to {}", path.display());
+            0
+        }
+        None => 1,
+    };
+    proxy.send_event(GameUserEvent::Exit(code)).log_warn();
+}
+
+impl Scene for Gallery {
+    fn handle_event<'a>(
+        self: Arc<Self>,
+        ctx: &mut MainContext,
+        root_scene: &RootScene,
+        event: GameEvent<'a>,
+    ) -> Option<GameEvent<'a>> {
+        let scene = self.state.lock().scene.clone();
+        match scene {
+            Some(scene) => scene.handle_event(ctx, root_scene, event),
+            None => Some(event),
+        }
+    }
+
+    fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
+        let (scene, frames) = {
+            let mut state = self.state.lock();
+            state.frames += 1;
+            (state.scene.clone(), state.frames)
+        };
+        let scene = match scene {
+            Some(scene) => scene,
+            None => return,
+        };
+        scene.draw(ctx);
+        if frames != self.frames {
+            return;
+        }
+
+        ctx.flush_shapes();
+        ctx.flush_sprites();
+        let image = screenshot::read_back_buffer(ctx)
+            .and_then(ImageData::into_rgba_image)
+            .context("unable to read back gallery capture")
+            .log_warn()
+            .unwrap_or_default();
+        ctx.base
+            .proxy
+            .send_event(GameUserEvent::Execute(Box::new(move |main_ctx, _| {
+                self.capture(main_ctx, image);
+                Ok(())
+            })))
+            .map_err(|e| anyhow::format_err!("{}", e))
+            .context("unable to send gallery capture to event loop")
+            .log_warn();
+    }
+}
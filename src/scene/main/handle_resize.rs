@@ -39,6 +39,7 @@ impl Scene for HandleResize {
                 window_id,
                 event: WindowEvent::Resized(size),
             } if main_ctx.display.get_window_id() == window_id => {
+                main_ctx.display.handle_resize(size);
                 let width = NonZeroU32::new(size.width);
                 let height = NonZeroU32::new(size.height);
                 let ui_size = size.to_logical(main_ctx.display.get_scale_factor()).into();
@@ -100,7 +101,7 @@ impl HandleResize {
                     context.resize(display_size, ui_size);
                     Ok(())
                 })
-                .and_then(std::convert::identity)
+                .and_then(|result| result.unwrap_or(Ok(())))
         } else {
             main_ctx.channels.draw.execute(move |context, _| {
                 context.resize(display_size, ui_size);
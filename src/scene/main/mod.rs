@@ -6,15 +6,16 @@ use crate::{
     events::GameEvent,
     exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
     graphics::context::DrawContext,
-    utils::args::args,
+    utils::{args::args, startup},
 };
 
-use self::handle_resize::HandleResize;
+use self::{gallery::Gallery, handle_resize::HandleResize};
 
 use super::{Scene, SceneContainer};
 
 pub mod content;
 pub mod core;
+pub mod gallery;
 pub mod handle_resize;
 pub mod test;
 pub mod utility;
@@ -30,7 +31,14 @@ impl RootScene {
         container.push(HandleResize::new());
         container.push_all(core::new(main_ctx).context("unable to initialize handle core scene")?);
         if args().test {
-            container.push_all(test::new(main_ctx).context("unable to initialize test scene")?);
+            container.push_all(
+                startup::phase("test tree", || test::new(main_ctx))
+                    .context("unable to initialize test scene")?,
+            );
+        } else if let Some(dir) = args().gallery.clone() {
+            container.push_arc(
+                Gallery::new(main_ctx, dir).context("unable to initialize gallery scene")?,
+            );
         } else {
             container
                 .push_all(content::new(main_ctx).context("unable to initialize content scene")?);
@@ -59,11 +67,8 @@ impl RootScene {
     pub fn draw(&self, draw_ctx: &mut DrawContext) {
         self.container.clone().draw(draw_ctx);
     }
-}
-
-#[test]
-fn test_sync() {
-    use crate::assert_sync;
 
-    assert_sync!(RootScene);
+    pub fn draw_pick(&self, draw_ctx: &mut DrawContext) {
+        self.container.clone().draw_pick(draw_ctx);
+    }
 }
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::{
+    events::GameUserEvent,
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    scene::{main::content::lights::Lights, SceneContainer},
+    test::{
+        assert::assert_equals,
+        tree::{LeafTestNode, ParentTestNode},
+    },
+    utils::alloc::{self, AllocTag},
+};
+
+// after the warm-up cycle
+const CYCLES: usize = 5;
+const DISPATCHES: usize = 64;
+const TAGS: [AllocTag; 3] = [AllocTag::Scene, AllocTag::Dispatch, AllocTag::Handles];
+
+type Snapshot = [(usize, usize); 3];
+type Leaves = [Arc<LeafTestNode>; 3];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    if !alloc::enabled() {
+        return Ok(());
+    }
+
+    let node = node.new_child_parent("alloc");
+    let leaves = TAGS.map(|tag| node.new_child_leaf(format!("{tag:?}").to_lowercase()));
+    step(main_ctx, 0, None, leaves)
+}
+
+fn snapshot() -> Snapshot {
+    TAGS.map(|tag| {
+        let stats = alloc::stats(tag);
+        (stats.allocations, stats.bytes)
+    })
+}
+
+/// Creates and destroys a scene and some dispatches, then waits for the draw
+/// server to process the resulting handle creations and deletions.
+fn step(
+    main_ctx: &mut MainContext,
+    cycle: usize,
+    mut baseline: Option<Snapshot>,
+    leaves: Leaves,
+) -> anyhow::Result<()> {
+    if cycle == 1 {
+        // containers have grown to their working size during the warm-up
+        baseline = Some(snapshot());
+    }
+    if cycle > CYCLES {
+        let current = snapshot();
+        let baseline = baseline.expect("baseline is taken after the warm-up");
+        for (i, leaf) in leaves.iter().enumerate() {
+            leaf.update(assert_equals(
+                &current[i],
+                &baseline[i],
+                "live allocations and bytes must return to the baseline",
+            ));
+        }
+        return Ok(());
+    }
+
+    {
+        let _scope = alloc::scope(AllocTag::Scene);
+        let mut container = SceneContainer::new();
+        container.push(Lights::new(main_ctx).context("unable to create lights scene")?);
+        drop(container);
+    }
+    let ids = (0..DISPATCHES)
+        .map(|_| main_ctx.dispatch_list.push(|_, _| Ok(())))
+        .collect::<Vec<_>>();
+    for id in ids {
+        drop(main_ctx.dispatch_list.pop(id));
+    }
+
+    main_ctx
+        .channels
+        .draw
+        .execute_draw_event(move |_, _| {
+            Some(GameUserEvent::Execute(Box::new(move |main_ctx, _| {
+                step(main_ctx, cycle + 1, baseline, leaves)
+            })))
+        })
+        .context("unable to send draw server round trip")
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use glam::Vec2;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{
+        atlas::{Atlas, AtlasRegion},
+        context::DrawContext,
+        sprite_renderer::Sprite,
+        wrappers::texture::ImageData,
+    },
+    test::{
+        assert::{assert_equals, assert_true},
+        tree::ParentTestNode,
+    },
+};
+
+use super::texture::read_pixels;
+
+const PAGE_SIZE: u32 = 16;
+// 4 padded regions per page
+const REGION_SIZE: u32 = 6;
+const COLORS: [[u8; 4]; 6] = [
+    [255, 0, 0, 255],
+    [0, 255, 0, 255],
+    [0, 0, 255, 255],
+    [255, 255, 255, 255],
+    [255, 0, 255, 255],
+    [255, 255, 0, 255],
+];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("atlas");
+    let draw = &mut main_ctx.channels.draw;
+    let mut atlas = Atlas::new("atlas test", PhysicalSize::new(PAGE_SIZE, PAGE_SIZE));
+    let mut regions = Vec::new();
+    let mut page_counts = Vec::new();
+    for (i, color) in COLORS.into_iter().enumerate() {
+        let image = ImageData::new(
+            PhysicalSize::new(REGION_SIZE, REGION_SIZE),
+            color.repeat((REGION_SIZE * REGION_SIZE) as usize),
+        )?;
+        // the space of the first region is reclaimed by a repack
+        if i == 4 {
+            regions.remove(0);
+        }
+        let region = atlas
+            .add(draw, image)
+            .context("unable to add atlas test region")?;
+        regions.push((color, region));
+        page_counts.push(atlas.page_count());
+    }
+    node.new_child_leaf("pack").update(assert_equals(
+        &page_counts,
+        &vec![1, 1, 1, 1, 1, 2],
+        "page count after each region",
+    ));
+    node.new_child_leaf("oversized").update(assert_true(
+        atlas
+            .add(
+                draw,
+                ImageData::new(
+                    PhysicalSize::new(PAGE_SIZE, 1),
+                    vec![0; PAGE_SIZE as usize * 4],
+                )?,
+            )
+            .is_err(),
+        "images larger than a page are refused",
+    ));
+
+    let upload_node = node.new_child_leaf("upload");
+    let draw_node = node.new_child_leaf("draw");
+    let query = draw
+        .query(move |ctx, _| {
+            let colors = regions
+                .iter()
+                .map(|(color, region)| (*color, sample_center(ctx, region)))
+                .collect::<Vec<_>>();
+            let (_, region) = &regions[0];
+            let sprite = Sprite::new(Vec2::ZERO, Vec2::ONE).uv(Vec2::ZERO, Vec2::splat(0.5));
+            ctx.draw_region(region, &sprite);
+            let queued = ctx.sprites.len();
+            ctx.flush_sprites();
+            (colors, queued)
+        })
+        .context("unable to query atlas test")?;
+    main_ctx.spawn_local(async move {
+        let (colors, queued) = query.await?;
+        upload_node.update(assert_true(
+            colors
+                .iter()
+                .all(|(color, sampled)| Some(*color) == *sampled),
+            format!("sampled region colors {colors:?}"),
+        ));
+        draw_node.update(assert_equals(&queued, &1, "sprites queued"));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+/// Color at the center of `region` in its page, `None` if it wasn't uploaded.
+fn sample_center(ctx: &mut DrawContext, region: &AtlasRegion) -> Option<[u8; 4]> {
+    let (texture, [uv0, uv1]) = region.placement()?;
+    let size = region.size();
+    let uv_size = (uv1 - uv0) * PAGE_SIZE as f32;
+    if uv_size != Vec2::new(size.width as f32, size.height as f32) {
+        return None;
+    }
+    let center = ((uv0 + uv1) * 0.5 * PAGE_SIZE as f32).as_uvec2();
+    let pixels = read_pixels(
+        &texture.try_get(ctx)?,
+        PhysicalSize::new(PAGE_SIZE, PAGE_SIZE),
+    );
+    let index = (center.y * PAGE_SIZE + center.x) as usize * 4;
+    pixels[index..index + 4].try_into().ok()
+}
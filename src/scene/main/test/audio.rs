@@ -0,0 +1,82 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    audio::{SoundData, SoundHandle},
+    exec::main_ctx::MainContext,
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+};
+
+const WAIT: Duration = Duration::from_millis(500);
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("audio");
+    let stop_node = node.new_child_leaf("stop");
+    let finish_node = node.new_child_leaf("finish");
+    let music_node = node.new_child_leaf("missing_music");
+
+    // quiet and short, in case the tests run with an actual output device
+    main_ctx
+        .channels
+        .audio
+        .set_channel_gain(0, 0.01)
+        .context("unable to set channel gain")?;
+    let long = SoundHandle::new(SoundData::sine(440.0, Duration::from_secs(10), 48000));
+    let short = SoundHandle::new(SoundData::sine(440.0, Duration::from_millis(20), 48000));
+
+    let audio = &main_ctx.channels.audio;
+    let long_id = audio.play(long).context("unable to play sound")?;
+    let short_id = audio.play(short).context("unable to play sound")?;
+    // decoding errors must end the stream instead of stalling the server
+    let music_id = audio
+        .play_music("nonexistent.ogg", Duration::from_millis(100))
+        .context("unable to play music")?;
+    let playing = audio
+        .is_playing(long_id)
+        .context("unable to query audio server")?;
+    audio.stop(long_id).context("unable to stop sound")?;
+    let stopped = audio
+        .is_playing(long_id)
+        .context("unable to query audio server")?;
+    main_ctx.spawn_local(async move {
+        let result: TestResult = async {
+            assert_equals(&playing.await?, &true, "playing after play")?;
+            assert_equals(&stopped.await?, &false, "not playing after stop")
+        }
+        .await;
+        stop_node.update(result);
+        Ok(())
+    });
+
+    main_ctx
+        .set_timeout(WAIT, move |main_ctx, _| {
+            let finished = main_ctx
+                .channels
+                .audio
+                .is_playing(short_id)
+                .context("unable to query audio server")?;
+            let music_finished = main_ctx
+                .channels
+                .audio
+                .is_playing(music_id)
+                .context("unable to query audio server")?;
+            main_ctx.spawn_local(async move {
+                let result: TestResult =
+                    async { assert_equals(&finished.await?, &false, "short sound finished") }.await;
+                finish_node.update(result);
+                let result: TestResult =
+                    async { assert_equals(&music_finished.await?, &false, "missing music ended") }
+                        .await;
+                music_node.update(result);
+                Ok(())
+            });
+            main_ctx
+                .channels
+                .audio
+                .set_channel_gain(0, 1.0)
+                .context("unable to reset channel gain")
+        })
+        .context("unable to set timeout")?;
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    audio::{
+        clock::{ScheduleAt, ScheduledAction, DEFAULT_BPM},
+        mixer::ChannelParams,
+        ChannelId, PlayOptions, SoundData, SoundHandle,
+    },
+    exec::main_ctx::MainContext,
+    test::{
+        assert::{assert_equals, assert_greater_equals, assert_greater_than},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+};
+
+/// 10 beats per second
+const BPM: f64 = 600.0;
+const START_BEAT: f64 = 2.0;
+const WAIT: Duration = Duration::from_millis(500);
+/// unnamed channel, only used by this test
+const CHANNEL: ChannelId = 100;
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("beat_clock");
+    let pending_node = node.new_child_leaf("pending");
+    let started_node = node.new_child_leaf("started");
+    let params_node = node.new_child_leaf("params");
+    let beat_node = node.new_child_leaf("beat");
+
+    let audio = &main_ctx.channels.audio;
+    audio.set_tempo(BPM).context("unable to set tempo")?;
+    audio.reset_beat().context("unable to reset beat")?;
+    // quiet, in case the tests run with an actual output device
+    audio
+        .set_channel_gain(CHANNEL, 0.01)
+        .context("unable to set channel gain")?;
+    let sound = SoundHandle::new(SoundData::sine(440.0, Duration::from_secs(1), 48000));
+    let options = PlayOptions {
+        channel: CHANNEL,
+        ..Default::default()
+    };
+    let id = audio
+        .schedule_play(ScheduleAt::Beat(START_BEAT), sound, options)
+        .context("unable to schedule sound")?;
+    for action in [
+        ScheduledAction::SetChannelPan(CHANNEL, -1.0),
+        ScheduledAction::SetChannelMuted(CHANNEL, true),
+    ] {
+        audio
+            .schedule(ScheduleAt::Beat(START_BEAT), action)
+            .context("unable to schedule channel change")?;
+    }
+    let pending = audio
+        .query(move |server| {
+            let mixer = server.mixer.lock();
+            (mixer.is_playing(id), mixer.scheduled_count())
+        })
+        .context("unable to query audio server")?;
+    main_ctx.spawn_local(async move {
+        let result: TestResult = async {
+            let (playing, scheduled) = pending.await?;
+            assert_equals(&playing, &false, "playing before the scheduled beat")?;
+            assert_greater_than(&scheduled, &0, "scheduled events")
+        }
+        .await;
+        pending_node.update(result);
+        Ok(())
+    });
+
+    main_ctx
+        .set_timeout(WAIT, move |main_ctx, _| {
+            let audio = &main_ctx.channels.audio;
+            let started = audio
+                .query(move |server| {
+                    let mixer = server.mixer.lock();
+                    (mixer.is_playing(id), mixer.channel(CHANNEL))
+                })
+                .context("unable to query audio server")?;
+            audio
+                .schedule(ScheduleAt::NextMultiple(1.0), ScheduledAction::Stop(id))
+                .context("unable to schedule stop")?;
+            audio
+                .set_tempo(DEFAULT_BPM)
+                .context("unable to reset tempo")?;
+            let beat = main_ctx
+                .channels
+                .update
+                .current_beat()
+                .context("unable to query update server")?;
+            main_ctx.spawn_local(async move {
+                let (playing, params) = started.await?;
+                started_node.update(assert_equals(
+                    &playing,
+                    &true,
+                    "playing after the scheduled beat",
+                ));
+                params_node.update(assert_equals(
+                    &params,
+                    &ChannelParams {
+                        gain: 0.01,
+                        pan: -1.0,
+                        muted: true,
+                    },
+                    "channel after the scheduled changes",
+                ));
+                let result: TestResult = async {
+                    let beat = beat.await?;
+                    assert_greater_equals(&beat, &START_BEAT, "beat seen by the update server")
+                }
+                .await;
+                beat_node.update(result);
+                Ok(())
+            });
+            Ok(())
+        })
+        .context("unable to set timeout")?;
+    Ok(())
+}
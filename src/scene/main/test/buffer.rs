@@ -0,0 +1,106 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use gl::types::{GLenum, GLint};
+
+use crate::{
+    enclose,
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::wrappers::{
+        buffer::{Buffer, BufferHandle, BufferTarget},
+        fence::Fence,
+    },
+    test::{
+        assert::{assert_equals, assert_true},
+        tree::ParentTestNode,
+    },
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("buffer");
+    let draw = &mut main_ctx.channels.draw;
+
+    let uploaded = BufferHandle::with_data(
+        draw,
+        "upload test buffer",
+        BufferTarget::ArrayBuffer,
+        vec![1u32, 2, 3, 4],
+    )
+    .context("unable to create upload test buffer")?;
+
+    let streamed = BufferHandle::new(draw, "stream test buffer", BufferTarget::ArrayBuffer)
+        .context("unable to create stream test buffer")?;
+    streamed
+        .stream(draw, vec![9u32; 16])
+        .context("unable to stream test buffer data")?;
+    // smaller than the previous data, the orphaned storage must not be reused
+    streamed
+        .stream(draw, vec![5u32, 6])
+        .context("unable to stream test buffer data")?;
+
+    let range_query = draw
+        .query(enclose!((uploaded) move |context, _| {
+            let buffer = uploaded.try_get(context).context("buffer was not created")?;
+            let signaled = Fence::insert().wait(Duration::from_secs(1));
+            let range = buffer.read_range(4, 8)?;
+            Ok::<_, anyhow::Error>((range, signaled))
+        }))
+        .context("unable to query test buffer range")?;
+    let query = draw
+        .query(move |context, _| {
+            [uploaded, streamed].map(|buffer| buffer.try_get(context).map(|b| read_buffer(&b)))
+        })
+        .context("unable to query test buffers")?;
+
+    let range_node = node.new_child_leaf("read_range");
+    let fence_node = node.new_child_leaf("fence");
+    main_ctx.spawn_local(async move {
+        let (range, signaled) = range_query.await??;
+        range_node.update(assert_equals(
+            &range,
+            &bytemuck::cast_slice::<u32, u8>(&[2, 3]).to_vec(),
+            "buffer range contents",
+        ));
+        fence_node.update(assert_true(signaled, "fence must be signaled"));
+        Ok(())
+    });
+
+    let upload_node = node.new_child_leaf("upload");
+    let stream_node = node.new_child_leaf("stream");
+    main_ctx.spawn_local(async move {
+        let [uploaded, streamed] = query.await?;
+        upload_node.update(assert_equals(
+            &uploaded,
+            &Some(vec![1, 2, 3, 4]),
+            "uploaded buffer contents",
+        ));
+        stream_node.update(assert_equals(
+            &streamed,
+            &Some(vec![5, 6]),
+            "streamed buffer contents",
+        ));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+pub fn read_buffer(buffer: &Buffer) -> Vec<u32> {
+    let target = buffer.target() as GLenum;
+    let mut size: GLint = 0;
+    let mut bytes = Vec::new();
+    buffer.bind();
+    unsafe {
+        gl::GetBufferParameteriv(target, gl::BUFFER_SIZE, &mut size);
+        let data = gl::MapBufferRange(target, 0, size as _, gl::MAP_READ_BIT);
+        if !data.is_null() {
+            bytes.extend_from_slice(std::slice::from_raw_parts(data as *const u8, size as usize));
+            gl::UnmapBuffer(target);
+        }
+    }
+    buffer.unbind();
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| u32::from_ne_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
@@ -0,0 +1,146 @@
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_4},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use glam::{Vec2, Vec4};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{
+        camera::Camera2D,
+        wrappers::texture::{ImageData, TextureHandle},
+    },
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    ui::utils::geom::{UIPos, UIRect, UISize},
+};
+
+use super::{
+    shape::{bind_target, unbind_target},
+    texture::read_pixels,
+};
+
+const SIZE: u32 = 16;
+const CLEAR: [u8; 4] = [0, 0, 0, 0];
+const RED: [u8; 4] = [255, 0, 0, 255];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("camera");
+    let draw = &mut main_ctx.channels.draw;
+    let target = ImageData::new(
+        PhysicalSize::new(SIZE, SIZE),
+        vec![0; (SIZE * SIZE * 4) as usize],
+    )?;
+    let target = TextureHandle::new(draw, "camera test target", target)
+        .context("unable to create camera test target")?;
+
+    // the left half of the target, 4 pixels per world unit, centered on
+    // (1, 1) and turned right
+    let mut camera = Camera2D::new(UIRect::new(
+        UIPos::ZERO,
+        UISize::new(SIZE as f32 / 2.0, SIZE as f32),
+    ));
+    camera.position = Vec2::ONE;
+    camera.zoom = 4.0;
+    camera.rotation = FRAC_PI_2;
+
+    let query = draw
+        .query(move |ctx, _| {
+            let previous_size = ctx.ui_size;
+            ctx.ui_size = UISize::new(SIZE as f32, SIZE as f32);
+            let fbo = bind_target(**target.get(ctx));
+
+            // something drawn before must not affect the camera
+            ctx.transform_stack.push();
+            ctx.transform_stack.translate(UIPos::new(3.0, 3.0));
+            ctx.push_camera(&camera);
+            let world = Vec2::new(2.0, 1.0);
+            let conversions = (
+                ctx.transform_stack.to_screen(world.into()),
+                ctx.transform_stack.to_local(camera.world_to_screen(world)),
+            );
+            // wider than the viewport once turned
+            ctx.draw_polygon(
+                &[
+                    Vec2::new(0.0, -2.0),
+                    Vec2::new(2.0, -2.0),
+                    Vec2::new(2.0, 4.0),
+                    Vec2::new(0.0, 4.0),
+                ],
+                Vec4::new(1.0, 0.0, 0.0, 1.0),
+            );
+            ctx.pop_camera();
+            ctx.flush_shapes();
+
+            ctx.transform_stack.reset_current_transform();
+            ctx.transform_stack.rotate(FRAC_PI_2);
+            ctx.transform_stack.scale(Vec2::new(2.0, 1.0));
+            ctx.transform_stack.skew(Vec2::new(FRAC_PI_4, 0.0));
+            let affine = ctx.transform_stack.to_screen(UIPos::new(1.0, 1.0));
+            ctx.transform_stack.pop();
+
+            let pixels = read_pixels(&target.get(ctx), PhysicalSize::new(SIZE, SIZE));
+            unbind_target(ctx, fbo);
+            ctx.ui_size = previous_size;
+            (conversions, affine, pixels)
+        })
+        .context("unable to query camera test")?;
+
+    let conversion_node = node.new_child_leaf("conversion");
+    let affine_node = node.new_child_leaf("affine");
+    let render_node = node.new_child_leaf("render");
+    main_ctx.spawn_local(async move {
+        let ((screen, local), affine, pixels) = query.await?;
+        conversion_node.update(check_conversion(&camera, screen, local));
+        // skewed to (2, 1), stretched to (4, 1), then turned
+        affine_node.update(assert_equals(
+            &affine,
+            &UIPos::new(-1.0, 4.0),
+            "affine transform",
+        ));
+        render_node.update(check_render(&pixels));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn check_conversion(camera: &Camera2D, screen: UIPos, local: UIPos) -> TestResult {
+    let world = Vec2::new(2.0, 1.0);
+    assert_equals(
+        &screen,
+        &camera.world_to_screen(world),
+        "world to screen with the camera pushed",
+    )?;
+    assert_equals(&local, &UIPos::from(world), "screen to world")?;
+    assert_equals(&screen, &UIPos::new(4.0, 4.0), "screen position")?;
+    assert_equals(
+        &UIPos::from(camera.screen_to_world(screen)),
+        &UIPos::from(world),
+        "screen to world with the camera",
+    )?;
+    assert_equals(&camera.contains(screen), &true, "inside the viewport")
+}
+
+fn check_render(pixels: &[u8]) -> TestResult {
+    // UI coordinates of the pixels, rows are read bottom to top
+    let pixel = |x: u32, y: u32| {
+        let start = (((SIZE - 1 - y) * SIZE + x) * 4) as usize;
+        &pixels[start..start + 4]
+    };
+    // turned, the world x axis points up: the rectangle covers rows 4 to 12
+    // across the whole viewport width
+    let expected = [
+        ((4, 5), RED, "world rectangle"),
+        ((1, 10), RED, "world rectangle, left edge of the viewport"),
+        ((4, 2), CLEAR, "above the world rectangle"),
+        ((4, 13), CLEAR, "below the world rectangle"),
+        ((12, 5), CLEAR, "outside the viewport"),
+    ];
+    for ((x, y), color, msg) in expected {
+        assert_equals(&pixel(x, y), &&color[..], msg)?;
+    }
+    Ok(())
+}
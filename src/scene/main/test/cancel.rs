@@ -0,0 +1,79 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use glam::Vec2;
+
+use crate::{
+    exec::{main_ctx::MainContext, task::Cancellable},
+    nav::{NavGrid, PathQuery},
+    scene::lifetime::SceneLifetime,
+    test::{assert::assert_equals, tree::ParentTestNode},
+    utils::mutex::Mutex,
+};
+
+const CHECK_DELAY: Duration = Duration::from_millis(200);
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("cancel");
+
+    // the task spins until its scene is removed
+    let test_node = node.new_child_leaf("blocking_task");
+    let observed = Arc::new(Mutex::new(false));
+    let scene = SceneLifetime::new();
+    let parent_scene = main_ctx.current_scene.replace(scene.clone());
+    main_ctx.execute_blocking_task(enclose!((observed) move |cancel| {
+        let start = Instant::now();
+        while !cancel.is_cancelled() && start.elapsed() < CHECK_DELAY * 5 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        *observed.lock() = cancel.is_cancelled();
+    }));
+    main_ctx.current_scene = parent_scene;
+    scene.invalidate();
+    main_ctx
+        .set_timeout(CHECK_DELAY, move |_, _| {
+            test_node.update(assert_equals(
+                &*observed.lock(),
+                &true,
+                "task observed the scene removal",
+            ));
+            Ok(())
+        })
+        .context("unable to set blocking task check timeout")?;
+
+    // the path is found, but the scene asking for it is gone
+    let test_node = node.new_child_leaf("find_path");
+    let executed = Arc::new(Mutex::new(false));
+    let scene = SceneLifetime::new();
+    let parent_scene = main_ctx.current_scene.replace(scene.clone());
+    let result = main_ctx.find_path(
+        Arc::new(NavGrid::from_rows(&[".."], 1.0)),
+        PathQuery {
+            start: Vec2::new(0.5, 0.5),
+            goal: Vec2::new(1.5, 0.5),
+            agent_radius: 0.0,
+        },
+        enclose!((executed) move |_, _, _| {
+            *executed.lock() = true;
+            Ok(())
+        }),
+    );
+    main_ctx.current_scene = parent_scene;
+    result.context("unable to queue cancelled path query")?;
+    scene.invalidate();
+    main_ctx
+        .set_timeout(CHECK_DELAY, move |_, _| {
+            test_node.update(assert_equals(
+                &*executed.lock(),
+                &false,
+                "path callback of removed scene executed",
+            ));
+            Ok(())
+        })
+        .context("unable to set path query check timeout")?;
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    test::{
+        assert::assert_true,
+        capture::{LogEntry, MergedLog},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+};
+
+const LOG: &str = "capture";
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_leaf("capture");
+
+    main_ctx.test_capture.record(LOG, "main 0");
+    main_ctx
+        .channels
+        .draw
+        .execute(|context, _| {
+            context.test_capture.record(LOG, "draw 0");
+        })
+        .context("unable to record draw log entry")?;
+
+    main_ctx
+        .set_timeout(Duration::from_millis(100), move |main_ctx, _| {
+            main_ctx.test_capture.record(LOG, "main 1");
+            let main_entries = main_ctx.test_capture.take(LOG);
+            let draw_entries = main_ctx
+                .channels
+                .draw
+                .query(|context, _| context.test_capture.take(LOG))
+                .context("unable to take draw log entries")?;
+            main_ctx.spawn_local(async move {
+                let draw_entries = draw_entries.await?;
+                node.update(check_merged(main_entries, draw_entries));
+                Ok(())
+            });
+            Ok(())
+        })
+        .context("unable to set capture test timeout")?;
+
+    Ok(())
+}
+
+fn check_merged(main_entries: Vec<LogEntry>, draw_entries: Vec<LogEntry>) -> TestResult {
+    let merged = MergedLog::merge([main_entries, draw_entries]);
+    merged.assert_order(&["main 0", "main 1"])?;
+    merged.assert_order(&["draw 0", "main 1"])?;
+    assert_true(
+        merged.assert_order(&["main 1", "main 0"]).is_err(),
+        "entries of the same source must keep their order",
+    )
+}
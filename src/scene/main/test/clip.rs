@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use glam::{Vec2, Vec4};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{
+        draw_queue::SortKey,
+        wrappers::texture::{ImageData, TextureHandle},
+    },
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    ui::utils::geom::{UIPos, UIRect, UISize},
+    utils::mutex::Mutex,
+};
+
+use super::{
+    shape::{bind_target, unbind_target},
+    texture::read_pixels,
+};
+
+const SIZE: u32 = 16;
+const CLEAR: [u8; 4] = [0, 0, 0, 0];
+const RED: [u8; 4] = [255, 0, 0, 255];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("clip");
+    let draw = &mut main_ctx.channels.draw;
+    let target = ImageData::new(
+        PhysicalSize::new(SIZE, SIZE),
+        vec![0; (SIZE * SIZE * 4) as usize],
+    )?;
+    let target = TextureHandle::new(draw, "clip test target", target)
+        .context("unable to create clip test target")?;
+
+    let query = draw
+        .query(move |ctx, _| {
+            let previous_size = ctx.ui_size;
+            ctx.ui_size = UISize::new(SIZE as f32, SIZE as f32);
+            let fbo = bind_target(**target.get(ctx));
+
+            // the top half, then the middle of it
+            let half = UISize::new(SIZE as f32, SIZE as f32 / 2.0);
+            ctx.push_clip(UIRect::new(UIPos::ZERO, half));
+            ctx.transform_stack.push();
+            ctx.transform_stack.translate(UIPos::new(4.0, 0.0));
+            ctx.push_clip(UIRect::new(UIPos::ZERO, UISize::new(8.0, SIZE as f32)));
+            let nested = ctx.clip_stack.current().map(|clip| clip.scissor);
+
+            let queued = Arc::new(Mutex::new(None));
+            let queued_clip = queued.clone();
+            ctx.queue_draw(SortKey::default(), move |ctx, _| {
+                *queued_clip.lock() = ctx.applied_clip.as_ref().map(|clip| clip.scissor);
+            });
+            ctx.draw_polygon(
+                &[
+                    Vec2::new(-4.0, 0.0),
+                    Vec2::new(12.0, 0.0),
+                    Vec2::new(12.0, 16.0),
+                    Vec2::new(-4.0, 16.0),
+                ],
+                Vec4::new(1.0, 0.0, 0.0, 1.0),
+            );
+            ctx.pop_clip();
+            ctx.transform_stack.pop();
+            ctx.pop_clip();
+            ctx.flush_draw_queue();
+            let scissor_test = unsafe { gl::IsEnabled(gl::SCISSOR_TEST) } != gl::FALSE;
+            let pixels = read_pixels(&target.get(ctx), PhysicalSize::new(SIZE, SIZE));
+
+            unbind_target(ctx, fbo);
+            ctx.ui_size = previous_size;
+            let queued = *queued.lock();
+            ((nested, queued, scissor_test), pixels)
+        })
+        .context("unable to query clip test")?;
+
+    let stack_node = node.new_child_leaf("stack");
+    let render_node = node.new_child_leaf("render");
+    main_ctx.spawn_local(async move {
+        let ((nested, queued, scissor_test), pixels) = query.await?;
+        stack_node.update(check_stack(nested, queued, scissor_test));
+        render_node.update(check_render(&pixels));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn check_stack(nested: Option<UIRect>, queued: Option<UIRect>, scissor_test: bool) -> TestResult {
+    let expected = UIRect::new(UIPos::new(4.0, 0.0), UISize::new(8.0, 8.0));
+    assert_equals(&nested, &Some(expected), "nested clip")?;
+    assert_equals(&queued, &Some(expected), "clip of the queued draw")?;
+    assert_equals(&scissor_test, &false, "scissor test after popping")
+}
+
+fn check_render(pixels: &[u8]) -> TestResult {
+    // UI coordinates of the pixels, rows are read bottom to top
+    let pixel = |x: u32, y: u32| {
+        let start = (((SIZE - 1 - y) * SIZE + x) * 4) as usize;
+        &pixels[start..start + 4]
+    };
+    let expected = [
+        ((8, 4), RED, "inside the clips"),
+        ((8, 12), CLEAR, "below the outer clip"),
+        ((1, 4), CLEAR, "left of the nested clip"),
+        ((14, 4), CLEAR, "right of the nested clip"),
+    ];
+    for ((x, y), color, msg) in expected {
+        assert_equals(&pixel(x, y), &&color[..], msg)?;
+    }
+    Ok(())
+}
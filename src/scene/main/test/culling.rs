@@ -0,0 +1,96 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use glam::{Vec2, Vec4};
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{camera::Camera2D, sprite_renderer::Sprite},
+    spatial::Aabb,
+    test::{assert::assert_equals, tree::ParentTestNode},
+    ui::utils::geom::{UIPos, UIRect, UISize},
+    utils::uid::Uid,
+};
+
+/// far from anything else in the spatial index
+const ORIGIN: Vec2 = Vec2::new(-10000.0, -10000.0);
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("culling");
+    let visible_node = node.new_child_leaf("visible");
+    let drawn_node = node.new_child_leaf("drawn");
+    let moved_node = node.new_child_leaf("moved");
+
+    let mut camera = Camera2D::new(UIRect::new(UIPos::ZERO, UISize::new(100.0, 100.0)));
+    camera.position = ORIGIN;
+    let inside = Uid::new();
+    let outside = Uid::new();
+    let edge = Uid::new();
+    let colliders = [
+        (inside, Aabb::from_center(ORIGIN, Vec2::splat(5.0))),
+        (
+            outside,
+            Aabb::from_center(ORIGIN + Vec2::new(200.0, 0.0), Vec2::splat(5.0)),
+        ),
+        (
+            edge,
+            Aabb::from_center(ORIGIN + Vec2::new(0.0, 52.0), Vec2::splat(5.0)),
+        ),
+    ];
+    for (id, aabb) in colliders {
+        main_ctx
+            .channels
+            .update
+            .set_collider(id, aabb)
+            .context("unable to set collider")?;
+    }
+    let view = main_ctx
+        .channels
+        .update
+        .add_cull_view(camera.world_bounds())
+        .context("unable to add cull view")?;
+
+    main_ctx
+        .set_timeout(Duration::from_millis(200), move |main_ctx, _| {
+            visible_node.update(assert_equals(
+                &*view.visible.get(),
+                &vec![inside, edge],
+                "visible colliders",
+            ));
+
+            // transparent, only counted
+            let sprites = [inside, outside, edge]
+                .map(|id| (id, Sprite::new(ORIGIN, Vec2::ONE).color(Vec4::ZERO)));
+            let visible = view.visible.clone();
+            let drawn = main_ctx.channels.draw.query(move |ctx, _| {
+                let queued = ctx.sprites.len();
+                ctx.draw_visible_sprites(0, &sprites, &visible);
+                ctx.sprites.len() - queued
+            })?;
+            main_ctx.spawn_local(async move {
+                drawn_node.update(assert_equals(&drawn.await?, &2, "sprites drawn"));
+                Ok(())
+            });
+
+            camera.position = ORIGIN + Vec2::new(200.0, 0.0);
+            view.set_bounds(camera.world_bounds())?;
+            main_ctx.set_timeout(Duration::from_millis(200), move |main_ctx, _| {
+                moved_node.update(assert_equals(
+                    &*view.visible.get(),
+                    &vec![outside],
+                    "visible colliders after moving the camera",
+                ));
+                drop(view);
+                for (id, _) in colliders {
+                    main_ctx
+                        .channels
+                        .update
+                        .remove_collider(id)
+                        .context("unable to remove collider")?;
+                }
+                Ok(())
+            })
+        })
+        .context("unable to set culling check timeout")?;
+    Ok(())
+}
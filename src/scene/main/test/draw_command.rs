@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use glam::Vec4;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{
+        main_ctx::MainContext,
+        server::{
+            draw::{command::DrawCommand, ServerSendChannelExt},
+            GameServerSendChannel,
+        },
+    },
+    graphics::wrappers::texture::TextureHandle,
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+};
+
+/// commands sent by this test, other scenes may send more meanwhile
+const SENT: [&str; 3] = ["set_clear_color", "set_viewport", "create_texture"];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("draw_command");
+    let log_node = node.new_child_leaf("log");
+    let replay_node = node.new_child_leaf("replay");
+
+    let draw = &mut main_ctx.channels.draw;
+    let counts = draw
+        .query(|context, _| {
+            context.command_log.start_recording();
+            SENT.map(|name| context.command_log.count(name))
+        })
+        .context("unable to start recording draw commands")?;
+
+    let size = main_ctx.display.get_size();
+    let draw = &mut main_ctx.channels.draw;
+    draw.set_clear_color(Vec4::new(0.0, 0.0, 0.0, 1.0))
+        .context("unable to set clear color")?;
+    draw.command(DrawCommand::SetViewport {
+        x: 0,
+        y: 0,
+        width: size.width.try_into()?,
+        height: size.height.try_into()?,
+    })
+    .context("unable to set viewport")?;
+    let texture = TextureHandle::new_2d(draw, "draw command test texture", PhysicalSize::new(4, 4))
+        .context("unable to create test texture")?;
+
+    let recorded = draw
+        .query(enclose!((texture) move |context, _| {
+            let recorded = context.command_log.stop_recording();
+            let counts = SENT.map(|name| context.command_log.count(name));
+            (recorded, counts, texture.try_get(context).is_some())
+        }))
+        .context("unable to stop recording draw commands")?;
+
+    let draw = draw.clone_sender();
+    main_ctx.spawn_local(async move {
+        let counts = counts.await?;
+        let (recorded, recorded_counts, created) = recorded.await?;
+        let names = recorded
+            .iter()
+            .map(|command| command.name())
+            .filter(|name| SENT.contains(name))
+            .collect::<Vec<_>>();
+        log_node.update(check_log(&names, counts, recorded_counts, created));
+
+        let textures = draw
+            .query(|context, _| context.handles.textures.len())
+            .context("unable to count textures")?;
+        let replayed = draw
+            .query(move |context, _| {
+                context.replay(recorded);
+                let counts = SENT.map(|name| context.command_log.count(name));
+                (counts, context.handles.textures.len())
+            })
+            .context("unable to replay draw commands")?;
+        let textures = textures.await?;
+        let (replayed_counts, replayed_textures) = replayed.await?;
+        replay_node.update(check_replay(
+            recorded_counts,
+            replayed_counts,
+            textures,
+            replayed_textures,
+        ));
+        drop(texture);
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn check_log(names: &[&str], before: [u64; 3], after: [u64; 3], created: bool) -> TestResult {
+    assert_equals(&names, &&SENT[..], "recorded commands")?;
+    for (name, (before, after)) in SENT.iter().zip(before.into_iter().zip(after)) {
+        assert_true(after > before, format!("{name} counted"))?;
+    }
+    assert_true(created, "texture created by command")
+}
+
+fn check_replay(
+    before: [u64; 3],
+    after: [u64; 3],
+    textures: usize,
+    replayed_textures: usize,
+) -> TestResult {
+    for (name, (before, after)) in SENT.iter().zip(before.into_iter().zip(after)) {
+        assert_true(after > before, format!("replayed {name} counted"))?;
+    }
+    assert_equals(
+        &replayed_textures,
+        &textures,
+        "replayed texture creation reuses the handle",
+    )
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use crate::{
+    events::error::{ErrorReport, GameError, Severity, Subsystem},
+    exec::main_ctx::MainContext,
+    test::{assert::assert_equals, tree::ParentTestNode},
+    utils::mutex::Mutex,
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("error");
+
+    let test_node = node.new_child_leaf("routing");
+    let subscription = Arc::new(Mutex::new(None));
+    *subscription.lock() = Some(main_ctx.event_bus.subscribe({
+        let subscription = subscription.clone();
+        move |main_ctx, _, report: &ErrorReport| {
+            if report.code == "test.routing" && subscription.lock().take().is_some() {
+                test_node.update(
+                    assert_equals(
+                        &report.chain,
+                        &vec!["unable to route".to_owned(), "expected error".to_owned()],
+                        "published error chain",
+                    )
+                    .and_then(|_| {
+                        assert_equals(
+                            &main_ctx.error_sink.recent().last(),
+                            &Some(report),
+                            "error recorded by the sink",
+                        )
+                    }),
+                );
+            }
+            Ok(())
+        }
+    }));
+
+    // warnings never fail the test run, even with `--fail-on-error`
+    main_ctx.report_error(
+        GameError::new(
+            Subsystem::Test,
+            "test.routing",
+            anyhow::anyhow!("expected error").context("unable to route"),
+        )
+        .severity(Severity::Warning),
+    );
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    test::{
+        assert::{assert_equals, assert_false},
+        tree::ParentTestNode,
+    },
+    utils::mutex::Mutex,
+};
+
+struct Ping(u32);
+struct Pong;
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("event_bus");
+
+    let test_node = node.new_child_leaf("dispatch");
+    // the subscription is dropped by the handler itself after the first event
+    let subscription = Arc::new(Mutex::new(None));
+    *subscription.lock() = Some(main_ctx.event_bus.subscribe({
+        let subscription = subscription.clone();
+        move |_, _, ping: &Ping| {
+            if subscription.lock().take().is_some() {
+                test_node.update(assert_equals(&ping.0, &1, "published value"));
+            }
+            Ok(())
+        }
+    }));
+
+    let test_node = node.new_child_leaf("channel");
+    let (channel_subscription, receiver) = main_ctx.event_bus.subscribe_channel::<Ping>();
+    main_ctx
+        .event_bus
+        .publish(Ping(1))
+        .context("unable to publish Ping")?;
+    // channel subscribers receive events on the publishing thread
+    test_node.update(
+        receiver
+            .try_recv()
+            .map_err(Into::into)
+            .and_then(|ping| assert_equals(&ping.map(|ping| ping.0), &Some(1), "received value")),
+    );
+    drop(channel_subscription);
+
+    let test_node = node.new_child_leaf("unsubscribe");
+    let called = Arc::new(AtomicBool::new(false));
+    drop(main_ctx.event_bus.subscribe({
+        let called = called.clone();
+        move |_, _, _: &Pong| {
+            called.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+    }));
+    main_ctx
+        .event_bus
+        .publish(Pong)
+        .context("unable to publish Pong")?;
+    main_ctx
+        .set_timeout(Duration::from_millis(100), move |_, _| {
+            test_node.update(assert_false(
+                called.load(Ordering::Relaxed),
+                "dropped subscription must not be called",
+            ));
+            Ok(())
+        })
+        .context("unable to set timeout")?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+
+use crate::{
+    exec::{main_ctx::MainContext, task::Cancellable},
+    scene::lifetime::SceneLifetime,
+    test::{
+        assert::{assert_equals, assert_true},
+        tree::ParentTestNode,
+    },
+    utils::mutex::Mutex,
+};
+
+const CHECK_DELAY: Duration = Duration::from_millis(200);
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("frame_callback");
+
+    // every frame runs the callback, the deltas add up to the elapsed time;
+    // the first one started on the frame before the callback was registered,
+    // possibly before `start`, so it's left out of the total
+    let test_node = node.new_child_leaf("delta");
+    let start = Instant::now();
+    let frames = Arc::new(Mutex::new((0usize, Duration::ZERO)));
+    let cancel = main_ctx.on_frame(enclose!((frames) move |_, _, delta| {
+        let mut frames = frames.lock();
+        if frames.0 > 0 {
+            frames.1 += delta;
+        }
+        frames.0 += 1;
+        Ok(())
+    }));
+    main_ctx
+        .set_timeout(CHECK_DELAY, move |_, _| {
+            cancel.cancel();
+            let (count, total) = *frames.lock();
+            test_node.update(assert_true(
+                count > 0 && total <= start.elapsed(),
+                format!("{count} frames took {total:?} out of {:?}", start.elapsed()),
+            ));
+            Ok(())
+        })
+        .context("unable to set frame callback check timeout")?;
+
+    // cancelled and ownerless callbacks never run
+    for (name, cancelled) in [("cancel", true), ("owner", false)] {
+        let test_node = node.new_child_leaf(name);
+        let count = Arc::new(Mutex::new(0usize));
+        let scene = SceneLifetime::new();
+        let parent_scene = main_ctx.current_scene.replace(scene.clone());
+        let cancel = main_ctx.on_frame(enclose!((count) move |_, _, _| {
+            *count.lock() += 1;
+            Ok(())
+        }));
+        main_ctx.current_scene = parent_scene;
+        if cancelled {
+            cancel.cancel();
+        } else {
+            scene.invalidate();
+        }
+        main_ctx
+            .set_timeout(CHECK_DELAY, move |_, _| {
+                test_node.update(assert_equals(&*count.lock(), &0, "frame callback runs"));
+                Ok(())
+            })
+            .context("unable to set frame callback check timeout")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use glam::{Vec2, Vec4};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    test::{
+        golden::{test_golden, GoldenTolerance},
+        tree::ParentTestNode,
+    },
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent_tagged("golden", &["gpu"]);
+    if !node.enabled() {
+        return Ok(());
+    }
+    test_golden(
+        main_ctx,
+        node.new_child_leaf("shapes"),
+        "shapes",
+        PhysicalSize::new(64, 64),
+        // the edges are antialiased differently by each driver
+        GoldenTolerance {
+            max_changed: 0.05,
+            ..Default::default()
+        },
+        |ctx| {
+            ctx.draw_rounded_rect(
+                Vec2::new(4.0, 4.0),
+                Vec2::new(60.0, 28.0),
+                6.0,
+                Vec4::new(0.9, 0.2, 0.2, 1.0),
+            );
+            ctx.draw_circle(Vec2::new(20.0, 46.0), 12.0, Vec4::new(0.2, 0.8, 0.3, 1.0));
+            ctx.draw_arc(
+                Vec2::new(46.0, 46.0),
+                10.0,
+                [0.0, PI * 1.5],
+                3.0,
+                Vec4::new(0.3, 0.4, 1.0, 1.0),
+            );
+            ctx.draw_line(Vec2::new(4.0, 60.0), Vec2::new(60.0, 36.0), 2.0, Vec4::ONE);
+        },
+    )
+}
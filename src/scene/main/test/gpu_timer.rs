@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{gpu_timer::GPU_TIMINGS_LOG, render_graph::SCENE_PASS},
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_leaf_tagged("gpu_timer", &["gpu"]);
+    if !node.enabled() {
+        return Ok(());
+    }
+    let query = main_ctx
+        .channels
+        .draw
+        .query(|ctx, _| {
+            ctx.get_test_log(GPU_TIMINGS_LOG);
+            // frames aren't rendered in headless mode, without the scenes
+            // it is fine to render some here
+            for _ in 0..3 {
+                ctx.render(None);
+            }
+            unsafe { gl::Finish() };
+            // the timings of the finished frames are read at the next one
+            ctx.render(None);
+            (ctx.gpu_timer.supported(), ctx.pop_test_log(GPU_TIMINGS_LOG))
+        })
+        .context("unable to query GPU timer test")?;
+    main_ctx.spawn_local(async move {
+        let (supported, log) = query.await?;
+        node.update(check_log(supported, &log));
+        Ok(())
+    });
+    Ok(())
+}
+
+fn check_log(supported: bool, log: &str) -> TestResult {
+    if !supported {
+        return assert_equals(&log, &"", "timings without timer query support");
+    }
+    let mut frames = Vec::new();
+    for line in log.lines() {
+        let fields = line.split(' ').collect::<Vec<_>>();
+        let frame = fields.first().and_then(|frame| frame.parse::<u64>().ok());
+        let nanos = fields.last().and_then(|nanos| nanos.parse::<u64>().ok());
+        assert_true(
+            fields.len() >= 3 && frame.is_some() && nanos.is_some(),
+            format!("malformed timing line {line:?}"),
+        )?;
+        if fields[1..fields.len() - 1].join(" ") == SCENE_PASS {
+            frames.extend(frame);
+        }
+    }
+    frames.dedup();
+    assert_true(
+        frames.len() >= 3,
+        format!(
+            "scene pass timed in {} frames, expected at least 3",
+            frames.len()
+        ),
+    )
+}
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{
+        wrappers::buffer::{BufferHandle, BufferTarget},
+        HandleInfo,
+    },
+    test::{
+        assert::{assert_equals, assert_true},
+        tree::ParentTestNode,
+    },
+};
+
+const NAME: &str = "handle dump test buffer";
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("handle_dump");
+    let draw = &mut main_ctx.channels.draw;
+    let buffer = BufferHandle::with_data(draw, NAME, BufferTarget::ArrayBuffer, vec![0u32; 16])
+        .context("unable to create handle dump test buffer")?;
+    let infos = draw
+        .query(|context, _| context.handles.handle_infos())
+        .context("unable to query handle infos")?;
+
+    let info_node = node.new_child_leaf("info");
+    let dump_node = node.new_child_leaf("dump");
+    main_ctx.spawn_local(async move {
+        let infos = infos.await?;
+        let info = infos
+            .iter()
+            .find(|info| info.name == NAME)
+            .map(|info| (info.type_name, info.estimated_size));
+        info_node.update(assert_equals(
+            &info,
+            &Some(("buffer", 64)),
+            "type and estimated size of the test buffer",
+        ));
+        let dump = HandleInfo::dump(infos);
+        dump_node.update(assert_true(
+            dump.contains(&format!("`{NAME}`")) && dump.contains("buffer: "),
+            "dump lists the test buffer and the buffer totals",
+        ));
+        drop(buffer);
+        Ok(())
+    });
+
+    Ok(())
+}
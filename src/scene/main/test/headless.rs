@@ -3,6 +3,7 @@ use std::{sync::Arc, time::Duration};
 use anyhow::Context;
 
 use crate::{
+    display::backend,
     enclose,
     exec::main_ctx::MainContext,
     graphics::context::DrawContext,
@@ -16,7 +17,9 @@ use crate::{
 };
 
 pub struct Headless {
-    no_draw: Arc<LeafTestNode>,
+    /// `offscreen_draw` with an offscreen backend, `no_draw` otherwise
+    draw_node: Arc<LeafTestNode>,
+    offscreen: bool,
 }
 
 impl Headless {
@@ -35,20 +38,33 @@ impl Headless {
         node.new_child_leaf("not_visible")
             .update(Self::test_not_visible(main_ctx));
 
-        let no_draw = node.new_child_leaf("no_draw");
+        // frames are only rendered when they go to an offscreen surface
+        let offscreen = backend::renders_frames();
+        let draw_node = node.new_child_leaf(if offscreen {
+            "offscreen_draw"
+        } else {
+            "no_draw"
+        });
         main_ctx
             .set_timeout(
                 Duration::from_secs(5),
-                enclose!((no_draw) move |_, _| {
-                    if !no_draw.finished() {
-                        no_draw.update(Ok(()));
+                enclose!((draw_node) move |_, _| {
+                    if !draw_node.finished() {
+                        draw_node.update(if offscreen {
+                            assert_unreachable("no frame was rendered offscreen")
+                        } else {
+                            Ok(())
+                        });
                     }
                     Ok(())
                 }),
             )
-            .context("unable to set timeout for no_draw test")?;
+            .context("unable to set timeout for headless draw test")?;
 
-        container.push(Self { no_draw });
+        container.push(Self {
+            draw_node,
+            offscreen,
+        });
         Ok(container)
     }
 
@@ -57,7 +73,7 @@ impl Headless {
             main_ctx
                 .display
                 .get_winit_window()
-                .is_visible()
+                .and_then(|window| window.is_visible())
                 .unwrap_or_default(),
             "Main window should not be visible in headless mode",
         )?;
@@ -72,6 +88,10 @@ impl Headless {
 
 impl Scene for Headless {
     fn draw(self: Arc<Self>, _ctx: &mut DrawContext) {
-        self.no_draw.update(Self::test_not_draw())
+        if !self.offscreen {
+            self.draw_node.update(Self::test_not_draw())
+        } else if !self.draw_node.finished() {
+            self.draw_node.update(Ok(()))
+        }
     }
 }
@@ -0,0 +1,107 @@
+use std::{fs, sync::Arc};
+
+use anyhow::Context;
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, query::ServerQuery, server::draw::ServerSendChannelExt},
+    graphics::{
+        image_loader::placeholder_image,
+        wrappers::texture::{
+            TextureFilter, TextureFormat, TextureHandle, TextureOptions, TextureWrap,
+        },
+    },
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    utils::args::args,
+};
+
+use super::texture::read_pixels;
+
+#[rustfmt::skip]
+const PIXELS: [u8; 16] = [
+    255, 0, 0, 255,     0, 255, 0, 255,
+    0, 0, 255, 255,     255, 255, 255, 128,
+];
+
+const OPTIONS: TextureOptions = TextureOptions {
+    format: TextureFormat::Rgba8,
+    min_filter: TextureFilter::Nearest,
+    mag_filter: TextureFilter::Nearest,
+    wrap: TextureWrap::ClampToEdge,
+    mipmaps: false,
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("image_loader");
+    let dir = args().artifacts_dir.join("image_loader");
+    fs::create_dir_all(&dir).context("unable to create image loader test directory")?;
+    let path = dir.join("image.png");
+    RgbaImage::from_raw(2, 2, PIXELS.to_vec())
+        .context("test pixels must match the image size")?
+        .save(&path)
+        .context("unable to write image loader test file")?;
+
+    let test_node = node.new_child_leaf("load");
+    let pending = main_ctx
+        .load_texture("image loader test texture", path, OPTIONS)
+        .context("unable to load test image")?;
+    let draw = main_ctx.channels.draw.clone_sender();
+    main_ctx.spawn_local(async move {
+        let pixels: anyhow::Result<_> = async {
+            let texture = pending.await.context("unable to load test image")?;
+            query_pixels(&draw, texture)?.await
+        }
+        .await;
+        test_node.update(pixels.map_err(Into::into).and_then(|pixels| {
+            assert_equals(
+                &pixels.as_deref(),
+                &Some(&PIXELS[..]),
+                "pixels of the loaded image",
+            )
+        }));
+        Ok(())
+    });
+
+    let test_node = node.new_child_leaf("missing_file");
+    let pending = main_ctx
+        .load_texture(
+            "missing image test texture",
+            dir.join("missing.png"),
+            OPTIONS,
+        )
+        .context("unable to load missing test image")?;
+    let placeholder = query_pixels(&main_ctx.channels.draw, pending.handle.clone())?;
+    main_ctx.spawn_local(async move {
+        let failed = pending.await.is_err();
+        let placeholder = placeholder.await?;
+        test_node.update(check_missing(failed, placeholder));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn query_pixels(
+    draw: &impl ServerSendChannelExt,
+    texture: TextureHandle,
+) -> anyhow::Result<ServerQuery<Option<Vec<u8>>>> {
+    draw.query(move |context, _| {
+        texture
+            .try_get(context)
+            .map(|texture| read_pixels(&texture, PhysicalSize::new(2, 2)))
+    })
+}
+
+fn check_missing(failed: bool, placeholder: Option<Vec<u8>>) -> TestResult {
+    assert_true(failed, "loading a missing file must fail")?;
+    assert_equals(
+        &placeholder,
+        &Some(placeholder_image().pixels),
+        "the placeholder must be drawable before the image is loaded",
+    )
+}
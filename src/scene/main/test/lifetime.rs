@@ -0,0 +1,195 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    exec::{dispatch::DispatchOwner, main_ctx::MainContext, task::Cancellable},
+    scene::lifetime::SceneLifetime,
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::Widget,
+    utils::mutex::Mutex,
+};
+
+use super::ui::TestWidgetBuilder;
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("lifetime");
+    node.new_child_leaf("dispatch_sweep")
+        .update(test_dispatch_sweep(main_ctx));
+    let test_node = node.new_child_leaf("focus_sweep");
+    test_node.update(test_focus_sweep(main_ctx, test_node.full_name()));
+
+    // the timeout fires after its scene is gone, the callback must not run
+    let test_node = node.new_child_leaf("removed_timeout");
+    let executed = Arc::new(Mutex::new(false));
+    let scene = SceneLifetime::new();
+    let parent_scene = main_ctx.current_scene.replace(scene.clone());
+    let result = main_ctx.set_timeout(
+        Duration::from_millis(50),
+        enclose!((executed) move |_, _| {
+            *executed.lock() = true;
+            Ok(())
+        }),
+    );
+    main_ctx.current_scene = parent_scene;
+    result.context("unable to set scoped timeout")?;
+    scene.invalidate();
+    main_ctx
+        .set_timeout(Duration::from_millis(200), move |_, _| {
+            test_node.update(assert_equals(
+                &*executed.lock(),
+                &false,
+                "timeout of removed scene executed",
+            ));
+            Ok(())
+        })
+        .context("unable to set check timeout")?;
+
+    test_widget_timeout(main_ctx, &node)?;
+    test_interval(main_ctx, &node)?;
+    Ok(())
+}
+
+/// The timeout fires after its widget is dropped, the callback must not run.
+fn test_widget_timeout(
+    main_ctx: &mut MainContext,
+    node: &Arc<ParentTestNode>,
+) -> anyhow::Result<()> {
+    let test_node = node.new_child_leaf("widget_timeout");
+    let executed = Arc::new(Mutex::new(false));
+    let widget: Arc<dyn Widget> =
+        TestWidgetBuilder::new().build(0, test_node.full_name().to_owned(), false, false, false);
+    let owner = main_ctx.widget_owner(&widget);
+    main_ctx
+        .set_timeout_owned(
+            Some(owner),
+            Duration::from_millis(50),
+            enclose!((executed) move |_, _| {
+                *executed.lock() = true;
+                Ok(())
+            }),
+        )
+        .context("unable to set widget timeout")?;
+    drop(widget);
+    main_ctx
+        .set_timeout(Duration::from_millis(200), move |_, _| {
+            test_node.update(assert_equals(
+                &*executed.lock(),
+                &false,
+                "timeout of dropped widget executed",
+            ));
+            Ok(())
+        })
+        .context("unable to set check timeout")
+}
+
+/// The interval runs a few times, then stops with its scene.
+fn test_interval(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    const PERIOD: Duration = Duration::from_millis(20);
+    const RUNS: usize = 3;
+
+    let test_node = node.new_child_leaf("interval");
+    let runs = Arc::new(Mutex::new(0));
+    let scene = SceneLifetime::new();
+    main_ctx
+        .set_interval_owned(
+            Some(DispatchOwner::Scene(scene.clone())),
+            PERIOD,
+            enclose!((runs, scene) move |_, _| {
+                let mut runs = runs.lock();
+                *runs += 1;
+                if *runs == RUNS {
+                    scene.invalidate();
+                }
+                Ok(())
+            }),
+        )
+        .context("unable to set interval")?;
+    // cancelled right away, never runs
+    let cancelled = Arc::new(Mutex::new(false));
+    main_ctx
+        .set_interval(
+            PERIOD,
+            enclose!((cancelled) move |_, _| {
+                *cancelled.lock() = true;
+                Ok(())
+            }),
+        )
+        .context("unable to set cancelled interval")?
+        .cancel();
+    main_ctx
+        .set_timeout(PERIOD * (RUNS as u32 + 5), move |_, _| {
+            test_node.update(check_interval(*runs.lock(), RUNS, *cancelled.lock()));
+            Ok(())
+        })
+        .context("unable to set check timeout")
+}
+
+fn check_interval(runs: usize, expected: usize, cancelled: bool) -> TestResult {
+    assert_equals(&runs, &expected, "interval runs")?;
+    assert_equals(&cancelled, &false, "cancelled interval executed")
+}
+
+fn test_dispatch_sweep(main_ctx: &mut MainContext) -> TestResult {
+    let scene = SceneLifetime::new();
+    let captured = Arc::new(());
+    let id = main_ctx.dispatch_list.push_scoped(
+        Some(DispatchOwner::Scene(scene.clone())),
+        enclose!((captured) move |_, _| {
+            drop(captured);
+            Ok(())
+        }),
+    );
+    main_ctx.sweep_expired();
+    assert_equals(
+        &Arc::strong_count(&captured),
+        &2,
+        "dispatch of a live scene kept",
+    )?;
+
+    scene.invalidate();
+    main_ctx.sweep_expired();
+    assert_equals(
+        &Arc::strong_count(&captured),
+        &1,
+        "dispatch of a removed scene released",
+    )?;
+    assert_true(
+        main_ctx.dispatch_list.pop(id).is_none(),
+        "swept dispatch still poppable",
+    )
+}
+
+fn test_focus_sweep(main_ctx: &mut MainContext, name: &str) -> TestResult {
+    if main_ctx.focused_widget.is_some() {
+        // focus is owned by some other widget, nothing to check
+        return Ok(());
+    }
+
+    let scene = SceneLifetime::new();
+    let widget = TestWidgetBuilder::new().build(0, name.to_owned(), false, false, false);
+    let parent_scene = main_ctx.current_scene.replace(scene.clone());
+    main_ctx.set_focus_widget(Some(widget.clone()));
+    main_ctx.current_scene = parent_scene;
+    assert_equals(
+        &main_ctx.get_focused_widget().map(|w| w.id()),
+        &Some(widget.id()),
+        "focused widget",
+    )?;
+
+    scene.invalidate();
+    main_ctx.sweep_expired();
+    assert_true(
+        main_ctx.focused_widget.is_none(),
+        "focus of a removed scene released",
+    )?;
+    assert_equals(
+        &Arc::strong_count(&widget),
+        &1,
+        "focus tracking doesn't retain the widget",
+    )
+}
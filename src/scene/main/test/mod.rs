@@ -1,29 +1,150 @@
-use anyhow::Context;
+use std::sync::Arc;
 
-use crate::{exec::main_ctx::MainContext, scene::SceneContainer};
+use crate::{
+    exec::main_ctx::MainContext,
+    scene::SceneContainer,
+    test::{
+        schedule::{GroupSetup, Isolation},
+        tree::ParentTestNode,
+    },
+};
 
-use self::headless::Headless;
+use self::{
+    headless::Headless, particles::ParticleEffects, picking::PickTargets, tilemap::TileMapTest,
+};
 
+pub mod alloc;
+pub mod atlas;
+pub mod audio;
+pub mod beat_clock;
+pub mod buffer;
+pub mod camera;
+pub mod cancel;
+pub mod capture;
+pub mod clip;
+pub mod culling;
+pub mod draw_command;
+pub mod error;
+pub mod event_bus;
+pub mod frame_callback;
+pub mod golden;
+pub mod gpu_timer;
+pub mod handle_dump;
 pub mod headless;
+pub mod image_loader;
+pub mod lifetime;
+pub mod msaa;
+pub mod nav;
+pub mod network;
+pub mod particles;
+pub mod pause;
+pub mod picking;
+pub mod pointer_latch;
+pub mod post_effect;
+pub mod present;
+pub mod query;
+pub mod render_graph;
+pub mod retry;
+pub mod screenshot;
+pub mod sequence;
+pub mod shader_reload;
+pub mod shape;
+pub mod soak;
+pub mod sprite;
+pub mod startup;
+pub mod state_machine;
+pub mod text;
+pub mod texture;
+pub mod tilemap;
 pub mod timeout_delay;
+pub mod tween;
 pub mod ui;
+pub mod undo;
+pub mod uniform;
+pub mod upload;
 
 pub fn new(main_ctx: &mut MainContext) -> anyhow::Result<SceneContainer> {
-    let mut container = SceneContainer::new();
-    let node = &main_ctx
-        .test_manager
-        .as_ref()
-        .expect("TestManager must exist in test mode")
-        .root
-        .clone();
-    timeout_delay::test(main_ctx, node).context("unable to initiate TimeoutDelay tests")?;
-    container
-        .push_all(Headless::new(main_ctx, node).context("unable to create Headless test scene")?);
-    container.push_all(ui::new(main_ctx, node).context("unable to create UI test scene")?);
-    main_ctx
+    use Isolation::{Exclusive, Shared};
+
+    let test_manager = main_ctx
         .test_manager
-        .as_ref()
-        .expect("TestManager must exist in test mode")
-        .finish_init();
+        .clone()
+        .expect("TestManager must exist in test mode");
+    // exclusive groups drive input, resize the window, read the screen,
+    // time frames or check global state such as allocations and errors
+    let groups = [
+        ("timeout_delay", Exclusive, tests(timeout_delay::test)),
+        ("alloc", Exclusive, tests(alloc::test)),
+        ("atlas", Shared, tests(atlas::test)),
+        ("audio", Shared, tests(audio::test)),
+        ("beat_clock", Shared, tests(beat_clock::test)),
+        ("buffer", Shared, tests(buffer::test)),
+        ("camera", Shared, tests(camera::test)),
+        ("cancel", Shared, tests(cancel::test)),
+        ("capture", Exclusive, tests(capture::test)),
+        ("clip", Shared, tests(clip::test)),
+        ("culling", Shared, tests(culling::test)),
+        ("draw_command", Shared, tests(draw_command::test)),
+        ("error", Exclusive, tests(error::test)),
+        ("event_bus", Shared, tests(event_bus::test)),
+        ("frame_callback", Shared, tests(frame_callback::test)),
+        ("golden", Exclusive, tests(golden::test)),
+        ("gpu_timer", Exclusive, tests(gpu_timer::test)),
+        ("handle_dump", Exclusive, tests(handle_dump::test)),
+        ("image_loader", Shared, tests(image_loader::test)),
+        ("lifetime", Shared, tests(lifetime::test)),
+        ("msaa", Exclusive, tests(msaa::test)),
+        ("nav", Shared, tests(nav::test)),
+        ("network", Shared, tests(network::test)),
+        ("pause", Exclusive, tests(pause::test)),
+        ("pointer_latch", Exclusive, tests(pointer_latch::test)),
+        ("post_effect", Exclusive, tests(post_effect::test)),
+        ("present", Exclusive, tests(present::test)),
+        ("query", Shared, tests(query::test)),
+        ("render_graph", Shared, tests(render_graph::test)),
+        ("retry", Shared, tests(retry::test)),
+        ("screenshot", Exclusive, tests(screenshot::test)),
+        ("sequence", Shared, tests(sequence::test)),
+        ("shader_reload", Exclusive, tests(shader_reload::test)),
+        ("shape", Shared, tests(shape::test)),
+        ("soak", Exclusive, tests(soak::test)),
+        ("sprite", Shared, tests(sprite::test)),
+        ("startup", Shared, tests(startup::test)),
+        ("state_machine", Shared, tests(state_machine::test)),
+        ("text", Shared, tests(text::test)),
+        ("texture", Shared, tests(texture::test)),
+        ("tween", Shared, tests(tween::test)),
+        ("undo", Shared, tests(undo::test)),
+        ("uniform", Shared, tests(uniform::test)),
+        ("upload", Shared, tests(upload::test)),
+        ("headless", Shared, scenes(Headless::new)),
+        ("ui", Exclusive, scenes(ui::new)),
+        ("ui_scene", Exclusive, scenes(ui::scene::new)),
+        ("picking", Exclusive, scenes(PickTargets::new)),
+        ("particles", Shared, scenes(ParticleEffects::new)),
+        ("tilemap", Shared, scenes(TileMapTest::new)),
+    ];
+    let mut container = SceneContainer::new();
+    for (name, isolation, setup) in groups {
+        container.push_arc(test_manager.add_group(name, isolation, setup));
+    }
+    test_manager.poll(main_ctx);
+    test_manager.finish_init();
     Ok(container)
 }
+
+/// Setup of a group without scenes.
+fn tests<F>(setup: F) -> Box<dyn GroupSetup>
+where
+    F: FnOnce(&mut MainContext, &Arc<ParentTestNode>) -> anyhow::Result<()> + Send + 'static,
+{
+    Box::new(
+        move |main_ctx: &mut MainContext, node: &Arc<ParentTestNode>| {
+            setup(main_ctx, node).map(|()| SceneContainer::new())
+        },
+    )
+}
+
+fn scenes<F: GroupSetup + 'static>(setup: F) -> Box<dyn GroupSetup> {
+    Box::new(setup)
+}
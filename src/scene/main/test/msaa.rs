@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use glam::Vec2;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{
+        render_graph::SCENE_PASS,
+        wrappers::{
+            framebuffer::MultisampleFramebuffer,
+            texture::{ImageData, TextureHandle},
+        },
+    },
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::utils::geom::UISize,
+};
+
+use super::{
+    shape::{bind_target, unbind_target},
+    texture::read_pixels,
+};
+
+const SIZE: u32 = 16;
+const SAMPLES: u32 = 4;
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("msaa");
+    let draw = &mut main_ctx.channels.draw;
+    let target = ImageData::new(
+        PhysicalSize::new(SIZE, SIZE),
+        vec![0; (SIZE * SIZE * 4) as usize],
+    )?;
+    let target = TextureHandle::new(draw, "msaa test target", target)
+        .context("unable to create msaa test target")?;
+    let mut multisample = MultisampleFramebuffer::new(draw, "msaa test framebuffer", SAMPLES)
+        .context("unable to create msaa test framebuffer")?;
+    draw.set_multisample(SAMPLES)
+        .context("unable to set scene multisampling")?;
+
+    let query = draw
+        .query(move |ctx, _| {
+            let previous_size = ctx.ui_size;
+            ctx.ui_size = UISize::new(SIZE as f32, SIZE as f32);
+            let fbo = bind_target(**target.get(ctx));
+            let resized = multisample
+                .resize_in_server(ctx, PhysicalSize::new(SIZE, SIZE))
+                .map_err(|e| format!("{e:#}"));
+            let samples = multisample.effective_samples().unwrap_or_default();
+
+            multisample.framebuffer.get(ctx).bind();
+            unsafe { gl::Clear(gl::COLOR_BUFFER_BIT) };
+            // the diagonal crosses the pixel centers of (7, 8) and (8, 7)
+            // without the anti-aliasing fringe of the shape renderer
+            ctx.draw_stencil_polygon(&[
+                Vec2::ZERO,
+                Vec2::new(SIZE as f32, 0.0),
+                Vec2::new(0.0, SIZE as f32),
+            ]);
+            ctx.flush_shapes();
+            multisample.resolve(ctx, fbo);
+
+            let pixels = read_pixels(&target.get(ctx), PhysicalSize::new(SIZE, SIZE));
+            unbind_target(ctx, fbo);
+            ctx.ui_size = previous_size;
+            // the scene is multisampled from the next frame on
+            let scene_samples = ctx.render_graph.samples(SCENE_PASS);
+            ((resized, samples, scene_samples), pixels)
+        })
+        .context("unable to query msaa test")?;
+
+    let framebuffer_node = node.new_child_leaf("framebuffer");
+    let resolve_node = node.new_child_leaf("resolve");
+    main_ctx.spawn_local(async move {
+        let ((resized, samples, scene_samples), pixels) = query.await?;
+        framebuffer_node.update(check_framebuffer(resized, samples, scene_samples));
+        resolve_node.update(check_resolve(&pixels, samples));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn check_framebuffer(
+    resized: Result<(), String>,
+    samples: u32,
+    scene_samples: Option<u32>,
+) -> TestResult {
+    assert_equals(&resized, &Ok(()), "multisample framebuffer allocation")?;
+    assert_true(
+        (1..=SAMPLES).contains(&samples),
+        "sample count clamped to the driver limit",
+    )?;
+    // not drawn yet, or drawn with the same limit
+    assert_true(
+        scene_samples.map(|s| s == samples).unwrap_or(true),
+        "scene pass sample count",
+    )
+}
+
+fn check_resolve(pixels: &[u8], samples: u32) -> TestResult {
+    // UI coordinates of the pixels, rows are read bottom to top
+    let pixel = |x: u32, y: u32| {
+        let start = (((SIZE - 1 - y) * SIZE + x) * 4) as usize;
+        pixels[start]
+    };
+    assert_equals(&pixel(2, 2), &255, "inside the triangle")?;
+    assert_equals(&pixel(13, 13), &0, "outside the triangle")?;
+    if samples > 1 {
+        let edge = pixel(7, 8);
+        assert_true(edge > 0 && edge < 255, "edge pixel partially covered")?;
+    }
+    Ok(())
+}
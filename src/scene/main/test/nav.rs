@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use glam::Vec2;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    nav::{NavGrid, NavPath, PathQuery},
+    test::{
+        assert::{assert_equals, assert_less_equals, assert_true},
+        result::{TestError, TestResult},
+        tree::ParentTestNode,
+    },
+};
+
+const CELL_SIZE: f32 = 10.0;
+
+#[rustfmt::skip]
+const MAP: [&str; 10] = [
+    "..........",
+    "..........",
+    "..........",
+    "..........",
+    "....#.....",
+    "....#.....",
+    "....#.....",
+    "....#.....",
+    "....#.....",
+    "....#.....",
+];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("nav");
+    let grid = Arc::new(NavGrid::from_rows(&MAP, CELL_SIZE));
+
+    let mut test = |name: &'static str,
+                    start: (f32, f32),
+                    goal: (f32, f32),
+                    agent_radius: f32,
+                    check: fn(&NavGrid, &PathQuery, Option<NavPath>) -> TestResult|
+     -> anyhow::Result<()> {
+        let test_node = node.new_child_leaf(name);
+        let query = PathQuery {
+            start: Vec2::new(start.0, start.1) * CELL_SIZE,
+            goal: Vec2::new(goal.0, goal.1) * CELL_SIZE,
+            agent_radius,
+        };
+        let grid = grid.clone();
+        main_ctx
+            .find_path(grid.clone(), query, move |_, _, path| {
+                test_node.update(check(&grid, &query, path));
+                Ok(())
+            })
+            .with_context(|| format!("unable to queue {name} path query"))
+    };
+
+    test(
+        "straight",
+        (0.5, 9.5),
+        (3.5, 9.5),
+        0.0,
+        |grid, query, path| {
+            let path = check_path(grid, query, path)?;
+            assert_equals(&path.cost, &30.0, "straight path cost")
+        },
+    )?;
+    test(
+        "around_wall",
+        (2.5, 7.5),
+        (7.5, 7.5),
+        0.0,
+        |grid, query, path| {
+            let path = check_path(grid, query, path)?;
+            assert_true(path.cost > 50.0, "path must go around the wall")
+        },
+    )?;
+    test(
+        "agent_radius",
+        (2.5, 7.5),
+        (7.5, 7.5),
+        8.0,
+        |grid, query, path| {
+            let path = check_path(grid, query, path)?;
+            for &point in &path.points[1..path.points.len() - 1] {
+                let cell = grid.cell_at(point).unwrap_or_default();
+                assert_less_equals(
+                    &query.agent_radius,
+                    &grid.clearance(cell),
+                    "waypoints must keep the agent away from obstacles",
+                )?;
+            }
+            Ok(())
+        },
+    )?;
+    test("unreachable", (2.5, 7.5), (7.5, 7.5), 20.0, |_, _, path| {
+        assert_equals(&path, &None, "agent is too large to pass")
+    })?;
+    test("blocked_goal", (2.5, 7.5), (4.5, 7.5), 0.0, |_, _, path| {
+        assert_equals(&path, &None, "goal is inside a wall")
+    })?;
+    Ok(())
+}
+
+fn check_path(
+    grid: &NavGrid,
+    query: &PathQuery,
+    path: Option<NavPath>,
+) -> Result<NavPath, TestError> {
+    let path = path.ok_or_else(|| anyhow::format_err!("no path found"))?;
+    assert_equals(&path.points.first(), &Some(&query.start), "path start")?;
+    assert_equals(&path.points.last(), &Some(&query.goal), "path goal")?;
+    for pair in path.points.windows(2) {
+        let (a, b) = (
+            grid.cell_at(pair[0]).unwrap_or_default(),
+            grid.cell_at(pair[1]).unwrap_or_default(),
+        );
+        assert_true(
+            !grid.is_blocked(a) && !grid.is_blocked(b),
+            "path must not go through walls",
+        )?;
+        assert_less_equals(
+            &(a.as_ivec2() - b.as_ivec2()).abs().max_element(),
+            &1,
+            "waypoints must be adjacent",
+        )?;
+    }
+    Ok(path)
+}
@@ -0,0 +1,62 @@
+use std::{
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+};
+
+use anyhow::Context;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    net::{Delivery, NetEvent, NetMessage},
+    test::{assert::assert_equals, tree::ParentTestNode},
+    utils::mutex::Mutex,
+};
+
+const PING: u16 = 1;
+
+/// A port nothing listens on, at least until the next bind.
+fn free_port() -> anyhow::Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").context("unable to bind a free port")?;
+    listener
+        .local_addr()
+        .context("unable to get the free port address")
+}
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("network");
+
+    let test_node = node.new_child_leaf("loopback");
+    // refused on a worker thread, the session below must still go through
+    main_ctx.channels.network.connect(free_port()?)?;
+
+    let addr = free_port()?;
+    main_ctx.channels.network.listen(addr)?;
+    main_ctx.channels.network.connect(addr)?;
+    // the handler removes itself once the ping arrives
+    let handler = Arc::new(Mutex::new(None));
+    *handler.lock() = Some(main_ctx.add_net_handler({
+        let handler = handler.clone();
+        move |main_ctx, _, event| {
+            match event {
+                // the client side, the server sees the client address
+                NetEvent::Connected(peer, peer_addr) if *peer_addr == addr => main_ctx
+                    .channels
+                    .network
+                    .send_message(*peer, Delivery::Reliable, NetMessage::new(PING, "ping"))?,
+                NetEvent::Message(_, message) if message.kind == PING => {
+                    if let Some(id) = handler.lock().take() {
+                        main_ctx.remove_net_handler(id);
+                        test_node.update(assert_equals(
+                            &message.payload.as_slice(),
+                            &b"ping".as_slice(),
+                            "received payload",
+                        ));
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+    }));
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use glam::{Vec2, Vec4};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    graphics::{
+        context::DrawContext,
+        particles::{Curve, EmitterDef, ParticleEffectHandle},
+        sprite_renderer::Sprite,
+        wrappers::texture::{ImageData, TextureHandle},
+    },
+    scene::{Scene, SceneContainer},
+    test::{
+        assert::{assert_less_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+};
+
+/// Off screen, so that the other tests reading the screen don't see them.
+const ORIGIN: Vec2 = Vec2::new(-1000.0, -1000.0);
+const LIFETIME: Duration = Duration::from_millis(300);
+const MAX_PARTICLES: usize = 16;
+
+/// A fountain drawn with the other test scenes.
+pub struct ParticleEffects {
+    effect: Arc<ParticleEffectHandle>,
+    texture: TextureHandle,
+}
+
+impl ParticleEffects {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        main_ctx: &mut MainContext,
+        node: &Arc<ParentTestNode>,
+    ) -> anyhow::Result<SceneContainer> {
+        let node = node.new_child_parent("particles");
+        if !node.enabled() {
+            return Ok(SceneContainer::new());
+        }
+        let simulate_node = node.new_child_leaf("simulate");
+        let emission_node = node.new_child_leaf("emission");
+
+        let def = EmitterDef::new(100.0, LIFETIME)
+            .lifetime(LIFETIME / 2, LIFETIME)
+            .velocity(Vec2::new(-10.0, -25.0), Vec2::new(10.0, -15.0))
+            .acceleration(Vec2::new(0.0, 50.0))
+            .color(Curve::new([
+                (0.0, Vec4::ONE),
+                (1.0, Vec4::new(1.0, 0.5, 0.0, 0.0)),
+            ])?)
+            .size(Curve::new([(0.0, 2.0), (1.0, 6.0)])?)
+            .max_particles(MAX_PARTICLES);
+        let effect = Arc::new(
+            main_ctx
+                .channels
+                .update
+                .add_particle_effect(def, Vec2::ZERO)
+                .context("unable to add test particle effect")?,
+        );
+        // moved before its first tick, nothing is spawned on screen
+        effect.set_position(ORIGIN)?;
+        let texture = TextureHandle::new(
+            &mut main_ctx.channels.draw,
+            "particle test texture",
+            ImageData::new(PhysicalSize::new(1, 1), vec![255; 4])?,
+        )
+        .context("unable to create particle test texture")?;
+
+        let simulated = effect.clone();
+        main_ctx
+            .set_timeout(Duration::from_millis(500), move |main_ctx, _| {
+                simulate_node.update(check_simulate(&simulated.sprites()));
+                simulated.set_emitting(false)?;
+                main_ctx.set_timeout(LIFETIME * 2, move |_, _| {
+                    emission_node.update(assert_true(
+                        simulated.sprites().is_empty(),
+                        "particles dead after emission stopped",
+                    ));
+                    Ok(())
+                })?;
+                Ok(())
+            })
+            .context("unable to set timeout for particle tests")?;
+
+        let mut container = SceneContainer::new();
+        container.push(Self { effect, texture });
+        Ok(container)
+    }
+}
+
+fn check_simulate(sprites: &[Sprite]) -> TestResult {
+    assert_true(!sprites.is_empty(), "particles spawned")?;
+    assert_less_equals(&sprites.len(), &MAX_PARTICLES, "particle count")?;
+    // launched upwards then falling, the horizontal spread is bounded by
+    // the initial velocity
+    let max_offset = 10.0 * LIFETIME.as_secs_f32();
+    assert_true(
+        sprites.iter().all(|sprite| {
+            (sprite.center.x - ORIGIN.x).abs() <= max_offset && sprite.center.y <= ORIGIN.y
+        }),
+        "particles moved within their velocity range",
+    )
+}
+
+impl Scene for ParticleEffects {
+    fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
+        if let Some(texture) = self.texture.try_get(ctx) {
+            self.effect.draw(ctx, *texture);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    ai::behavior_tree::{BehaviorTree, Node, Status},
+    exec::{main_ctx::MainContext, server::ServerKind},
+    test::{
+        assert::{assert_equals, assert_greater_than},
+        tree::ParentTestNode,
+    },
+};
+
+const WAIT: Duration = Duration::from_millis(300);
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("pause");
+    let paused_node = node.new_child_leaf("paused");
+    let resumed_node = node.new_child_leaf("resumed");
+
+    let tree = BehaviorTree::new(Node::action("count", |bb| {
+        let count = bb.get_or_default::<u32>("count");
+        bb.set("count", count + 1);
+        Status::Running
+    }));
+    main_ctx.executor.pause_server(ServerKind::Update);
+    // messages are still processed while paused
+    let tree = main_ctx
+        .channels
+        .update
+        .add_behavior_tree(tree)
+        .context("unable to add behavior tree")?;
+
+    // timeouts keep firing while the update server is paused
+    main_ctx
+        .set_timeout(WAIT, move |main_ctx, _| {
+            let count = tree.blackboard.lock().get_or_default::<u32>("count");
+            paused_node.update(assert_equals(&count, &0, "paused server must not tick"));
+            main_ctx.executor.resume_server(ServerKind::Update);
+
+            main_ctx.set_timeout(WAIT, move |_, _| {
+                let count = tree.blackboard.lock().get_or_default::<u32>("count");
+                resumed_node.update(assert_greater_than(&count, &0, "resumed server must tick"));
+                Ok(())
+            })
+        })
+        .context("unable to set timeout")?;
+    Ok(())
+}
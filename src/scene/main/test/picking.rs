@@ -0,0 +1,93 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    graphics::context::DrawContext,
+    scene::{Scene, SceneContainer},
+    test::{
+        assert::{assert_equals, assert_true},
+        tree::ParentTestNode,
+    },
+    ui::{
+        containers::{stack::Stack, ContainerWidget},
+        controls::button::Button,
+        utils::geom::{UIPos, UIRect, UISize},
+        Alignment, HorizontalAlignment, VerticalAlignment, Visibility, Widget,
+    },
+    utils::uid::Uid,
+};
+
+/// Two overlapping rects, drawn after the other test scenes, under a
+/// hidden button.
+pub struct PickTargets {
+    ids: [Uid; 2],
+    hidden: Stack,
+}
+
+impl PickTargets {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        main_ctx: &mut MainContext,
+        node: &Arc<ParentTestNode>,
+    ) -> anyhow::Result<SceneContainer> {
+        let node = node.new_child_parent("picking");
+        let inside_node = node.new_child_leaf("inside");
+        let overlap_node = node.new_child_leaf("overlap");
+        let outside_node = node.new_child_leaf("outside");
+        let hidden_node = node.new_child_leaf("hidden");
+        let ids = [Uid::new(), Uid::new()];
+
+        // over the first rect, which stays pickable
+        let hidden = Stack::new();
+        let button = Button::new(None);
+        button.set_bounds(UIRect::new(UIPos::new(10.0, 10.0), UISize::new(10.0, 10.0)));
+        let alignment = Alignment::new(HorizontalAlignment::Left, VerticalAlignment::Top);
+        hidden.push(button, alignment);
+        hidden.set_visibility(Visibility::PhyiscalHidden);
+
+        // picks sent once the draw server has the root scene
+        main_ctx
+            .set_timeout(Duration::ZERO, move |main_ctx, _| {
+                let inside = main_ctx.pick_at(UIPos::new(20.0, 20.0))?;
+                let overlap = main_ctx.pick_at(UIPos::new(35.0, 35.0))?;
+                let outside = main_ctx.pick_at(UIPos::new(100.0, 100.0))?;
+                let hidden = main_ctx.pick_at(UIPos::new(15.0, 15.0))?;
+                main_ctx.spawn_local(async move {
+                    inside_node.update(assert_equals(&inside.await?, &Some(ids[0]), "picked id"));
+                    overlap_node.update(assert_equals(
+                        &overlap.await?,
+                        &Some(ids[1]),
+                        "id drawn last",
+                    ));
+                    let outside = outside.await?;
+                    outside_node.update(assert_true(
+                        outside.map(|id| !ids.contains(&id)).unwrap_or(true),
+                        format!("nothing drawn there, picked {outside:?}"),
+                    ));
+                    hidden_node.update(assert_equals(
+                        &hidden.await?,
+                        &Some(ids[0]),
+                        "id under the hidden button",
+                    ));
+                    Ok(())
+                });
+                Ok(())
+            })
+            .context("unable to set timeout for picking tests")?;
+
+        let mut container = SceneContainer::new();
+        container.push(Self { ids, hidden });
+        Ok(container)
+    }
+}
+
+impl Scene for PickTargets {
+    fn draw_pick(self: Arc<Self>, ctx: &mut DrawContext) {
+        let size = UISize::new(30.0, 30.0);
+        ctx.draw_pick_rect(self.ids[0], UIRect::new(UIPos::new(10.0, 10.0), size));
+        ctx.draw_pick_rect(self.ids[1], UIRect::new(UIPos::new(30.0, 30.0), size));
+        self.hidden.draw_pick(ctx);
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use crate::{
+    exec::{
+        main_ctx::MainContext,
+        server::{draw::ServerSendChannelExt, GameServerSendChannel},
+    },
+    test::{assert::assert_true, input::InputDriver, result::TestResult, tree::ParentTestNode},
+    ui::utils::geom::UIPos,
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_leaf("pointer_latch");
+    let latch = main_ctx.subscribe_pointer();
+    let driver = InputDriver::new(main_ctx);
+    let draw = main_ctx.channels.draw.clone_sender();
+    let positions = [UIPos::new(10.0, 20.0), UIPos::new(30.0, 40.0)];
+    main_ctx.spawn_local(async move {
+        let result: TestResult = async {
+            for position in positions {
+                driver.move_to(position)?.await?;
+            }
+            // read on the draw thread, like a scene drawing an ink stroke
+            let (latest, samples) = draw
+                .query(move |_, _| (latch.latest(), latch.drain()))?
+                .await?;
+            assert_true(latest.is_some(), "no pointer sample was latched")?;
+            // other tests (or the user) may move the cursor meanwhile
+            let mut expected = positions.iter().peekable();
+            for sample in samples.iter() {
+                if expected.peek() == Some(&&sample.position) {
+                    expected.next();
+                }
+            }
+            assert_true(
+                expected.peek().is_none(),
+                format!("latched samples {samples:?} don't include {positions:?} in order"),
+            )
+        }
+        .await;
+        node.update(result);
+        Ok(())
+    });
+    Ok(())
+}
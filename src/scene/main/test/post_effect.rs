@@ -0,0 +1,139 @@
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::Context;
+use gl::types::GLuint;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{
+        context::DrawContext,
+        post_effect::{PostEffect, Vignette, POST_PASS},
+        render_graph::SCENE_PASS,
+    },
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    utils::mutex::Mutex,
+};
+
+const FIRST: &str = "post effect test first";
+const SECOND: &str = "post effect test second";
+
+type CallLog = Arc<Mutex<Vec<(&'static str, &'static str)>>>;
+
+/// Logs its calls, and whether it drew offscreen, then copies `src` over.
+struct Recorder {
+    name: &'static str,
+    log: CallLog,
+}
+
+impl PostEffect for Recorder {
+    fn name(&self) -> Cow<'static, str> {
+        self.name.into()
+    }
+
+    fn init(&mut self, _: &mut DrawContext) -> anyhow::Result<()> {
+        self.log.lock().push((self.name, "init"));
+        Ok(())
+    }
+
+    fn resize(&mut self, _: &mut DrawContext, _: PhysicalSize<u32>) -> anyhow::Result<()> {
+        self.log.lock().push((self.name, "resize"));
+        Ok(())
+    }
+
+    fn apply(&mut self, _: &mut DrawContext, src: GLuint, dst: GLuint) -> anyhow::Result<()> {
+        let call = if src != 0 && dst != 0 {
+            "apply offscreen"
+        } else {
+            "apply"
+        };
+        self.log.lock().push((self.name, call));
+        Ok(())
+    }
+}
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("post_effect");
+    let log = CallLog::default();
+    let recorder = |name| Recorder {
+        name,
+        log: log.clone(),
+    };
+    let vignette = Vignette::new(main_ctx.dummy_vao(), &mut main_ctx.channels.draw)
+        .context("unable to create post effect test vignette")?;
+    // inserted out of order, the indices decide the order
+    main_ctx.insert_post_effect(0, recorder(FIRST))?;
+    main_ctx.insert_post_effect(usize::MAX, vignette)?;
+    main_ctx.insert_post_effect(1, recorder(SECOND))?;
+
+    let query = main_ctx
+        .channels
+        .draw
+        .query(move |ctx, _| {
+            let effects = ctx.post_effects.names();
+            let passes = pass_names(ctx);
+            // frames aren't rendered in headless mode, without the scenes
+            // it is fine to render one here
+            ctx.render(None);
+            for name in effects.iter() {
+                ctx.remove_post_effect(name);
+            }
+            let effects = effects.into_iter().map(Cow::into_owned).collect::<Vec<_>>();
+            (effects, passes, pass_names(ctx), log.lock().clone())
+        })
+        .context("unable to query post effect test")?;
+
+    let order_node = node.new_child_leaf("order");
+    let calls_node = node.new_child_leaf("calls");
+    let remove_node = node.new_child_leaf("remove");
+    main_ctx.spawn_local(async move {
+        let (effects, passes, removed_passes, log) = query.await?;
+        order_node.update(check_order(&effects, &passes));
+        calls_node.update(check_calls(&log));
+        remove_node.update(assert_equals(
+            &removed_passes,
+            &vec![SCENE_PASS.to_owned()],
+            "passes left after removing the effects",
+        ));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn pass_names(ctx: &mut DrawContext) -> Vec<String> {
+    ctx.render_graph
+        .pass_names()
+        .into_iter()
+        .map(str::to_owned)
+        .collect()
+}
+
+fn check_order(effects: &[String], passes: &[String]) -> TestResult {
+    assert_equals(
+        &effects,
+        &[FIRST, SECOND, "vignette"].map(str::to_owned).as_slice(),
+        "post effect order",
+    )?;
+    assert_equals(
+        &passes,
+        &[SCENE_PASS, POST_PASS].map(str::to_owned).as_slice(),
+        "render passes with post effects",
+    )
+}
+
+fn check_calls(log: &[(&str, &str)]) -> TestResult {
+    // only the effects before the last one draw offscreen
+    assert_equals(
+        &log.iter().take(6).copied().collect::<Vec<_>>(),
+        &vec![
+            (FIRST, "init"),
+            (SECOND, "init"),
+            (FIRST, "resize"),
+            (SECOND, "resize"),
+            (FIRST, "apply offscreen"),
+            (SECOND, "apply offscreen"),
+        ],
+        "post effect calls",
+    )
+}
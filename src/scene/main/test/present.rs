@@ -0,0 +1,59 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::present::{PresentMode, PresentModeReport},
+    test::{assert::assert_true, result::TestResult, tree::ParentTestNode},
+    utils::mutex::Mutex,
+};
+
+/// The report comes with the next frame at the latest.
+const REPORT_LATENCY: Duration = Duration::from_secs(1);
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("present_mode");
+
+    let test_node = node.new_child_leaf("fallback");
+    // other scenes may change the mode too, only our request is checked
+    let subscription = Arc::new(Mutex::new(None));
+    *subscription.lock() = Some(main_ctx.event_bus.subscribe({
+        let subscription = subscription.clone();
+        move |main_ctx, _, report: &PresentModeReport| {
+            if report.requested != PresentMode::Immediate || subscription.lock().take().is_none() {
+                return Ok(());
+            }
+            test_node.update(
+                check_fallback(report)
+                    .and_then(|_| test_node.assert_completed_within(REPORT_LATENCY)),
+            );
+            main_ctx.channels.draw.set_present_mode(PresentMode::Fifo)
+        }
+    }));
+    main_ctx
+        .channels
+        .draw
+        .set_present_mode(PresentMode::Immediate)
+        .context("unable to request immediate present mode")
+}
+
+fn check_fallback(report: &PresentModeReport) -> TestResult {
+    let effective = report.effective.unwrap_or(report.requested);
+    assert_true(
+        report.requested.fallbacks().contains(&effective),
+        "the effective mode must be one of the fallbacks",
+    )?;
+    assert_true(
+        report.supported.contains(&effective),
+        "the effective mode must be supported",
+    )?;
+    // the requested mode wins whenever the surface accepts it
+    assert_true(
+        effective == report.requested || !report.supported.contains(&report.requested),
+        format!(
+            "fell back to {effective:?} though {:?} is supported",
+            report.requested
+        ),
+    )
+}
@@ -0,0 +1,82 @@
+use std::{fmt::Debug, sync::Arc};
+
+use anyhow::Context;
+
+use crate::{
+    exec::{
+        main_ctx::MainContext,
+        query::ServerQuery,
+        server::{draw::ServerSendChannelExt, GameServerSendChannel},
+    },
+    test::{
+        assert::assert_equals,
+        result::TestResult,
+        tree::{LeafTestNode, ParentTestNode},
+    },
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("query");
+
+    fn check<R>(
+        main_ctx: &mut MainContext,
+        test_node: Arc<LeafTestNode>,
+        query: ServerQuery<R>,
+        expected: R,
+    ) where
+        R: PartialEq + Debug + 'static,
+    {
+        main_ctx.spawn_local(async move {
+            let result: TestResult = match query.await {
+                Ok(value) => assert_equals(&value, &expected, "query result"),
+                Err(e) => Err(e.into()),
+            };
+            test_node.update(result);
+            Ok(())
+        });
+    }
+
+    let query = main_ctx
+        .channels
+        .draw
+        .query(|_, _| 6 * 7)
+        .context("unable to query draw server")?;
+    check(main_ctx, node.new_child_leaf("draw"), query, 42);
+
+    let query = main_ctx
+        .channels
+        .update
+        .query(|_| "update")
+        .context("unable to query update server")?;
+    check(main_ctx, node.new_child_leaf("update"), query, "update");
+
+    let query = main_ctx
+        .channels
+        .audio
+        .query(|_| "audio")
+        .context("unable to query audio server")?;
+    check(main_ctx, node.new_child_leaf("audio"), query, "audio");
+
+    // a task awaiting several queries in a row
+    let test_node = node.new_child_leaf("chained");
+    let first = main_ctx
+        .channels
+        .draw
+        .query(|_, _| 1)
+        .context("unable to query draw server")?;
+    let draw = main_ctx.channels.draw.clone_sender();
+    main_ctx.spawn_local(async move {
+        let result: TestResult = async {
+            let first = first.await?;
+            let second = draw
+                .query(move |_, _| first + 1)
+                .context("unable to query draw server")?
+                .await?;
+            assert_equals(&second, &2, "chained query result")
+        }
+        .await;
+        test_node.update(result);
+        Ok(())
+    });
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::{
+    exec::{
+        main_ctx::MainContext,
+        server::{draw::ServerSendChannelExt, GameServerSendChannel},
+    },
+    graphics::{
+        frame_graph::FrameGraph,
+        render_graph::{RenderPass, RenderTarget, SCENE_PASS},
+        wrappers::framebuffer::DefaultTextureFramebuffer,
+    },
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    utils::mutex::Mutex,
+};
+
+const FILL: &str = "render graph test fill";
+const BLUR: &str = "render graph test blur";
+const POST: &str = "render graph test post";
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("render_graph");
+    let draw = &mut main_ctx.channels.draw;
+    let scene = DefaultTextureFramebuffer::new(draw, "render graph test scene")
+        .context("unable to create render graph test target")?;
+    let blurred = DefaultTextureFramebuffer::new(draw, "render graph test blurred")
+        .context("unable to create render graph test target")?;
+    let log = Arc::new(Mutex::new(Vec::<(&str, bool)>::new()));
+
+    let add_query = draw
+        .query({
+            let log = log.clone();
+            move |ctx, _| {
+                let (scene, blurred) = (
+                    RenderTarget::Offscreen(scene),
+                    RenderTarget::Offscreen(blurred),
+                );
+                // added in reverse, the inputs decide the order
+                let pass = |name: &'static str, output| {
+                    let log = log.clone();
+                    RenderPass::new(name, output, move |_, _| {
+                        let complete = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) }
+                            == gl::FRAMEBUFFER_COMPLETE;
+                        log.lock().push((name, complete));
+                    })
+                };
+                let graph = &mut ctx.render_graph;
+                graph.add_pass(pass(POST, RenderTarget::Screen).input(blurred.clone()));
+                graph.add_pass(pass(BLUR, blurred).input(scene.clone()));
+                graph.add_pass(pass(FILL, scene));
+                graph
+                    .pass_names()
+                    .into_iter()
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>()
+            }
+        })
+        .context("unable to query render graph test")?;
+
+    let order_node = node.new_child_leaf("order");
+    let execute_node = node.new_child_leaf("execute");
+    let remove_node = node.new_child_leaf("remove");
+    let export_node = node.new_child_leaf("export");
+    let debug_group_node = node.new_child_leaf("debug_group");
+    let draw = draw.clone_sender();
+    main_ctx.spawn_local(async move {
+        let names = add_query.await?;
+        order_node.update(check_order(&names));
+        let (executed, names, frame_graph) = draw
+            .query(move |ctx, _| {
+                // frames aren't rendered in headless mode, without the scenes
+                // it is fine to render one here
+                ctx.render(None);
+                let frame_graph = ctx.frame_graph();
+                let graph = &mut ctx.render_graph;
+                for name in [FILL, BLUR, POST] {
+                    graph.remove_pass(name);
+                }
+                let names = graph
+                    .pass_names()
+                    .into_iter()
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>();
+                (log.lock().clone(), names, frame_graph)
+            })
+            .context("unable to query render graph test")?
+            .await?;
+        execute_node.update(check_execute(&executed));
+        export_node.update(check_export(&frame_graph));
+        remove_node.update(assert_equals(
+            &names,
+            &vec![SCENE_PASS.to_owned()],
+            "passes left after removal",
+        ));
+        let depths = draw
+            .query(|ctx, _| {
+                let depth = || {
+                    let mut depth = 0;
+                    unsafe { gl::GetIntegerv(gl::DEBUG_GROUP_STACK_DEPTH, &mut depth) };
+                    depth
+                };
+                let before = depth();
+                let inside = ctx.debug_group("outer", |ctx| ctx.debug_group("inner", |_| depth()));
+                let nested = if gl::PushDebugGroup::is_loaded() {
+                    2
+                } else {
+                    0
+                };
+                ((inside - before, depth() - before), nested)
+            })
+            .context("unable to query debug group test")?
+            .await?;
+        debug_group_node.update(assert_equals(
+            &depths.0,
+            &(depths.1, 0),
+            "debug group depth inside and after nested groups",
+        ));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn check_order(names: &[String]) -> TestResult {
+    assert_equals(
+        &names,
+        &[SCENE_PASS, FILL, BLUR, POST].map(str::to_owned).as_slice(),
+        "pass order",
+    )
+}
+
+fn check_execute(executed: &[(&str, bool)]) -> TestResult {
+    assert_equals(
+        &executed.iter().take(3).copied().collect::<Vec<_>>(),
+        &vec![(FILL, true), (BLUR, true), (POST, true)],
+        "executed passes and whether their framebuffer was complete",
+    )
+}
+
+fn check_export(graph: &FrameGraph) -> TestResult {
+    let blur = graph
+        .passes
+        .iter()
+        .find(|pass| pass.name == BLUR)
+        .context("blur pass missing from the frame graph")?;
+    let inputs = blur
+        .inputs
+        .iter()
+        .map(|&input| graph.targets[input].name.as_str())
+        .collect::<Vec<_>>();
+    assert_equals(
+        &inputs,
+        &vec!["render graph test scene"],
+        "blur pass inputs",
+    )?;
+    assert_equals(
+        &graph.targets[blur.output].name.as_str(),
+        &"render graph test blurred",
+        "blur pass output",
+    )?;
+    let post = graph
+        .passes
+        .iter()
+        .find(|pass| pass.name == POST)
+        .context("post pass missing from the frame graph")?;
+    assert_equals(
+        &graph.targets[post.output].name.as_str(),
+        &"screen",
+        "post pass output",
+    )?;
+    let blur_index = graph.passes.iter().position(|pass| pass.name == BLUR);
+    let dot = graph.to_dot();
+    assert_true(
+        blur_index.map_or(false, |i| {
+            dot.contains(&format!("p{i} -> t{};", blur.output))
+        }),
+        "blur pass output edge in the Graphviz graph",
+    )
+}
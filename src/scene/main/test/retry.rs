@@ -0,0 +1,167 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    test::{
+        assert::{assert_equals, assert_true},
+        filter::TestSelection,
+        result::{TestError, TestResult},
+        tree::{LeafOptions, LeafTestNode, ParentTestNode, TreeSettings},
+    },
+};
+
+const LEAF_TIMEOUT: Duration = Duration::from_millis(100);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// after the next attempt started, but before it times out
+const STALE_DELAY: Duration = Duration::from_millis(50);
+/// long enough for every attempt of the sandbox tests
+const CHECK_DELAY: Duration = Duration::from_millis(600);
+
+/// Runs timing out and flaky tests in a sandbox tree, so that their
+/// failures don't fail the run, then checks how they were recorded.
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("retry");
+    if !node.enabled() {
+        return Ok(());
+    }
+    let timeout_node = node.new_child_leaf("timeout");
+    let flaky_node = node.new_child_leaf("flaky");
+    let exhausted_node = node.new_child_leaf("exhausted");
+    let stale_node = node.new_child_leaf("stale");
+    let failed_then_passed_node = node.new_child_leaf("failed_then_passed");
+
+    let sandbox = ParentTestNode::new_root(
+        "sandbox",
+        TreeSettings {
+            selection: TestSelection::default(),
+            leaf_timeout: LEAF_TIMEOUT,
+            on_failure: None,
+        },
+        |_, _| {},
+    );
+    let timed_out = sandbox.new_child_leaf("timed_out");
+    let flaky = sandbox.new_child_leaf_with(
+        "flaky",
+        LeafOptions {
+            retries: 2,
+            ..Default::default()
+        },
+    );
+    let runs = Arc::new(AtomicU32::new(0));
+    flaky.run(main_ctx, move |_, node| {
+        // passes the second time
+        node.update(assert_true(
+            runs.fetch_add(1, Ordering::Relaxed) > 0,
+            "first run fails",
+        ));
+        Ok(())
+    });
+    let exhausted = sandbox.new_child_leaf_with(
+        "exhausted",
+        LeafOptions {
+            retries: 1,
+            ..Default::default()
+        },
+    );
+    // fails without updating, then times out
+    exhausted.run(main_ctx, |_, node| {
+        if node.attempts() == 1 {
+            anyhow::bail!("first run fails");
+        }
+        Ok(())
+    });
+    let stale = sandbox.new_child_leaf_with(
+        "stale",
+        LeafOptions {
+            retries: 1,
+            ..Default::default()
+        },
+    );
+    // the first run passes too late, the second one times out
+    stale.run(main_ctx, |main_ctx, node| {
+        let attempt = node.attempts();
+        if attempt == 1 {
+            let node = node.clone();
+            main_ctx
+                .set_timeout(STALE_DELAY, move |_, _| {
+                    node.update_attempt(attempt, Ok(()));
+                    Ok(())
+                })
+                .context("unable to set timeout for stale result")?;
+            anyhow::bail!("first run fails");
+        }
+        Ok(())
+    });
+    let failed_then_passed = sandbox.new_child_leaf_with(
+        "failed_then_passed",
+        LeafOptions {
+            retries: 1,
+            ..Default::default()
+        },
+    );
+    // the first run fails then passes before the retry starts, the second
+    // one times out
+    failed_then_passed.run(main_ctx, |_, node| {
+        let attempt = node.attempts();
+        if attempt == 1 {
+            node.update(assert_true(false, "first run fails"));
+            node.update_attempt(attempt, Ok(()));
+        }
+        Ok(())
+    });
+
+    let poll = main_ctx
+        .set_interval(POLL_INTERVAL, move |main_ctx, _| {
+            sandbox.poll(main_ctx);
+            Ok(())
+        })
+        .context("unable to poll sandbox tests")?;
+    main_ctx
+        .set_timeout(CHECK_DELAY, move |_, _| {
+            poll.cancel();
+            timeout_node.update(check_error(
+                &timed_out,
+                |e| matches!(e, TestError::Timeout(timeout) if *timeout == LEAF_TIMEOUT),
+            ));
+            flaky_node.update(check_flaky(&flaky));
+            let retries_timed_out = |e: &TestError| {
+                matches!(
+                    e,
+                    TestError::RetriesExhausted { attempts: 2, last }
+                        if matches!(**last, TestError::Timeout(_))
+                )
+            };
+            exhausted_node.update(check_error(&exhausted, retries_timed_out));
+            stale_node.update(check_error(&stale, retries_timed_out));
+            failed_then_passed_node.update(check_error(&failed_then_passed, retries_timed_out));
+            Ok(())
+        })
+        .context("unable to set timeout for retry tests")
+}
+
+fn check_error(leaf: &LeafTestNode, expected: impl Fn(&TestError) -> bool) -> TestResult {
+    match leaf.result.lock().as_ref() {
+        Some(Err(e)) => assert_true(expected(e), format!("unexpected error {e:?}")),
+        result => Err(anyhow::format_err!(
+            "test `{}` finished with {result:?} instead of failing",
+            leaf.full_name()
+        )
+        .into()),
+    }
+}
+
+fn check_flaky(leaf: &LeafTestNode) -> TestResult {
+    assert_true(
+        matches!(*leaf.result.lock(), Some(Ok(()))),
+        "flaky test passed",
+    )?;
+    assert_equals(&leaf.attempts(), &2, "flaky test attempts")
+}
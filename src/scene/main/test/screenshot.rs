@@ -0,0 +1,61 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::Context;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{screenshot, wrappers::texture::ImageData},
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    utils::args::args,
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("screenshot");
+    let capture_node = node.new_child_leaf("capture");
+    let png_node = node.new_child_leaf("png");
+
+    let display_size = main_ctx
+        .channels
+        .draw
+        .query(|context, _| {
+            PhysicalSize::new(
+                context.display_size.width.get(),
+                context.display_size.height.get(),
+            )
+        })
+        .context("unable to query display size")?;
+    let frame = main_ctx
+        .channels
+        .draw
+        .capture_frame()
+        .context("unable to capture frame")?;
+    let path = args().artifacts_dir.join("screenshot").join("frame.png");
+    main_ctx.spawn_local(async move {
+        let (display_size, image) = (display_size.await?, frame.await?);
+        capture_node.update(
+            assert_equals(&image.size, &display_size, "captured frame size").and_then(|_| {
+                assert_true(
+                    image.pixels.chunks_exact(4).all(|pixel| pixel[3] == 255),
+                    "captured frame must be opaque",
+                )
+            }),
+        );
+        png_node.update(check_png(image, &path));
+        Ok(())
+    });
+    Ok(())
+}
+
+fn check_png(image: ImageData, path: &Path) -> TestResult {
+    let pixels = image.pixels.clone();
+    screenshot::save_png(image, path)?;
+    let written = image::open(path)
+        .context("unable to read the written screenshot")?
+        .into_rgba8();
+    assert_equals(&written.into_raw(), &pixels, "written screenshot pixels")
+}
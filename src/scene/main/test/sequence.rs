@@ -0,0 +1,100 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+
+use crate::{
+    exec::{dispatch::EventDispatch, main_ctx::MainContext, server::update::sequence::Sequence},
+    scene::main::RootScene,
+    test::{
+        assert::{assert_equals, assert_greater_equals, assert_unreachable},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    utils::mutex::Mutex,
+};
+
+const STEP: Duration = Duration::from_millis(100);
+
+type Log = Arc<Mutex<Vec<&'static str>>>;
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("sequence");
+
+    let test_node = node.new_child_leaf("steps");
+    let log = Log::default();
+    let flag = Arc::new(AtomicBool::new(false));
+    let start = Instant::now();
+    let sequence = Sequence::new()
+        .wait(STEP)
+        .call(record(&log, "a"))
+        .parallel([
+            Sequence::new().wait(STEP).call(record(&log, "b")),
+            Sequence::new().wait(STEP * 2).call(record(&log, "c")),
+        ])
+        .wait_until(enclose!((flag) move || flag.load(Ordering::Relaxed)))
+        .call(move |_, _| {
+            test_node.update(check_steps(&log.lock(), start.elapsed()));
+            Ok(())
+        });
+    main_ctx
+        .run_sequence(sequence)
+        .context("unable to run sequence")?;
+    main_ctx
+        .set_timeout(STEP * 4, move |_, _| {
+            flag.store(true, Ordering::Relaxed);
+            Ok(())
+        })
+        .context("unable to set sequence flag timeout")?;
+
+    let test_node = node.new_child_leaf("cancel");
+    let id = main_ctx
+        .run_sequence(
+            Sequence::new()
+                .wait(STEP)
+                .call(enclose!((test_node) move |_, _| {
+                    test_node.update(assert_unreachable("cancelled sequence must not run"));
+                    Ok(())
+                })),
+        )
+        .context("unable to run cancelled sequence")?;
+    main_ctx
+        .cancel_sequence(id)
+        .context("unable to cancel sequence")?;
+    main_ctx
+        .set_timeout(STEP * 3, move |_, _| {
+            if !test_node.finished() {
+                test_node.update(Ok(()));
+            }
+            Ok(())
+        })
+        .context("unable to set cancelled sequence timeout")?;
+
+    Ok(())
+}
+
+fn record(log: &Log, name: &'static str) -> impl EventDispatch {
+    let log = log.clone();
+    move |_: &mut MainContext, _: &mut RootScene| {
+        log.lock().push(name);
+        Ok(())
+    }
+}
+
+fn check_steps(log: &[&'static str], elapsed: Duration) -> TestResult {
+    assert_equals(
+        log,
+        &["a", "b", "c"][..],
+        "steps executed before the condition",
+    )?;
+    assert_greater_equals(
+        &elapsed,
+        &(STEP * 4),
+        "the sequence must wait for the condition",
+    )
+}
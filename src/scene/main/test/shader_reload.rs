@@ -0,0 +1,104 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use gl::types::GLuint;
+
+use crate::{
+    events::error::ErrorReport,
+    exec::main_ctx::MainContext,
+    graphics::{shader_watcher::ShaderFiles, wrappers::shader::ProgramHandle},
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    utils::{args::args, mutex::Mutex},
+};
+
+const VERTEX: &str = r#"#version 300 es
+void main() {
+    gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+}
+"#;
+const FRAGMENT: &str = r#"#version 300 es
+precision mediump float;
+out vec4 color;
+void main() {
+    color = vec4(1.0);
+}
+"#;
+const BROKEN_FRAGMENT: &str = r#"#version 300 es
+precision mediump float;
+out vec4 color;
+void main() {
+    color = ;
+}
+"#;
+/// leaves time for the watcher to notice the edit and for the draw server
+/// to recompile the program
+const RELOAD_WAIT: Duration = Duration::from_millis(1500);
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("shader_reload");
+    let broken_node = node.new_child_leaf("broken");
+    let fixed_node = node.new_child_leaf("fixed");
+
+    let dir = args().artifacts_dir.join("shader_reload");
+    std::fs::create_dir_all(&dir).context("unable to create shader reload test directory")?;
+    let files = ShaderFiles::new(dir.join("test.vert"), dir.join("test.frag"));
+    std::fs::write(&files.vertex, VERTEX).context("unable to write test vertex shader")?;
+    std::fs::write(&files.fragment, FRAGMENT).context("unable to write test fragment shader")?;
+    let program = main_ctx
+        .load_program("shader reload test program", files.clone())
+        .context("unable to load test program")?;
+
+    let errors = Arc::new(Mutex::new(0usize));
+    let subscription =
+        main_ctx
+            .event_bus
+            .subscribe(enclose!((errors) move |_, _, report: &ErrorReport| {
+                if report.code == "draw.reload_program" {
+                    *errors.lock() += 1;
+                }
+                Ok(())
+            }));
+
+    main_ctx
+        .set_timeout(Duration::from_millis(100), move |main_ctx, _| {
+            let initial = program_id(main_ctx, &program)?;
+            std::fs::write(&files.fragment, BROKEN_FRAGMENT)
+                .context("unable to write broken fragment shader")?;
+            main_ctx.set_timeout(RELOAD_WAIT, move |main_ctx, _| {
+                let current = program_id(main_ctx, &program)?;
+                broken_node.update(check_broken(*errors.lock(), initial, current));
+                std::fs::write(&files.fragment, FRAGMENT)
+                    .context("unable to write fixed fragment shader")?;
+                main_ctx.set_timeout(RELOAD_WAIT, move |main_ctx, _| {
+                    let _subscription = subscription;
+                    let reloaded = program_id(main_ctx, &program)?;
+                    fixed_node.update(check_fixed(initial, reloaded));
+                    Ok(())
+                })
+            })
+        })
+        .context("unable to set shader reload timeout")?;
+
+    Ok(())
+}
+
+fn program_id(main_ctx: &mut MainContext, program: &ProgramHandle) -> anyhow::Result<GLuint> {
+    let program = program.clone();
+    main_ctx
+        .execute_draw_sync(move |context, _| program.try_get(context).map(|p| *p))?
+        .flatten()
+        .context("test program was not created")
+}
+
+fn check_broken(errors: usize, initial: GLuint, current: GLuint) -> TestResult {
+    assert_equals(&errors, &1, "reported reload errors")?;
+    assert_equals(&current, &initial, "program kept after a failed reload")
+}
+
+fn check_fixed(initial: GLuint, reloaded: GLuint) -> TestResult {
+    assert_true(reloaded != initial, "program swapped after the fix")
+}
@@ -0,0 +1,142 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use anyhow::Context;
+use gl::types::GLuint;
+use glam::{Vec2, Vec4};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{
+        context::DrawContext,
+        wrappers::texture::{ImageData, TextureHandle},
+    },
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    ui::utils::geom::UISize,
+};
+
+use super::texture::read_pixels;
+
+const SIZE: u32 = 16;
+const CLEAR: [u8; 4] = [0, 0, 0, 0];
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+const YELLOW: [u8; 4] = [255, 255, 0, 255];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("shape");
+    let draw = &mut main_ctx.channels.draw;
+    let target = ImageData::new(
+        PhysicalSize::new(SIZE, SIZE),
+        vec![0; (SIZE * SIZE * 4) as usize],
+    )?;
+    let target = TextureHandle::new(draw, "shape test target", target)
+        .context("unable to create shape test target")?;
+
+    let query = draw
+        .query(move |ctx, _| {
+            let previous_size = ctx.ui_size;
+            ctx.ui_size = UISize::new(SIZE as f32, SIZE as f32);
+            let fbo = bind_target(**target.get(ctx));
+
+            let color =
+                |[r, g, b, a]: [u8; 4]| Vec4::new(r as f32, g as f32, b as f32, a as f32) / 255.0;
+            ctx.draw_rounded_rect(Vec2::ZERO, Vec2::new(16.0, 8.0), 2.0, color(RED));
+            ctx.draw_circle(Vec2::new(8.0, 12.0), 3.0, color(GREEN));
+            ctx.draw_arc(Vec2::new(8.0, 12.0), 4.5, [0.0, PI], 1.0, color(YELLOW));
+            ctx.draw_line(Vec2::new(0.0, 12.0), Vec2::new(4.0, 12.0), 2.0, color(BLUE));
+            // an L, concave at (14, 10)
+            ctx.draw_polygon(
+                &[
+                    Vec2::new(13.0, 9.0),
+                    Vec2::new(16.0, 9.0),
+                    Vec2::new(16.0, 10.0),
+                    Vec2::new(14.0, 10.0),
+                    Vec2::new(14.0, 13.0),
+                    Vec2::new(13.0, 13.0),
+                ],
+                color(WHITE),
+            );
+            let pending = ctx.shapes.len();
+            let triangles = ctx.flush_shapes();
+            let flushed = ctx.shapes.is_empty();
+            let pixels = read_pixels(&target.get(ctx), PhysicalSize::new(SIZE, SIZE));
+
+            unbind_target(ctx, fbo);
+            ctx.ui_size = previous_size;
+            ((pending, triangles, flushed), pixels)
+        })
+        .context("unable to query shape test")?;
+
+    let batching_node = node.new_child_leaf("batching");
+    let render_node = node.new_child_leaf("render");
+    main_ctx.spawn_local(async move {
+        let ((pending, triangles, flushed), pixels) = query.await?;
+        batching_node.update(check_batching(pending, triangles, flushed));
+        render_node.update(check_render(&pixels));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+pub fn bind_target(target: GLuint) -> GLuint {
+    let mut fbo = 0;
+    unsafe {
+        gl::GenFramebuffers(1, &mut fbo);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            target,
+            0,
+        );
+        gl::Viewport(0, 0, SIZE as _, SIZE as _);
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+    }
+    fbo
+}
+
+pub fn unbind_target(ctx: &DrawContext, fbo: GLuint) {
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::DeleteFramebuffers(1, &fbo);
+        gl::Viewport(
+            0,
+            0,
+            ctx.display_size.width.get() as _,
+            ctx.display_size.height.get() as _,
+        );
+    }
+}
+
+fn check_batching(pending: usize, triangles: usize, flushed: bool) -> TestResult {
+    assert_equals(&triangles, &pending, "triangles drawn by the flush")?;
+    assert_equals(&(pending > 0), &true, "shapes were queued")?;
+    assert_equals(&flushed, &true, "shapes left after flush")
+}
+
+fn check_render(pixels: &[u8]) -> TestResult {
+    // UI coordinates of the pixels, rows are read bottom to top
+    let pixel = |x: u32, y: u32| {
+        let start = (((SIZE - 1 - y) * SIZE + x) * 4) as usize;
+        &pixels[start..start + 4]
+    };
+    let expected = [
+        ((0, 0), CLEAR, "rounded corner"),
+        ((8, 4), RED, "rounded rectangle"),
+        ((8, 12), GREEN, "circle"),
+        ((12, 12), YELLOW, "arc"),
+        ((1, 11), BLUE, "line"),
+        ((13, 11), WHITE, "polygon"),
+        ((15, 11), CLEAR, "concave part of the polygon"),
+    ];
+    for ((x, y), color, msg) in expected {
+        assert_equals(&pixel(x, y), &&color[..], msg)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{
+        main_ctx::MainContext,
+        runner::{RunnerId, MAIN_RUNNER_ID},
+        server::{audio, draw::ServerSendChannelExt, update, GameServerSendChannel, ServerKind},
+    },
+    graphics::debug_callback::gl_error_count,
+    test::{
+        assert::{assert_equals, assert_false},
+        input::InputDriver,
+        result::{TestError, TestResult},
+        tree::{LeafTestNode, ParentTestNode},
+    },
+    utils::args::args,
+};
+
+const STEP: Duration = Duration::from_millis(50);
+// execute closures sent to every server each step
+const FLOOD_SIZE: usize = 256;
+// how long the servers have to answer the final queries
+const DEADLOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const FREQUENCIES: [f64; 5] = [30.0, 60.0, 144.0, 240.0, 1000.0];
+const WINDOW_SIZES: [(u32, u32); 4] = [(640, 480), (1280, 720), (800, 800), (320, 240)];
+const RUNNERS: [RunnerId; 4] = [0, 1, 2, MAIN_RUNNER_ID];
+// the server layout set up in `main`
+const INITIAL_LOCATIONS: [(ServerKind, RunnerId); 4] = [
+    (ServerKind::Audio, 0),
+    (ServerKind::Update, 0),
+    (ServerKind::Network, 0),
+    (ServerKind::Draw, 1),
+];
+
+/// Sequence numbers of the execute closures sent to a server, checked on
+/// the server side to detect lost or reordered messages.
+#[derive(Default)]
+struct Counter {
+    sent: AtomicU64,
+    received: AtomicU64,
+    out_of_order: AtomicBool,
+}
+
+struct Soak {
+    end: Instant,
+    locations: HashMap<ServerKind, RunnerId>,
+    /// thread runners that have been constructed, only those accept a new
+    /// frequency
+    thread_runners: BTreeSet<RunnerId>,
+    counters: HashMap<ServerKind, Arc<Counter>>,
+    migrations: usize,
+    errors: Vec<String>,
+    gl_errors: usize,
+    window_size: PhysicalSize<u32>,
+    node: Arc<ParentTestNode>,
+}
+
+impl Counter {
+    fn next(self: &Arc<Self>) -> impl FnOnce() + Send + 'static {
+        let seq = self.sent.fetch_add(1, Ordering::Relaxed);
+        let slf = self.clone();
+        move || {
+            if slf.received.fetch_add(1, Ordering::Relaxed) != seq {
+                slf.out_of_order.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn check(&self) -> TestResult {
+        assert_equals(
+            &self.received.load(Ordering::Relaxed),
+            &self.sent.load(Ordering::Relaxed),
+            "every message must be received",
+        )?;
+        assert_false(
+            self.out_of_order.load(Ordering::Relaxed),
+            "messages must be received in order",
+        )?;
+        Ok(())
+    }
+}
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let duration = match args().soak {
+        Some(duration) => Duration::from_secs(duration),
+        None => return Ok(()),
+    };
+
+    let node = node.new_child_parent_tagged("soak", &["slow"]);
+    if !node.enabled() {
+        return Ok(());
+    }
+
+    let soak = Soak {
+        end: Instant::now() + duration,
+        locations: INITIAL_LOCATIONS.into_iter().collect(),
+        thread_runners: INITIAL_LOCATIONS
+            .into_iter()
+            .map(|(_, runner)| runner)
+            .filter(|&runner| runner != MAIN_RUNNER_ID)
+            .collect(),
+        counters: [ServerKind::Audio, ServerKind::Draw, ServerKind::Update]
+            .into_iter()
+            .map(|kind| (kind, Arc::default()))
+            .collect(),
+        migrations: 0,
+        errors: Vec::new(),
+        gl_errors: gl_error_count(),
+        window_size: main_ctx.display.get_size(),
+        node,
+    };
+    tracing::info!("running soak test for {duration:?}");
+    soak.schedule(main_ctx)
+}
+
+/// Resizes the window, windowless displays are sent the `Resized` event
+/// they would never get otherwise.
+fn resize(main_ctx: &MainContext, size: PhysicalSize<u32>) -> anyhow::Result<()> {
+    match main_ctx.display.get_winit_window() {
+        Some(window) => window.set_inner_size(size),
+        None => {
+            InputDriver::new(main_ctx).resize(size)?;
+        }
+    }
+    Ok(())
+}
+
+impl Soak {
+    fn schedule(self, main_ctx: &mut MainContext) -> anyhow::Result<()> {
+        main_ctx
+            .set_timeout(STEP, move |main_ctx, _| self.step(main_ctx))
+            .context("unable to schedule soak test step")
+    }
+
+    fn step(mut self, main_ctx: &mut MainContext) -> anyhow::Result<()> {
+        if Instant::now() >= self.end {
+            return self.finish(main_ctx);
+        }
+
+        self.flood(main_ctx)?;
+        let mut rng = thread_rng();
+        match rng.gen_range(0..3) {
+            0 => {
+                let kind = *ServerKind::ALL.choose(&mut rng).expect("non-empty");
+                let from = self.locations[&kind];
+                let to = *RUNNERS
+                    .iter()
+                    .filter(|&&runner| runner != from)
+                    .collect::<Vec<_>>()
+                    .choose(&mut rng)
+                    .expect("non-empty");
+                self.migrate(main_ctx, kind, to);
+            }
+
+            1 => {
+                let runners: Vec<_> = self.thread_runners.iter().copied().collect();
+                let runner = *runners.choose(&mut rng).expect("non-empty");
+                let frequency = *FREQUENCIES.choose(&mut rng).expect("non-empty");
+                if let Err(e) = main_ctx.executor.set_frequency(runner, frequency) {
+                    self.errors
+                        .push(format!("unable to set frequency of runner {runner}: {e:#}"));
+                }
+            }
+
+            _ => {
+                let (width, height) = *WINDOW_SIZES.choose(&mut rng).expect("non-empty");
+                resize(main_ctx, PhysicalSize::new(width, height))?;
+            }
+        }
+
+        self.schedule(main_ctx)
+    }
+
+    fn migrate(&mut self, main_ctx: &mut MainContext, kind: ServerKind, to: RunnerId) {
+        let from = self.locations[&kind];
+        match main_ctx.executor.move_server(from, to, kind) {
+            Ok(()) => {
+                self.locations.insert(kind, to);
+                if to != MAIN_RUNNER_ID {
+                    self.thread_runners.insert(to);
+                }
+                self.migrations += 1;
+            }
+            Err(e) => self.errors.push(format!("{e:#}")),
+        }
+    }
+
+    fn flood(&self, main_ctx: &mut MainContext) -> anyhow::Result<()> {
+        let channels = &main_ctx.channels;
+        for _ in 0..FLOOD_SIZE {
+            let f = self.counters[&ServerKind::Audio].next();
+            channels.audio.send(audio::RecvMsg::Execute(Box::new(
+                move |_: &mut audio::Server| f(),
+            )))?;
+            let f = self.counters[&ServerKind::Update].next();
+            channels.update.send(update::RecvMsg::Execute(Box::new(
+                move |_: &mut update::Server| f(),
+            )))?;
+            let f = self.counters[&ServerKind::Draw].next();
+            channels.draw.execute(move |_, _| f())?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, main_ctx: &mut MainContext) -> anyhow::Result<()> {
+        tracing::info!("soak test done, {} migrations", self.migrations);
+        resize(main_ctx, self.window_size)?;
+        for (kind, runner) in INITIAL_LOCATIONS {
+            if self.locations[&kind] != runner {
+                self.migrate(main_ctx, kind, runner);
+            }
+        }
+
+        let node = self.node;
+        let errors = self.errors;
+        node.new_child_leaf("executor").update(assert_equals(
+            &errors,
+            &Vec::new(),
+            "executor operations must not fail",
+        ));
+
+        // the draw server only reports GL errors while drawing, check them
+        // after the final query to make sure the last frames are included
+        let gl_errors = self.gl_errors;
+        for (kind, counter) in self.counters {
+            let leaf = node.new_child_leaf(format!("{kind:?}_messages").to_lowercase());
+            let query = match kind {
+                ServerKind::Audio => main_ctx.channels.audio.query(|_| ()),
+                ServerKind::Update => main_ctx.channels.update.query(|_| ()),
+                _ => main_ctx.channels.draw.query(|_, _| ()),
+            }
+            .context("unable to query server")?;
+
+            main_ctx.spawn_local(enclose!((leaf) async move {
+                let result: TestResult = match query.await {
+                    Ok(()) => counter.check(),
+                    Err(e) => Err(e.into()),
+                };
+                if !leaf.finished() {
+                    leaf.update(result);
+                }
+                Ok(())
+            }));
+            Self::deadlock_check(main_ctx, leaf)?;
+        }
+
+        let gl_node = node.new_child_leaf("gl_errors");
+        main_ctx
+            .set_timeout(DEADLOCK_TIMEOUT, move |_, _| {
+                gl_node.update(assert_equals(
+                    &gl_error_count(),
+                    &gl_errors,
+                    "no GL errors must be reported",
+                ));
+                Ok(())
+            })
+            .context("unable to set GL error check timeout")
+    }
+
+    fn deadlock_check(main_ctx: &mut MainContext, leaf: Arc<LeafTestNode>) -> anyhow::Result<()> {
+        main_ctx
+            .set_timeout(DEADLOCK_TIMEOUT, move |_, _| {
+                if !leaf.finished() {
+                    leaf.update(Err(TestError::GenericError(anyhow::format_err!(
+                        "server didn't answer within {DEADLOCK_TIMEOUT:?}"
+                    ))));
+                }
+                Ok(())
+            })
+            .context("unable to set deadlock timeout")
+    }
+}
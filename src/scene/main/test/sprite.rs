@@ -0,0 +1,274 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use gl::types::GLuint;
+use glam::{Vec2, Vec4};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{
+        context::DrawContext,
+        sprite_renderer::{Margins, NinePatchMargins, Sprite},
+        wrappers::texture::{ImageData, TextureFilter, TextureHandle, TextureOptions, TextureWrap},
+    },
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    ui::utils::geom::{UIPos, UIRect, UISize},
+};
+
+use super::texture::read_pixels;
+
+const SIZE: u32 = 4;
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("sprite");
+    let options = TextureOptions {
+        min_filter: TextureFilter::Nearest,
+        mag_filter: TextureFilter::Nearest,
+        wrap: TextureWrap::ClampToEdge,
+        mipmaps: false,
+        ..Default::default()
+    };
+    let draw = &mut main_ctx.channels.draw;
+    let white = ImageData::new(PhysicalSize::new(1, 1), vec![255; 4])?.options(options);
+    let atlas = TextureHandle::new(draw, "sprite test atlas", white.clone())
+        .context("unable to create sprite test atlas")?;
+    let other = TextureHandle::new(draw, "sprite test other atlas", white)
+        .context("unable to create sprite test atlas")?;
+    let target = ImageData::new(
+        PhysicalSize::new(SIZE, SIZE),
+        vec![0; (SIZE * SIZE * 4) as usize],
+    )?
+    .options(options);
+    let target = TextureHandle::new(draw, "sprite test target", target)
+        .context("unable to create sprite test target")?;
+
+    let query = draw
+        .query(move |ctx, _| {
+            let atlas = **atlas.get(ctx);
+            let other = **other.get(ctx);
+            let previous_size = ctx.ui_size;
+            ctx.ui_size = UISize::new(SIZE as f32, SIZE as f32);
+            let fbo = bind_target(**target.get(ctx));
+
+            // invisible, only counted
+            for i in 0..5000 {
+                ctx.draw_sprite(
+                    atlas,
+                    &Sprite::new(Vec2::splat(i as f32 % 4.0), Vec2::ONE).color(Vec4::ZERO),
+                );
+            }
+            let pending = ctx.sprites.len();
+            let batching = (pending, ctx.flush_sprites(), ctx.sprites.is_empty());
+
+            let half = SIZE as f32 / 2.0;
+            ctx.draw_sprite(
+                atlas,
+                &Sprite::new(Vec2::splat(half), Vec2::splat(SIZE as f32))
+                    .color(Vec4::new(1.0, 0.0, 0.0, 1.0)),
+            );
+            // top right pixel, the batch of `other` is drawn after the first one
+            ctx.draw_sprite(
+                other,
+                &Sprite::new(Vec2::new(SIZE as f32 - 0.5, 0.5), Vec2::ONE)
+                    .color(Vec4::new(0.0, 0.0, 1.0, 1.0)),
+            );
+            // left half, rotated by half a turn so that the corners are swapped
+            ctx.draw_sprite(
+                atlas,
+                &Sprite::new(Vec2::new(half / 2.0, half), Vec2::new(half, SIZE as f32))
+                    .color(Vec4::new(0.0, 1.0, 0.0, 1.0))
+                    .rotation(std::f32::consts::PI),
+            );
+            let draw_calls = ctx.flush_sprites();
+            let pixels = read_pixels(&target.get(ctx), PhysicalSize::new(SIZE, SIZE));
+
+            unbind_target(ctx, fbo);
+            ctx.ui_size = previous_size;
+            (batching, (draw_calls, pixels))
+        })
+        .context("unable to query sprite test")?;
+
+    let batching_node = node.new_child_leaf("batching");
+    let render_node = node.new_child_leaf("render");
+    main_ctx.spawn_local(async move {
+        let ((pending, draw_calls, flushed), (render_calls, pixels)) = query.await?;
+        batching_node.update(check_batching(pending, draw_calls, flushed));
+        render_node.update(check_render(render_calls, &pixels));
+        Ok(())
+    });
+
+    // red corners, green edges and a blue center
+    let draw = &mut main_ctx.channels.draw;
+    let patch = [
+        RED, GREEN, RED, //
+        GREEN, BLUE, GREEN, //
+        RED, GREEN, RED,
+    ]
+    .concat();
+    let patch = ImageData::new(PhysicalSize::new(3, 3), patch)?.options(options);
+    let patch = TextureHandle::new(draw, "nine patch test texture", patch)
+        .context("unable to create nine patch test texture")?;
+    let target = ImageData::new(
+        PhysicalSize::new(SIZE, SIZE),
+        vec![0; (SIZE * SIZE * 4) as usize],
+    )?
+    .options(options);
+    let target = TextureHandle::new(draw, "nine patch test target", target)
+        .context("unable to create nine patch test target")?;
+    let query = draw
+        .query(move |ctx, _| {
+            let previous_size = ctx.ui_size;
+            ctx.ui_size = UISize::new(SIZE as f32, SIZE as f32);
+            let fbo = bind_target(*target.get(ctx));
+            let margins = NinePatchMargins {
+                source: Margins::uniform(1.0 / 3.0),
+                dest: Margins::uniform(1.0),
+            };
+            ctx.draw_nine_patch(
+                *patch.get(ctx),
+                [Vec2::ZERO, Vec2::ONE],
+                &margins,
+                UIRect::new(UIPos::ZERO, ctx.ui_size),
+                Vec4::ONE,
+            );
+            ctx.flush_sprites();
+            let pixels = read_pixels(&target.get(ctx), PhysicalSize::new(SIZE, SIZE));
+            unbind_target(ctx, fbo);
+            ctx.ui_size = previous_size;
+            pixels
+        })
+        .context("unable to query nine patch test")?;
+    let nine_patch_node = node.new_child_leaf("nine_patch");
+    main_ctx.spawn_local(async move {
+        nine_patch_node.update(check_nine_patch(&query.await?));
+        Ok(())
+    });
+
+    let draw = &mut main_ctx.channels.draw;
+    let white = ImageData::new(PhysicalSize::new(1, 1), vec![255; 4])?.options(options);
+    let atlas = TextureHandle::new(draw, "instanced sprite test atlas", white)
+        .context("unable to create instanced sprite test atlas")?;
+    let target = ImageData::new(
+        PhysicalSize::new(SIZE, SIZE),
+        vec![0; (SIZE * SIZE * 4) as usize],
+    )?
+    .options(options);
+    let target = TextureHandle::new(draw, "instanced sprite test target", target)
+        .context("unable to create instanced sprite test target")?;
+    let query = draw
+        .query(move |ctx, _| {
+            let previous_size = ctx.ui_size;
+            ctx.ui_size = UISize::new(SIZE as f32, SIZE as f32);
+            let fbo = bind_target(*target.get(ctx));
+            // the sprites of the render test, shifted back by the transform
+            let half = SIZE as f32 / 2.0;
+            let offset = Vec2::splat(10.0);
+            let sprites = [
+                Sprite::new(Vec2::splat(half), Vec2::splat(SIZE as f32))
+                    .color(Vec4::new(1.0, 0.0, 0.0, 1.0)),
+                Sprite::new(Vec2::new(SIZE as f32 - 0.5, 0.5), Vec2::ONE)
+                    .color(Vec4::new(0.0, 0.0, 1.0, 1.0)),
+                Sprite::new(Vec2::new(half / 2.0, half), Vec2::new(half, SIZE as f32))
+                    .color(Vec4::new(0.0, 1.0, 0.0, 1.0))
+                    .rotation(std::f32::consts::PI),
+            ]
+            .map(|sprite| Sprite {
+                center: sprite.center + offset,
+                ..sprite
+            });
+            ctx.transform_stack.push();
+            ctx.transform_stack.translate(UIPos::from(-offset));
+            ctx.draw_sprites_instanced(*atlas.get(ctx), &sprites);
+            ctx.transform_stack.pop();
+            let pixels = read_pixels(&target.get(ctx), PhysicalSize::new(SIZE, SIZE));
+            unbind_target(ctx, fbo);
+            ctx.ui_size = previous_size;
+            pixels
+        })
+        .context("unable to query instanced sprite test")?;
+    let instanced_node = node.new_child_leaf("instanced");
+    main_ctx.spawn_local(async move {
+        instanced_node.update(check_render_pixels(&query.await?));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn bind_target(target: GLuint) -> GLuint {
+    let mut fbo = 0;
+    unsafe {
+        gl::GenFramebuffers(1, &mut fbo);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            target,
+            0,
+        );
+        gl::Viewport(0, 0, SIZE as _, SIZE as _);
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+    }
+    fbo
+}
+
+fn unbind_target(ctx: &DrawContext, fbo: GLuint) {
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::DeleteFramebuffers(1, &fbo);
+        gl::Viewport(
+            0,
+            0,
+            ctx.display_size.width.get() as _,
+            ctx.display_size.height.get() as _,
+        );
+    }
+}
+
+fn check_batching(pending: usize, draw_calls: usize, flushed: bool) -> TestResult {
+    assert_equals(&pending, &5000, "pending sprites")?;
+    // one batch, split because of the 16-bit indices
+    assert_equals(&draw_calls, &2, "draw calls")?;
+    assert_equals(&flushed, &true, "sprites left after flush")
+}
+
+fn check_nine_patch(pixels: &[u8]) -> TestResult {
+    for (i, pixel) in pixels.chunks_exact(4).enumerate() {
+        let (x, y) = (i as u32 % SIZE, i as u32 / SIZE);
+        let border = |v| v == 0 || v == SIZE - 1;
+        let expected = match (border(x), border(y)) {
+            (true, true) => RED,
+            (false, false) => BLUE,
+            _ => GREEN,
+        };
+        assert_equals(&pixel, &&expected[..], format!("pixel at {x}, {y}"))?;
+    }
+    Ok(())
+}
+
+fn check_render(draw_calls: usize, pixels: &[u8]) -> TestResult {
+    assert_equals(&draw_calls, &2, "draw calls of two atlases")?;
+    check_render_pixels(pixels)
+}
+
+fn check_render_pixels(pixels: &[u8]) -> TestResult {
+    // rows are read bottom to top
+    for (i, pixel) in pixels.chunks_exact(4).enumerate() {
+        let (x, y) = (i as u32 % SIZE, SIZE - 1 - i as u32 / SIZE);
+        let expected = if (x, y) == (SIZE - 1, 0) {
+            BLUE
+        } else if x < SIZE / 2 {
+            GREEN
+        } else {
+            RED
+        };
+        assert_equals(&pixel, &&expected[..], format!("pixel at {x}, {y}"))?;
+    }
+    Ok(())
+}
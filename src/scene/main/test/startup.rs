@@ -0,0 +1,77 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    test::{
+        assert::{assert_equals, assert_greater_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    utils::startup::{self, StartupPhase},
+};
+
+/// Phases timed by `main`, in the order they end, the test tree is built
+/// inside the root scene.
+const PHASES: &[&str] = &[
+    "display",
+    "draw server",
+    "audio server",
+    "update server",
+    "network server",
+    "runner threads",
+    "main context",
+    "test tree",
+    "root scene",
+];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("startup");
+    let phases_node = node.new_child_leaf("phases");
+    let first_frame_node = node.new_child_leaf("first_frame");
+
+    // the root scene phase ends after the tests are initialized
+    main_ctx
+        .set_timeout(Duration::ZERO, move |_, _| {
+            phases_node.update(check_phases(&startup::phases()));
+            Ok(())
+        })
+        .context("unable to set timeout for startup phases test")?;
+    main_ctx
+        .set_timeout(Duration::from_secs(1), move |_, _| {
+            first_frame_node.update(check_first_frame(
+                startup::first_frame(),
+                &startup::phases(),
+            ));
+            Ok(())
+        })
+        .context("unable to set timeout for first frame test")?;
+    Ok(())
+}
+
+fn check_phases(phases: &[StartupPhase]) -> TestResult {
+    let names = phases
+        .iter()
+        .filter(|phase| !phase.lazy)
+        .map(|phase| phase.name.as_ref())
+        .collect::<Vec<_>>();
+    assert_equals(names.as_slice(), PHASES, "startup phases")
+}
+
+fn check_first_frame(first_frame: Option<Duration>, phases: &[StartupPhase]) -> TestResult {
+    assert_true(first_frame.is_some(), "first frame reported")?;
+    // the phases before the root scene ran one after the other, the draw
+    // server may finish a frame right before the root scene phase ends
+    let total = phases
+        .iter()
+        .filter(|phase| !phase.lazy)
+        .take_while(|phase| phase.name != "test tree")
+        .map(|phase| phase.duration)
+        .sum::<Duration>();
+    assert_greater_equals(
+        &first_frame.unwrap_or_default(),
+        &total,
+        "time to first frame",
+    )
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    utils::state_machine::{
+        StateHooks, StateMachine,
+        TransitionEvent::{self, Enter, Exit},
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum GameState {
+    Menu,
+    Loading,
+    InGame,
+    Playing,
+    Paused,
+}
+
+#[derive(Default)]
+struct Context {
+    progress: u32,
+    entered_in_game: u32,
+}
+
+fn new_machine() -> anyhow::Result<StateMachine<GameState, Context>> {
+    use GameState::*;
+    let mut machine = StateMachine::new()
+        .with_state(Menu, None, StateHooks::new())?
+        .with_state(
+            Loading,
+            None,
+            StateHooks::new()
+                .on_enter(|ctx: &mut Context| ctx.progress = 0)
+                .on_update(|ctx: &mut Context| {
+                    ctx.progress += 50;
+                    (ctx.progress >= 100).then_some(Playing)
+                }),
+        )?
+        .with_state(
+            InGame,
+            None,
+            StateHooks::new().on_enter(|ctx: &mut Context| ctx.entered_in_game += 1),
+        )?
+        .with_state(Playing, Some(InGame), StateHooks::new())?
+        .with_state(Paused, Some(InGame), StateHooks::new())?;
+    machine.set_tracing(true);
+    Ok(machine)
+}
+
+pub fn test(_: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("state_machine");
+    node.new_child_leaf("flat_transitions")
+        .update(test_flat_transitions());
+    node.new_child_leaf("hierarchical_transitions")
+        .update(test_hierarchical_transitions());
+    node.new_child_leaf("update_transitions")
+        .update(test_update_transitions());
+    Ok(())
+}
+
+fn assert_trace(
+    machine: &mut StateMachine<GameState, Context>,
+    expected: &[TransitionEvent<GameState>],
+    msg: &'static str,
+) -> TestResult {
+    assert_equals(machine.take_trace().as_slice(), expected, msg)
+}
+
+fn test_flat_transitions() -> TestResult {
+    use GameState::*;
+    let mut machine = new_machine()?;
+    let mut ctx = Context::default();
+
+    machine.transition(&mut ctx, Menu)?;
+    assert_trace(&mut machine, &[Enter(Menu)], "initial transition")?;
+    machine.transition(&mut ctx, Loading)?;
+    assert_trace(
+        &mut machine,
+        &[Exit(Menu), Enter(Loading)],
+        "menu -> loading",
+    )?;
+    machine.transition(&mut ctx, Loading)?;
+    assert_trace(
+        &mut machine,
+        &[Exit(Loading), Enter(Loading)],
+        "self transition",
+    )?;
+    machine.stop(&mut ctx);
+    assert_trace(&mut machine, &[Exit(Loading)], "stop")?;
+    assert_equals(&machine.current(), &None, "no state after stop")?;
+    Ok(())
+}
+
+fn test_hierarchical_transitions() -> TestResult {
+    use GameState::*;
+    let mut machine = new_machine()?;
+    let mut ctx = Context::default();
+
+    machine.transition(&mut ctx, Playing)?;
+    assert_trace(
+        &mut machine,
+        &[Enter(InGame), Enter(Playing)],
+        "entering a nested state enters its parent first",
+    )?;
+    machine.transition(&mut ctx, Paused)?;
+    assert_trace(
+        &mut machine,
+        &[Exit(Playing), Enter(Paused)],
+        "sibling transition keeps the parent active",
+    )?;
+    assert_equals(&machine.is_in(InGame), &true, "paused is in game")?;
+    assert_equals(&ctx.entered_in_game, &1, "parent entered once")?;
+    machine.transition(&mut ctx, Menu)?;
+    assert_trace(
+        &mut machine,
+        &[Exit(Paused), Exit(InGame), Enter(Menu)],
+        "leaving a nested state exits its parent last",
+    )?;
+    Ok(())
+}
+
+fn test_update_transitions() -> TestResult {
+    use GameState::*;
+    let mut machine = new_machine()?;
+    let mut ctx = Context::default();
+
+    machine.transition(&mut ctx, Loading)?;
+    machine.take_trace();
+    assert_equals(&machine.update(&mut ctx)?, &false, "still loading")?;
+    assert_equals(&machine.update(&mut ctx)?, &true, "done loading")?;
+    assert_trace(
+        &mut machine,
+        &[Exit(Loading), Enter(InGame), Enter(Playing)],
+        "loading -> playing",
+    )?;
+    assert_equals(&machine.current(), &Some(Playing), "playing after loading")?;
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use gl::types::GLuint;
+use glam::{Vec2, Vec4};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    enclose,
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{
+        context::DrawContext,
+        text::{Font, ATLAS_INITIAL_HEIGHT},
+    },
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::utils::geom::UISize,
+};
+
+const TEXT: &str = "Hi,\nAVA";
+const TEXT_SIZE: f32 = 16.0;
+const LARGE_TEXT_SIZE: f32 = 100.0;
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("text");
+    node.new_child_leaf("missing_font").update(assert_true(
+        Font::load("nonexistent.ttf").is_err(),
+        "loading a missing font should fail",
+    ));
+
+    let layout_node = node.new_child_leaf("layout");
+    let render_node = node.new_child_leaf("render");
+    let grow_node = node.new_child_leaf("grow");
+    let font = match Font::bundled() {
+        Ok(font) => font,
+        Err(e) => {
+            for leaf in [layout_node, render_node, grow_node] {
+                leaf.update(Err(anyhow::format_err!("{e:?}").into()));
+            }
+            return Ok(());
+        }
+    };
+    layout_node.update(test_layout(&font));
+
+    let query = main_ctx
+        .channels
+        .draw
+        .query(enclose!((font) move |ctx, _| {
+            let width = ctx.display_size.width.get();
+            let height = ctx.display_size.height.get();
+            let previous_size = ctx.ui_size;
+            // one UI unit per pixel
+            ctx.ui_size = UISize::new(width as f32, height as f32);
+            let (fbo, target) = bind_target(width, height);
+
+            ctx.draw_text(&font, TEXT, Vec2::ZERO, TEXT_SIZE, Vec4::ONE);
+            ctx.flush_sprites();
+            let covered = covered_pixels(width, height);
+            let rasterized = !ctx.glyphs.is_empty();
+
+            let printable = (' '..='~').collect::<String>();
+            ctx.draw_text(
+                &font,
+                &printable,
+                Vec2::new(0.0, height as f32),
+                LARGE_TEXT_SIZE,
+                Vec4::ONE,
+            );
+            ctx.flush_sprites();
+
+            unbind_target(ctx, fbo, target);
+            ctx.ui_size = previous_size;
+            (covered, rasterized, ctx.glyphs.len(), ctx.glyphs.size())
+        }))
+        .context("unable to query text test")?;
+
+    main_ctx.spawn_local(async move {
+        let (covered, rasterized, glyphs, atlas_size) = query.await?;
+        render_node.update(check_render(&font, &covered, rasterized));
+        grow_node.update(check_grow(glyphs, atlas_size));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn test_layout(font: &Font) -> TestResult {
+    let line_height = font.line_height(TEXT_SIZE);
+    let glyphs = font.layout(TEXT, TEXT_SIZE);
+    // the newline is not a glyph
+    assert_equals(&glyphs.len(), &6, "glyph count")?;
+    assert_equals(&glyphs[3].pen.x, &0.0, "pen of the second line")?;
+    assert_true(
+        (glyphs[3].pen.y - glyphs[0].pen.y - line_height).abs() < 1e-3,
+        "distance between baselines should be the line height",
+    )?;
+    assert_true(
+        glyphs[1].pen.x > glyphs[0].pen.x,
+        "glyphs should advance to the right",
+    )?;
+    let size = font.measure(TEXT, TEXT_SIZE);
+    assert_true(
+        (size.y - line_height * 2.0).abs() < 1e-3,
+        "text should be two lines high",
+    )?;
+    assert_true(size.x > glyphs[5].pen.x, "text width")?;
+    assert_equals(&font.measure("", TEXT_SIZE).x, &0.0, "width of empty text")?;
+    let tabbed = font.layout("\tA", TEXT_SIZE);
+    assert_true(tabbed[0].pen.x > 0.0, "tabs should be expanded")
+}
+
+fn check_render(font: &Font, covered: &[(u32, u32)], rasterized: bool) -> TestResult {
+    assert_true(rasterized, "no glyph was rasterized")?;
+    assert_true(!covered.is_empty(), "no text was drawn")?;
+    let size = font.measure(TEXT, TEXT_SIZE);
+    // glyphs may overhang their advance by a pixel or so
+    let bounds = size + Vec2::splat(2.0);
+    match covered
+        .iter()
+        .find(|(x, y)| *x as f32 > bounds.x || *y as f32 > bounds.y)
+    {
+        Some(pixel) => {
+            Err(anyhow::format_err!("pixel {pixel:?} outside of the measured text {size}").into())
+        }
+        None => Ok(()),
+    }
+}
+
+fn check_grow(glyphs: usize, atlas_size: PhysicalSize<u32>) -> TestResult {
+    // the printable ASCII characters at the large size, plus the small ones
+    assert_true(glyphs > 95, format!("{glyphs} glyphs in the atlas"))?;
+    assert_true(
+        atlas_size.height > ATLAS_INITIAL_HEIGHT,
+        format!("glyph atlas didn't grow, {atlas_size:?}"),
+    )
+}
+
+fn bind_target(width: u32, height: u32) -> (GLuint, GLuint) {
+    let (mut fbo, mut texture) = (0, 0);
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA8 as _,
+            width as _,
+            height as _,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            std::ptr::null(),
+        );
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        gl::GenFramebuffers(1, &mut fbo);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            texture,
+            0,
+        );
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+    }
+    (fbo, texture)
+}
+
+fn unbind_target(ctx: &DrawContext, fbo: GLuint, texture: GLuint) {
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::DeleteFramebuffers(1, &fbo);
+        gl::DeleteTextures(1, &texture);
+        let color = ctx.clear_color;
+        gl::ClearColor(color.x, color.y, color.z, color.w);
+    }
+}
+
+/// UI positions of the pixels of the bound framebuffer with some coverage.
+fn covered_pixels(width: u32, height: u32) -> Vec<(u32, u32)> {
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    unsafe {
+        gl::ReadPixels(
+            0,
+            0,
+            width as _,
+            height as _,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut _,
+        );
+    }
+    pixels
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|(_, pixel)| pixel[3] > 0)
+        .map(|(i, _)| (i as u32 % width, height - 1 - i as u32 / width))
+        .collect()
+}
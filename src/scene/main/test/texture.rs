@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use gl::types::GLint;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::wrappers::texture::{
+        ImageData, Texture, TextureFilter, TextureFormat, TextureHandle, TextureOptions,
+        TextureWrap,
+    },
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+};
+
+#[rustfmt::skip]
+const PIXELS: [u8; 16] = [
+    255, 0, 0, 255,     0, 255, 0, 255,
+    0, 0, 255, 255,     255, 255, 255, 255,
+];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("texture");
+    let size = PhysicalSize::new(2, 2);
+
+    node.new_child_leaf("invalid_size").update(assert_true(
+        ImageData::new(size, vec![0; 15]).is_err(),
+        "pixel data must match the image size",
+    ));
+
+    let image = ImageData::new(size, PIXELS.to_vec())?.options(TextureOptions {
+        format: TextureFormat::Rgba8,
+        min_filter: TextureFilter::Nearest,
+        mag_filter: TextureFilter::Nearest,
+        wrap: TextureWrap::ClampToEdge,
+        mipmaps: false,
+    });
+    let texture = TextureHandle::new(&mut main_ctx.channels.draw, "upload test texture", image)
+        .context("unable to create upload test texture")?;
+    let image = ImageData::new(size, PIXELS.to_vec())?;
+    let mipmapped = TextureHandle::new(&mut main_ctx.channels.draw, "mipmap test texture", image)
+        .context("unable to create mipmap test texture")?;
+
+    let test_node = node.new_child_leaf("upload");
+    let query = main_ctx
+        .channels
+        .draw
+        .query(move |context, _| -> anyhow::Result<(Vec<u8>, GLint)> {
+            let texture = texture
+                .try_get(context)
+                .context("upload test texture was not created")?;
+            Ok((read_pixels(&texture, size), min_filter(&texture)))
+        })
+        .context("unable to query upload test texture")?;
+    main_ctx.spawn_local(async move {
+        test_node.update(query.await?.map_err(Into::into).and_then(
+            |(pixels, filter)| -> TestResult {
+                assert_equals(&pixels.as_slice(), &&PIXELS[..], "uploaded pixels")?;
+                assert_equals(&filter, &(gl::NEAREST as GLint), "min filter")
+            },
+        ));
+        Ok(())
+    });
+
+    let test_node = node.new_child_leaf("mipmaps");
+    let query = main_ctx
+        .channels
+        .draw
+        .query(move |context, _| mipmapped.try_get(context).map(|t| min_filter(&t)))
+        .context("unable to query mipmap test texture")?;
+    main_ctx.spawn_local(async move {
+        test_node.update(assert_equals(
+            &query.await?,
+            &Some(gl::LINEAR_MIPMAP_LINEAR as GLint),
+            "min filter of a mipmapped texture",
+        ));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+/// Reads back the pixels of a 2D RGBA texture through a temporary
+/// framebuffer.
+pub fn read_pixels(texture: &Texture, size: PhysicalSize<u32>) -> Vec<u8> {
+    let mut pixels = vec![0u8; size.width as usize * size.height as usize * 4];
+    unsafe {
+        let mut fbo = 0;
+        gl::GenFramebuffers(1, &mut fbo);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            **texture,
+            0,
+        );
+        gl::ReadPixels(
+            0,
+            0,
+            size.width as _,
+            size.height as _,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut _,
+        );
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::DeleteFramebuffers(1, &fbo);
+    }
+    pixels
+}
+
+fn min_filter(texture: &Texture) -> GLint {
+    let mut filter = 0;
+    texture.bind();
+    unsafe { gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, &mut filter) };
+    texture.unbind();
+    filter
+}
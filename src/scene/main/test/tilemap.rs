@@ -0,0 +1,167 @@
+use std::{fmt::Write, fs, path::Path, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use glam::Vec2;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    graphics::{
+        camera::Camera2D,
+        context::DrawContext,
+        tilemap::{tiled, TileMap, TileMapRenderer},
+    },
+    scene::{Scene, SceneContainer},
+    test::{
+        assert::{assert_equals, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::utils::geom::{UIPos, UIRect, UISize},
+    utils::mutex::Mutex,
+};
+
+/// In tiles, three chunks wide.
+const MAP_SIZE: (u32, u32) = (40, 2);
+const TILE_SIZE: u32 = 8;
+
+/// A map of a half speed background and a ground layer, drawn through an
+/// off screen viewport so that the other tests reading the screen don't
+/// see it.
+pub struct TileMapTest {
+    renderer: Mutex<TileMapRenderer>,
+    camera: Mutex<Camera2D>,
+    /// chunks drawn by the last frame
+    drawn: Mutex<Option<usize>>,
+}
+
+impl TileMapTest {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        main_ctx: &mut MainContext,
+        node: &Arc<ParentTestNode>,
+    ) -> anyhow::Result<SceneContainer> {
+        let node = node.new_child_parent("tilemap");
+        if !node.enabled() {
+            return Ok(SceneContainer::new());
+        }
+        let load_node = node.new_child_leaf("load");
+        let culling_node = node.new_child_leaf("culling");
+        let parallax_node = node.new_child_leaf("parallax");
+
+        let dir = std::env::temp_dir().join(format!("tilemap-{}", std::process::id()));
+        let map = write_map(&dir).and_then(|path| tiled::load(&path));
+        let map = match map {
+            Ok(map) => map,
+            Err(e) => {
+                fs::remove_dir_all(&dir).ok();
+                return Err(e.context("unable to load test map"));
+            }
+        };
+        let (renderer, loaded) = TileMapRenderer::new(main_ctx, &map)
+            .context("unable to create test tilemap renderer")?;
+        let checked = check_load(&map, &renderer);
+        // the tileset image is read in the background
+        main_ctx.spawn_local(async move {
+            let loaded = loaded.await;
+            fs::remove_dir_all(&dir).ok();
+            load_node.update(checked.and_then(|()| {
+                loaded
+                    .context("unable to load test tileset")
+                    .map_err(Into::into)
+            }));
+            Ok(())
+        });
+
+        let mut camera = Camera2D::new(UIRect::new(
+            UIPos::new(-1000.0, -1000.0),
+            UISize::new(32.0, 16.0),
+        ));
+        // the left of the first chunk of both layers
+        camera.position = Vec2::new(16.0, 8.0);
+        let slf = Arc::new(Self {
+            renderer: Mutex::new(renderer),
+            camera: Mutex::new(camera),
+            drawn: Mutex::new(None),
+        });
+
+        let scene = slf.clone();
+        main_ctx
+            .set_timeout(Duration::from_millis(200), move |main_ctx, _| {
+                culling_node.update(assert_equals(
+                    &*scene.drawn.lock(),
+                    &Some(2),
+                    "chunks drawn",
+                ));
+                // past the end of the ground, the background trails behind
+                // and shows its last chunk
+                scene.camera.lock().position.x = 600.0;
+                *scene.drawn.lock() = None;
+                main_ctx.set_timeout(Duration::from_millis(200), move |_, _| {
+                    parallax_node.update(assert_equals(
+                        &*scene.drawn.lock(),
+                        &Some(1),
+                        "chunks drawn past the ground",
+                    ));
+                    Ok(())
+                })?;
+                Ok(())
+            })
+            .context("unable to set timeout for tilemap tests")?;
+
+        let mut container = SceneContainer::new();
+        container.push_arc(slf);
+        Ok(container)
+    }
+}
+
+/// Writes the tileset image and the map into `dir`, returns the path of
+/// the map.
+fn write_map(dir: &Path) -> anyhow::Result<std::path::PathBuf> {
+    fs::create_dir_all(dir)?;
+    image::RgbaImage::from_pixel(TILE_SIZE * 2, TILE_SIZE, image::Rgba([255; 4]))
+        .save(dir.join("tiles.png"))?;
+    let (width, height) = MAP_SIZE;
+    let data = vec!["1"; (width * height) as usize].join(",");
+    let mut map = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+        <map orientation="orthogonal" width="{width}" height="{height}" tilewidth="{TILE_SIZE}" tileheight="{TILE_SIZE}">
+          <tileset firstgid="1" name="test tiles" tilewidth="{TILE_SIZE}" tileheight="{TILE_SIZE}" tilecount="2" columns="2">
+            <image source="tiles.png" width="{}" height="{TILE_SIZE}"/>
+          </tileset>"#,
+        TILE_SIZE * 2
+    );
+    for (name, parallax) in [("background", 0.5), ("ground", 1.0)] {
+        write!(
+            map,
+            r#"
+          <layer name="{name}" width="{width}" height="{height}" parallaxx="{parallax}">
+            <data encoding="csv">{data}</data>
+          </layer>"#
+        )?;
+    }
+    map.push_str("\n        </map>\n");
+    let path = dir.join("map.tmx");
+    fs::write(&path, map)?;
+    Ok(path)
+}
+
+fn check_load(map: &TileMap, renderer: &TileMapRenderer) -> TestResult {
+    assert_equals(&map.layers.len(), &2, "layer count")?;
+    assert_equals(&map.size(), &Vec2::new(320.0, 16.0), "map size")?;
+    assert_true(
+        map.layers[0].parallax == Vec2::new(0.5, 1.0),
+        "background parallax",
+    )?;
+    assert_equals(&renderer.chunk_count(), &6, "chunk count")
+}
+
+impl Scene for TileMapTest {
+    fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
+        let camera = *self.camera.lock();
+        let drawn = ctx.draw_tilemap(&self.renderer.lock(), &camera);
+        // nothing is drawn until the buffers and textures are created
+        if drawn > 0 {
+            *self.drawn.lock() = Some(drawn);
+        }
+    }
+}
@@ -1,39 +1,46 @@
-use std::{
-    ops::Sub,
-    sync::Arc,
-    time::{Duration, Instant},
-};
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Context;
 
 use crate::{
-    exec::main_ctx::MainContext,
+    exec::{
+        lockstep::ServerClock,
+        main_ctx::MainContext,
+        server::{update, GameServer},
+    },
     test::{
         assert::{assert_greater_equals, assert_less_equals},
         result::TestResult,
-        tree::ParentTestNode,
+        tree::{LeafTestNode, ParentTestNode},
+    },
+    utils::{
+        clock::{Clock, VirtualClock},
+        mutex::Mutex,
     },
 };
 
 const MAX_DELAY: Duration = Duration::from_millis(100);
+/// step of the virtual clock between two update server runs
+const VIRTUAL_STEP: Duration = Duration::from_millis(10);
 
 pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
     let node = node.new_child_parent("set_timeout_delay");
 
     let mut test = |timeout: Duration, name: &'static str| -> anyhow::Result<()> {
         let test_node = node.new_child_leaf(name);
-        let now = Instant::now();
 
-        fn do_test(elapsed: Duration, timeout: Duration) -> TestResult {
-            assert_greater_equals(&elapsed, &timeout, "elapsed must be greater than timeout")?;
-            let delay = elapsed.sub(timeout);
-            assert_less_equals(&delay, &MAX_DELAY, "more timeout delay than expected")?;
-            Ok(())
+        fn do_test(node: &LeafTestNode, timeout: Duration) -> TestResult {
+            assert_greater_equals(
+                &node.elapsed(),
+                &timeout,
+                "elapsed must be greater than timeout",
+            )?;
+            node.assert_completed_within(timeout + MAX_DELAY)
         }
 
         main_ctx
             .set_timeout(timeout, move |_, _| {
-                test_node.update(do_test(now.elapsed(), timeout));
+                test_node.update(do_test(&test_node, timeout));
                 Ok(())
             })
             .context("unable to set timeout")?;
@@ -46,5 +53,71 @@ pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::R
     test(Duration::from_secs(3), "3s")?;
     test(Duration::from_secs(5), "5s")?;
     test(Duration::from_secs(10), "10s")?;
+
+    test_virtual(main_ctx, node).context("unable to run virtual clock timeout tests")
+}
+
+/// Same timeouts, set on a separate update server driven by a virtual clock,
+/// so they fire right away and exactly on the first step past their end.
+fn test_virtual(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("virtual");
+    let clock = VirtualClock::new();
+    let (mut server, channel) = update::Server::with_clock(
+        main_ctx.event_loop_proxy.clone(),
+        main_ctx.task_executor.clone(),
+        ServerClock::Virtual(clock.clone()),
+        0,
+    );
+
+    let timeouts = [
+        (Duration::from_millis(100), "100ms"),
+        (Duration::from_secs(1), "1s"),
+        (Duration::from_millis(1500), "1.5s"),
+        (Duration::from_secs(10), "10s"),
+    ];
+    let mut pending = Vec::new();
+    for (timeout, name) in timeouts {
+        let test_node = node.new_child_leaf(name);
+        let fired_at = Arc::new(Mutex::new(None));
+        let id = main_ctx
+            .dispatch_list
+            .push(enclose!((fired_at) move |_, _| {
+                let fired_at = fired_at.lock().unwrap_or_default();
+                test_node.update(do_test_virtual(Duration::from_secs_f64(fired_at), timeout));
+                Ok(())
+            }));
+        channel
+            .set_timeout(timeout, id)
+            .context("unable to set virtual timeout")?;
+        pending.push((id, fired_at));
+    }
+
+    server.run(false, 0.0)?;
+    while !pending.is_empty() {
+        clock.advance(VIRTUAL_STEP.as_secs_f64());
+        server.run(false, 0.0)?;
+        pending.retain(|(id, fired_at)| {
+            let fired = !server.timeouts.contains_key(id);
+            if fired {
+                *fired_at.lock() = Some(clock.now());
+            }
+            !fired
+        });
+    }
     Ok(())
 }
+
+fn do_test_virtual(fired_at: Duration, timeout: Duration) -> TestResult {
+    // the clock is advanced in floating point steps
+    const EPSILON: Duration = Duration::from_micros(1);
+    assert_greater_equals(
+        &(fired_at + EPSILON),
+        &timeout,
+        "virtual timeout fired early",
+    )?;
+    assert_less_equals(
+        &fired_at,
+        &(timeout + VIRTUAL_STEP + EPSILON),
+        "virtual timeout fired more than a step late",
+    )
+}
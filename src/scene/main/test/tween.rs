@@ -0,0 +1,103 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    exec::{
+        main_ctx::MainContext,
+        server::update::tween::{Easing, Tween},
+    },
+    test::{
+        assert::{assert_equals, assert_true, assert_unreachable},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::{
+        utils::geom::{UIPos, UIRect, UISize},
+        Widget,
+    },
+    utils::mutex::Mutex,
+};
+
+use super::ui::TestWidgetBuilder;
+
+const DURATION: Duration = Duration::from_millis(200);
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("tween");
+
+    let test_node = node.new_child_leaf("value");
+    let values = Arc::new(Mutex::new(Vec::new()));
+    main_ctx
+        .tween(
+            Tween::new(0.0f32, 1.0, DURATION).easing(Easing::QuadOut),
+            enclose!((values) move |value| values.lock().push(value)),
+            move |_, _| {
+                test_node.update(check_values(&values.lock()));
+                Ok(())
+            },
+        )
+        .context("unable to start value tween")?;
+
+    let test_node = node.new_child_leaf("widget_bounds");
+    let widget =
+        TestWidgetBuilder::new().build(0, test_node.full_name().to_owned(), false, false, false);
+    let target = UIRect::new(UIPos::new(100.0, 50.0), UISize::new(200.0, 100.0));
+    main_ctx
+        .tween(
+            Tween::new(UIRect::ZERO, target, DURATION).delay(DURATION / 2),
+            enclose!((widget) move |bounds| widget.set_bounds(bounds)),
+            move |_, _| {
+                test_node.update(check_bounds(widget.get_bounds(), target));
+                Ok(())
+            },
+        )
+        .context("unable to start widget bounds tween")?;
+
+    let test_node = node.new_child_leaf("cancel");
+    let value = Arc::new(Mutex::new(0.0f32));
+    let id = main_ctx
+        .tween(
+            Tween::new(0.0f32, 1.0, DURATION),
+            enclose!((value) move |v| *value.lock() = v),
+            enclose!((test_node) move |_, _| {
+                test_node.update(assert_unreachable("cancelled tween must not complete"));
+                Ok(())
+            }),
+        )
+        .context("unable to start cancelled tween")?;
+    main_ctx
+        .cancel_tween(id)
+        .context("unable to cancel tween")?;
+    main_ctx
+        .set_timeout(DURATION * 2, move |_, _| {
+            if !test_node.finished() {
+                test_node.update(assert_true(
+                    *value.lock() < 1.0,
+                    "cancelled tween must not reach its end value",
+                ));
+            }
+            Ok(())
+        })
+        .context("unable to set cancelled tween timeout")?;
+
+    Ok(())
+}
+
+fn check_values(values: &[f32]) -> TestResult {
+    assert_true(!values.is_empty(), "the tween must be applied")?;
+    assert_true(
+        values.windows(2).all(|w| w[0] <= w[1]),
+        "eased values must not decrease",
+    )?;
+    assert_true(
+        values.iter().all(|v| (0.0..=1.0).contains(v)),
+        "eased values must stay in range",
+    )?;
+    assert_equals(&values.last(), &Some(&1.0), "final value")
+}
+
+fn check_bounds(bounds: UIRect, target: UIRect) -> TestResult {
+    assert_equals(&bounds.pos, &target.pos, "final widget position")?;
+    assert_equals(&bounds.size, &target.size, "final widget size")
+}
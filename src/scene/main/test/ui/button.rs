@@ -0,0 +1,141 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use winit::event::{ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode};
+
+use crate::{
+    exec::main_ctx::MainContext,
+    scene::main::test::ui::TestWidgetBuilder,
+    test::{
+        assert::{assert_equals, assert_equals_err},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::{
+        controls::button::{Button, ButtonState},
+        event::{UICursorEvent, UIFocusEvent, UIPropagatingEvent},
+        utils::geom::{UIPos, UIRect, UISize},
+        EventContext, UISizeConstraint, Widget,
+    },
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("button_test");
+    let leaf = node.new_child_leaf("layout");
+    leaf.update(test_layout(leaf.full_name()));
+    node.new_child_leaf("click").update(test_click(main_ctx));
+    Ok(())
+}
+
+fn test_layout(name: &str) -> TestResult {
+    let content: Arc<dyn Widget> = TestWidgetBuilder::new().pref_size(50.0, 20.0).build(
+        0,
+        name.to_owned(),
+        false,
+        false,
+        false,
+    );
+    let button = Button::new(Some(content.clone()));
+
+    // 8 units of padding on the sides, 4 above and below
+    let size = button.layout(&UISizeConstraint::new(
+        UISize::ZERO,
+        UISize::new(200.0, 200.0),
+    ));
+    assert_equals_err(&size, &UISize::new(66.0, 28.0), "button size")?;
+    let size = button.layout(&UISizeConstraint::exact(UISize::new(100.0, 40.0)));
+    assert_equals_err(&size, &UISize::new(100.0, 40.0), "stretched button size")?;
+    let expected = UIRect::new(UIPos::new(25.0, 10.0), UISize::new(50.0, 20.0));
+    assert_equals_err(&content.get_bounds(), &expected, "content centered")
+}
+
+#[allow(deprecated)]
+fn key(key: VirtualKeyCode) -> UIFocusEvent {
+    UIFocusEvent::KeyboardInput(KeyboardInput {
+        scancode: 0,
+        state: ElementState::Pressed,
+        virtual_keycode: Some(key),
+        modifiers: ModifiersState::empty(),
+    })
+}
+
+fn mouse(state: ElementState) -> UIPropagatingEvent {
+    UIPropagatingEvent::MouseInput {
+        state,
+        button: MouseButton::Left,
+    }
+}
+
+fn test_click(main_ctx: &mut MainContext) -> TestResult {
+    let clicks = Arc::new(AtomicUsize::new(0));
+    let button = Arc::new(Button::new(None).on_click({
+        let clicks = clicks.clone();
+        move |_| {
+            clicks.fetch_add(1, Ordering::Relaxed);
+        }
+    }));
+    button.layout(&UISizeConstraint::exact(UISize::new(100.0, 40.0)));
+    let id = button.id();
+    let mut ctx = EventContext { main_ctx };
+    let count = || clicks.load(Ordering::Relaxed);
+
+    assert_equals(&button.state(), &ButtonState::Normal, "initial state")?;
+    button
+        .clone()
+        .handle_cursor_event(&mut ctx, UICursorEvent::CursorEntered);
+    assert_equals(&button.state(), &ButtonState::Hovered, "hovered")?;
+    button
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Pressed));
+    assert_equals(&button.state(), &ButtonState::Pressed, "pressed")?;
+    assert_equals(&count(), &0, "no click before the release")?;
+    button
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Released));
+    assert_equals(&count(), &1, "clicked")?;
+    ctx.main_ctx.release_focus_if(|focused| focused == id);
+    assert_equals(
+        &button.state(),
+        &ButtonState::Hovered,
+        "hovered after click",
+    )?;
+
+    // released outside of the button
+    button
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Pressed));
+    button
+        .clone()
+        .handle_cursor_event(&mut ctx, UICursorEvent::CursorExited);
+    button
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Released));
+    assert_equals(&count(), &1, "no click released outside")?;
+
+    button
+        .clone()
+        .handle_focus_event(&mut ctx, key(VirtualKeyCode::Return));
+    button
+        .clone()
+        .handle_focus_event(&mut ctx, key(VirtualKeyCode::A));
+    assert_equals(&count(), &2, "clicked with the keyboard")?;
+
+    button.set_enabled(false);
+    button
+        .clone()
+        .handle_cursor_event(&mut ctx, UICursorEvent::CursorEntered);
+    button
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Pressed));
+    button
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Released));
+    button
+        .clone()
+        .handle_focus_event(&mut ctx, key(VirtualKeyCode::Space));
+    ctx.main_ctx.release_focus_if(|focused| focused == id);
+    assert_equals(&button.state(), &ButtonState::Disabled, "disabled")?;
+    assert_equals(&count(), &2, "disabled button not clicked")
+}
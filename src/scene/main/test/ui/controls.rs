@@ -0,0 +1,204 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use winit::event::{ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode};
+
+use crate::{
+    exec::main_ctx::MainContext,
+    scene::main::test::ui::TestWidgetBuilder,
+    test::{
+        assert::{assert_equals, assert_equals_err},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::{
+        controls::{checkbox::Checkbox, radio_group::RadioGroup, slider::Slider},
+        event::{UICursorEvent, UIFocusEvent, UIPropagatingEvent},
+        utils::geom::{UIPos, UISize},
+        EventContext, UISizeConstraint, Widget,
+    },
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("controls_test");
+    node.new_child_leaf("slider").update(test_slider(main_ctx));
+    let leaf = node.new_child_leaf("checkbox");
+    leaf.update(test_checkbox(main_ctx, leaf.full_name()));
+    let leaf = node.new_child_leaf("radio_group");
+    leaf.update(test_radio_group(main_ctx, leaf.full_name()));
+    Ok(())
+}
+
+#[allow(deprecated)]
+fn key(key: VirtualKeyCode) -> UIFocusEvent {
+    UIFocusEvent::KeyboardInput(KeyboardInput {
+        scancode: 0,
+        state: ElementState::Pressed,
+        virtual_keycode: Some(key),
+        modifiers: ModifiersState::empty(),
+    })
+}
+
+fn mouse(state: ElementState) -> UIPropagatingEvent {
+    UIPropagatingEvent::MouseInput {
+        state,
+        button: MouseButton::Left,
+    }
+}
+
+fn test_slider(main_ctx: &mut MainContext) -> TestResult {
+    let changes = Arc::new(AtomicUsize::new(0));
+    let slider = Arc::new(Slider::new(0.0, 10.0, 5.0).step(1.0).on_change({
+        let changes = changes.clone();
+        move |_, _| {
+            changes.fetch_add(1, Ordering::Relaxed);
+        }
+    }));
+    // 16 units of thumb at the ends, the track is 100 units long
+    slider.layout(&UISizeConstraint::exact(UISize::new(116.0, 20.0)));
+    let id = slider.id();
+    let mut ctx = EventContext { main_ctx };
+
+    slider
+        .clone()
+        .handle_focus_event(&mut ctx, key(VirtualKeyCode::Right));
+    assert_equals_err(&slider.value(), &6.0, "stepped right")?;
+    slider
+        .clone()
+        .handle_focus_event(&mut ctx, key(VirtualKeyCode::Home));
+    assert_equals_err(&slider.value(), &0.0, "moved to the start")?;
+    slider
+        .clone()
+        .handle_focus_event(&mut ctx, key(VirtualKeyCode::Left));
+    assert_equals_err(&slider.value(), &0.0, "clamped to the range")?;
+    assert_equals(
+        &changes.load(Ordering::Relaxed),
+        &2,
+        "unchanged not notified",
+    )?;
+
+    slider
+        .clone()
+        .handle_cursor_event(&mut ctx, UICursorEvent::CursorMoved(UIPos::new(38.0, 10.0)));
+    slider
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Pressed));
+    assert_equals_err(&slider.value(), &3.0, "pressed on the track")?;
+    slider
+        .clone()
+        .handle_cursor_event(&mut ctx, UICursorEvent::CursorMoved(UIPos::new(78.0, 10.0)));
+    assert_equals_err(&slider.value(), &7.0, "dragged and snapped")?;
+    slider
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Released));
+    slider
+        .clone()
+        .handle_cursor_event(&mut ctx, UICursorEvent::CursorMoved(UIPos::new(8.0, 10.0)));
+    ctx.main_ctx.release_focus_if(|focused| focused == id);
+    assert_equals_err(&slider.value(), &7.0, "not dragged after the release")?;
+
+    let reversed = Slider::new(10.0, 0.0, 15.0);
+    assert_equals_err(&reversed.value(), &10.0, "reversed range swapped")
+}
+
+fn test_checkbox(main_ctx: &mut MainContext, name: &str) -> TestResult {
+    let label: Arc<dyn Widget> = TestWidgetBuilder::new().pref_size(50.0, 10.0).build(
+        0,
+        name.to_owned(),
+        false,
+        false,
+        false,
+    );
+    let checkbox = Arc::new(Checkbox::new(Some(label)));
+    // 16 units of box and 6 of spacing before the label
+    let size = checkbox.layout(&UISizeConstraint::new(
+        UISize::ZERO,
+        UISize::new(200.0, 200.0),
+    ));
+    assert_equals_err(&size, &UISize::new(72.0, 16.0), "checkbox size")?;
+
+    let id = checkbox.id();
+    let mut ctx = EventContext { main_ctx };
+    checkbox
+        .clone()
+        .handle_cursor_event(&mut ctx, UICursorEvent::CursorEntered);
+    checkbox
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Pressed));
+    checkbox
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Released));
+    assert_equals(&checkbox.checked(), &true, "checked by a click")?;
+    checkbox
+        .clone()
+        .handle_focus_event(&mut ctx, key(VirtualKeyCode::Space));
+    assert_equals(&checkbox.checked(), &false, "unchecked with the keyboard")?;
+
+    checkbox.set_enabled(false);
+    checkbox
+        .clone()
+        .handle_focus_event(&mut ctx, key(VirtualKeyCode::Space));
+    ctx.main_ctx.release_focus_if(|focused| focused == id);
+    assert_equals(&checkbox.checked(), &false, "disabled checkbox not toggled")
+}
+
+fn test_radio_group(main_ctx: &mut MainContext, name: &str) -> TestResult {
+    let options = (0..3)
+        .map(|i| {
+            TestWidgetBuilder::new().pref_size(40.0, 20.0).build(
+                i,
+                name.to_owned(),
+                false,
+                false,
+                false,
+            ) as Arc<dyn Widget>
+        })
+        .collect();
+    let last_selected = Arc::new(AtomicUsize::new(usize::MAX));
+    let group = Arc::new(RadioGroup::new(options).on_change({
+        let last_selected = last_selected.clone();
+        move |_, index| last_selected.store(index, Ordering::Relaxed)
+    }));
+    // rows of 20 units separated by 4
+    let size = group.layout(&UISizeConstraint::new(
+        UISize::ZERO,
+        UISize::new(200.0, 200.0),
+    ));
+    assert_equals_err(&size, &UISize::new(62.0, 68.0), "radio group size")?;
+
+    let id = group.id();
+    let mut ctx = EventContext { main_ctx };
+    assert_equals(&group.selected(), &None, "nothing selected initially")?;
+    group
+        .clone()
+        .handle_cursor_event(&mut ctx, UICursorEvent::CursorMoved(UIPos::new(5.0, 30.0)));
+    group
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Pressed));
+    group
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Released));
+    assert_equals(&group.selected(), &Some(1), "clicked option")?;
+    group
+        .clone()
+        .handle_focus_event(&mut ctx, key(VirtualKeyCode::Down));
+    group
+        .clone()
+        .handle_focus_event(&mut ctx, key(VirtualKeyCode::Down));
+    ctx.main_ctx.release_focus_if(|focused| focused == id);
+    assert_equals(&group.selected(), &Some(2), "selection stops at the end")?;
+    assert_equals(
+        &last_selected.load(Ordering::Relaxed),
+        &2,
+        "notified of the selection",
+    )?;
+
+    let empty = Arc::new(RadioGroup::new(Vec::new()));
+    assert_equals(
+        &empty.handle_focus_event(&mut ctx, key(VirtualKeyCode::Tab)),
+        &Some(key(VirtualKeyCode::Tab)),
+        "focus events left to an empty group's parent",
+    )
+}
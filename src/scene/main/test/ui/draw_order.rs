@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::{
+    assert_log_eq_test,
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{context::DrawContext, draw_queue::SortKey},
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    ui::{
+        containers::stack::Stack,
+        utils::geom::{UIPos, UIRect, UISize},
+        Alignment, HorizontalAlignment, UISizeConstraint, VerticalAlignment, Widget,
+    },
+};
+
+use super::GenericTestWidgetBuilder;
+
+/// (test id, z-index, batch key) in submission order
+const WIDGETS: [(usize, i32, u64); 5] = [(1, 1, 2), (2, 0, 5), (3, 1, 1), (4, 0, 5), (5, -1, 9)];
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_leaf("draw_order");
+    let name = node.full_name().to_owned();
+
+    let stack = Arc::new(Stack::new());
+    for (test_id, z_index, batch) in WIDGETS {
+        let name = name.clone();
+        let widget = GenericTestWidgetBuilder::new(test_id, ())
+            .layout(|slf, size| {
+                slf.bounds.lock().size = size.min;
+                size.min
+            })
+            .draw(move |slf, ctx| {
+                let name = name.clone();
+                let test_id = slf.test_id;
+                ctx.queue_draw(SortKey::new(z_index, batch), move |ctx, transform| {
+                    let offset = transform.translation;
+                    ctx.test_capture
+                        .record(name, format!("{test_id} at {} {}", offset.x, offset.y));
+                });
+            })
+            .build();
+        stack.push_arc(
+            widget,
+            Alignment::new(HorizontalAlignment::Left, VerticalAlignment::Top),
+        );
+    }
+    stack.layout(&UISizeConstraint::exact(UISize::new(100.0, 100.0)));
+    stack.set_bounds(UIRect::new(
+        UIPos::new(10.0, 20.0),
+        UISize::new(100.0, 100.0),
+    ));
+
+    main_ctx
+        .channels
+        .draw
+        .execute(move |ctx, _| {
+            stack.draw(ctx);
+            let queued = ctx.draw_queue.len();
+            let flushed = ctx.flush_draw_queue();
+            node.update(check_order(ctx, &name, queued, flushed));
+        })
+        .context("unable to send draw order test to draw server")?;
+
+    Ok(())
+}
+
+fn check_order(ctx: &mut DrawContext, name: &str, queued: usize, flushed: usize) -> TestResult {
+    assert_equals(&queued, &WIDGETS.len(), "queued draws")?;
+    assert_equals(&flushed, &WIDGETS.len(), "flushed draws")?;
+    assert_equals(&ctx.draw_queue.len(), &0, "draws left after flush")?;
+    let log = ctx.test_capture.take_text(name);
+    // sorted by z-index, then batch key, ties keep the submission order
+    assert_log_eq_test!(
+        log,
+        "5 at 10 20\n2 at 10 20\n4 at 10 20\n3 at 10 20\n1 at 10 20",
+        "draw order"
+    )
+}
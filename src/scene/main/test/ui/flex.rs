@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    scene::main::test::ui::TestWidgetBuilder,
+    test::{
+        assert::{assert_equals, assert_equals_err},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::{
+        containers::flex::{CrossAlignment, Flex, MainAlignment},
+        utils::geom::{UIPos, UIRect, UISize},
+        LayoutAxis, Padding, UISizeConstraint, Widget,
+    },
+};
+
+pub fn test(_: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("flex_test");
+    let leaf = node.new_child_leaf("weights");
+    leaf.update(test_weights(leaf.full_name()));
+    let leaf = node.new_child_leaf("main_alignment");
+    leaf.update(test_main_alignment(leaf.full_name()));
+    let leaf = node.new_child_leaf("stretch");
+    leaf.update(test_stretch(leaf.full_name()));
+    Ok(())
+}
+
+fn test_widget(i: usize, name: &str, width: f32, height: f32) -> Arc<dyn Widget> {
+    TestWidgetBuilder::new()
+        .pref_size(width, height)
+        .build(i, name.to_owned(), false, false, false)
+}
+
+/// Pushes test widgets of the given sizes, flex weights and alignments.
+fn push_children(
+    flex: &Flex,
+    name: &str,
+    children: &[(f32, f32, f32, Option<CrossAlignment>)],
+) -> Vec<Arc<dyn Widget>> {
+    children
+        .iter()
+        .enumerate()
+        .map(|(i, &(width, height, weight, alignment))| {
+            let widget = test_widget(i, name, width, height);
+            flex.push_arc(widget.clone(), weight, alignment);
+            widget
+        })
+        .collect()
+}
+
+#[rustfmt::skip]
+fn check_layout(
+    case: &str,
+    flex: &Flex,
+    widgets: &[Arc<dyn Widget>],
+    constraints: UISizeConstraint,
+    expected_size: (f32, f32),
+    expected_bounds: &[(f32, f32, f32, f32)],
+) -> TestResult {
+    let size = flex.layout(&constraints);
+    assert_equals_err(&size, &UISize::new(expected_size.0, expected_size.1), format!("{case}: container size"))?;
+    for (i, (widget, &(x, y, w, h))) in widgets.iter().zip(expected_bounds).enumerate() {
+        let expected = UIRect::new(UIPos::new(x, y), UISize::new(w, h));
+        assert_equals_err(&widget.get_bounds(), &expected, format!("{case}: child (index: {i}) bounds mismatch"))?;
+    }
+    Ok(())
+}
+
+#[rustfmt::skip]
+fn test_weights(name: &str) -> TestResult {
+    let widgets = [
+        test_widget(0, name, 100.0, 50.0),
+        test_widget(1, name, 50.0, 20.0),
+        test_widget(2, name, 50.0, 80.0),
+    ];
+    let flex = Flex::row()
+        .spacing(10.0)
+        .padding(Padding::uniform(5.0))
+        .child(widgets[0].clone())
+        .flex_child(widgets[1].clone(), 1.0)
+        .flex_child(widgets[2].clone(), 2.0);
+
+    // 990 - 100 - 2 * 10 = 870 shared 1:2 by the flex children
+    check_layout(
+        "weights",
+        &flex,
+        &widgets,
+        UISizeConstraint::new(UISize::ZERO, UISize::new(1000.0, 200.0)),
+        (1000.0, 90.0),
+        &[
+            (5.0, 5.0, 100.0, 50.0),
+            (115.0, 5.0, 290.0, 20.0),
+            (415.0, 5.0, 580.0, 80.0),
+        ],
+    )?;
+
+    let min_width = flex.min_intrinsic_size(LayoutAxis::Horizontal, f32::INFINITY);
+    assert_equals(&min_width, &230.0, "min intrinsic width")?;
+    let min_height = flex.min_intrinsic_size(LayoutAxis::Vertical, f32::INFINITY);
+    assert_equals(&min_height, &90.0, "min intrinsic height")
+}
+
+#[rustfmt::skip]
+fn test_main_alignment(name: &str) -> TestResult {
+    let flex = Flex::column().cross_alignment(CrossAlignment::Center);
+    let widgets = push_children(&flex, name, &[
+        (100.0, 50.0, 0.0, None),
+        (50.0, 100.0, 0.0, None),
+        (150.0, 50.0, 0.0, None),
+    ]);
+
+    // 200 of free space in a 400 high column
+    for (alignment, [y0, y1, y2]) in [
+        (MainAlignment::Start, [0.0, 50.0, 150.0]),
+        (MainAlignment::End, [200.0, 250.0, 350.0]),
+        (MainAlignment::Center, [100.0, 150.0, 250.0]),
+        (MainAlignment::SpaceBetween, [0.0, 150.0, 350.0]),
+        (MainAlignment::SpaceAround, [100.0 / 3.0, 150.0, 950.0 / 3.0]),
+        (MainAlignment::SpaceEvenly, [50.0, 150.0, 300.0]),
+    ] {
+        flex.set_main_alignment(alignment);
+        check_layout(
+            &format!("{alignment:?}"),
+            &flex,
+            &widgets,
+            UISizeConstraint::exact(UISize::new(200.0, 400.0)),
+            (200.0, 400.0),
+            &[
+                (50.0, y0, 100.0, 50.0),
+                (75.0, y1, 50.0, 100.0),
+                (25.0, y2, 150.0, 50.0),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+#[rustfmt::skip]
+fn test_stretch(name: &str) -> TestResult {
+    let flex = Flex::row().cross_alignment(CrossAlignment::Stretch);
+    let widgets = push_children(&flex, name, &[
+        (100.0, 50.0, 0.0, None),
+        (100.0, 80.0, 0.0, None),
+        (50.0, 20.0, 0.0, Some(CrossAlignment::End)),
+    ]);
+
+    // unbounded cross axis, stretched to the tallest child
+    check_layout(
+        "stretch",
+        &flex,
+        &widgets,
+        UISizeConstraint::new(UISize::ZERO, UISize::new(1000.0, f32::INFINITY)),
+        (250.0, 80.0),
+        &[
+            (0.0, 0.0, 100.0, 80.0),
+            (100.0, 0.0, 100.0, 80.0),
+            (200.0, 60.0, 50.0, 20.0),
+        ],
+    )
+}
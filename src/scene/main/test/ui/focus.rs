@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    scene::main::test::ui::TestWidgetBuilder,
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    ui::{
+        containers::{stack::Stack, ContainerWidget},
+        event::FocusDirection,
+        focus_order, subtree_ids,
+        utils::geom::UISize,
+        Alignment, HorizontalAlignment, UISizeConstraint, VerticalAlignment, Visibility, Widget,
+    },
+};
+
+const CENTER: Alignment = Alignment {
+    horizontal: HorizontalAlignment::Center,
+    vertical: VerticalAlignment::Middle,
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("focus_test");
+    let leaf = node.new_child_leaf("order");
+    leaf.update(test_order(leaf.full_name()));
+    let leaf = node.new_child_leaf("traversal");
+    leaf.update(test_traversal(main_ctx, leaf.full_name()));
+    Ok(())
+}
+
+fn widget(test_id: usize, focusable: bool, test_log_name: &str) -> Arc<dyn Widget> {
+    TestWidgetBuilder::new()
+        .pref_size(100.0, 100.0)
+        .focusable(focusable)
+        .build(test_id, test_log_name.to_owned(), true, false, false)
+}
+
+fn test_order(name: &str) -> TestResult {
+    let widgets = (0..5).map(|i| widget(i, i != 2, name)).collect::<Vec<_>>();
+    let root = Arc::new(Stack::new());
+    let inner = Arc::new(Stack::new());
+    let hidden = Arc::new(Stack::new());
+    root.push_arc(widgets[0].clone(), CENTER);
+    inner.push_arc(widgets[1].clone(), CENTER);
+    inner.push_arc(widgets[2].clone(), CENTER);
+    root.push_arc(inner, CENTER);
+    hidden.push_arc(widgets[3].clone(), CENTER);
+    hidden.set_visibility(Visibility::LogicalHidden);
+    root.push_arc(hidden, CENTER);
+    root.push_arc(widgets[4].clone(), CENTER);
+
+    let root: Arc<dyn Widget> = root;
+    let order = focus_order(&root)
+        .iter()
+        .map(|w| w.id())
+        .collect::<Vec<_>>();
+    assert_equals(
+        &order,
+        &vec![widgets[0].id(), widgets[1].id(), widgets[4].id()],
+        "depth-first order without hidden or unfocusable widgets",
+    )
+}
+
+fn test_traversal(main_ctx: &mut MainContext, name: &str) -> TestResult {
+    let root = Arc::new(Stack::new());
+    for i in 0..2 {
+        root.push_arc(widget(i, true, name), CENTER);
+    }
+    root.layout(&UISizeConstraint::exact(UISize::new(1000.0, 1000.0)));
+    let root: Arc<dyn Widget> = root;
+
+    // the focus starts on a widget outside of the tree, claimed the way a
+    // click does, whoever held it before
+    let outside = widget(2, true, name);
+    main_ctx.begin_focus_press();
+    main_ctx.set_focus_widget(Some(outside.clone()));
+    main_ctx.end_focus_press();
+    let log = main_ctx.test_capture.take_text(name);
+    assert_equals(log.trim(), "focus - Gained - 2", "outside widget focused")?;
+
+    let mut step = |direction, expected: &str, msg: &'static str| {
+        main_ctx.move_focus(&root, direction);
+        let log = main_ctx.test_capture.take_text(name);
+        assert_equals(log.trim(), expected.trim(), msg)
+    };
+    step(
+        FocusDirection::Next,
+        "focus - Lost - 2\nfocus - Gained - 0",
+        "first widget focused",
+    )?;
+    step(
+        FocusDirection::Next,
+        "focus - Lost - 0\nfocus - Gained - 1",
+        "lost before gained",
+    )?;
+    step(
+        FocusDirection::Next,
+        "focus - Lost - 1\nfocus - Gained - 0",
+        "wrapped to the first widget",
+    )?;
+    step(
+        FocusDirection::Previous,
+        "focus - Lost - 0\nfocus - Gained - 1",
+        "wrapped to the last widget",
+    )?;
+
+    let ids = subtree_ids(&root);
+    main_ctx.release_focus_if(|focused| ids.contains(&focused));
+    let log = main_ctx.test_capture.take_text(name);
+    assert_equals(log.trim(), "focus - Lost - 1", "focus released")?;
+    assert_equals(
+        &main_ctx.get_focused_widget().map(|w| w.id()),
+        &None,
+        "no widget focused",
+    )
+}
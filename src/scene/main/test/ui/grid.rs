@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    scene::main::test::ui::TestWidgetBuilder,
+    test::{
+        assert::{assert_equals, assert_equals_err},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::{
+        containers::grid::{Grid, GridCell, TrackSize},
+        utils::geom::{UIPos, UIRect, UISize},
+        LayoutAxis, UISizeConstraint, Widget,
+    },
+};
+
+pub fn test(_: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("grid_test");
+    let leaf = node.new_child_leaf("layout");
+    leaf.update(test_layout(leaf.full_name()));
+    Ok(())
+}
+
+#[rustfmt::skip]
+fn test_layout(name: &str) -> TestResult {
+    let grid = Grid::new(
+        vec![TrackSize::Fixed(100.0), TrackSize::Auto, TrackSize::Fraction(1.0), TrackSize::Fraction(2.0)],
+        vec![TrackSize::Auto, TrackSize::Fixed(40.0), TrackSize::Fraction(1.0)],
+    )
+    .spacing(10.0);
+    let children = [
+        (80.0, 30.0, GridCell::new(0, 0)),
+        (120.0, 50.0, GridCell::new(0, 1)),
+        // wider than the fixed and auto columns, the auto one grows
+        (250.0, 20.0, GridCell::new(1, 0).span(1, 2)),
+        (10.0, 10.0, GridCell::new(1, 2).span(2, 2)),
+        (30.0, 30.0, GridCell::new(0, 3)),
+    ];
+    let widgets = children
+        .iter()
+        .enumerate()
+        .map(|(i, &(width, height, cell))| {
+            let widget: Arc<dyn Widget> = TestWidgetBuilder::new()
+                .pref_size(width, height)
+                .build(i, name.to_owned(), false, false, false);
+            grid.push_arc(widget.clone(), cell);
+            widget
+        })
+        .collect::<Vec<_>>();
+
+    // columns: 100, 140, 110, 220 (330 left shared 1:2)
+    // rows: 50, 40, 190 (190 left)
+    let size = grid.layout(&UISizeConstraint::new(UISize::ZERO, UISize::new(600.0, 300.0)));
+    assert_equals_err(&size, &UISize::new(600.0, 300.0), "container size")?;
+    let expected_bounds = [
+        (0.0, 0.0, 100.0, 50.0),
+        (110.0, 0.0, 140.0, 50.0),
+        (0.0, 60.0, 250.0, 40.0),
+        (260.0, 60.0, 340.0, 240.0),
+        (380.0, 0.0, 220.0, 50.0),
+    ];
+    for (i, (widget, (x, y, w, h))) in widgets.iter().zip(expected_bounds).enumerate() {
+        let expected = UIRect::new(UIPos::new(x, y), UISize::new(w, h));
+        assert_equals_err(&widget.get_bounds(), &expected, format!("child (index: {i}) bounds mismatch"))?;
+    }
+
+    // unbounded, fractional columns fit their children like auto ones
+    let max_width = grid.max_intrinsic_size(LayoutAxis::Horizontal, f32::INFINITY);
+    assert_equals(&max_width, &300.0, "max intrinsic width")?;
+    let max_height = grid.max_intrinsic_size(LayoutAxis::Vertical, f32::INFINITY);
+    assert_equals(&max_height, &110.0, "max intrinsic height")
+}
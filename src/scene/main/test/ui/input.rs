@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use winit::event::{
+    ElementState, Event, ModifiersState, MouseButton, MouseScrollDelta, WindowEvent,
+};
+
+use crate::{
+    enclose,
+    exec::main_ctx::MainContext,
+    scene::SceneContainer,
+    test::{
+        assert::assert_equals,
+        input::{synthetic_device_id, InputDriver},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::{
+        containers::stack::Stack,
+        event::{wheel_gestures, ScrollDelta, UICursorEvent, UIFocusEvent, UIPropagatingEvent},
+        utils::geom::{UIPos, UISize},
+        Alignment, EventContext, HorizontalAlignment, LayoutAxis, UISizeConstraint,
+        VerticalAlignment, Widget,
+    },
+    utils::mutex::Mutex,
+};
+
+use super::{GenericTestWidget, GenericTestWidgetBuilder};
+
+pub(super) type RecordingWidget = GenericTestWidget<Mutex<Vec<String>>>;
+
+/// A 100x50 widget recording the events it receives, consuming the
+/// propagating ones.
+pub(super) fn recording_widget(test_id: usize) -> Arc<RecordingWidget> {
+    GenericTestWidgetBuilder::new(test_id, Mutex::new(Vec::new()))
+        .layout(|slf, size| {
+            let size = UISize::new(100.0, 50.0).clamp(&size.min, &size.max);
+            slf.bounds.lock().size = size;
+            size
+        })
+        .handle_cursor_event(|slf, _, event| {
+            slf.data.lock().push(format!("{event:?}"));
+            Some(event)
+        })
+        .handle_focus_event(|slf, _, event| {
+            slf.data.lock().push(format!("{event:?}"));
+            None
+        })
+        .handle_propagating_event(|slf, _, event| {
+            slf.data.lock().push(format!("{event:?}"));
+            None
+        })
+        .build()
+}
+
+/// Forwards the synthetic window events to `root`, `focused` gets the
+/// keyboard events.
+fn route_event(
+    main_ctx: &mut MainContext,
+    root: &Arc<Stack>,
+    focused: &Arc<RecordingWidget>,
+    event: &WindowEvent,
+) {
+    let mut ctx = EventContext { main_ctx };
+    match event {
+        WindowEvent::CursorMoved {
+            device_id,
+            position,
+            ..
+        } if *device_id == synthetic_device_id() => {
+            let scale_factor = ctx.main_ctx.display.get_scale_factor();
+            root.clone().handle_cursor_event(
+                &mut ctx,
+                UICursorEvent::CursorMoved(position.to_logical::<f32>(scale_factor).into()),
+            );
+        }
+        WindowEvent::MouseInput {
+            device_id,
+            state,
+            button,
+            ..
+        } if *device_id == synthetic_device_id() => {
+            root.clone().handle_propagating_event(
+                &mut ctx,
+                UIPropagatingEvent::MouseInput {
+                    state: *state,
+                    button: *button,
+                },
+            );
+        }
+        WindowEvent::MouseWheel {
+            device_id, delta, ..
+        } if *device_id == synthetic_device_id() => {
+            let scale_factor = ctx.main_ctx.display.get_scale_factor();
+            for gesture in wheel_gestures(*delta, ModifiersState::empty(), scale_factor) {
+                root.clone().handle_propagating_event(&mut ctx, gesture);
+            }
+        }
+        WindowEvent::ReceivedCharacter(ch) => {
+            focused
+                .clone()
+                .handle_focus_event(&mut ctx, UIFocusEvent::ReceivedCharacter(*ch));
+        }
+        _ => {}
+    }
+}
+
+pub(super) fn mouse_input(state: ElementState) -> UIPropagatingEvent {
+    UIPropagatingEvent::MouseInput {
+        state,
+        button: MouseButton::Left,
+    }
+}
+
+pub fn new(
+    main_ctx: &mut MainContext,
+    node: &Arc<ParentTestNode>,
+) -> anyhow::Result<SceneContainer> {
+    let node = node.new_child_leaf("input_driver");
+    let root = Arc::new(Stack::new());
+    let other = recording_widget(1);
+    let target = recording_widget(2);
+    root.push_arc(
+        other.clone(),
+        Alignment::new(HorizontalAlignment::Left, VerticalAlignment::Top),
+    );
+    root.push_arc(
+        target.clone(),
+        Alignment::new(HorizontalAlignment::Right, VerticalAlignment::Bottom),
+    );
+    root.layout(&UISizeConstraint::exact(UISize::new(400.0, 300.0)));
+
+    let mut container = SceneContainer::new();
+    container.push_event_handler(enclose!((root, target) move |main_ctx, _, event| {
+        if let Event::WindowEvent { window_id, event } = &event {
+            if *window_id == main_ctx.display.get_window_id() {
+                route_event(main_ctx, &root, &target, event);
+            }
+        }
+        Some(event)
+    }));
+
+    let driver = InputDriver::new(main_ctx);
+    main_ctx.spawn_local(async move {
+        let result: TestResult = async {
+            driver.move_to_widget(root.as_ref(), target.id())?.await?;
+            driver.click(MouseButton::Left)?.await?;
+            driver.type_text("hi")?.await?;
+            driver
+                .scroll(MouseScrollDelta::LineDelta(1.0, -3.0))?
+                .await?;
+            assert_equals(
+                &*target.data.lock(),
+                &vec![
+                    format!("{:?}", UICursorEvent::CursorEntered),
+                    format!("{:?}", UICursorEvent::CursorMoved(UIPos::new(50.0, 25.0))),
+                    format!("{:?}", mouse_input(ElementState::Pressed)),
+                    format!("{:?}", mouse_input(ElementState::Released)),
+                    format!("{:?}", UIFocusEvent::ReceivedCharacter('h')),
+                    format!("{:?}", UIFocusEvent::ReceivedCharacter('i')),
+                    format!(
+                        "{:?}",
+                        UIPropagatingEvent::Scroll {
+                            axis: LayoutAxis::Horizontal,
+                            delta: ScrollDelta::Lines(-1.0),
+                        }
+                    ),
+                    format!(
+                        "{:?}",
+                        UIPropagatingEvent::Scroll {
+                            axis: LayoutAxis::Vertical,
+                            delta: ScrollDelta::Lines(3.0),
+                        }
+                    ),
+                ],
+                "events received by the target widget",
+            )?;
+            assert_equals(
+                &*other.data.lock(),
+                &Vec::<String>::new(),
+                "events received by the other widget",
+            )
+        }
+        .await;
+        node.update(result);
+        Ok(())
+    });
+
+    Ok(container)
+}
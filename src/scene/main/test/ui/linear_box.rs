@@ -1,13 +1,47 @@
 use std::sync::Arc;
 
-use crate::{exec::main_ctx::MainContext, test::tree::ParentTestNode};
+use crate::{
+    exec::main_ctx::MainContext,
+    scene::main::test::ui::TestWidgetBuilder,
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    ui::{containers::linear_box::LinearBox, AxisY, HorizontalAlignment, LayoutAxis, Widget},
+};
 
 pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
     let node = node.new_child_parent("linear_box_test");
     layout_tests::test(main_ctx, &node);
+    let leaf = node.new_child_leaf("intrinsic");
+    leaf.update(test_intrinsic(leaf.full_name()));
     Ok(())
 }
 
+fn test_intrinsic(name: &str) -> TestResult {
+    let linear_box = LinearBox::<AxisY>::new();
+    let sizes = [
+        (200.0, 300.0, None),
+        (300.0, 400.0, Some((100.0, 200.0))),
+        (400.0, 100.0, Some((150.0, 100.0))),
+    ];
+    for (i, (width, height, min_size)) in sizes.into_iter().enumerate() {
+        let mut builder = TestWidgetBuilder::new().pref_size(width, height);
+        if let Some((min_width, min_height)) = min_size {
+            builder = builder.min_size(min_width, min_height);
+        }
+        let widget = builder.build(i, name.to_owned(), false, false, false);
+        linear_box.push_arc(widget, HorizontalAlignment::Left);
+    }
+
+    // default spacing is 4, after every child like in the layout
+    let min_height = linear_box.min_intrinsic_size(LayoutAxis::Vertical, f32::INFINITY);
+    assert_equals(&min_height, &612.0, "min intrinsic height")?;
+    let max_height = linear_box.max_intrinsic_size(LayoutAxis::Vertical, f32::INFINITY);
+    assert_equals(&max_height, &812.0, "max intrinsic height")?;
+    let min_width = linear_box.min_intrinsic_size(LayoutAxis::Horizontal, f32::INFINITY);
+    assert_equals(&min_width, &200.0, "min intrinsic width")?;
+    let max_width = linear_box.max_intrinsic_size(LayoutAxis::Horizontal, f32::INFINITY);
+    assert_equals(&max_width, &400.0, "max intrinsic width")
+}
+
 mod layout_tests {
     use std::{borrow::Cow, sync::Arc};
 
@@ -17,6 +51,7 @@ mod layout_tests {
         test::{
             assert::{assert_equals_err, assert_true},
             result::TestResult,
+            snapshot::assert_layout_snapshot,
             tree::{LeafTestNode, ParentTestNode},
         },
         ui::{
@@ -102,13 +137,10 @@ mod layout_tests {
             .enumerate()
             .map(|(i, (width, height, align))| {
                 (
-                    TestWidgetBuilder::new().pref_size(width, height).build(
-                        i,
-                        node.full_name().to_owned(),
-                        false,
-                        false,
-                        false,
-                    ),
+                    TestWidgetBuilder::new()
+                        .pref_size(width, height)
+                        .trace(node)
+                        .build(i, node.full_name().to_owned(), false, false, false),
                     align,
                 )
             })
@@ -120,6 +152,7 @@ mod layout_tests {
             stack.push_arc(widget.clone(), *alignment)
         }
 
+        let mut all_constraints = Vec::new();
         for (test_case_index, (min_width, min_height, max_width, max_height, child_layouts)) in
             expected_results.into_iter().enumerate()
         {
@@ -127,7 +160,9 @@ mod layout_tests {
                 UISize::new(min_width, min_height),
                 UISize::new(max_width, max_height),
             );
+            all_constraints.push(constraints);
             stack.layout(&constraints);
+            node.snapshot_layout(stack.as_ref());
 
             let container_size = stack.get_bounds().size;
             assert_true(constraints.test(&container_size), format!("container size does not fits (constraits: {constraints:?}, actual size: {container_size:?})"))?;
@@ -142,6 +177,6 @@ mod layout_tests {
             }
         }
 
-        Ok(())
+        assert_layout_snapshot(node.full_name(), stack.as_ref(), all_constraints)
     }
 }
@@ -7,18 +7,29 @@ use crate::{
     exec::main_ctx::MainContext,
     graphics::context::DrawContext,
     scene::SceneContainer,
-    test::tree::ParentTestNode,
+    test::tree::{LeafTestNode, ParentTestNode},
     ui::{
         acquire_widget_id,
         event::{UICursorEvent, UIFocusEvent, UIPropagatingEvent},
         utils::geom::{UIRect, UISize},
-        EventContext, UISizeConstraint, Widget, WidgetId,
+        EventContext, LayoutAxis, UISizeConstraint, Widget, WidgetId,
     },
     utils::mutex::Mutex,
 };
 
+pub mod button;
+pub mod controls;
+pub mod draw_order;
+pub mod flex;
+pub mod focus;
+pub mod grid;
+pub mod input;
 pub mod linear_box;
+pub mod overlay;
+pub mod scene;
+pub mod scroll_view;
 pub mod stack;
+pub mod text;
 
 pub fn new(
     main_ctx: &mut MainContext,
@@ -27,13 +38,26 @@ pub fn new(
     let node = node.new_child_parent("ui");
     stack::test(main_ctx, &node)?;
     linear_box::test(main_ctx, &node)?;
-    Ok(SceneContainer::new())
+    flex::test(main_ctx, &node)?;
+    grid::test(main_ctx, &node)?;
+    scroll_view::test(main_ctx, &node)?;
+    text::test(main_ctx, &node)?;
+    button::test(main_ctx, &node)?;
+    controls::test(main_ctx, &node)?;
+    focus::test(main_ctx, &node)?;
+    overlay::test(main_ctx, &node)?;
+    draw_order::test(main_ctx, &node)?;
+    let mut container = input::new(main_ctx, &node)?;
+    container.push_all(stack::scripted(main_ctx, &node)?);
+    Ok(container)
 }
 
 type TestWidgetId = usize;
 
 trait_set! {
 pub trait LayoutCallback<T> = Fn(&GenericTestWidget<T>, &UISizeConstraint) -> UISize + Send + Sync;
+/// min and max intrinsic sizes along the axis, given the cross size
+pub trait IntrinsicSizeCallback<T> = Fn(&GenericTestWidget<T>, LayoutAxis, f32) -> (f32, f32) + Send + Sync;
 pub trait DrawCallback<T> = Fn(&GenericTestWidget<T>, &mut DrawContext) + Send + Sync;
 pub trait HandleCursorEventCallback<T> = Fn(&Arc<GenericTestWidget<T>>, &mut EventContext, UICursorEvent) -> Option<UICursorEvent>
     + Send
@@ -51,7 +75,9 @@ pub struct GenericTestWidget<T: Send + Sync> {
     pub canonical_id: WidgetId,
     pub test_id: TestWidgetId,
     pub bounds: Mutex<UIRect>,
+    pub focusable: bool,
     pub layout_callback: Box<dyn LayoutCallback<T>>,
+    pub intrinsic_size_callback: Box<dyn IntrinsicSizeCallback<T>>,
     pub draw_callback: Box<dyn DrawCallback<T>>,
     pub handle_focus_event_callback: Box<dyn HandleFocusEventCallback<T>>,
     pub handle_cursor_event_callback: Box<dyn HandleCursorEventCallback<T>>,
@@ -62,7 +88,9 @@ pub struct GenericTestWidget<T: Send + Sync> {
 pub struct GenericTestWidgetBuilder<T: Send + Sync> {
     test_id: TestWidgetId,
     data: T,
+    focusable: bool,
     layout_callback: Option<Box<dyn LayoutCallback<T>>>,
+    intrinsic_size_callback: Option<Box<dyn IntrinsicSizeCallback<T>>>,
     draw_callback: Option<Box<dyn DrawCallback<T>>>,
     handle_focus_event_callback: Option<Box<dyn HandleFocusEventCallback<T>>>,
     handle_cursor_event_callback: Option<Box<dyn HandleCursorEventCallback<T>>>,
@@ -78,6 +106,14 @@ impl<T: Send + Sync> Widget for GenericTestWidget<T> {
         (self.layout_callback)(self, size_constraints)
     }
 
+    fn min_intrinsic_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        (self.intrinsic_size_callback)(self, axis, cross).0
+    }
+
+    fn max_intrinsic_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        (self.intrinsic_size_callback)(self, axis, cross).1
+    }
+
     fn set_bounds(&self, bounds: UIRect) {
         *self.bounds.lock() = bounds;
     }
@@ -86,6 +122,14 @@ impl<T: Send + Sync> Widget for GenericTestWidget<T> {
         *self.bounds.lock()
     }
 
+    fn debug_name(&self) -> Cow<'static, str> {
+        format!("test widget {}", self.test_id).into()
+    }
+
+    fn focusable(&self) -> bool {
+        self.focusable
+    }
+
     fn draw(&self, ctx: &mut DrawContext) {
         (self.draw_callback)(self, ctx)
     }
@@ -120,14 +164,21 @@ impl<T: Send + Sync> GenericTestWidgetBuilder<T> {
         Self {
             test_id,
             data,
+            focusable: false,
             handle_propagating_event_callback: None,
             handle_cursor_event_callback: None,
             handle_focus_event_callback: None,
             draw_callback: None,
             layout_callback: None,
+            intrinsic_size_callback: None,
         }
     }
 
+    pub fn focusable(mut self, focusable: bool) -> Self {
+        self.focusable = focusable;
+        self
+    }
+
     pub fn handle_propagating_event<F>(mut self, callback: F) -> Self
     where
         F: HandlePropagatingEventCallback<T> + 'static,
@@ -168,13 +219,28 @@ impl<T: Send + Sync> GenericTestWidgetBuilder<T> {
         self
     }
 
+    pub fn intrinsic_size<F>(mut self, callback: F) -> Self
+    where
+        F: IntrinsicSizeCallback<T> + 'static,
+    {
+        self.intrinsic_size_callback = Some(Box::new(callback));
+        self
+    }
+
     pub fn build(self) -> Arc<GenericTestWidget<T>> {
         Arc::new(GenericTestWidget {
             test_id: self.test_id,
             canonical_id: acquire_widget_id(),
             data: self.data,
             bounds: Mutex::new(UIRect::ZERO),
+            focusable: self.focusable,
             layout_callback: self.layout_callback.expect("layout callback not specified"),
+            intrinsic_size_callback: self.intrinsic_size_callback.unwrap_or_else(|| {
+                Box::new(|slf, axis, cross| {
+                    let size = axis.layout_intrinsic_size(slf, cross);
+                    (size, size)
+                })
+            }),
             draw_callback: self.draw_callback.unwrap_or_else(|| Box::new(|_, _| {})),
             handle_focus_event_callback: self
                 .handle_focus_event_callback
@@ -192,8 +258,12 @@ impl<T: Send + Sync> GenericTestWidgetBuilder<T> {
 #[derive(Default)]
 pub struct TestWidgetBuilder {
     pref_size: UISize,
+    /// `pref_size` if not set
+    min_size: Option<UISize>,
     mouse_passthrough: bool,
     consume_propagate: bool,
+    focusable: bool,
+    trace: Option<Arc<LeafTestNode>>,
 }
 
 impl TestWidgetBuilder {
@@ -206,6 +276,13 @@ impl TestWidgetBuilder {
         self
     }
 
+    /// Reported as the min intrinsic size, the layout still uses
+    /// `pref_size`.
+    pub fn min_size(mut self, width: f32, height: f32) -> Self {
+        self.min_size = Some(UISize::new(width, height));
+        self
+    }
+
     pub fn mouse_passthrough(mut self, passthrough: bool) -> Self {
         self.mouse_passthrough = passthrough;
         self
@@ -216,6 +293,17 @@ impl TestWidgetBuilder {
         self
     }
 
+    pub fn focusable(mut self, focusable: bool) -> Self {
+        self.focusable = focusable;
+        self
+    }
+
+    /// Records every event received by the widget in the artifacts of `node`.
+    pub fn trace(mut self, node: &Arc<LeafTestNode>) -> Self {
+        self.trace = Some(node.clone());
+        self
+    }
+
     #[allow(unused_mut)]
     pub fn build(
         self,
@@ -227,12 +315,16 @@ impl TestWidgetBuilder {
     ) -> Arc<GenericTestWidget<()>> {
         let Self {
             pref_size,
+            min_size,
             mouse_passthrough,
             consume_propagate,
+            focusable,
+            trace,
         } = self;
         let test_log_name = test_log_name.into();
 
         GenericTestWidgetBuilder::new(test_id, ())
+            .focusable(focusable)
             .layout(move |slf, size| {
                 let width = pref_size.width.clamp(size.min.width, size.max.width);
                 let height = pref_size.height.clamp(size.min.height, size.max.height);
@@ -240,41 +332,50 @@ impl TestWidgetBuilder {
                 slf.bounds.lock().size = size;
                 size
             })
+            .intrinsic_size(move |_, axis, _| {
+                let min_size = min_size.unwrap_or(pref_size);
+                (axis.get_size(min_size), axis.get_size(pref_size))
+            })
             .draw(enclose!((test_log_name) move |slf, ctx| {
-                let log = ctx.get_test_log(&test_log_name);
-                log.push_str(slf.test_id.to_string().as_str());
-                log.push('\n');
+                ctx.test_capture
+                    .record(test_log_name.clone(), slf.test_id.to_string());
             }))
-            .handle_propagating_event(enclose!((test_log_name) move |slf, ctx, event| {
-                let log = ctx.main_ctx.get_test_log(&test_log_name);
-                log.push_str("propagating - ");
-                if print_propagate_event {
-                    log.push_str(format!("{event:?} - ").as_str());
+            .handle_propagating_event(enclose!((test_log_name, trace) move |slf, ctx, event| {
+                if let Some(node) = trace.as_ref() {
+                    node.trace(slf.as_ref(), &event);
                 }
-                log.push_str(slf.test_id.to_string().as_str());
-                log.push('\n');
+                let text = if print_propagate_event {
+                    format!("propagating - {event:?} - {}", slf.test_id)
+                } else {
+                    format!("propagating - {}", slf.test_id)
+                };
+                ctx.main_ctx.test_capture.record(test_log_name.clone(), text);
 
                 (!consume_propagate).then_some(event)
             }))
-            .handle_focus_event(enclose!((test_log_name) move |slf, ctx, event| {
-                let log = ctx.main_ctx.get_test_log(&test_log_name);
-                log.push_str("focus - ");
-                if print_focus_event {
-                    log.push_str(format!("{event:?} - ").as_str());
+            .handle_focus_event(enclose!((test_log_name, trace) move |slf, ctx, event| {
+                if let Some(node) = trace.as_ref() {
+                    node.trace(slf.as_ref(), &event);
                 }
-                log.push_str(slf.test_id.to_string().as_str());
-                log.push('\n');
+                let text = if print_focus_event {
+                    format!("focus - {event:?} - {}", slf.test_id)
+                } else {
+                    format!("focus - {}", slf.test_id)
+                };
+                ctx.main_ctx.test_capture.record(test_log_name.clone(), text);
 
                 Some(event)
             }))
             .handle_cursor_event(enclose!((test_log_name) move |slf, ctx, event| {
-                let log = ctx.main_ctx.get_test_log(&test_log_name);
-                log.push_str("cursor - ");
-                if print_cursor_event {
-                    log.push_str(format!("{event:?} - ").as_str());
+                if let Some(node) = trace.as_ref() {
+                    node.trace(slf.as_ref(), &event);
                 }
-                log.push_str(slf.test_id.to_string().as_str());
-                log.push('\n');
+                let text = if print_cursor_event {
+                    format!("cursor - {event:?} - {}", slf.test_id)
+                } else {
+                    format!("cursor - {}", slf.test_id)
+                };
+                ctx.main_ctx.test_capture.record(test_log_name.clone(), text);
 
                 mouse_passthrough.then_some(event)
             }))
@@ -0,0 +1,177 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use winit::event::{ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode};
+
+use crate::{
+    exec::main_ctx::MainContext,
+    scene::main::test::ui::TestWidgetBuilder,
+    test::{
+        assert::{assert_equals, assert_equals_err, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::{
+        event::{UICursorEvent, UIPropagatingEvent},
+        overlay::{Overlay, OverlayLayer},
+        utils::geom::{UIPos, UIRect, UISize},
+        EventContext, Widget,
+    },
+};
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("overlay_test");
+    let leaf = node.new_child_leaf("placement");
+    leaf.update(test_placement(leaf.full_name()));
+    let leaf = node.new_child_leaf("dismiss");
+    leaf.update(test_dismiss(main_ctx, leaf.full_name()));
+    let leaf = node.new_child_leaf("modal");
+    leaf.update(test_modal(main_ctx, leaf.full_name()));
+    Ok(())
+}
+
+fn widget(test_id: usize, test_log_name: &str) -> Arc<dyn Widget> {
+    TestWidgetBuilder::new()
+        .pref_size(200.0, 100.0)
+        .consume_propagate(true)
+        .build(test_id, test_log_name.to_owned(), false, false, true)
+}
+
+fn layer() -> OverlayLayer {
+    let layer = OverlayLayer::new();
+    layer.layout(UISize::new(1000.0, 1000.0));
+    layer
+}
+
+fn moved(x: f32, y: f32) -> UICursorEvent {
+    UICursorEvent::CursorMoved(UIPos::new(x, y))
+}
+
+fn press() -> UIPropagatingEvent {
+    UIPropagatingEvent::MouseInput {
+        state: ElementState::Pressed,
+        button: MouseButton::Left,
+    }
+}
+
+pub(super) fn count_closes(closed: &Arc<AtomicUsize>) -> impl Fn(&mut EventContext) + Send + Sync {
+    let closed = closed.clone();
+    move |_| {
+        closed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn test_placement(name: &str) -> TestResult {
+    let layer = layer();
+    let centered = widget(0, name);
+    let anchored = widget(1, name);
+    layer.open(Overlay::new(centered.clone()));
+    layer.open(Overlay::new(anchored.clone()).at(UIPos::new(900.0, 50.0)));
+    assert_equals_err(
+        &centered.get_bounds(),
+        &UIRect::new(UIPos::new(400.0, 450.0), UISize::new(200.0, 100.0)),
+        "centered overlay",
+    )?;
+    assert_equals_err(
+        &anchored.get_bounds(),
+        &UIRect::new(UIPos::new(800.0, 50.0), UISize::new(200.0, 100.0)),
+        "overlay kept inside the window",
+    )
+}
+
+fn test_dismiss(main_ctx: &mut MainContext, name: &str) -> TestResult {
+    let layer = layer();
+    let closed = Arc::new(AtomicUsize::new(0));
+    let id = layer.open(
+        Overlay::new(widget(0, name))
+            .at(UIPos::new(100.0, 100.0))
+            .on_close(count_closes(&closed)),
+    );
+    let mut ctx = EventContext { main_ctx };
+
+    assert_equals(
+        &layer.handle_cursor_event(&mut ctx, moved(150.0, 120.0)),
+        &Some(UICursorEvent::CursorExited),
+        "the UI under the overlay is left",
+    )?;
+    assert_equals(
+        &layer.handle_cursor_event(&mut ctx, moved(160.0, 120.0)),
+        &None,
+        "moves over the overlay consumed",
+    )?;
+    assert_equals(
+        ctx.main_ctx.test_capture.take_text(name).trim(),
+        r"
+cursor - CursorEntered - 0
+cursor - CursorMoved(UIPos { x: 50.0, y: 20.0 }) - 0
+cursor - CursorMoved(UIPos { x: 60.0, y: 20.0 }) - 0"
+            .trim(),
+        "cursor events in the space of the overlay",
+    )?;
+    assert_true(
+        layer.handle_propagating_event(&mut ctx, press()).is_none(),
+        "press over the overlay consumed",
+    )?;
+    assert_true(layer.is_open(id), "still open after a press inside")?;
+
+    assert_equals(
+        &layer.handle_cursor_event(&mut ctx, moved(500.0, 500.0)),
+        &Some(moved(500.0, 500.0)),
+        "the UI under the overlay is entered again",
+    )?;
+    assert_true(
+        layer.handle_propagating_event(&mut ctx, press()).is_some(),
+        "press outside passed to the UI under the overlay",
+    )?;
+    assert_true(!layer.is_open(id), "dismissed by a press outside")?;
+
+    let id = layer.open(Overlay::new(widget(1, name)).on_close(count_closes(&closed)));
+    #[allow(deprecated)]
+    let escape = KeyboardInput {
+        scancode: 0,
+        state: ElementState::Pressed,
+        virtual_keycode: Some(VirtualKeyCode::Escape),
+        modifiers: ModifiersState::empty(),
+    };
+    assert_true(
+        layer.handle_keyboard_input(&mut ctx, &escape),
+        "escape consumed",
+    )?;
+    assert_true(!layer.is_open(id), "dismissed by escape")?;
+    ctx.main_ctx.test_capture.take(name);
+    assert_equals(&closed.load(Ordering::Relaxed), &2, "close callbacks")
+}
+
+fn test_modal(main_ctx: &mut MainContext, name: &str) -> TestResult {
+    let layer = layer();
+    let dialog = widget(0, name);
+    let id = layer.open(Overlay::new(dialog.clone()).modal(true).dismissable(false));
+    let mut ctx = EventContext { main_ctx };
+
+    assert_equals(
+        &layer.handle_cursor_event(&mut ctx, moved(10.0, 10.0)),
+        &Some(UICursorEvent::CursorExited),
+        "cursor kept from the UI under a modal overlay",
+    )?;
+    assert_true(
+        layer.handle_propagating_event(&mut ctx, press()).is_none(),
+        "press outside of a modal overlay blocked",
+    )?;
+    assert_true(layer.is_open(id), "undismissable overlay kept")?;
+    assert_equals(
+        &layer.modal_root().map(|w| w.id()),
+        &Some(dialog.id()),
+        "focus kept in the modal overlay",
+    )?;
+
+    assert_true(layer.close(&mut ctx, id), "closed")?;
+    ctx.main_ctx.test_capture.take(name);
+    assert_true(layer.is_empty(), "no overlay left")?;
+    assert_equals(
+        &layer.handle_cursor_event(&mut ctx, moved(20.0, 10.0)),
+        &Some(moved(20.0, 10.0)),
+        "cursor back to the UI",
+    )
+}
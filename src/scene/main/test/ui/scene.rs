@@ -0,0 +1,311 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use winit::event::{ElementState, ModifiersState, MouseButton, VirtualKeyCode};
+
+use crate::{
+    enclose,
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::draw_queue::SortKey,
+    scene::{main::content::ui::UI, SceneContainer},
+    test::{
+        assert::{assert_equals, assert_true},
+        input::InputDriver,
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::{
+        event::{UICursorEvent, UIFocusEvent},
+        overlay::Overlay,
+        utils::geom::{UIPos, UISize},
+        Alignment, EventContext, HorizontalAlignment, UISizeConstraint, VerticalAlignment, Widget,
+    },
+    utils::{mutex::Mutex, undo::HistoryAction},
+};
+
+use super::{
+    input::{mouse_input, recording_widget, RecordingWidget},
+    overlay::count_closes,
+    GenericTestWidgetBuilder,
+};
+
+const UI_SIZE: UISize = UISize::new(400.0, 300.0);
+const CHECK_DELAY: Duration = Duration::from_millis(200);
+
+/// Tests of the UI scene the game runs, driven by synthetic window events.
+pub fn new(
+    main_ctx: &mut MainContext,
+    node: &Arc<ParentTestNode>,
+) -> anyhow::Result<SceneContainer> {
+    let node = node.new_child_parent("ui_scene");
+    let ui = UI::new(main_ctx)?;
+    ui.root.layout(&UISizeConstraint::exact(UI_SIZE));
+    ui.overlays.layout(UI_SIZE);
+
+    // one after the other: closing an overlay closes the ones opened over
+    // it and a click would take the focus from the traversal test
+    let overlay_click = test_overlay_click(main_ctx, &node, &ui);
+    let tab_traversal = test_tab_traversal(main_ctx, &node, &ui);
+    let undo_shortcuts = test_undo_shortcuts(main_ctx, &node);
+    test_draw_flush(main_ctx, &node, &ui, async move {
+        overlay_click.await;
+        tab_traversal.await;
+        undo_shortcuts.await;
+    })?;
+
+    let mut container = SceneContainer::new();
+    container.push_arc(ui);
+    Ok(container)
+}
+
+/// Draws queued by the widgets are flushed every frame, under the
+/// overlays. `then` runs once the overlay of the test is closed.
+fn test_draw_flush(
+    main_ctx: &mut MainContext,
+    node: &Arc<ParentTestNode>,
+    ui: &Arc<UI>,
+    then: impl Future<Output = ()> + 'static,
+) -> anyhow::Result<()> {
+    let node = node.new_child_leaf("draw_flush");
+    let name = node.full_name().to_owned();
+    let queued = GenericTestWidgetBuilder::new(5, ())
+        .layout(|slf, size| {
+            let size = UISize::new(10.0, 10.0).clamp(&size.min, &size.max);
+            slf.bounds.lock().size = size;
+            size
+        })
+        .draw(enclose!((name) move |_, ctx| {
+            let name = name.clone();
+            ctx.queue_draw(SortKey::default(), move |ctx, _| {
+                ctx.test_capture.record(name, "queued");
+            });
+        }))
+        .build();
+    ui.root.push_arc(
+        queued,
+        Alignment::new(HorizontalAlignment::Left, VerticalAlignment::Bottom),
+    );
+    ui.root.layout(&UISizeConstraint::exact(UI_SIZE));
+
+    let overlay = GenericTestWidgetBuilder::new(6, ())
+        .layout(|slf, size| {
+            let size = UISize::new(100.0, 50.0).clamp(&size.min, &size.max);
+            slf.bounds.lock().size = size;
+            size
+        })
+        .draw(enclose!((name) move |_, ctx| {
+            ctx.test_capture.record(name.clone(), "overlay")
+        }))
+        .build();
+    let id = ui
+        .overlays
+        .open(Overlay::new(overlay).at(UIPos::new(UI_SIZE.width, 0.0)));
+
+    main_ctx
+        .set_timeout(CHECK_DELAY, move |main_ctx, _| {
+            let overlays = main_ctx.overlays.clone();
+            overlays.close(&mut EventContext { main_ctx }, id);
+            let log = main_ctx
+                .channels
+                .draw
+                .query(move |ctx, _| ctx.test_capture.take_text(&name))?;
+            main_ctx.spawn_local(async move {
+                let log = log.await?;
+                let frames = log.lines().collect::<Vec<_>>();
+                node.update(
+                    assert_true(!frames.is_empty(), "frames drawn").and_then(|()| {
+                        assert_true(
+                            frames.chunks(2).all(|frame| frame == ["queued", "overlay"]),
+                            format!("queued draws flushed under the overlays every frame:\n{log}"),
+                        )
+                    }),
+                );
+                Ok(())
+            });
+            main_ctx.spawn_local(async move {
+                then.await;
+                Ok(())
+            });
+            Ok(())
+        })
+        .context("unable to set draw flush check timeout")?;
+    Ok(())
+}
+
+/// A click on an overlay goes to it only, the next one outside of it
+/// closes it and reaches the widget under it.
+fn test_overlay_click(
+    main_ctx: &mut MainContext,
+    node: &Arc<ParentTestNode>,
+    ui: &Arc<UI>,
+) -> impl Future<Output = ()> {
+    let node = node.new_child_leaf("overlay_click");
+    let below = recording_widget(1);
+    ui.root.push_arc(
+        below.clone(),
+        Alignment::new(HorizontalAlignment::Left, VerticalAlignment::Top),
+    );
+    ui.root.layout(&UISizeConstraint::exact(UI_SIZE));
+
+    let overlay = recording_widget(2);
+    let closed = Arc::new(AtomicUsize::new(0));
+    let overlays = ui.overlays.clone();
+    let driver = InputDriver::new(main_ctx);
+    async move {
+        let id = overlays.open(
+            Overlay::new(overlay.clone())
+                .at(UIPos::new(100.0, 100.0))
+                .on_close(count_closes(&closed)),
+        );
+        let result: TestResult = async {
+            driver.move_to(UIPos::new(140.0, 120.0))?.await?;
+            driver.click(MouseButton::Left)?.await?;
+            assert_true(overlays.is_open(id), "still open after a click on it")?;
+            driver.move_to(UIPos::new(20.0, 20.0))?.await?;
+            driver.click(MouseButton::Left)?.await?;
+
+            assert_equals(
+                &*overlay.data.lock(),
+                &vec![
+                    format!("{:?}", UICursorEvent::CursorEntered),
+                    format!("{:?}", UICursorEvent::CursorMoved(UIPos::new(40.0, 20.0))),
+                    format!("{:?}", mouse_input(ElementState::Pressed)),
+                    format!("{:?}", mouse_input(ElementState::Released)),
+                    format!("{:?}", UICursorEvent::CursorExited),
+                ],
+                "events received by the overlay",
+            )?;
+            let presses = [ElementState::Pressed, ElementState::Released]
+                .map(|state| format!("{:?}", mouse_input(state)));
+            assert_equals(
+                &below
+                    .data
+                    .lock()
+                    .iter()
+                    .filter(|event| presses.contains(event))
+                    .cloned()
+                    .collect::<Vec<_>>(),
+                &presses.to_vec(),
+                "clicks received under the overlay",
+            )?;
+            assert_true(!overlays.is_open(id), "closed by the click outside")?;
+            assert_equals(&closed.load(Ordering::Relaxed), &1, "close callbacks")
+        }
+        .await;
+        node.update(result);
+    }
+}
+
+/// Tab moves the focus through the widgets of the root, wrapping around.
+fn test_tab_traversal(
+    main_ctx: &mut MainContext,
+    node: &Arc<ParentTestNode>,
+    ui: &Arc<UI>,
+) -> impl Future<Output = ()> {
+    let node = node.new_child_leaf("tab_traversal");
+    let widgets = [3, 4].map(focusable_widget);
+    for (widget, vertical) in widgets
+        .iter()
+        .zip([VerticalAlignment::Middle, VerticalAlignment::Bottom])
+    {
+        ui.root.push_arc(
+            widget.clone(),
+            Alignment::new(HorizontalAlignment::Right, vertical),
+        );
+    }
+    ui.root.layout(&UISizeConstraint::exact(UI_SIZE));
+
+    let driver = InputDriver::new(main_ctx);
+    async move {
+        let result: TestResult = async {
+            for _ in 0..3 {
+                driver.press_key(VirtualKeyCode::Tab)?.await?;
+            }
+            let [gained, lost] =
+                [UIFocusEvent::Gained, UIFocusEvent::Lost].map(|e| format!("{e:?}"));
+            let changes = |widget: &Arc<RecordingWidget>| {
+                widget
+                    .data
+                    .lock()
+                    .iter()
+                    .filter(|&event| *event == gained || *event == lost)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+            assert_equals(
+                &changes(&widgets[0]),
+                &vec![gained.clone(), lost.clone(), gained.clone()],
+                "focus changes of the first widget",
+            )?;
+            assert_equals(
+                &changes(&widgets[1]),
+                &vec![gained.clone(), lost.clone()],
+                "focus changes of the last widget",
+            )
+        }
+        .await;
+        node.update(result);
+    }
+}
+
+/// Ctrl+Z, Ctrl+Shift+Z and Ctrl+Y left by the focused widget are
+/// published as `HistoryAction`s.
+fn test_undo_shortcuts(
+    main_ctx: &mut MainContext,
+    node: &Arc<ParentTestNode>,
+) -> impl Future<Output = ()> {
+    let node = node.new_child_leaf("undo_shortcuts");
+    let (subscription, receiver) = main_ctx.event_bus.subscribe_channel::<HistoryAction>();
+    let driver = InputDriver::new(main_ctx);
+    async move {
+        let result: TestResult = async {
+            let ctrl = ModifiersState::CTRL;
+            driver.press_key_with(ctrl, VirtualKeyCode::Z)?.await?;
+            driver
+                .press_key_with(ctrl | ModifiersState::SHIFT, VirtualKeyCode::Z)?
+                .await?;
+            driver.press_key_with(ctrl, VirtualKeyCode::Y)?.await?;
+            // not a shortcut without the modifier
+            driver.press_key(VirtualKeyCode::Z)?.await?;
+            let mut actions = Vec::new();
+            while let Some(action) = receiver.try_recv()? {
+                actions.push(*action);
+            }
+            assert_equals(
+                &actions,
+                &vec![
+                    HistoryAction::Undo,
+                    HistoryAction::Redo,
+                    HistoryAction::Redo,
+                ],
+                "published actions",
+            )
+        }
+        .await;
+        drop(subscription);
+        node.update(result);
+    }
+}
+
+/// A focusable 50x50 widget recording its focus events.
+fn focusable_widget(test_id: usize) -> Arc<RecordingWidget> {
+    GenericTestWidgetBuilder::new(test_id, Mutex::new(Vec::new()))
+        .focusable(true)
+        .layout(|slf, size| {
+            let size = UISize::new(50.0, 50.0).clamp(&size.min, &size.max);
+            slf.bounds.lock().size = size;
+            size
+        })
+        .handle_focus_event(|slf, _, event| {
+            slf.data.lock().push(format!("{event:?}"));
+            Some(event)
+        })
+        .build()
+}
@@ -0,0 +1,308 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use winit::event::{ElementState, MouseButton};
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    test::{
+        assert::{assert_equals, assert_equals_err, assert_true, assert_unreachable},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::{
+        containers::scroll_view::ScrollView,
+        event::{ScrollDelta, UICursorEvent, UIPropagatingEvent},
+        utils::geom::{UIPos, UIRect, UISize},
+        EventContext, LayoutAxis, UISizeConstraint, Widget,
+    },
+    utils::mutex::Mutex,
+};
+
+use super::{GenericTestWidget, GenericTestWidgetBuilder};
+
+type CursorWidget = GenericTestWidget<Mutex<Vec<UIPos>>>;
+
+/// Frames get this long to move the fling forward.
+const FRAME_DELAY: Duration = Duration::from_millis(200);
+
+/// 200x1000 widget recording where the cursor moved over it.
+fn content_widget() -> Arc<CursorWidget> {
+    GenericTestWidgetBuilder::new(0, Mutex::new(Vec::new()))
+        .layout(|slf, size| {
+            let size = UISize::new(200.0, 1000.0).clamp(&size.min, &size.max);
+            slf.bounds.lock().size = size;
+            size
+        })
+        .handle_cursor_event(|slf, _, event| {
+            if let UICursorEvent::CursorMoved(position) = event {
+                slf.data.lock().push(position);
+            }
+            Some(event)
+        })
+        .build()
+}
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("scroll_view_test");
+    node.new_child_leaf("wheel").update(test_wheel(main_ctx));
+    node.new_child_leaf("drag").update(test_drag(main_ctx));
+    node.new_child_leaf("scrollbar")
+        .update(test_scrollbar(main_ctx));
+    test_fling(main_ctx, &node)?;
+    test_clip(main_ctx, &node)
+}
+
+fn mouse(state: ElementState) -> UIPropagatingEvent {
+    UIPropagatingEvent::MouseInput {
+        state,
+        button: MouseButton::Left,
+    }
+}
+
+/// Presses at `from`, moves to `to` `dt` seconds later.
+fn drag(ctx: &mut EventContext, view: &Arc<ScrollView>, from: UIPos, to: UIPos, dt: f32) {
+    view.clone()
+        .handle_cursor_event(ctx, UICursorEvent::CursorMoved(from));
+    view.clone()
+        .handle_propagating_event(ctx, mouse(ElementState::Pressed));
+    view.advance(dt);
+    view.clone()
+        .handle_cursor_event(ctx, UICursorEvent::CursorMoved(to));
+}
+
+fn scroll(axis: LayoutAxis, delta: ScrollDelta) -> UIPropagatingEvent {
+    UIPropagatingEvent::Scroll { axis, delta }
+}
+
+fn test_wheel(main_ctx: &mut MainContext) -> TestResult {
+    let content = content_widget();
+    let view = Arc::new(ScrollView::new(content.clone(), false, true));
+    let size = view.layout(&UISizeConstraint::new(
+        UISize::ZERO,
+        UISize::new(300.0, 400.0),
+    ));
+    assert_equals_err(&size, &UISize::new(200.0, 400.0), "viewport size")?;
+
+    let mut ctx = EventContext { main_ctx };
+    let unhandled = view.clone().handle_propagating_event(
+        &mut ctx,
+        scroll(LayoutAxis::Vertical, ScrollDelta::Lines(2.0)),
+    );
+    assert_true(unhandled.is_none(), "vertical scroll is consumed")?;
+    assert_equals_err(
+        &view.offset(),
+        &UIPos::new(0.0, 80.0),
+        "offset after 2 lines",
+    )?;
+
+    view.clone().handle_propagating_event(
+        &mut ctx,
+        scroll(LayoutAxis::Vertical, ScrollDelta::Pixels(10000.0)),
+    );
+    assert_equals_err(
+        &view.offset(),
+        &UIPos::new(0.0, 600.0),
+        "offset clamped to the end",
+    )?;
+
+    let unhandled = view.clone().handle_propagating_event(
+        &mut ctx,
+        scroll(LayoutAxis::Horizontal, ScrollDelta::Lines(1.0)),
+    );
+    assert_true(unhandled.is_some(), "horizontal scroll is passed on")?;
+
+    // the cursor reaches the content in its own space
+    view.clone()
+        .handle_cursor_event(&mut ctx, UICursorEvent::CursorMoved(UIPos::new(50.0, 50.0)));
+    let moves = content.data.lock().clone();
+    assert_equals(
+        &moves,
+        &vec![UIPos::new(50.0, 650.0)],
+        "cursor in content space",
+    )
+}
+
+fn test_drag(main_ctx: &mut MainContext) -> TestResult {
+    let content = content_widget();
+    let view = Arc::new(ScrollView::new(content, false, true));
+    view.layout(&UISizeConstraint::exact(UISize::new(200.0, 400.0)));
+
+    let mut ctx = EventContext { main_ctx };
+    drag(
+        &mut ctx,
+        &view,
+        UIPos::new(100.0, 300.0),
+        UIPos::new(100.0, 200.0),
+        0.05,
+    );
+    assert_equals_err(
+        &view.offset(),
+        &UIPos::new(0.0, 100.0),
+        "offset while dragging",
+    )?;
+
+    let unhandled = view
+        .clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Released));
+    assert_true(unhandled.is_none(), "release after a drag is not a click")?;
+
+    // released at 2000 units per second
+    view.advance(0.1);
+    assert_equals_err(
+        &view.offset(),
+        &UIPos::new(0.0, 300.0),
+        "offset after fling",
+    )?;
+    for _ in 0..20 {
+        view.advance(0.1);
+    }
+    assert_equals_err(
+        &view.offset(),
+        &UIPos::new(0.0, 600.0),
+        "fling stopped at the end",
+    )
+}
+
+fn test_scrollbar(main_ctx: &mut MainContext) -> TestResult {
+    let view = Arc::new(ScrollView::new(content_widget(), false, true));
+    view.layout(&UISizeConstraint::exact(UISize::new(200.0, 400.0)));
+    assert_true(
+        view.scrollbar(LayoutAxis::Vertical).is_none(),
+        "scrollbar hidden before scrolling",
+    )?;
+
+    let mut ctx = EventContext { main_ctx };
+    view.clone().handle_propagating_event(
+        &mut ctx,
+        scroll(LayoutAxis::Vertical, ScrollDelta::Pixels(150.0)),
+    );
+    // 400 of the 1000 units long content are shown, a quarter of the way
+    // through the 600 it scrolls, along the right edge
+    let (bounds, alpha) = match view.scrollbar(LayoutAxis::Vertical) {
+        Some(scrollbar) => scrollbar,
+        None => return assert_unreachable("scrollbar hidden after scrolling"),
+    };
+    assert_equals_err(
+        &bounds,
+        &UIRect::new(UIPos::new(192.0, 60.0), UISize::new(6.0, 160.0)),
+        "scrollbar bounds",
+    )?;
+    assert_equals_err(&alpha, &1.0, "scrollbar opacity")?;
+    assert_true(
+        view.scrollbar(LayoutAxis::Horizontal).is_none(),
+        "no scrollbar along the fixed axis",
+    )?;
+
+    // shown for a second, then fades out in 0.3
+    view.advance(1.15);
+    let alpha = view
+        .scrollbar(LayoutAxis::Vertical)
+        .map(|(_, alpha)| alpha)
+        .unwrap_or_default();
+    assert_equals_err(&alpha, &0.5, "scrollbar opacity while fading out")?;
+    view.advance(0.5);
+    assert_true(
+        view.scrollbar(LayoutAxis::Vertical).is_none(),
+        "scrollbar faded out",
+    )
+}
+
+/// A drag held still before the release doesn't fling, a quick one flings
+/// from the frame callback once the view is animated.
+fn test_fling(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let test_node = node.new_child_leaf("fling");
+    let view = Arc::new(ScrollView::new(content_widget(), false, true));
+    view.layout(&UISizeConstraint::exact(UISize::new(200.0, 400.0)));
+
+    let mut ctx = EventContext { main_ctx };
+    drag(
+        &mut ctx,
+        &view,
+        UIPos::new(100.0, 300.0),
+        UIPos::new(100.0, 200.0),
+        0.05,
+    );
+    view.advance(0.2);
+    view.clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Released));
+    view.advance(0.1);
+    let held = assert_equals_err(
+        &view.offset(),
+        &UIPos::new(0.0, 100.0),
+        "offset after a drag held still",
+    );
+    if held.is_err() {
+        test_node.update(held);
+        return Ok(());
+    }
+
+    drag(
+        &mut ctx,
+        &view,
+        UIPos::new(100.0, 200.0),
+        UIPos::new(100.0, 100.0),
+        0.05,
+    );
+    view.clone()
+        .handle_propagating_event(&mut ctx, mouse(ElementState::Released));
+    ScrollView::animate(&view, ctx.main_ctx);
+    ctx.main_ctx
+        .set_timeout(FRAME_DELAY, move |_, _| {
+            let offset = view.offset();
+            test_node.update(assert_true(
+                offset.y > 200.0,
+                format!("the fling moves on with the frames, offset {offset:?}"),
+            ));
+            Ok(())
+        })
+        .context("unable to set fling check timeout")?;
+    Ok(())
+}
+
+/// The content is drawn clipped to the bounds of the view, not scrolled
+/// along with it.
+fn test_clip(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let test_node = node.new_child_leaf("clip");
+    let content = GenericTestWidgetBuilder::new(0, Mutex::new(None))
+        .layout(|slf, size| {
+            let size = UISize::new(200.0, 1000.0).clamp(&size.min, &size.max);
+            slf.bounds.lock().size = size;
+            size
+        })
+        .draw(|slf, ctx| {
+            *slf.data.lock() = Some(ctx.clip_stack.current().map(|clip| clip.scissor));
+        })
+        .build();
+    let view = Arc::new(ScrollView::new(content.clone(), false, true));
+    let size = view.layout(&UISizeConstraint::exact(UISize::new(200.0, 400.0)));
+    let bounds = UIRect::new(UIPos::new(10.0, 20.0), size);
+    view.set_bounds(bounds);
+    let mut ctx = EventContext { main_ctx };
+    view.clone().handle_propagating_event(
+        &mut ctx,
+        scroll(LayoutAxis::Vertical, ScrollDelta::Lines(2.0)),
+    );
+    // hides the scrollbar, nothing else is drawn along with the content
+    view.advance(2.0);
+
+    let query = ctx
+        .main_ctx
+        .channels
+        .draw
+        .query(move |ctx, _| {
+            view.draw(ctx);
+            content.data.lock().take()
+        })
+        .context("unable to query scroll view clip")?;
+    ctx.main_ctx.spawn_local(async move {
+        let clip = query.await?;
+        test_node.update(assert_equals(
+            &clip,
+            &Some(Some(bounds)),
+            "clip of the content",
+        ));
+        Ok(())
+    });
+    Ok(())
+}
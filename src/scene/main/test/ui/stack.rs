@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::{exec::main_ctx::MainContext, test::tree::ParentTestNode};
+use crate::{exec::main_ctx::MainContext, scene::SceneContainer, test::tree::ParentTestNode};
 
 pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
     let node = node.new_child_parent("stack_test");
@@ -8,9 +8,18 @@ pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::R
     propagating_tests::test(main_ctx, &node);
     cursor_tests::test(main_ctx, &node);
     draw_tests::test(main_ctx, &node)?;
+    mutation_tests::test(main_ctx, &node);
     Ok(())
 }
 
+/// The stack tests driven by synthetic input, they need the window events.
+pub fn scripted(
+    main_ctx: &mut MainContext,
+    node: &Arc<ParentTestNode>,
+) -> anyhow::Result<SceneContainer> {
+    scripted_tests::new(main_ctx, node)
+}
+
 mod layout_tests {
     use std::{borrow::Cow, sync::Arc};
 
@@ -20,6 +29,7 @@ mod layout_tests {
         test::{
             assert::{assert_equals_err, assert_true},
             result::TestResult,
+            snapshot::assert_layout_snapshot,
             tree::{LeafTestNode, ParentTestNode},
         },
         ui::{
@@ -145,13 +155,10 @@ mod layout_tests {
             .enumerate()
             .map(|(i, (width, height, h_align, v_align))| {
                 (
-                    TestWidgetBuilder::new().pref_size(width, height).build(
-                        i,
-                        node.full_name().to_owned(),
-                        false,
-                        false,
-                        false,
-                    ),
+                    TestWidgetBuilder::new()
+                        .pref_size(width, height)
+                        .trace(node)
+                        .build(i, node.full_name().to_owned(), false, false, false),
                     Alignment::new(h_align, v_align),
                 )
             })
@@ -163,6 +170,7 @@ mod layout_tests {
             stack.push_arc(widget.clone(), *alignment)
         }
 
+        let mut all_constraints = Vec::new();
         for (test_case_index, (min_width, min_height, max_width, max_height, child_layouts)) in
             expected_results.into_iter().enumerate()
         {
@@ -170,7 +178,9 @@ mod layout_tests {
                 UISize::new(min_width, min_height),
                 UISize::new(max_width, max_height),
             );
+            all_constraints.push(constraints);
             stack.layout(&constraints);
+            node.snapshot_layout(stack.as_ref());
 
             let container_size = stack.get_bounds().size;
             assert_true(constraints.test(&container_size), format!("container size does not fits (constraits: {constraints:?}, actual size: {container_size:?})"))?;
@@ -185,7 +195,7 @@ mod layout_tests {
             }
         }
 
-        Ok(())
+        assert_layout_snapshot(node.full_name(), stack.as_ref(), all_constraints)
     }
 }
 
@@ -195,10 +205,11 @@ mod draw_tests {
     use anyhow::Context;
 
     use crate::{
+        assert_log_eq_test,
         exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
         graphics::context::DrawContext,
         scene::main::test::ui::{TestWidgetBuilder, TestWidgetId},
-        test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+        test::{result::TestResult, tree::ParentTestNode},
         ui::{containers::stack::Stack, Alignment, HorizontalAlignment, VerticalAlignment, Widget},
     };
 
@@ -276,6 +287,7 @@ mod draw_tests {
             .draw
             .execute(move |ctx, _| {
                 stack.draw(ctx);
+                node.snapshot_layout(stack.as_ref());
                 node.update(test_body(ctx, name, expected_log));
             })
             .context("unable to send test to run on draw server")?;
@@ -284,13 +296,8 @@ mod draw_tests {
     }
 
     fn test_body(ctx: &mut DrawContext, name: String, expected_log: &str) -> TestResult {
-        let log = ctx.pop_test_log(name.as_str());
-        let log = log.trim();
-        let expected_log = expected_log.trim();
-
-        assert_equals(log, expected_log, "draw log mismatch")?;
-
-        Ok(())
+        let log = ctx.test_capture.take_text(name.as_str());
+        assert_log_eq_test!(log, expected_log, "draw log mismatch")
     }
 }
 
@@ -300,9 +307,10 @@ mod propagating_tests {
     use winit::window::Theme;
 
     use crate::{
+        assert_log_eq_test,
         exec::main_ctx::MainContext,
         scene::main::test::ui::TestWidgetBuilder,
-        test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+        test::{result::TestResult, tree::ParentTestNode},
         ui::{
             containers::stack::Stack,
             event::{UICursorEvent, UIPropagatingEvent},
@@ -363,6 +371,7 @@ mod propagating_tests {
             let widget = TestWidgetBuilder::new()
                 .pref_size(width, height)
                 .consume_propagate(consume_event)
+                .trace(&node)
                 .build(i, node.full_name().to_owned(), false, false, false);
             let align = Alignment::new(h_align, v_align);
             stack.push_arc(widget, align);
@@ -373,13 +382,15 @@ mod propagating_tests {
             .clone()
             .handle_cursor_event(&mut ctx, UICursorEvent::CursorEntered);
 
-        node.update(test_body(
+        let result = test_body(
             &mut ctx,
             node.full_name(),
             &stack,
             non_hover_output,
             hover_output,
-        ));
+        );
+        node.snapshot_layout(stack.as_ref());
+        node.update(result);
     }
 
     fn test_body(
@@ -399,11 +410,11 @@ mod propagating_tests {
             stack
                 .clone()
                 .handle_propagating_event(ctx, UIPropagatingEvent::ThemeChanged(Theme::Dark));
-            let log = ctx.main_ctx.pop_test_log(name);
-            assert_equals(
-                log.trim(),
-                non_hover_output.trim(),
-                "non-hover test case event log mismatch",
+            let log = ctx.main_ctx.test_capture.take_text(name);
+            assert_log_eq_test!(
+                log,
+                non_hover_output,
+                "non-hover test case event log mismatch"
             )?;
         }
 
@@ -415,12 +426,8 @@ mod propagating_tests {
                 .clone()
                 .handle_propagating_event(ctx, UIPropagatingEvent::TestHover);
 
-            let log = ctx.main_ctx.pop_test_log(name);
-            assert_equals(
-                log.trim(),
-                expected_log.trim(),
-                format!("hover test case {i} event log mismatch"),
-            )?;
+            let log = ctx.main_ctx.test_capture.take_text(name);
+            assert_log_eq_test!(log, expected_log, "hover test case {i} event log mismatch")?;
 
             // reset state
             stack
@@ -429,7 +436,7 @@ mod propagating_tests {
             stack
                 .clone()
                 .handle_cursor_event(ctx, UICursorEvent::CursorEntered);
-            ctx.main_ctx.pop_test_log(name);
+            ctx.main_ctx.test_capture.take(name);
         }
 
         Ok(())
@@ -440,9 +447,10 @@ mod cursor_tests {
     use std::sync::Arc;
 
     use crate::{
+        assert_log_eq_test,
         exec::main_ctx::MainContext,
         scene::main::test::ui::TestWidgetBuilder,
-        test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+        test::{result::TestResult, tree::ParentTestNode},
         ui::{
             containers::stack::Stack,
             event::UICursorEvent,
@@ -514,6 +522,7 @@ cursor - 1",
             let widget = TestWidgetBuilder::new()
                 .pref_size(width, height)
                 .mouse_passthrough(mouse_passthrough)
+                .trace(&node)
                 .build(i, node.full_name().to_owned(), false, false, false);
             stack.push_arc(widget, Alignment::new(h_align, v_align));
         }
@@ -527,6 +536,7 @@ cursor - 1",
             test_cases,
         );
 
+        node.snapshot_layout(stack.as_ref());
         node.update(result);
     }
 
@@ -554,14 +564,371 @@ cursor - 1",
                 .clone()
                 .handle_cursor_event(ctx, UICursorEvent::CursorExited);
 
-            let log = ctx.main_ctx.pop_test_log(name);
-            assert_equals(
-                log.trim(),
-                expected_log.trim(),
-                format!("event log mismatch in test case {i}"),
-            )?;
+            let log = ctx.main_ctx.test_capture.take_text(name);
+            assert_log_eq_test!(log, expected_log, "event log mismatch in test case {i}")?;
         }
 
         Ok(())
     }
 }
+
+mod mutation_tests {
+    use std::sync::Arc;
+
+    use crate::{
+        exec::main_ctx::MainContext,
+        scene::main::test::ui::TestWidgetBuilder,
+        test::{
+            assert::{assert_equals, assert_equals_err, assert_true},
+            result::TestResult,
+            tree::ParentTestNode,
+        },
+        ui::{
+            containers::stack::Stack,
+            event::UICursorEvent,
+            utils::geom::{UIPos, UIRect, UISize},
+            Alignment, EventContext, HorizontalAlignment, UISizeConstraint, VerticalAlignment,
+            Widget,
+        },
+    };
+
+    const CENTER: Alignment = Alignment {
+        horizontal: HorizontalAlignment::Center,
+        vertical: VerticalAlignment::Middle,
+    };
+
+    pub(super) fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) {
+        let node = node.new_child_parent("mutation");
+        let mut ctx = EventContext { main_ctx };
+        let test_node = node.new_child_leaf("insert");
+        test_node.update(test_insert(&mut ctx, test_node.full_name()));
+
+        let test_node = node.new_child_leaf("remove_hovered");
+        let name = test_node.full_name();
+        let result = test_remove_hovered(&mut ctx, name);
+        // kept for the failure artifacts otherwise
+        if result.is_ok() {
+            ctx.main_ctx.test_capture.take(name);
+        }
+        test_node.update(result);
+
+        let test_node = node.new_child_leaf("remove_focused");
+        test_node.update(test_remove_focused(&mut ctx, test_node.full_name()));
+    }
+
+    fn exact_layout(stack: &Stack) {
+        stack.layout(&UISizeConstraint::exact(UISize::new(1000.0, 1000.0)));
+    }
+
+    fn test_insert(ctx: &mut EventContext, name: &str) -> TestResult {
+        let stack = Arc::new(Stack::new());
+        let first = TestWidgetBuilder::new().pref_size(100.0, 100.0).build(
+            0,
+            name.to_owned(),
+            false,
+            false,
+            false,
+        );
+        stack.push_arc(first.clone(), CENTER);
+        exact_layout(&stack);
+
+        let second = TestWidgetBuilder::new().pref_size(200.0, 200.0).build(
+            1,
+            name.to_owned(),
+            false,
+            false,
+            false,
+        );
+        stack.insert(
+            ctx,
+            0,
+            second.clone(),
+            Alignment::new(HorizontalAlignment::Left, VerticalAlignment::Top),
+        );
+        ctx.main_ctx.test_capture.take(name);
+
+        assert_equals(
+            &stack.children().iter().map(|w| w.id()).collect::<Vec<_>>(),
+            &vec![second.id(), first.id()],
+            "children order",
+        )?;
+        assert_equals_err(
+            &second.get_bounds(),
+            &UIRect::new(UIPos::new(0.0, 0.0), UISize::new(200.0, 200.0)),
+            "inserted child laid out",
+        )
+    }
+
+    fn test_remove_hovered(ctx: &mut EventContext, name: &str) -> TestResult {
+        let stack = Arc::new(Stack::new());
+        let widgets = [300.0, 500.0].map(|size| {
+            TestWidgetBuilder::new().pref_size(size, size).build(
+                size as usize / 100,
+                name.to_owned(),
+                false,
+                false,
+                true,
+            )
+        });
+        for widget in widgets.iter() {
+            stack.push_arc(widget.clone(), CENTER);
+        }
+        exact_layout(&stack);
+
+        stack
+            .clone()
+            .handle_cursor_event(ctx, UICursorEvent::CursorEntered);
+        stack
+            .clone()
+            .handle_cursor_event(ctx, UICursorEvent::CursorMoved(UIPos::new(500.0, 500.0)));
+        ctx.main_ctx.test_capture.take(name);
+
+        let removed = stack.remove(ctx, widgets[1].id());
+        assert_true(removed.is_some(), "hovered child removed")?;
+        let log = ctx.main_ctx.test_capture.take_text(name);
+        assert_equals(
+            log.trim(),
+            r"
+cursor - CursorExited - 5
+cursor - CursorEntered - 3
+cursor - CursorMoved(UIPos { x: 150.0, y: 150.0 }) - 3"
+                .trim(),
+            "hover moved to the uncovered child",
+        )?;
+        assert_true(
+            stack.remove(ctx, widgets[1].id()).is_none(),
+            "child removed twice",
+        )
+    }
+
+    fn test_remove_focused(ctx: &mut EventContext, name: &str) -> TestResult {
+        if ctx.main_ctx.focused_widget.is_some() {
+            // focus is owned by some other widget, nothing to check
+            return Ok(());
+        }
+
+        let stack = Arc::new(Stack::new());
+        let inner = Arc::new(Stack::new());
+        let widget = TestWidgetBuilder::new().build(0, name.to_owned(), false, false, false);
+        inner.push_arc(widget.clone(), CENTER);
+        stack.push_arc(inner.clone(), CENTER);
+        exact_layout(&stack);
+
+        ctx.main_ctx.set_focus_widget(Some(widget.clone()));
+        stack.remove(ctx, inner.id());
+        ctx.main_ctx.test_capture.take(name);
+        assert_true(
+            ctx.main_ctx.get_focused_widget().is_none(),
+            "focus released with the removed subtree",
+        )
+    }
+}
+
+mod scripted_tests {
+    use std::{sync::Arc, time::Duration};
+
+    use winit::{
+        dpi::PhysicalSize,
+        event::{ElementState, Event, MouseButton, WindowEvent},
+    };
+
+    use crate::{
+        enclose,
+        events::GameUserEvent,
+        exec::main_ctx::MainContext,
+        scene::{
+            main::test::ui::{GenericTestWidget, GenericTestWidgetBuilder},
+            SceneContainer,
+        },
+        test::{
+            assert::{assert_equals, assert_equals_err},
+            input::{synthetic_device_id, InputAction, InputDriver, InputScript},
+            result::TestResult,
+            tree::ParentTestNode,
+        },
+        ui::{
+            containers::stack::Stack,
+            event::{UICursorEvent, UIPropagatingEvent},
+            utils::geom::{UIPos, UIRect, UISize},
+            Alignment, EventContext, HorizontalAlignment, UISizeConstraint, VerticalAlignment,
+            Widget,
+        },
+        utils::mutex::Mutex,
+    };
+
+    const SCRIPT_SIZE: PhysicalSize<u32> = PhysicalSize::new(400, 300);
+    const WIDGET_SIZE: UISize = UISize::new(100.0, 50.0);
+
+    type RecordingWidget = GenericTestWidget<Mutex<Vec<String>>>;
+
+    fn recording_widget(test_id: usize) -> Arc<RecordingWidget> {
+        GenericTestWidgetBuilder::new(test_id, Mutex::new(Vec::new()))
+            .layout(|slf, size| {
+                let size = WIDGET_SIZE.clamp(&size.min, &size.max);
+                slf.bounds.lock().size = size;
+                size
+            })
+            .handle_cursor_event(|slf, _, event| {
+                if !matches!(event, UICursorEvent::CursorMoved(_)) {
+                    slf.data.lock().push(format!("{event:?}"));
+                }
+                Some(event)
+            })
+            .handle_propagating_event(|slf, _, event| {
+                slf.data.lock().push(format!("{event:?}"));
+                None
+            })
+            .build()
+    }
+
+    /// A stack laid out to the window size, driven by a script resizing the
+    /// window, then pressing and releasing the mouse over the widget in its
+    /// bottom right corner.
+    pub(super) fn new(
+        main_ctx: &mut MainContext,
+        node: &Arc<ParentTestNode>,
+    ) -> anyhow::Result<SceneContainer> {
+        let node = node.new_child_leaf("stack_scripted");
+        if !node.enabled() {
+            return Ok(SceneContainer::new());
+        }
+        let stack = Arc::new(Stack::new());
+        let other = recording_widget(0);
+        let target = recording_widget(1);
+        stack.push_arc(
+            other.clone(),
+            Alignment::new(HorizontalAlignment::Left, VerticalAlignment::Top),
+        );
+        stack.push_arc(
+            target.clone(),
+            Alignment::new(HorizontalAlignment::Right, VerticalAlignment::Bottom),
+        );
+        let ui_size = Arc::new(Mutex::new(None));
+
+        let mut container = SceneContainer::new();
+        container.push_event_handler(enclose!((stack, ui_size) move |main_ctx, _, event| {
+            match &event {
+                Event::UserEvent(GameUserEvent::CheckedResize { ui_size: size, .. }) => {
+                    stack.layout(&UISizeConstraint::exact(*size));
+                    *ui_size.lock() = Some(*size);
+                }
+                Event::WindowEvent { window_id, event }
+                    if *window_id == main_ctx.display.get_window_id() =>
+                {
+                    route_event(main_ctx, &stack, event);
+                }
+                _ => {}
+            }
+            Some(event)
+        }));
+
+        let driver = InputDriver::new(main_ctx);
+        let window_size = main_ctx.display.get_size();
+        let expected_size = SCRIPT_SIZE
+            .to_logical::<f32>(main_ctx.display.get_scale_factor())
+            .into();
+        let script = InputScript::new()
+            .at(Duration::ZERO, InputAction::Resize(SCRIPT_SIZE))
+            .at(
+                Duration::from_millis(50),
+                InputAction::MoveToWidget(stack.clone(), target.id()),
+            )
+            .at(
+                Duration::from_millis(100),
+                InputAction::Press(MouseButton::Left),
+            )
+            .at(
+                Duration::from_millis(150),
+                InputAction::Release(MouseButton::Left),
+            );
+        let played = driver.play(main_ctx, script)?;
+        main_ctx.spawn_local(async move {
+            let result: TestResult = async {
+                played.await?;
+                let result = check(&target, &other, *ui_size.lock(), expected_size);
+                // the other scenes are drawn at the real size
+                driver.resize(window_size)?.await?;
+                result
+            }
+            .await;
+            node.update(result);
+            Ok(())
+        });
+
+        Ok(container)
+    }
+
+    fn check(
+        target: &RecordingWidget,
+        other: &RecordingWidget,
+        ui_size: Option<UISize>,
+        expected_size: UISize,
+    ) -> TestResult {
+        assert_equals_err(
+            &ui_size.unwrap_or(UISize::ZERO),
+            &expected_size,
+            "ui size after the resize",
+        )?;
+        assert_equals_err(
+            &target.get_bounds(),
+            &UIRect::new(
+                UIPos::new(
+                    expected_size.width - WIDGET_SIZE.width,
+                    expected_size.height - WIDGET_SIZE.height,
+                ),
+                WIDGET_SIZE,
+            ),
+            "target laid out in the bottom right corner",
+        )?;
+        let mouse_input = |state| UIPropagatingEvent::MouseInput {
+            state,
+            button: MouseButton::Left,
+        };
+        assert_equals(
+            &*target.data.lock(),
+            &vec![
+                format!("{:?}", UICursorEvent::CursorEntered),
+                format!("{:?}", mouse_input(ElementState::Pressed)),
+                format!("{:?}", mouse_input(ElementState::Released)),
+            ],
+            "events received by the target widget",
+        )?;
+        assert_equals(
+            &*other.data.lock(),
+            &Vec::<String>::new(),
+            "events received by the other widget",
+        )
+    }
+
+    fn route_event(main_ctx: &mut MainContext, stack: &Arc<Stack>, event: &WindowEvent) {
+        let mut ctx = EventContext { main_ctx };
+        match event {
+            WindowEvent::CursorMoved {
+                device_id,
+                position,
+                ..
+            } if *device_id == synthetic_device_id() => {
+                let scale_factor = ctx.main_ctx.display.get_scale_factor();
+                stack.clone().handle_cursor_event(
+                    &mut ctx,
+                    UICursorEvent::CursorMoved(position.to_logical::<f32>(scale_factor).into()),
+                );
+            }
+            WindowEvent::MouseInput {
+                device_id,
+                state,
+                button,
+                ..
+            } if *device_id == synthetic_device_id() => {
+                stack.clone().handle_propagating_event(
+                    &mut ctx,
+                    UIPropagatingEvent::MouseInput {
+                        state: *state,
+                        button: *button,
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+}
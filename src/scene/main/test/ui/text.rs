@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use glam::Vec4;
+
+use crate::{
+    exec::main_ctx::MainContext,
+    graphics::text::Font,
+    test::{
+        assert::{assert_equals, assert_equals_err, assert_true},
+        result::TestResult,
+        tree::ParentTestNode,
+    },
+    ui::{
+        controls::{label::Label, paragraph::Paragraph},
+        utils::geom::UISize,
+        HorizontalAlignment, LayoutAxis, UISizeConstraint, Widget,
+    },
+};
+
+const TEXT_SIZE: f32 = 16.0;
+
+pub fn test(_: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("text_test");
+    let label_node = node.new_child_leaf("label");
+    let paragraph_node = node.new_child_leaf("paragraph");
+    let font = match Font::bundled() {
+        Ok(font) => font,
+        Err(e) => {
+            for leaf in [label_node, paragraph_node] {
+                leaf.update(Err(anyhow::format_err!("{e:?}").into()));
+            }
+            return Ok(());
+        }
+    };
+    label_node.update(test_label(&font));
+    paragraph_node.update(test_paragraph(&font));
+    Ok(())
+}
+
+fn unbounded_width(height: f32) -> UISizeConstraint {
+    UISizeConstraint::new(UISize::ZERO, UISize::new(f32::INFINITY, height))
+}
+
+fn test_label(font: &Font) -> TestResult {
+    let label = Label::new(font.clone(), "Hello", TEXT_SIZE).color(Vec4::ONE);
+    label.set_text("Hello world");
+    let natural = font.measure("Hello world", TEXT_SIZE);
+
+    let size = label.layout(&unbounded_width(100.0));
+    assert_equals_err(&size, &UISize::from(natural), "size of the whole text")?;
+    assert_equals(&label.shown_text(), &"Hello world".to_owned(), "text fits")?;
+    let min_width = label.min_intrinsic_size(LayoutAxis::Horizontal, f32::INFINITY);
+    let ellipsis = font.measure("\u{2026}", TEXT_SIZE).x;
+    assert_equals_err(&min_width, &ellipsis, "min intrinsic width")?;
+    let max_intrinsic = label.max_intrinsic_size(LayoutAxis::Horizontal, f32::INFINITY);
+    assert_equals_err(&max_intrinsic, &natural.x, "max intrinsic width")?;
+
+    let max_width = natural.x * 0.6;
+    let size = label.layout(&UISizeConstraint::new(
+        UISize::ZERO,
+        UISize::new(max_width, 100.0),
+    ));
+    let shown = label.shown_text();
+    assert_true(
+        shown.ends_with('\u{2026}') && shown.len() < "Hello world…".len(),
+        format!("text cut with an ellipsis, shown: {shown:?}"),
+    )?;
+    assert_true(
+        size.width <= max_width && font.measure(&shown, TEXT_SIZE).x <= max_width,
+        format!("ellipsized text fits, width: {}", size.width),
+    )?;
+
+    label.layout(&UISizeConstraint::new(
+        UISize::ZERO,
+        UISize::new(1.0, 100.0),
+    ));
+    assert_equals(&label.shown_text(), &String::new(), "nothing fits")
+}
+
+fn test_paragraph(font: &Font) -> TestResult {
+    let paragraph = Paragraph::new(font.clone(), "", TEXT_SIZE)
+        .color(Vec4::ONE)
+        .alignment(HorizontalAlignment::Right)
+        .line_spacing(1.5);
+    paragraph.set_text("aaa bbb  ccc\ndd");
+    let first_width = font.measure("aaa bbb", TEXT_SIZE).x;
+    let line_advance = font.line_height(TEXT_SIZE) * 1.5;
+
+    let size = paragraph.layout(&UISizeConstraint::new(
+        UISize::ZERO,
+        UISize::new(first_width + 0.5, 1000.0),
+    ));
+    assert_equals_err(
+        &size,
+        &UISize::new(first_width, 3.0 * line_advance),
+        "wrapped size",
+    )?;
+    let lines = paragraph.lines();
+    let texts = lines
+        .iter()
+        .map(|(text, _)| text.as_str())
+        .collect::<Vec<_>>();
+    assert_equals(&texts, &vec!["aaa bbb", "ccc", "dd"], "wrapped lines")?;
+    let ccc_x = first_width - font.measure("ccc", TEXT_SIZE).x;
+    assert_equals_err(&lines[1].1, &ccc_x, "right aligned line")?;
+
+    let min_width = paragraph.min_intrinsic_size(LayoutAxis::Horizontal, f32::INFINITY);
+    let longest_word = ["aaa", "bbb", "ccc", "dd"]
+        .into_iter()
+        .map(|word| font.measure(word, TEXT_SIZE).x)
+        .fold(0.0, f32::max);
+    assert_equals_err(&min_width, &longest_word, "min intrinsic width")?;
+    let max_width = paragraph.max_intrinsic_size(LayoutAxis::Horizontal, f32::INFINITY);
+    let unwrapped = font.measure("aaa bbb ccc", TEXT_SIZE).x;
+    assert_equals_err(&max_width, &unwrapped, "max intrinsic width")?;
+    let height = paragraph.max_intrinsic_size(LayoutAxis::Vertical, unwrapped);
+    assert_equals_err(&height, &(2.0 * line_advance), "height unwrapped")
+}
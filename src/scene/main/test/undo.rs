@@ -0,0 +1,145 @@
+use std::{borrow::Cow, sync::Arc};
+
+use crate::{
+    exec::main_ctx::MainContext,
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    utils::undo::{Command, History, HistoryAction},
+};
+
+struct Push(i32);
+
+struct Add {
+    index: usize,
+    delta: i32,
+}
+
+impl Command<Vec<i32>> for Push {
+    fn name(&self) -> Cow<'static, str> {
+        format!("push {}", self.0).into()
+    }
+
+    fn apply(&mut self, target: &mut Vec<i32>) -> anyhow::Result<()> {
+        target.push(self.0);
+        Ok(())
+    }
+
+    fn undo(&mut self, target: &mut Vec<i32>) -> anyhow::Result<()> {
+        target.pop();
+        Ok(())
+    }
+}
+
+impl Command<Vec<i32>> for Add {
+    fn name(&self) -> Cow<'static, str> {
+        format!("add {} to #{}", self.delta, self.index).into()
+    }
+
+    fn apply(&mut self, target: &mut Vec<i32>) -> anyhow::Result<()> {
+        let value = target
+            .get_mut(self.index)
+            .ok_or_else(|| anyhow::format_err!("index {} out of range", self.index))?;
+        *value += self.delta;
+        Ok(())
+    }
+
+    fn undo(&mut self, target: &mut Vec<i32>) -> anyhow::Result<()> {
+        target[self.index] -= self.delta;
+        Ok(())
+    }
+
+    fn merge(&mut self, next: &dyn Command<Vec<i32>>) -> bool {
+        match next.as_any().downcast_ref::<Self>() {
+            Some(next) if next.index == self.index => {
+                self.delta += next.delta;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+pub fn test(_: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("undo");
+    node.new_child_leaf("undo_redo").update(test_undo_redo());
+    node.new_child_leaf("merge").update(test_merge());
+    node.new_child_leaf("limit").update(test_limit());
+    Ok(())
+}
+
+fn test_undo_redo() -> TestResult {
+    let mut doc = Vec::new();
+    let mut history = History::default();
+    history.execute(&mut doc, Push(1))?;
+    history.execute(&mut doc, Push(2))?;
+    assert_equals(&history.undo(&mut doc)?, &true, "undo succeeds")?;
+    assert_equals(&doc, &vec![1], "undo reverts the last command")?;
+    history.perform(&mut doc, HistoryAction::Redo)?;
+    assert_equals(&doc, &vec![1, 2], "redo reapplies it")?;
+
+    history.undo(&mut doc)?;
+    history.execute(&mut doc, Push(3))?;
+    assert_equals(&history.can_redo(), &false, "executing clears redo")?;
+    assert_equals(
+        &history.undo_names(),
+        &vec![Cow::from("push 3"), Cow::from("push 1")],
+        "undo names",
+    )?;
+
+    assert_equals(
+        &history
+            .execute(&mut doc, Add { index: 5, delta: 1 })
+            .is_err(),
+        &true,
+        "failing command",
+    )?;
+    assert_equals(
+        &history.undo_names().len(),
+        &2,
+        "failed command not recorded",
+    )?;
+
+    history.undo(&mut doc)?;
+    history.undo(&mut doc)?;
+    assert_equals(&history.undo(&mut doc)?, &false, "nothing left to undo")?;
+    assert_equals(&doc, &Vec::new(), "fully undone")?;
+    Ok(())
+}
+
+fn test_merge() -> TestResult {
+    let mut doc = vec![0, 0];
+    let mut history = History::default();
+    history.execute(&mut doc, Add { index: 0, delta: 1 })?;
+    history.execute(&mut doc, Add { index: 0, delta: 2 })?;
+    history.execute(&mut doc, Add { index: 1, delta: 5 })?;
+    assert_equals(&history.undo_names().len(), &2, "same index merged")?;
+
+    history.seal();
+    history.execute(&mut doc, Add { index: 1, delta: 5 })?;
+    assert_equals(&history.undo_names().len(), &3, "sealed history not merged")?;
+
+    history.undo(&mut doc)?;
+    history.undo(&mut doc)?;
+    assert_equals(&doc, &vec![3, 0], "merged commands are undone together")?;
+    history.undo(&mut doc)?;
+    assert_equals(&doc, &vec![0, 0], "fully undone")?;
+    Ok(())
+}
+
+fn test_limit() -> TestResult {
+    let mut doc = Vec::new();
+    let mut history = History::new(3);
+    for i in 0..5 {
+        history.execute(&mut doc, Push(i))?;
+    }
+    assert_equals(&history.undo_names().len(), &3, "history trimmed")?;
+    while history.undo(&mut doc)? {}
+    assert_equals(&doc, &vec![0, 1], "oldest commands can't be undone")?;
+
+    history.set_limit(1);
+    assert_equals(&history.undo_names().len(), &0, "undo stack empty")?;
+    history.redo(&mut doc)?;
+    history.redo(&mut doc)?;
+    assert_equals(&history.undo_names().len(), &1, "limit applies to redo")?;
+    assert_equals(&doc, &vec![0, 1, 2, 3], "redone")?;
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use gl::types::{GLint, GLuint};
+use glam::{Mat3, Mat4, Vec2, Vec3};
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{
+        uniform::{impl_std140, FrameData, Std140Writer, FRAME_DATA_BINDING},
+        wrappers::shader::ProgramHandle,
+    },
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+};
+
+const VERTEX: &str = r#"
+#version 300 es
+
+layout(std140) uniform FrameData {
+    mat4 ui_projection;
+    vec2 ui_size;
+    vec2 resolution;
+    float time;
+};
+
+layout(std140) uniform TestBlock {
+    vec3 position;
+    float radius;
+    float weights[2];
+    vec2 offset;
+    mat3 rotation;
+};
+
+void main() {
+    vec3 pos = rotation * position * radius + vec3(offset, weights[0] + weights[1]);
+    gl_Position = ui_projection * vec4(pos.xy, 0.0, 1.0) + vec4(ui_size, resolution) * time;
+}
+"#;
+
+const FRAGMENT: &str = r#"
+#version 300 es
+precision mediump float;
+
+out vec4 color;
+
+void main() {
+    color = vec4(1.0);
+}
+"#;
+
+/// Mirror of `TestBlock`.
+struct TestBlock {
+    position: Vec3,
+    radius: f32,
+    weights: [f32; 2],
+    offset: Vec2,
+    rotation: Mat3,
+}
+
+impl_std140!(TestBlock {
+    position,
+    radius,
+    weights,
+    offset,
+    rotation,
+});
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("uniform");
+    let draw = &mut main_ctx.channels.draw;
+    let program = ProgramHandle::new_vf(draw, "uniform test program", VERTEX, FRAGMENT)
+        .context("unable to create uniform test program")?;
+    let query = draw
+        .query(move |ctx, _| {
+            let program = *program.get(ctx);
+            let bound = ctx.frame_uniforms.bind_block(program);
+            ctx.upload_frame_data(true);
+            let mut buffer = 0;
+            unsafe {
+                gl::GetIntegeri_v(gl::UNIFORM_BUFFER_BINDING, FRAME_DATA_BINDING, &mut buffer)
+            };
+            let frame_data = (bound, buffer != 0, block_size(program, "FrameData\0"));
+            let test_block = (
+                block_size(program, "TestBlock\0"),
+                uniform_offset(program, "offset\0"),
+                uniform_offset(program, "rotation\0"),
+            );
+            (frame_data, test_block)
+        })
+        .context("unable to query uniform test")?;
+
+    let frame_data_node = node.new_child_leaf("frame_data");
+    let layout_node = node.new_child_leaf("std140_layout");
+    main_ctx.spawn_local(async move {
+        let (frame_data, test_block) = query.await?;
+        frame_data_node.update(check_frame_data(frame_data));
+        layout_node.update(check_layout(test_block));
+        Ok(())
+    });
+    Ok(())
+}
+
+fn block_size(program: GLuint, name: &str) -> GLint {
+    let mut size = 0;
+    unsafe {
+        let index = gl::GetUniformBlockIndex(program, name.as_ptr() as *const _);
+        if index != gl::INVALID_INDEX {
+            gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut size);
+        }
+    }
+    size
+}
+
+fn uniform_offset(program: GLuint, name: &str) -> GLint {
+    let mut offset = -1;
+    unsafe {
+        let names = [name.as_ptr() as *const _];
+        let mut index = gl::INVALID_INDEX;
+        gl::GetUniformIndices(program, 1, names.as_ptr(), &mut index);
+        if index != gl::INVALID_INDEX {
+            gl::GetActiveUniformsiv(program, 1, &index, gl::UNIFORM_OFFSET, &mut offset);
+        }
+    }
+    offset
+}
+
+/// Drivers may leave out the padding at the end of the block.
+fn padded(size: GLint) -> usize {
+    (size as usize + 15) / 16 * 16
+}
+
+fn check_frame_data((bound, uploaded, size): (bool, bool, GLint)) -> TestResult {
+    assert_equals(&bound, &true, "FrameData block bound")?;
+    assert_equals(&uploaded, &true, "frame data buffer bound")?;
+    let mut writer = Std140Writer::new();
+    writer.push(&FrameData {
+        ui_projection: Mat4::ZERO,
+        ui_size: Vec2::ZERO,
+        resolution: Vec2::ZERO,
+        time: 0.0,
+    });
+    assert_equals(&padded(size), &writer.finish().len(), "FrameData size")
+}
+
+fn check_layout((size, offset, rotation): (GLint, GLint, GLint)) -> TestResult {
+    let mut writer = Std140Writer::new();
+    writer.push(&TestBlock {
+        position: Vec3::ZERO,
+        radius: 0.0,
+        weights: [0.0; 2],
+        offset: Vec2::ZERO,
+        rotation: Mat3::IDENTITY,
+    });
+    assert_equals(&padded(size), &writer.finish().len(), "TestBlock size")?;
+    // the array elements take 16 bytes each, the matrix is aligned to 16
+    assert_equals(&(offset, rotation), &(64, 80), "TestBlock offsets")
+}
@@ -0,0 +1,124 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{
+        main_ctx::MainContext,
+        server::{draw::ServerSendChannelExt, GameServerSendChannel},
+    },
+    graphics::{
+        context::DrawContext,
+        wrappers::{
+            buffer::{BufferHandle, BufferTarget},
+            texture::{ImageData, TextureHandle, TextureOptions},
+        },
+    },
+    test::{assert::assert_equals, result::TestResult, tree::ParentTestNode},
+    utils::mutex::Mutex,
+};
+
+use super::{buffer::read_buffer, texture::read_pixels};
+
+/// Four steps of the 256 KiB upload chunks.
+const SLICED_LEN: usize = 3 * 64 * 1024 + 5;
+const IMAGE_SIZE: PhysicalSize<u32> = PhysicalSize::new(256, 600);
+
+pub fn test(main_ctx: &mut MainContext, node: &Arc<ParentTestNode>) -> anyhow::Result<()> {
+    let node = node.new_child_parent("upload");
+    let draw = &mut main_ctx.channels.draw;
+    let buffer = BufferHandle::with_data(
+        draw,
+        "sliced upload test buffer",
+        BufferTarget::ArrayBuffer,
+        vec![7u32; 4],
+    )
+    .context("unable to create sliced upload test buffer")?;
+    let staging = unsafe { BufferHandle::new_uninit(draw) };
+    let data = (0..SLICED_LEN as u32).collect::<Vec<_>>();
+    let steps_query = draw
+        .query({
+            let buffer = buffer.clone();
+            move |ctx, _| {
+                let result = Arc::new(Mutex::new(None));
+                let bytes = bytemuck::cast_slice::<u32, u8>(&data).to_vec();
+                ctx.uploads.push_buffer(buffer.clone(), staging, bytes, {
+                    let result = result.clone();
+                    move |_, upload_result: anyhow::Result<()>| {
+                        *result.lock() = Some(upload_result.is_ok());
+                    }
+                });
+                let read = |ctx: &DrawContext| buffer.try_get(ctx).map(|b| read_buffer(&b));
+                // other uploads may be ahead of this one
+                let mut steps = Vec::new();
+                while result.lock().is_none() {
+                    steps.push(read(ctx) == Some(vec![7; 4]));
+                    ctx.run_uploads(Duration::ZERO);
+                }
+                let uploaded = *result.lock() == Some(true) && read(ctx) == Some(data);
+                (steps, uploaded)
+            }
+        })
+        .context("unable to query sliced upload test")?;
+
+    let pixels = (0..IMAGE_SIZE.width * IMAGE_SIZE.height)
+        .flat_map(|i| [(i % 251) as u8, (i / 251 % 251) as u8, 0, 255])
+        .collect::<Vec<_>>();
+    let image = ImageData::new(IMAGE_SIZE, pixels.clone())?.options(TextureOptions {
+        mipmaps: false,
+        ..Default::default()
+    });
+    let placeholder = ImageData::new(IMAGE_SIZE, vec![0; pixels.len()])?;
+    let texture = TextureHandle::new(draw, "sliced upload test texture", placeholder)
+        .context("unable to create sliced upload test texture")?;
+    let texture_upload = texture
+        .upload_sliced(draw, image)
+        .context("unable to upload sliced test texture")?;
+    let buffer_upload = buffer
+        .upload_sliced(draw, vec![1u32, 2, 3])
+        .context("unable to upload sliced test buffer")?;
+
+    let steps_node = node.new_child_leaf("steps");
+    let texture_node = node.new_child_leaf("texture");
+    let buffer_node = node.new_child_leaf("buffer");
+    let draw = draw.clone_sender();
+    main_ctx.spawn_local(async move {
+        let (steps, uploaded) = steps_query.await?;
+        steps_node.update(check_steps(&steps, uploaded));
+
+        texture_upload.await??;
+        buffer_upload.await??;
+        let (texture_pixels, buffer_data) = draw
+            .query(move |ctx, _| {
+                (
+                    texture.try_get(ctx).map(|t| read_pixels(&t, IMAGE_SIZE)),
+                    buffer.try_get(ctx).map(|b| read_buffer(&b)),
+                )
+            })
+            .context("unable to query sliced upload results")?
+            .await?;
+        texture_node.update(assert_equals(
+            &(texture_pixels.as_ref() == Some(&pixels)),
+            &true,
+            "pixels of the sliced texture upload",
+        ));
+        buffer_node.update(assert_equals(
+            &buffer_data,
+            &Some(vec![1, 2, 3]),
+            "contents of the sliced buffer upload",
+        ));
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn check_steps(steps: &[bool], uploaded: bool) -> TestResult {
+    assert_equals(
+        &steps.iter().rev().take(4).copied().collect::<Vec<_>>(),
+        &vec![true; 4],
+        "old contents kept during the last steps of the upload",
+    )?;
+    assert_equals(&uploaded, &true, "buffer contents after the upload")
+}
@@ -1,22 +1,32 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use anyhow::Context;
 use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 
 use crate::{
     events::GameEvent,
-    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    exec::main_ctx::MainContext,
     scene::{main::RootScene, Scene},
     utils::error::ResultExt,
 };
 
+/// Periodically logs the frame pacing stats of every runner, toggled with
+/// the Q key.
 pub struct FreqProfile {
     current_freq_profile: AtomicBool,
+    // invalidates report timers scheduled before the last toggle
+    generation: AtomicU64,
 }
 
 impl Scene for FreqProfile {
     fn handle_event<'a>(
-        self: std::sync::Arc<Self>,
+        self: Arc<Self>,
         ctx: &mut MainContext,
         _: &RootScene,
         event: GameEvent<'a>,
@@ -48,31 +58,47 @@ impl Scene for FreqProfile {
 }
 
 impl FreqProfile {
+    const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
     pub fn new() -> Self {
         Self {
             current_freq_profile: AtomicBool::new(false),
+            generation: AtomicU64::new(0),
         }
     }
 
-    pub fn toggle(&self, main_ctx: &mut MainContext) -> anyhow::Result<()> {
+    pub fn toggle(self: Arc<Self>, main_ctx: &mut MainContext) -> anyhow::Result<()> {
         let current_freq_profile = !self.current_freq_profile.load(Ordering::Relaxed);
         self.current_freq_profile
             .store(current_freq_profile, Ordering::Relaxed);
-        main_ctx
-            .channels
-            .update
-            .set_frequency_profiling(current_freq_profile)?;
-        main_ctx
-            .channels
-            .draw
-            .set_frequency_profiling(current_freq_profile)?;
-        main_ctx
-            .channels
-            .audio
-            .set_frequency_profiling(current_freq_profile)?;
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::info!("frequency profiling enabled: {}", current_freq_profile);
+
+        if current_freq_profile {
+            main_ctx.reset_runner_stats();
+            self.schedule_report(main_ctx, generation)?;
+        }
 
         Ok(())
     }
+
+    fn schedule_report(
+        self: Arc<Self>,
+        main_ctx: &mut MainContext,
+        generation: u64,
+    ) -> anyhow::Result<()> {
+        main_ctx.set_timeout(Self::REPORT_INTERVAL, move |main_ctx, _| {
+            if self.generation.load(Ordering::Relaxed) != generation {
+                return Ok(());
+            }
+
+            for (id, stats) in main_ctx.runner_stats() {
+                tracing::info!("runner {}: {}", id, stats);
+            }
+            main_ctx.reset_runner_stats();
+            self.schedule_report(main_ctx, generation)
+        })
+    }
 }
 
 impl Default for FreqProfile {
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    events::GameEvent,
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::HandleInfo,
+    scene::{main::RootScene, Scene},
+    utils::error::ResultExt,
+};
+
+/// Logs every live GL object with its estimated size with the F9 key, to
+/// find out which subsystem leaks them.
+pub struct HandleDump;
+
+impl Scene for HandleDump {
+    fn handle_event<'a>(
+        self: Arc<Self>,
+        ctx: &mut MainContext,
+        _: &RootScene,
+        event: GameEvent<'a>,
+    ) -> Option<GameEvent<'a>> {
+        match &event {
+            Event::WindowEvent {
+                window_id,
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Released,
+                                virtual_keycode: Some(VirtualKeyCode::F9),
+                                ..
+                            },
+                        ..
+                    },
+            } if ctx.display.get_window_id() == *window_id => {
+                self.dump(ctx)
+                    .context("unable to dump GL handles")
+                    .log_warn();
+            }
+
+            _ => {}
+        };
+
+        Some(event)
+    }
+}
+
+impl HandleDump {
+    pub fn dump(&self, main_ctx: &mut MainContext) -> anyhow::Result<()> {
+        let infos = main_ctx
+            .channels
+            .draw
+            .query(|context, _| context.handles.handle_infos())?;
+        main_ctx.spawn_local(async move {
+            tracing::info!("{}", HandleInfo::dump(infos.await?));
+            Ok(())
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use anyhow::Context;
+use glam::{Vec2, Vec4};
+use winit::event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    events::GameEvent,
+    exec::main_ctx::MainContext,
+    graphics::{context::DrawContext, text::Font},
+    scene::{main::RootScene, Scene},
+    utils::{
+        error::ResultExt,
+        mutex::Mutex,
+        property::{Property, PropertyRegistry, PropertyValue},
+    },
+};
+
+const MARGIN: f32 = 10.0;
+const PADDING: f32 = 8.0;
+const ROW_HEIGHT: f32 = 24.0;
+const LABEL_WIDTH: f32 = 150.0;
+const CONTROL_WIDTH: f32 = 110.0;
+const VALUE_WIDTH: f32 = 50.0;
+const TEXT_SIZE: f32 = 14.0;
+const CHECKBOX_SIZE: f32 = 14.0;
+
+/// Lists the debug properties registered by the scenes, with a slider or a
+/// checkbox editing each of them, toggled with the I key.
+pub struct Inspector {
+    properties: PropertyRegistry,
+    font: Option<Font>,
+    visible: AtomicBool,
+    cursor: Mutex<Vec2>,
+    /// float property whose slider is being dragged
+    dragging: Mutex<Option<Property>>,
+}
+
+impl Scene for Inspector {
+    fn handle_event<'a>(
+        self: Arc<Self>,
+        ctx: &mut MainContext,
+        _: &RootScene,
+        event: GameEvent<'a>,
+    ) -> Option<GameEvent<'a>> {
+        let window_event = match &event {
+            Event::WindowEvent { window_id, event }
+                if ctx.display.get_window_id() == *window_id =>
+            {
+                event
+            }
+            _ => return Some(event),
+        };
+        match window_event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Released,
+                        virtual_keycode: Some(VirtualKeyCode::I),
+                        ..
+                    },
+                ..
+            } => {
+                self.visible.fetch_xor(true, Ordering::Relaxed);
+                *self.dragging.lock() = None;
+            }
+
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = position.to_logical::<f32>(ctx.display.get_scale_factor());
+                let cursor = Vec2::new(position.x, position.y);
+                *self.cursor.lock() = cursor;
+                let dragging = self.dragging.lock().clone();
+                if let Some(property) = dragging {
+                    let row = self
+                        .properties
+                        .properties()
+                        .iter()
+                        .position(|p| p.name() == property.name());
+                    if let Some(row) = row {
+                        self.edit(ctx, &property, cursor, row);
+                    }
+                }
+            }
+
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } if self.visible.load(Ordering::Relaxed) => {
+                let released = *state == ElementState::Released;
+                if released {
+                    let dragged = self.dragging.lock().take().is_some();
+                    return (!dragged).then_some(event);
+                }
+                let cursor = *self.cursor.lock();
+                let properties = self.properties.properties();
+                if let Some(row) = hit_test(cursor, properties.len()) {
+                    let property = &properties[row];
+                    if let PropertyValue::Float { .. } = property.value() {
+                        *self.dragging.lock() = Some(property.clone());
+                    }
+                    self.edit(ctx, property, cursor, row);
+                }
+                let [min, max] = panel_rect(properties.len());
+                if in_rect(cursor, min, max) {
+                    return None;
+                }
+            }
+
+            _ => {}
+        }
+
+        Some(event)
+    }
+
+    fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
+        if !self.visible.load(Ordering::Relaxed) {
+            return;
+        }
+        let properties = self.properties.properties();
+        let [min, max] = panel_rect(properties.len());
+        ctx.draw_rounded_rect(min, max, 6.0, Vec4::new(0.0, 0.0, 0.0, 0.7));
+        let white = Vec4::ONE;
+        let gray = Vec4::new(0.5, 0.5, 0.5, 1.0);
+        for (row, property) in properties.iter().enumerate() {
+            let [min, max] = control_rect(row);
+            let center_y = (min.y + max.y) * 0.5;
+            let text_y = center_y - TEXT_SIZE * 0.5;
+            let value = match property.value() {
+                PropertyValue::Float {
+                    value,
+                    min: low,
+                    max: high,
+                } => {
+                    let ratio = if high > low {
+                        (value - low) / (high - low)
+                    } else {
+                        0.0
+                    };
+                    let knob = Vec2::new(min.x + ratio * (max.x - min.x), center_y);
+                    ctx.draw_line(
+                        Vec2::new(min.x, center_y),
+                        Vec2::new(max.x, center_y),
+                        2.0,
+                        gray,
+                    );
+                    ctx.draw_circle(knob, 6.0, white);
+                    Some(format!("{value:.2}"))
+                }
+                PropertyValue::Bool(value) => {
+                    let box_min = Vec2::new(min.x, center_y - CHECKBOX_SIZE * 0.5);
+                    let box_max = box_min + Vec2::splat(CHECKBOX_SIZE);
+                    ctx.draw_rounded_rect(box_min, box_max, 3.0, gray);
+                    if value {
+                        let inset = Vec2::splat(3.0);
+                        ctx.draw_rounded_rect(box_min + inset, box_max - inset, 2.0, white);
+                    }
+                    None
+                }
+            };
+            if let Some(font) = &self.font {
+                let label = Vec2::new(MARGIN + PADDING, text_y);
+                ctx.draw_text(font, property.name(), label, TEXT_SIZE, white);
+                if let Some(value) = value {
+                    let position = Vec2::new(max.x + PADDING, text_y);
+                    ctx.draw_text(font, &value, position, TEXT_SIZE, white);
+                }
+            }
+        }
+    }
+}
+
+impl Inspector {
+    pub fn new(main_ctx: &mut MainContext) -> Self {
+        Self {
+            properties: main_ctx.properties.clone(),
+            font: Font::system_default()
+                .context("unable to load a font, inspector labels won't be drawn")
+                .log_warn(),
+            visible: AtomicBool::new(false),
+            cursor: Mutex::new(Vec2::ZERO),
+            dragging: Mutex::new(None),
+        }
+    }
+
+    /// Sets `property` from the cursor position over the control of `row`.
+    fn edit(&self, ctx: &mut MainContext, property: &Property, cursor: Vec2, row: usize) {
+        let changed = match property.value() {
+            PropertyValue::Float { min, max, .. } => {
+                let [control_min, control_max] = control_rect(row);
+                let ratio =
+                    ((cursor.x - control_min.x) / (control_max.x - control_min.x)).clamp(0.0, 1.0);
+                property.set_f32(min + ratio * (max - min))
+            }
+            PropertyValue::Bool(value) => property.set_bool(!value),
+        };
+        if changed {
+            property
+                .publish(&ctx.event_bus)
+                .context("unable to publish property change")
+                .log_warn();
+        }
+    }
+}
+
+fn panel_rect(rows: usize) -> [Vec2; 2] {
+    let min = Vec2::splat(MARGIN);
+    let size = Vec2::new(
+        LABEL_WIDTH + CONTROL_WIDTH + VALUE_WIDTH + PADDING * 3.0,
+        rows as f32 * ROW_HEIGHT + PADDING * 2.0,
+    );
+    [min, min + size]
+}
+
+/// Area of the slider or checkbox of `row`.
+fn control_rect(row: usize) -> [Vec2; 2] {
+    let min = Vec2::new(
+        MARGIN + PADDING + LABEL_WIDTH,
+        MARGIN + PADDING + row as f32 * ROW_HEIGHT,
+    );
+    [min, min + Vec2::new(CONTROL_WIDTH, ROW_HEIGHT)]
+}
+
+fn in_rect(point: Vec2, min: Vec2, max: Vec2) -> bool {
+    point.cmpge(min).all() && point.cmplt(max).all()
+}
+
+/// The row whose control is under `point`.
+fn hit_test(point: Vec2, rows: usize) -> Option<usize> {
+    (0..rows).find(|&row| {
+        let [min, max] = control_rect(row);
+        in_rect(point, min, max)
+    })
+}
+
+#[test]
+fn test() {
+    let [min, max] = control_rect(1);
+    assert_eq!(hit_test((min + max) * 0.5, 3), Some(1));
+    assert_eq!(hit_test((min + max) * 0.5, 1), None);
+    assert_eq!(hit_test(Vec2::splat(MARGIN + 1.0), 3), None);
+    let [panel_min, panel_max] = panel_rect(3);
+    assert!(in_rect(min, panel_min, panel_max) && in_rect(max - 1.0, panel_min, panel_max));
+}
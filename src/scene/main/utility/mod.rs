@@ -2,11 +2,18 @@ use anyhow::Context;
 
 use crate::{exec::main_ctx::MainContext, scene::SceneContainer};
 
-use self::{freq_profile::FreqProfile, update_delay_test::UpdateDelayTest, vsync::VSync};
+use self::{
+    freq_profile::FreqProfile, handle_dump::HandleDump, inspector::Inspector,
+    post_effects::PostEffects, screenshot::Screenshot, update_delay_test::UpdateDelayTest,
+    vsync::VSync,
+};
 
 pub mod close;
-pub mod error;
 pub mod freq_profile;
+pub mod handle_dump;
+pub mod inspector;
+pub mod post_effects;
+pub mod screenshot;
 pub mod update_delay_test;
 pub mod vsync;
 
@@ -15,7 +22,10 @@ pub fn new(main_ctx: &mut MainContext) -> anyhow::Result<SceneContainer> {
     container.push(VSync::new(main_ctx).context("unable to initialize VSync scene")?);
     container.push(FreqProfile::new());
     container.push(UpdateDelayTest::new());
+    container.push(Inspector::new(main_ctx));
+    container.push(PostEffects::new());
+    container.push(Screenshot);
+    container.push(HandleDump);
     container.push_event_handler(close::handle_event);
-    container.push_event_handler(error::handle_event);
     Ok(container)
 }
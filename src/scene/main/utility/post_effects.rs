@@ -0,0 +1,121 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use anyhow::Context;
+use glam::Vec3;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    events::GameEvent,
+    exec::main_ctx::MainContext,
+    graphics::{
+        blur::BlurEffect,
+        post_effect::{ChromaticAberration, ColorGrading, Vignette},
+    },
+    scene::{main::RootScene, Scene},
+    utils::error::ResultExt,
+};
+
+/// Names of the effects of every preset, in chain order.
+const PRESETS: &[&[&str]] = &[
+    &[],
+    &["vignette"],
+    &["chromatic aberration", "vignette"],
+    &["color grading", "vignette"],
+    &["blur"],
+];
+
+const LUT_SIZE: u32 = 16;
+
+/// Cycles through post effect presets with the P key.
+pub struct PostEffects {
+    preset: AtomicUsize,
+}
+
+impl Scene for PostEffects {
+    fn handle_event<'a>(
+        self: Arc<Self>,
+        ctx: &mut MainContext,
+        _: &RootScene,
+        event: GameEvent<'a>,
+    ) -> Option<GameEvent<'a>> {
+        match &event {
+            Event::WindowEvent {
+                window_id,
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Released,
+                                virtual_keycode: Some(VirtualKeyCode::P),
+                                ..
+                            },
+                        ..
+                    },
+            } if ctx.display.get_window_id() == *window_id => {
+                self.cycle(ctx)
+                    .context("unable to change post effect preset")
+                    .log_warn();
+            }
+
+            _ => {}
+        };
+
+        Some(event)
+    }
+}
+
+impl PostEffects {
+    pub fn new() -> Self {
+        Self {
+            preset: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn cycle(&self, main_ctx: &mut MainContext) -> anyhow::Result<()> {
+        let old = self.preset.load(Ordering::Relaxed);
+        let new = (old + 1) % PRESETS.len();
+        self.preset.store(new, Ordering::Relaxed);
+        for name in PRESETS[old] {
+            main_ctx.remove_post_effect(*name)?;
+        }
+
+        let dummy_vao = main_ctx.dummy_vao();
+        let draw = &mut main_ctx.channels.draw;
+        match new {
+            1 => {
+                let vignette = Vignette::new(dummy_vao, draw)?;
+                main_ctx.insert_post_effect(usize::MAX, vignette)
+            }
+            2 => {
+                let aberration = ChromaticAberration::new(dummy_vao.clone(), draw)?;
+                let vignette = Vignette::new(dummy_vao, draw)?;
+                main_ctx.insert_post_effect(usize::MAX, aberration)?;
+                main_ctx.insert_post_effect(usize::MAX, vignette)
+            }
+            3 => {
+                // warm tint with a bit more contrast
+                let grading = ColorGrading::new(dummy_vao.clone(), draw, LUT_SIZE, |color| {
+                    let contrast = (color - 0.5) * 1.15 + 0.5;
+                    contrast * Vec3::new(1.08, 1.0, 0.88)
+                })?;
+                let vignette = Vignette::new(dummy_vao, draw)?;
+                main_ctx.insert_post_effect(usize::MAX, grading)?;
+                main_ctx.insert_post_effect(usize::MAX, vignette)
+            }
+            4 => {
+                let blur = BlurEffect::new(dummy_vao, draw, 4.0)?;
+                main_ctx.insert_post_effect(usize::MAX, blur)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for PostEffects {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    events::GameEvent,
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::screenshot,
+    scene::{main::RootScene, Scene},
+    utils::{args::args, error::ResultExt},
+};
+
+/// Writes the next frame to `--screenshot-dir` as a PNG file with the F12
+/// key, along with its frame graph as Graphviz and JSON files.
+pub struct Screenshot;
+
+impl Scene for Screenshot {
+    fn handle_event<'a>(
+        self: Arc<Self>,
+        ctx: &mut MainContext,
+        _: &RootScene,
+        event: GameEvent<'a>,
+    ) -> Option<GameEvent<'a>> {
+        match &event {
+            Event::WindowEvent {
+                window_id,
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Released,
+                                virtual_keycode: Some(VirtualKeyCode::F12),
+                                ..
+                            },
+                        ..
+                    },
+            } if ctx.display.get_window_id() == *window_id => {
+                self.capture(ctx)
+                    .context("unable to capture screenshot")
+                    .log_warn();
+            }
+
+            _ => {}
+        };
+
+        Some(event)
+    }
+}
+
+impl Screenshot {
+    pub fn capture(&self, main_ctx: &mut MainContext) -> anyhow::Result<()> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = args()
+            .screenshot_dir
+            .join(format!("screenshot-{millis}.png"));
+        let frame = main_ctx.channels.draw.capture_frame()?;
+        let frame_graph = main_ctx.channels.draw.query(|ctx, _| ctx.frame_graph())?;
+        let task_executor = main_ctx.task_executor.clone();
+        main_ctx.spawn_local(async move {
+            let (image, frame_graph) = (frame.await?, frame_graph.await?);
+            // encoding takes a while for large windows
+            task_executor.execute(move || {
+                frame_graph
+                    .save(&path)
+                    .context("unable to save frame graph")
+                    .log_warn();
+                if screenshot::save_png(image, &path)
+                    .context("unable to save screenshot")
+                    .log_warn()
+                    .is_some()
+                {
+                    tracing::info!("screenshot written to {}", path.display());
+                }
+            });
+            Ok(())
+        });
+        Ok(())
+    }
+}
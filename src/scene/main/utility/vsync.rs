@@ -1,24 +1,19 @@
-use std::{
-    num::NonZeroU32,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
-};
+use std::sync::Arc;
 
 use anyhow::Context;
-use glutin::surface::SwapInterval;
 use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 
 use crate::{
     events::GameEvent,
-    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    exec::{event_bus::Subscription, main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::present::PresentMode,
     scene::{main::RootScene, Scene},
-    utils::error::ResultExt,
+    utils::{config::ConfigChanged, error::ResultExt, mutex::Mutex},
 };
 
 pub struct VSync {
-    current_vsync: AtomicBool,
+    requested_mode: Arc<Mutex<PresentMode>>,
+    _config_subscription: Subscription,
 }
 
 impl Scene for VSync {
@@ -42,8 +37,8 @@ impl Scene for VSync {
                         ..
                     },
             } if ctx.display.get_window_id() == *window_id => {
-                self.toggle(ctx)
-                    .context("unable to toggle VSync mode")
+                self.cycle(ctx)
+                    .context("unable to change present mode")
                     .log_warn();
             }
 
@@ -56,33 +51,38 @@ impl Scene for VSync {
 
 impl VSync {
     pub fn new(main_ctx: &mut MainContext) -> anyhow::Result<Self> {
+        let mode = main_ctx.config.present_mode;
+        let requested_mode = Arc::new(Mutex::new(mode));
+        // a present mode change in the config file overrides the one cycled to
+        let config_subscription = main_ctx.event_bus.subscribe({
+            let requested_mode = requested_mode.clone();
+            move |main_ctx, _, change: &ConfigChanged| {
+                let mode = change.new.present_mode;
+                if mode != change.old.present_mode {
+                    *requested_mode.lock() = mode;
+                    main_ctx.channels.draw.set_present_mode(mode)?;
+                }
+                Ok(())
+            }
+        });
         let slf = Self {
-            current_vsync: AtomicBool::new(false),
+            requested_mode,
+            _config_subscription: config_subscription,
         };
-        slf.toggle(main_ctx)
-            .context("unable to reset vsync to default state")?; // current_mode is now true
+        slf.set(main_ctx, mode)
+            .context("unable to reset present mode to default state")?;
         Ok(slf)
     }
 
-    pub fn toggle(&self, main_ctx: &mut MainContext) -> anyhow::Result<()> {
-        let current_vsync = !self.current_vsync.load(Ordering::Relaxed);
-        self.current_vsync.store(current_vsync, Ordering::Relaxed);
-        let interval = if current_vsync {
-            SwapInterval::Wait(NonZeroU32::new(1).unwrap())
-        } else {
-            SwapInterval::DontWait
-        };
-        main_ctx.channels.draw.execute(move |s, _| {
-            s.set_swap_interval(interval)
-                .with_context(|| format!("unable to set vsync swap interval to {interval:?}"))
-                .log_error();
-            tracing::info!(
-                "VSync swap interval set to {} ({:?})",
-                interval != SwapInterval::DontWait,
-                interval
-            );
-        })?;
+    /// Requests the mode after the last requested one, whichever mode it
+    /// actually fell back to.
+    pub fn cycle(&self, main_ctx: &mut MainContext) -> anyhow::Result<()> {
+        let next = self.requested_mode.lock().next();
+        self.set(main_ctx, next)
+    }
 
-        Ok(())
+    pub fn set(&self, main_ctx: &mut MainContext, mode: PresentMode) -> anyhow::Result<()> {
+        *self.requested_mode.lock() = mode;
+        main_ctx.channels.draw.set_present_mode(mode)
     }
 }
@@ -2,15 +2,23 @@ use std::sync::Arc;
 
 use trait_set::trait_set;
 
-use crate::{events::GameEvent, exec::main_ctx::MainContext, graphics::context::DrawContext};
+use crate::{
+    events::GameEvent,
+    exec::main_ctx::MainContext,
+    graphics::context::DrawContext,
+    utils::alloc::{self, AllocTag},
+};
 
-use self::main::RootScene;
+use self::{lifetime::SceneLifetime, main::RootScene};
 
+pub mod lifetime;
 pub mod main;
 
+/// Dropping the container or removing a scene from it invalidates the
+/// lifetime of the scenes, see `MainContext::sweep_expired`.
 #[derive(Default)]
 pub struct SceneContainer {
-    scenes: Vec<Arc<dyn Scene>>,
+    scenes: Vec<(Arc<dyn Scene>, SceneLifetime)>,
 }
 
 trait_set! {
@@ -29,7 +37,26 @@ impl SceneContainer {
     }
 
     pub fn push_arc(&mut self, scene: Arc<dyn Scene>) {
-        self.scenes.push(scene)
+        let _scope = alloc::scope(AllocTag::Scene);
+        self.scenes.push((scene, SceneLifetime::new()))
+    }
+
+    /// Removes `scene`, callbacks and widget references retained on its
+    /// behalf are released on the next frame.
+    pub fn remove(&mut self, scene: &Arc<dyn Scene>) -> bool {
+        // compare data pointers only, vtables of the same type may differ
+        let ptr = Arc::as_ptr(scene) as *const u8;
+        if let Some(index) = self
+            .scenes
+            .iter()
+            .position(|(s, _)| Arc::as_ptr(s) as *const u8 == ptr)
+        {
+            let (_, lifetime) = self.scenes.remove(index);
+            lifetime.invalidate();
+            true
+        } else {
+            false
+        }
     }
 
     pub fn push_event_handler<F>(&mut self, event_handler: F)
@@ -78,6 +105,9 @@ pub trait Scene: Send + Sync {
     }
 
     fn draw(self: Arc<Self>, _ctx: &mut DrawContext) {}
+
+    /// Draws the pickable objects for `pick_at`, see `DrawContext::pick_color`.
+    fn draw_pick(self: Arc<Self>, _ctx: &mut DrawContext) {}
 }
 
 impl Scene for SceneContainer {
@@ -87,20 +117,38 @@ impl Scene for SceneContainer {
         root_scene: &RootScene,
         mut event: GameEvent<'a>,
     ) -> Option<GameEvent<'a>> {
-        for scene in self.scenes.iter().rev() {
+        let parent_scene = ctx.current_scene.take();
+        for (scene, lifetime) in self.scenes.iter().rev() {
+            ctx.current_scene = Some(lifetime.clone());
             if let Some(e) = scene.clone().handle_event(ctx, root_scene, event) {
                 event = e;
             } else {
+                ctx.current_scene = parent_scene;
                 return None;
             }
         }
 
+        ctx.current_scene = parent_scene;
         Some(event)
     }
 
     fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
-        for scene in self.scenes.iter() {
+        for (scene, _) in self.scenes.iter() {
             scene.clone().draw(ctx);
         }
     }
+
+    fn draw_pick(self: Arc<Self>, ctx: &mut DrawContext) {
+        for (scene, _) in self.scenes.iter() {
+            scene.clone().draw_pick(ctx);
+        }
+    }
+}
+
+impl Drop for SceneContainer {
+    fn drop(&mut self) {
+        self.scenes
+            .iter()
+            .for_each(|(_, lifetime)| lifetime.invalidate());
+    }
 }
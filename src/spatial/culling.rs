@@ -0,0 +1,27 @@
+use std::sync::Arc;
+
+use crate::utils::{mutex::Mutex, uid::Uid};
+
+/// Ids of the boxes in view of a camera, written by the culling stage of the
+/// update server and read by the draw server, which only has to submit what
+/// is in there.
+#[derive(Default)]
+pub struct VisibleSet {
+    /// sorted, replaced as a whole so that readers never block the culling
+    ids: Mutex<Arc<Vec<Uid>>>,
+}
+
+impl VisibleSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Result of the last finished culling job, empty until the first one.
+    pub fn get(&self) -> Arc<Vec<Uid>> {
+        self.ids.lock().clone()
+    }
+
+    pub fn set(&self, ids: Vec<Uid>) {
+        *self.ids.lock() = Arc::new(ids);
+    }
+}
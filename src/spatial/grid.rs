@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use glam::{IVec2, Vec2};
+
+use crate::utils::uid::Uid;
+
+use super::{Aabb, Ray};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub id: Uid,
+    pub distance: f32,
+    pub point: Vec2,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    aabb: Aabb,
+    min_cell: IVec2,
+    max_cell: IVec2,
+}
+
+/// Uniform hash grid of axis-aligned boxes, for queries that don't need a
+/// full physics engine (picking, line of sight, simple collision response).
+///
+/// Boxes are registered in every cell they overlap, so `cell_size` should be
+/// around the size of a typical box.
+#[derive(Clone, Debug)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    entries: HashMap<Uid, Entry>,
+    cells: HashMap<IVec2, Vec<Uid>>,
+}
+
+impl SpatialGrid {
+    pub const DEFAULT_CELL_SIZE: f32 = 64.0;
+
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            entries: HashMap::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, id: Uid) -> Option<Aabb> {
+        self.entries.get(&id).map(|entry| entry.aabb)
+    }
+
+    fn cell_of(&self, point: Vec2) -> IVec2 {
+        (point / self.cell_size).floor().as_ivec2()
+    }
+
+    fn for_each_cell(min: IVec2, max: IVec2, mut f: impl FnMut(IVec2)) {
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                f(IVec2::new(x, y));
+            }
+        }
+    }
+
+    /// Inserts a box, or moves it if `id` is already in the grid.
+    pub fn insert(&mut self, id: Uid, aabb: Aabb) {
+        let min_cell = self.cell_of(aabb.min);
+        let max_cell = self.cell_of(aabb.max);
+        if let Some(entry) = self.entries.get_mut(&id) {
+            if entry.min_cell == min_cell && entry.max_cell == max_cell {
+                entry.aabb = aabb;
+                return;
+            }
+            self.remove(id);
+        }
+
+        let cells = &mut self.cells;
+        Self::for_each_cell(min_cell, max_cell, |cell| {
+            cells.entry(cell).or_default().push(id)
+        });
+        self.entries.insert(
+            id,
+            Entry {
+                aabb,
+                min_cell,
+                max_cell,
+            },
+        );
+    }
+
+    /// Same as `insert`, kept for readability at call sites moving boxes.
+    pub fn update(&mut self, id: Uid, aabb: Aabb) {
+        self.insert(id, aabb)
+    }
+
+    pub fn remove(&mut self, id: Uid) -> Option<Aabb> {
+        let entry = self.entries.remove(&id)?;
+        let cells = &mut self.cells;
+        Self::for_each_cell(entry.min_cell, entry.max_cell, |cell| {
+            if let Some(ids) = cells.get_mut(&cell) {
+                ids.retain(|&other| other != id);
+                if ids.is_empty() {
+                    cells.remove(&cell);
+                }
+            }
+        });
+        Some(entry.aabb)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cells.clear();
+    }
+
+    /// Ids of every box intersecting `region`, sorted.
+    pub fn query_region(&self, region: &Aabb) -> Vec<Uid> {
+        let mut result = Vec::new();
+        Self::for_each_cell(self.cell_of(region.min), self.cell_of(region.max), |cell| {
+            for &id in self.cells.get(&cell).into_iter().flatten() {
+                if self.entries[&id].aabb.intersects(region) {
+                    result.push(id);
+                }
+            }
+        });
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    pub fn query_point(&self, point: Vec2) -> Vec<Uid> {
+        self.query_region(&Aabb::new(point, point))
+    }
+
+    /// Every box overlapping the box `id`, along with the translation moving
+    /// `id` out of it.
+    pub fn overlaps(&self, id: Uid) -> Vec<(Uid, Vec2)> {
+        let aabb = match self.get(id) {
+            Some(aabb) => aabb,
+            None => return Vec::new(),
+        };
+        self.query_region(&aabb)
+            .into_iter()
+            .filter(|&other| other != id)
+            .filter_map(|other| {
+                aabb.penetration(&self.entries[&other].aabb)
+                    .map(|offset| (other, offset))
+            })
+            .collect()
+    }
+
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        self.raycast_filtered(ray, |_| true)
+    }
+
+    /// Closest box hit by `ray` among the ones accepted by `filter`, walks
+    /// the cells crossed by the ray in order (DDA) and stops at the first
+    /// cell containing a hit.
+    pub fn raycast_filtered(
+        &self,
+        ray: &Ray,
+        mut filter: impl FnMut(Uid) -> bool,
+    ) -> Option<RayHit> {
+        debug_assert!(ray.max_distance.is_finite());
+        if self.is_empty() || ray.direction == Vec2::ZERO {
+            return None;
+        }
+
+        let mut cell = self.cell_of(ray.origin);
+        let step = IVec2::new(
+            ray.direction.x.signum() as i32,
+            ray.direction.y.signum() as i32,
+        );
+        let t_delta = (Vec2::splat(self.cell_size) / ray.direction).abs();
+        let next_boundary = |cell: IVec2, axis: usize| {
+            let direction = ray.direction[axis];
+            if direction == 0.0 {
+                return f32::INFINITY;
+            }
+            let boundary = if direction > 0.0 {
+                cell[axis] + 1
+            } else {
+                cell[axis]
+            };
+            (boundary as f32 * self.cell_size - ray.origin[axis]) / direction
+        };
+        let mut t_max = Vec2::new(next_boundary(cell, 0), next_boundary(cell, 1));
+
+        let mut best: Option<RayHit> = None;
+        loop {
+            for &id in self.cells.get(&cell).into_iter().flatten() {
+                if !filter(id) {
+                    continue;
+                }
+                if let Some(distance) = self.entries[&id].aabb.ray_distance(ray) {
+                    if best.map(|hit| distance < hit.distance).unwrap_or(true) {
+                        best = Some(RayHit {
+                            id,
+                            distance,
+                            point: ray.at(distance),
+                        });
+                    }
+                }
+            }
+
+            // boxes in the next cells can't be hit before leaving this one
+            let cell_exit = t_max.min_element();
+            if let Some(hit) = best {
+                if hit.distance <= cell_exit {
+                    return Some(hit);
+                }
+            }
+            if cell_exit > ray.max_distance {
+                return best;
+            }
+
+            if t_max.x < t_max.y {
+                cell.x += step.x;
+                t_max.x += t_delta.x;
+            } else {
+                cell.y += step.y;
+                t_max.y += t_delta.y;
+            }
+        }
+    }
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CELL_SIZE)
+    }
+}
+
+#[test]
+fn test() {
+    let mut grid = SpatialGrid::new(10.0);
+    let (a, b, c) = (Uid::new(), Uid::new(), Uid::new());
+    grid.insert(a, Aabb::new(Vec2::new(0.0, 0.0), Vec2::new(5.0, 5.0)));
+    grid.insert(b, Aabb::new(Vec2::new(4.0, 0.0), Vec2::new(25.0, 5.0)));
+    grid.insert(c, Aabb::new(Vec2::new(40.0, -5.0), Vec2::new(45.0, 5.0)));
+
+    assert_eq!(grid.query_point(Vec2::new(4.5, 1.0)), vec![a, b]);
+    assert_eq!(
+        grid.query_region(&Aabb::new(Vec2::new(20.0, 0.0), Vec2::new(50.0, 1.0))),
+        vec![b, c]
+    );
+    assert_eq!(grid.overlaps(a), vec![(b, Vec2::new(-1.0, 0.0))]);
+
+    let ray = Ray {
+        origin: Vec2::new(100.0, 2.0),
+        direction: Vec2::new(-1.0, 0.0),
+        max_distance: 200.0,
+    };
+    assert_eq!(
+        grid.raycast(&ray).map(|hit| (hit.id, hit.distance)),
+        Some((c, 55.0))
+    );
+    assert_eq!(
+        grid.raycast_filtered(&ray, |id| id != c).map(|hit| hit.id),
+        Some(b)
+    );
+
+    grid.update(c, Aabb::new(Vec2::new(40.0, 50.0), Vec2::new(45.0, 55.0)));
+    assert_eq!(grid.raycast(&ray).map(|hit| hit.id), Some(b));
+    assert!(grid.remove(b).is_some());
+    assert_eq!(grid.raycast(&ray).map(|hit| hit.id), Some(a));
+    assert!(grid.query_point(Vec2::new(20.0, 1.0)).is_empty());
+}
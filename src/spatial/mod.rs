@@ -0,0 +1,82 @@
+use glam::Vec2;
+
+pub mod culling;
+pub mod grid;
+
+/// Axis-aligned bounding box in world space.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec2,
+    /// doesn't have to be normalized, distances are in multiples of its
+    /// length
+    pub direction: Vec2,
+    pub max_distance: f32,
+}
+
+impl Aabb {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self {
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    pub fn from_center(center: Vec2, half_extents: Vec2) -> Self {
+        Self::new(center - half_extents, center + half_extents)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    /// Smallest translation moving `self` out of `other`, `None` if the boxes
+    /// don't overlap.
+    pub fn penetration(&self, other: &Aabb) -> Option<Vec2> {
+        let overlap = self.max.min(other.max) - self.min.max(other.min);
+        if overlap.x <= 0.0 || overlap.y <= 0.0 {
+            return None;
+        }
+
+        let delta = self.center() - other.center();
+        Some(if overlap.x < overlap.y {
+            Vec2::new(overlap.x.copysign(delta.x), 0.0)
+        } else {
+            Vec2::new(0.0, overlap.y.copysign(delta.y))
+        })
+    }
+
+    /// Distance along `ray` where it enters the box (0 if the origin is
+    /// inside), slab method.
+    pub fn ray_distance(&self, ray: &Ray) -> Option<f32> {
+        let inv_direction = Vec2::ONE / ray.direction;
+        let t1 = (self.min - ray.origin) * inv_direction;
+        let t2 = (self.max - ray.origin) * inv_direction;
+        let enter = t1.min(t2).max_element().max(0.0);
+        let exit = t1.max(t2).min_element().min(ray.max_distance);
+        (enter <= exit).then_some(enter)
+    }
+}
+
+impl Ray {
+    pub fn at(&self, distance: f32) -> Vec2 {
+        self.origin + self.direction * distance
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    fmt::{Debug, Write},
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::Context;
+
+/// A single UI event received by a widget during a test.
+#[derive(Clone, Debug)]
+pub struct TraceEntry {
+    /// seconds since the test node was created
+    pub time: f64,
+    pub widget: String,
+    pub event: String,
+}
+
+/// Debugging data collected while a test runs, dumped to the artifacts
+/// directory if the test fails.
+pub struct TestArtifacts {
+    start: Instant,
+    pub trace: Vec<TraceEntry>,
+    pub layout_snapshot: Option<String>,
+}
+
+impl Default for TestArtifacts {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            trace: Vec::new(),
+            layout_snapshot: None,
+        }
+    }
+}
+
+impl TestArtifacts {
+    pub fn record(&mut self, widget: impl Into<String>, event: &impl Debug) {
+        self.trace.push(TraceEntry {
+            time: self.start.elapsed().as_secs_f64(),
+            widget: widget.into(),
+            event: format!("{event:?}"),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trace.is_empty() && self.layout_snapshot.is_none()
+    }
+
+    /// Writes `trace.txt` and `layout.txt` into `dir`, see `test_dir`,
+    /// returning the paths of the written files.
+    pub fn dump(&self, dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        if !self.trace.is_empty() {
+            let mut trace = String::new();
+            for entry in self.trace.iter() {
+                let _ = writeln!(
+                    trace,
+                    "[{:>10.6}s] {}: {}",
+                    entry.time, entry.widget, entry.event
+                );
+            }
+            paths.push(write_artifact(dir, "trace", &trace)?);
+        }
+        if let Some(layout) = self.layout_snapshot.as_ref() {
+            paths.push(write_artifact(dir, "layout", layout)?);
+        }
+        Ok(paths)
+    }
+}
+
+/// Directory of the artifacts of the test `full_name`.
+pub fn test_dir(artifacts_dir: &Path, full_name: &str) -> PathBuf {
+    artifacts_dir.join(full_name)
+}
+
+/// Whether the test log `name` belongs to the test `full_name`, tests name
+/// their logs after themselves, optionally followed by `.` and a suffix.
+pub fn is_log_of(name: &str, full_name: &str) -> bool {
+    match name.strip_prefix(full_name) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.'),
+        None => false,
+    }
+}
+
+/// Writes the test logs, as sorted `[name]` sections, into `logs.txt`.
+pub fn write_logs(dir: &Path, logs: &[(String, String)]) -> anyhow::Result<PathBuf> {
+    let mut logs = logs.iter().collect::<Vec<_>>();
+    logs.sort();
+    let mut content = String::new();
+    for (name, log) in logs {
+        let _ = writeln!(content, "[{name}]\n{}\n", log.trim_end());
+    }
+    write_artifact(dir, "logs", &content)
+}
+
+fn write_artifact(dir: &Path, kind: &str, content: &str) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("unable to create artifacts directory {}", dir.display()))?;
+    let path = dir.join(format!("{kind}.txt"));
+    fs::write(&path, content).with_context(|| format!("unable to write {}", path.display()))?;
+    Ok(path)
+}
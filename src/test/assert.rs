@@ -1,4 +1,7 @@
-use std::{borrow::Cow, fmt::Debug};
+use std::{
+    borrow::Cow,
+    fmt::{Debug, Write},
+};
 
 use crate::utils::has_metric::HasDistance;
 
@@ -51,7 +54,7 @@ pub fn assert_less_than<T: PartialOrd + Debug + ?Sized>(
         Err(TestError::AssertCompareError {
             found: format!("{found:?}"),
             expected: format!("{expected:?}"),
-            comparison: Comparison::Equals,
+            comparison: Comparison::Less,
             compare_error: None,
             custom_msg: msg.into(),
         })
@@ -179,3 +182,149 @@ pub fn assert_not_equals_err<T: HasDistance + Debug + ?Sized>(
         })
     }
 }
+
+/// Compares two test logs, ignoring the whitespace around them.
+pub fn assert_log_equals(
+    found: &str,
+    expected: &str,
+    msg: impl Into<Cow<'static, str>>,
+) -> TestResult {
+    assert_equals(found.trim(), expected.trim(), msg)
+}
+
+const LOG_CONTEXT_LINES: usize = 2;
+
+/// The lines of two logs around their first difference, `None` if they're
+/// equal.
+pub fn log_diff(found: &str, expected: &str) -> Option<String> {
+    let found = found.trim().lines().collect::<Vec<_>>();
+    let expected = expected.trim().lines().collect::<Vec<_>>();
+    let first = (0..found.len().max(expected.len())).find(|&i| found.get(i) != expected.get(i))?;
+    let mut snippet = format!("  log differs from line {}:", first + 1);
+    for line in &found[first.saturating_sub(LOG_CONTEXT_LINES)..first] {
+        write!(snippet, "\n      {line}").ok();
+    }
+    for line in expected.iter().skip(first).take(LOG_CONTEXT_LINES + 1) {
+        write!(snippet, "\n    - {line}").ok();
+    }
+    for line in found.iter().skip(first).take(LOG_CONTEXT_LINES + 1) {
+        write!(snippet, "\n    + {line}").ok();
+    }
+    Some(snippet)
+}
+
+/// Asserts that a condition holds like `assert_true`, recording where the
+/// assertion failed. Prefixed with `node;`, the leaf node is updated with
+/// the result instead.
+#[macro_export]
+macro_rules! assert_test {
+    (@impl $value:expr, $msg:expr) => {
+        $crate::test::assert::assert_true($value, $msg)
+            .map_err(|e| e.located(file!(), line!(), None))
+    };
+    ($node:expr; $($args:tt)+) => {
+        $node.update($crate::assert_test!($($args)+))
+    };
+    ($value:expr $(,)?) => {
+        $crate::assert_test!(@impl $value, concat!("`", stringify!($value), "`"))
+    };
+    ($value:expr, $($msg:tt)+) => {
+        $crate::assert_test!(@impl $value, format!($($msg)+))
+    };
+}
+
+/// `assert_equals` counterpart of `assert_test!`.
+#[macro_export]
+macro_rules! assert_eq_test {
+    (@impl $found:expr, $expected:expr, $msg:expr) => {
+        $crate::test::assert::assert_equals(&$found, &$expected, $msg)
+            .map_err(|e| e.located(file!(), line!(), None))
+    };
+    ($node:expr; $($args:tt)+) => {
+        $node.update($crate::assert_eq_test!($($args)+))
+    };
+    ($found:expr, $expected:expr $(,)?) => {
+        $crate::assert_eq_test!(
+            @impl $found,
+            $expected,
+            concat!("`", stringify!($found), "` == `", stringify!($expected), "`")
+        )
+    };
+    ($found:expr, $expected:expr, $($msg:tt)+) => {
+        $crate::assert_eq_test!(@impl $found, $expected, format!($($msg)+))
+    };
+}
+
+/// `assert_not_equals` counterpart of `assert_test!`.
+#[macro_export]
+macro_rules! assert_ne_test {
+    (@impl $found:expr, $expected:expr, $msg:expr) => {
+        $crate::test::assert::assert_not_equals(&$found, &$expected, $msg)
+            .map_err(|e| e.located(file!(), line!(), None))
+    };
+    ($node:expr; $($args:tt)+) => {
+        $node.update($crate::assert_ne_test!($($args)+))
+    };
+    ($found:expr, $expected:expr $(,)?) => {
+        $crate::assert_ne_test!(
+            @impl $found,
+            $expected,
+            concat!("`", stringify!($found), "` != `", stringify!($expected), "`")
+        )
+    };
+    ($found:expr, $expected:expr, $($msg:tt)+) => {
+        $crate::assert_ne_test!(@impl $found, $expected, format!($($msg)+))
+    };
+}
+
+/// `assert_log_equals` counterpart of `assert_test!`, the error shows the
+/// lines around the first difference of the logs.
+#[macro_export]
+macro_rules! assert_log_eq_test {
+    (@impl $found:expr, $expected:expr, $msg:expr) => {{
+        let found: &str = &$found;
+        let expected: &str = &$expected;
+        $crate::test::assert::assert_log_equals(found, expected, $msg).map_err(|e| {
+            e.located(
+                file!(),
+                line!(),
+                $crate::test::assert::log_diff(found, expected),
+            )
+        })
+    }};
+    ($node:expr; $($args:tt)+) => {
+        $node.update($crate::assert_log_eq_test!($($args)+))
+    };
+    ($found:expr, $expected:expr $(,)?) => {
+        $crate::assert_log_eq_test!(@impl $found, $expected, "log mismatch")
+    };
+    ($found:expr, $expected:expr, $($msg:tt)+) => {
+        $crate::assert_log_eq_test!(@impl $found, $expected, format!($($msg)+))
+    };
+}
+
+#[test]
+fn test() {
+    assert!(assert_eq_test!(1 + 1, 2).is_ok());
+    assert!(assert_test!(true, "never fails").is_ok());
+
+    let line = line!() + 1;
+    let error = assert_ne_test!("a", "a").unwrap_err();
+    assert!(matches!(
+        &error,
+        TestError::Located { file, line: l, log_snippet: None, .. }
+            if *file == file!() && *l == line
+    ));
+    assert!(error.to_string().contains("`\"a\"` != `\"a\"`"));
+
+    assert_eq!(log_diff("1\n2\n", "1\n2"), None);
+    assert_eq!(
+        log_diff("0\n1\n2\n3\nx", "0\n1\n2\n3\n4\n5").as_deref(),
+        Some("  log differs from line 5:\n      2\n      3\n    - 4\n    - 5\n    + x")
+    );
+    let error = assert_log_eq_test!("a\nb".to_owned(), "a\nc", "draw log of case {}", 1)
+        .unwrap_err()
+        .to_string();
+    assert!(error.starts_with("draw log of case 1\n  found:    \"a\\nb\""));
+    assert!(error.ends_with("log differs from line 2:\n      a\n    - c\n    + b"));
+}
@@ -0,0 +1,188 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::{
+    assert::{assert_true, assert_unreachable},
+    result::TestResult,
+};
+
+/// Frame counter shared by every source, advanced by the main thread once
+/// per event loop iteration.
+static FRAME: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogSource {
+    Main,
+    Draw,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    pub source: LogSource,
+    /// value of the shared frame counter when the entry was recorded
+    pub frame: u64,
+    /// order of the entry among the ones of the same source
+    pub seq: u64,
+    pub text: String,
+}
+
+/// Test logs written by a single thread, so that entries of different
+/// threads never interleave in the same buffer. The buffers of all sources
+/// are combined with `MergedLog::merge` once the test is done writing.
+pub struct LogCapture {
+    source: LogSource,
+    seq: u64,
+    logs: HashMap<Cow<'static, str>, Vec<LogEntry>>,
+}
+
+/// Entries of all sources for one log, ordered by frame, then source, then
+/// sequence number. Entries of the same frame but of different sources
+/// have no defined order between them.
+#[derive(Clone, Debug, Default)]
+pub struct MergedLog {
+    pub entries: Vec<LogEntry>,
+}
+
+pub fn next_frame() -> u64 {
+    FRAME.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+pub fn current_frame() -> u64 {
+    FRAME.load(Ordering::SeqCst)
+}
+
+impl LogEntry {
+    /// Whether `self` was certainly recorded before `other`.
+    pub fn happens_before(&self, other: &LogEntry) -> bool {
+        self.frame < other.frame
+            || (self.frame == other.frame && self.source == other.source && self.seq < other.seq)
+    }
+}
+
+impl LogCapture {
+    pub fn new(source: LogSource) -> Self {
+        Self {
+            source,
+            seq: 0,
+            logs: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, name: impl Into<Cow<'static, str>>, text: impl Into<String>) {
+        self.seq += 1;
+        let entry = LogEntry {
+            source: self.source,
+            frame: current_frame(),
+            seq: self.seq,
+            text: text.into(),
+        };
+        self.logs.entry(name.into()).or_default().push(entry);
+    }
+
+    pub fn take(&mut self, name: &str) -> Vec<LogEntry> {
+        self.logs.remove(name).unwrap_or_default()
+    }
+
+    /// Texts of the entries of `name`, one per line, for logs written by a
+    /// single source.
+    pub fn take_text(&mut self, name: &str) -> String {
+        join_texts(&self.take(name))
+    }
+
+    /// Every log with its text, without taking them.
+    pub fn texts(&self) -> impl Iterator<Item = (&str, String)> {
+        self.logs
+            .iter()
+            .map(|(name, entries)| (name.as_ref(), join_texts(entries)))
+    }
+}
+
+fn join_texts(entries: &[LogEntry]) -> String {
+    entries
+        .iter()
+        .map(|e| e.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl MergedLog {
+    pub fn merge(sources: impl IntoIterator<Item = Vec<LogEntry>>) -> Self {
+        let mut entries = sources.into_iter().flatten().collect::<Vec<_>>();
+        entries.sort_by_key(|e| (e.frame, e.source, e.seq));
+        Self { entries }
+    }
+
+    pub fn texts(&self) -> Vec<&str> {
+        self.entries.iter().map(|e| e.text.as_str()).collect()
+    }
+
+    /// Checks that the entries with the texts of `expected` may have been
+    /// recorded in that order. Two entries of the same frame coming from
+    /// different sources are accepted in either order, only an entry that
+    /// certainly happened before the previous one is an ordering bug.
+    pub fn assert_order(&self, expected: &[&str]) -> TestResult {
+        let mut used = vec![false; self.entries.len()];
+        let mut previous: Option<&LogEntry> = None;
+        for text in expected {
+            let index = match self
+                .entries
+                .iter()
+                .enumerate()
+                .position(|(i, e)| !used[i] && e.text == *text)
+            {
+                Some(index) => index,
+                None => {
+                    return assert_unreachable(format!(
+                        "log entry `{text}` not found in {:?}",
+                        self.texts()
+                    ))
+                }
+            };
+            used[index] = true;
+            let entry = &self.entries[index];
+            if let Some(previous) = previous {
+                assert_true(
+                    !entry.happens_before(previous),
+                    format!(
+                        "`{text}` ({:?}, frame {}) was recorded before `{}` ({:?}, frame {})",
+                        entry.source, entry.frame, previous.text, previous.source, previous.frame
+                    ),
+                )?;
+            }
+            previous = Some(entry);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test() {
+    let mut main = LogCapture::new(LogSource::Main);
+    let mut draw = LogCapture::new(LogSource::Draw);
+    let frame = current_frame();
+    main.record("log", "a");
+    draw.record("log", "b");
+    main.record("log", "c");
+    // frames are counted by the main thread, it may move on concurrently
+    let next = next_frame();
+    draw.record("log", "d");
+
+    assert_eq!(
+        main.texts().collect::<Vec<_>>(),
+        [("log", "a\nc".to_owned())]
+    );
+    let merged = MergedLog::merge([main.take("log"), draw.take("log")]);
+    assert!(main.take("log").is_empty());
+    if next == frame + 1 {
+        assert_eq!(merged.texts(), ["a", "c", "b", "d"]);
+    }
+    // "b" and "c" are recorded in the same frame by different sources
+    assert!(merged.assert_order(&["a", "b", "c", "d"]).is_ok());
+    assert!(merged.assert_order(&["a", "c", "b", "d"]).is_ok());
+    assert!(merged.assert_order(&["c", "a"]).is_err());
+    assert!(merged.assert_order(&["d", "b"]).is_err());
+    assert!(merged.assert_order(&["e"]).is_err());
+}
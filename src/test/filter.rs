@@ -0,0 +1,125 @@
+use std::{borrow::Cow, iter};
+
+use crate::utils::args::args;
+
+/// Which tests run, selected with `--test-filter` and `--test-tag`.
+///
+/// Tests are matched by their path: their full name without the root,
+/// e.g. `tilemap.culling`. Tags are inherited by the children of a node.
+#[derive(Debug, Default)]
+pub struct TestSelection {
+    filters: Vec<String>,
+    tags: Vec<String>,
+    excluded_tags: Vec<String>,
+}
+
+impl TestSelection {
+    /// `tags` starting with `!` exclude the tests having them instead.
+    pub fn new(filters: Vec<String>, tags: Vec<String>) -> Self {
+        let (excluded_tags, tags) = tags
+            .into_iter()
+            .partition::<Vec<_>, _>(|tag| tag.starts_with('!'));
+        Self {
+            filters,
+            tags,
+            excluded_tags: excluded_tags
+                .into_iter()
+                .map(|tag| tag[1..].to_owned())
+                .collect(),
+        }
+    }
+
+    pub fn from_args() -> Self {
+        Self::new(args().test_filter.clone(), args().test_tag.clone())
+    }
+
+    pub fn is_everything(&self) -> bool {
+        self.filters.is_empty() && self.tags.is_empty() && self.excluded_tags.is_empty()
+    }
+
+    /// Whether the leaf test `path` runs: it or one of its parents matches
+    /// a filter, and it has a selected tag.
+    pub fn runs_leaf(&self, path: &str, tags: &[Cow<'static, str>]) -> bool {
+        let has_tag =
+            |selected: &Vec<String>| tags.iter().any(|tag| selected.iter().any(|s| s == tag));
+        (self.tags.is_empty() || has_tag(&self.tags))
+            && !has_tag(&self.excluded_tags)
+            && (self.filters.is_empty()
+                || ancestors(path).any(|path| self.filters.iter().any(|f| glob_match(f, path))))
+    }
+
+    /// Whether some leaf test under the parent `path` may run. Children can
+    /// still be tagged, so only excluded tags are known to skip them.
+    pub fn may_run_parent(&self, path: &str, tags: &[Cow<'static, str>]) -> bool {
+        let prefix = format!("{path}.");
+        !tags
+            .iter()
+            .any(|tag| self.excluded_tags.iter().any(|s| s == tag))
+            && (self.filters.is_empty()
+                || self.filters.iter().any(|filter| {
+                    glob_match_prefix(filter, &prefix)
+                        || ancestors(path).any(|path| glob_match(filter, path))
+                }))
+    }
+}
+
+/// `path` and the paths of its parents.
+fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('.')
+        .map(|(i, _)| &path[..i])
+        .chain(iter::once(path))
+}
+
+/// Matches `text` against `pattern`, where `*` matches any string and `?`
+/// any character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    glob(&pattern, &text, false)
+}
+
+/// Whether a text starting with `prefix` can match `pattern`.
+fn glob_match_prefix(pattern: &str, prefix: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let prefix = prefix.chars().collect::<Vec<_>>();
+    glob(&pattern, &prefix, true)
+}
+
+fn glob(pattern: &[char], text: &[char], prefix: bool) -> bool {
+    match (pattern.split_first(), text.split_first()) {
+        (_, None) if prefix => true,
+        (Some(('*', rest)), _) => {
+            glob(rest, text, prefix) || (!text.is_empty() && glob(pattern, &text[1..], prefix))
+        }
+        (Some((p, pattern)), Some((c, text))) => {
+            (*p == '?' || p == c) && glob(pattern, text, prefix)
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[test]
+fn test() {
+    assert!(glob_match("tilemap.*", "tilemap.culling"));
+    assert!(glob_match("*.cull?ng", "tilemap.culling"));
+    assert!(!glob_match("tilemap", "tilemap.culling"));
+    assert!(glob_match_prefix("*.culling", "tilemap."));
+    assert!(!glob_match_prefix("ui.*", "tilemap."));
+
+    let selection = TestSelection::new(vec!["tilemap".into(), "ui.*.click".into()], vec![]);
+    assert!(selection.runs_leaf("tilemap.culling", &[]));
+    assert!(selection.runs_leaf("ui.button.click", &[]));
+    assert!(!selection.runs_leaf("ui.button.hover", &[]));
+    assert!(selection.may_run_parent("ui", &[]));
+    assert!(selection.may_run_parent("ui.button", &[]));
+    assert!(!selection.may_run_parent("golden", &[]));
+
+    let selection = TestSelection::new(vec![], vec!["gpu".into(), "!slow".into()]);
+    assert!(selection.runs_leaf("golden.shapes", &["gpu".into()]));
+    assert!(!selection.runs_leaf("golden.shapes", &["gpu".into(), "slow".into()]));
+    assert!(!selection.runs_leaf("tween.linear", &[]));
+    assert!(selection.may_run_parent("tween", &[]));
+    assert!(!selection.may_run_parent("soak", &["slow".into()]));
+    assert!(TestSelection::default().is_everything());
+}
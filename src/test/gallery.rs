@@ -0,0 +1,213 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use image::{Rgba, RgbaImage};
+
+/// Channel differences up to this are ignored, e.g. dithering or driver
+/// rounding.
+const DIFF_TOLERANCE: u8 = 2;
+
+/// Screenshot of a scene captured by `--gallery`, compared with the one of
+/// the previous run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GalleryEntry {
+    pub name: String,
+    /// ratio of changed pixels, `None` if the scene wasn't captured by the
+    /// previous run
+    pub changed: Option<f64>,
+    /// whether the screenshots have different sizes, no diff image is
+    /// written in that case
+    pub size_changed: bool,
+}
+
+fn current_dir(dir: &Path) -> PathBuf {
+    dir.join("current")
+}
+
+fn previous_dir(dir: &Path) -> PathBuf {
+    dir.join("previous")
+}
+
+fn diff_dir(dir: &Path) -> PathBuf {
+    dir.join("diff")
+}
+
+/// Moves the screenshots of the last run to `previous`, replacing the ones
+/// of the run before it.
+pub fn prepare_run(dir: &Path) -> anyhow::Result<()> {
+    let (current, previous, diff) = (current_dir(dir), previous_dir(dir), diff_dir(dir));
+    if current.is_dir() {
+        if previous.is_dir() {
+            fs::remove_dir_all(&previous)
+                .with_context(|| format!("unable to remove {}", previous.display()))?;
+        }
+        fs::rename(&current, &previous).with_context(|| {
+            format!(
+                "unable to move {} to {}",
+                current.display(),
+                previous.display()
+            )
+        })?;
+    }
+    if diff.is_dir() {
+        fs::remove_dir_all(&diff)
+            .with_context(|| format!("unable to remove {}", diff.display()))?;
+    }
+    for dir in [current, diff] {
+        fs::create_dir_all(&dir).with_context(|| format!("unable to create {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Writes the screenshot of `name` and its diff with the previous run.
+pub fn record(dir: &Path, name: &str, image: &RgbaImage) -> anyhow::Result<GalleryEntry> {
+    let file = format!("{name}.png");
+    let path = current_dir(dir).join(&file);
+    image
+        .save(&path)
+        .with_context(|| format!("unable to write {}", path.display()))?;
+
+    let previous = match image::open(previous_dir(dir).join(&file)) {
+        Ok(previous) => previous.into_rgba8(),
+        Err(_) => {
+            return Ok(GalleryEntry {
+                name: name.to_owned(),
+                changed: None,
+                size_changed: false,
+            })
+        }
+    };
+    let (diff, changed) = match diff_images(&previous, image) {
+        Some(diff) => diff,
+        None => {
+            return Ok(GalleryEntry {
+                name: name.to_owned(),
+                changed: Some(1.0),
+                size_changed: true,
+            })
+        }
+    };
+    let path = diff_dir(dir).join(&file);
+    diff.save(&path)
+        .with_context(|| format!("unable to write {}", path.display()))?;
+    Ok(GalleryEntry {
+        name: name.to_owned(),
+        changed: Some(changed),
+        size_changed: false,
+    })
+}
+
+/// Changed pixels in red over a dimmed grayscale `current`, and the ratio
+/// of changed pixels. `None` if the sizes differ.
+pub fn diff_images(previous: &RgbaImage, current: &RgbaImage) -> Option<(RgbaImage, f64)> {
+    diff_images_by(previous, current, |a, b| {
+        a.0.iter()
+            .zip(b.0)
+            .any(|(a, b)| a.abs_diff(b) > DIFF_TOLERANCE)
+    })
+}
+
+/// `diff_images` with the pixels compared by `differs`.
+pub fn diff_images_by(
+    previous: &RgbaImage,
+    current: &RgbaImage,
+    differs: impl Fn(&Rgba<u8>, &Rgba<u8>) -> bool,
+) -> Option<(RgbaImage, f64)> {
+    if previous.dimensions() != current.dimensions() {
+        return None;
+    }
+    let mut changed = 0usize;
+    let diff = RgbaImage::from_fn(current.width(), current.height(), |x, y| {
+        let (a, b) = (previous.get_pixel(x, y), current.get_pixel(x, y));
+        if differs(a, b) {
+            changed += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = b.0.map(u32::from);
+            let luma = ((r * 3 + g * 6 + b) / 10 / 3) as u8;
+            Rgba([luma, luma, luma, 255])
+        }
+    });
+    let pixels = (current.width() as usize * current.height() as usize).max(1);
+    Some((diff, changed as f64 / pixels as f64))
+}
+
+/// Writes `index.html` showing the previous, current and diff images of
+/// every entry side by side, returns its path.
+pub fn write_html(dir: &Path, entries: &[GalleryEntry]) -> anyhow::Result<PathBuf> {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Scene gallery</title>\n<style>\n\
+         body { font-family: sans-serif; }\n\
+         img { max-width: 30vw; border: 1px solid #888; }\n\
+         .changed { color: #c00; }\n\
+         </style>\n</head>\n<body>\n<table>\n\
+         <tr><th>scene</th><th>previous</th><th>current</th><th>diff</th></tr>\n",
+    );
+    for entry in entries {
+        let name = escape(&entry.name);
+        let image = |dir: &str| format!("<img src=\"{dir}/{name}.png\" alt=\"{dir}\">");
+        let (status, previous, diff) = match entry.changed {
+            None => ("new".to_owned(), String::new(), String::new()),
+            Some(_) if entry.size_changed => (
+                "<span class=\"changed\">size changed</span>".to_owned(),
+                image("previous"),
+                String::new(),
+            ),
+            Some(changed) if changed > 0.0 => (
+                format!(
+                    "<span class=\"changed\">{:.2}% changed</span>",
+                    changed * 100.0
+                ),
+                image("previous"),
+                image("diff"),
+            ),
+            Some(_) => ("unchanged".to_owned(), image("previous"), image("diff")),
+        };
+        let _ = writeln!(
+            html,
+            "<tr><td>{name}<br>{status}</td><td>{previous}</td><td>{}</td><td>{diff}</td></tr>",
+            image("current")
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    let path = dir.join("index.html");
+    fs::write(&path, html).with_context(|| format!("unable to write {}", path.display()))?;
+    Ok(path)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn test() {
+    let previous = RgbaImage::from_pixel(2, 2, Rgba([10, 20, 30, 255]));
+    let mut current = previous.clone();
+    current.put_pixel(1, 0, Rgba([11, 21, 31, 255]));
+    current.put_pixel(0, 1, Rgba([200, 20, 30, 255]));
+    let (diff, changed) = diff_images(&previous, &current).unwrap();
+    assert_eq!(changed, 0.25);
+    assert_eq!(diff.get_pixel(0, 1), &Rgba([255, 0, 0, 255]));
+    assert_ne!(diff.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+    assert!(diff_images(&previous, &RgbaImage::new(2, 3)).is_none());
+
+    let dir = std::env::temp_dir().join(format!("gallery-{}", std::process::id()));
+    prepare_run(&dir).unwrap();
+    assert_eq!(record(&dir, "scene", &previous).unwrap().changed, None);
+    prepare_run(&dir).unwrap();
+    assert!(previous_dir(&dir).join("scene.png").is_file());
+    let entry = record(&dir, "scene", &current).unwrap();
+    assert_eq!(entry.changed, Some(0.25));
+    let html = fs::read_to_string(write_html(&dir, &[entry]).unwrap()).unwrap();
+    assert!(html.contains("diff/scene.png") && html.contains("25.00% changed"));
+    assert_eq!(escape("<a&\">"), "&lt;a&amp;&quot;&gt;");
+    fs::remove_dir_all(&dir).unwrap();
+}
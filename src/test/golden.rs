@@ -0,0 +1,230 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use glam::{Affine2, Vec2};
+use image::{Rgba, RgbaImage};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::{context::DrawContext, screenshot},
+    utils::args::args,
+};
+
+use super::{
+    gallery,
+    result::{Comparison, TestError, TestResult},
+    tree::LeafTestNode,
+};
+
+/// Largest YIQ difference between two colors.
+const MAX_YIQ_DELTA: f32 = 35215.0;
+
+/// How far a rendering may drift from its golden image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GoldenTolerance {
+    /// perceptual difference between 0 and 1 under which two pixels look
+    /// the same, see `perceptual_delta`
+    pub threshold: f32,
+    /// ratio of differing pixels still passing, e.g. for antialiased edges
+    /// rasterized differently by another driver
+    pub max_changed: f64,
+}
+
+impl Default for GoldenTolerance {
+    fn default() -> Self {
+        Self {
+            threshold: 0.1,
+            max_changed: 0.001,
+        }
+    }
+}
+
+/// Perceived difference between two colors, from 0 for the same color to 1
+/// for the most different ones. Compares them in the YIQ space, weighting
+/// luma the most, like pixelmatch. Alpha is ignored, captures are opaque.
+pub fn perceptual_delta(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    let [dr, dg, db] = [0, 1, 2].map(|i| a.0[i] as f32 - b.0[i] as f32);
+    let y = dr * 0.298_895 + dg * 0.586_622 + db * 0.114_482;
+    let i = dr * 0.595_978 - dg * 0.274_176 - db * 0.321_802;
+    let q = dr * 0.211_470 - dg * 0.522_617 + db * 0.311_147;
+    let delta = 0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q;
+    (delta / MAX_YIQ_DELTA).sqrt().min(1.0)
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    args().golden_dir.join(format!("{name}.png"))
+}
+
+fn failure_dir() -> PathBuf {
+    args().artifacts_dir.join("golden")
+}
+
+/// Compares `image` with the golden image `name` in the golden directory.
+///
+/// Golden images are committed, a missing one fails like a mismatching one
+/// unless `--update-snapshots` is given, in which case it is (re)written.
+/// On a failure the image, and its diff if any, are written to the `golden`
+/// artifacts directory.
+pub fn compare_golden(name: &str, image: &RgbaImage, tolerance: GoldenTolerance) -> TestResult {
+    let path = golden_path(name);
+    let expected = match image::open(&path) {
+        Ok(expected) => expected.into_rgba8(),
+        Err(image::ImageError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            if args().update_snapshots {
+                tracing::info!("new golden image {}", path.display());
+                return write_png(&path, image);
+            }
+            let found_path = failure_dir().join(format!("{name}.png"));
+            write_png(&found_path, image)?;
+            return Err(TestError::AssertCompareError {
+                found: format!("rendered image written to {}", found_path.display()),
+                expected: format!("golden image {}", path.display()),
+                custom_msg: format!("golden image {name} is missing").into(),
+                comparison: Comparison::Equals,
+                compare_error: Some("run with --update-snapshots to accept it".to_owned()),
+            });
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context(format!("unable to read {}", path.display()))
+                .into())
+        }
+    };
+    let diff = gallery::diff_images_by(&expected, image, |a, b| {
+        perceptual_delta(a, b) > tolerance.threshold
+    });
+    let (found, compare_error) = match diff {
+        Some((_, changed)) if changed <= tolerance.max_changed => return Ok(()),
+        Some((diff, changed)) => {
+            let diff_path = failure_dir().join(format!("{name}.diff.png"));
+            write_png(&diff_path, &diff)?;
+            (
+                format!("{:.3}% of the pixels changed", changed * 100.0),
+                format!("diff written to {}", diff_path.display()),
+            )
+        }
+        None => (
+            format!("{}x{} image", image.width(), image.height()),
+            format!(
+                "the golden image is {}x{}",
+                expected.width(),
+                expected.height()
+            ),
+        ),
+    };
+    if args().update_snapshots {
+        tracing::info!("updated golden image {}", path.display());
+        return write_png(&path, image);
+    }
+
+    let found_path = failure_dir().join(format!("{name}.png"));
+    write_png(&found_path, image)?;
+    Err(TestError::AssertCompareError {
+        found,
+        expected: format!("at most {:.3}%", tolerance.max_changed * 100.0),
+        custom_msg: format!("golden image {name} differs").into(),
+        comparison: Comparison::LessEquals,
+        compare_error: Some(format!(
+            "{compare_error}, rendered image written to {}",
+            found_path.display()
+        )),
+    })
+}
+
+/// Renders `draw` alone on the draw server, with one unit per pixel from
+/// the top left corner of the cleared back buffer, captures the `size`
+/// pixels at that corner and updates `node` with their comparison with the
+/// golden image `name`.
+pub fn test_golden<F>(
+    main_ctx: &mut MainContext,
+    node: Arc<LeafTestNode>,
+    name: &'static str,
+    size: PhysicalSize<u32>,
+    tolerance: GoldenTolerance,
+    draw: F,
+) -> anyhow::Result<()>
+where
+    F: FnOnce(&mut DrawContext) + Send + 'static,
+{
+    let image = main_ctx
+        .channels
+        .draw
+        .query(move |ctx, _| {
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            }
+            let scale = 1.0 / ctx.ui_scale();
+            ctx.transform_stack.push();
+            ctx.transform_stack
+                .apply(&Affine2::from_scale(Vec2::splat(scale)));
+            draw(ctx);
+            ctx.flush_shapes();
+            ctx.flush_sprites();
+            ctx.transform_stack.pop();
+            // the frames are rendered over it afterwards
+            screenshot::read_back_buffer(ctx)
+        })
+        .with_context(|| format!("unable to render golden image {name}"))?;
+    main_ctx.spawn_local(async move {
+        let result = image
+            .await
+            .and_then(|image| crop(image?.into_rgba_image()?, size));
+        node.update(match result {
+            Ok(image) => compare_golden(name, &image, tolerance),
+            Err(e) => Err(e.into()),
+        });
+        Ok(())
+    });
+    Ok(())
+}
+
+fn crop(image: RgbaImage, size: PhysicalSize<u32>) -> anyhow::Result<RgbaImage> {
+    anyhow::ensure!(
+        size.width <= image.width() && size.height <= image.height(),
+        "{}x{} golden region larger than the {}x{} frame",
+        size.width,
+        size.height,
+        image.width(),
+        image.height()
+    );
+    Ok(image::imageops::crop_imm(&image, 0, 0, size.width, size.height).to_image())
+}
+
+fn write_png(path: &Path, image: &RgbaImage) -> TestResult {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("unable to create {}", dir.display()))?;
+    }
+    image
+        .save(path)
+        .with_context(|| format!("unable to write {}", path.display()))?;
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let black = Rgba([0, 0, 0, 255]);
+    let white = Rgba([255, 255, 255, 255]);
+    assert!(perceptual_delta(&black, &white) > 0.95);
+    assert_eq!(perceptual_delta(&white, &white), 0.0);
+    // luma changes are more visible than the same change of blue
+    let gray = Rgba([128, 128, 128, 255]);
+    let lighter = Rgba([148, 148, 148, 255]);
+    let bluer = Rgba([128, 128, 148, 255]);
+    assert!(perceptual_delta(&gray, &lighter) > perceptual_delta(&gray, &bluer));
+
+    let image = RgbaImage::from_pixel(4, 4, gray);
+    assert_eq!(
+        crop(image.clone(), PhysicalSize::new(2, 3))
+            .unwrap()
+            .dimensions(),
+        (2, 3)
+    );
+    assert!(crop(image, PhysicalSize::new(5, 1)).is_err());
+}
@@ -0,0 +1,307 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context};
+use winit::{
+    dpi::{LogicalPosition, PhysicalSize},
+    event::{
+        DeviceId, ElementState, Event, KeyboardInput, ModifiersState, MouseButton,
+        MouseScrollDelta, TouchPhase, VirtualKeyCode, WindowEvent,
+    },
+    event_loop::EventLoopProxy,
+};
+
+use crate::{
+    events::GameUserEvent,
+    exec::{
+        main_ctx::MainContext,
+        query::{self, QueryReturn, ServerQuery},
+    },
+    scene::main::RootScene,
+    ui::{
+        utils::geom::{UIPos, UIRect},
+        Widget, WidgetId,
+    },
+};
+
+/// Scripts synthetic window events for end-to-end tests.
+///
+/// The events are handled by the main thread as if they came from winit,
+/// every action resolves once all of its events have been handled.
+#[derive(Clone)]
+pub struct InputDriver {
+    proxy: EventLoopProxy<GameUserEvent>,
+}
+
+type SyntheticEvents = Vec<WindowEvent<'static>>;
+
+/// A synthetic input, see `InputDriver::perform` and `InputScript`.
+#[derive(Clone)]
+pub enum InputAction {
+    /// moves the cursor, in logical window coordinates
+    MoveTo(UIPos),
+    /// moves the cursor to the center of the widget of the tree under the
+    /// root, located when the action is performed
+    MoveToWidget(Arc<dyn Widget>, WidgetId),
+    Press(MouseButton),
+    Release(MouseButton),
+    Click(MouseButton),
+    Scroll(MouseScrollDelta),
+    PressKey(VirtualKeyCode),
+    /// presses the key while holding the modifiers, e.g. a shortcut
+    PressKeyWith(ModifiersState, VirtualKeyCode),
+    TypeText(String),
+    /// resizes the window, in physical pixels. Handled like a real resize,
+    /// the draw server and every scene see the new size
+    Resize(PhysicalSize<u32>),
+}
+
+/// Synthetic inputs performed at given times since the start of the
+/// script, on the update server clock: virtual in lockstep runs.
+#[derive(Clone, Default)]
+pub struct InputScript {
+    steps: Vec<(Duration, InputAction)>,
+}
+
+impl InputScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Performs `action` at `time`, actions at the same time are performed
+    /// in the order they were added.
+    pub fn at(mut self, time: Duration, action: InputAction) -> Self {
+        self.steps.push((time, action));
+        self
+    }
+}
+
+impl InputDriver {
+    pub fn new(main_ctx: &MainContext) -> Self {
+        Self {
+            proxy: main_ctx.event_loop_proxy.clone(),
+        }
+    }
+
+    /// Moves the cursor to `pos`, in logical window coordinates.
+    pub fn move_to(&self, pos: UIPos) -> anyhow::Result<ServerQuery<()>> {
+        self.perform(InputAction::MoveTo(pos))
+    }
+
+    /// Moves the cursor to the center of the widget `id` of the tree under
+    /// `root`.
+    pub fn move_to_widget(
+        &self,
+        root: &dyn Widget,
+        id: WidgetId,
+    ) -> anyhow::Result<ServerQuery<()>> {
+        let pos = widget_center(root, id).with_context(|| format!("widget {id:?} not found"))?;
+        self.move_to(pos)
+    }
+
+    /// Presses and releases `button` at the current cursor position.
+    pub fn click(&self, button: MouseButton) -> anyhow::Result<ServerQuery<()>> {
+        self.perform(InputAction::Click(button))
+    }
+
+    /// Scrolls by `delta` at the current cursor position.
+    pub fn scroll(&self, delta: MouseScrollDelta) -> anyhow::Result<ServerQuery<()>> {
+        self.perform(InputAction::Scroll(delta))
+    }
+
+    /// Presses and releases `key`.
+    pub fn press_key(&self, key: VirtualKeyCode) -> anyhow::Result<ServerQuery<()>> {
+        self.perform(InputAction::PressKey(key))
+    }
+
+    /// Presses and releases `key` while holding `modifiers`.
+    pub fn press_key_with(
+        &self,
+        modifiers: ModifiersState,
+        key: VirtualKeyCode,
+    ) -> anyhow::Result<ServerQuery<()>> {
+        self.perform(InputAction::PressKeyWith(modifiers, key))
+    }
+
+    /// Sends one `ReceivedCharacter` event per character of `text`.
+    pub fn type_text(&self, text: &str) -> anyhow::Result<ServerQuery<()>> {
+        self.perform(InputAction::TypeText(text.to_owned()))
+    }
+
+    /// Resizes the window to `size`, in physical pixels, see
+    /// `InputAction::Resize`.
+    pub fn resize(&self, size: PhysicalSize<u32>) -> anyhow::Result<ServerQuery<()>> {
+        self.perform(InputAction::Resize(size))
+    }
+
+    /// Performs `action` as soon as the main thread handles it.
+    pub fn perform(&self, action: InputAction) -> anyhow::Result<ServerQuery<()>> {
+        let (ret, query) = query::query();
+        self.proxy
+            .send_event(GameUserEvent::Execute(Box::new(
+                move |main_ctx, root_scene| {
+                    dispatch(main_ctx, root_scene, action)?;
+                    ret.send((), &main_ctx.event_loop_proxy)
+                },
+            )))
+            .map_err(|e| anyhow!("{e}"))
+            .context("unable to send synthetic input to main thread")?;
+        Ok(query)
+    }
+
+    /// Plays `script` from now on, resolves once its last action has been
+    /// handled.
+    pub fn play(
+        &self,
+        main_ctx: &mut MainContext,
+        script: InputScript,
+    ) -> anyhow::Result<ServerQuery<()>> {
+        let mut steps = script.steps;
+        steps.sort_by_key(|(time, _)| *time);
+        let (ret, query) = query::query();
+        schedule(main_ctx, steps.into(), Duration::ZERO, ret)?;
+        Ok(query)
+    }
+}
+
+/// Schedules the first of `steps`, which schedules the next one once
+/// performed, so that the actions are never reordered. `now` is the time
+/// of the script.
+fn schedule(
+    main_ctx: &mut MainContext,
+    mut steps: VecDeque<(Duration, InputAction)>,
+    now: Duration,
+    ret: QueryReturn<()>,
+) -> anyhow::Result<()> {
+    let (time, action) = match steps.pop_front() {
+        Some(step) => step,
+        None => return ret.send((), &main_ctx.event_loop_proxy),
+    };
+    main_ctx
+        .set_timeout(time - now, move |main_ctx, root_scene| {
+            dispatch(main_ctx, root_scene, action)?;
+            schedule(main_ctx, steps, time, ret)
+        })
+        .context("unable to schedule synthetic input")
+}
+
+/// Handles the events of `action` as if they came from winit.
+fn dispatch(
+    main_ctx: &mut MainContext,
+    root_scene: &mut RootScene,
+    action: InputAction,
+) -> anyhow::Result<()> {
+    let window_id = main_ctx.display.get_window_id();
+    for event in action.events(main_ctx)? {
+        main_ctx.handle_event(root_scene, Event::WindowEvent { window_id, event })?;
+    }
+    Ok(())
+}
+
+impl InputAction {
+    #[allow(deprecated)]
+    fn events(self, main_ctx: &MainContext) -> anyhow::Result<SyntheticEvents> {
+        Ok(match self {
+            Self::MoveTo(pos) => vec![cursor_moved(main_ctx, pos)],
+            Self::MoveToWidget(root, id) => {
+                let pos = widget_center(root.as_ref(), id)
+                    .with_context(|| format!("widget {id:?} not found"))?;
+                vec![cursor_moved(main_ctx, pos)]
+            }
+            Self::Press(button) => vec![mouse_input(ElementState::Pressed, button)],
+            Self::Release(button) => vec![mouse_input(ElementState::Released, button)],
+            Self::Click(button) => vec![
+                mouse_input(ElementState::Pressed, button),
+                mouse_input(ElementState::Released, button),
+            ],
+            Self::Scroll(delta) => vec![WindowEvent::MouseWheel {
+                device_id: synthetic_device_id(),
+                delta,
+                phase: TouchPhase::Moved,
+                modifiers: ModifiersState::empty(),
+            }],
+            Self::PressKey(key) => vec![
+                keyboard_input(ElementState::Pressed, key),
+                keyboard_input(ElementState::Released, key),
+            ],
+            Self::PressKeyWith(modifiers, key) => vec![
+                WindowEvent::ModifiersChanged(modifiers),
+                keyboard_input(ElementState::Pressed, key),
+                keyboard_input(ElementState::Released, key),
+                WindowEvent::ModifiersChanged(ModifiersState::empty()),
+            ],
+            Self::TypeText(text) => text.chars().map(WindowEvent::ReceivedCharacter).collect(),
+            Self::Resize(size) => vec![WindowEvent::Resized(size)],
+        })
+    }
+}
+
+/// Center of the widget `id` in the coordinates of `root`'s parent, the
+/// bounds of every widget are relative to its container.
+pub fn widget_center(root: &dyn Widget, id: WidgetId) -> Option<UIPos> {
+    fn find(widget: &Arc<dyn Widget>, id: WidgetId, offset: UIPos) -> Option<UIRect> {
+        let bounds = widget.get_bounds();
+        let pos = UIPos::new(offset.x + bounds.pos.x, offset.y + bounds.pos.y);
+        if widget.id() == id {
+            return Some(UIRect::new(pos, bounds.size));
+        }
+        widget
+            .children()
+            .iter()
+            .find_map(|child| find(child, id, pos))
+    }
+
+    let bounds = if root.id() == id {
+        UIRect::new(UIPos::ZERO, root.get_bounds().size)
+    } else {
+        // the root receives window coordinates as is
+        root.children()
+            .iter()
+            .find_map(|child| find(child, id, UIPos::ZERO))?
+    };
+    Some(UIPos::new(
+        bounds.pos.x + bounds.size.width / 2.0,
+        bounds.pos.y + bounds.size.height / 2.0,
+    ))
+}
+
+/// Device of every synthetic event, lets handlers tell them apart from real
+/// input.
+pub fn synthetic_device_id() -> DeviceId {
+    // SAFETY: the dummy id is only compared against other ids, synthetic
+    // events are never passed back to winit
+    unsafe { DeviceId::dummy() }
+}
+
+#[allow(deprecated)]
+fn cursor_moved(main_ctx: &MainContext, pos: UIPos) -> WindowEvent<'static> {
+    let scale_factor = main_ctx.display.get_scale_factor();
+    WindowEvent::CursorMoved {
+        device_id: synthetic_device_id(),
+        position: LogicalPosition::new(pos.x as f64, pos.y as f64).to_physical(scale_factor),
+        modifiers: ModifiersState::empty(),
+    }
+}
+
+#[allow(deprecated)]
+fn mouse_input(state: ElementState, button: MouseButton) -> WindowEvent<'static> {
+    WindowEvent::MouseInput {
+        device_id: synthetic_device_id(),
+        state,
+        button,
+        modifiers: ModifiersState::empty(),
+    }
+}
+
+#[allow(deprecated)]
+fn keyboard_input(state: ElementState, key: VirtualKeyCode) -> WindowEvent<'static> {
+    WindowEvent::KeyboardInput {
+        device_id: synthetic_device_id(),
+        input: KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(key),
+            modifiers: ModifiersState::empty(),
+        },
+        is_synthetic: true,
+    }
+}
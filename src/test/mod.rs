@@ -1,25 +1,65 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use winit::event_loop::EventLoopProxy;
 
 use crate::{
-    events::GameUserEvent,
-    utils::{error::ResultExt, mutex::Mutex},
+    display::backend,
+    events::{
+        error::{ErrorReport, Severity},
+        GameUserEvent,
+    },
+    exec::{main_ctx::MainContext, server::draw::ServerSendChannelExt},
+    graphics::screenshot,
+    utils::{args::args, config::TestConfig, error::ResultExt, mutex::Mutex},
 };
 
-use self::tree::ParentTestNode;
+use self::{
+    capture::LogCapture,
+    filter::TestSelection,
+    result::{TestOutcome, TestResult},
+    schedule::{GroupScene, GroupSetup, Isolation, TestScheduler},
+    tree::{LeafTestNode, ParentTestNode, TreeSettings},
+};
 
+pub mod artifacts;
 pub mod assert;
+pub mod capture;
+pub mod filter;
+pub mod gallery;
+pub mod golden;
+pub mod input;
 pub mod result;
+pub mod schedule;
+pub mod snapshot;
 pub mod tree;
 
 pub struct TestManager {
     pub root: Arc<ParentTestNode>,
+    scheduler: Mutex<TestScheduler>,
     proxy: Mutex<EventLoopProxy<GameUserEvent>>,
     done_init: AtomicBool,
+    fail_on_error: bool,
+    /// errors failing the run, only recorded if `fail_on_error` is set
+    errors: Mutex<Vec<ErrorReport>>,
+    /// failures being captured, see `capture_failure`
+    captures: Mutex<PendingCaptures>,
+}
+
+/// The run exits once the failures are captured.
+#[derive(Default)]
+struct PendingCaptures {
+    count: usize,
+    exit_code: Option<TestExitCode>,
 }
 
 enum TestExitCode {
@@ -29,58 +69,318 @@ enum TestExitCode {
 }
 
 impl TestManager {
-    pub fn new(proxy: EventLoopProxy<GameUserEvent>) -> Arc<Self> {
+    pub fn new(proxy: EventLoopProxy<GameUserEvent>, config: &TestConfig) -> Arc<Self> {
+        let selection = TestSelection::from_args();
+        if !selection.is_everything() {
+            tracing::info!("only running the tests selected by {selection:?}");
+        }
+        let fail_on_error = config.fail_on_error;
         Arc::<Self>::new_cyclic(|weak| {
+            let settings = TreeSettings {
+                selection,
+                leaf_timeout: Duration::from_secs(config.leaf_timeout),
+                on_failure: Some(Box::new(enclose!((weak) move |leaf: &LeafTestNode| {
+                    if let Some(slf) = weak.upgrade() {
+                        slf.on_failure(leaf.full_name());
+                    }
+                }))),
+            };
             let weak = weak.clone();
+            let root = ParentTestNode::new_root("root", settings, move |_, result| {
+                if let Some(slf) = weak.upgrade() {
+                    if !slf.done_init.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    let exit_code = slf.exit_code(result);
+                    tracing::info!("all test finished, result of root test is {:?}", result);
+                    slf.exit(exit_code);
+                }
+            });
             Self {
                 proxy: Mutex::new(proxy),
-                root: ParentTestNode::new_root("root", move |_, result| {
-                    if let Some(slf) = weak.upgrade() {
-                        if !slf.done_init.load(Ordering::Relaxed) {
-                            return;
-                        }
-
-                        let exit_code = if result.is_ok() {
-                            TestExitCode::Complete
-                        } else {
-                            TestExitCode::Failed
-                        };
-                        tracing::info!("all test finished, result of root test is {:?}", result);
-                        slf.proxy
-                            .lock()
-                            .send_event(GameUserEvent::Exit(exit_code as _))
-                            .log_warn();
-                    }
-                }),
+                scheduler: Mutex::new(TestScheduler::new(root.clone(), config.parallel_groups)),
+                root,
                 done_init: AtomicBool::new(false),
+                fail_on_error,
+                errors: Mutex::new(Vec::new()),
+                captures: Mutex::new(PendingCaptures::default()),
             }
         })
     }
 
     pub fn set_timeout_func(&self) {
-        let result = self.root.result.lock();
-        let exit_code = match *result {
-            Some(Ok(_)) => TestExitCode::Complete,
-            Some(Err(_)) => TestExitCode::Failed,
+        let exit_code = match self.root.get_result() {
+            Some(result) => self.exit_code(&result),
             None => TestExitCode::Timeout,
         };
-        self.proxy
-            .lock()
-            .send_event(GameUserEvent::Exit(exit_code as _))
-            .log_warn();
+        // don't wait for the captures, the draw server may be stuck
+        self.exit_now(exit_code);
     }
 
     pub fn finish_init(&self) {
         self.done_init.store(true, Ordering::Relaxed);
-        let result = self.root.result.lock();
-        let exit_code = match *result {
-            Some(Ok(_)) => TestExitCode::Complete,
-            Some(Err(_)) => TestExitCode::Failed,
+        // every selected test may have finished during the setup
+        let exit_code = match self.root.get_result() {
+            Some(result) => self.exit_code(&result),
             None => return,
         };
+        self.exit(exit_code);
+    }
+
+    /// Adds a group of tests to the root, see `TestScheduler::add`.
+    pub fn add_group<F>(
+        &self,
+        name: &'static str,
+        isolation: Isolation,
+        setup: F,
+    ) -> Arc<GroupScene>
+    where
+        F: GroupSetup + 'static,
+    {
+        self.scheduler.lock().add(name, isolation, setup)
+    }
+
+    /// Starts the test groups that can run, times the tests out and retries
+    /// them, see `ParentTestNode::poll`.
+    pub fn poll(&self, main_ctx: &mut MainContext) {
+        TestScheduler::poll(&self.scheduler, main_ctx);
+        self.root.poll(main_ctx);
+    }
+
+    /// Records `report` if it fails the run, see `TestConfig::fail_on_error`.
+    pub fn report_error(&self, report: &ErrorReport) {
+        if self.fail_on_error && report.severity >= Severity::Error {
+            tracing::error!("test run will fail because of the error: {report}");
+            self.errors.lock().push(report.clone());
+        }
+    }
+
+    /// Exits once the pending failures are captured.
+    fn exit(&self, exit_code: TestExitCode) {
+        let mut captures = self.captures.lock();
+        if captures.count > 0 {
+            captures.exit_code = Some(exit_code);
+        } else {
+            drop(captures);
+            self.exit_now(exit_code);
+        }
+    }
+
+    fn exit_now(&self, exit_code: TestExitCode) {
+        self.log_report();
         self.proxy
             .lock()
             .send_event(GameUserEvent::Exit(exit_code as _))
             .log_warn();
     }
+
+    /// Logs the outcome and duration of every selected test, how many were
+    /// skipped, then why the failed ones failed.
+    fn log_report(&self) {
+        let mut report = String::from("test report:");
+        let mut failures = String::new();
+        let mut counts = [0usize; 4];
+        for leaf in self.root.leaves() {
+            let outcome = leaf.outcome();
+            let (count, status) = match outcome {
+                Some(TestOutcome::Passed) => (0, "ok"),
+                Some(TestOutcome::Failed) => (1, "FAILED"),
+                None => (2, "pending"),
+                Some(TestOutcome::Skipped) => (3, "skipped"),
+            };
+            counts[count] += 1;
+            if outcome == Some(TestOutcome::Skipped) {
+                continue;
+            }
+            if let Some(Err(error)) = leaf.result.lock().as_ref() {
+                write!(failures, "\n{}: {error}", leaf.full_name()).ok();
+            }
+            let duration = match leaf.duration() {
+                Some(duration) => format!("{duration:.1?}"),
+                None => "-".to_owned(),
+            };
+            write!(
+                report,
+                "\n  {status:<7} {duration:>10}  {}",
+                leaf.full_name()
+            )
+            .ok();
+        }
+        let [passed, failed, pending, skipped] = counts;
+        write!(
+            report,
+            "\n{passed} passed, {failed} failed, {pending} pending, {skipped} skipped"
+        )
+        .ok();
+        tracing::info!("{report}");
+        if !failures.is_empty() {
+            tracing::error!("test failures:{failures}");
+        }
+    }
+
+    /// Dumps the logs of the failed test and, when frames are rendered, a
+    /// screenshot of the next frame into its artifacts directory.
+    fn on_failure(self: &Arc<Self>, full_name: &str) {
+        let full_name = full_name.to_owned();
+        let dir = artifacts::test_dir(&args().artifacts_dir, &full_name);
+        self.captures.lock().count += 1;
+        let slf = self.clone();
+        let sent = self
+            .proxy
+            .lock()
+            .send_event(GameUserEvent::Execute(Box::new(move |main_ctx, _| {
+                let result = slf.clone().capture_failure(main_ctx, full_name, dir);
+                if result.is_err() {
+                    slf.capture_done();
+                }
+                result
+            })));
+        if sent.log_warn().is_none() {
+            self.capture_done();
+        }
+    }
+
+    fn capture_failure(
+        self: Arc<Self>,
+        main_ctx: &mut MainContext,
+        full_name: String,
+        dir: PathBuf,
+    ) -> anyhow::Result<()> {
+        let mut logs = failure_logs(
+            "main",
+            &main_ctx.test_logs,
+            &main_ctx.test_capture,
+            &full_name,
+        );
+        let draw_logs = main_ctx.channels.draw.query(move |context, _| {
+            failure_logs(
+                "draw",
+                &context.test_logs,
+                &context.test_capture,
+                &full_name,
+            )
+        })?;
+        let frame = backend::renders_frames()
+            .then(|| main_ctx.channels.draw.capture_frame())
+            .transpose()?;
+        main_ctx.spawn_local(async move {
+            let result: anyhow::Result<()> = async {
+                logs.extend(draw_logs.await?);
+                let path = artifacts::write_logs(&dir, &logs)?;
+                tracing::error!("failed test logs: {}", path.display());
+                if let Some(frame) = frame {
+                    let path = dir.join("screenshot.png");
+                    screenshot::save_png(frame.await?, &path)?;
+                    tracing::error!("failed test screenshot: {}", path.display());
+                }
+                Ok(())
+            }
+            .await;
+            self.capture_done();
+            result
+        });
+        Ok(())
+    }
+
+    fn capture_done(&self) {
+        let mut captures = self.captures.lock();
+        captures.count -= 1;
+        if captures.count == 0 {
+            if let Some(exit_code) = captures.exit_code.take() {
+                drop(captures);
+                self.exit_now(exit_code);
+            }
+        }
+    }
+
+    fn exit_code(&self, result: &TestResult) -> TestExitCode {
+        if result.is_ok() && self.errors.lock().is_empty() {
+            TestExitCode::Complete
+        } else {
+            TestExitCode::Failed
+        }
+    }
+}
+
+/// Logs of `source` written by the test `full_name`, see
+/// `artifacts::is_log_of`.
+fn failure_logs(
+    source: &str,
+    logs: &HashMap<Cow<'static, str>, String>,
+    capture: &LogCapture,
+    full_name: &str,
+) -> Vec<(String, String)> {
+    logs.iter()
+        .map(|(name, log)| (name.as_ref(), log.clone()))
+        .chain(capture.texts())
+        .filter(|(name, _)| artifacts::is_log_of(name, full_name))
+        .map(|(name, log)| (format!("{source}: {name}"), log))
+        .collect()
+}
+
+#[test]
+fn test() {
+    let failed = Arc::new(Mutex::new(Vec::new()));
+    let settings = TreeSettings {
+        selection: TestSelection::default(),
+        leaf_timeout: Duration::from_secs(1),
+        on_failure: Some(Box::new(enclose!((failed) move |leaf: &LeafTestNode| {
+            failed.lock().push(leaf.full_name().to_owned());
+        }))),
+    };
+    let root = ParentTestNode::new_root("root", settings, |_, _| {});
+    let node = root.new_child_parent("capture");
+    node.new_child_leaf("passing").update(Ok(()));
+    node.new_child_leaf("failing")
+        .update(assert::assert_unreachable("deliberate failure"));
+    let failed = failed.lock().clone();
+    assert_eq!(failed, ["root.capture.failing"]);
+
+    let settings = TreeSettings {
+        selection: TestSelection::new(vec!["selected.*".into()], vec![]),
+        leaf_timeout: Duration::from_secs(1),
+        on_failure: None,
+    };
+    let selected_root = ParentTestNode::new_root("root", settings, |_, _| {});
+    let selected = selected_root.new_child_parent("selected");
+    let other = selected_root.new_child_parent("other");
+    let leaf = other.new_child_leaf("leaf");
+    assert_eq!(selected.outcome(), None);
+    assert_eq!(leaf.outcome(), Some(TestOutcome::Skipped));
+    assert_eq!(other.outcome(), Some(TestOutcome::Skipped));
+    let leaf = selected.new_child_leaf("leaf");
+    assert_eq!(leaf.outcome(), None);
+    leaf.update(Ok(()));
+    assert_eq!(leaf.outcome(), Some(TestOutcome::Passed));
+    assert_eq!(selected_root.outcome(), Some(TestOutcome::Passed));
+
+    let logs = [
+        "root.capture.failing",
+        "root.capture.failing.draw order",
+        "root.capture.failing_twice",
+        "root.capture.passing",
+        "stack insert",
+    ]
+    .into_iter()
+    .map(|name| (Cow::Borrowed(name), format!("{name} log")))
+    .collect();
+    let mut log_capture = LogCapture::new(capture::LogSource::Main);
+    log_capture.record("root.capture.failing.events", "entered");
+    log_capture.record("root.capture.failing.events", "left");
+    log_capture.record("root.capture.passing", "entered");
+    let mut captured = failure_logs("main", &logs, &log_capture, &failed[0]);
+    captured.sort();
+    assert_eq!(
+        captured,
+        [
+            ("main: root.capture.failing", "root.capture.failing log"),
+            (
+                "main: root.capture.failing.draw order",
+                "root.capture.failing.draw order log"
+            ),
+            ("main: root.capture.failing.events", "entered\nleft"),
+        ]
+        .map(|(name, log)| (name.to_owned(), log.to_owned()))
+    );
 }
@@ -1,7 +1,20 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
 
 pub type TestResult = anyhow::Result<(), TestError>;
 
+/// How a finished test ended, see `GenericTestNode::outcome`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    /// not selected by `--test-filter` and `--test-tag`, never run
+    Skipped,
+}
+
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Comparison {
     Less,
@@ -12,6 +25,19 @@ pub enum Comparison {
     NotEquals,
 }
 
+impl Comparison {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Less => "<",
+            Self::Greater => ">",
+            Self::LessEquals => "<=",
+            Self::GreaterEquals => ">=",
+            Self::Equals => "==",
+            Self::NotEquals => "!=",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum TestError {
     ChildFailedError(Vec<Cow<'static, str>>),
@@ -30,6 +56,83 @@ pub enum TestError {
         custom_msg: Cow<'static, str>,
     },
     GenericError(anyhow::Error),
+    /// the test wasn't finished before its timeout
+    Timeout(Duration),
+    /// the test failed every attempt, with the error of the last one
+    RetriesExhausted {
+        attempts: u32,
+        last: Box<TestError>,
+    },
+    /// error of an assertion macro, with where it failed
+    Located {
+        error: Box<TestError>,
+        file: &'static str,
+        line: u32,
+        /// part of the test log around the first mismatch
+        log_snippet: Option<String>,
+    },
+}
+
+impl TestError {
+    /// Wraps the error of an assertion failing at `file:line`.
+    pub fn located(self, file: &'static str, line: u32, log_snippet: Option<String>) -> Self {
+        Self::Located {
+            error: Box::new(self),
+            file,
+            line,
+            log_snippet,
+        }
+    }
+}
+
+impl Display for TestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ChildFailedError(children) => {
+                write!(f, "failed children: {}", children.join(", "))
+            }
+            Self::AssertCompareError {
+                found,
+                expected,
+                custom_msg,
+                comparison,
+                compare_error,
+            } => {
+                write!(f, "{custom_msg}\n  found:    {found}\n  expected: ")?;
+                if *comparison != Comparison::Equals {
+                    write!(f, "{} ", comparison.symbol())?;
+                }
+                write!(f, "{expected}")?;
+                match compare_error {
+                    Some(error) => write!(f, "\n  error:    {error}"),
+                    None => Ok(()),
+                }
+            }
+            Self::AssertError { result, custom_msg } => {
+                write!(f, "{custom_msg}: assertion was {result}")
+            }
+            Self::AssertUnreachable { custom_msg } => {
+                write!(f, "{custom_msg}: reached unreachable code")
+            }
+            Self::GenericError(error) => write!(f, "{error:#}"),
+            Self::Timeout(timeout) => write!(f, "timed out after {timeout:?}"),
+            Self::RetriesExhausted { attempts, last } => {
+                write!(f, "failed {attempts} attempts, the last one with: {last}")
+            }
+            Self::Located {
+                error,
+                file,
+                line,
+                log_snippet,
+            } => {
+                write!(f, "{error}\n  at {file}:{line}")?;
+                match log_snippet {
+                    Some(snippet) => write!(f, "\n{snippet}"),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
 }
 
 impl From<anyhow::Error> for TestError {
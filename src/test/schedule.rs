@@ -0,0 +1,224 @@
+use std::{collections::VecDeque, sync::Arc, time::Instant};
+
+use trait_set::trait_set;
+
+use crate::{
+    events::GameEvent,
+    exec::main_ctx::MainContext,
+    graphics::context::DrawContext,
+    scene::{main::RootScene, Scene, SceneContainer},
+    utils::mutex::Mutex,
+};
+
+use super::tree::ParentTestNode;
+
+trait_set! {
+    /// Creates the tests of a group under the given node, and the scenes
+    /// they need.
+    pub trait GroupSetup = FnOnce(&mut MainContext, &Arc<ParentTestNode>) -> anyhow::Result<SceneContainer> + Send;
+}
+
+/// How a test group may overlap with the other groups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Isolation {
+    /// runs alongside the other shared groups, e.g. pure logic tests
+    Shared,
+    /// runs alone, e.g. tests driving input, resizing the window, reading
+    /// the screen or timing frames
+    Exclusive,
+}
+
+/// Scenes of a test group, empty until the group starts and emptied once
+/// its tests finished.
+#[derive(Default)]
+pub struct GroupScene {
+    container: Mutex<Option<Arc<SceneContainer>>>,
+}
+
+struct TestGroup {
+    name: &'static str,
+    isolation: Isolation,
+    node: Arc<ParentTestNode>,
+    scene: Arc<GroupScene>,
+    setup: Option<Box<dyn GroupSetup>>,
+    started_at: Option<Instant>,
+}
+
+/// Starts the test groups in the order they were added, running at most
+/// `max_parallel` shared groups at once, and exclusive groups alone.
+pub struct TestScheduler {
+    root: Arc<ParentTestNode>,
+    max_parallel: usize,
+    pending: VecDeque<TestGroup>,
+    running: Vec<TestGroup>,
+}
+
+impl Scene for GroupScene {
+    fn handle_event<'a>(
+        self: Arc<Self>,
+        ctx: &mut MainContext,
+        root_scene: &RootScene,
+        event: GameEvent<'a>,
+    ) -> Option<GameEvent<'a>> {
+        let container = self.container.lock().clone();
+        match container {
+            Some(container) => container.handle_event(ctx, root_scene, event),
+            None => Some(event),
+        }
+    }
+
+    fn draw(self: Arc<Self>, ctx: &mut DrawContext) {
+        let container = self.container.lock().clone();
+        if let Some(container) = container {
+            container.draw(ctx);
+        }
+    }
+
+    fn draw_pick(self: Arc<Self>, ctx: &mut DrawContext) {
+        let container = self.container.lock().clone();
+        if let Some(container) = container {
+            container.draw_pick(ctx);
+        }
+    }
+}
+
+impl TestScheduler {
+    pub fn new(root: Arc<ParentTestNode>, max_parallel: usize) -> Self {
+        Self {
+            root,
+            max_parallel: max_parallel.max(1),
+            pending: VecDeque::new(),
+            running: Vec::new(),
+        }
+    }
+
+    /// Adds a group whose tests are created by `setup` once it starts. The
+    /// scenes it returns are pushed to the returned scene, which should be
+    /// in the test scene.
+    pub fn add<F>(&mut self, name: &'static str, isolation: Isolation, setup: F) -> Arc<GroupScene>
+    where
+        F: GroupSetup + 'static,
+    {
+        let scene = Arc::new(GroupScene::default());
+        self.pending.push_back(TestGroup {
+            name,
+            isolation,
+            node: self.root.new_child_group(name),
+            scene: scene.clone(),
+            setup: Some(Box::new(setup)),
+            started_at: None,
+        });
+        scene
+    }
+
+    /// Removes the scenes of the finished groups, returns the next group
+    /// to set up if it can start.
+    fn next(&mut self) -> Option<TestGroup> {
+        self.running.retain(|group| {
+            if !group.node.finished() {
+                return true;
+            }
+            let elapsed = group.started_at.map(|t| t.elapsed()).unwrap_or_default();
+            tracing::info!("test group `{}` finished in {elapsed:.1?}", group.name);
+            group.scene.container.lock().take();
+            false
+        });
+        let exclusive_running = self
+            .running
+            .iter()
+            .any(|group| group.isolation == Isolation::Exclusive);
+        let can_start = !exclusive_running
+            && match self.pending.front()?.isolation {
+                Isolation::Exclusive => self.running.is_empty(),
+                Isolation::Shared => self.running.len() < self.max_parallel,
+            };
+        can_start.then(|| self.pending.pop_front()).flatten()
+    }
+
+    /// Retires the finished groups and sets up the next ones.
+    pub fn poll(this: &Mutex<Self>, main_ctx: &mut MainContext) {
+        loop {
+            let (name, node, scene, setup) = {
+                let mut slf = this.lock();
+                let mut group = match slf.next() {
+                    Some(group) => group,
+                    None => return,
+                };
+                group.started_at = Some(Instant::now());
+                let setup = group.setup.take().expect("test group started twice");
+                let started = (group.name, group.node.clone(), group.scene.clone(), setup);
+                slf.running.push(group);
+                started
+            };
+            tracing::info!("starting test group `{name}`");
+            // not holding the lock, setups may complete tests right away
+            match setup(main_ctx, &node) {
+                Ok(container) => *scene.container.lock() = Some(Arc::new(container)),
+                Err(e) => {
+                    tracing::error!("unable to set up test group `{name}`: {e:?}");
+                    node.new_child_leaf(format!("{name}_setup"))
+                        .update(Err(e.into()));
+                }
+            }
+            node.settle();
+        }
+    }
+}
+
+#[test]
+fn test() {
+    use super::{filter::TestSelection, tree::TreeSettings};
+
+    let root = ParentTestNode::new_root(
+        "root",
+        TreeSettings {
+            selection: TestSelection::default(),
+            leaf_timeout: std::time::Duration::from_secs(1),
+            on_failure: None,
+        },
+        |_, _| {},
+    );
+    let mut scheduler = TestScheduler::new(root, 2);
+    for (name, isolation) in [
+        ("a", Isolation::Shared),
+        ("b", Isolation::Shared),
+        ("c", Isolation::Shared),
+        ("d", Isolation::Exclusive),
+        ("e", Isolation::Shared),
+    ] {
+        scheduler.add(name, isolation, |_, _| Ok(SceneContainer::new()));
+    }
+    let start = |scheduler: &mut TestScheduler| {
+        let group = scheduler.next()?;
+        let name = group.name;
+        let leaf = group.node.new_child_leaf(name);
+        scheduler.running.push(group);
+        Some((name, leaf))
+    };
+
+    let (_, a) = start(&mut scheduler).unwrap();
+    let (_, b) = start(&mut scheduler).unwrap();
+    assert!(start(&mut scheduler).is_none(), "at most 2 shared groups");
+    a.update(Ok(()));
+    let (name, c) = start(&mut scheduler).unwrap();
+    assert_eq!(name, "c");
+    b.update(Ok(()));
+    assert!(
+        start(&mut scheduler).is_none(),
+        "exclusive group runs alone"
+    );
+    c.update(Ok(()));
+    let (name, d) = start(&mut scheduler).unwrap();
+    assert_eq!(name, "d");
+    assert!(
+        start(&mut scheduler).is_none(),
+        "nothing runs with exclusive group"
+    );
+    d.update(Ok(()));
+    let (name, e) = start(&mut scheduler).unwrap();
+    assert_eq!(name, "e");
+    e.update(Ok(()));
+    assert!(start(&mut scheduler).is_none());
+    assert!(scheduler.running.is_empty() && scheduler.pending.is_empty());
+    assert!(matches!(scheduler.root.get_result(), Some(Ok(()))));
+}
@@ -0,0 +1,114 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    ui::{
+        utils::{
+            geom::{UIPos, UIRect},
+            snapshot::bounds_tree_snapshot,
+        },
+        UISizeConstraint, Widget,
+    },
+    utils::args::args,
+};
+
+use super::result::{Comparison, TestError, TestResult};
+
+/// Lays `root` out under each of `constraints` and compares the resulting
+/// bounds trees with the snapshot `name` in the snapshot directory.
+///
+/// Snapshots are committed, a missing or mismatching one fails with the
+/// new snapshot written next to it with a `.new` extension, unless
+/// `--update-snapshots` is given, in which case it is (re)written.
+pub fn assert_layout_snapshot(
+    name: &str,
+    root: &dyn Widget,
+    constraints: impl IntoIterator<Item = UISizeConstraint>,
+) -> TestResult {
+    let mut found = String::new();
+    for constraints in constraints {
+        let _ = writeln!(
+            found,
+            "constraints min=({}, {}) max=({}, {})",
+            constraints.min.width,
+            constraints.min.height,
+            constraints.max.width,
+            constraints.max.height
+        );
+        let size = root.layout(&constraints);
+        root.set_bounds(UIRect::new(UIPos::ZERO, size));
+        found.push_str(&bounds_tree_snapshot(root));
+    }
+    compare_snapshot(name, &found)
+}
+
+fn snapshot_path(name: &str) -> PathBuf {
+    args().snapshot_dir.join(format!("{name}.snap"))
+}
+
+pub fn compare_snapshot(name: &str, found: &str) -> TestResult {
+    let path = snapshot_path(name);
+    let expected = match fs::read_to_string(&path) {
+        Ok(expected) => expected,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if args().update_snapshots {
+                tracing::info!("new snapshot {}", path.display());
+                return write_snapshot(&path, found);
+            }
+            let new_path = path.with_extension("snap.new");
+            write_snapshot(&new_path, found)?;
+            return Err(TestError::AssertCompareError {
+                found: format!("new snapshot written to {}", new_path.display()),
+                expected: format!("snapshot {}", path.display()),
+                custom_msg: format!("snapshot {name} is missing").into(),
+                comparison: Comparison::Equals,
+                compare_error: Some("run with --update-snapshots to accept it".to_owned()),
+            });
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context(format!("unable to read {}", path.display()))
+                .into())
+        }
+    };
+    if expected == found {
+        return Ok(());
+    }
+    if args().update_snapshots {
+        tracing::info!("updated snapshot {}", path.display());
+        return write_snapshot(&path, found);
+    }
+
+    let new_path = path.with_extension("snap.new");
+    write_snapshot(&new_path, found)?;
+    // only the first difference is reported, the files can be diffed
+    let (line, (found_line, expected_line)) = found
+        .lines()
+        .map(Some)
+        .chain(std::iter::repeat(None))
+        .zip(expected.lines().map(Some).chain(std::iter::repeat(None)))
+        .take(found.lines().count().max(expected.lines().count()))
+        .enumerate()
+        .find(|(_, (found, expected))| found != expected)
+        .unwrap_or((0, (None, None)));
+    Err(TestError::AssertCompareError {
+        found: found_line.unwrap_or("<end of snapshot>").to_owned(),
+        expected: expected_line.unwrap_or("<end of snapshot>").to_owned(),
+        custom_msg: format!("snapshot {name} differs at line {}", line + 1).into(),
+        comparison: Comparison::Equals,
+        compare_error: Some(format!("new snapshot written to {}", new_path.display())),
+    })
+}
+
+fn write_snapshot(path: &Path, content: &str) -> TestResult {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("unable to create {}", dir.display()))?;
+    }
+    fs::write(path, content).with_context(|| format!("unable to write {}", path.display()))?;
+    Ok(())
+}
@@ -1,19 +1,52 @@
 use std::{
     borrow::Cow,
     collections::BTreeMap,
+    fmt::Debug,
     sync::{Arc, Weak},
+    time::{Duration, Instant},
 };
 
 use anyhow::Context;
 use derive_more::From;
 use trait_set::trait_set;
 
-use crate::utils::mutex::Mutex;
+use crate::{
+    exec::main_ctx::MainContext,
+    ui::{utils::snapshot::widget_tree_snapshot, Widget},
+    utils::{args::args, error::ResultExt, mutex::Mutex},
+};
 
-use super::result::{TestError, TestResult};
+use super::{
+    artifacts::{test_dir, TestArtifacts},
+    assert::assert_less_equals,
+    filter::TestSelection,
+    result::{TestError, TestOutcome, TestResult},
+};
 
 trait_set! {
     pub trait OnCompleteCallback<C> = Fn(&GenericTestNode<C>, &TestResult) + Send + Sync;
+    pub trait RetryCallback = FnMut(&mut MainContext, &Arc<LeafTestNode>) -> anyhow::Result<()> + Send;
+    pub trait FailureCallback = Fn(&LeafTestNode) + Send + Sync;
+}
+
+/// Settings shared by every node of a tree.
+pub struct TreeSettings {
+    pub selection: TestSelection,
+    /// timeout of the leaves not given one
+    pub leaf_timeout: Duration,
+    /// called when a leaf fails, after its artifacts are dumped
+    pub on_failure: Option<Box<dyn FailureCallback>>,
+}
+
+/// Options of a leaf test, see `ParentTestNode::new_child_leaf_with`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LeafOptions {
+    pub tags: &'static [&'static str],
+    /// overrides `TreeSettings::leaf_timeout`
+    pub timeout: Option<Duration>,
+    /// times the test is run again after failing, only for tests started
+    /// with `LeafTestNode::run`
+    pub retries: u32,
 }
 
 #[allow(clippy::type_complexity)]
@@ -21,13 +54,21 @@ pub struct GenericTestNode<C> {
     parent: Option<Weak<ParentTestNode>>,
     name: Cow<'static, str>,
     full_name: String,
+    /// own tags and the tags of the parents
+    tags: Vec<Cow<'static, str>>,
+    /// whether the test is selected, see `enabled`
+    enabled: bool,
+    /// see `ParentTestNode::new_child_group`
+    group: bool,
+    settings: Arc<TreeSettings>,
     content: C,
     pub result: Mutex<Option<TestResult>>,
+    pub artifacts: Mutex<TestArtifacts>,
     on_complete: Option<Box<dyn OnCompleteCallback<C>>>,
 }
 
 pub type ParentTestNode = GenericTestNode<Mutex<ParentNodeContent>>;
-pub type LeafTestNode = GenericTestNode<()>;
+pub type LeafTestNode = GenericTestNode<LeafNodeContent>;
 
 #[derive(From)]
 pub enum TestNode {
@@ -40,8 +81,33 @@ pub struct ParentNodeContent {
     children: BTreeMap<Cow<'static, str>, TestNode>,
 }
 
+pub struct LeafNodeContent {
+    timeout: Duration,
+    retries: u32,
+    state: Mutex<LeafState>,
+    /// the test run by `LeafTestNode::run`, taken while it runs
+    retry: Mutex<Option<Box<dyn RetryCallback>>>,
+}
+
+struct LeafState {
+    /// start of the last attempt, or creation of the node
+    started_at: Instant,
+    finished_at: Option<Instant>,
+    deadline: Instant,
+    attempts: u32,
+    failed_attempts: u32,
+    /// whether the test is run by `LeafTestNode::run`
+    retryable: bool,
+    /// the last attempt failed, the test is run again at the next poll
+    retry_pending: bool,
+}
+
 impl ParentTestNode {
-    pub fn new_root<F>(name: impl Into<Cow<'static, str>>, on_complete: F) -> Arc<Self>
+    pub fn new_root<F>(
+        name: impl Into<Cow<'static, str>>,
+        settings: TreeSettings,
+        on_complete: F,
+    ) -> Arc<Self>
     where
         F: OnCompleteCallback<Mutex<ParentNodeContent>> + 'static,
     {
@@ -49,56 +115,206 @@ impl ParentTestNode {
         Arc::new(Self {
             name: name.clone(),
             full_name: String::from(name),
+            tags: Vec::new(),
+            enabled: true,
+            group: false,
+            settings: Arc::new(settings),
             content: Mutex::new(ParentNodeContent::default()),
             on_complete: Some(Box::new(on_complete)),
             parent: None,
             result: Mutex::new(None),
+            artifacts: Mutex::new(TestArtifacts::default()),
         })
     }
 
-    fn new_child<C>(&self, child: GenericTestNode<C>) -> Arc<GenericTestNode<C>>
+    /// Inserts a child, skipped right away if it isn't selected. Skipped
+    /// nodes don't fail their parents, see `outcome`.
+    fn new_child<C, F>(
+        self: &Arc<Self>,
+        name: Cow<'static, str>,
+        tags: &[&'static str],
+        content: C,
+        is_enabled: F,
+    ) -> Arc<GenericTestNode<C>>
     where
         TestNode: From<Arc<GenericTestNode<C>>>,
+        F: FnOnce(&TestSelection, &str, &[Cow<'static, str>]) -> bool,
     {
-        let child = Arc::new(child);
-        let mut content = self.content.lock();
-        let ret_child = child.clone();
-        let old_value = content
-            .children
-            .insert(child.name.clone(), TestNode::from(child));
-        debug_assert!(old_value.is_none());
-        *self.result.lock() = None;
-        ret_child
+        let full_name = format!("{}.{}", self.full_name, name);
+        let mut child_tags = self.tags.clone();
+        child_tags.extend(tags.iter().map(|&tag| Cow::Borrowed(tag)));
+        // the path of the child, without the root name
+        let path = full_name
+            .split_once('.')
+            .map(|(_, path)| path)
+            .unwrap_or_default();
+        let enabled = self.enabled && is_enabled(&self.settings.selection, path, &child_tags);
+        let child = Arc::new(GenericTestNode {
+            parent: Some(Arc::downgrade(self)),
+            name: name.clone(),
+            full_name,
+            tags: child_tags,
+            enabled,
+            group: false,
+            settings: self.settings.clone(),
+            content,
+            result: Mutex::new(None),
+            artifacts: Mutex::new(TestArtifacts::default()),
+            on_complete: None,
+        });
+        {
+            let mut content = self.content.lock();
+            let old_value = content
+                .children
+                .insert(name.clone(), TestNode::from(child.clone()));
+            debug_assert!(old_value.is_none());
+        }
+        self.reset_result();
+        if !enabled {
+            tracing::trace!("test `{}` skipped", child.full_name);
+            self.update_child(&name, TestResult::Ok(()));
+        }
+        child
     }
 
     pub fn new_child_parent(
         self: &Arc<Self>,
         name: impl Into<Cow<'static, str>>,
     ) -> Arc<ParentTestNode> {
-        let name = name.into();
-        self.new_child(Self {
+        self.new_child_parent_tagged(name, &[])
+    }
+
+    /// Creates a node grouping tests set up together, see `TestScheduler`.
+    /// The group is transparent: its children are named and selected as if
+    /// they were children of this node.
+    pub fn new_child_group(self: &Arc<Self>, name: &'static str) -> Arc<ParentTestNode> {
+        let name = Cow::Borrowed(name);
+        let group = Arc::new(GenericTestNode {
             parent: Some(Arc::downgrade(self)),
-            full_name: format!("{}.{}", self.full_name, name),
-            name,
-            result: Mutex::new(None),
+            name: name.clone(),
+            full_name: self.full_name.clone(),
+            tags: self.tags.clone(),
+            enabled: self.enabled,
+            group: true,
+            settings: self.settings.clone(),
             content: Mutex::new(ParentNodeContent::default()),
+            result: Mutex::new(None),
+            artifacts: Mutex::new(TestArtifacts::default()),
             on_complete: None,
-        })
+        });
+        let old_value = self
+            .content
+            .lock()
+            .children
+            .insert(name, TestNode::from(group.clone()));
+        debug_assert!(old_value.is_none());
+        self.reset_result();
+        group
+    }
+
+    /// Completes the node if none of its children is pending, e.g. when a
+    /// group was set up without creating any test.
+    pub fn settle(&self) {
+        if self.finished() {
+            return;
+        }
+        if let Some(result) = self.get_result() {
+            self.update_result(result);
+        }
+    }
+
+    /// Marks the node and its parents pending again, a child was added.
+    fn reset_result(&self) {
+        *self.result.lock() = None;
+        if let Some(parent) = self.parent.as_ref().and_then(Weak::upgrade) {
+            parent.reset_result();
+        }
+    }
+
+    /// Creates a parent node whose tests all have `tags`. Tag parents rather
+    /// than leaves where possible, so that unselected tags skip their setup.
+    pub fn new_child_parent_tagged(
+        self: &Arc<Self>,
+        name: impl Into<Cow<'static, str>>,
+        tags: &'static [&'static str],
+    ) -> Arc<ParentTestNode> {
+        self.new_child(
+            name.into(),
+            tags,
+            Mutex::new(ParentNodeContent::default()),
+            TestSelection::may_run_parent,
+        )
     }
 
     pub fn new_child_leaf(
         self: &Arc<Self>,
         name: impl Into<Cow<'static, str>>,
     ) -> Arc<LeafTestNode> {
-        let name = name.into();
-        self.new_child(GenericTestNode {
-            parent: Some(Arc::downgrade(self)),
-            full_name: format!("{}.{}", self.full_name, name),
+        self.new_child_leaf_with(name, LeafOptions::default())
+    }
+
+    pub fn new_child_leaf_tagged(
+        self: &Arc<Self>,
+        name: impl Into<Cow<'static, str>>,
+        tags: &'static [&'static str],
+    ) -> Arc<LeafTestNode> {
+        self.new_child_leaf_with(
             name,
-            result: Mutex::new(None),
-            content: (),
-            on_complete: None,
-        })
+            LeafOptions {
+                tags,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Creates a leaf failing with `TestError::Timeout` if it isn't updated
+    /// before its timeout, counted from its creation or its last attempt.
+    pub fn new_child_leaf_with(
+        self: &Arc<Self>,
+        name: impl Into<Cow<'static, str>>,
+        options: LeafOptions,
+    ) -> Arc<LeafTestNode> {
+        let timeout = options.timeout.unwrap_or(self.settings.leaf_timeout);
+        let content = LeafNodeContent {
+            timeout,
+            retries: options.retries,
+            state: Mutex::new(LeafState {
+                started_at: Instant::now(),
+                finished_at: None,
+                deadline: Instant::now() + timeout,
+                attempts: 0,
+                failed_attempts: 0,
+                retryable: false,
+                retry_pending: false,
+            }),
+            retry: Mutex::new(None),
+        };
+        self.new_child(name.into(), options.tags, content, TestSelection::runs_leaf)
+    }
+
+    /// Fails the pending leaves past their timeout and runs the failed
+    /// leaves having retries left again.
+    pub fn poll(&self, main_ctx: &mut MainContext) {
+        let now = Instant::now();
+        for leaf in self.leaves() {
+            leaf.poll(main_ctx, now);
+        }
+    }
+
+    /// Every leaf under this node, in name order.
+    pub fn leaves(&self) -> Vec<Arc<LeafTestNode>> {
+        let mut leaves = Vec::new();
+        self.collect_leaves(&mut leaves);
+        leaves
+    }
+
+    fn collect_leaves(&self, leaves: &mut Vec<Arc<LeafTestNode>>) {
+        for child in self.content.lock().children.values() {
+            match child {
+                TestNode::Parent(par) => par.collect_leaves(leaves),
+                TestNode::Leaf(leaf) => leaves.push(leaf.clone()),
+            }
+        }
     }
 
     fn update_child(&self, name: &str, new_result: TestResult) {
@@ -119,17 +335,23 @@ impl ParentTestNode {
         }
     }
 
-    fn get_result(&self) -> Option<TestResult> {
+    /// The result of the children, `None` while some are pending.
+    pub fn get_result(&self) -> Option<TestResult> {
         let lock = self.content.lock();
         let mut failed_tests = Vec::new();
         let mut pending_tests = Vec::new();
         for (name, node) in lock.children.iter() {
-            let (guard, full_name) = match node {
-                TestNode::Parent(par) => (par.result.lock(), par.full_name.clone()),
-                TestNode::Leaf(leaf) => (leaf.result.lock(), leaf.full_name.clone()),
+            let (guard, full_name, group) = match node {
+                TestNode::Parent(par) => (par.result.lock(), par.full_name.clone(), par.group),
+                TestNode::Leaf(leaf) => (leaf.result.lock(), leaf.full_name.clone(), false),
             };
 
             match *guard {
+                // list the failed tests of groups, they share the name of
+                // this node
+                Some(TestResult::Err(TestError::ChildFailedError(ref failed))) if group => {
+                    failed_tests.extend(failed.iter().cloned())
+                }
                 Some(TestResult::Err(_)) => failed_tests.push(full_name.into()),
                 None => pending_tests.push(name.clone()),
                 _ => {}
@@ -149,15 +371,206 @@ impl ParentTestNode {
 }
 
 impl LeafTestNode {
+    /// Records the result of the test, ignored if the test isn't selected
+    /// or already finished, e.g. by timing out. Failures of tests started
+    /// with `run` schedule another attempt while retries are left.
     pub fn update(&self, result: TestResult) {
+        let now = Instant::now();
+        if !self.enabled {
+            return;
+        }
+        if self.finished() {
+            tracing::warn!(
+                "ignoring result {:?} of finished test `{}`",
+                result,
+                self.full_name
+            );
+            return;
+        }
+        let result = {
+            let mut state = self.content.state.lock();
+            match result {
+                // the attempt already failed, the next one decides
+                _ if state.retry_pending => return,
+                Ok(()) => {
+                    if state.failed_attempts > 0 {
+                        tracing::warn!(
+                            "test `{}` passed after {} failed attempts",
+                            self.full_name,
+                            state.failed_attempts
+                        );
+                    }
+                    state.finished_at = Some(now);
+                    Ok(())
+                }
+                Err(e) => {
+                    state.failed_attempts += 1;
+                    if state.retryable && state.failed_attempts <= self.content.retries {
+                        tracing::warn!(
+                            "test `{}` failed on attempt {}, retrying: {:?}",
+                            self.full_name,
+                            state.failed_attempts,
+                            e
+                        );
+                        state.retry_pending = true;
+                        return;
+                    }
+                    state.finished_at = Some(now);
+                    if state.failed_attempts > 1 {
+                        Err(TestError::RetriesExhausted {
+                            attempts: state.failed_attempts,
+                            last: Box::new(e),
+                        })
+                    } else {
+                        Err(e)
+                    }
+                }
+            }
+        };
         tracing::info!(
-            "test `{}` finished with result {:?}",
+            "test `{}` finished in {:?} with result {:?}",
             self.full_name,
+            self.elapsed(),
             result
         );
         debug_assert!(self.parent.is_some());
+        if result.is_err() {
+            self.dump_artifacts();
+            if let Some(on_failure) = self.settings.on_failure.as_ref() {
+                on_failure(self);
+            }
+        }
         self.update_result(result);
     }
+
+    /// Records the result of the attempt `attempt` of a test started with
+    /// `run`, see `attempts`. Results of earlier attempts, e.g. arriving
+    /// asynchronously after their attempt timed out, are dropped.
+    pub fn update_attempt(&self, attempt: u32, result: TestResult) {
+        let current = self.attempts();
+        if attempt != current {
+            tracing::warn!(
+                "ignoring result {:?} of attempt {} of test `{}`, now on attempt {}",
+                result,
+                attempt,
+                self.full_name,
+                current
+            );
+            return;
+        }
+        self.update(result);
+    }
+
+    /// Runs the test `f` now, and again after each failed attempt while
+    /// `LeafOptions::retries` are left. Each run must update this node, or
+    /// return an error. Runs updating it asynchronously should use
+    /// `update_attempt`.
+    pub fn run<F>(self: &Arc<Self>, main_ctx: &mut MainContext, f: F)
+    where
+        F: RetryCallback + 'static,
+    {
+        if !self.enabled {
+            return;
+        }
+        self.content.state.lock().retryable = true;
+        self.attempt(main_ctx, Box::new(f));
+    }
+
+    fn attempt(self: &Arc<Self>, main_ctx: &mut MainContext, mut f: Box<dyn RetryCallback>) {
+        {
+            let mut state = self.content.state.lock();
+            state.started_at = Instant::now();
+            state.deadline = state.started_at + self.content.timeout;
+            state.attempts += 1;
+        }
+        let result = f(main_ctx, self);
+        *self.content.retry.lock() = Some(f);
+        if let Err(e) = result {
+            self.update(Err(e.into()));
+        }
+    }
+
+    fn poll(self: &Arc<Self>, main_ctx: &mut MainContext, now: Instant) {
+        if !self.enabled || self.finished() {
+            return;
+        }
+        let (retry, expired) = {
+            let mut state = self.content.state.lock();
+            (
+                std::mem::take(&mut state.retry_pending),
+                now >= state.deadline,
+            )
+        };
+        if retry {
+            let f = self.content.retry.lock().take();
+            if let Some(f) = f {
+                self.attempt(main_ctx, f);
+            }
+        } else if expired {
+            self.update(Err(TestError::Timeout(self.content.timeout)));
+        }
+    }
+
+    /// Time since the test started, see `duration`.
+    pub fn elapsed(&self) -> Duration {
+        let state = self.content.state.lock();
+        state
+            .finished_at
+            .unwrap_or_else(Instant::now)
+            .saturating_duration_since(state.started_at)
+    }
+
+    /// Time the test took from its creation, or the start of its last
+    /// attempt, until its result. `None` until it finishes.
+    pub fn duration(&self) -> Option<Duration> {
+        let state = self.content.state.lock();
+        state
+            .finished_at
+            .map(|finished_at| finished_at.saturating_duration_since(state.started_at))
+    }
+
+    /// Fails if more than `max` elapsed since the test started, call it
+    /// right before `update` for latency sensitive tests.
+    pub fn assert_completed_within(&self, max: Duration) -> TestResult {
+        assert_less_equals(
+            &self.elapsed(),
+            &max,
+            format!("test `{}` took too long", self.full_name),
+        )
+    }
+
+    /// Times the test was run by `run` so far.
+    pub fn attempts(&self) -> u32 {
+        self.content.state.lock().attempts
+    }
+
+    /// Records a UI event received by `widget`, the trace is dumped if the
+    /// test fails.
+    pub fn trace(&self, widget: &dyn Widget, event: &impl Debug) {
+        self.artifacts.lock().record(widget.debug_name(), event);
+    }
+
+    /// Keeps a snapshot of the widget tree rooted at `root`, dumped if the
+    /// test fails. Should be called right before `update`.
+    pub fn snapshot_layout(&self, root: &dyn Widget) {
+        self.artifacts.lock().layout_snapshot = Some(widget_tree_snapshot(root));
+    }
+
+    fn dump_artifacts(&self) {
+        let artifacts = self.artifacts.lock();
+        if artifacts.is_empty() {
+            return;
+        }
+        if let Some(paths) = artifacts
+            .dump(&test_dir(&args().artifacts_dir, &self.full_name))
+            .context("unable to dump test artifacts")
+            .log_error()
+        {
+            for path in paths {
+                tracing::error!("test `{}` artifact: {}", self.full_name, path.display());
+            }
+        }
+    }
 }
 
 impl<C> GenericTestNode<C> {
@@ -173,6 +586,24 @@ impl<C> GenericTestNode<C> {
         }
     }
 
+    /// Whether the test is selected by `--test-filter` and `--test-tag`.
+    /// For parents, whether some of their tests may be: the setup of
+    /// disabled nodes can be skipped entirely, they are already finished.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// `None` while the test is pending.
+    pub fn outcome(&self) -> Option<TestOutcome> {
+        if !self.enabled {
+            return Some(TestOutcome::Skipped);
+        }
+        match self.result.lock().as_ref()? {
+            Ok(()) => Some(TestOutcome::Passed),
+            Err(_) => Some(TestOutcome::Failed),
+        }
+    }
+
     pub fn finished(&self) -> bool {
         self.result.lock().is_some()
     }
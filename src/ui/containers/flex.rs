@@ -0,0 +1,361 @@
+use std::{iter::Map, sync::Arc};
+
+use crate::{
+    ui::{
+        acquire_widget_id,
+        utils::geom::{UIRect, UISize},
+        LayoutAxis, Padding, UISizeConstraint, Visibility, Widget, WidgetId,
+    },
+    utils::{
+        mutex::{Mutex, MutexGuard},
+        profile::profile_span,
+    },
+};
+
+use super::{ContainerHint, ContainerWidget};
+
+/// Distribution of the free space along the main axis, only left when no
+/// child has a flex weight.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum MainAlignment {
+    Start,
+    End,
+    Center,
+    /// free space between the children, none before the first and after
+    /// the last
+    SpaceBetween,
+    /// free space around each child, so half of it at both ends
+    SpaceAround,
+    /// same free space between the children and at both ends
+    SpaceEvenly,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum CrossAlignment {
+    Start,
+    End,
+    Center,
+    /// children are laid out with the cross size of the container
+    Stretch,
+}
+
+impl MainAlignment {
+    /// Offset of the first child and extra gap between the children.
+    fn distribute(self, free: f32, count: usize) -> (f32, f32) {
+        let count = count as f32;
+        match self {
+            MainAlignment::Start => (0.0, 0.0),
+            MainAlignment::End => (free, 0.0),
+            MainAlignment::Center => (free * 0.5, 0.0),
+            MainAlignment::SpaceBetween if count > 1.0 => (0.0, free / (count - 1.0)),
+            MainAlignment::SpaceBetween => (0.0, 0.0),
+            MainAlignment::SpaceAround => (free / count * 0.5, free / count),
+            MainAlignment::SpaceEvenly => (free / (count + 1.0), free / (count + 1.0)),
+        }
+    }
+}
+
+impl CrossAlignment {
+    fn calc_offset(self, container_size: f32, size: f32) -> f32 {
+        match self {
+            CrossAlignment::Start | CrossAlignment::Stretch => 0.0,
+            CrossAlignment::End => container_size - size,
+            CrossAlignment::Center => (container_size - size) * 0.5,
+        }
+    }
+}
+
+pub struct FlexChild {
+    widget: Arc<dyn Widget>,
+    /// share of the free main axis space, content sized if zero
+    flex: f32,
+    /// overrides the cross alignment of the container
+    alignment: Option<CrossAlignment>,
+    size: UISize,
+}
+
+/// Lays out its children in a row or a column. Children with a flex weight
+/// share the space left by the others proportionally to their weight.
+pub struct Flex {
+    id: WidgetId,
+    axis: LayoutAxis,
+    children: Mutex<Vec<FlexChild>>,
+    hover: Mutex<Vec<Arc<dyn Widget>>>,
+    bounds: Mutex<UIRect>,
+    main_alignment: Mutex<MainAlignment>,
+    cross_alignment: Mutex<CrossAlignment>,
+    spacing: Mutex<f32>,
+    padding: Mutex<Padding>,
+    visibility: Mutex<Visibility>,
+}
+
+impl Flex {
+    pub fn new(axis: LayoutAxis) -> Self {
+        Self {
+            id: acquire_widget_id(),
+            axis,
+            children: Mutex::new(Vec::new()),
+            hover: Mutex::new(Vec::new()),
+            bounds: Mutex::new(UIRect::ZERO),
+            main_alignment: Mutex::new(MainAlignment::Start),
+            cross_alignment: Mutex::new(CrossAlignment::Start),
+            spacing: Mutex::new(0.0),
+            padding: Mutex::new(Padding::default()),
+            visibility: Mutex::new(Visibility::Visible),
+        }
+    }
+
+    pub fn row() -> Self {
+        Self::new(LayoutAxis::Horizontal)
+    }
+
+    pub fn column() -> Self {
+        Self::new(LayoutAxis::Vertical)
+    }
+
+    pub fn main_alignment(self, alignment: MainAlignment) -> Self {
+        *self.main_alignment.lock() = alignment;
+        self
+    }
+
+    pub fn cross_alignment(self, alignment: CrossAlignment) -> Self {
+        *self.cross_alignment.lock() = alignment;
+        self
+    }
+
+    pub fn spacing(self, spacing: f32) -> Self {
+        *self.spacing.lock() = spacing;
+        self
+    }
+
+    pub fn padding(self, padding: Padding) -> Self {
+        *self.padding.lock() = padding;
+        self
+    }
+
+    /// Adds a content sized child.
+    pub fn child(self, child: Arc<dyn Widget>) -> Self {
+        self.push_arc(child, 0.0, None);
+        self
+    }
+
+    pub fn flex_child(self, child: Arc<dyn Widget>, flex: f32) -> Self {
+        self.push_arc(child, flex, None);
+        self
+    }
+
+    /// Adds `child` with a `flex` weight (zero for content sized), aligned
+    /// on the cross axis by `alignment` or the container's alignment.
+    pub fn push_arc(&self, child: Arc<dyn Widget>, flex: f32, alignment: Option<CrossAlignment>) {
+        self.children.lock().push(FlexChild {
+            widget: child,
+            flex: flex.max(0.0),
+            alignment,
+            size: UISize::ZERO,
+        });
+    }
+
+    pub fn set_main_alignment(&self, alignment: MainAlignment) {
+        *self.main_alignment.lock() = alignment;
+    }
+}
+
+impl Flex {
+    /// Children are summed along the main axis, spacing included, and
+    /// measured without bounds along it for the cross axis.
+    fn intrinsic_size(
+        &self,
+        axis: LayoutAxis,
+        cross: f32,
+        query: impl Fn(&dyn Widget, LayoutAxis, f32) -> f32,
+    ) -> f32 {
+        let padding = *self.padding.lock();
+        let cross = (cross - padding.along(axis.other())).max(0.0);
+        let children = self.children.lock();
+        let content = if axis == self.axis {
+            let spacing = *self.spacing.lock() * children.len().saturating_sub(1) as f32;
+            children
+                .iter()
+                .map(|child| query(child.widget.as_ref(), axis, cross))
+                .sum::<f32>()
+                + spacing
+        } else {
+            children
+                .iter()
+                .map(|child| query(child.widget.as_ref(), axis, f32::INFINITY))
+                .fold(0.0, f32::max)
+        };
+        content + padding.along(axis)
+    }
+}
+
+impl ContainerWidget for Flex {
+    fn container_id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn layout_container(&self, size_constraints: &UISizeConstraint) -> UISize {
+        let _span = profile_span!("flex layout").entered();
+        let axis = self.axis;
+        let cross_axis = axis.other();
+        let padding = *self.padding.lock();
+        let (inner_constraints, pos_offset) = padding.apply_to_constraints(size_constraints);
+        let max_main = axis.get_size(inner_constraints.max);
+        let max_cross = cross_axis.get_size(inner_constraints.max);
+        let min_cross = cross_axis.get_size(inner_constraints.min);
+        let spacing = *self.spacing.lock();
+        let container_alignment = *self.cross_alignment.lock();
+        let mut children = self.children.lock();
+
+        let stretch_to = |alignment: CrossAlignment| {
+            if alignment == CrossAlignment::Stretch && max_cross.is_finite() {
+                max_cross
+            } else {
+                0.0
+            }
+        };
+
+        // content sized children first, flex children share what they left
+        let total_spacing = spacing * children.len().saturating_sub(1) as f32;
+        let mut used_main = total_spacing;
+        let mut total_flex = 0.0;
+        for child in children.iter_mut() {
+            if child.flex > 0.0 && max_main.is_finite() {
+                total_flex += child.flex;
+                continue;
+            }
+            let alignment = child.alignment.unwrap_or(container_alignment);
+            let constraints = UISizeConstraint::new(
+                axis.new_size(0.0, stretch_to(alignment)),
+                axis.new_size((max_main - used_main).max(0.0), max_cross),
+            );
+            child.size = child.widget.layout(&constraints);
+            used_main += axis.get_size(child.size).max(0.0);
+        }
+
+        let free_main = (max_main - used_main).max(0.0);
+        for child in children.iter_mut() {
+            if child.flex <= 0.0 || !max_main.is_finite() {
+                continue;
+            }
+            let alignment = child.alignment.unwrap_or(container_alignment);
+            let main = free_main * child.flex / total_flex;
+            let constraints = UISizeConstraint::new(
+                axis.new_size(main, stretch_to(alignment)),
+                axis.new_size(main, max_cross),
+            );
+            child.size = child.widget.layout(&constraints);
+            used_main += axis.get_size(child.size).max(0.0);
+        }
+
+        let cross_size = children
+            .iter()
+            .map(|child| cross_axis.get_size(child.size))
+            .fold(min_cross, f32::max)
+            .min(max_cross);
+
+        // stretched children only know the cross size of an unbounded
+        // container once all children are measured
+        for child in children.iter_mut() {
+            let alignment = child.alignment.unwrap_or(container_alignment);
+            let child_cross = cross_axis.get_size(child.size);
+            let fit_container = child_cross == UISize::FIT_CONTAINER;
+            if (alignment == CrossAlignment::Stretch || fit_container) && child_cross != cross_size
+            {
+                let main = axis.get_size(child.size).max(0.0);
+                let constraints = UISizeConstraint::exact(axis.new_size(main, cross_size));
+                child.widget.layout(&constraints);
+                child.size = axis.new_size(main, cross_size);
+            }
+        }
+
+        let main_size = if total_flex > 0.0 {
+            max_main
+        } else {
+            used_main.max(axis.get_size(inner_constraints.min))
+        };
+
+        let (mut main_pos, extra_gap) = if total_flex > 0.0 {
+            (0.0, 0.0)
+        } else {
+            self.main_alignment
+                .lock()
+                .distribute((main_size - used_main).max(0.0), children.len())
+        };
+        for child in children.iter() {
+            let alignment = child.alignment.unwrap_or(container_alignment);
+            let mut child_pos = axis.new_pos(
+                main_pos,
+                alignment.calc_offset(cross_size, cross_axis.get_size(child.size)),
+            );
+
+            child_pos.x += pos_offset.x;
+            child_pos.y += pos_offset.y;
+
+            child.widget.set_bounds(UIRect::new(child_pos, child.size));
+            main_pos += axis.get_size(child.size).max(0.0) + spacing + extra_gap;
+        }
+
+        let size = axis.new_size(
+            main_size + padding.along(axis),
+            cross_size + padding.along(cross_axis),
+        );
+        size.clamp(&size_constraints.min, &size_constraints.max)
+    }
+
+    fn min_intrinsic_container_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        self.intrinsic_size(axis, cross, |widget, axis, cross| {
+            widget.min_intrinsic_size(axis, cross)
+        })
+    }
+
+    fn max_intrinsic_container_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        self.intrinsic_size(axis, cross, |widget, axis, cross| {
+            widget.max_intrinsic_size(axis, cross)
+        })
+    }
+
+    fn set_container_bounds(&self, bounds: UIRect) {
+        *self.bounds.lock() = bounds;
+    }
+
+    fn get_container_bounds(&self) -> UIRect {
+        *self.bounds.lock()
+    }
+
+    fn container_hints() -> super::ContainerHint {
+        ContainerHint::NO_OVERLAP
+    }
+
+    type ChildrenGuard<'a> = MutexGuard<'a, Vec<FlexChild>>;
+
+    type ChildrenIterator<'c> =
+        Map<std::slice::Iter<'c, FlexChild>, fn(&FlexChild) -> Arc<dyn Widget>>;
+
+    fn lock_children(&self) -> Self::ChildrenGuard<'_> {
+        self.children.lock()
+    }
+
+    fn iterate_child_widgets<'c>(
+        &self,
+        guard: &'c Self::ChildrenGuard<'_>,
+    ) -> Self::ChildrenIterator<'c> {
+        fn get_widget(child: &FlexChild) -> Arc<dyn Widget> {
+            child.widget.clone()
+        }
+
+        guard.iter().map(get_widget)
+    }
+
+    fn hover_widgets(&self) -> MutexGuard<'_, Vec<Arc<dyn Widget>>> {
+        self.hover.lock()
+    }
+
+    fn get_visibility(&self) -> Visibility {
+        *self.visibility.lock()
+    }
+
+    fn set_visibility(&self, visibility: Visibility) {
+        *self.visibility.lock() = visibility;
+    }
+}
@@ -0,0 +1,336 @@
+use std::{iter::Map, sync::Arc};
+
+use crate::{
+    ui::{
+        acquire_widget_id,
+        utils::geom::{UIPos, UIRect, UISize},
+        LayoutAxis, UISizeConstraint, Visibility, Widget, WidgetId,
+    },
+    utils::{
+        mutex::{Mutex, MutexGuard},
+        profile::profile_span,
+    },
+};
+
+use super::{ContainerHint, ContainerWidget};
+
+/// Size of a grid column or row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrackSize {
+    Fixed(f32),
+    /// fits the largest child spanning only this track
+    Auto,
+    /// share of the space left by the other tracks, `Auto` if unbounded
+    Fraction(f32),
+}
+
+/// Cell of a grid child, spanning `row_span` rows and `column_span`
+/// columns from `row` and `column`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridCell {
+    pub row: usize,
+    pub column: usize,
+    pub row_span: usize,
+    pub column_span: usize,
+}
+
+impl GridCell {
+    pub fn new(row: usize, column: usize) -> Self {
+        Self {
+            row,
+            column,
+            row_span: 1,
+            column_span: 1,
+        }
+    }
+
+    pub fn span(mut self, row_span: usize, column_span: usize) -> Self {
+        self.row_span = row_span.max(1);
+        self.column_span = column_span.max(1);
+        self
+    }
+}
+
+pub struct GridChild {
+    widget: Arc<dyn Widget>,
+    cell: GridCell,
+}
+
+/// Lays out its children in the cells of fixed, auto and fractional
+/// columns and rows. Children fill their cells.
+pub struct Grid {
+    id: WidgetId,
+    columns: Vec<TrackSize>,
+    rows: Vec<TrackSize>,
+    children: Mutex<Vec<GridChild>>,
+    hover: Mutex<Vec<Arc<dyn Widget>>>,
+    bounds: Mutex<UIRect>,
+    spacing: Mutex<f32>,
+    visibility: Mutex<Visibility>,
+}
+
+/// Sizes of the tracks of one axis, and their offsets (one more than the
+/// tracks, so that spans ending at the last track can be measured).
+struct Tracks {
+    sizes: Vec<f32>,
+    offsets: Vec<f32>,
+    spacing: f32,
+}
+
+impl Tracks {
+    /// Resolves `specs` in `available` space, given the extent of the
+    /// children as `(first track, span, size)`.
+    fn resolve(
+        specs: &[TrackSize],
+        available: f32,
+        spacing: f32,
+        contents: impl IntoIterator<Item = (usize, usize, f32)>,
+    ) -> Self {
+        let fractional = available.is_finite();
+        let is_auto = |spec: &TrackSize| match spec {
+            TrackSize::Fixed(_) => false,
+            TrackSize::Auto => true,
+            TrackSize::Fraction(_) => !fractional,
+        };
+        let mut sizes = specs
+            .iter()
+            .map(|spec| match spec {
+                TrackSize::Fixed(size) => *size,
+                _ => 0.0,
+            })
+            .collect::<Vec<_>>();
+
+        // single track children first, spanning ones grow the auto tracks
+        // they span evenly if they don't fit yet
+        let mut spanning = Vec::new();
+        for (start, span, size) in contents {
+            let end = (start + span).min(specs.len());
+            if start >= end {
+                continue;
+            }
+            if end - start > 1 {
+                spanning.push((start, end, size));
+            } else if is_auto(&specs[start]) {
+                sizes[start] = sizes[start].max(size);
+            }
+        }
+        for (start, end, size) in spanning {
+            let current =
+                sizes[start..end].iter().sum::<f32>() + spacing * (end - start - 1) as f32;
+            let autos = (start..end)
+                .filter(|&i| is_auto(&specs[i]))
+                .collect::<Vec<_>>();
+            if size > current && !autos.is_empty() {
+                let extra = (size - current) / autos.len() as f32;
+                for i in autos {
+                    sizes[i] += extra;
+                }
+            }
+        }
+
+        if fractional {
+            let total_spacing = spacing * specs.len().saturating_sub(1) as f32;
+            let free = (available - sizes.iter().sum::<f32>() - total_spacing).max(0.0);
+            let total_fraction = specs
+                .iter()
+                .map(|spec| match spec {
+                    TrackSize::Fraction(fraction) => *fraction,
+                    _ => 0.0,
+                })
+                .sum::<f32>();
+            for (size, spec) in sizes.iter_mut().zip(specs) {
+                if let TrackSize::Fraction(fraction) = spec {
+                    *size = free * fraction / total_fraction.max(f32::EPSILON);
+                }
+            }
+        }
+
+        let mut offsets = Vec::with_capacity(sizes.len() + 1);
+        let mut offset = 0.0;
+        for size in sizes.iter() {
+            offsets.push(offset);
+            offset += size + spacing;
+        }
+        offsets.push(offset);
+        Self {
+            sizes,
+            offsets,
+            spacing,
+        }
+    }
+
+    fn total(&self) -> f32 {
+        let spacing = self.spacing * self.sizes.len().saturating_sub(1) as f32;
+        self.sizes.iter().sum::<f32>() + spacing
+    }
+
+    /// Offset and extent of a span, empty past the last track.
+    fn span(&self, start: usize, span: usize) -> (f32, f32) {
+        let start = start.min(self.sizes.len());
+        let end = (start + span).min(self.sizes.len());
+        let size = if end > start {
+            self.offsets[end] - self.offsets[start] - self.spacing
+        } else {
+            0.0
+        };
+        (self.offsets[start], size)
+    }
+}
+
+impl Grid {
+    pub fn new(columns: Vec<TrackSize>, rows: Vec<TrackSize>) -> Self {
+        Self {
+            id: acquire_widget_id(),
+            columns,
+            rows,
+            children: Mutex::new(Vec::new()),
+            hover: Mutex::new(Vec::new()),
+            bounds: Mutex::new(UIRect::ZERO),
+            spacing: Mutex::new(0.0),
+            visibility: Mutex::new(Visibility::Visible),
+        }
+    }
+
+    pub fn spacing(self, spacing: f32) -> Self {
+        *self.spacing.lock() = spacing;
+        self
+    }
+
+    /// Adds `child` in `cell`, which should not overlap the cells of the
+    /// other children.
+    pub fn push_arc(&self, child: Arc<dyn Widget>, cell: GridCell) {
+        self.children.lock().push(GridChild {
+            widget: child,
+            cell,
+        });
+    }
+
+    /// Columns are measured first, rows with the width of the columns
+    /// spanned by each child.
+    fn tracks(
+        &self,
+        children: &[GridChild],
+        available: UISize,
+        query: impl Fn(&dyn Widget, LayoutAxis, f32) -> f32,
+    ) -> (Tracks, Tracks) {
+        let spacing = *self.spacing.lock();
+        let columns = Tracks::resolve(
+            &self.columns,
+            available.width,
+            spacing,
+            children.iter().map(|child| {
+                let width = query(child.widget.as_ref(), LayoutAxis::Horizontal, f32::INFINITY);
+                (child.cell.column, child.cell.column_span, width)
+            }),
+        );
+        let rows = Tracks::resolve(
+            &self.rows,
+            available.height,
+            spacing,
+            children.iter().map(|child| {
+                let (_, width) = columns.span(child.cell.column, child.cell.column_span);
+                let height = query(child.widget.as_ref(), LayoutAxis::Vertical, width);
+                (child.cell.row, child.cell.row_span, height)
+            }),
+        );
+        (columns, rows)
+    }
+
+    fn intrinsic_size(
+        &self,
+        axis: LayoutAxis,
+        query: impl Fn(&dyn Widget, LayoutAxis, f32) -> f32,
+    ) -> f32 {
+        let children = self.children.lock();
+        let (columns, rows) =
+            self.tracks(&children, UISize::new(f32::INFINITY, f32::INFINITY), query);
+        match axis {
+            LayoutAxis::Horizontal => columns.total(),
+            LayoutAxis::Vertical => rows.total(),
+        }
+    }
+}
+
+impl ContainerWidget for Grid {
+    fn container_id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn layout_container(&self, size_constraints: &UISizeConstraint) -> UISize {
+        let _span = profile_span!("grid layout").entered();
+        let children = self.children.lock();
+        let (columns, rows) =
+            self.tracks(&children, size_constraints.max, |widget, axis, cross| {
+                widget.max_intrinsic_size(axis, cross)
+            });
+        for child in children.iter() {
+            let cell = child.cell;
+            let (x, width) = columns.span(cell.column, cell.column_span);
+            let (y, height) = rows.span(cell.row, cell.row_span);
+            let cell_size = UISize::new(width, height);
+            child.widget.layout(&UISizeConstraint::exact(cell_size));
+            child
+                .widget
+                .set_bounds(UIRect::new(UIPos::new(x, y), cell_size));
+        }
+
+        UISize::new(columns.total(), rows.total())
+            .clamp(&size_constraints.min, &size_constraints.max)
+    }
+
+    fn min_intrinsic_container_size(&self, axis: LayoutAxis, _cross: f32) -> f32 {
+        self.intrinsic_size(axis, |widget, axis, cross| {
+            widget.min_intrinsic_size(axis, cross)
+        })
+    }
+
+    fn max_intrinsic_container_size(&self, axis: LayoutAxis, _cross: f32) -> f32 {
+        self.intrinsic_size(axis, |widget, axis, cross| {
+            widget.max_intrinsic_size(axis, cross)
+        })
+    }
+
+    fn set_container_bounds(&self, bounds: UIRect) {
+        *self.bounds.lock() = bounds;
+    }
+
+    fn get_container_bounds(&self) -> UIRect {
+        *self.bounds.lock()
+    }
+
+    fn container_hints() -> super::ContainerHint {
+        ContainerHint::NO_OVERLAP
+    }
+
+    type ChildrenGuard<'a> = MutexGuard<'a, Vec<GridChild>>;
+
+    type ChildrenIterator<'c> =
+        Map<std::slice::Iter<'c, GridChild>, fn(&GridChild) -> Arc<dyn Widget>>;
+
+    fn lock_children(&self) -> Self::ChildrenGuard<'_> {
+        self.children.lock()
+    }
+
+    fn iterate_child_widgets<'c>(
+        &self,
+        guard: &'c Self::ChildrenGuard<'_>,
+    ) -> Self::ChildrenIterator<'c> {
+        fn get_widget(child: &GridChild) -> Arc<dyn Widget> {
+            child.widget.clone()
+        }
+
+        guard.iter().map(get_widget)
+    }
+
+    fn hover_widgets(&self) -> MutexGuard<'_, Vec<Arc<dyn Widget>>> {
+        self.hover.lock()
+    }
+
+    fn get_visibility(&self) -> Visibility {
+        *self.visibility.lock()
+    }
+
+    fn set_visibility(&self, visibility: Visibility) {
+        *self.visibility.lock() = visibility;
+    }
+}
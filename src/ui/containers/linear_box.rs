@@ -4,9 +4,12 @@ use crate::{
     ui::{
         acquire_widget_id,
         utils::geom::{UIRect, UISize},
-        Axis, Padding, UISizeConstraint, Visibility, Widget, WidgetId,
+        Axis, LayoutAxis, Padding, UISizeConstraint, Visibility, Widget, WidgetId,
+    },
+    utils::{
+        mutex::{Mutex, MutexGuard},
+        profile::profile_span,
     },
-    utils::mutex::{Mutex, MutexGuard},
 };
 
 use super::{ContainerHint, ContainerWidget};
@@ -53,6 +56,32 @@ impl<A: Axis> LinearBox<A> {
     }
 }
 
+impl<A: Axis> LinearBox<A> {
+    /// Children are summed along the main axis, spacing included, and
+    /// measured without bounds along it for the cross axis.
+    fn intrinsic_size(
+        &self,
+        axis: LayoutAxis,
+        cross: f32,
+        query: impl Fn(&dyn Widget, LayoutAxis, f32) -> f32,
+    ) -> f32 {
+        let cross = (cross - self.padding.lock().along(axis.other())).max(0.0);
+        let children = self.children.lock();
+        if axis == A::LAYOUT_AXIS {
+            let spacing = *self.spacing.lock();
+            children
+                .iter()
+                .map(|child| query(child.widget.as_ref(), axis, cross) + spacing)
+                .sum()
+        } else {
+            children
+                .iter()
+                .map(|child| query(child.widget.as_ref(), axis, f32::INFINITY))
+                .fold(0.0, f32::max)
+        }
+    }
+}
+
 impl<A: Axis> Default for LinearBox<A> {
     fn default() -> Self {
         Self::new()
@@ -65,6 +94,7 @@ impl<A: Axis> ContainerWidget for LinearBox<A> {
     }
 
     fn layout_container(&self, size_constraints: &UISizeConstraint) -> UISize {
+        let _span = profile_span!("linear box layout").entered();
         let (size_constraints, pos_offset) =
             self.padding.lock().apply_to_constraints(size_constraints);
         let mut main_size: f32 = 0.0;
@@ -108,6 +138,18 @@ impl<A: Axis> ContainerWidget for LinearBox<A> {
         A::new_size(main_size, cross_size)
     }
 
+    fn min_intrinsic_container_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        self.intrinsic_size(axis, cross, |widget, axis, cross| {
+            widget.min_intrinsic_size(axis, cross)
+        })
+    }
+
+    fn max_intrinsic_container_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        self.intrinsic_size(axis, cross, |widget, axis, cross| {
+            widget.max_intrinsic_size(axis, cross)
+        })
+    }
+
     fn set_container_bounds(&self, bounds: UIRect) {
         *self.bounds.lock() = bounds;
     }
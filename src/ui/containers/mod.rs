@@ -1,27 +1,44 @@
 use std::{collections::HashMap, sync::Arc};
 
 use bitflags::bitflags;
+use glam::Affine2;
 
 use crate::{graphics::context::DrawContext, utils::mutex::MutexGuard};
 
 use super::{
     event::{UICursorEvent, UIFocusEvent, UIPropagatingEvent},
     utils::geom::{UIPos, UIRect, UISize},
-    EventContext, UISizeConstraint, Visibility, Widget, WidgetId,
+    EventContext, LayoutAxis, UISizeConstraint, Visibility, Widget, WidgetId,
 };
 
+pub mod flex;
+pub mod grid;
 pub mod linear_box;
+pub mod scroll_view;
 pub mod stack;
 
 bitflags! {
     pub struct ContainerHint : u32 {
         const NO_OVERLAP = 0x1;
+        /// children are clipped to the bounds of the container
+        const CLIP_CHILDREN = 0x2;
     }
 }
 
 pub trait ContainerWidget: Widget {
     fn container_id(&self) -> WidgetId;
     fn layout_container(&self, size_constraints: &UISizeConstraint) -> UISize;
+
+    /// See `Widget::min_intrinsic_size`.
+    fn min_intrinsic_container_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        axis.layout_intrinsic_size(self, cross)
+    }
+
+    /// See `Widget::max_intrinsic_size`.
+    fn max_intrinsic_container_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        axis.layout_intrinsic_size(self, cross)
+    }
+
     fn set_container_bounds(&self, bounds: UIRect);
     fn get_container_bounds(&self) -> UIRect;
 
@@ -38,6 +55,17 @@ pub trait ContainerWidget: Widget {
 
     fn hover_widgets(&self) -> MutexGuard<'_, Vec<Arc<dyn Widget>>>;
 
+    /// Transform of the children relative to the container's origin, e.g.
+    /// the zoom of a scroll view. Cursor positions are mapped back through
+    /// it before hit-testing the children.
+    fn child_transform(&self) -> Affine2 {
+        Affine2::IDENTITY
+    }
+
+    /// Drawn over the children in the space of the container, unaffected
+    /// by `child_transform`, e.g. scrollbars.
+    fn draw_container_overlay(&self, _ctx: &mut DrawContext) {}
+
     fn handle_focus_event_impl(
         &self,
         _ctx: &mut EventContext,
@@ -75,6 +103,14 @@ impl<T: ContainerWidget> Widget for T {
         self.layout_container(size_constraints)
     }
 
+    fn min_intrinsic_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        self.min_intrinsic_container_size(axis, cross)
+    }
+
+    fn max_intrinsic_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        self.max_intrinsic_container_size(axis, cross)
+    }
+
     fn set_bounds(&self, bounds: UIRect) {
         self.set_container_bounds(bounds);
     }
@@ -83,6 +119,16 @@ impl<T: ContainerWidget> Widget for T {
         self.get_container_bounds()
     }
 
+    fn visibility(&self) -> Visibility {
+        self.get_visibility()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn Widget>> {
+        let children = self.lock_children();
+        let widgets = self.iterate_child_widgets(&children).collect();
+        widgets
+    }
+
     fn handle_focus_event(
         self: Arc<Self>,
         ctx: &mut EventContext,
@@ -151,6 +197,11 @@ impl<T: ContainerWidget> Widget for T {
                     Some(event)
                 }
                 UICursorEvent::CursorMoved(position) => {
+                    let position = UIPos::from(
+                        self.child_transform()
+                            .inverse()
+                            .transform_point2(position.into()),
+                    );
                     let mut hover_widgets = self.hover_widgets();
                     let mut last_hover_widgets = hover_widgets
                         .iter()
@@ -202,13 +253,51 @@ impl<T: ContainerWidget> Widget for T {
         let old_len = ctx.transform_stack.len();
         ctx.transform_stack.push();
         ctx.transform_stack.translate(self.get_bounds().pos);
+        let clip = Self::container_hints().contains(ContainerHint::CLIP_CHILDREN);
+        if clip {
+            ctx.push_clip(UIRect::new(UIPos::ZERO, self.get_bounds().size));
+        }
+        ctx.transform_stack.push();
+        ctx.transform_stack.apply(&self.child_transform());
 
         let children = self.lock_children();
         for widget in self.iterate_child_widgets(&children) {
             widget.draw(ctx);
         }
+        drop(children);
 
+        ctx.transform_stack.pop();
+        self.draw_container_overlay(ctx);
+
+        if clip {
+            ctx.pop_clip();
+        }
         ctx.transform_stack.pop();
         debug_assert!(old_len == ctx.transform_stack.len());
     }
+
+    /// Only the children are pickable, containers are mostly layout.
+    fn draw_pick(&self, ctx: &mut DrawContext) {
+        if !self.get_visibility().draw() {
+            return;
+        }
+
+        ctx.transform_stack.push();
+        ctx.transform_stack.translate(self.get_bounds().pos);
+        let clip = Self::container_hints().contains(ContainerHint::CLIP_CHILDREN);
+        if clip {
+            ctx.push_clip(UIRect::new(UIPos::ZERO, self.get_bounds().size));
+        }
+        ctx.transform_stack.apply(&self.child_transform());
+
+        let children = self.lock_children();
+        for widget in self.iterate_child_widgets(&children) {
+            widget.draw_pick(ctx);
+        }
+
+        if clip {
+            ctx.pop_clip();
+        }
+        ctx.transform_stack.pop();
+    }
 }
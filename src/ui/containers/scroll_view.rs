@@ -0,0 +1,410 @@
+use std::{iter::Once, sync::Arc};
+
+use glam::{Affine2, Vec2, Vec4};
+use winit::event::{ElementState, MouseButton};
+
+use crate::{
+    exec::{main_ctx::MainContext, task::CancellationToken},
+    graphics::context::DrawContext,
+    ui::{
+        acquire_widget_id,
+        event::{ScrollDelta, UICursorEvent, UIPropagatingEvent},
+        utils::geom::{UIPos, UIRect, UISize},
+        EventContext, LayoutAxis, UISizeConstraint, Visibility, Widget, WidgetId,
+    },
+    utils::{
+        mutex::{Mutex, MutexGuard},
+        profile::profile_span,
+    },
+};
+
+use super::{ContainerHint, ContainerWidget};
+
+/// Distance scrolled by a wheel notch.
+const LINE_SIZE: f32 = 40.0;
+/// Distance the cursor moves with the button held before it drags.
+const DRAG_THRESHOLD: f32 = 4.0;
+/// Exponential decay rate of the fling velocity, per second.
+const FRICTION: f32 = 4.0;
+/// Fling velocity under which the content stops.
+const MIN_VELOCITY: f32 = 10.0;
+/// Drags held still this long before the release don't fling.
+const FLING_TIMEOUT: f32 = 0.1;
+/// Scrollbars stay visible this long after a scroll, then fade out.
+const SCROLLBAR_DELAY: f32 = 1.0;
+const SCROLLBAR_FADE: f32 = 0.3;
+const SCROLLBAR_THICKNESS: f32 = 6.0;
+const SCROLLBAR_MIN_LENGTH: f32 = 16.0;
+
+struct Drag {
+    start_cursor: UIPos,
+    start_offset: Vec2,
+    dragging: bool,
+    /// time and position of the last cursor move, for the fling velocity
+    last_sample: (f32, UIPos),
+}
+
+struct ScrollState {
+    offset: Vec2,
+    velocity: Vec2,
+    viewport: UISize,
+    content: UISize,
+    /// last cursor position, in the space of the view
+    cursor: UIPos,
+    drag: Option<Drag>,
+    /// time advanced by `ScrollView::advance`
+    time: f32,
+    since_scroll: f32,
+}
+
+/// Shows a part of a child larger than itself, scrolled with the wheel or
+/// by dragging it, which flings it when released.
+pub struct ScrollView {
+    id: WidgetId,
+    child: Arc<dyn Widget>,
+    horizontal: bool,
+    vertical: bool,
+    state: Mutex<ScrollState>,
+    hover: Mutex<Vec<Arc<dyn Widget>>>,
+    bounds: Mutex<UIRect>,
+    visibility: Mutex<Visibility>,
+}
+
+impl ScrollState {
+    fn max_offset(&self) -> Vec2 {
+        (Vec2::from(self.content) - Vec2::from(self.viewport)).max(Vec2::ZERO)
+    }
+
+    /// Moves to `offset`, stopping the fling along the axes that hit an
+    /// edge.
+    fn scroll_to(&mut self, offset: Vec2) {
+        let clamped = offset.clamp(Vec2::ZERO, self.max_offset());
+        if clamped.x != offset.x {
+            self.velocity.x = 0.0;
+        }
+        if clamped.y != offset.y {
+            self.velocity.y = 0.0;
+        }
+        if clamped != self.offset {
+            self.since_scroll = 0.0;
+        }
+        self.offset = clamped;
+    }
+
+    fn scrollbar_alpha(&self) -> f32 {
+        ((SCROLLBAR_DELAY + SCROLLBAR_FADE - self.since_scroll) / SCROLLBAR_FADE).clamp(0.0, 1.0)
+    }
+}
+
+impl ScrollView {
+    /// Scrolls `child` along the enabled axes, it is laid out without
+    /// bounds along them.
+    pub fn new(child: Arc<dyn Widget>, horizontal: bool, vertical: bool) -> Self {
+        Self {
+            id: acquire_widget_id(),
+            child,
+            horizontal,
+            vertical,
+            state: Mutex::new(ScrollState {
+                offset: Vec2::ZERO,
+                velocity: Vec2::ZERO,
+                viewport: UISize::ZERO,
+                content: UISize::ZERO,
+                cursor: UIPos::ZERO,
+                drag: None,
+                time: 0.0,
+                since_scroll: f32::INFINITY,
+            }),
+            hover: Mutex::new(Vec::new()),
+            bounds: Mutex::new(UIRect::ZERO),
+            visibility: Mutex::new(Visibility::Visible),
+        }
+    }
+
+    fn scrolls(&self, axis: LayoutAxis) -> bool {
+        match axis {
+            LayoutAxis::Horizontal => self.horizontal,
+            LayoutAxis::Vertical => self.vertical,
+        }
+    }
+
+    /// Only the enabled axes of `delta`.
+    fn mask(&self, delta: Vec2) -> Vec2 {
+        Vec2::new(
+            if self.horizontal { delta.x } else { 0.0 },
+            if self.vertical { delta.y } else { 0.0 },
+        )
+    }
+
+    /// Offset of the child, positive when scrolled towards its end.
+    pub fn offset(&self) -> UIPos {
+        self.state.lock().offset.into()
+    }
+
+    /// Advances `view` by the frame delta every frame until it is dropped
+    /// or the returned token is cancelled.
+    pub fn animate(view: &Arc<Self>, main_ctx: &mut MainContext) -> CancellationToken {
+        let widget: Arc<dyn Widget> = view.clone();
+        let owner = main_ctx.widget_owner(&widget);
+        let view = Arc::downgrade(view);
+        main_ctx.on_frame_owned(Some(owner), move |_, _, delta| {
+            if let Some(view) = view.upgrade() {
+                view.advance(delta.as_secs_f32());
+            }
+            Ok(())
+        })
+    }
+
+    /// Moves the fling forward by `dt` seconds and fades the scrollbars.
+    pub fn advance(&self, dt: f32) {
+        let mut state = self.state.lock();
+        state.time += dt;
+        state.since_scroll += dt;
+        if state.drag.is_some() || state.velocity == Vec2::ZERO {
+            return;
+        }
+        let offset = state.offset + state.velocity * dt;
+        state.scroll_to(offset);
+        state.velocity *= (-FRICTION * dt).exp();
+        if state.velocity.length() < MIN_VELOCITY {
+            state.velocity = Vec2::ZERO;
+        }
+    }
+
+    /// Bounds and opacity of the scrollbar along `axis`, in the space of
+    /// the view, `None` while it is hidden or the content fits.
+    pub fn scrollbar(&self, axis: LayoutAxis) -> Option<(UIRect, f32)> {
+        let state = self.state.lock();
+        let alpha = state.scrollbar_alpha();
+        let index = match axis {
+            LayoutAxis::Horizontal => 0,
+            LayoutAxis::Vertical => 1,
+        };
+        let max_offset = state.max_offset()[index];
+        if alpha <= 0.0 || !self.scrolls(axis) || max_offset <= 0.0 {
+            return None;
+        }
+        let viewport = Vec2::from(state.viewport);
+        let content = Vec2::from(state.content);
+        let length = (viewport[index] * viewport[index] / content[index])
+            .max(SCROLLBAR_MIN_LENGTH)
+            .min(viewport[index]);
+        let start = state.offset[index] / max_offset * (viewport[index] - length);
+        let cross = viewport[1 - index] - SCROLLBAR_THICKNESS - 2.0;
+        let bounds = UIRect::new(
+            axis.new_pos(start, cross),
+            axis.new_size(length, SCROLLBAR_THICKNESS),
+        );
+        Some((bounds, alpha))
+    }
+
+    fn cursor_moved(&self, position: UIPos) {
+        let mut state = self.state.lock();
+        state.cursor = position;
+        let time = state.time;
+        let mut drag = match state.drag.take() {
+            Some(drag) => drag,
+            None => return,
+        };
+        let delta = Vec2::from(position) - Vec2::from(drag.start_cursor);
+        drag.dragging |= self.mask(delta).length() > DRAG_THRESHOLD;
+        if drag.dragging {
+            let (last_time, last_pos) = drag.last_sample;
+            if time > last_time {
+                let moved = Vec2::from(last_pos) - Vec2::from(position);
+                state.velocity = self.mask(moved / (time - last_time));
+            }
+            drag.last_sample = (time, position);
+            state.scroll_to(drag.start_offset - self.mask(delta));
+        }
+        state.drag = Some(drag);
+    }
+}
+
+impl ContainerWidget for ScrollView {
+    fn container_id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn layout_container(&self, size_constraints: &UISizeConstraint) -> UISize {
+        let _span = profile_span!("scroll view layout").entered();
+        let UISizeConstraint { min, max } = *size_constraints;
+        let child_constraints = UISizeConstraint::new(
+            UISize::new(
+                if self.horizontal { 0.0 } else { min.width },
+                if self.vertical { 0.0 } else { min.height },
+            ),
+            UISize::new(
+                if self.horizontal {
+                    f32::INFINITY
+                } else {
+                    max.width
+                },
+                if self.vertical {
+                    f32::INFINITY
+                } else {
+                    max.height
+                },
+            ),
+        );
+        let content = Vec2::from(self.child.layout(&child_constraints)).max(Vec2::ZERO);
+        let content = UISize::from(content);
+        self.child.set_bounds(UIRect::new(UIPos::ZERO, content));
+
+        let viewport = content.clamp(&min, &max);
+        let mut state = self.state.lock();
+        state.viewport = viewport;
+        state.content = content;
+        let offset = state.offset;
+        state.scroll_to(offset);
+        viewport
+    }
+
+    fn min_intrinsic_container_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        if self.scrolls(axis) {
+            return 0.0;
+        }
+        let cross = if self.scrolls(axis.other()) {
+            f32::INFINITY
+        } else {
+            cross
+        };
+        self.child.min_intrinsic_size(axis, cross)
+    }
+
+    fn max_intrinsic_container_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        let cross = if self.scrolls(axis.other()) {
+            f32::INFINITY
+        } else {
+            cross
+        };
+        self.child.max_intrinsic_size(axis, cross)
+    }
+
+    fn set_container_bounds(&self, bounds: UIRect) {
+        *self.bounds.lock() = bounds;
+    }
+
+    fn get_container_bounds(&self) -> UIRect {
+        *self.bounds.lock()
+    }
+
+    fn container_hints() -> ContainerHint {
+        ContainerHint::NO_OVERLAP | ContainerHint::CLIP_CHILDREN
+    }
+
+    type ChildrenGuard<'a> = Arc<dyn Widget>;
+
+    type ChildrenIterator<'c> = Once<Arc<dyn Widget>>;
+
+    fn lock_children(&self) -> Self::ChildrenGuard<'_> {
+        self.child.clone()
+    }
+
+    fn iterate_child_widgets<'c>(
+        &self,
+        guard: &'c Self::ChildrenGuard<'_>,
+    ) -> Self::ChildrenIterator<'c> {
+        std::iter::once(guard.clone())
+    }
+
+    fn hover_widgets(&self) -> MutexGuard<'_, Vec<Arc<dyn Widget>>> {
+        self.hover.lock()
+    }
+
+    fn child_transform(&self) -> Affine2 {
+        Affine2::from_translation(-self.state.lock().offset)
+    }
+
+    fn draw_container_overlay(&self, ctx: &mut DrawContext) {
+        for axis in [LayoutAxis::Horizontal, LayoutAxis::Vertical] {
+            if let Some((bounds, alpha)) = self.scrollbar(axis) {
+                let color = Vec4::new(0.5, 0.5, 0.5, 0.8 * alpha);
+                let min = Vec2::from(bounds.pos);
+                let max = min + Vec2::from(bounds.size);
+                ctx.draw_rounded_rect(min, max, SCROLLBAR_THICKNESS * 0.5, color);
+            }
+        }
+    }
+
+    /// Scroll events go to the child first, and are never passed on to
+    /// the widgets behind along the scrolled axes.
+    fn handle_propagating_event_impl(
+        &self,
+        ctx: &mut EventContext,
+        event: UIPropagatingEvent,
+    ) -> Option<UIPropagatingEvent> {
+        match event {
+            UIPropagatingEvent::Scroll { axis, delta } if self.scrolls(axis) => {
+                let hovered = !self.hover.lock().is_empty();
+                if hovered {
+                    self.child.clone().handle_propagating_event(ctx, event)?;
+                }
+                let distance = match delta {
+                    ScrollDelta::Lines(lines) => lines * LINE_SIZE,
+                    ScrollDelta::Pixels(pixels) => pixels,
+                };
+                let mut state = self.state.lock();
+                state.velocity = Vec2::ZERO;
+                let offset = state.offset + Vec2::from(axis.new_pos(distance, 0.0));
+                state.scroll_to(offset);
+                None
+            }
+
+            UIPropagatingEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+            } => {
+                let mut state = self.state.lock();
+                state.velocity = Vec2::ZERO;
+                state.drag = Some(Drag {
+                    start_cursor: state.cursor,
+                    start_offset: state.offset,
+                    dragging: false,
+                    last_sample: (state.time, state.cursor),
+                });
+                Some(event)
+            }
+
+            UIPropagatingEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+            } => {
+                let mut state = self.state.lock();
+                let drag = state.drag.take();
+                let held = drag.as_ref().map(|drag| state.time - drag.last_sample.0);
+                if held.map(|held| held > FLING_TIMEOUT).unwrap_or(true) {
+                    state.velocity = Vec2::ZERO;
+                }
+                match drag {
+                    // the release ends the drag, not a click on the child
+                    Some(drag) if drag.dragging => None,
+                    _ => Some(event),
+                }
+            }
+
+            event => Some(event),
+        }
+    }
+
+    fn handle_cursor_event_impl(
+        &self,
+        _ctx: &mut EventContext,
+        event: UICursorEvent,
+    ) -> Option<UICursorEvent> {
+        match event {
+            UICursorEvent::CursorMoved(position) => self.cursor_moved(position),
+            UICursorEvent::CursorExited => self.state.lock().drag = None,
+            UICursorEvent::CursorEntered => {}
+        }
+        Some(event)
+    }
+
+    fn get_visibility(&self) -> Visibility {
+        *self.visibility.lock()
+    }
+
+    fn set_visibility(&self, visibility: Visibility) {
+        *self.visibility.lock() = visibility;
+    }
+}
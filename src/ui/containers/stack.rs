@@ -3,10 +3,16 @@ use std::{iter::Map, sync::Arc};
 use crate::{
     ui::{
         acquire_widget_id,
+        event::UICursorEvent,
+        subtree_ids,
         utils::geom::{UIPos, UIRect, UISize},
-        Alignment, Padding, UISizeConstraint, Visibility, Widget, WidgetId,
+        Alignment, EventContext, LayoutAxis, Padding, UISizeConstraint, Visibility, Widget,
+        WidgetId,
+    },
+    utils::{
+        mutex::{Mutex, MutexGuard},
+        profile::profile_span,
     },
-    utils::mutex::{Mutex, MutexGuard},
 };
 
 use super::{ContainerHint, ContainerWidget};
@@ -24,6 +30,11 @@ pub struct Stack {
     id: WidgetId,
     padding: Mutex<Padding>,
     visibility: Mutex<Visibility>,
+    /// constraints of the last layout, reused when children change
+    constraints: Mutex<Option<UISizeConstraint>>,
+    /// last cursor position inside the stack, to find the hovered children
+    /// again after they moved
+    cursor: Mutex<Option<UIPos>>,
 }
 
 fn map_child(child: &StackChild) -> Arc<dyn Widget> {
@@ -66,7 +77,21 @@ impl ContainerWidget for Stack {
         self.hover_children.lock()
     }
 
+    fn min_intrinsic_container_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        self.intrinsic_size(axis, cross, |widget, axis, cross| {
+            widget.min_intrinsic_size(axis, cross)
+        })
+    }
+
+    fn max_intrinsic_container_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        self.intrinsic_size(axis, cross, |widget, axis, cross| {
+            widget.max_intrinsic_size(axis, cross)
+        })
+    }
+
     fn layout_container(&self, size_constraints: &UISizeConstraint) -> UISize {
+        let _span = profile_span!("stack layout").entered();
+        *self.constraints.lock() = Some(*size_constraints);
         let (size_constraints, pos_offset) =
             self.padding.lock().apply_to_constraints(size_constraints);
         let mut container_size = size_constraints.min;
@@ -115,6 +140,19 @@ impl ContainerWidget for Stack {
         container_size
     }
 
+    fn handle_cursor_event_impl(
+        &self,
+        _ctx: &mut EventContext,
+        event: UICursorEvent,
+    ) -> Option<UICursorEvent> {
+        match event {
+            UICursorEvent::CursorMoved(position) => *self.cursor.lock() = Some(position),
+            UICursorEvent::CursorExited => *self.cursor.lock() = None,
+            UICursorEvent::CursorEntered => {}
+        }
+        Some(event)
+    }
+
     fn get_visibility(&self) -> Visibility {
         *self.visibility.lock()
     }
@@ -125,6 +163,21 @@ impl ContainerWidget for Stack {
 }
 
 impl Stack {
+    /// The largest of the children, which all get the same room.
+    fn intrinsic_size(
+        &self,
+        axis: LayoutAxis,
+        cross: f32,
+        query: impl Fn(&dyn Widget, LayoutAxis, f32) -> f32,
+    ) -> f32 {
+        let cross = (cross - self.padding.lock().along(axis.other())).max(0.0);
+        self.children
+            .lock()
+            .iter()
+            .map(|child| query(child.widget.as_ref(), axis, cross))
+            .fold(0.0, f32::max)
+    }
+
     pub fn new() -> Self {
         Self {
             id: acquire_widget_id(),
@@ -133,6 +186,8 @@ impl Stack {
             hover_children: Mutex::new(Vec::new()),
             padding: Mutex::new(Padding::default()),
             visibility: Mutex::new(Visibility::Visible),
+            constraints: Mutex::new(None),
+            cursor: Mutex::new(None),
         }
     }
 
@@ -147,6 +202,79 @@ impl Stack {
             size: UISize::ZERO,
         })
     }
+
+    /// Inserts `widget` at `index`, drawn above the children before it, then
+    /// lays the stack out again and updates the hovered children.
+    pub fn insert(
+        self: &Arc<Self>,
+        ctx: &mut EventContext,
+        index: usize,
+        widget: Arc<dyn Widget>,
+        alignment: Alignment,
+    ) {
+        self.children.lock().insert(
+            index,
+            StackChild {
+                widget,
+                alignment,
+                size: UISize::ZERO,
+            },
+        );
+        self.invalidate_layout();
+        self.refresh_hover(ctx);
+    }
+
+    /// Removes the child `id`. The child gets a `CursorExited` event if it
+    /// was hovered, and the focus is released if it was held inside the
+    /// removed subtree.
+    pub fn remove(
+        self: &Arc<Self>,
+        ctx: &mut EventContext,
+        id: WidgetId,
+    ) -> Option<Arc<dyn Widget>> {
+        let widget = {
+            let mut children = self.children.lock();
+            let index = children.iter().position(|child| child.widget.id() == id)?;
+            children.remove(index).widget
+        };
+
+        let hovered = {
+            let mut hover_children = self.hover_children.lock();
+            let len = hover_children.len();
+            hover_children.retain(|child| child.id() != id);
+            hover_children.len() != len
+        };
+        if hovered {
+            widget
+                .clone()
+                .handle_cursor_event(ctx, UICursorEvent::CursorExited);
+        }
+
+        let removed = subtree_ids(&widget);
+        ctx.main_ctx
+            .release_focus_if(|focused| removed.contains(&focused));
+
+        self.invalidate_layout();
+        self.refresh_hover(ctx);
+        Some(widget)
+    }
+
+    /// Lays the stack out again with the constraints of its last layout, the
+    /// parent isn't notified if the size of the stack changes.
+    pub fn invalidate_layout(&self) {
+        let constraints = *self.constraints.lock();
+        if let Some(constraints) = constraints {
+            self.layout(&constraints);
+        }
+    }
+
+    fn refresh_hover(self: &Arc<Self>, ctx: &mut EventContext) {
+        let cursor = *self.cursor.lock();
+        if let Some(position) = cursor {
+            self.clone()
+                .handle_cursor_event(ctx, UICursorEvent::CursorMoved(position));
+        }
+    }
 }
 
 impl Default for Stack {
@@ -0,0 +1,248 @@
+use std::sync::Arc;
+
+use glam::{Vec2, Vec4};
+use trait_set::trait_set;
+
+use crate::{
+    graphics::context::DrawContext,
+    ui::{
+        acquire_widget_id,
+        event::{UICursorEvent, UIFocusEvent, UIPropagatingEvent},
+        utils::geom::{UIPos, UIRect, UISize},
+        EventContext, LayoutAxis, Padding, UISizeConstraint, Widget, WidgetId, FOCUS_COLOR,
+        FOCUS_RING,
+    },
+    utils::mutex::Mutex,
+};
+
+use super::interaction::{is_activation_key, Interaction, PointerAction};
+
+const CORNER_RADIUS: f32 = 4.0;
+
+trait_set! {
+    pub trait ClickCallback = Fn(&mut EventContext) + Send + Sync;
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum ButtonState {
+    Normal,
+    Hovered,
+    Pressed,
+    Disabled,
+}
+
+impl ButtonState {
+    pub(super) fn of(interaction: &Interaction) -> Self {
+        if !interaction.enabled() {
+            ButtonState::Disabled
+        } else if interaction.pressed() {
+            ButtonState::Pressed
+        } else if interaction.hovered() {
+            ButtonState::Hovered
+        } else {
+            ButtonState::Normal
+        }
+    }
+
+    pub(super) fn color(self) -> Vec4 {
+        match self {
+            ButtonState::Normal => Vec4::new(0.25, 0.25, 0.25, 1.0),
+            ButtonState::Hovered => Vec4::new(0.35, 0.35, 0.35, 1.0),
+            ButtonState::Pressed => Vec4::new(0.15, 0.15, 0.15, 1.0),
+            ButtonState::Disabled => Vec4::new(0.2, 0.2, 0.2, 0.5),
+        }
+    }
+}
+
+/// Calls its `on_click` callback when clicked, or when Enter or Space is
+/// pressed while it's focused. Pressing it focuses it.
+pub struct Button {
+    id: WidgetId,
+    /// drawn centered in the button, not receiving events
+    content: Option<Arc<dyn Widget>>,
+    padding: Padding,
+    on_click: Option<Box<dyn ClickCallback>>,
+    interaction: Interaction,
+    bounds: Mutex<UIRect>,
+}
+
+impl Button {
+    pub fn new(content: Option<Arc<dyn Widget>>) -> Self {
+        Self {
+            id: acquire_widget_id(),
+            content,
+            padding: Padding::new(4.0, 4.0, 8.0, 8.0),
+            on_click: None,
+            interaction: Interaction::default(),
+            bounds: Mutex::new(UIRect::ZERO),
+        }
+    }
+
+    pub fn on_click<F>(mut self, callback: F) -> Self
+    where
+        F: ClickCallback + 'static,
+    {
+        self.on_click = Some(Box::new(callback));
+        self
+    }
+
+    /// Disabled buttons ignore the cursor and the keyboard.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.interaction.set_enabled(enabled);
+    }
+
+    pub fn state(&self) -> ButtonState {
+        ButtonState::of(&self.interaction)
+    }
+
+    fn click(&self, ctx: &mut EventContext) {
+        if let Some(on_click) = self.on_click.as_ref() {
+            on_click(ctx);
+        }
+    }
+}
+
+impl Widget for Button {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn handle_propagating_event(
+        self: Arc<Self>,
+        ctx: &mut EventContext,
+        event: UIPropagatingEvent,
+    ) -> Option<UIPropagatingEvent> {
+        match self
+            .interaction
+            .handle_propagating_event(ctx, self.clone(), &event)
+        {
+            PointerAction::Pressed => None,
+            PointerAction::Released { inside } => {
+                if inside {
+                    self.click(ctx);
+                }
+                None
+            }
+            PointerAction::None => Some(event),
+        }
+    }
+
+    fn handle_focus_event(
+        self: Arc<Self>,
+        ctx: &mut EventContext,
+        event: UIFocusEvent,
+    ) -> Option<UIFocusEvent> {
+        match self.interaction.pressed_key(&event) {
+            Some(key) if is_activation_key(key) => {
+                self.click(ctx);
+                None
+            }
+            _ => Some(event),
+        }
+    }
+
+    fn handle_cursor_event(
+        self: Arc<Self>,
+        _ctx: &mut EventContext,
+        event: UICursorEvent,
+    ) -> Option<UICursorEvent> {
+        self.interaction.handle_cursor_event(event);
+        Some(event)
+    }
+
+    fn focus_changed(&self, _ctx: &mut EventContext, new_focus: bool) {
+        self.interaction.focus_changed(new_focus);
+    }
+
+    fn focusable(&self) -> bool {
+        self.interaction.enabled()
+    }
+
+    fn draw(&self, ctx: &mut DrawContext) {
+        let bounds = self.get_bounds();
+        let min = Vec2::from(bounds.pos);
+        let max = min + Vec2::from(bounds.size);
+        if self.interaction.focused() {
+            self.draw_focus_ring(ctx);
+        }
+        ctx.draw_rounded_rect(min, max, CORNER_RADIUS, self.state().color());
+
+        if let Some(content) = self.content.as_ref() {
+            ctx.transform_stack.push();
+            ctx.transform_stack.translate(bounds.pos);
+            content.draw(ctx);
+            ctx.transform_stack.pop();
+        }
+    }
+
+    /// Drawn under the button, following its rounded corners.
+    fn draw_focus_ring(&self, ctx: &mut DrawContext) {
+        let bounds = self.get_bounds();
+        let ring = Vec2::splat(FOCUS_RING);
+        let min = Vec2::from(bounds.pos) - ring;
+        let max = Vec2::from(bounds.pos) + Vec2::from(bounds.size) + ring;
+        ctx.draw_rounded_rect(min, max, CORNER_RADIUS + FOCUS_RING, FOCUS_COLOR);
+    }
+
+    fn layout(&self, size_constraints: &UISizeConstraint) -> UISize {
+        let (content_constraints, offset) = self.padding.apply_to_constraints(size_constraints);
+        let content_size = match self.content.as_ref() {
+            Some(content) => content.layout(&UISizeConstraint::new(
+                UISize::ZERO,
+                content_constraints.max,
+            )),
+            None => UISize::ZERO,
+        };
+        let size = UISize::new(
+            content_size.width + self.padding.along(LayoutAxis::Horizontal),
+            content_size.height + self.padding.along(LayoutAxis::Vertical),
+        )
+        .clamp(&size_constraints.min, &size_constraints.max);
+
+        if let Some(content) = self.content.as_ref() {
+            // centered in the padded area
+            let inner = UISize::new(
+                size.width - self.padding.along(LayoutAxis::Horizontal),
+                size.height - self.padding.along(LayoutAxis::Vertical),
+            );
+            let pos = UIPos::new(
+                offset.x + (inner.width - content_size.width) * 0.5,
+                offset.y + (inner.height - content_size.height) * 0.5,
+            );
+            content.set_bounds(UIRect::new(pos, content_size));
+        }
+        size
+    }
+
+    fn min_intrinsic_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        let cross = (cross - self.padding.along(axis.other())).max(0.0);
+        let content = self
+            .content
+            .as_ref()
+            .map(|content| content.min_intrinsic_size(axis, cross))
+            .unwrap_or(0.0);
+        content + self.padding.along(axis)
+    }
+
+    fn max_intrinsic_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        let cross = (cross - self.padding.along(axis.other())).max(0.0);
+        let content = self
+            .content
+            .as_ref()
+            .map(|content| content.max_intrinsic_size(axis, cross))
+            .unwrap_or(0.0);
+        content + self.padding.along(axis)
+    }
+
+    fn set_bounds(&self, bounds: UIRect) {
+        *self.bounds.lock() = bounds;
+    }
+
+    fn get_bounds(&self) -> UIRect {
+        *self.bounds.lock()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn Widget>> {
+        self.content.iter().cloned().collect()
+    }
+}
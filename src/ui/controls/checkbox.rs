@@ -0,0 +1,224 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use glam::{Vec2, Vec4};
+use trait_set::trait_set;
+
+use crate::{
+    graphics::context::DrawContext,
+    ui::{
+        acquire_widget_id,
+        event::{UICursorEvent, UIFocusEvent, UIPropagatingEvent},
+        utils::geom::{UIPos, UIRect, UISize},
+        EventContext, UISizeConstraint, Widget, WidgetId, FOCUS_COLOR, FOCUS_RING,
+    },
+    utils::mutex::Mutex,
+};
+
+use super::{
+    button::ButtonState,
+    interaction::{is_activation_key, Interaction, PointerAction},
+};
+
+/// Size of the box of checkboxes and the circle of radio buttons.
+pub(super) const INDICATOR_SIZE: f32 = 16.0;
+/// Space between the indicator and its label.
+pub(super) const LABEL_SPACING: f32 = 6.0;
+pub(super) const CHECKED_COLOR: Vec4 = Vec4::new(0.3, 0.6, 1.0, 1.0);
+
+trait_set! {
+    pub trait CheckboxCallback = Fn(&mut EventContext, bool) + Send + Sync;
+}
+
+/// Lays out `label` on the right of an indicator in a row starting at
+/// `y`, returns the size of the row.
+pub(super) fn layout_labeled_row(
+    label: Option<&Arc<dyn Widget>>,
+    max_width: f32,
+    y: f32,
+) -> UISize {
+    let offset = INDICATOR_SIZE + LABEL_SPACING;
+    let label_size = match label {
+        Some(label) => label.layout(&UISizeConstraint::new(
+            UISize::ZERO,
+            UISize::new((max_width - offset).max(0.0), f32::INFINITY),
+        )),
+        None => return UISize::new(INDICATOR_SIZE, INDICATOR_SIZE),
+    };
+    let height = label_size.height.max(INDICATOR_SIZE);
+    if let Some(label) = label {
+        let pos = UIPos::new(offset, y + (height - label_size.height) * 0.5);
+        label.set_bounds(UIRect::new(pos, label_size));
+    }
+    UISize::new(offset + label_size.width, height)
+}
+
+/// Draws `label` in the space of the control at `origin`.
+pub(super) fn draw_label(ctx: &mut DrawContext, label: &Arc<dyn Widget>, origin: UIPos) {
+    ctx.transform_stack.push();
+    ctx.transform_stack.translate(origin);
+    label.draw(ctx);
+    ctx.transform_stack.pop();
+}
+
+/// Toggled when clicked, or when Enter or Space is pressed while it's
+/// focused.
+pub struct Checkbox {
+    id: WidgetId,
+    label: Option<Arc<dyn Widget>>,
+    checked: AtomicBool,
+    on_change: Option<Box<dyn CheckboxCallback>>,
+    interaction: Interaction,
+    bounds: Mutex<UIRect>,
+}
+
+impl Checkbox {
+    pub fn new(label: Option<Arc<dyn Widget>>) -> Self {
+        Self {
+            id: acquire_widget_id(),
+            label,
+            checked: AtomicBool::new(false),
+            on_change: None,
+            interaction: Interaction::default(),
+            bounds: Mutex::new(UIRect::ZERO),
+        }
+    }
+
+    /// Called when toggled from the UI, not by `set_checked`.
+    pub fn on_change<F>(mut self, callback: F) -> Self
+    where
+        F: CheckboxCallback + 'static,
+    {
+        self.on_change = Some(Box::new(callback));
+        self
+    }
+
+    pub fn checked(&self) -> bool {
+        self.checked.load(Ordering::Relaxed)
+    }
+
+    pub fn set_checked(&self, checked: bool) {
+        self.checked.store(checked, Ordering::Relaxed);
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.interaction.set_enabled(enabled);
+    }
+
+    /// Corners of the box, centered vertically on the left.
+    fn indicator(&self) -> (Vec2, Vec2) {
+        let bounds = self.get_bounds();
+        let min = Vec2::new(
+            bounds.pos.x,
+            bounds.pos.y + (bounds.size.height - INDICATOR_SIZE) * 0.5,
+        );
+        (min, min + Vec2::splat(INDICATOR_SIZE))
+    }
+
+    fn toggle(&self, ctx: &mut EventContext) {
+        let checked = !self.checked.fetch_xor(true, Ordering::Relaxed);
+        if let Some(on_change) = self.on_change.as_ref() {
+            on_change(ctx, checked);
+        }
+    }
+}
+
+impl Widget for Checkbox {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn handle_propagating_event(
+        self: Arc<Self>,
+        ctx: &mut EventContext,
+        event: UIPropagatingEvent,
+    ) -> Option<UIPropagatingEvent> {
+        match self
+            .interaction
+            .handle_propagating_event(ctx, self.clone(), &event)
+        {
+            PointerAction::Pressed => None,
+            PointerAction::Released { inside } => {
+                if inside {
+                    self.toggle(ctx);
+                }
+                None
+            }
+            PointerAction::None => Some(event),
+        }
+    }
+
+    fn handle_focus_event(
+        self: Arc<Self>,
+        ctx: &mut EventContext,
+        event: UIFocusEvent,
+    ) -> Option<UIFocusEvent> {
+        match self.interaction.pressed_key(&event) {
+            Some(key) if is_activation_key(key) => {
+                self.toggle(ctx);
+                None
+            }
+            _ => Some(event),
+        }
+    }
+
+    fn handle_cursor_event(
+        self: Arc<Self>,
+        _ctx: &mut EventContext,
+        event: UICursorEvent,
+    ) -> Option<UICursorEvent> {
+        self.interaction.handle_cursor_event(event);
+        Some(event)
+    }
+
+    fn focus_changed(&self, _ctx: &mut EventContext, new_focus: bool) {
+        self.interaction.focus_changed(new_focus);
+    }
+
+    fn focusable(&self) -> bool {
+        self.interaction.enabled()
+    }
+
+    fn draw(&self, ctx: &mut DrawContext) {
+        let bounds = self.get_bounds();
+        let (min, max) = self.indicator();
+        if self.interaction.focused() {
+            self.draw_focus_ring(ctx);
+        }
+        let state = ButtonState::of(&self.interaction);
+        ctx.draw_rounded_rect(min, max, 3.0, state.color());
+        if self.checked() {
+            let inset = Vec2::splat(4.0);
+            ctx.draw_rounded_rect(min + inset, max - inset, 1.0, CHECKED_COLOR);
+        }
+        if let Some(label) = self.label.as_ref() {
+            draw_label(ctx, label, bounds.pos);
+        }
+    }
+
+    /// Drawn around the box only.
+    fn draw_focus_ring(&self, ctx: &mut DrawContext) {
+        let (min, max) = self.indicator();
+        let ring = Vec2::splat(FOCUS_RING);
+        ctx.draw_rounded_rect(min - ring, max + ring, 3.0 + FOCUS_RING, FOCUS_COLOR);
+    }
+
+    fn layout(&self, size_constraints: &UISizeConstraint) -> UISize {
+        layout_labeled_row(self.label.as_ref(), size_constraints.max.width, 0.0)
+            .clamp(&size_constraints.min, &size_constraints.max)
+    }
+
+    fn set_bounds(&self, bounds: UIRect) {
+        *self.bounds.lock() = bounds;
+    }
+
+    fn get_bounds(&self) -> UIRect {
+        *self.bounds.lock()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn Widget>> {
+        self.label.iter().cloned().collect()
+    }
+}
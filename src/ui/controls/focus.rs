@@ -67,7 +67,8 @@ impl Widget for Focus {
 
             UIPropagatingEvent::VisibilityChanged(visibility) if !visibility.handle_event() => {
                 if self.focused.load(Ordering::Relaxed) {
-                    ctx.main_ctx.set_focus_widget(None);
+                    let id = self.id;
+                    ctx.main_ctx.release_focus_if(|focused| focused == id);
                 }
             }
 
@@ -80,4 +81,8 @@ impl Widget for Focus {
     fn focus_changed(&self, _: &mut EventContext, new_focus: bool) {
         self.focused.store(new_focus, Ordering::Relaxed);
     }
+
+    fn focusable(&self) -> bool {
+        true
+    }
 }
@@ -0,0 +1,159 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode};
+
+use crate::{
+    ui::{
+        event::{UICursorEvent, UIFocusEvent, UIPropagatingEvent},
+        utils::geom::UIPos,
+        EventContext, Widget,
+    },
+    utils::mutex::Mutex,
+};
+
+/// What a control does with a propagating event, see
+/// `Interaction::handle_propagating_event`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PointerAction {
+    /// pressed with the left button, the control got the focus
+    Pressed,
+    /// released after a press, `inside` if the cursor is still over it
+    Released {
+        inside: bool,
+    },
+    None,
+}
+
+/// Hover, press and focus tracking shared by the controls, so that they
+/// all react the same way to the cursor and the focus.
+pub struct Interaction {
+    enabled: AtomicBool,
+    hovered: AtomicBool,
+    pressed: AtomicBool,
+    focused: AtomicBool,
+    /// last cursor position, relative to the control
+    cursor: Mutex<UIPos>,
+}
+
+impl Default for Interaction {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(true),
+            hovered: AtomicBool::new(false),
+            pressed: AtomicBool::new(false),
+            focused: AtomicBool::new(false),
+            cursor: Mutex::new(UIPos::ZERO),
+        }
+    }
+}
+
+impl Interaction {
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn hovered(&self) -> bool {
+        self.hovered.load(Ordering::Relaxed)
+    }
+
+    pub fn pressed(&self) -> bool {
+        self.pressed.load(Ordering::Relaxed)
+    }
+
+    pub fn focused(&self) -> bool {
+        self.focused.load(Ordering::Relaxed)
+    }
+
+    pub fn cursor(&self) -> UIPos {
+        *self.cursor.lock()
+    }
+
+    /// Disabled controls ignore the cursor and the keyboard.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.pressed.store(false, Ordering::Relaxed);
+        }
+    }
+
+    pub fn handle_cursor_event(&self, event: UICursorEvent) {
+        match event {
+            UICursorEvent::CursorEntered => self.hovered.store(true, Ordering::Relaxed),
+            UICursorEvent::CursorMoved(position) => {
+                self.hovered.store(true, Ordering::Relaxed);
+                *self.cursor.lock() = position;
+            }
+            UICursorEvent::CursorExited => {
+                self.hovered.store(false, Ordering::Relaxed);
+                self.pressed.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Tracks the presses of the left button, focusing `widget` when it's
+    /// pressed, and drops the focus when it's hidden. The mouse events of
+    /// a press should be consumed.
+    pub fn handle_propagating_event(
+        &self,
+        ctx: &mut EventContext,
+        widget: Arc<dyn Widget>,
+        event: &UIPropagatingEvent,
+    ) -> PointerAction {
+        match event {
+            UIPropagatingEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+            } if self.enabled() => {
+                self.pressed.store(true, Ordering::Relaxed);
+                ctx.main_ctx.set_focus_widget(Some(widget));
+                PointerAction::Pressed
+            }
+
+            UIPropagatingEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+            } if self.pressed.swap(false, Ordering::Relaxed) => PointerAction::Released {
+                inside: self.enabled() && self.hovered(),
+            },
+
+            UIPropagatingEvent::VisibilityChanged(visibility) if !visibility.handle_event() => {
+                self.hovered.store(false, Ordering::Relaxed);
+                self.pressed.store(false, Ordering::Relaxed);
+                if self.focused() {
+                    let id = widget.id();
+                    ctx.main_ctx.release_focus_if(|focused| focused == id);
+                }
+                PointerAction::None
+            }
+
+            _ => PointerAction::None,
+        }
+    }
+
+    pub fn focus_changed(&self, new_focus: bool) {
+        self.focused.store(new_focus, Ordering::Relaxed);
+    }
+
+    /// The key pressed while the enabled control is focused.
+    pub fn pressed_key(&self, event: &UIFocusEvent) -> Option<VirtualKeyCode> {
+        match event {
+            UIFocusEvent::KeyboardInput(KeyboardInput {
+                state: ElementState::Pressed,
+                virtual_keycode: Some(key),
+                ..
+            }) if self.enabled() => Some(*key),
+            _ => None,
+        }
+    }
+}
+
+/// Keys activating the focused control, like a click.
+pub fn is_activation_key(key: VirtualKeyCode) -> bool {
+    matches!(
+        key,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter | VirtualKeyCode::Space
+    )
+}
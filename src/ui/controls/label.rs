@@ -0,0 +1,139 @@
+use glam::{Vec2, Vec4};
+
+use crate::{
+    graphics::{context::DrawContext, text::Font},
+    ui::{
+        acquire_widget_id,
+        utils::geom::{UIRect, UISize},
+        LayoutAxis, UISizeConstraint, Widget, WidgetId,
+    },
+    utils::mutex::Mutex,
+};
+
+const ELLIPSIS: &str = "\u{2026}";
+
+/// A single line of text, cut with an ellipsis when it doesn't fit.
+pub struct Label {
+    id: WidgetId,
+    font: Font,
+    size: f32,
+    color: Vec4,
+    text: Mutex<String>,
+    /// `text`, or its longest prefix fitting with an ellipsis
+    shown: Mutex<String>,
+    bounds: Mutex<UIRect>,
+}
+
+impl Label {
+    /// `size` is the font size, in UI units.
+    pub fn new(font: Font, text: impl Into<String>, size: f32) -> Self {
+        let text = text.into();
+        Self {
+            id: acquire_widget_id(),
+            font,
+            size,
+            color: Vec4::ONE,
+            shown: Mutex::new(text.clone()),
+            text: Mutex::new(text),
+            bounds: Mutex::new(UIRect::ZERO),
+        }
+    }
+
+    pub fn color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Takes effect on the next layout.
+    pub fn set_text(&self, text: impl Into<String>) {
+        *self.text.lock() = text.into();
+    }
+
+    /// The text drawn since the last layout.
+    pub fn shown_text(&self) -> String {
+        self.shown.lock().clone()
+    }
+
+    fn natural_size(&self) -> Vec2 {
+        let width = self.font.measure(&self.text.lock(), self.size).x;
+        Vec2::new(width, self.font.line_height(self.size))
+    }
+
+    /// Longest prefix of `text` followed by an ellipsis at most `width`
+    /// wide, empty if not even the ellipsis fits.
+    fn ellipsize(&self, text: &str, width: f32) -> String {
+        let fits = |end: usize| {
+            let shown = format!("{}{ELLIPSIS}", &text[..end]);
+            self.font.measure(&shown, self.size).x <= width
+        };
+        if !fits(0) {
+            return String::new();
+        }
+        // binary search on the char boundaries, `fits(boundaries[low])`
+        let boundaries = text.char_indices().map(|(i, _)| i).collect::<Vec<_>>();
+        let (mut low, mut high) = (0, boundaries.len());
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            if fits(boundaries[mid]) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        let end = boundaries.get(low).copied().unwrap_or(0);
+        format!("{}{ELLIPSIS}", text[..end].trim_end())
+    }
+}
+
+impl Widget for Label {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn layout(&self, size_constraints: &UISizeConstraint) -> UISize {
+        let natural = self.natural_size();
+        let max_width = size_constraints.max.width;
+        let text = self.text.lock().clone();
+        let (shown, width) = if natural.x <= max_width {
+            (text, natural.x)
+        } else {
+            let shown = self.ellipsize(&text, max_width);
+            let width = self.font.measure(&shown, self.size).x;
+            (shown, width)
+        };
+        *self.shown.lock() = shown;
+        UISize::new(width, natural.y).clamp(&size_constraints.min, &size_constraints.max)
+    }
+
+    /// The label shrinks down to the ellipsis alone.
+    fn min_intrinsic_size(&self, axis: LayoutAxis, _cross: f32) -> f32 {
+        let natural = self.natural_size();
+        match axis {
+            LayoutAxis::Horizontal => natural.x.min(self.font.measure(ELLIPSIS, self.size).x),
+            LayoutAxis::Vertical => natural.y,
+        }
+    }
+
+    fn max_intrinsic_size(&self, axis: LayoutAxis, _cross: f32) -> f32 {
+        axis.get_size(self.natural_size().into())
+    }
+
+    fn draw(&self, ctx: &mut DrawContext) {
+        let position = self.get_bounds().pos.into();
+        ctx.draw_text(
+            &self.font,
+            &self.shown.lock(),
+            position,
+            self.size,
+            self.color,
+        );
+    }
+
+    fn set_bounds(&self, bounds: UIRect) {
+        *self.bounds.lock() = bounds;
+    }
+
+    fn get_bounds(&self) -> UIRect {
+        *self.bounds.lock()
+    }
+}
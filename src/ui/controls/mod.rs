@@ -1,2 +1,8 @@
+pub mod button;
+pub mod checkbox;
 pub mod focus;
+pub mod interaction;
+pub mod label;
+pub mod paragraph;
+pub mod radio_group;
 pub mod slider;
@@ -0,0 +1,182 @@
+use glam::{Vec2, Vec4};
+
+use crate::{
+    graphics::{context::DrawContext, text::Font},
+    ui::{
+        acquire_widget_id,
+        utils::geom::{UIRect, UISize},
+        HorizontalAlignment, LayoutAxis, UISizeConstraint, Widget, WidgetId,
+    },
+    utils::mutex::Mutex,
+};
+
+struct Line {
+    text: String,
+    /// offset from the left of the paragraph, from its alignment
+    x: f32,
+}
+
+/// Text wrapped at the spaces between words to the width it is laid out
+/// in. Words wider than that overflow on their own line.
+pub struct Paragraph {
+    id: WidgetId,
+    font: Font,
+    size: f32,
+    color: Vec4,
+    alignment: HorizontalAlignment,
+    /// multiplier of the line height of the font
+    line_spacing: f32,
+    text: Mutex<String>,
+    lines: Mutex<Vec<Line>>,
+    bounds: Mutex<UIRect>,
+}
+
+impl Paragraph {
+    /// `size` is the font size, in UI units.
+    pub fn new(font: Font, text: impl Into<String>, size: f32) -> Self {
+        Self {
+            id: acquire_widget_id(),
+            font,
+            size,
+            color: Vec4::ONE,
+            alignment: HorizontalAlignment::Left,
+            line_spacing: 1.0,
+            text: Mutex::new(text.into()),
+            lines: Mutex::new(Vec::new()),
+            bounds: Mutex::new(UIRect::ZERO),
+        }
+    }
+
+    pub fn color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn alignment(mut self, alignment: HorizontalAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+
+    /// Takes effect on the next layout.
+    pub fn set_text(&self, text: impl Into<String>) {
+        *self.text.lock() = text.into();
+    }
+
+    /// The lines drawn since the last layout, with their offset from the
+    /// left of the paragraph.
+    pub fn lines(&self) -> Vec<(String, f32)> {
+        self.lines
+            .lock()
+            .iter()
+            .map(|line| (line.text.clone(), line.x))
+            .collect()
+    }
+
+    fn width_of(&self, text: &str) -> f32 {
+        self.font.measure(text, self.size).x
+    }
+
+    fn line_advance(&self) -> f32 {
+        self.font.line_height(self.size) * self.line_spacing
+    }
+
+    /// Greedily fills lines of at most `width`, keeping the line breaks of
+    /// the text.
+    fn wrap(&self, text: &str, width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let mut line = String::new();
+            for word in paragraph.split_whitespace() {
+                if line.is_empty() {
+                    line.push_str(word);
+                    continue;
+                }
+                let candidate = format!("{line} {word}");
+                if self.width_of(&candidate) <= width {
+                    line = candidate;
+                } else {
+                    lines.push(std::mem::replace(&mut line, word.to_owned()));
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn height_of(&self, line_count: usize) -> f32 {
+        line_count as f32 * self.line_advance()
+    }
+}
+
+impl Widget for Paragraph {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn layout(&self, size_constraints: &UISizeConstraint) -> UISize {
+        let text = self.text.lock().clone();
+        let wrapped = self
+            .wrap(&text, size_constraints.max.width)
+            .into_iter()
+            .map(|text| (self.width_of(&text), text))
+            .collect::<Vec<_>>();
+        let width = wrapped.iter().map(|(width, _)| *width).fold(0.0, f32::max);
+        let size = UISize::new(width, self.height_of(wrapped.len()))
+            .clamp(&size_constraints.min, &size_constraints.max);
+
+        *self.lines.lock() = wrapped
+            .into_iter()
+            .map(|(width, text)| Line {
+                text,
+                x: self.alignment.calc_x_offset(size.width, width),
+            })
+            .collect();
+        size
+    }
+
+    /// At least as wide as its longest word, at most as its longest line.
+    fn min_intrinsic_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        match axis {
+            LayoutAxis::Horizontal => self
+                .text
+                .lock()
+                .split_whitespace()
+                .map(|word| self.width_of(word))
+                .fold(0.0, f32::max),
+            LayoutAxis::Vertical => self.max_intrinsic_size(axis, cross),
+        }
+    }
+
+    fn max_intrinsic_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        let text = self.text.lock().clone();
+        match axis {
+            LayoutAxis::Horizontal => text
+                .split('\n')
+                .map(|line| self.width_of(&line.split_whitespace().collect::<Vec<_>>().join(" ")))
+                .fold(0.0, f32::max),
+            LayoutAxis::Vertical => self.height_of(self.wrap(&text, cross).len()),
+        }
+    }
+
+    fn draw(&self, ctx: &mut DrawContext) {
+        let origin = Vec2::from(self.get_bounds().pos);
+        let advance = self.line_advance();
+        for (i, line) in self.lines.lock().iter().enumerate() {
+            let position = origin + Vec2::new(line.x, i as f32 * advance);
+            ctx.draw_text(&self.font, &line.text, position, self.size, self.color);
+        }
+    }
+
+    fn set_bounds(&self, bounds: UIRect) {
+        *self.bounds.lock() = bounds;
+    }
+
+    fn get_bounds(&self) -> UIRect {
+        *self.bounds.lock()
+    }
+}
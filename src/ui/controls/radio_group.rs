@@ -0,0 +1,232 @@
+use std::sync::Arc;
+
+use glam::{Vec2, Vec4};
+use trait_set::trait_set;
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    graphics::context::DrawContext,
+    ui::{
+        acquire_widget_id,
+        event::{UICursorEvent, UIFocusEvent, UIPropagatingEvent},
+        utils::geom::{UIRect, UISize},
+        EventContext, UISizeConstraint, Widget, WidgetId, FOCUS_COLOR, FOCUS_RING,
+    },
+    utils::mutex::Mutex,
+};
+
+use super::{
+    button::ButtonState,
+    checkbox::{draw_label, layout_labeled_row, CHECKED_COLOR, INDICATOR_SIZE},
+    interaction::{Interaction, PointerAction},
+};
+
+/// Space between the options.
+const ROW_SPACING: f32 = 4.0;
+
+trait_set! {
+    pub trait RadioCallback = Fn(&mut EventContext, usize) + Send + Sync;
+}
+
+/// Options of which at most one is selected, by clicking it or moving the
+/// selection with the arrow keys when focused.
+pub struct RadioGroup {
+    id: WidgetId,
+    /// labels of the options
+    options: Vec<Arc<dyn Widget>>,
+    /// vertical extent of the options, from the last layout
+    rows: Mutex<Vec<(f32, f32)>>,
+    selected: Mutex<Option<usize>>,
+    on_change: Option<Box<dyn RadioCallback>>,
+    interaction: Interaction,
+    bounds: Mutex<UIRect>,
+}
+
+impl RadioGroup {
+    pub fn new(options: Vec<Arc<dyn Widget>>) -> Self {
+        Self {
+            id: acquire_widget_id(),
+            options,
+            rows: Mutex::new(Vec::new()),
+            selected: Mutex::new(None),
+            on_change: None,
+            interaction: Interaction::default(),
+            bounds: Mutex::new(UIRect::ZERO),
+        }
+    }
+
+    /// Called with the index of the option selected from the UI, not by
+    /// `set_selected`.
+    pub fn on_change<F>(mut self, callback: F) -> Self
+    where
+        F: RadioCallback + 'static,
+    {
+        self.on_change = Some(Box::new(callback));
+        self
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        *self.selected.lock()
+    }
+
+    pub fn set_selected(&self, selected: Option<usize>) {
+        *self.selected.lock() = selected.filter(|&i| i < self.options.len());
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.interaction.set_enabled(enabled);
+    }
+
+    fn select(&self, ctx: &mut EventContext, index: usize) {
+        if index >= self.options.len() {
+            return;
+        }
+        let previous = self.selected.lock().replace(index);
+        if previous != Some(index) {
+            if let Some(on_change) = self.on_change.as_ref() {
+                on_change(ctx, index);
+            }
+        }
+    }
+
+    /// Center of the circle of the option laid out in `row`.
+    fn indicator_center(&self, (top, height): (f32, f32)) -> Vec2 {
+        let bounds = self.get_bounds();
+        let radius = INDICATOR_SIZE * 0.5;
+        Vec2::new(bounds.pos.x + radius, bounds.pos.y + top + height * 0.5)
+    }
+
+    fn option_under_cursor(&self) -> Option<usize> {
+        let y = self.interaction.cursor().y;
+        self.rows
+            .lock()
+            .iter()
+            .position(|&(top, height)| top <= y && y <= top + height)
+    }
+}
+
+impl Widget for RadioGroup {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn handle_propagating_event(
+        self: Arc<Self>,
+        ctx: &mut EventContext,
+        event: UIPropagatingEvent,
+    ) -> Option<UIPropagatingEvent> {
+        match self
+            .interaction
+            .handle_propagating_event(ctx, self.clone(), &event)
+        {
+            PointerAction::Pressed => None,
+            PointerAction::Released { inside } => {
+                if let Some(index) = self.option_under_cursor().filter(|_| inside) {
+                    self.select(ctx, index);
+                }
+                None
+            }
+            PointerAction::None => Some(event),
+        }
+    }
+
+    fn handle_focus_event(
+        self: Arc<Self>,
+        ctx: &mut EventContext,
+        event: UIFocusEvent,
+    ) -> Option<UIFocusEvent> {
+        // an empty group has nothing to select, Tab still moves the focus
+        let last = match self.options.len().checked_sub(1) {
+            Some(last) => last,
+            None => return Some(event),
+        };
+        let selected = self.selected();
+        let index = match self.interaction.pressed_key(&event) {
+            Some(VirtualKeyCode::Up | VirtualKeyCode::Left) => {
+                selected.map(|i| i.saturating_sub(1)).unwrap_or(0)
+            }
+            Some(VirtualKeyCode::Down | VirtualKeyCode::Right) => {
+                selected.map(|i| (i + 1).min(last)).unwrap_or(0)
+            }
+            _ => return Some(event),
+        };
+        self.select(ctx, index);
+        None
+    }
+
+    fn handle_cursor_event(
+        self: Arc<Self>,
+        _ctx: &mut EventContext,
+        event: UICursorEvent,
+    ) -> Option<UICursorEvent> {
+        self.interaction.handle_cursor_event(event);
+        Some(event)
+    }
+
+    fn focus_changed(&self, _ctx: &mut EventContext, new_focus: bool) {
+        self.interaction.focus_changed(new_focus);
+    }
+
+    fn focusable(&self) -> bool {
+        self.interaction.enabled() && !self.options.is_empty()
+    }
+
+    fn draw(&self, ctx: &mut DrawContext) {
+        let bounds = self.get_bounds();
+        let radius = INDICATOR_SIZE * 0.5;
+        let state = ButtonState::of(&self.interaction);
+        let selected = self.selected();
+        if self.interaction.focused() {
+            self.draw_focus_ring(ctx);
+        }
+        for (i, &row) in self.rows.lock().iter().enumerate() {
+            let center = self.indicator_center(row);
+            ctx.draw_circle(center, radius, state.color());
+            if selected == Some(i) {
+                ctx.draw_circle(center, radius * 0.5, CHECKED_COLOR);
+            } else {
+                ctx.draw_circle(center, radius * 0.5, Vec4::new(0.0, 0.0, 0.0, 0.3));
+            }
+        }
+        for label in self.options.iter() {
+            draw_label(ctx, label, bounds.pos);
+        }
+    }
+
+    /// Drawn around the selected option, the first one if none is.
+    fn draw_focus_ring(&self, ctx: &mut DrawContext) {
+        let row = self.rows.lock().get(self.selected().unwrap_or(0)).copied();
+        if let Some(row) = row {
+            let radius = INDICATOR_SIZE * 0.5 + FOCUS_RING;
+            ctx.draw_circle(self.indicator_center(row), radius, FOCUS_COLOR);
+        }
+    }
+
+    fn layout(&self, size_constraints: &UISizeConstraint) -> UISize {
+        let mut rows = Vec::with_capacity(self.options.len());
+        let mut size = UISize::ZERO;
+        for label in self.options.iter() {
+            if !rows.is_empty() {
+                size.height += ROW_SPACING;
+            }
+            let row = layout_labeled_row(Some(label), size_constraints.max.width, size.height);
+            rows.push((size.height, row.height));
+            size.width = size.width.max(row.width);
+            size.height += row.height;
+        }
+        *self.rows.lock() = rows;
+        size.clamp(&size_constraints.min, &size_constraints.max)
+    }
+
+    fn set_bounds(&self, bounds: UIRect) {
+        *self.bounds.lock() = bounds;
+    }
+
+    fn get_bounds(&self) -> UIRect {
+        *self.bounds.lock()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn Widget>> {
+        self.options.clone()
+    }
+}
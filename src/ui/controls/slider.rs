@@ -1,7 +1,245 @@
-pub struct SliderState {
-    pub min: f32,
-    pub max: f32,
-    pub value: f32,
+use std::sync::Arc;
+
+use glam::{Vec2, Vec4};
+use trait_set::trait_set;
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    graphics::context::DrawContext,
+    ui::{
+        acquire_widget_id,
+        event::{UICursorEvent, UIFocusEvent, UIPropagatingEvent},
+        utils::geom::{UIRect, UISize},
+        EventContext, UISizeConstraint, Widget, WidgetId, FOCUS_COLOR, FOCUS_RING,
+    },
+    utils::mutex::Mutex,
+};
+
+use super::{
+    button::ButtonState,
+    interaction::{Interaction, PointerAction},
+};
+
+const PREFERRED_SIZE: UISize = UISize::new(160.0, 20.0);
+const TRACK_THICKNESS: f32 = 4.0;
+const THUMB_RADIUS: f32 = 8.0;
+/// Keyboard steps of sliders without a step, as a fraction of the range.
+const KEY_STEP: f32 = 0.01;
+
+trait_set! {
+    pub trait SliderCallback = Fn(&mut EventContext, f32) + Send + Sync;
+}
+
+/// A horizontal slider picking a value in a range, dragged with the cursor
+/// or moved with the arrow keys, Home and End when focused.
+pub struct Slider {
+    id: WidgetId,
+    min: f32,
+    max: f32,
+    /// values snap to multiples of `step` from `min`, continuous if zero
+    step: f32,
+    value: Mutex<f32>,
+    on_change: Option<Box<dyn SliderCallback>>,
+    interaction: Interaction,
+    bounds: Mutex<UIRect>,
+}
+
+impl Slider {
+    /// `min` and `max` are swapped if given in the wrong order.
+    pub fn new(min: f32, max: f32, value: f32) -> Self {
+        let (min, max) = (min.min(max), min.max(max));
+        Self {
+            id: acquire_widget_id(),
+            min,
+            max,
+            step: 0.0,
+            value: Mutex::new(value.clamp(min, max)),
+            on_change: None,
+            interaction: Interaction::default(),
+            bounds: Mutex::new(UIRect::ZERO),
+        }
+    }
+
+    pub fn step(mut self, step: f32) -> Self {
+        self.step = step.max(0.0);
+        self
+    }
+
+    /// Called when the value is changed from the UI, not by `set_value`.
+    pub fn on_change<F>(mut self, callback: F) -> Self
+    where
+        F: SliderCallback + 'static,
+    {
+        self.on_change = Some(Box::new(callback));
+        self
+    }
+
+    pub fn value(&self) -> f32 {
+        *self.value.lock()
+    }
+
+    pub fn set_value(&self, value: f32) {
+        *self.value.lock() = self.snap(value);
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.interaction.set_enabled(enabled);
+    }
+
+    fn snap(&self, value: f32) -> f32 {
+        let value = if self.step > 0.0 {
+            self.min + ((value - self.min) / self.step).round() * self.step
+        } else {
+            value
+        };
+        value.clamp(self.min, self.max)
+    }
+
+    fn change(&self, ctx: &mut EventContext, value: f32) {
+        let value = self.snap(value);
+        let changed = {
+            let mut current = self.value.lock();
+            let changed = *current != value;
+            *current = value;
+            changed
+        };
+        if changed {
+            if let Some(on_change) = self.on_change.as_ref() {
+                on_change(ctx, value);
+            }
+        }
+    }
+
+    /// Value under the cursor, the thumb stays inside the bounds.
+    fn value_at_cursor(&self) -> f32 {
+        let track = (self.get_bounds().size.width - 2.0 * THUMB_RADIUS).max(f32::EPSILON);
+        let t = ((self.interaction.cursor().x - THUMB_RADIUS) / track).clamp(0.0, 1.0);
+        self.min + t * (self.max - self.min)
+    }
+
+    /// Corners of the track, the thumb stays inside the bounds.
+    fn track(&self) -> (Vec2, Vec2) {
+        let bounds = self.get_bounds();
+        let min = Vec2::from(bounds.pos);
+        let center_y = min.y + bounds.size.height * 0.5;
+        let track_min = Vec2::new(min.x + THUMB_RADIUS, center_y - TRACK_THICKNESS * 0.5);
+        let track_max = Vec2::new(
+            min.x + bounds.size.width - THUMB_RADIUS,
+            center_y + TRACK_THICKNESS * 0.5,
+        );
+        (track_min, track_max)
+    }
+
+    fn thumb_center(&self) -> Vec2 {
+        let (track_min, track_max) = self.track();
+        let range = (self.max - self.min).max(f32::EPSILON);
+        let t = (self.value() - self.min) / range;
+        Vec2::new(
+            track_min.x + t * (track_max.x - track_min.x),
+            (track_min.y + track_max.y) * 0.5,
+        )
+    }
+
+    fn key_step(&self) -> f32 {
+        if self.step > 0.0 {
+            self.step
+        } else {
+            (self.max - self.min) * KEY_STEP
+        }
+    }
 }
 
-pub struct DrawSlider {}
+impl Widget for Slider {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn handle_propagating_event(
+        self: Arc<Self>,
+        ctx: &mut EventContext,
+        event: UIPropagatingEvent,
+    ) -> Option<UIPropagatingEvent> {
+        match self
+            .interaction
+            .handle_propagating_event(ctx, self.clone(), &event)
+        {
+            PointerAction::Pressed => {
+                self.change(ctx, self.value_at_cursor());
+                None
+            }
+            PointerAction::Released { .. } => None,
+            PointerAction::None => Some(event),
+        }
+    }
+
+    fn handle_focus_event(
+        self: Arc<Self>,
+        ctx: &mut EventContext,
+        event: UIFocusEvent,
+    ) -> Option<UIFocusEvent> {
+        let value = self.value();
+        let new_value = match self.interaction.pressed_key(&event) {
+            Some(VirtualKeyCode::Left | VirtualKeyCode::Down) => value - self.key_step(),
+            Some(VirtualKeyCode::Right | VirtualKeyCode::Up) => value + self.key_step(),
+            Some(VirtualKeyCode::Home) => self.min,
+            Some(VirtualKeyCode::End) => self.max,
+            _ => return Some(event),
+        };
+        self.change(ctx, new_value);
+        None
+    }
+
+    fn handle_cursor_event(
+        self: Arc<Self>,
+        ctx: &mut EventContext,
+        event: UICursorEvent,
+    ) -> Option<UICursorEvent> {
+        self.interaction.handle_cursor_event(event);
+        if matches!(event, UICursorEvent::CursorMoved(_)) && self.interaction.pressed() {
+            self.change(ctx, self.value_at_cursor());
+        }
+        Some(event)
+    }
+
+    fn focus_changed(&self, _ctx: &mut EventContext, new_focus: bool) {
+        self.interaction.focus_changed(new_focus);
+    }
+
+    fn focusable(&self) -> bool {
+        self.interaction.enabled()
+    }
+
+    fn draw(&self, ctx: &mut DrawContext) {
+        let (track_min, track_max) = self.track();
+        let state = ButtonState::of(&self.interaction);
+        ctx.draw_rounded_rect(track_min, track_max, TRACK_THICKNESS * 0.5, state.color());
+
+        if self.interaction.focused() {
+            self.draw_focus_ring(ctx);
+        }
+        let thumb = self.thumb_center();
+        let thumb_color = if self.interaction.enabled() {
+            Vec4::ONE
+        } else {
+            Vec4::new(0.5, 0.5, 0.5, 1.0)
+        };
+        ctx.draw_circle(thumb, THUMB_RADIUS, thumb_color);
+    }
+
+    /// Drawn around the thumb only.
+    fn draw_focus_ring(&self, ctx: &mut DrawContext) {
+        ctx.draw_circle(self.thumb_center(), THUMB_RADIUS + FOCUS_RING, FOCUS_COLOR);
+    }
+
+    fn layout(&self, size_constraints: &UISizeConstraint) -> UISize {
+        PREFERRED_SIZE.clamp(&size_constraints.min, &size_constraints.max)
+    }
+
+    fn set_bounds(&self, bounds: UIRect) {
+        *self.bounds.lock() = bounds;
+    }
+
+    fn get_bounds(&self) -> UIRect {
+        *self.bounds.lock()
+    }
+}
@@ -1,11 +1,19 @@
 use std::path::PathBuf;
 
 use winit::{
-    event::{ElementState, Ime, KeyboardInput, MouseButton, MouseScrollDelta},
+    event::{
+        ElementState, Ime, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+        VirtualKeyCode,
+    },
     window::Theme,
 };
 
-use super::{utils::geom::UIPos, Visibility};
+use super::{utils::geom::UIPos, LayoutAxis, Visibility};
+
+/// Zoom factor of one wheel notch with Ctrl held.
+pub const ZOOM_PER_LINE: f32 = 1.1;
+/// Pixel deltas (trackpads) per line, for the zoom of pixel deltas.
+pub const PIXELS_PER_LINE: f32 = 20.0;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum DragDropAction {
@@ -17,7 +25,11 @@ pub enum DragDropAction {
 // applied to only the focused widget
 #[derive(Clone, Debug, PartialEq)]
 pub enum UIFocusEvent {
-    Focus(bool),
+    /// Delivered after `Widget::focus_changed`. When the focus moves, the
+    /// widget losing it always gets `Lost` before the other one gets
+    /// `Gained`, neither is delivered if the focus stays on the same widget.
+    Gained,
+    Lost,
     ReceivedCharacter(char),
     Ime(Ime),
     KeyboardInput(KeyboardInput),
@@ -30,7 +42,14 @@ pub enum UIFocusEvent {
 pub enum UIPropagatingEvent {
     ThemeChanged(Theme),
     DragDrop(DragDropAction),
-    MouseWheel(MouseScrollDelta),
+    /// How far the view moves along `axis`, positive towards the bottom or
+    /// the right, the content moving the other way.
+    Scroll {
+        axis: LayoutAxis,
+        delta: ScrollDelta,
+    },
+    /// Ctrl+wheel, more than 1 zooms in.
+    Zoom(f32),
     MouseInput {
         state: ElementState,
         button: MouseButton,
@@ -46,6 +65,78 @@ impl UIPropagatingEvent {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScrollDelta {
+    /// wheel notches, the widget decides how far a line is
+    Lines(f32),
+    /// precise deltas of trackpads, in UI units
+    Pixels(f32),
+}
+
+/// The semantic events of a wheel or trackpad scroll, one per axis it moved
+/// along. Shift scrolls vertical wheels horizontally, Ctrl zooms instead.
+pub fn wheel_gestures(
+    delta: MouseScrollDelta,
+    modifiers: ModifiersState,
+    scale_factor: f64,
+) -> Vec<UIPropagatingEvent> {
+    let (x, y, delta): (f32, f32, fn(f32) -> ScrollDelta) = match delta {
+        MouseScrollDelta::LineDelta(x, y) => (x, y, ScrollDelta::Lines),
+        MouseScrollDelta::PixelDelta(position) => {
+            let position = position.to_logical::<f32>(scale_factor);
+            (position.x, position.y, ScrollDelta::Pixels)
+        }
+    };
+    if modifiers.ctrl() {
+        let lines = match delta(y) {
+            ScrollDelta::Lines(lines) => lines,
+            ScrollDelta::Pixels(pixels) => pixels / PIXELS_PER_LINE,
+        };
+        return if lines != 0.0 {
+            vec![UIPropagatingEvent::Zoom(ZOOM_PER_LINE.powf(lines))]
+        } else {
+            Vec::new()
+        };
+    }
+    let (x, y) = if modifiers.shift() && x == 0.0 {
+        (y, 0.0)
+    } else {
+        (x, y)
+    };
+    // winit deltas move the content
+    [(LayoutAxis::Horizontal, x), (LayoutAxis::Vertical, y)]
+        .into_iter()
+        .filter(|(_, value)| *value != 0.0)
+        .map(|(axis, value)| UIPropagatingEvent::Scroll {
+            axis,
+            delta: delta(-value),
+        })
+        .collect()
+}
+
+/// Direction of a keyboard focus traversal, along `ui::focus_order`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FocusDirection {
+    Next,
+    Previous,
+}
+
+/// Tab moves the focus to the next widget, Shift+Tab to the previous one.
+pub fn focus_traversal(input: &KeyboardInput, modifiers: ModifiersState) -> Option<FocusDirection> {
+    match input {
+        KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::Tab),
+            ..
+        } => Some(if modifiers.shift() {
+            FocusDirection::Previous
+        } else {
+            FocusDirection::Next
+        }),
+        _ => None,
+    }
+}
+
 // special cursor events, entered and exited
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum UICursorEvent {
@@ -53,3 +144,77 @@ pub enum UICursorEvent {
     CursorExited,
     CursorMoved(UIPos),
 }
+
+#[test]
+fn test() {
+    use winit::dpi::PhysicalPosition;
+
+    let scroll = |axis, delta| UIPropagatingEvent::Scroll { axis, delta };
+    let none = ModifiersState::empty();
+    assert_eq!(
+        wheel_gestures(MouseScrollDelta::LineDelta(0.0, 2.0), none, 1.0),
+        vec![scroll(LayoutAxis::Vertical, ScrollDelta::Lines(-2.0))]
+    );
+    assert_eq!(
+        wheel_gestures(
+            MouseScrollDelta::PixelDelta(PhysicalPosition::new(4.0, -8.0)),
+            none,
+            2.0
+        ),
+        vec![
+            scroll(LayoutAxis::Horizontal, ScrollDelta::Pixels(-2.0)),
+            scroll(LayoutAxis::Vertical, ScrollDelta::Pixels(4.0)),
+        ]
+    );
+    assert_eq!(
+        wheel_gestures(
+            MouseScrollDelta::LineDelta(0.0, 1.0),
+            ModifiersState::SHIFT,
+            1.0
+        ),
+        vec![scroll(LayoutAxis::Horizontal, ScrollDelta::Lines(-1.0))]
+    );
+    assert_eq!(
+        wheel_gestures(
+            MouseScrollDelta::LineDelta(0.0, 2.0),
+            ModifiersState::CTRL,
+            1.0
+        ),
+        vec![UIPropagatingEvent::Zoom(ZOOM_PER_LINE.powf(2.0))]
+    );
+    assert_eq!(
+        wheel_gestures(
+            MouseScrollDelta::LineDelta(1.0, 0.0),
+            ModifiersState::CTRL,
+            1.0
+        ),
+        vec![]
+    );
+
+    #[allow(deprecated)]
+    let key = |state, key| KeyboardInput {
+        scancode: 0,
+        state,
+        virtual_keycode: Some(key),
+        modifiers: ModifiersState::empty(),
+    };
+    assert_eq!(
+        focus_traversal(&key(ElementState::Pressed, VirtualKeyCode::Tab), none),
+        Some(FocusDirection::Next)
+    );
+    assert_eq!(
+        focus_traversal(
+            &key(ElementState::Pressed, VirtualKeyCode::Tab),
+            ModifiersState::SHIFT
+        ),
+        Some(FocusDirection::Previous)
+    );
+    assert_eq!(
+        focus_traversal(&key(ElementState::Released, VirtualKeyCode::Tab), none),
+        None
+    );
+    assert_eq!(
+        focus_traversal(&key(ElementState::Pressed, VirtualKeyCode::Space), none),
+        None
+    );
+}
@@ -1,6 +1,8 @@
-use std::sync::Arc;
+use std::{borrow::Cow, collections::HashSet, sync::Arc};
 
 use event::{UICursorEvent, UIFocusEvent, UIPropagatingEvent};
+use glam::{Vec2, Vec4};
+use overlay::OverlayLayer;
 use utils::geom::{UIPos, UIRect, UISize};
 
 use crate::{exec::main_ctx::MainContext, graphics::context::DrawContext, utils::uid::Uid};
@@ -8,19 +10,67 @@ use crate::{exec::main_ctx::MainContext, graphics::context::DrawContext, utils::
 pub mod containers;
 pub mod controls;
 pub mod event;
+pub mod overlay;
 pub mod utils;
 
 pub type WidgetId = Uid;
 
+/// Color of the ring around the widget holding the keyboard focus.
+pub const FOCUS_COLOR: Vec4 = Vec4::new(0.3, 0.6, 1.0, 1.0);
+/// Width of the focus ring, drawn outside of the widget's bounds.
+pub const FOCUS_RING: f32 = 2.0;
+
 pub fn acquire_widget_id() -> WidgetId {
     WidgetId::default()
 }
 
+/// Ids of `widget` and all of its descendants.
+pub fn subtree_ids(widget: &Arc<dyn Widget>) -> HashSet<WidgetId> {
+    fn collect(widget: &Arc<dyn Widget>, ids: &mut HashSet<WidgetId>) {
+        ids.insert(widget.id());
+        for child in widget.children() {
+            collect(&child, ids);
+        }
+    }
+
+    let mut ids = HashSet::new();
+    collect(widget, &mut ids);
+    ids
+}
+
+/// Focusable widgets of the tree under `root`, in the order Tab moves the
+/// focus along: depth-first, each container's children in their own order.
+/// Subtrees not handling events are skipped.
+pub fn focus_order(root: &Arc<dyn Widget>) -> Vec<Arc<dyn Widget>> {
+    fn collect(widget: &Arc<dyn Widget>, order: &mut Vec<Arc<dyn Widget>>) {
+        if !widget.visibility().handle_event() {
+            return;
+        }
+        if widget.focusable() {
+            order.push(widget.clone());
+        }
+        for child in widget.children() {
+            collect(&child, order);
+        }
+    }
+
+    let mut order = Vec::new();
+    collect(root, &mut order);
+    order
+}
+
 pub struct EventContext<'a> {
     pub main_ctx: &'a mut MainContext,
     ////  pub root_scene: &'a RootScene,
 }
 
+impl EventContext<'_> {
+    /// The layer tooltips, dropdowns and dialogs are opened in.
+    pub fn overlays(&self) -> Arc<OverlayLayer> {
+        self.main_ctx.overlays.clone()
+    }
+}
+
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Visibility {
     PhyiscalHidden, // not handling events, not drawn on screen
@@ -67,11 +117,80 @@ pub trait Widget: Send + Sync {
 
     fn focus_changed(&self, _ctx: &mut EventContext, _new_focus: bool) {}
 
+    /// Whether Tab can move the keyboard focus to the widget, see
+    /// `focus_order`.
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    /// Only containers can be hidden.
+    fn visibility(&self) -> Visibility {
+        Visibility::Visible
+    }
+
     fn draw(&self, _ctx: &mut DrawContext) {}
 
+    /// Draws the ring showing the widget holds the keyboard focus, called
+    /// by focused widgets from `draw`. Outlines the bounds by default,
+    /// override it to follow the shape of the widget or to draw nothing.
+    fn draw_focus_ring(&self, ctx: &mut DrawContext) {
+        let bounds = self.get_bounds();
+        let offset = Vec2::splat(FOCUS_RING * 0.5);
+        let min = Vec2::from(bounds.pos) - offset;
+        let max = Vec2::from(bounds.pos) + Vec2::from(bounds.size) + offset;
+        ctx.draw_polyline(
+            &[min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)],
+            true,
+            FOCUS_RING,
+            FOCUS_COLOR,
+        );
+    }
+
+    /// Draws the widget for `pick_at`, its bounds by default. Widgets that
+    /// aren't drawn can't be picked either.
+    fn draw_pick(&self, ctx: &mut DrawContext) {
+        if self.visibility().draw() {
+            ctx.draw_pick_rect(self.id(), self.get_bounds());
+        }
+    }
+
     fn layout(&self, size_constraints: &UISizeConstraint) -> UISize;
+
+    /// Smallest extent along `axis` the widget can be laid out in without
+    /// clipping its content, given `cross` along the other axis
+    /// (`f32::INFINITY` if unbounded), e.g. the longest word of wrapped
+    /// text. Defaults to the size laid out without bounds along `axis`.
+    fn min_intrinsic_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        axis.layout_intrinsic_size(self, cross)
+    }
+
+    /// Extent along `axis` past which the widget has no use for more room,
+    /// given `cross` along the other axis, e.g. text on a single line.
+    fn max_intrinsic_size(&self, axis: LayoutAxis, cross: f32) -> f32 {
+        axis.layout_intrinsic_size(self, cross)
+    }
+
     fn set_bounds(&self, bounds: UIRect);
     fn get_bounds(&self) -> UIRect;
+
+    /// Name shown in widget tree snapshots and event traces, the type name
+    /// without module paths by default.
+    fn debug_name(&self) -> Cow<'static, str> {
+        Cow::Owned(utils::snapshot::short_type_name(
+            std::any::type_name::<Self>(),
+        ))
+    }
+
+    /// Widgets the widget lays out, draws and forwards events to, in that
+    /// order, e.g. the children of a container or the label of a button.
+    fn children(&self) -> Vec<Arc<dyn Widget>> {
+        Vec::new()
+    }
+
+    /// Children shown in widget tree snapshots, `children` by default.
+    fn debug_children(&self) -> Vec<Arc<dyn Widget>> {
+        self.children()
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -89,6 +208,12 @@ impl UISizeConstraint {
         Self::new(size, size)
     }
 
+    /// Unbounded along `axis` and at most `cross` along the other one, to
+    /// measure a widget that doesn't answer intrinsic size queries.
+    pub fn intrinsic(axis: LayoutAxis, cross: f32) -> Self {
+        Self::new(UISize::ZERO, axis.new_size(f32::INFINITY, cross))
+    }
+
     pub fn test(&self, size: &UISize) -> bool {
         self.min.width <= size.width
             && size.width <= self.max.width
@@ -146,7 +271,54 @@ impl VerticalAlignment {
     }
 }
 
+/// Axis of an intrinsic size query, the runtime counterpart of `Axis`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum LayoutAxis {
+    Horizontal,
+    Vertical,
+}
+
+impl LayoutAxis {
+    pub fn other(self) -> Self {
+        match self {
+            LayoutAxis::Horizontal => LayoutAxis::Vertical,
+            LayoutAxis::Vertical => LayoutAxis::Horizontal,
+        }
+    }
+
+    pub fn get_size(self, size: UISize) -> f32 {
+        match self {
+            LayoutAxis::Horizontal => size.width,
+            LayoutAxis::Vertical => size.height,
+        }
+    }
+
+    pub fn new_size(self, this_axis: f32, other_axis: f32) -> UISize {
+        match self {
+            LayoutAxis::Horizontal => UISize::new(this_axis, other_axis),
+            LayoutAxis::Vertical => UISize::new(other_axis, this_axis),
+        }
+    }
+
+    pub fn new_pos(self, this_axis: f32, other_axis: f32) -> UIPos {
+        match self {
+            LayoutAxis::Horizontal => UIPos::new(this_axis, other_axis),
+            LayoutAxis::Vertical => UIPos::new(other_axis, this_axis),
+        }
+    }
+
+    /// Intrinsic size of a widget measured with a layout pass, see
+    /// `UISizeConstraint::intrinsic`.
+    pub fn layout_intrinsic_size(self, widget: &(impl Widget + ?Sized), cross: f32) -> f32 {
+        let size = self.get_size(widget.layout(&UISizeConstraint::intrinsic(self, cross)));
+        // `FIT_CONTAINER` widgets have no size of their own
+        size.max(0.0)
+    }
+}
+
 pub trait Axis: 'static {
+    const LAYOUT_AXIS: LayoutAxis;
+
     type MainAlignment: Send + Sync + Clone + Copy + 'static;
     type CrossAlignment: Send + Sync + Clone + Copy + 'static;
     type OtherAxis: Axis<
@@ -168,6 +340,8 @@ pub struct AxisX;
 pub struct AxisY;
 
 impl Axis for AxisX {
+    const LAYOUT_AXIS: LayoutAxis = LayoutAxis::Horizontal;
+
     type MainAlignment = HorizontalAlignment;
     type CrossAlignment = VerticalAlignment;
 
@@ -195,6 +369,8 @@ impl Axis for AxisX {
 }
 
 impl Axis for AxisY {
+    const LAYOUT_AXIS: LayoutAxis = LayoutAxis::Vertical;
+
     type MainAlignment = VerticalAlignment;
     type CrossAlignment = HorizontalAlignment;
 
@@ -221,7 +397,7 @@ impl Axis for AxisY {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Padding {
     top: f32,
     bottom: f32,
@@ -230,6 +406,27 @@ pub struct Padding {
 }
 
 impl Padding {
+    pub fn new(top: f32, bottom: f32, left: f32, right: f32) -> Self {
+        Self {
+            top,
+            bottom,
+            left,
+            right,
+        }
+    }
+
+    pub fn uniform(padding: f32) -> Self {
+        Self::new(padding, padding, padding, padding)
+    }
+
+    /// Total padding along `axis`.
+    fn along(&self, axis: LayoutAxis) -> f32 {
+        match axis {
+            LayoutAxis::Horizontal => self.left + self.right,
+            LayoutAxis::Vertical => self.top + self.bottom,
+        }
+    }
+
     fn remove_padding(&self, size: UISize) -> UISize {
         let width = 0.0f32.max(size.width - self.left - self.right);
         let height = 0.0f32.max(size.height - self.top - self.bottom);
@@ -0,0 +1,390 @@
+use std::sync::Arc;
+
+use glam::{Vec2, Vec4};
+use trait_set::trait_set;
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+
+use crate::{graphics::context::DrawContext, utils::mutex::Mutex};
+
+use super::{
+    event::{UICursorEvent, UIPropagatingEvent},
+    subtree_ids,
+    utils::geom::{UIPos, UIRect, UISize},
+    EventContext, UISizeConstraint, Widget, WidgetId,
+};
+
+/// Dims the UI under modal overlays.
+const BACKDROP_COLOR: Vec4 = Vec4::new(0.0, 0.0, 0.0, 0.4);
+
+trait_set! {
+    pub trait CloseCallback = Fn(&mut EventContext) + Send + Sync;
+}
+
+/// A widget opened over the UI, e.g. a tooltip, a dropdown or a dialog.
+pub struct Overlay {
+    widget: Arc<dyn Widget>,
+    /// top-left corner in window UI units, centered if `None`
+    anchor: Option<UIPos>,
+    modal: bool,
+    dismissable: bool,
+    on_close: Option<Box<dyn CloseCallback>>,
+}
+
+impl Overlay {
+    /// Centered in the window, dismissable and not modal by default.
+    pub fn new(widget: Arc<dyn Widget>) -> Self {
+        Self {
+            widget,
+            anchor: None,
+            modal: false,
+            dismissable: true,
+            on_close: None,
+        }
+    }
+
+    /// Opens the overlay at `anchor`, moved back inside the window if it
+    /// would overflow it.
+    pub fn at(mut self, anchor: UIPos) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    /// Modal overlays dim the UI under them and keep the cursor from it.
+    pub fn modal(mut self, modal: bool) -> Self {
+        self.modal = modal;
+        self
+    }
+
+    /// Dismissable overlays are closed by clicks outside of them and by
+    /// Escape.
+    pub fn dismissable(mut self, dismissable: bool) -> Self {
+        self.dismissable = dismissable;
+        self
+    }
+
+    /// Called once the overlay is closed, whether it was dismissed or not.
+    pub fn on_close<F>(mut self, callback: F) -> Self
+    where
+        F: CloseCallback + 'static,
+    {
+        self.on_close = Some(Box::new(callback));
+        self
+    }
+
+    fn place(&self, ui_size: UISize) {
+        let size = self
+            .widget
+            .layout(&UISizeConstraint::new(UISize::ZERO, ui_size));
+        let anchor = self.anchor.unwrap_or_else(|| {
+            UIPos::new(
+                (ui_size.width - size.width) * 0.5,
+                (ui_size.height - size.height) * 0.5,
+            )
+        });
+        let pos = UIPos::new(
+            anchor.x.min(ui_size.width - size.width).max(0.0),
+            anchor.y.min(ui_size.height - size.height).max(0.0),
+        );
+        self.widget.set_bounds(UIRect::new(pos, size));
+    }
+}
+
+#[derive(Default)]
+struct LayerState {
+    /// bottom to top
+    overlays: Vec<Overlay>,
+    ui_size: UISize,
+    /// overlay under the cursor
+    hovered: Option<WidgetId>,
+    /// whether the UI under the layer was last told the cursor left it
+    captured: bool,
+}
+
+impl LayerState {
+    fn position(&self, id: WidgetId) -> Option<usize> {
+        self.overlays.iter().position(|o| o.widget.id() == id)
+    }
+
+    fn widget(&self, id: WidgetId) -> Option<Arc<dyn Widget>> {
+        self.position(id).map(|i| self.overlays[i].widget.clone())
+    }
+
+    fn has_modal(&self) -> bool {
+        self.overlays.iter().any(|o| o.modal)
+    }
+}
+
+/// The top layer of the UI, over the root widget. Its overlays get the
+/// cursor events first, top to bottom, and the mouse presses over them.
+/// Widgets reach it with `EventContext::overlays`.
+///
+/// No lock is held while the widgets handle events, they can open and
+/// close overlays meanwhile.
+#[derive(Default)]
+pub struct OverlayLayer {
+    state: Mutex<LayerState>,
+}
+
+impl OverlayLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens `overlay` over the others, returns the id of its widget to
+    /// close it with.
+    pub fn open(&self, overlay: Overlay) -> WidgetId {
+        let id = overlay.widget.id();
+        let mut state = self.state.lock();
+        overlay.place(state.ui_size);
+        state.overlays.push(overlay);
+        id
+    }
+
+    pub fn is_open(&self, id: WidgetId) -> bool {
+        self.state.lock().position(id).is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().overlays.is_empty()
+    }
+
+    /// Closes the overlay of `id` and the ones opened over it, returns
+    /// whether it was open.
+    pub fn close(&self, ctx: &mut EventContext, id: WidgetId) -> bool {
+        let index = self.state.lock().position(id);
+        if let Some(index) = index {
+            self.close_from(ctx, index);
+        }
+        index.is_some()
+    }
+
+    /// Drops every overlay without notifying them, e.g. at shutdown.
+    pub fn clear(&self) {
+        let overlays = std::mem::take(&mut self.state.lock().overlays);
+        drop(overlays);
+    }
+
+    /// Dismisses the topmost dismissable overlay, returns whether there
+    /// was one.
+    pub fn dismiss_top(&self, ctx: &mut EventContext) -> bool {
+        let index = self
+            .state
+            .lock()
+            .overlays
+            .iter()
+            .rposition(|o| o.dismissable);
+        if let Some(index) = index {
+            self.close_from(ctx, index);
+        }
+        index.is_some()
+    }
+
+    /// Root of the keyboard focus traversal while a modal overlay is open,
+    /// the topmost one.
+    pub fn modal_root(&self) -> Option<Arc<dyn Widget>> {
+        let state = self.state.lock();
+        let overlay = state.overlays.iter().rev().find(|o| o.modal)?;
+        Some(overlay.widget.clone())
+    }
+
+    /// Lays the overlays out again, e.g. after a resize of the UI.
+    pub fn layout(&self, ui_size: UISize) {
+        let mut state = self.state.lock();
+        state.ui_size = ui_size;
+        for overlay in state.overlays.iter() {
+            overlay.place(ui_size);
+        }
+    }
+
+    /// Returns the event for the UI under the layer, `CursorExited` when
+    /// the cursor moved onto an overlay or is kept from the UI by a modal
+    /// one.
+    pub fn handle_cursor_event(
+        &self,
+        ctx: &mut EventContext,
+        event: UICursorEvent,
+    ) -> Option<UICursorEvent> {
+        let position = match event {
+            UICursorEvent::CursorEntered => return Some(event),
+            UICursorEvent::CursorExited => {
+                let hovered = {
+                    let mut state = self.state.lock();
+                    state.captured = false;
+                    state.hovered.take().and_then(|id| state.widget(id))
+                };
+                if let Some(widget) = hovered {
+                    widget.handle_cursor_event(ctx, UICursorEvent::CursorExited);
+                }
+                return Some(event);
+            }
+            UICursorEvent::CursorMoved(position) => position,
+        };
+
+        let (previous, hit, captured, was_captured) = {
+            let mut state = self.state.lock();
+            let mut hit = None;
+            let mut blocked = false;
+            for overlay in state.overlays.iter().rev() {
+                if overlay.widget.get_bounds().contains(position) {
+                    hit = Some(overlay.widget.clone());
+                    break;
+                }
+                if overlay.modal {
+                    blocked = true;
+                    break;
+                }
+            }
+            let previous = state.hovered.and_then(|id| state.widget(id));
+            state.hovered = hit.as_ref().map(|w| w.id());
+            let captured = hit.is_some() || blocked;
+            let was_captured = std::mem::replace(&mut state.captured, captured);
+            (previous, hit, captured, was_captured)
+        };
+
+        let hit_id = hit.as_ref().map(|w| w.id());
+        let entered = previous.as_ref().map(|w| w.id()) != hit_id;
+        if let Some(previous) = previous.filter(|_| entered) {
+            previous.handle_cursor_event(ctx, UICursorEvent::CursorExited);
+        }
+        if let Some(hit) = hit {
+            if entered {
+                hit.clone()
+                    .handle_cursor_event(ctx, UICursorEvent::CursorEntered);
+            }
+            let bounds = hit.get_bounds();
+            hit.handle_cursor_event(
+                ctx,
+                UICursorEvent::CursorMoved(UIPos::new(
+                    position.x - bounds.pos.x,
+                    position.y - bounds.pos.y,
+                )),
+            );
+        }
+
+        match (captured, was_captured) {
+            (false, _) => Some(event),
+            (true, false) => Some(UICursorEvent::CursorExited),
+            (true, true) => None,
+        }
+    }
+
+    /// Presses outside of the overlays dismiss them, hover events go to
+    /// the overlay under the cursor and are consumed by it. Returns the
+    /// event for the UI under the layer, unless a modal overlay is open.
+    pub fn handle_propagating_event(
+        &self,
+        ctx: &mut EventContext,
+        event: UIPropagatingEvent,
+    ) -> Option<UIPropagatingEvent> {
+        if !event.only_propagate_hover() {
+            let widgets = self
+                .state
+                .lock()
+                .overlays
+                .iter()
+                .map(|o| o.widget.clone())
+                .collect::<Vec<_>>();
+            for widget in widgets {
+                widget.handle_propagating_event(ctx, event.clone());
+            }
+            return Some(event);
+        }
+
+        if let UIPropagatingEvent::MouseInput {
+            state: ElementState::Pressed,
+            ..
+        } = event
+        {
+            self.dismiss_outside(ctx);
+        }
+
+        let (hit, modal) = {
+            let state = self.state.lock();
+            let hit = state.hovered.and_then(|id| state.widget(id));
+            (hit, state.has_modal())
+        };
+        if let Some(hit) = hit {
+            hit.handle_propagating_event(ctx, event);
+            return None;
+        }
+        (!modal).then_some(event)
+    }
+
+    /// Escape dismisses the topmost dismissable overlay, returns whether
+    /// `input` was consumed.
+    pub fn handle_keyboard_input(&self, ctx: &mut EventContext, input: &KeyboardInput) -> bool {
+        match input {
+            KeyboardInput {
+                state: ElementState::Pressed,
+                virtual_keycode: Some(VirtualKeyCode::Escape),
+                ..
+            } => self.dismiss_top(ctx),
+            _ => false,
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut DrawContext) {
+        let state = self.state.lock();
+        for overlay in state.overlays.iter() {
+            if overlay.modal {
+                ctx.draw_rounded_rect(Vec2::ZERO, state.ui_size.into(), 0.0, BACKDROP_COLOR);
+            }
+            overlay.widget.draw(ctx);
+        }
+    }
+
+    pub fn draw_pick(&self, ctx: &mut DrawContext) {
+        let state = self.state.lock();
+        for overlay in state.overlays.iter() {
+            overlay.widget.draw_pick(ctx);
+        }
+    }
+
+    /// Dismisses the dismissable overlays over the one under the cursor,
+    /// all of them if the cursor isn't over any.
+    fn dismiss_outside(&self, ctx: &mut EventContext) {
+        let index = {
+            let state = self.state.lock();
+            let start = state
+                .hovered
+                .and_then(|id| state.position(id))
+                .map(|i| i + 1)
+                .unwrap_or(0);
+            (start..state.overlays.len()).find(|&i| state.overlays[i].dismissable)
+        };
+        if let Some(index) = index {
+            self.close_from(ctx, index);
+        }
+    }
+
+    /// Closes the overlays from `index` up, topmost first, releasing the
+    /// hover and the focus they held.
+    fn close_from(&self, ctx: &mut EventContext, index: usize) {
+        let (closed, hovered) = {
+            let mut state = self.state.lock();
+            let closed = state.overlays.split_off(index);
+            let hovered = state
+                .hovered
+                .filter(|&id| closed.iter().any(|o| o.widget.id() == id));
+            if hovered.is_some() {
+                state.hovered = None;
+            }
+            (closed, hovered)
+        };
+
+        for overlay in closed.into_iter().rev() {
+            let widget = overlay.widget.clone();
+            if hovered == Some(widget.id()) {
+                widget
+                    .clone()
+                    .handle_cursor_event(ctx, UICursorEvent::CursorExited);
+            }
+            let ids = subtree_ids(&widget);
+            ctx.main_ctx
+                .release_focus_if(|focused| ids.contains(&focused));
+            if let Some(on_close) = overlay.on_close.as_ref() {
+                on_close(ctx);
+            }
+        }
+    }
+}
@@ -114,7 +114,7 @@ fn equals_2d(lhs: (f32, f32), rhs: (f32, f32)) -> bool {
     dx * dx + dy * dy <= EPSILON
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct UIRect {
     pub pos: UIPos,
     pub size: UISize,
@@ -132,4 +132,15 @@ impl UIRect {
             && self.pos.y <= pos.y
             && pos.y <= self.pos.y + self.size.height
     }
+
+    /// Empty (at the position of `self`) if the rectangles don't overlap.
+    pub fn intersection(&self, other: &UIRect) -> UIRect {
+        let min = Vec2::from(self.pos).max(other.pos.into());
+        let max = (Vec2::from(self.pos) + Vec2::from(self.size))
+            .min(Vec2::from(other.pos) + Vec2::from(other.size));
+        if min.x > max.x || min.y > max.y {
+            return UIRect::new(self.pos, UISize::ZERO);
+        }
+        UIRect::new(min.into(), (max - min).into())
+    }
 }
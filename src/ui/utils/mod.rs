@@ -1,2 +1,3 @@
 pub mod geom;
 pub mod helpers;
+pub mod snapshot;
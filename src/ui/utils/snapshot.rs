@@ -0,0 +1,76 @@
+use std::fmt::Write;
+
+use crate::ui::Widget;
+
+/// Indented dump of the widget tree rooted at `root` with the bounds of
+/// every widget (relative to its parent), one widget per line.
+pub fn widget_tree_snapshot(root: &dyn Widget) -> String {
+    let mut out = String::new();
+    write_widget(&mut out, root, 0, true);
+    out
+}
+
+/// Same as `widget_tree_snapshot` without the widget ids, which change
+/// between runs, for snapshots stored on disk.
+pub fn bounds_tree_snapshot(root: &dyn Widget) -> String {
+    let mut out = String::new();
+    write_widget(&mut out, root, 0, false);
+    out
+}
+
+fn write_widget(out: &mut String, widget: &dyn Widget, depth: usize, with_id: bool) {
+    let bounds = widget.get_bounds();
+    let _ = write!(
+        out,
+        "{:indent$}{}",
+        "",
+        widget.debug_name(),
+        indent = depth * 2
+    );
+    if with_id {
+        let _ = write!(out, " ({:?})", widget.id());
+    }
+    let _ = writeln!(
+        out,
+        " pos=({}, {}) size=({}, {})",
+        bounds.pos.x, bounds.pos.y, bounds.size.width, bounds.size.height,
+    );
+    for child in widget.debug_children() {
+        write_widget(out, child.as_ref(), depth + 1, with_id);
+    }
+}
+
+/// `type_name` without the module paths of the type and of its generic
+/// arguments, e.g. `LinearBox<AxisY>`.
+pub fn short_type_name(type_name: &str) -> String {
+    let mut out = String::with_capacity(type_name.len());
+    let mut path_start = 0;
+    for (i, c) in type_name.char_indices() {
+        if !(c.is_alphanumeric() || c == '_' || c == ':') {
+            out.push_str(last_segment(&type_name[path_start..i]));
+            out.push(c);
+            path_start = i + c.len_utf8();
+        }
+    }
+    out.push_str(last_segment(&type_name[path_start..]));
+    out
+}
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+#[test]
+fn test() {
+    assert_eq!(
+        short_type_name(
+            "game_arch_test::ui::containers::linear_box::LinearBox<game_arch_test::ui::AxisY>"
+        ),
+        "LinearBox<AxisY>"
+    );
+    assert_eq!(
+        short_type_name("(alloc::sync::Arc<dyn game::ui::Widget>, [u8; 4])"),
+        "(Arc<dyn Widget>, [u8; 4])"
+    );
+    assert_eq!(short_type_name("Stack"), "Stack");
+}
@@ -0,0 +1,182 @@
+use std::fmt::Debug;
+
+use anyhow::bail;
+
+/// A set of logical CPU core indices (up to 64 cores), used to pin threads
+/// to specific cores.
+#[derive(Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub struct CoreSet(u64);
+
+impl CoreSet {
+    pub const MAX_CORES: usize = u64::BITS as usize;
+
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn single(core: usize) -> Self {
+        Self::new().with(core)
+    }
+
+    /// all cores reported by the OS
+    pub fn all() -> Self {
+        let num_cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        (0..num_cores.min(Self::MAX_CORES)).collect()
+    }
+
+    pub fn with(mut self, core: usize) -> Self {
+        self.insert(core);
+        self
+    }
+
+    pub fn insert(&mut self, core: usize) {
+        assert!(core < Self::MAX_CORES, "core index {core} out of range");
+        self.0 |= 1 << core;
+    }
+
+    pub fn remove(&mut self, core: usize) {
+        if core < Self::MAX_CORES {
+            self.0 &= !(1 << core);
+        }
+    }
+
+    pub fn contains(&self, core: usize) -> bool {
+        core < Self::MAX_CORES && self.0 & (1 << core) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as _
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let mask = self.0;
+        (0..Self::MAX_CORES).filter(move |core| mask & (1 << core) != 0)
+    }
+}
+
+impl FromIterator<usize> for CoreSet {
+    fn from_iter<T: IntoIterator<Item = usize>>(iter: T) -> Self {
+        let mut set = Self::new();
+        iter.into_iter().for_each(|core| set.insert(core));
+        set
+    }
+}
+
+impl Debug for CoreSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// Pins the calling thread to `cores`.
+#[cfg(target_os = "linux")]
+pub fn set_current_thread_affinity(cores: &CoreSet) -> anyhow::Result<()> {
+    if cores.is_empty() {
+        bail!("unable to pin thread to an empty core set");
+    }
+
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for core in cores.iter() {
+            libc::CPU_SET(core, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            bail!(
+                "sched_setaffinity failed: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Pins the calling thread to `cores`.
+///
+/// Only single-core sets are supported on this platform.
+#[cfg(not(target_os = "linux"))]
+pub fn set_current_thread_affinity(cores: &CoreSet) -> anyhow::Result<()> {
+    let mut iter = cores.iter();
+    match (iter.next(), iter.next()) {
+        (Some(id), None) => {
+            if core_affinity::set_for_current(core_affinity::CoreId { id }) {
+                Ok(())
+            } else {
+                bail!("unable to pin thread to core {id}")
+            }
+        }
+        (None, _) => bail!("unable to pin thread to an empty core set"),
+        _ => bail!(
+            "pinning a thread to multiple cores ({cores:?}) is not supported on this platform"
+        ),
+    }
+}
+
+/// Scheduling priority hint of a thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum ThreadPriority {
+    Low,
+    Normal,
+    /// usually requires elevated privileges
+    High,
+}
+
+impl ThreadPriority {
+    /// nice value used on unix-like systems
+    pub fn nice(self) -> i32 {
+        match self {
+            Self::Low => 10,
+            Self::Normal => 0,
+            Self::High => -10,
+        }
+    }
+}
+
+/// Sets the priority of the calling thread.
+#[cfg(target_os = "linux")]
+pub fn set_current_thread_priority(priority: ThreadPriority) -> anyhow::Result<()> {
+    unsafe {
+        // on Linux, nice values are per thread
+        let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        if libc::setpriority(libc::PRIO_PROCESS, tid, priority.nice()) != 0 {
+            bail!("setpriority failed: {}", std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Sets the priority of the calling thread.
+#[cfg(not(target_os = "linux"))]
+pub fn set_current_thread_priority(priority: ThreadPriority) -> anyhow::Result<()> {
+    bail!("setting thread priority ({priority:?}) is not supported on this platform")
+}
+
+/// Core the calling thread is running on, `None` if unknown.
+#[cfg(target_os = "linux")]
+pub fn current_core() -> Option<usize> {
+    usize::try_from(unsafe { libc::sched_getcpu() }).ok()
+}
+
+/// Core the calling thread is running on, `None` if unknown.
+#[cfg(not(target_os = "linux"))]
+pub fn current_core() -> Option<usize> {
+    None
+}
+
+#[test]
+fn test() {
+    let set = CoreSet::single(1).with(3).with(63);
+    assert_eq!(set.len(), 3);
+    assert!(set.contains(3));
+    assert!(!set.contains(2));
+    assert!(!set.contains(64));
+    assert_eq!(set.iter().collect::<Vec<_>>(), vec![1, 3, 63]);
+    assert_eq!([1, 3, 63].into_iter().collect::<CoreSet>(), set);
+    assert!(CoreSet::new().is_empty());
+}
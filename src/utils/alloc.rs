@@ -0,0 +1,149 @@
+use std::{
+    cell::Cell,
+    fmt::Write,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Subsystems allocations are attributed to, see `scope`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum AllocTag {
+    Other,
+    Scene,
+    Dispatch,
+    Handles,
+}
+
+pub const NUM_ALLOC_TAGS: usize = 4;
+
+/// Live allocations of a subsystem, all zeros unless the `track-alloc`
+/// feature is enabled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub allocations: usize,
+    pub bytes: usize,
+    /// allocations made since startup, including freed ones
+    pub total_allocations: usize,
+}
+
+struct Counters {
+    allocations: AtomicUsize,
+    bytes: AtomicUsize,
+    total_allocations: AtomicUsize,
+}
+
+/// Restores the previous tag of the thread when dropped.
+pub struct AllocScope(u8);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO_COUNTERS: Counters = Counters {
+    allocations: AtomicUsize::new(0),
+    bytes: AtomicUsize::new(0),
+    total_allocations: AtomicUsize::new(0),
+};
+static COUNTERS: [Counters; NUM_ALLOC_TAGS] = [ZERO_COUNTERS; NUM_ALLOC_TAGS];
+
+thread_local! {
+    // must not allocate, it is read from inside the allocator
+    static CURRENT_TAG: Cell<u8> = const { Cell::new(AllocTag::Other as u8) };
+}
+
+impl AllocTag {
+    pub const ALL: [AllocTag; NUM_ALLOC_TAGS] =
+        [Self::Other, Self::Scene, Self::Dispatch, Self::Handles];
+}
+
+pub fn enabled() -> bool {
+    cfg!(feature = "track-alloc")
+}
+
+/// Attributes the allocations made by this thread to `tag` until the
+/// returned guard is dropped. Frees are attributed to the tag of the
+/// allocation, whichever thread they happen on.
+pub fn scope(tag: AllocTag) -> AllocScope {
+    AllocScope(
+        CURRENT_TAG
+            .try_with(|current| current.replace(tag as u8))
+            .unwrap_or(AllocTag::Other as u8),
+    )
+}
+
+pub fn stats(tag: AllocTag) -> AllocStats {
+    let counters = &COUNTERS[tag as usize];
+    AllocStats {
+        allocations: counters.allocations.load(Ordering::Relaxed),
+        bytes: counters.bytes.load(Ordering::Relaxed),
+        total_allocations: counters.total_allocations.load(Ordering::Relaxed),
+    }
+}
+
+/// One line per subsystem, for logging.
+pub fn report() -> String {
+    let mut out = String::new();
+    for tag in AllocTag::ALL {
+        let stats = stats(tag);
+        let _ = writeln!(
+            out,
+            "{tag:?}: {} live allocations ({} bytes), {} in total",
+            stats.allocations, stats.bytes, stats.total_allocations
+        );
+    }
+    out
+}
+
+impl Drop for AllocScope {
+    fn drop(&mut self) {
+        let _ = CURRENT_TAG.try_with(|current| current.set(self.0));
+    }
+}
+
+#[cfg(feature = "track-alloc")]
+mod tracking {
+    use std::alloc::{GlobalAlloc, Layout, System};
+
+    use super::{AllocTag, Ordering, COUNTERS, CURRENT_TAG, NUM_ALLOC_TAGS};
+
+    /// Wraps the system allocator, every allocation is prefixed with the tag
+    /// of the thread that made it.
+    struct TrackingAllocator;
+
+    #[global_allocator]
+    static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+    // the header keeps the alignment of the allocation
+    fn with_header(layout: Layout) -> (Layout, usize) {
+        let offset = layout.align().max(std::mem::size_of::<usize>());
+        let outer = Layout::from_size_align(layout.size() + offset, layout.align())
+            .expect("allocation too large");
+        (outer, offset)
+    }
+
+    unsafe impl GlobalAlloc for TrackingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let (outer, offset) = with_header(layout);
+            let base = System.alloc(outer);
+            if base.is_null() {
+                return base;
+            }
+
+            let tag = CURRENT_TAG
+                .try_with(|current| current.get())
+                .unwrap_or(AllocTag::Other as u8);
+            base.write(tag);
+            let counters = &COUNTERS[tag as usize % NUM_ALLOC_TAGS];
+            counters.allocations.fetch_add(1, Ordering::Relaxed);
+            counters.total_allocations.fetch_add(1, Ordering::Relaxed);
+            counters.bytes.fetch_add(layout.size(), Ordering::Relaxed);
+            base.add(offset)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let (outer, offset) = with_header(layout);
+            let base = ptr.sub(offset);
+            let counters = &COUNTERS[base.read() as usize % NUM_ALLOC_TAGS];
+            counters.allocations.fetch_sub(1, Ordering::Relaxed);
+            counters.bytes.fetch_sub(layout.size(), Ordering::Relaxed);
+            System.dealloc(base, outer)
+        }
+    }
+}
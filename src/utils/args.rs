@@ -1,8 +1,15 @@
-use std::mem::MaybeUninit;
+use std::{mem::MaybeUninit, net::SocketAddr, path::PathBuf};
 
 use clap::Parser;
 use tracing::Level;
 
+use crate::{
+    display::{backend::GLBackend, surface_format::SurfaceFormat},
+    exec::runner::RunnerId,
+    graphics::present::PresentMode,
+    utils::affinity::ThreadPriority,
+};
+
 /// A Rust rhythm game architecture test
 #[derive(Parser, Debug)]
 pub struct Args {
@@ -16,12 +23,64 @@ pub struct Args {
     /// Whether or not to select OpenGL config with sRGB capabilities
     #[arg(long)]
     pub gl_disable_srgb: bool,
+    /// Color format of the window surface, falls back to a lower precision
+    /// format if the display doesn't support it
+    #[arg(long, value_enum, default_value_t = SurfaceFormat::Rgba8)]
+    pub surface_format: SurfaceFormat,
+    /// What the draw server renders into. The offscreen backends render
+    /// every frame into a hidden pbuffer, so that draw tests can run with
+    /// `--headless` on CI machines, `software` without a GPU.
+    #[arg(long, value_enum, default_value_t = GLBackend::Window)]
+    pub gl_backend: GLBackend,
+    /// Samples per pixel of the scene, 0 or 1 disables multisampling.
+    /// Overrides `graphics.msaa_samples` of the config.
+    #[arg(long)]
+    pub msaa_samples: Option<u32>,
     /// Log level, use this to turn off unnecessary log messages
     #[arg(long, default_value_t = Level::TRACE)]
     pub log_level: Level,
     /// Log file, can be relative or absolute path
     #[arg(long, default_value = "amk.log")]
     pub log_file: Option<String>,
+    /// TOML config file, ignored if it doesn't exist. Its values can be
+    /// overridden by `GAME_*` environment variables and CLI flags, and it
+    /// is reloaded when modified.
+    #[arg(long, default_value = "config.toml")]
+    pub config: PathBuf,
+    /// Initial window width in physical pixels. Overrides `window.width` of
+    /// the config.
+    #[arg(long)]
+    pub window_width: Option<u32>,
+    /// Initial window height in physical pixels. Overrides `window.height`
+    /// of the config.
+    #[arg(long)]
+    pub window_height: Option<u32>,
+    /// How frames are presented, `fifo` enables vsync.
+    /// Overrides `present_mode` of the config.
+    #[arg(long, value_enum)]
+    pub present_mode: Option<PresentMode>,
+    /// Runner the audio server starts on. Overrides `runners.audio` of the
+    /// config, likewise for the other servers.
+    #[arg(long)]
+    pub audio_runner: Option<RunnerId>,
+    #[arg(long)]
+    pub draw_runner: Option<RunnerId>,
+    #[arg(long)]
+    pub update_runner: Option<RunnerId>,
+    #[arg(long)]
+    pub network_runner: Option<RunnerId>,
+    /// Runner frequencies indexed by runner id, 0 means unthrottled, e.g.
+    /// `--runner-frequencies 1000,144`. Overrides `runners.frequencies` of
+    /// the config.
+    #[arg(long, value_delimiter = ',')]
+    pub runner_frequencies: Vec<f64>,
+    /// Writes the profiling spans (runner ticks, server runs, draw passes,
+    /// UI layout) to this file, in the chrome://tracing JSON format.
+    #[arg(long)]
+    pub profile_chrome: Option<PathBuf>,
+    /// Streams the profiling spans to Tracy, requires the `tracy` feature.
+    #[arg(long)]
+    pub profile_tracy: bool,
     /// Whether or not to block the event loop on certain events like
     /// `RedrawRequested` or `Resize`. This should be turned on or off
     /// accordingly for better performance and in order to get intended
@@ -56,7 +115,8 @@ pub struct Args {
     pub test: bool,
     /// Whether or not to hide the window. Hiding the window will also come with a
     /// side effect of disabling all rendering calls (jobs executed by
-    /// `execute_draw_event` and `execute_draw_sync` will still be executed).
+    /// `execute_draw_event` and `execute_draw_sync` will still be executed),
+    /// unless an offscreen `--gl-backend` is selected.
     #[arg(long)]
     pub headless: bool,
     /// Whether or not to automatically run all tests on program launch (if `test`
@@ -65,6 +125,93 @@ pub struct Args {
     /// is enabled in CI contexts.
     #[arg(long)]
     pub auto_run_tests: bool,
+    /// Runs the exec subsystem soak test for this many seconds in `test`
+    /// mode (servers migrating between runners, changing frequencies,
+    /// flooded channels and window resizes).
+    #[arg(long)]
+    pub soak: Option<u64>,
+    /// Fails the test run in `test` mode if an error is reported to the main
+    /// thread, even if every test passed.
+    #[arg(long)]
+    pub fail_on_error: bool,
+    /// Where failed tests dump their debugging artifacts (UI event traces,
+    /// widget tree snapshots, test logs and a screenshot), in a directory
+    /// named after the test.
+    #[arg(long, default_value = "artifacts")]
+    pub artifacts_dir: PathBuf,
+    /// Where layout snapshots are stored and compared against.
+    #[arg(long, default_value = "snapshots")]
+    pub snapshot_dir: PathBuf,
+    /// Where the reference images of the golden image tests are stored.
+    #[arg(long, default_value = "golden")]
+    pub golden_dir: PathBuf,
+    /// Only runs the tests matching one of these globs in `test` mode, along
+    /// with the tests under them. Tests are named by their dotted path in
+    /// the test logs without `root.`, e.g. `tilemap`, `ui.*.click`; `*`
+    /// matches any string and `?` any character.
+    #[arg(long, value_delimiter = ',')]
+    pub test_filter: Vec<String>,
+    /// Only runs the tests having one of these tags in `test` mode, tags
+    /// starting with `!` skip the tests having them instead, e.g. `!slow`.
+    #[arg(long, value_delimiter = ',')]
+    pub test_tag: Vec<String>,
+    /// Overwrites mismatching layout snapshots and golden images instead of
+    /// failing.
+    #[arg(long)]
+    pub update_snapshots: bool,
+    /// Where screenshots taken with F12 are written.
+    #[arg(long, default_value = "screenshots")]
+    pub screenshot_dir: PathBuf,
+    /// Replaces the `content` scene by a gallery rendering each content scene
+    /// alone, writing their screenshots and an `index.html` diffing them
+    /// against the previous run to this directory, then exits.
+    #[arg(long)]
+    pub gallery: Option<PathBuf>,
+    /// Number of frames each scene is rendered before its screenshot is
+    /// captured in `--gallery` mode.
+    #[arg(long, default_value_t = 60)]
+    pub gallery_frames: u32,
+    /// Writes a report of the monitors, OpenGL configs, OpenGL context
+    /// versions and audio devices available to this file, then exits. Use
+    /// this when the main display can't be created.
+    #[arg(long)]
+    pub diagnose: Option<PathBuf>,
+    /// Whether or not to automatically migrate servers off overloaded
+    /// runners onto idle ones.
+    #[arg(long)]
+    pub auto_balance: bool,
+    /// Pins every runner to its own core, runner `i` to core `i` modulo the
+    /// number of cores (the main runner has id 3).
+    #[arg(long)]
+    pub pin_runners: bool,
+    /// Scheduling priority hint of every runner thread.
+    #[arg(long, value_enum)]
+    pub runner_priority: Option<ThreadPriority>,
+    /// Enables the deterministic lockstep mode: the update server ticks this
+    /// many times per rendered frame, using a virtual clock advancing by a
+    /// fixed step every tick.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub lockstep: Option<u32>,
+    /// Seed of the update server RNG, a random seed is used (and logged) if
+    /// not provided.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Records the window input events of the session to this file, along
+    /// with the frame they were received on. Record with `--lockstep` for a
+    /// deterministic replay.
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+    /// Replays a session recorded with `--record`, real input is ignored
+    /// meanwhile. Lockstep mode is enabled with the recorded ticks per frame
+    /// if `--lockstep` isn't given.
+    #[arg(long, conflicts_with = "record")]
+    pub replay: Option<PathBuf>,
+    /// Address the network server accepts connections on (TCP and UDP).
+    #[arg(long)]
+    pub listen: Option<SocketAddr>,
+    /// Address of a network server to connect to on launch.
+    #[arg(long)]
+    pub connect: Option<SocketAddr>,
 }
 
 static mut STATIC_ARGS: MaybeUninit<Args> = MaybeUninit::uninit();
@@ -1,4 +1,10 @@
-use std::time::{Instant, SystemTime};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
 pub trait Clock {
     fn now(&self) -> f64;
@@ -6,8 +12,14 @@ pub trait Clock {
     fn ellapsed(&self, since: f64) -> f64 {
         self.now() - since
     }
+
+    /// Waits until `duration` has passed on this clock.
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
 }
 
+#[derive(Clone, Copy)]
 pub struct SteadyClock {
     start: Instant,
 }
@@ -34,6 +46,68 @@ impl Clock for SteadyClock {
     }
 }
 
+/// A clock that only moves when told to, clones share the same time.
+#[derive(Clone, Default)]
+pub struct VirtualClock {
+    // bits of a f64
+    time: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, time: f64) {
+        self.time.store(time.to_bits(), Ordering::Release);
+    }
+
+    pub fn advance(&self, seconds: f64) {
+        self.set(self.now() + seconds);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> f64 {
+        f64::from_bits(self.time.load(Ordering::Acquire))
+    }
+
+    /// Doesn't wait at all, the clock is moved forward instead.
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration.as_secs_f64());
+    }
+}
+
+/// Clock of a runner, virtual clocks let tests drive runners without
+/// waiting on the wall clock.
+#[derive(Clone)]
+pub enum GameClock {
+    Steady(SteadyClock),
+    Virtual(VirtualClock),
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        Self::Steady(SteadyClock::new())
+    }
+}
+
+impl Clock for GameClock {
+    fn now(&self) -> f64 {
+        match self {
+            Self::Steady(clock) => clock.now(),
+            Self::Virtual(clock) => clock.now(),
+        }
+    }
+
+    fn sleep(&self, duration: Duration) {
+        match self {
+            Self::Steady(clock) => clock.sleep(duration),
+            Self::Virtual(clock) => clock.sleep(duration),
+        }
+    }
+}
+
 pub fn debug_get_time() -> f64 {
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -0,0 +1,343 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use toml::{value::Table, Value};
+
+use crate::{
+    display::platform::PowerState, exec::runner::RunnerId, graphics::present::PresentMode,
+};
+
+use super::args::{args, Args};
+
+/// Prefix of the environment variables overriding the config file, nested
+/// keys are separated by `__`, e.g. `GAME_WINDOW__WIDTH=1920`.
+pub const ENV_PREFIX: &str = "GAME_";
+
+/// Settings merged from, by increasing priority: the defaults, the TOML file
+/// given by `--config`, `GAME_*` environment variables and CLI flags.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub present_mode: PresentMode,
+    pub graphics: GraphicsConfig,
+    pub runners: RunnersConfig,
+    pub test: TestConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsConfig {
+    /// samples per pixel of the scene, 0 or 1 disables multisampling
+    pub msaa_samples: u32,
+}
+
+/// Runner each server starts on and runner frequencies.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunnersConfig {
+    pub audio: RunnerId,
+    pub draw: RunnerId,
+    pub update: RunnerId,
+    pub network: RunnerId,
+    /// indexed by runner id, 0 or missing means unthrottled
+    pub frequencies: Vec<f64>,
+    /// caps every runner frequency while running on battery, 0 disables
+    /// the cap
+    pub battery_frequency: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestConfig {
+    /// seconds before the test run is considered timed out, the soak
+    /// duration is added to it
+    pub timeout: u64,
+    /// seconds before a test times out, unless the test sets its own
+    pub leaf_timeout: u64,
+    /// test groups running at once, exclusive groups always run alone
+    pub parallel_groups: usize,
+    pub fail_on_error: bool,
+}
+
+/// Published on the `EventBus` when the config file changed and the merged
+/// config is different.
+#[derive(Clone, Debug)]
+pub struct ConfigChanged {
+    pub old: Arc<Config>,
+    pub new: Arc<Config>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            title: "hello".to_owned(),
+        }
+    }
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        Self { msaa_samples: 4 }
+    }
+}
+
+impl Default for RunnersConfig {
+    fn default() -> Self {
+        Self {
+            audio: 0,
+            draw: 1,
+            update: 0,
+            network: 0,
+            frequencies: vec![1000.0],
+            battery_frequency: 0.0,
+        }
+    }
+}
+
+impl RunnersConfig {
+    pub fn frequency(&self, id: RunnerId) -> f64 {
+        self.frequencies
+            .get(usize::from(id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Frequency of the runner `id` once capped according to `power`.
+    pub fn governed_frequency(&self, id: RunnerId, power: &PowerState) -> f64 {
+        let frequency = self.frequency(id);
+        if !power.on_battery() || self.battery_frequency <= 0.0 {
+            frequency
+        } else if frequency <= 0.0 {
+            self.battery_frequency
+        } else {
+            frequency.min(self.battery_frequency)
+        }
+    }
+}
+
+impl Default for TestConfig {
+    fn default() -> Self {
+        Self {
+            timeout: 60,
+            leaf_timeout: 20,
+            parallel_groups: 4,
+            fail_on_error: false,
+        }
+    }
+}
+
+impl Config {
+    /// Merges every layer, the config file is optional.
+    pub fn load() -> anyhow::Result<Self> {
+        let mut config = Self::merge(
+            Some(&args().config),
+            std::env::vars().filter(|(key, _)| key.starts_with(ENV_PREFIX)),
+        )?;
+        config.apply_args(args());
+        Ok(config)
+    }
+
+    fn merge(
+        path: Option<&Path>,
+        env: impl Iterator<Item = (String, String)>,
+    ) -> anyhow::Result<Self> {
+        let mut value = Value::try_from(Self::default()).context("unable to serialize defaults")?;
+        if let Some(path) = path.filter(|path| path.exists()) {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("unable to read config file {}", path.display()))?;
+            let file: Value = toml::from_str(&content)
+                .with_context(|| format!("unable to parse config file {}", path.display()))?;
+            merge_value(&mut value, file);
+        }
+        for (key, raw) in env {
+            let keys: Vec<_> = key[ENV_PREFIX.len()..]
+                .split("__")
+                .map(|key| key.to_lowercase())
+                .collect();
+            merge_value(&mut value, env_value(&keys, &raw));
+        }
+        value.try_into().context("invalid config")
+    }
+
+    /// CLI flags take precedence over everything else.
+    fn apply_args(&mut self, args: &Args) {
+        let overrides = [
+            (&mut self.window.width, args.window_width),
+            (&mut self.window.height, args.window_height),
+            (&mut self.graphics.msaa_samples, args.msaa_samples),
+        ];
+        for (value, arg) in overrides {
+            if let Some(arg) = arg {
+                *value = arg;
+            }
+        }
+        if let Some(present_mode) = args.present_mode {
+            self.present_mode = present_mode;
+        }
+        let runners = [
+            (&mut self.runners.audio, args.audio_runner),
+            (&mut self.runners.draw, args.draw_runner),
+            (&mut self.runners.update, args.update_runner),
+            (&mut self.runners.network, args.network_runner),
+        ];
+        for (runner, arg) in runners {
+            if let Some(arg) = arg {
+                *runner = arg;
+            }
+        }
+        if !args.runner_frequencies.is_empty() {
+            self.runners.frequencies = args.runner_frequencies.clone();
+        }
+        self.test.fail_on_error |= args.fail_on_error;
+    }
+}
+
+/// Overwrites the leaves of `base` present in `layer`, recursing in tables.
+fn merge_value(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Table(base), Value::Table(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_value(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+/// Nested table holding `raw` at `keys`, parsed as a TOML value if possible
+/// and kept as a string otherwise.
+fn env_value(keys: &[String], raw: &str) -> Value {
+    let value = toml::from_str::<Table>(&format!("value = {raw}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_owned()));
+    keys.iter().rev().fold(value, |value, key| {
+        let mut table = Table::new();
+        table.insert(key.clone(), value);
+        Value::Table(table)
+    })
+}
+
+/// Watches the config file for changes.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new() -> Self {
+        let path = args().config.clone();
+        Self {
+            modified: modified_time(&path),
+            path,
+        }
+    }
+
+    /// Reloads every layer if the file was modified since the last call,
+    /// `None` if it wasn't.
+    pub fn poll(&mut self) -> Option<anyhow::Result<Config>> {
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(Config::load())
+    }
+}
+
+impl Default for ConfigWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[test]
+fn test() {
+    use clap::Parser;
+
+    let path = std::env::temp_dir().join(format!("config-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        "present_mode = \"immediate\"\n[window]\nwidth = 800\n[runners]\nfrequencies = [500.0]\n",
+    )
+    .unwrap();
+    let env = [
+        ("GAME_WINDOW__WIDTH", "1024"),
+        ("GAME_WINDOW__TITLE", "env title"),
+        ("GAME_TEST__FAIL_ON_ERROR", "true"),
+        ("GAME_GRAPHICS__MSAA_SAMPLES", "8"),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_owned(), value.to_owned()));
+    let config = Config::merge(Some(&path), env).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(config.window.width, 1024);
+    assert_eq!(config.window.height, 720);
+    assert_eq!(config.window.title, "env title");
+    assert_eq!(config.present_mode, PresentMode::Immediate);
+    assert_eq!(config.runners.frequency(0), 500.0);
+    assert_eq!(config.runners.frequency(1), 0.0);
+    assert_eq!(config.runners.draw, 1);
+    assert!(config.test.fail_on_error);
+    assert_eq!(config.graphics.msaa_samples, 8);
+
+    let battery = PowerState {
+        source: crate::display::platform::PowerSource::Battery,
+        battery: Some(0.5),
+    };
+    let mut runners = config.runners.clone();
+    assert_eq!(runners.governed_frequency(0, &battery), 500.0);
+    runners.battery_frequency = 60.0;
+    assert_eq!(runners.governed_frequency(0, &battery), 60.0);
+    assert_eq!(runners.governed_frequency(1, &battery), 60.0);
+    assert_eq!(runners.governed_frequency(0, &PowerState::default()), 500.0);
+
+    let mut config = Config::merge(None, std::iter::empty()).unwrap();
+    assert_eq!(config, Config::default());
+
+    let args = Args::parse_from([
+        "game",
+        "--window-width",
+        "640",
+        "--present-mode",
+        "fifo",
+        "--draw-runner",
+        "2",
+        "--runner-frequencies",
+        "1000,144",
+    ]);
+    config.apply_args(&args);
+    assert_eq!(config.window.width, 640);
+    assert_eq!(config.window.height, 720);
+    assert_eq!(config.present_mode, PresentMode::Fifo);
+    assert_eq!(config.runners.draw, 2);
+    assert_eq!(config.runners.update, 0);
+    assert_eq!(config.runners.frequencies, [1000.0, 144.0]);
+    assert!(Args::try_parse_from(["game", "--lockstep", "0"]).is_err());
+}
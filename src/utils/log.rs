@@ -1,30 +1,102 @@
 use std::io;
 
 use anyhow::Context;
-use tracing::subscriber::set_global_default;
+use tracing::{subscriber::set_global_default, Subscriber};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_log::LogTracer;
 use tracing_subscriber::{
+    filter::filter_fn,
     fmt::{self},
     prelude::__tracing_subscriber_SubscriberExt,
-    EnvFilter,
+    registry::LookupSpan,
+    EnvFilter, Layer,
 };
 
-use crate::utils::args::args;
+use crate::utils::{
+    args::args,
+    profile::{ChromeTraceGuard, ChromeTraceLayer, PROFILE_TARGET},
+};
+
+/// Must be kept alive until the program exits, flushes the log file and the
+/// chrome trace when dropped.
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+    _chrome_trace: Option<ChromeTraceGuard>,
+}
+
+pub fn init_log() -> anyhow::Result<LogGuard> {
+    // profile spans are only seen by the profiling layers, not logged
+    let log_filter = || {
+        EnvFilter::from_default_env()
+            .add_directive(args().log_level.into())
+            .add_directive(
+                format!("{PROFILE_TARGET}=off")
+                    .parse()
+                    .expect("invalid profile filter directive"),
+            )
+    };
+    let profile_filter = || filter_fn(|metadata| metadata.target() == PROFILE_TARGET);
+
+    let (file_layer, file_guard) = match args().log_file.as_ref() {
+        Some(log_file) => {
+            let appender = tracing_appender::rolling::never(".", log_file);
+            let (nonblocking, guard) = tracing_appender::non_blocking(appender);
+            let layer = fmt::Layer::new()
+                .with_ansi(false)
+                .with_writer(nonblocking)
+                .with_filter(log_filter());
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let (chrome_layer, chrome_guard) = match args().profile_chrome.as_ref() {
+        Some(path) => {
+            let (layer, guard) = ChromeTraceLayer::new(path)?;
+            (Some(layer.with_filter(profile_filter())), Some(guard))
+        }
+        None => (None, None),
+    };
 
-pub fn init_log() -> anyhow::Result<Option<WorkerGuard>> {
     let collector = tracing_subscriber::registry()
-        .with(EnvFilter::from_default_env().add_directive(args().log_level.into()))
-        .with(fmt::Layer::new().with_writer(io::stdout));
+        .with(
+            fmt::Layer::new()
+                .with_writer(io::stdout)
+                .with_filter(log_filter()),
+        )
+        .with(file_layer)
+        .with(chrome_layer)
+        .with(tracy_layer().map(|layer| layer.with_filter(profile_filter())));
 
     LogTracer::init()?;
-    if let Some(log_file) = args().log_file.as_ref() {
-        let appender = tracing_appender::rolling::never(".", log_file);
-        let (nonblocking, guard) = tracing_appender::non_blocking(appender);
-        let collector = collector.with(fmt::Layer::new().with_ansi(false).with_writer(nonblocking));
-        set_global_default(collector).map(|_| Some(guard))
-    } else {
-        set_global_default(collector).map(|_| None)
+    set_global_default(collector).context("unable to set global logger")?;
+    if let Some(path) = args().profile_chrome.as_ref() {
+        tracing::info!("writing chrome trace to {}", path.display());
     }
-    .context("unable to set global logger")
+    if args().profile_tracy && !cfg!(feature = "tracy") {
+        tracing::warn!(
+            "--profile-tracy ignored, the program was built without the `tracy` feature"
+        );
+    }
+    Ok(LogGuard {
+        _file: file_guard,
+        _chrome_trace: chrome_guard,
+    })
+}
+
+#[cfg(feature = "tracy")]
+fn tracy_layer<S>() -> Option<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    args().profile_tracy.then(tracing_tracy::TracyLayer::new)
+}
+
+#[cfg(not(feature = "tracy"))]
+fn tracy_layer<S>() -> Option<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    // warned about once the logger is set up
+    None::<tracing_subscriber::layer::Identity>
 }
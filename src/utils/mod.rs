@@ -1,7 +1,10 @@
 use std::time::Duration;
 
+pub mod affinity;
+pub mod alloc;
 pub mod args;
 pub mod clock;
+pub mod config;
 pub mod debug_handle;
 pub mod enclose;
 pub mod error;
@@ -10,9 +13,15 @@ pub mod has_metric;
 pub mod log;
 pub mod mpsc;
 pub mod mutex;
+pub mod profile;
+pub mod property;
+pub mod rng;
 pub mod send_sync;
+pub mod startup;
+pub mod state_machine;
 pub mod sync;
 pub mod uid;
+pub mod undo;
 
 // one year, basically Duration::MAX without the overflowing
 pub const ONE_YEAR: Duration = Duration::from_secs(31556926);
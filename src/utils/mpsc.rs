@@ -1,26 +1,68 @@
-use std::time::Duration;
+use std::{fmt, time::Duration};
 
-use flume::TryRecvError;
+use flume::{RecvTimeoutError, SendTimeoutError, TryRecvError, TrySendError};
+
+/// How long a blocking sender waits for room before giving up, so that a
+/// receiver that stopped draining can't deadlock the sender forever.
+pub const BLOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What `Sender::send` does when a bounded channel is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backpressure {
+    /// wait for room, up to `BLOCK_TIMEOUT`
+    Block,
+    /// make room by discarding the oldest queued message, the sender keeps
+    /// the channel alive so it can't tell whether the receiver was dropped
+    DropOldest,
+    /// fail right away
+    Error,
+}
+
+/// Error of sending or receiving on a channel whose other side was dropped,
+/// e.g. a server that shut down.
+#[derive(Debug)]
+pub struct Disconnected;
+
+impl fmt::Display for Disconnected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel disconnected")
+    }
+}
+
+impl std::error::Error for Disconnected {}
+
+/// Whether `error` was caused by a disconnected channel, whatever context
+/// was added to it.
+pub fn is_disconnected_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|e| e.is::<Disconnected>())
+}
 
 pub struct Receiver<T>(flume::Receiver<T>);
-pub struct Sender<T>(flume::Sender<T>);
+pub struct Sender<T> {
+    inner: flume::Sender<T>,
+    policy: Backpressure,
+    /// only kept by `DropOldest` senders, to pop the oldest message
+    receiver: Option<flume::Receiver<T>>,
+}
 
 impl<T> Receiver<T> {
     pub fn recv(&self) -> anyhow::Result<T> {
-        Ok(self.0.recv()?)
+        Ok(self.0.recv().map_err(|_| Disconnected)?)
     }
 
     pub fn recv_timeout(&self, timeout: Duration) -> anyhow::Result<Option<T>> {
         match self.0.recv_timeout(timeout) {
-            Err(flume::RecvTimeoutError::Timeout) => Ok(None),
-            r => Ok(r.map(Some)?),
+            Ok(msg) => Ok(Some(msg)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(Disconnected.into()),
         }
     }
 
     pub fn try_recv(&self) -> anyhow::Result<Option<T>> {
         match self.0.try_recv() {
+            Ok(msg) => Ok(Some(msg)),
             Err(TryRecvError::Empty) => Ok(None),
-            r => Ok(r.map(Some)?),
+            Err(TryRecvError::Disconnected) => Err(Disconnected.into()),
         }
     }
 
@@ -38,23 +80,142 @@ impl<T> Receiver<T> {
     pub fn is_disconnected(&self) -> bool {
         self.0.is_disconnected()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl<T> Sender<T> {
     pub fn send(&self, msg: T) -> anyhow::Result<()> {
-        self.0
-            .send(msg)
-            .map_err(|_| anyhow::Error::msg("mpsc::SendError(...)"))
+        match self.policy {
+            Backpressure::Block => {
+                self.inner
+                    .send_timeout(msg, BLOCK_TIMEOUT)
+                    .map_err(|e| match e {
+                        SendTimeoutError::Timeout(_) => anyhow::format_err!(
+                            "mpsc channel full for {BLOCK_TIMEOUT:?} ({} messages)",
+                            self.inner.len()
+                        ),
+                        SendTimeoutError::Disconnected(_) => Disconnected.into(),
+                    })
+            }
+
+            Backpressure::DropOldest => {
+                let mut msg = msg;
+                loop {
+                    match self.inner.try_send(msg) {
+                        Ok(()) => return Ok(()),
+                        Err(TrySendError::Full(rejected)) => {
+                            msg = rejected;
+                            if let Some(receiver) = self.receiver.as_ref() {
+                                // another receiver may have made room already
+                                let _ = receiver.try_recv();
+                            }
+                        }
+                        Err(TrySendError::Disconnected(_)) => return Err(Disconnected.into()),
+                    }
+                }
+            }
+
+            Backpressure::Error => self.inner.try_send(msg).map_err(|e| match e {
+                TrySendError::Full(_) => {
+                    anyhow::format_err!("mpsc channel full ({} messages)", self.inner.len())
+                }
+                TrySendError::Disconnected(_) => Disconnected.into(),
+            }),
+        }
+    }
+
+    /// Sends `msg` only if there is room right away, whatever the policy,
+    /// handing it back otherwise so that it can be sent again later.
+    pub fn try_send(&self, msg: T) -> anyhow::Result<Option<T>> {
+        match self.inner.try_send(msg) {
+            Ok(()) => Ok(None),
+            Err(TrySendError::Full(msg)) => Ok(Some(msg)),
+            Err(TrySendError::Disconnected(_)) => Err(Disconnected.into()),
+        }
+    }
+
+    /// Messages waiting to be received.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
     }
 }
 
 impl<T> Clone for Sender<T> {
     fn clone(&self) -> Self {
-        Self(self.0.clone())
+        Self {
+            inner: self.inner.clone(),
+            policy: self.policy,
+            receiver: self.receiver.clone(),
+        }
     }
 }
 
+/// Unbounded channel, only for channels whose volume is bounded by their
+/// usage (one-shot replies, handshakes).
 pub fn channels<T>() -> (Sender<T>, Receiver<T>) {
     let (sender, receiver) = flume::unbounded();
-    (Sender(sender), Receiver(receiver))
+    (
+        Sender {
+            inner: sender,
+            policy: Backpressure::Block,
+            receiver: None,
+        },
+        Receiver(receiver),
+    )
+}
+
+/// Channel holding at most `capacity` messages, `policy` decides what
+/// happens to messages sent while it is full.
+pub fn bounded_channels<T>(capacity: usize, policy: Backpressure) -> (Sender<T>, Receiver<T>) {
+    let (sender, receiver) = flume::bounded(capacity);
+    (
+        Sender {
+            inner: sender,
+            policy,
+            receiver: (policy == Backpressure::DropOldest).then(|| receiver.clone()),
+        },
+        Receiver(receiver),
+    )
+}
+
+#[test]
+fn test() {
+    let (sender, receiver) = bounded_channels(2, Backpressure::DropOldest);
+    for i in 0..4 {
+        sender.send(i).unwrap();
+    }
+    assert_eq!(receiver.try_iter(None).unwrap().collect::<Vec<_>>(), [2, 3]);
+
+    let (sender, receiver) = bounded_channels(2, Backpressure::Error);
+    sender.send(0).unwrap();
+    sender.send(1).unwrap();
+    assert!(sender.send(2).is_err());
+    assert_eq!(receiver.try_recv().unwrap(), Some(0));
+    sender.send(2).unwrap();
+    assert_eq!(sender.len(), 2);
+
+    let (blocking, blocked) = bounded_channels(1, Backpressure::Block);
+    assert_eq!(blocking.try_send(0).unwrap(), None);
+    assert_eq!(blocking.try_send(1).unwrap(), Some(1));
+    assert_eq!(blocked.try_recv().unwrap(), Some(0));
+    drop(blocked);
+    assert!(is_disconnected_error(&blocking.try_send(2).unwrap_err()));
+
+    drop(receiver);
+    let error = sender
+        .send(3)
+        .unwrap_err()
+        .context("sending to a dropped receiver");
+    assert!(is_disconnected_error(&error));
+    let (sender, receiver) = channels::<()>();
+    drop(sender);
+    assert!(is_disconnected_error(&receiver.recv().unwrap_err()));
+    assert!(!is_disconnected_error(&anyhow::format_err!("unrelated")));
 }
@@ -0,0 +1,223 @@
+use std::{
+    cell::Cell,
+    fmt::Write as _,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use anyhow::Context;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context as LayerContext, registry::LookupSpan, Layer};
+
+use super::{error::ResultExt, mutex::Mutex};
+
+/// Target of the spans created by `profile_span!`, only those are exported.
+pub const PROFILE_TARGET: &str = "profile";
+
+/// Span covering a piece of frame work, exported to the chrome trace and
+/// Tracy if enabled. Returns a `tracing::Span` to enter.
+#[macro_export]
+macro_rules! profile_span {
+    ($name:literal) => {
+        tracing::trace_span!(target: "profile", $name)
+    };
+}
+
+pub use profile_span;
+
+/// Sample of a counter, exported as a counter track to the chrome trace.
+#[macro_export]
+macro_rules! profile_counter {
+    ($name:expr, $value:expr) => {
+        tracing::trace!(target: "profile", counter = $name, counter_value = $value)
+    };
+}
+
+pub use profile_counter;
+
+/// Writes spans in the chrome://tracing (and Perfetto) JSON format, as
+/// complete events, one per span entry.
+pub struct ChromeTraceLayer {
+    writer: Arc<Mutex<BufWriter<File>>>,
+    start: Instant,
+}
+
+/// Flushes the chrome trace when dropped.
+pub struct ChromeTraceGuard {
+    writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+/// Time the current entry of a span started at, stored in its extensions.
+struct EnteredAt(Instant);
+
+/// Fields of a `profile_counter!` event.
+#[derive(Default)]
+struct CounterVisitor {
+    name: Option<String>,
+    value: Option<f64>,
+}
+
+impl Visit for CounterVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "counter_value" {
+            self.value = Some(value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "counter" {
+            self.name = Some(value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+}
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: Cell<u64> = Cell::new(0);
+}
+
+impl ChromeTraceLayer {
+    pub fn new(path: &Path) -> anyhow::Result<(Self, ChromeTraceGuard)> {
+        let file = File::create(path)
+            .with_context(|| format!("unable to create chrome trace file {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        // the closing bracket is optional in the array format, which lets
+        // the trace be streamed and survive crashes
+        writer.write_all(b"[\n")?;
+        let writer = Arc::new(Mutex::new(writer));
+        Ok((
+            Self {
+                writer: writer.clone(),
+                start: Instant::now(),
+            },
+            ChromeTraceGuard { writer },
+        ))
+    }
+
+    fn write_event(&self, event: &str) {
+        let mut writer = self.writer.lock();
+        writer
+            .write_all(event.as_bytes())
+            .and_then(|_| writer.write_all(b",\n"))
+            .context("unable to write chrome trace event")
+            .log_warn();
+    }
+
+    /// Id of the calling thread in the trace, named after the thread the
+    /// first time it's seen.
+    fn thread_id(&self) -> u64 {
+        THREAD_ID.with(|id| {
+            if id.get() == 0 {
+                id.set(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+                let thread = std::thread::current();
+                let name = thread.name().unwrap_or("unnamed");
+                self.write_event(&format!(
+                    r#"{{"name":"thread_name","ph":"M","pid":1,"tid":{},"args":{{"name":"{}"}}}}"#,
+                    id.get(),
+                    escape(name)
+                ));
+            }
+            id.get()
+        })
+    }
+}
+
+impl<S> Layer<S> for ChromeTraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &span::Id, ctx: LayerContext<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().replace(EnteredAt(Instant::now()));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _: LayerContext<'_, S>) {
+        let mut visitor = CounterVisitor::default();
+        event.record(&mut visitor);
+        if let (Some(name), Some(value)) = (visitor.name, visitor.value) {
+            let ts = self.start.elapsed().as_secs_f64() * 1e6;
+            self.write_event(&format!(
+                r#"{{"name":"{}","ph":"C","pid":1,"ts":{ts:.3},"args":{{"value":{value}}}}}"#,
+                escape(&name)
+            ));
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: LayerContext<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        let entered_at = match span.extensions_mut().remove::<EnteredAt>() {
+            Some(EnteredAt(entered_at)) => entered_at,
+            None => return,
+        };
+        let start = entered_at.saturating_duration_since(self.start);
+        let mut event = String::new();
+        write!(
+            event,
+            r#"{{"name":"{}","cat":"{}","ph":"X","pid":1,"tid":{},"ts":{:.3},"dur":{:.3}}}"#,
+            escape(span.name()),
+            span.metadata().target(),
+            self.thread_id(),
+            start.as_secs_f64() * 1e6,
+            entered_at.elapsed().as_secs_f64() * 1e6,
+        )
+        .unwrap();
+        self.write_event(&event);
+    }
+}
+
+impl Drop for ChromeTraceGuard {
+    fn drop(&mut self) {
+        self.writer
+            .lock()
+            .flush()
+            .context("unable to flush chrome trace")
+            .log_warn();
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[test]
+fn test() {
+    use tracing_subscriber::prelude::__tracing_subscriber_SubscriberExt;
+
+    let path = std::env::temp_dir().join(format!("chrome-trace-{}.json", std::process::id()));
+    let (layer, guard) = ChromeTraceLayer::new(&path).unwrap();
+    let subscriber = tracing_subscriber::registry().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let _outer = profile_span!("outer").entered();
+        let _inner = profile_span!("inner \"quoted\"").entered();
+        profile_counter!("counter", 1.5);
+    });
+    drop(guard);
+
+    let trace = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let events: Vec<_> = trace.lines().skip(1).collect();
+    assert_eq!(events.len(), 4, "{trace}");
+    assert!(events[0].contains(r#""name":"counter","ph":"C""#));
+    assert!(events[0].contains(r#""args":{"value":1.5}"#));
+    assert!(events[1].contains(r#""name":"thread_name""#));
+    // spans are written when exited, inner first
+    assert!(events[2].contains(r#""name":"inner \"quoted\"""#));
+    assert!(events[3].contains(r#""name":"outer""#));
+    assert!(events.iter().all(|event| event.ends_with(',')));
+}
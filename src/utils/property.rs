@@ -0,0 +1,156 @@
+use std::{
+    borrow::Cow,
+    ops::RangeInclusive,
+    sync::{Arc, Weak},
+};
+
+use crate::exec::event_bus::EventBus;
+
+use super::mutex::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PropertyValue {
+    Float { value: f32, min: f32, max: f32 },
+    Bool(bool),
+}
+
+/// Published on the event bus when a property is edited.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyChanged {
+    pub name: Cow<'static, str>,
+    pub value: PropertyValue,
+}
+
+struct PropertyInner {
+    name: Cow<'static, str>,
+    value: Mutex<PropertyValue>,
+}
+
+/// A value tunable at runtime from the inspector, unregistered once every
+/// clone is dropped.
+#[derive(Clone)]
+pub struct Property(Arc<PropertyInner>);
+
+impl Property {
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    pub fn value(&self) -> PropertyValue {
+        *self.0.value.lock()
+    }
+
+    /// The value of a float property, 0 for other properties.
+    pub fn as_f32(&self) -> f32 {
+        match self.value() {
+            PropertyValue::Float { value, .. } => value,
+            PropertyValue::Bool(_) => 0.0,
+        }
+    }
+
+    /// The value of a bool property, false for other properties.
+    pub fn as_bool(&self) -> bool {
+        matches!(self.value(), PropertyValue::Bool(true))
+    }
+
+    /// Sets the value of a float property, clamped to its range.
+    pub fn set_f32(&self, new_value: f32) -> bool {
+        match &mut *self.0.value.lock() {
+            PropertyValue::Float { value, min, max } => {
+                *value = new_value.clamp(*min, *max);
+                true
+            }
+            PropertyValue::Bool(_) => false,
+        }
+    }
+
+    pub fn set_bool(&self, new_value: bool) -> bool {
+        match &mut *self.0.value.lock() {
+            PropertyValue::Bool(value) => {
+                *value = new_value;
+                true
+            }
+            PropertyValue::Float { .. } => false,
+        }
+    }
+
+    /// Notifies the `PropertyChanged` subscribers of the current value.
+    pub fn publish(&self, event_bus: &EventBus) -> anyhow::Result<()> {
+        event_bus.publish(PropertyChanged {
+            name: self.0.name.clone(),
+            value: self.value(),
+        })
+    }
+}
+
+/// Properties registered by the scenes, listed in registration order.
+#[derive(Clone, Default)]
+pub struct PropertyRegistry(Arc<Mutex<Vec<Weak<PropertyInner>>>>);
+
+impl PropertyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register_f32(
+        &self,
+        name: impl Into<Cow<'static, str>>,
+        range: RangeInclusive<f32>,
+        value: f32,
+    ) -> Property {
+        let (min, max) = range.into_inner();
+        self.register(
+            name.into(),
+            PropertyValue::Float {
+                value: value.clamp(min, max),
+                min,
+                max,
+            },
+        )
+    }
+
+    pub fn register_bool(&self, name: impl Into<Cow<'static, str>>, value: bool) -> Property {
+        self.register(name.into(), PropertyValue::Bool(value))
+    }
+
+    fn register(&self, name: Cow<'static, str>, value: PropertyValue) -> Property {
+        let property = Arc::new(PropertyInner {
+            name,
+            value: Mutex::new(value),
+        });
+        let mut properties = self.0.lock();
+        properties.retain(|property| property.strong_count() > 0);
+        properties.push(Arc::downgrade(&property));
+        Property(property)
+    }
+
+    pub fn properties(&self) -> Vec<Property> {
+        self.0
+            .lock()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(Property)
+            .collect()
+    }
+}
+
+#[test]
+fn test() {
+    let registry = PropertyRegistry::new();
+    let float = registry.register_f32("float", 0.0..=1.0, 2.0);
+    let flag = registry.register_bool("flag", false);
+    assert_eq!(float.as_f32(), 1.0);
+    assert!(float.set_f32(-1.0) && float.as_f32() == 0.0);
+    assert!(!float.set_bool(true));
+    assert!(flag.set_bool(true) && flag.as_bool());
+    let names = |registry: &PropertyRegistry| {
+        registry
+            .properties()
+            .iter()
+            .map(|p| p.name().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&registry), ["float", "flag"]);
+    drop(float);
+    assert_eq!(names(&registry), ["flag"]);
+}
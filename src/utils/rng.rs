@@ -0,0 +1,56 @@
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+
+pub type GameRng = StdRng;
+
+/// Seedable source of randomness for game logic.
+///
+/// Game logic should derive its RNGs from this service instead of using
+/// `thread_rng`, so that runs with the same seed (and inputs) are
+/// reproducible.
+pub struct RngService {
+    seed: u64,
+    rng: GameRng,
+}
+
+impl RngService {
+    /// blackboard key of the RNG given to behavior trees
+    pub const BLACKBOARD_KEY: &'static str = "rng";
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: GameRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn from_entropy() -> Self {
+        Self::new(thread_rng().gen())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Self::new(seed);
+    }
+
+    pub fn rng(&mut self) -> &mut GameRng {
+        &mut self.rng
+    }
+
+    /// Creates an independent RNG, deterministic given the seed and the
+    /// number of previous calls.
+    pub fn fork(&mut self) -> GameRng {
+        GameRng::seed_from_u64(self.rng.gen())
+    }
+}
+
+#[test]
+fn test() {
+    let mut a = RngService::new(42);
+    let mut b = RngService::from_entropy();
+    b.reseed(42);
+    assert_eq!(a.rng().gen::<u64>(), b.rng().gen::<u64>());
+    assert_eq!(a.fork().gen::<u64>(), b.fork().gen::<u64>());
+}
@@ -0,0 +1,126 @@
+use std::{
+    borrow::Cow,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use parking_lot::{const_mutex, Mutex};
+
+/// A timed step of startup, `lazy` ones are deferred until first use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StartupPhase {
+    pub name: Cow<'static, str>,
+    pub duration: Duration,
+    pub lazy: bool,
+}
+
+struct Startup {
+    start: Option<Instant>,
+    phases: Vec<StartupPhase>,
+    /// since `start`, set once the first frame with the root scene is done
+    first_frame: Option<Duration>,
+}
+
+static STARTUP: Mutex<Startup> = const_mutex(Startup {
+    start: None,
+    phases: Vec::new(),
+    first_frame: None,
+});
+
+/// Starts the startup clock, the report measures the first frame from
+/// here.
+pub fn begin() {
+    STARTUP.lock().start.get_or_insert_with(Instant::now);
+}
+
+/// Times `f` as the startup phase `name`.
+pub fn phase<T>(name: impl Into<Cow<'static, str>>, f: impl FnOnce() -> T) -> T {
+    timed(name.into(), false, f)
+}
+
+/// Times `f` as the lazy initialization of `name`, logged on its own if it
+/// happens after the first frame.
+pub fn lazy<T>(name: impl Into<Cow<'static, str>>, f: impl FnOnce() -> T) -> T {
+    timed(name.into(), true, f)
+}
+
+fn timed<T>(name: Cow<'static, str>, lazy: bool, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let ret = f();
+    let duration = start.elapsed();
+    let mut startup = STARTUP.lock();
+    if startup.first_frame.is_some() {
+        tracing::info!("lazy initialization of {name} took {duration:.2?}");
+    }
+    startup.phases.push(StartupPhase {
+        name,
+        duration,
+        lazy,
+    });
+    ret
+}
+
+/// Phases recorded so far, in the order they ended.
+pub fn phases() -> Vec<StartupPhase> {
+    STARTUP.lock().phases.clone()
+}
+
+/// Time from `begin` to the end of the first frame, once it's drawn.
+pub fn first_frame() -> Option<Duration> {
+    STARTUP.lock().first_frame
+}
+
+/// Logs the startup report the first time it's called, at the end of the
+/// first frame drawn with the root scene.
+pub fn finish_first_frame() {
+    let mut startup = STARTUP.lock();
+    if startup.first_frame.is_some() {
+        return;
+    }
+    let first_frame = startup
+        .start
+        .map(|start| start.elapsed())
+        .unwrap_or_default();
+    startup.first_frame = Some(first_frame);
+    tracing::info!("{}", report(&startup.phases, first_frame));
+}
+
+fn report(phases: &[StartupPhase], first_frame: Duration) -> String {
+    let mut report = format!("first frame after {first_frame:.2?}");
+    for phase in phases.iter() {
+        let lazy = if phase.lazy { " (lazy)" } else { "" };
+        let duration = format!("{:.2?}", phase.duration);
+        write!(report, "\n  {duration:>10}  {}{lazy}", phase.name).unwrap();
+    }
+    report
+}
+
+#[test]
+fn test() {
+    let sample = [
+        StartupPhase {
+            name: "display".into(),
+            duration: Duration::from_millis(12),
+            lazy: false,
+        },
+        StartupPhase {
+            name: "glyph atlas".into(),
+            duration: Duration::from_micros(1500),
+            lazy: true,
+        },
+    ];
+    assert_eq!(
+        report(&sample, Duration::from_millis(40)),
+        "first frame after 40.00ms\n     12.00ms  display\n      1.50ms  glyph atlas (lazy)"
+    );
+
+    assert_eq!(phase("unit test phase", || 42), 42);
+    lazy("unit test lazy", || {});
+    let recorded = phases();
+    assert!(recorded
+        .iter()
+        .any(|phase| phase.name == "unit test phase" && !phase.lazy));
+    assert!(recorded
+        .iter()
+        .any(|phase| phase.name == "unit test lazy" && phase.lazy));
+}
@@ -0,0 +1,249 @@
+use std::{collections::HashMap, fmt::Debug, hash::Hash};
+
+use anyhow::{ensure, Context};
+use trait_set::trait_set;
+
+trait_set! {
+    pub trait StateKey = Copy + Eq + Hash + Debug;
+    pub trait StateHook<C> = FnMut(&mut C) + Send;
+    pub trait StateUpdateHook<S, C> = FnMut(&mut C) -> Option<S> + Send;
+}
+
+/// Hooks of a single state, all of them are optional.
+pub struct StateHooks<S, C> {
+    on_enter: Option<Box<dyn StateHook<C>>>,
+    on_exit: Option<Box<dyn StateHook<C>>>,
+    on_update: Option<Box<dyn StateUpdateHook<S, C>>>,
+}
+
+impl<S, C> Default for StateHooks<S, C> {
+    fn default() -> Self {
+        Self {
+            on_enter: None,
+            on_exit: None,
+            on_update: None,
+        }
+    }
+}
+
+impl<S, C> StateHooks<S, C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_enter(mut self, f: impl StateHook<C> + 'static) -> Self {
+        self.on_enter = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_exit(mut self, f: impl StateHook<C> + 'static) -> Self {
+        self.on_exit = Some(Box::new(f));
+        self
+    }
+
+    /// `f` returns the state to transition to, if any
+    pub fn on_update(mut self, f: impl StateUpdateHook<S, C> + 'static) -> Self {
+        self.on_update = Some(Box::new(f));
+        self
+    }
+}
+
+struct StateNode<S, C> {
+    parent: Option<S>,
+    hooks: StateHooks<S, C>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransitionEvent<S> {
+    Enter(S),
+    Exit(S),
+}
+
+/// A hierarchical state machine over the state keys `S`, with hooks
+/// receiving a `&mut C` context.
+///
+/// The current state is always a single (not necessarily leaf) state, being
+/// in a state also means being in all of its ancestors. Transitions exit
+/// states up to the closest common ancestor of the current and the target
+/// state, then enter states down to the target. Transitioning to the current
+/// state exits and re-enters it.
+pub struct StateMachine<S, C> {
+    states: HashMap<S, StateNode<S, C>>,
+    current: Option<S>,
+    trace: Option<Vec<TransitionEvent<S>>>,
+}
+
+impl<S: StateKey, C> Default for StateMachine<S, C> {
+    fn default() -> Self {
+        Self {
+            states: HashMap::new(),
+            current: None,
+            trace: None,
+        }
+    }
+}
+
+impl<S: StateKey, C> StateMachine<S, C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `state` as a child of `parent`, the parent must be added first.
+    pub fn add_state(
+        &mut self,
+        state: S,
+        parent: Option<S>,
+        hooks: StateHooks<S, C>,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            !self.states.contains_key(&state),
+            "state {state:?} already exists"
+        );
+        if let Some(parent) = parent {
+            ensure!(
+                self.states.contains_key(&parent),
+                "parent state {parent:?} of state {state:?} doesn't exist"
+            );
+        }
+        self.states.insert(state, StateNode { parent, hooks });
+        Ok(())
+    }
+
+    pub fn with_state(
+        mut self,
+        state: S,
+        parent: Option<S>,
+        hooks: StateHooks<S, C>,
+    ) -> anyhow::Result<Self> {
+        self.add_state(state, parent, hooks)?;
+        Ok(self)
+    }
+
+    /// Records every enter/exit event, to be retrieved with `take_trace`.
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.trace = enabled.then(Vec::new);
+    }
+
+    pub fn take_trace(&mut self) -> Vec<TransitionEvent<S>> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub fn current(&self) -> Option<S> {
+        self.current
+    }
+
+    /// whether `state` is the current state or one of its ancestors
+    pub fn is_in(&self, state: S) -> bool {
+        let mut current = self.current;
+        while let Some(s) = current {
+            if s == state {
+                return true;
+            }
+            current = self.states.get(&s).and_then(|node| node.parent);
+        }
+        false
+    }
+
+    // states from the root down to `state`
+    fn path(&self, state: S) -> Vec<S> {
+        let mut path = vec![state];
+        while let Some(parent) = self
+            .states
+            .get(path.last().unwrap())
+            .and_then(|node| node.parent)
+        {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    fn record(&mut self, event: TransitionEvent<S>) {
+        tracing::trace!("state machine transition: {event:?}");
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(event);
+        }
+    }
+
+    fn exit(&mut self, ctx: &mut C, state: S) {
+        if let Some(on_exit) = self
+            .states
+            .get_mut(&state)
+            .and_then(|node| node.hooks.on_exit.as_mut())
+        {
+            on_exit(ctx);
+        }
+        self.record(TransitionEvent::Exit(state));
+    }
+
+    fn enter(&mut self, ctx: &mut C, state: S) {
+        if let Some(on_enter) = self
+            .states
+            .get_mut(&state)
+            .and_then(|node| node.hooks.on_enter.as_mut())
+        {
+            on_enter(ctx);
+        }
+        self.record(TransitionEvent::Enter(state));
+    }
+
+    pub fn transition(&mut self, ctx: &mut C, target: S) -> anyhow::Result<()> {
+        ensure!(
+            self.states.contains_key(&target),
+            "transition target state {target:?} doesn't exist"
+        );
+
+        let target_path = self.path(target);
+        let current_path = self.current.map(|s| self.path(s)).unwrap_or_default();
+        let mut common = current_path
+            .iter()
+            .zip(target_path.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if self.current == Some(target) {
+            common -= 1;
+        }
+
+        for &state in current_path[common..].iter().rev() {
+            self.exit(ctx, state);
+        }
+        self.current = Some(target);
+        for &state in &target_path[common..] {
+            self.enter(ctx, state);
+        }
+        Ok(())
+    }
+
+    /// Runs the update hooks from the current state up to the root, the
+    /// first hook requesting a transition stops the propagation. Returns
+    /// whether a transition happened.
+    pub fn update(&mut self, ctx: &mut C) -> anyhow::Result<bool> {
+        let current = match self.current {
+            Some(current) => current,
+            None => return Ok(false),
+        };
+
+        for state in self.path(current).into_iter().rev() {
+            let target = self
+                .states
+                .get_mut(&state)
+                .and_then(|node| node.hooks.on_update.as_mut())
+                .and_then(|on_update| on_update(ctx));
+            if let Some(target) = target {
+                self.transition(ctx, target)
+                    .with_context(|| format!("invalid transition requested by state {state:?}"))?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Exits every active state.
+    pub fn stop(&mut self, ctx: &mut C) {
+        if let Some(current) = self.current.take() {
+            for state in self.path(current).into_iter().rev() {
+                self.exit(ctx, state);
+            }
+        }
+    }
+}
@@ -12,6 +12,12 @@ pub trait ClockSync {
     fn sync_impl(&mut self, frequency: f64);
 }
 
+/// Fraction of a period at `frequency` covered by `elapsed` seconds, clamped
+/// to [0, 1].
+pub fn frame_alpha(elapsed: f64, frequency: f64) -> f64 {
+    (elapsed * frequency).clamp(0.0, 1.0)
+}
+
 pub struct OFClockSync<C: Clock> {
     clock: C,
     current_time: f64,
@@ -29,7 +35,7 @@ impl<C: Clock> ClockSync for OFClockSync<C> {
         let before = self.current_time;
         let sleep_time = (excess_time + self.sleep_error).max(0.0);
 
-        std::thread::sleep(Duration::from_secs_f64(sleep_time));
+        self.clock.sleep(Duration::from_secs_f64(sleep_time));
         self.current_time = self.clock.now();
         let time_slept = self.current_time - before;
 
@@ -54,3 +60,18 @@ impl<C: Clock + Default> Default for OFClockSync<C> {
         Self::new(C::default())
     }
 }
+
+#[test]
+fn test() {
+    use super::clock::VirtualClock;
+
+    let clock = VirtualClock::new();
+    let mut sync = OFClockSync::new(clock.clone());
+    let start = std::time::Instant::now();
+    for _ in 0..100 {
+        sync.sync(10.0);
+    }
+    // 10 seconds of virtual time without waiting for them
+    assert!((clock.now() - 10.0).abs() < 1e-6);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
@@ -0,0 +1,233 @@
+use std::{any::Any, borrow::Cow, collections::VecDeque, fmt::Write};
+
+use winit::event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent};
+
+pub trait AsAny: Any {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A reversible edit of a `T`.
+pub trait Command<T>: AsAny + Send {
+    /// shown in history listings
+    fn name(&self) -> Cow<'static, str>;
+
+    fn apply(&mut self, target: &mut T) -> anyhow::Result<()>;
+
+    fn undo(&mut self, target: &mut T) -> anyhow::Result<()>;
+
+    /// Tries to fold `next` (already applied) into `self`, so that both are
+    /// undone at once, e.g. consecutive drags of the same object. `next` can
+    /// be downcasted with `next.as_any().downcast_ref::<Self>()`.
+    fn merge(&mut self, _next: &dyn Command<T>) -> bool {
+        false
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryAction {
+    Undo,
+    Redo,
+}
+
+/// Undo/redo stacks of commands applied to a `T`.
+///
+/// Executing a command clears the redo stack. The oldest commands are
+/// dropped once the history grows past its limit.
+pub struct History<T> {
+    undo_stack: VecDeque<Box<dyn Command<T>>>,
+    redo_stack: Vec<Box<dyn Command<T>>>,
+    limit: usize,
+    /// whether the next command may be merged into the last one
+    mergeable: bool,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LIMIT)
+    }
+}
+
+impl<T> History<T> {
+    pub const DEFAULT_LIMIT: usize = 256;
+
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            limit,
+            mergeable: true,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.undo_stack.len() > self.limit {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    /// Applies `command` and pushes it onto the undo stack, nothing is
+    /// recorded if it fails.
+    pub fn execute<C>(&mut self, target: &mut T, command: C) -> anyhow::Result<()>
+    where
+        C: Command<T> + 'static,
+    {
+        self.execute_boxed(target, Box::new(command))
+    }
+
+    pub fn execute_boxed(
+        &mut self,
+        target: &mut T,
+        mut command: Box<dyn Command<T>>,
+    ) -> anyhow::Result<()> {
+        command.apply(target)?;
+        self.redo_stack.clear();
+
+        let merged = self.mergeable
+            && self
+                .undo_stack
+                .back_mut()
+                .map(|last| last.merge(command.as_ref()))
+                .unwrap_or_default();
+        if !merged {
+            self.undo_stack.push_back(command);
+            self.trim();
+        }
+        self.mergeable = true;
+        Ok(())
+    }
+
+    /// Prevents the next command from being merged into the last one, e.g.
+    /// when the mouse is released at the end of a drag.
+    pub fn seal(&mut self) {
+        self.mergeable = false;
+    }
+
+    /// Returns false if there was nothing to undo. A failed command is
+    /// dropped from the history.
+    pub fn undo(&mut self, target: &mut T) -> anyhow::Result<bool> {
+        let mut command = match self.undo_stack.pop_back() {
+            Some(command) => command,
+            None => return Ok(false),
+        };
+        command.undo(target)?;
+        self.redo_stack.push(command);
+        self.mergeable = false;
+        Ok(true)
+    }
+
+    pub fn redo(&mut self, target: &mut T) -> anyhow::Result<bool> {
+        let mut command = match self.redo_stack.pop() {
+            Some(command) => command,
+            None => return Ok(false),
+        };
+        command.apply(target)?;
+        self.undo_stack.push_back(command);
+        self.trim();
+        self.mergeable = false;
+        Ok(true)
+    }
+
+    pub fn perform(&mut self, target: &mut T, action: HistoryAction) -> anyhow::Result<bool> {
+        match action {
+            HistoryAction::Undo => self.undo(target),
+            HistoryAction::Redo => self.redo(target),
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// Names of the undoable commands, most recent first.
+    pub fn undo_names(&self) -> Vec<Cow<'static, str>> {
+        self.undo_stack.iter().rev().map(|c| c.name()).collect()
+    }
+
+    /// Names of the redoable commands, next to be redone first.
+    pub fn redo_names(&self) -> Vec<Cow<'static, str>> {
+        self.redo_stack.iter().rev().map(|c| c.name()).collect()
+    }
+
+    /// Human readable listing of both stacks, for debugging tools.
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+        for name in self.redo_names().iter().rev() {
+            let _ = writeln!(out, "  (redo) {name}");
+        }
+        let _ = writeln!(
+            out,
+            "-- {} undo, {} redo --",
+            self.undo_stack.len(),
+            self.redo_stack.len()
+        );
+        for name in self.undo_names() {
+            let _ = writeln!(out, "  {name}");
+        }
+        out
+    }
+}
+
+/// Tracks the keyboard modifiers and turns Ctrl+Z into `Undo`, Ctrl+Y and
+/// Ctrl+Shift+Z into `Redo` (Cmd instead of Ctrl works too). The UI scene
+/// publishes the actions on the event bus.
+#[derive(Default)]
+pub struct UndoShortcuts {
+    modifiers: ModifiersState,
+}
+
+impl UndoShortcuts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) -> Option<HistoryAction> {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                None
+            }
+
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } if self.modifiers.ctrl() || self.modifiers.logo() => {
+                match (key, self.modifiers.shift()) {
+                    (VirtualKeyCode::Z, false) => Some(HistoryAction::Undo),
+                    (VirtualKeyCode::Z, true) | (VirtualKeyCode::Y, _) => Some(HistoryAction::Redo),
+                    _ => None,
+                }
+            }
+
+            _ => None,
+        }
+    }
+}